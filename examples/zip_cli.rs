@@ -0,0 +1,240 @@
+//! A small `zip` command line tool, built on the public API of the crate
+//!
+//! ```text
+//! zip-cli create [--password <password>] <archive> <path>...
+//! zip-cli list <archive>
+//! zip-cli extract [--password <password>] <archive> <directory>
+//! zip-cli test [--password <password>] <archive>
+//! ```
+//!
+//! `create` writes the archive to standard output as a stream when `<archive>` is `-`, and
+//! encrypts the files with AES-256 when a password is given. Files of 4 GiB or more get ZIP64
+//! headers. `test` reads the archive with the async reader and checks the CRC-32 of every file.
+//!
+//! Run it with `cargo run --example zip-cli --features zip-cli -- <command> ...`.
+
+use futures::io::AllowStdIo;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Seek, Write};
+use std::path::Path;
+use zip::read::ExtractOptions;
+use zip::write::FileOptions;
+use zip::{AesMode, AsyncZipArchive, ZipArchive, ZipWriter};
+
+type CliResult = Result<(), Box<dyn Error>>;
+
+const USAGE: &str = "Usage:
+    zip-cli create [--password <password>] <archive> <path>...
+    zip-cli list <archive>
+    zip-cli extract [--password <password>] <archive> <directory>
+    zip-cli test [--password <password>] <archive>";
+
+fn main() {
+    std::process::exit(real_main());
+}
+
+fn real_main() -> i32 {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let password = match args.iter().position(|arg| arg == "--password") {
+        Some(i) if i + 1 < args.len() => {
+            let password = args.remove(i + 1);
+            args.remove(i);
+            Some(password)
+        }
+        Some(_) => {
+            eprintln!("{}", USAGE);
+            return 1;
+        }
+        None => None,
+    };
+    let password = password.as_ref().map(|password| password.as_bytes());
+
+    let result = match args.iter().map(|arg| &**arg).collect::<Vec<_>>()[..] {
+        ["create", archive, ref paths @ ..] if !paths.is_empty() => {
+            create(archive, paths, password)
+        }
+        ["list", archive] => list(archive),
+        ["extract", archive, directory] => extract(archive, directory, password),
+        ["test", archive] => test(archive, password),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 1;
+        }
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn create(archive: &str, paths: &[&str], password: Option<&[u8]>) -> CliResult {
+    let mut options = FileOptions::default();
+    if let Some(password) = password {
+        options = options.with_aes_encryption(AesMode::Aes256, password);
+    }
+    if archive == "-" {
+        let stdout = io::stdout();
+        let mut zip = ZipWriter::new_stream(stdout.lock());
+        add_paths(&mut zip, paths, options)?;
+        zip.finish()?;
+    } else {
+        let mut zip = ZipWriter::new(fs::File::create(archive)?);
+        add_paths(&mut zip, paths, options)?;
+        zip.finish()?;
+    }
+    Ok(())
+}
+
+fn add_paths<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    paths: &[&str],
+    options: FileOptions,
+) -> CliResult {
+    for path in paths {
+        let path = Path::new(path);
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => return Err(format!("{} has no file name", path.display()).into()),
+        };
+        add_path(zip, path, name, options)?;
+    }
+    Ok(())
+}
+
+/// Add a file, or a directory with all of its contents, as the entry `name`.
+fn add_path<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    path: &Path,
+    name: String,
+    options: FileOptions,
+) -> CliResult {
+    let metadata = fs::metadata(path)?;
+    if metadata.is_dir() {
+        zip.add_directory(name.as_str(), options)?;
+        let mut entries = fs::read_dir(path)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let entry_name = format!("{}/{}", name, entry.file_name().to_string_lossy());
+            add_path(zip, &entry.path(), entry_name, options)?;
+        }
+    } else {
+        let options = options.large_file(metadata.len() >= u64::from(u32::MAX));
+        zip.start_file(name.as_str(), options)?;
+        io::copy(&mut fs::File::open(path)?, zip)?;
+        eprintln!("added {}", name);
+    }
+    Ok(())
+}
+
+fn list(archive: &str) -> CliResult {
+    let mut archive = ZipArchive::new(fs::File::open(archive)?)?;
+    println!(
+        "{:>12} {:>12} {:>8} {:16}  Name",
+        "Size", "Compressed", "Method", "Modified"
+    );
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+        let modified = file.last_modified();
+        println!(
+            "{:>12} {:>12} {:>8} {:04}-{:02}-{:02} {:02}:{:02}  {}{}",
+            file.size(),
+            file.compressed_size(),
+            file.compression().to_string(),
+            modified.year(),
+            modified.month(),
+            modified.day(),
+            modified.hour(),
+            modified.minute(),
+            file.name(),
+            if file.encrypted() { " (encrypted)" } else { "" },
+        );
+    }
+    if !archive.comment().is_empty() {
+        println!("{}", String::from_utf8_lossy(archive.comment()));
+    }
+    Ok(())
+}
+
+fn extract(archive: &str, directory: &str, password: Option<&[u8]>) -> CliResult {
+    let mut archive = ZipArchive::new(fs::File::open(archive)?)?;
+    let password = match password {
+        Some(password) => password,
+        None => {
+            let options = ExtractOptions::default().skip_invalid_names(true);
+            let summary = archive.extract_with_options(directory, &options)?;
+            for warning in summary.warnings() {
+                eprintln!("warning: {:?}", warning);
+            }
+            println!(
+                "{} files, {} directories, {} bytes extracted, {} entries skipped",
+                summary.files_written(),
+                summary.directories_created(),
+                summary.bytes_written(),
+                summary.entries_skipped(),
+            );
+            return Ok(());
+        }
+    };
+
+    // The extraction of the archive does not decrypt files, so they are written one by one
+    for i in 0..archive.len() {
+        let mut file = archive.by_index_decrypt(i, password)??;
+        let outpath = match file.enclosed_name() {
+            Some(path) => Path::new(directory).join(path),
+            None => {
+                eprintln!("skipped {}", file.name());
+                continue;
+            }
+        };
+        if file.is_dir() {
+            fs::create_dir_all(&outpath)?;
+            continue;
+        }
+        if let Some(parent) = outpath.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut file, &mut fs::File::create(&outpath)?)?;
+        println!("{}", outpath.display());
+    }
+    Ok(())
+}
+
+fn test(archive: &str, password: Option<&[u8]>) -> CliResult {
+    futures::executor::block_on(async {
+        let reader = AllowStdIo::new(fs::File::open(archive)?);
+        let mut archive = AsyncZipArchive::new(reader).await?;
+        let mut failed = 0;
+        for i in 0..archive.len() {
+            let file = match password {
+                Some(password) => match archive.by_index_decrypt(i, password).await {
+                    Ok(file) => file.map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                },
+                None => archive.by_index(i).await.map_err(|e| e.to_string()),
+            };
+            let result = match file {
+                Ok(mut file) => futures::io::copy(&mut file, &mut futures::io::sink())
+                    .await
+                    .map(|_| file.name().to_owned())
+                    .map_err(|e| format!("{}: {}", file.name(), e)),
+                Err(e) => Err(format!("entry {}: {}", i, e)),
+            };
+            match result {
+                Ok(name) => println!("OK     {}", name),
+                Err(e) => {
+                    println!("FAILED {}", e);
+                    failed += 1;
+                }
+            }
+        }
+        if failed > 0 {
+            return Err(format!("{} of {} files failed the test", failed, archive.len()).into());
+        }
+        println!("No errors in {} files", archive.len());
+        Ok(())
+    })
+}
@@ -0,0 +1,351 @@
+//! Implementation of the WinZip AES encryption
+//!
+//! The layout of the data of an encrypted file is described in
+//! <https://www.winzip.com/win/en/aes_info.html>: a salt and a password verification value are
+//! followed by the data encrypted with AES in CTR mode, and an authentication code computed with
+//! HMAC-SHA1 over the encrypted data.
+//!
+//! The `aes` crate detects AES-NI at runtime on x86 and x86-64, and uses the ARMv8 instructions
+//! with the `aes-armv8` feature. The keystream is computed eight blocks at a time, so that these
+//! instructions can work on several blocks in parallel.
+
+use crate::types::AesMode;
+use crate::zipcrypto::{fill_random, for_each_chunk};
+use aes::cipher::{BlockEncrypt, NewBlockCipher};
+use hmac::{Hmac, Mac, NewMac};
+use sha1::{Digest, Sha1};
+use std::io::{self, Read, Write};
+
+#[cfg(feature = "async")]
+use futures::io::{AsyncRead, AsyncReadExt};
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+
+/// The length of the password verification value in bytes
+pub const PWD_VERIFY_LENGTH: usize = 2;
+
+/// The length of the authentication code in bytes
+pub const AUTH_CODE_LENGTH: usize = 10;
+
+/// The block size of SHA-1, which is the size of the HMAC-SHA1 keys
+const HMAC_KEY_LENGTH: usize = 64;
+
+/// The number of blocks of keystream computed at once
+const PARALLEL_BLOCKS: usize = 8;
+
+/// The size of the AES blocks in bytes
+const BLOCK_SIZE: usize = 16;
+
+/// A password in a form that has a fixed size, so it can be part of `FileOptions`
+///
+/// HMAC pads keys shorter than the block size with zeros and hashes longer ones, so the padded or
+/// hashed password gives the same HMAC, and therefore the same derived keys, as the password.
+#[derive(Clone, Copy)]
+pub struct AesPassword([u8; HMAC_KEY_LENGTH]);
+
+impl AesPassword {
+    pub fn new(password: &[u8]) -> AesPassword {
+        let mut key = [0; HMAC_KEY_LENGTH];
+        if password.len() > HMAC_KEY_LENGTH {
+            let digest = Sha1::digest(password);
+            key[..digest.len()].copy_from_slice(&digest);
+        } else {
+            key[..password.len()].copy_from_slice(password);
+        }
+        AesPassword(key)
+    }
+}
+
+/// Encrypt the data of an entry and write it, with the salt, password verification value and
+/// authentication code. The keys are derived with `iteration_count` PBKDF2 iterations, and the
+/// data is read in chunks of the size of `buffer`.
+pub fn encrypt_entry<R: Read, W: Write>(
+    mode: AesMode,
+    password: &AesPassword,
+    iteration_count: u32,
+    data: &mut R,
+    buffer: &mut [u8],
+    writer: &mut W,
+) -> io::Result<()> {
+    let mut salt = vec![0; mode.salt_length()];
+    fill_random(&mut salt)?;
+    let keys = DerivedKeys::new(mode, &password.0, &salt, iteration_count);
+
+    let mut ctr = AesCtr::new(mode, &keys.encryption_key);
+    let mut hmac =
+        Hmac::<Sha1>::new_from_slice(&keys.hmac_key).expect("HMAC accepts keys of any length");
+    writer.write_all(&salt)?;
+    writer.write_all(&keys.pwd_verify)?;
+    for_each_chunk(data, buffer, |chunk| {
+        ctr.crypt_in_place(chunk);
+        hmac.update(chunk);
+        writer.write_all(chunk)
+    })?;
+
+    let auth_code = hmac.finalize().into_bytes();
+    writer.write_all(&auth_code[..AUTH_CODE_LENGTH])
+}
+
+/// The keys derived from a password and salt.
+struct DerivedKeys {
+    encryption_key: Vec<u8>,
+    hmac_key: Vec<u8>,
+    pwd_verify: [u8; PWD_VERIFY_LENGTH],
+}
+
+impl DerivedKeys {
+    fn new(mode: AesMode, password: &[u8], salt: &[u8], iteration_count: u32) -> DerivedKeys {
+        let key_length = mode.key_length();
+        let mut derived = vec![0; 2 * key_length + PWD_VERIFY_LENGTH];
+        pbkdf2::pbkdf2::<Hmac<Sha1>>(password, salt, iteration_count, &mut derived);
+
+        let mut pwd_verify = [0; PWD_VERIFY_LENGTH];
+        pwd_verify.copy_from_slice(&derived[2 * key_length..]);
+        derived.truncate(2 * key_length);
+        let hmac_key = derived.split_off(key_length);
+        DerivedKeys {
+            encryption_key: derived,
+            hmac_key,
+            pwd_verify,
+        }
+    }
+}
+
+enum Cipher {
+    Aes128(aes::Aes128),
+    Aes192(aes::Aes192),
+    Aes256(aes::Aes256),
+}
+
+/// AES in CTR mode, as used by WinZip: the counter is little endian and starts at 1.
+pub struct AesCtr {
+    cipher: Cipher,
+    counter: u128,
+    keystream: [u8; PARALLEL_BLOCKS * BLOCK_SIZE],
+    position: usize,
+}
+
+impl AesCtr {
+    fn new(mode: AesMode, key: &[u8]) -> AesCtr {
+        let cipher = match mode {
+            AesMode::Aes128 => aes::Aes128::new_from_slice(key).map(Cipher::Aes128),
+            AesMode::Aes192 => aes::Aes192::new_from_slice(key).map(Cipher::Aes192),
+            AesMode::Aes256 => aes::Aes256::new_from_slice(key).map(Cipher::Aes256),
+        }
+        .expect("The key length matches the AES mode");
+        AesCtr {
+            cipher,
+            counter: 0,
+            keystream: [0; PARALLEL_BLOCKS * BLOCK_SIZE],
+            position: PARALLEL_BLOCKS * BLOCK_SIZE,
+        }
+    }
+
+    /// Encrypt or decrypt `data` in place.
+    pub fn crypt_in_place(&mut self, data: &mut [u8]) {
+        for byte in data.iter_mut() {
+            if self.position == self.keystream.len() {
+                self.next_blocks();
+            }
+            *byte ^= self.keystream[self.position];
+            self.position += 1;
+        }
+    }
+
+    fn next_blocks(&mut self) {
+        let mut blocks = aes::ParBlocks::default();
+        for block in blocks.iter_mut() {
+            self.counter = self.counter.wrapping_add(1);
+            block.copy_from_slice(&self.counter.to_le_bytes());
+        }
+        match self.cipher {
+            Cipher::Aes128(ref cipher) => cipher.encrypt_par_blocks(&mut blocks),
+            Cipher::Aes192(ref cipher) => cipher.encrypt_par_blocks(&mut blocks),
+            Cipher::Aes256(ref cipher) => cipher.encrypt_par_blocks(&mut blocks),
+        }
+        for (keystream, block) in self.keystream.chunks_mut(BLOCK_SIZE).zip(blocks.iter()) {
+            keystream.copy_from_slice(block);
+        }
+        self.position = 0;
+    }
+}
+
+/// A reader of AES encrypted data with an unverified password
+pub struct AesReader<R> {
+    reader: R,
+    mode: AesMode,
+    data_length: u64,
+}
+
+impl<R> AesReader<R> {
+    /// Create a reader of the encrypted data of a file, which is `compressed_size` bytes long
+    /// including the salt, password verification value and authentication code.
+    pub fn new(reader: R, mode: AesMode, compressed_size: u64) -> AesReader<R> {
+        let overhead = (mode.salt_length() + PWD_VERIFY_LENGTH + AUTH_CODE_LENGTH) as u64;
+        AesReader {
+            reader,
+            mode,
+            data_length: compressed_size.saturating_sub(overhead),
+        }
+    }
+
+    /// Check the password against the salt and password verification value that were read.
+    fn check_password(
+        self,
+        salt: &[u8],
+        pwd_verify: &[u8],
+        password: &[u8],
+        iteration_count: u32,
+    ) -> Option<AesReaderValid<R>> {
+        let keys = DerivedKeys::new(self.mode, password, salt, iteration_count);
+        if !constant_time_eq::constant_time_eq(pwd_verify, &keys.pwd_verify) {
+            return None;
+        }
+
+        Some(AesReaderValid {
+            reader: self.reader,
+            cipher: AesCtr::new(self.mode, &keys.encryption_key),
+            hmac: Hmac::<Sha1>::new_from_slice(&keys.hmac_key)
+                .expect("HMAC accepts keys of any length"),
+            data_remaining: self.data_length,
+            auth_code: [0; AUTH_CODE_LENGTH],
+            #[cfg(feature = "async")]
+            auth_code_read: 0,
+            auth_code_checked: false,
+        })
+    }
+}
+
+impl<R: Read> AesReader<R> {
+    /// Read the salt and password verification value, and check the password, deriving the keys
+    /// with `iteration_count` PBKDF2 iterations.
+    ///
+    /// Returns `None` if the password is wrong. There is a 1 in 65536 chance that a wrong password
+    /// is accepted, in which case the authentication code will not match once all data is read.
+    pub fn validate(
+        mut self,
+        password: &[u8],
+        iteration_count: u32,
+    ) -> io::Result<Option<AesReaderValid<R>>> {
+        let mut salt = vec![0; self.mode.salt_length()];
+        self.reader.read_exact(&mut salt)?;
+        let mut pwd_verify = [0; PWD_VERIFY_LENGTH];
+        self.reader.read_exact(&mut pwd_verify)?;
+        Ok(self.check_password(&salt, &pwd_verify, password, iteration_count))
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + Unpin> AesReader<R> {
+    /// Read the salt and password verification value, and check the password, like
+    /// [`AesReader::validate`].
+    pub async fn validate_async(
+        mut self,
+        password: &[u8],
+        iteration_count: u32,
+    ) -> io::Result<Option<AesReaderValid<R>>> {
+        let mut salt = vec![0; self.mode.salt_length()];
+        self.reader.read_exact(&mut salt).await?;
+        let mut pwd_verify = [0; PWD_VERIFY_LENGTH];
+        self.reader.read_exact(&mut pwd_verify).await?;
+        Ok(self.check_password(&salt, &pwd_verify, password, iteration_count))
+    }
+}
+
+/// A reader of AES encrypted data with a verified password
+///
+/// The authentication code is checked when the end of the data is reached.
+pub struct AesReaderValid<R> {
+    reader: R,
+    cipher: AesCtr,
+    hmac: Hmac<Sha1>,
+    data_remaining: u64,
+    /// The authentication code, which is read once the end of the data is reached
+    auth_code: [u8; AUTH_CODE_LENGTH],
+    /// How much of the authentication code the async reader has read
+    #[cfg(feature = "async")]
+    auth_code_read: usize,
+    auth_code_checked: bool,
+}
+
+impl<R> AesReaderValid<R> {
+    /// Compare the authentication code that was read to the one of the data.
+    fn check_auth_code(&mut self) -> io::Result<()> {
+        self.auth_code_checked = true;
+        let computed = self.hmac.clone().finalize().into_bytes();
+        if !constant_time_eq::constant_time_eq(&computed[..AUTH_CODE_LENGTH], &self.auth_code) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid authentication code",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Decrypt the `count` bytes that were read into `buf`.
+    fn decrypt(&mut self, buf: &mut [u8], count: usize) -> io::Result<usize> {
+        if count == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.hmac.update(&buf[..count]);
+        self.cipher.crypt_in_place(&mut buf[..count]);
+        self.data_remaining -= count as u64;
+        Ok(count)
+    }
+
+    /// Consumes this reader, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Read for AesReaderValid<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.data_remaining == 0 {
+            if !self.auth_code_checked {
+                self.reader.read_exact(&mut self.auth_code)?;
+                self.check_auth_code()?;
+            }
+            return Ok(0);
+        }
+        let length = (buf.len() as u64).min(self.data_remaining) as usize;
+        let count = self.reader.read(&mut buf[..length])?;
+        self.decrypt(buf, count)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + Unpin> AsyncRead for AesReaderValid<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        if this.data_remaining == 0 {
+            while !this.auth_code_checked {
+                if this.auth_code_read == AUTH_CODE_LENGTH {
+                    this.check_auth_code()?;
+                    continue;
+                }
+                let auth_code = &mut this.auth_code[this.auth_code_read..];
+                match futures::ready!(Pin::new(&mut this.reader).poll_read(cx, auth_code))? {
+                    0 => return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into())),
+                    count => this.auth_code_read += count,
+                }
+            }
+            return Poll::Ready(Ok(0));
+        }
+        let length = (buf.len() as u64).min(this.data_remaining) as usize;
+        let count = futures::ready!(Pin::new(&mut this.reader).poll_read(cx, &mut buf[..length]))?;
+        Poll::Ready(this.decrypt(buf, count))
+    }
+}
@@ -1,63 +1,33 @@
-use futures::{AsyncRead, AsyncWrite};
-use pin_project::pin_project;
-use tokio::io::{AsyncRead as TokioAsyncRead, AsyncWrite as TokioAsyncWrite};
-
-/// Wrapper for converting between tokio and futures async read types
-#[pin_project]
-pub(crate) struct Compat<T>(#[pin] pub T);
-
-impl<T> Compat<T> {
-    pub fn into_inner(self) -> T {
-        self.0
-    }
-}
-
-impl<T: AsyncRead> TokioAsyncRead for Compat<T> {
-    fn poll_read(
-        self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-        buf: &mut tokio::io::ReadBuf<'_>,
-    ) -> std::task::Poll<std::io::Result<()>> {
-        self.project()
-            .0
-            .poll_read(cx, buf.initialize_unfilled())
-            .map(|r| r.map(|n| buf.set_filled(n)))
-    }
-}
-
-impl<T: AsyncWrite> TokioAsyncWrite for Compat<T> {
-    fn poll_write(
-        self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-        buf: &[u8],
-    ) -> std::task::Poll<Result<usize, std::io::Error>> {
-        self.project().0.poll_write(cx, buf)
-    }
-
-    fn poll_flush(
-        self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Result<(), std::io::Error>> {
-        self.project().0.poll_flush(cx)
-    }
-
-    fn poll_shutdown(
-        self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Result<(), std::io::Error>> {
-        self.project().0.poll_close(cx)
-    }
-}
-
-pub(crate) trait CompatExt<T>: Sized {
-    fn compat(self) -> Compat<Self> {
-        Compat(self)
-    }
-
-    fn compat_mut(&mut self) -> Compat<&mut Self> {
-        Compat(self)
-    }
-}
-
-impl<T: AsyncRead> CompatExt<T> for T {}
-// We can't also implement for AsyncWrite without specialization :(
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use std::io;
+
+macro_rules! read_le {
+    ($name:ident, $type:ty) => {
+        /// Read a little-endian integer
+        pub(crate) async fn $name<R: AsyncRead + Unpin + ?Sized>(
+            reader: &mut R,
+        ) -> io::Result<$type> {
+            let mut bytes = [0; std::mem::size_of::<$type>()];
+            reader.read_exact(&mut bytes).await?;
+            Ok(<$type>::from_le_bytes(bytes))
+        }
+    };
+}
+
+macro_rules! write_le {
+    ($name:ident, $type:ty) => {
+        /// Write a little-endian integer
+        pub(crate) async fn $name<W: AsyncWrite + Unpin + ?Sized>(
+            writer: &mut W,
+            value: $type,
+        ) -> io::Result<()> {
+            writer.write_all(&value.to_le_bytes()).await
+        }
+    };
+}
+
+read_le!(read_u16_le, u16);
+read_le!(read_u32_le, u32);
+write_le!(write_u16_le, u16);
+write_le!(write_u32_le, u32);
+write_le!(write_u64_le, u64);
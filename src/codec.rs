@@ -0,0 +1,116 @@
+//! Traits for plugging in compression methods that are not built into this crate.
+//!
+//! Some applications use method IDs outside of the ones listed in APPNOTE.TXT, or are only
+//! interested in a method this crate doesn't implement. A [`Decompressor`] can be registered
+//! with [`ZipArchive::register_decompressor`] and a [`Compressor`] with
+//! [`ZipWriter::register_compressor`]; entries using that method are then routed through them.
+//!
+//! Use [`CompressionMethod::custom`] to refer to a method ID that has no constant.
+//!
+//! [`ZipArchive::register_decompressor`]: crate::read::ZipArchive::register_decompressor
+//! [`ZipWriter::register_compressor`]: crate::write::ZipWriter::register_compressor
+//! [`CompressionMethod::custom`]: crate::CompressionMethod::custom
+
+use crate::compression::CompressionMethod;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+/// Creates readers that decompress entry data for a single compression method.
+pub trait Decompressor: Send + Sync {
+    /// Wrap the reader of the compressed (and already decrypted) entry data in a reader that
+    /// yields the decompressed data.
+    fn decompress<'a>(&self, reader: Box<dyn Read + 'a>) -> Box<dyn Read + 'a>;
+}
+
+/// Creates compression streams for a single compression method.
+pub trait Compressor: Send + Sync {
+    /// Start compressing a new entry.
+    fn start_entry(&self) -> Box<dyn CompressorStream>;
+}
+
+/// The compression state of a single entry.
+///
+/// The stream does not own its output; it is handed the archive writer on every call instead.
+pub trait CompressorStream: Send {
+    /// Compress `data`, writing any output that is ready to `output`.
+    fn write(&mut self, data: &[u8], output: &mut dyn Write) -> io::Result<()>;
+
+    /// Write all remaining output to `output`. Called once at the end of the entry.
+    fn finish(self: Box<Self>, output: &mut dyn Write) -> io::Result<()>;
+}
+
+/// Codecs registered by compression method ID.
+pub(crate) struct Codecs<T: ?Sized>(HashMap<u16, Arc<T>>);
+
+impl<T: ?Sized> Codecs<T> {
+    pub fn insert(&mut self, method: CompressionMethod, codec: Arc<T>) {
+        #[allow(deprecated)]
+        self.0.insert(method.to_u16(), codec);
+    }
+
+    pub fn get(&self, method: CompressionMethod) -> Option<Arc<T>> {
+        #[allow(deprecated)]
+        self.0.get(&method.to_u16()).cloned()
+    }
+}
+
+impl<T: ?Sized> Default for Codecs<T> {
+    fn default() -> Self {
+        Codecs(HashMap::new())
+    }
+}
+
+impl<T: ?Sized> Clone for Codecs<T> {
+    fn clone(&self) -> Self {
+        Codecs(self.0.clone())
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for Codecs<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+/// Writer that routes data through a [`CompressorStream`] into the inner writer.
+pub(crate) struct CustomEncoder<W: Write> {
+    inner: W,
+    stream: Box<dyn CompressorStream>,
+    method: CompressionMethod,
+}
+
+impl<W: Write> CustomEncoder<W> {
+    pub fn new(inner: W, compressor: &dyn Compressor, method: CompressionMethod) -> Self {
+        CustomEncoder {
+            inner,
+            stream: compressor.start_entry(),
+            method,
+        }
+    }
+
+    pub fn method(&self) -> CompressionMethod {
+        self.method
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.stream.finish(&mut self.inner)?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for CustomEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf, &mut self.inner)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -68,6 +68,16 @@ impl CompressionMethod {
     pub const PPMD: Self = CompressionMethod::Unsupported(98);
 }
 impl CompressionMethod {
+    /// Construct the compression method with the given ID
+    ///
+    /// This is meant for methods that have no constant, to be used together with the
+    /// [`codec`](crate::codec) traits. IDs of known methods resolve to the same value as the
+    /// corresponding constant.
+    pub fn custom(id: u16) -> CompressionMethod {
+        #[allow(deprecated)]
+        CompressionMethod::from_u16(id)
+    }
+
     /// Converts an u16 to its corresponding CompressionMethod
     #[deprecated(
         since = "0.5.7",
@@ -1,12 +1,26 @@
 //! Helper module to compute a CRC32 checksum
+//!
+//! [`Crc32Reader`] exposes the state of the checksum while it reads, so that reading an entry can
+//! be interrupted and resumed later: store [`Crc32Reader::current`] and
+//! [`Crc32Reader::bytes_read`], or the hasher returned by [`Crc32Reader::into_parts`], and continue
+//! with [`Crc32Reader::with_hasher`]. [`Crc32Writer`] computes the checksum of data that is
+//! written in the same way.
+//!
+//! The CRC-32 of two pieces of data can be combined into the CRC-32 of their concatenation with
+//! [`crc32_combine`]. With the `parallel` feature, `ParallelHasher` uses this to hash chunks
+//! of the data on several threads.
 
+#[cfg(feature = "parallel")]
+use std::collections::VecDeque;
 use std::io;
 use std::io::prelude::*;
+#[cfg(feature = "parallel")]
+use std::sync::{mpsc, Arc, Mutex};
 
-use crc32fast::Hasher;
+pub use crc32fast::Hasher;
 
 #[cfg(feature = "async")]
-use futures::io::AsyncRead;
+use futures::io::{AsyncRead, AsyncWrite};
 #[cfg(feature = "async")]
 use pin_project::pin_project;
 
@@ -16,26 +30,61 @@ pub struct Crc32Reader<R> {
     #[cfg_attr(feature = "async", pin)]
     inner: R,
     hasher: Hasher,
+    bytes_read: u64,
     check: u32,
+    /// AE-2 encrypted files store a CRC-32 of 0, their integrity is checked by the decryption
+    ae2_encrypted: bool,
 }
 
 impl<R> Crc32Reader<R> {
     /// Get a new Crc32Reader which check the inner reader against checksum.
-    pub fn new(inner: R, checksum: u32) -> Crc32Reader<R> {
+    ///
+    /// The checksum is not checked if `ae2_encrypted` is set.
+    pub fn new(inner: R, checksum: u32, ae2_encrypted: bool) -> Crc32Reader<R> {
         Crc32Reader {
             inner,
             hasher: Hasher::new(),
+            bytes_read: 0,
             check: checksum,
+            ae2_encrypted,
         }
     }
 
+    /// Continue the checksum of data of which `bytes_read` bytes were already read, for example
+    /// by another reader that was split with [`Crc32Reader::into_parts`].
+    ///
+    /// A hasher for a known checksum can be created with [`Hasher::new_with_initial`].
+    pub fn with_hasher(mut self, hasher: Hasher, bytes_read: u64) -> Crc32Reader<R> {
+        self.hasher = hasher;
+        self.bytes_read = bytes_read;
+        self
+    }
+
+    /// The CRC-32 of the data read so far
+    pub fn current(&self) -> u32 {
+        self.hasher.clone().finalize()
+    }
+
+    /// The number of bytes read so far, including the bytes given to
+    /// [`Crc32Reader::with_hasher`]
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
     fn check_matches(&self) -> bool {
-        self.check == self.hasher.clone().finalize()
+        self.ae2_encrypted || self.check == self.current()
     }
 
+    /// Consumes this reader, returning the underlying reader.
     pub fn into_inner(self) -> R {
         self.inner
     }
+
+    /// Consumes this reader, returning the underlying reader and the hasher of the data read so
+    /// far.
+    pub fn into_parts(self) -> (R, Hasher) {
+        (self.inner, self.hasher)
+    }
 }
 
 impl<R: Read> Read for Crc32Reader<R> {
@@ -48,10 +97,16 @@ impl<R: Read> Read for Crc32Reader<R> {
             Err(e) => return Err(e),
         };
         self.hasher.update(&buf[0..count]);
+        self.bytes_read += count as u64;
         Ok(count)
     }
 }
 
+/// Whether `error` was returned by a [`Crc32Reader`] whose data did not match the checksum
+pub(crate) fn is_checksum_error(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::Other && error.to_string() == "Invalid checksum"
+}
+
 #[cfg(feature = "async")]
 impl<R: AsyncRead> AsyncRead for Crc32Reader<R> {
     fn poll_read(
@@ -62,20 +117,320 @@ impl<R: AsyncRead> AsyncRead for Crc32Reader<R> {
         let Crc32ReaderProject {
             inner,
             hasher,
+            bytes_read,
             check,
+            ae2_encrypted,
         } = self.as_mut().project();
         inner.poll_read(cx, buf).map(|count| {
             let count = match count {
-                Ok(0) if !buf.is_empty() && !(*check == hasher.clone().finalize()) => {
+                Ok(0)
+                    if !buf.is_empty()
+                        && !*ae2_encrypted
+                        && *check != hasher.clone().finalize() =>
+                {
                     return Err(io::Error::new(io::ErrorKind::Other, "Invalid checksum"))
                 }
                 Ok(n) => n,
                 Err(e) => return Err(e),
             };
             hasher.update(&buf[0..count]);
+            *bytes_read += count as u64;
+            Ok(count)
+        })
+    }
+}
+
+/// Writer that computes the CRC32 of the data written to it.
+#[cfg_attr(feature = "async", pin_project(project=Crc32WriterProject))]
+pub struct Crc32Writer<W> {
+    #[cfg_attr(feature = "async", pin)]
+    inner: W,
+    hasher: Hasher,
+    bytes_written: u64,
+}
+
+impl<W> Crc32Writer<W> {
+    /// Get a new Crc32Writer which computes the checksum of the data written to `inner`.
+    pub fn new(inner: W) -> Crc32Writer<W> {
+        Crc32Writer {
+            inner,
+            hasher: Hasher::new(),
+            bytes_written: 0,
+        }
+    }
+
+    /// Continue the checksum of data of which `bytes_written` bytes were already written.
+    pub fn with_hasher(mut self, hasher: Hasher, bytes_written: u64) -> Crc32Writer<W> {
+        self.hasher = hasher;
+        self.bytes_written = bytes_written;
+        self
+    }
+
+    /// The CRC-32 of the data written so far
+    pub fn current(&self) -> u32 {
+        self.hasher.clone().finalize()
+    }
+
+    /// The number of bytes written so far, including the bytes given to
+    /// [`Crc32Writer::with_hasher`]
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Consumes this writer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Consumes this writer, returning the underlying writer and the hasher of the data written so
+    /// far.
+    pub fn into_parts(self) -> (W, Hasher) {
+        (self.inner, self.hasher)
+    }
+}
+
+impl<W: Write> Write for Crc32Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = self.inner.write(buf)?;
+        self.hasher.update(&buf[0..count]);
+        self.bytes_written += count as u64;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<W: AsyncWrite> AsyncWrite for Crc32Writer<W> {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<io::Result<usize>> {
+        let Crc32WriterProject {
+            inner,
+            hasher,
+            bytes_written,
+        } = self.project();
+        inner.poll_write(cx, buf).map(|count| {
+            let count = count?;
+            hasher.update(&buf[0..count]);
+            *bytes_written += count as u64;
             Ok(count)
         })
     }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_close(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        self.project().inner.poll_close(cx)
+    }
+}
+
+/// The CRC-32 reflected polynomial
+const POLYNOMIAL: u32 = 0xedb8_8320;
+
+/// Combine the CRC-32 `crc1` of some data with the CRC-32 `crc2` of the `len2` bytes that follow
+/// it, into the CRC-32 of all the data, like `crc32_combine` of zlib.
+pub fn crc32_combine(mut crc1: u32, crc2: u32, mut len2: u64) -> u32 {
+    if len2 == 0 {
+        return crc1;
+    }
+
+    // The operator that appends one zero bit to the data, as a matrix over GF(2)
+    let mut odd = [0; 32];
+    odd[0] = POLYNOMIAL;
+    for (n, row) in odd.iter_mut().enumerate().skip(1) {
+        *row = 1 << (n - 1);
+    }
+    // The operators for two and four zero bits
+    let mut even = gf2_matrix_square(&odd);
+    odd = gf2_matrix_square(&even);
+
+    // Apply the operators for one zero byte, two, four and so on, for each bit set in `len2`
+    loop {
+        even = gf2_matrix_square(&odd);
+        if len2 & 1 != 0 {
+            crc1 = gf2_matrix_times(&even, crc1);
+        }
+        len2 >>= 1;
+        if len2 == 0 {
+            break;
+        }
+
+        odd = gf2_matrix_square(&even);
+        if len2 & 1 != 0 {
+            crc1 = gf2_matrix_times(&odd, crc1);
+        }
+        len2 >>= 1;
+        if len2 == 0 {
+            break;
+        }
+    }
+    crc1 ^ crc2
+}
+
+fn gf2_matrix_times(matrix: &[u32; 32], mut vector: u32) -> u32 {
+    let mut sum = 0;
+    for row in matrix {
+        if vector == 0 {
+            break;
+        }
+        if vector & 1 != 0 {
+            sum ^= row;
+        }
+        vector >>= 1;
+    }
+    sum
+}
+
+fn gf2_matrix_square(matrix: &[u32; 32]) -> [u32; 32] {
+    let mut square = [0; 32];
+    for (row, &vector) in square.iter_mut().zip(matrix) {
+        *row = gf2_matrix_times(matrix, vector);
+    }
+    square
+}
+
+/// The size of the chunks that are hashed separately by a [`ParallelHasher`]
+#[cfg(feature = "parallel")]
+const PARALLEL_CHUNK_SIZE: usize = 1 << 22;
+
+/// A chunk to hash, with where to send its CRC-32
+#[cfg(feature = "parallel")]
+type Job = (Vec<u8>, mpsc::Sender<u32>);
+
+/// Hasher that computes the CRC-32 of chunks of the data on worker threads, and combines them
+/// with [`crc32_combine`].
+///
+/// The data is given to it with [`ParallelHasher::update`] or by writing to it, for example with
+/// `io::copy`. The data is copied into chunks of 4 MiB, so it helps for large amounts of data.
+#[cfg(feature = "parallel")]
+pub struct ParallelHasher {
+    /// The data that is not yet handed to a worker
+    chunk: Vec<u8>,
+    /// The CRC-32 of the chunks handed to the workers, oldest first, with their lengths
+    pending: VecDeque<(mpsc::Receiver<u32>, u64)>,
+    /// The CRC-32 of the chunks that were combined
+    crc: u32,
+    bytes_hashed: u64,
+    jobs: mpsc::Sender<Job>,
+    threads: usize,
+}
+
+#[cfg(feature = "parallel")]
+impl ParallelHasher {
+    /// Create a hasher that hashes on `threads` threads.
+    pub fn new(threads: usize) -> ParallelHasher {
+        let threads = threads.max(1);
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..threads {
+            let receiver = receiver.clone();
+            // The workers stop once the hasher, and with it the sender of the jobs, is dropped
+            std::thread::spawn(move || loop {
+                let job = receiver.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                let (chunk, result) = match job {
+                    Ok(job) => job,
+                    Err(_) => return,
+                };
+                let mut hasher = Hasher::new();
+                hasher.update(&chunk);
+                let _ = result.send(hasher.finalize());
+            });
+        }
+        ParallelHasher {
+            chunk: Vec::with_capacity(PARALLEL_CHUNK_SIZE),
+            pending: VecDeque::new(),
+            crc: 0,
+            bytes_hashed: 0,
+            jobs,
+            threads,
+        }
+    }
+
+    /// Hash `data` after the data given before.
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let count = data.len().min(PARALLEL_CHUNK_SIZE - self.chunk.len());
+            self.chunk.extend_from_slice(&data[..count]);
+            data = &data[count..];
+            if self.chunk.len() == PARALLEL_CHUNK_SIZE {
+                self.submit();
+            }
+        }
+    }
+
+    /// The number of bytes given to the hasher so far
+    pub fn bytes_hashed(&self) -> u64 {
+        self.bytes_hashed
+            + self.pending.iter().map(|&(_, len)| len).sum::<u64>()
+            + self.chunk.len() as u64
+    }
+
+    /// Wait for the workers, and return the CRC-32 of all the data.
+    pub fn finalize(mut self) -> u32 {
+        self.submit();
+        while !self.pending.is_empty() {
+            self.combine_oldest();
+        }
+        self.crc
+    }
+
+    /// Hand the current chunk to a worker, first combining the oldest CRC-32 if enough chunks are
+    /// pending that all workers are busy.
+    fn submit(&mut self) {
+        if self.chunk.is_empty() {
+            return;
+        }
+        if self.pending.len() >= 2 * self.threads {
+            self.combine_oldest();
+        }
+        let chunk = std::mem::replace(&mut self.chunk, Vec::with_capacity(PARALLEL_CHUNK_SIZE));
+        let len = chunk.len() as u64;
+        let (result, receiver) = mpsc::channel();
+        self.jobs
+            .send((chunk, result))
+            .expect("The hashing threads do not stop before the hasher");
+        self.pending.push_back((receiver, len));
+    }
+
+    fn combine_oldest(&mut self) {
+        if let Some((receiver, len)) = self.pending.pop_front() {
+            let crc = receiver
+                .recv()
+                .expect("The hashing threads do not stop before the hasher");
+            self.crc = crc32_combine(self.crc, crc, len);
+            self.bytes_hashed += len;
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl Write for ParallelHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -83,15 +438,48 @@ mod test {
     use super::*;
     use std::io::Read;
 
+    #[test]
+    fn combine() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i * 7 % 256) as u8).collect();
+        let crc = |data: &[u8]| {
+            let mut hasher = Hasher::new();
+            hasher.update(data);
+            hasher.finalize()
+        };
+        for &split in &[0, 1, 3, 4096, 9_999, 10_000] {
+            let (first, second) = data.split_at(split);
+            let combined = crc32_combine(crc(first), crc(second), second.len() as u64);
+            assert_eq!(combined, crc(&data));
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_hasher() {
+        let data: Vec<u8> = (0..10_000_000u32).map(|i| (i % 253) as u8).collect();
+        let mut expected = Hasher::new();
+        expected.update(&data);
+        let expected = expected.finalize();
+        for &threads in &[1, 3] {
+            let mut hasher = ParallelHasher::new(threads);
+            for piece in data.chunks(1_000_001) {
+                hasher.update(piece);
+            }
+            assert_eq!(hasher.bytes_hashed(), data.len() as u64);
+            assert_eq!(hasher.finalize(), expected);
+        }
+        assert_eq!(ParallelHasher::new(2).finalize(), 0);
+    }
+
     #[test]
     fn test_empty_reader() {
         let data: &[u8] = b"";
         let mut buf = [0; 1];
 
-        let mut reader = Crc32Reader::new(data, 0);
+        let mut reader = Crc32Reader::new(data, 0, false);
         assert_eq!(reader.read(&mut buf).unwrap(), 0);
 
-        let mut reader = Crc32Reader::new(data, 1);
+        let mut reader = Crc32Reader::new(data, 1, false);
         assert!(reader
             .read(&mut buf)
             .unwrap_err()
@@ -99,12 +487,22 @@ mod test {
             .contains("Invalid checksum"));
     }
 
+    #[test]
+    fn test_ae2_encrypted() {
+        let data: &[u8] = b"1234";
+        let mut buf = [0; 5];
+
+        let mut reader = Crc32Reader::new(data, 0, true);
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+    }
+
     #[test]
     fn test_byte_by_byte() {
         let data: &[u8] = b"1234";
         let mut buf = [0; 1];
 
-        let mut reader = Crc32Reader::new(data, 0x9be3e0a3);
+        let mut reader = Crc32Reader::new(data, 0x9be3e0a3, false);
         assert_eq!(reader.read(&mut buf).unwrap(), 1);
         assert_eq!(reader.read(&mut buf).unwrap(), 1);
         assert_eq!(reader.read(&mut buf).unwrap(), 1);
@@ -119,8 +517,60 @@ mod test {
         let data: &[u8] = b"1234";
         let mut buf = [0; 5];
 
-        let mut reader = Crc32Reader::new(data, 0x9be3e0a3);
+        let mut reader = Crc32Reader::new(data, 0x9be3e0a3, false);
         assert_eq!(reader.read(&mut buf[..0]).unwrap(), 0);
         assert_eq!(reader.read(&mut buf).unwrap(), 4);
     }
+
+    #[test]
+    fn test_resume() {
+        let data: &[u8] = b"1234";
+        let mut buf = [0; 2];
+
+        let mut reader = Crc32Reader::new(data, 0x9be3e0a3, false);
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(reader.current(), crc32fast::hash(b"12"));
+        assert_eq!(reader.bytes_read(), 2);
+        let (rest, hasher) = reader.into_parts();
+
+        let mut reader = Crc32Reader::new(rest, 0x9be3e0a3, false).with_hasher(hasher, 2);
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert_eq!(reader.bytes_read(), 4);
+
+        let hasher = Hasher::new_with_initial(crc32fast::hash(b"12"));
+        let mut reader = Crc32Reader::new(&data[2..], 0x9be3e0a3, false).with_hasher(hasher, 2);
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+        let mut reader = Crc32Reader::new(&data[2..], 0x9be3e0a3, false);
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert!(reader.read(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_writer() {
+        let mut writer = Crc32Writer::new(Vec::new());
+        writer.write_all(b"12").unwrap();
+        assert_eq!(writer.current(), crc32fast::hash(b"12"));
+        let (data, hasher) = writer.into_parts();
+
+        let mut writer = Crc32Writer::new(data).with_hasher(hasher, 2);
+        writer.write_all(b"34").unwrap();
+        assert_eq!(writer.current(), 0x9be3e0a3);
+        assert_eq!(writer.bytes_written(), 4);
+        assert_eq!(writer.into_inner(), b"1234");
+    }
+
+    #[cfg(feature = "async")]
+    #[futures_await_test::async_test]
+    async fn test_async_writer() {
+        use futures::io::AsyncWriteExt;
+
+        let mut writer = Crc32Writer::new(futures::io::Cursor::new(Vec::new()));
+        writer.write_all(b"1234").await.unwrap();
+        writer.close().await.unwrap();
+        assert_eq!(writer.current(), 0x9be3e0a3);
+        assert_eq!(writer.bytes_written(), 4);
+    }
 }
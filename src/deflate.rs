@@ -0,0 +1,1024 @@
+//! The Deflate implementation used by the synchronous reader and writer
+//!
+//! Which implementation is used is decided by the enabled features:
+//!
+//! - `deflate-libdeflate` uses libdeflate. It only works on whole buffers, so entries are held in
+//!   memory while they are compressed or decompressed. At most the declared size of an entry is
+//!   allocated for its data, see [`read_compressed`].
+//! - Otherwise flate2 is used, with the backend selected through `deflate` (miniz_oxide),
+//!   `deflate-miniz` (miniz), `deflate-zlib` (the system zlib) or `deflate-zlib-ng` (zlib-ng).
+//!
+//! Only miniz_oxide supports compression strategies, so entries using one are always compressed
+//! with miniz_oxide, which requires the `deflate` feature.
+//!
+//! Entries with a preset dictionary are also compressed and decompressed with miniz_oxide. The
+//! dictionary of an entry is identified by the extra field 0x6444, which contains the CRC-32 of
+//! the dictionary as a little endian `u32`. The deflate stream of such an entry is a raw deflate
+//! stream that can refer back into the dictionary, as if the dictionary was decompressed before
+//! the data.
+//!
+//! The asynchronous reader and writer always use flate2.
+//!
+//! With the `parallel` feature, entries can be compressed on several threads with flate2, like
+//! pigz does: the data is split into blocks that are compressed separately, each ending on a
+//! byte boundary with a sync flush, so the compressed blocks form one deflate stream when they
+//! are written one after the other. A block can't refer back into the previous one, which costs
+//! a little compression.
+
+#[cfg(feature = "deflate")]
+use crate::codec::{Compressor, CompressorStream, Decompressor};
+use crate::pool::BufferPool;
+#[cfg(not(feature = "deflate-libdeflate"))]
+use crate::pool::{PooledBuffer, PooledCompress, PooledDecompress};
+#[cfg(not(feature = "deflate"))]
+use crate::result::ZipError;
+use crate::result::ZipResult;
+use crate::write::DeflateStrategy;
+#[cfg(feature = "deflate")]
+use std::collections::HashMap;
+#[cfg(feature = "parallel")]
+use std::collections::VecDeque;
+#[cfg(feature = "deflate")]
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+#[cfg(any(feature = "deflate", feature = "parallel"))]
+use std::sync::Arc;
+#[cfg(feature = "parallel")]
+use std::sync::{mpsc, Mutex};
+#[cfg(feature = "parallel")]
+use std::thread;
+
+/// Read the compressed data of an entry whose data is held in memory while it is decompressed.
+///
+/// Fails if there is more than twice `declared_size` and 64 KiB of it, which is more than a
+/// deflate stream of that much data needs, so a bomb can't make it allocate arbitrary amounts.
+#[cfg(feature = "deflate")]
+fn read_compressed<R: Read>(inner: R, declared_size: u64) -> io::Result<Vec<u8>> {
+    let max_length = declared_size.saturating_mul(2).saturating_add(1 << 16);
+    let mut input = Vec::new();
+    inner
+        .take(max_length.saturating_add(1))
+        .read_to_end(&mut input)?;
+    if input.len() as u64 > max_length {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "The compressed data is longer than its declared size allows",
+        ));
+    }
+    Ok(input)
+}
+
+/// The size of the first buffer for decompressing `input` in one go, and the most it may grow to
+#[cfg(feature = "deflate")]
+fn output_sizes(input: &[u8], max_size: u64) -> (usize, usize) {
+    use std::convert::TryFrom;
+
+    let max_size = usize::try_from(max_size).unwrap_or(usize::MAX);
+    let initial = input.len().saturating_mul(4).max(1 << 16).min(max_size);
+    (initial, max_size)
+}
+
+/// The highest supported compression level
+pub const MAX_LEVEL: u32 = 9;
+
+/// The size of the deflate window, which is the most of a dictionary that can be used
+#[cfg(feature = "deflate")]
+pub const MAX_DICTIONARY_SIZE: usize = 1 << 15;
+
+/// Encoder for any of the supported backends.
+pub enum Encoder<W: Write> {
+    #[cfg(not(feature = "deflate-libdeflate"))]
+    Flate2(Flate2Encoder<W>),
+    #[cfg(feature = "deflate-libdeflate")]
+    Libdeflate(LibdeflateEncoder<W>),
+    #[cfg(feature = "deflate")]
+    MinizOxide(MinizOxideEncoder<W>),
+    #[cfg(feature = "parallel")]
+    Parallel(ParallelEncoder<W>),
+}
+
+/// Create an encoder that writes the compressed data to `inner`, with buffers from `buffers`.
+///
+/// `level` is clamped to [`MAX_LEVEL`]; `None` selects the default level of the backend.
+pub fn encoder<W: Write>(
+    inner: W,
+    level: Option<u32>,
+    strategy: DeflateStrategy,
+    #[cfg_attr(feature = "deflate-libdeflate", allow(unused_variables))] buffers: &BufferPool,
+) -> ZipResult<Encoder<W>> {
+    let level = level.map(|level| level.min(MAX_LEVEL));
+
+    // libdeflate has no level 0
+    let use_miniz_oxide = strategy != DeflateStrategy::Default
+        || (cfg!(feature = "deflate-libdeflate") && level == Some(0));
+    if use_miniz_oxide {
+        #[cfg(feature = "deflate")]
+        return Ok(Encoder::MinizOxide(MinizOxideEncoder::new(
+            inner, level, strategy,
+        )));
+        #[cfg(not(feature = "deflate"))]
+        return Err(ZipError::UnsupportedArchive(
+            "Deflate strategies require the `deflate` feature",
+        ));
+    }
+
+    #[cfg(not(feature = "deflate-libdeflate"))]
+    let encoder = Encoder::Flate2(Flate2Encoder::new(
+        inner,
+        level.map_or_else(flate2::Compression::default, flate2::Compression::new),
+        buffers,
+    ));
+    #[cfg(feature = "deflate-libdeflate")]
+    let encoder = Encoder::Libdeflate(LibdeflateEncoder {
+        inner,
+        buffer: Vec::new(),
+        level: level.map_or_else(libdeflater::CompressionLvl::default, |level| {
+            libdeflater::CompressionLvl::new(level as i32).unwrap_or_default()
+        }),
+    });
+    Ok(encoder)
+}
+
+/// Create an encoder that compresses blocks of the data on `threads` threads, and writes the
+/// compressed data to `inner`.
+///
+/// `level` is clamped to [`MAX_LEVEL`]; `None` selects the default level of flate2.
+#[cfg(feature = "parallel")]
+pub fn parallel_encoder<W: Write>(inner: W, level: Option<u32>, threads: usize) -> Encoder<W> {
+    let level = level.map_or_else(flate2::Compression::default, |level| {
+        flate2::Compression::new(level.min(MAX_LEVEL))
+    });
+    Encoder::Parallel(ParallelEncoder::new(inner, level, threads))
+}
+
+impl<W: Write> Encoder<W> {
+    /// Get a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        match self {
+            #[cfg(not(feature = "deflate-libdeflate"))]
+            Encoder::Flate2(w) => &w.inner,
+            #[cfg(feature = "deflate-libdeflate")]
+            Encoder::Libdeflate(w) => &w.inner,
+            #[cfg(feature = "deflate")]
+            Encoder::MinizOxide(w) => &w.inner,
+            #[cfg(feature = "parallel")]
+            Encoder::Parallel(w) => &w.inner,
+        }
+    }
+
+    /// Write all remaining compressed data and return the inner writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            #[cfg(not(feature = "deflate-libdeflate"))]
+            Encoder::Flate2(w) => w.finish(),
+            #[cfg(feature = "deflate-libdeflate")]
+            Encoder::Libdeflate(w) => w.finish(),
+            #[cfg(feature = "deflate")]
+            Encoder::MinizOxide(w) => w.finish(),
+            #[cfg(feature = "parallel")]
+            Encoder::Parallel(w) => w.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            #[cfg(not(feature = "deflate-libdeflate"))]
+            Encoder::Flate2(w) => w.write(buf),
+            #[cfg(feature = "deflate-libdeflate")]
+            Encoder::Libdeflate(w) => w.write(buf),
+            #[cfg(feature = "deflate")]
+            Encoder::MinizOxide(w) => w.write(buf),
+            #[cfg(feature = "parallel")]
+            Encoder::Parallel(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            #[cfg(not(feature = "deflate-libdeflate"))]
+            Encoder::Flate2(w) => w.flush(),
+            #[cfg(feature = "deflate-libdeflate")]
+            Encoder::Libdeflate(w) => w.flush(),
+            #[cfg(feature = "deflate")]
+            Encoder::MinizOxide(w) => w.flush(),
+            #[cfg(feature = "parallel")]
+            Encoder::Parallel(w) => w.flush(),
+        }
+    }
+}
+
+/// Encoder that calls flate2 with a compression state and an output buffer from a pool.
+#[cfg(not(feature = "deflate-libdeflate"))]
+pub struct Flate2Encoder<W: Write> {
+    inner: W,
+    compress: PooledCompress,
+    /// The compressed data that is not yet written to `inner`
+    output: PooledBuffer,
+}
+
+#[cfg(not(feature = "deflate-libdeflate"))]
+impl<W: Write> Flate2Encoder<W> {
+    fn new(inner: W, level: flate2::Compression, buffers: &BufferPool) -> Flate2Encoder<W> {
+        let mut output = buffers.buffer();
+        output.clear();
+        Flate2Encoder {
+            inner,
+            compress: buffers.compress(level),
+            output,
+        }
+    }
+
+    fn write_output(&mut self) -> io::Result<()> {
+        if !self.output.is_empty() {
+            self.inner.write_all(&self.output)?;
+            self.output.clear();
+        }
+        Ok(())
+    }
+
+    /// Compress `input` with `flush`, returning how much of it was consumed and how much output
+    /// was produced.
+    fn compress(&mut self, input: &[u8], flush: flate2::FlushCompress) -> io::Result<(u64, u64)> {
+        let (total_in, total_out) = (self.compress.total_in(), self.compress.total_out());
+        self.compress
+            .compress_vec(input, &mut self.output, flush)
+            .map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Deflate compression failed")
+            })?;
+        Ok((
+            self.compress.total_in() - total_in,
+            self.compress.total_out() - total_out,
+        ))
+    }
+
+    /// Compress with `flush` until no more output is produced.
+    fn drain(&mut self, flush: flate2::FlushCompress) -> io::Result<()> {
+        loop {
+            self.write_output()?;
+            if self.compress(&[], flush)?.1 == 0 {
+                return self.write_output();
+            }
+        }
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        self.drain(flate2::FlushCompress::Finish)?;
+        Ok(self.inner)
+    }
+}
+
+#[cfg(not(feature = "deflate-libdeflate"))]
+impl<W: Write> Write for Flate2Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        loop {
+            self.write_output()?;
+            let (consumed, _) = self.compress(buf, flate2::FlushCompress::None)?;
+            if consumed > 0 || buf.is_empty() {
+                return Ok(consumed as usize);
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.compress(&[], flate2::FlushCompress::Sync)?;
+        self.drain(flate2::FlushCompress::None)?;
+        self.inner.flush()
+    }
+}
+
+/// Encoder that calls miniz_oxide directly, to be able to set the compression strategy.
+#[cfg(feature = "deflate")]
+pub struct MinizOxideEncoder<W: Write> {
+    inner: W,
+    compressor: MinizOxideCompressor,
+}
+
+#[cfg(feature = "deflate")]
+impl<W: Write> MinizOxideEncoder<W> {
+    fn new(inner: W, level: Option<u32>, strategy: DeflateStrategy) -> MinizOxideEncoder<W> {
+        MinizOxideEncoder {
+            inner,
+            compressor: MinizOxideCompressor::new(level, strategy),
+        }
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        self.compressor.finish(&mut self.inner)?;
+        Ok(self.inner)
+    }
+}
+
+#[cfg(feature = "deflate")]
+impl<W: Write> Write for MinizOxideEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.compressor.write(buf, &mut self.inner)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The state of miniz_oxide compressing a single stream, which does not own its output.
+#[cfg(feature = "deflate")]
+struct MinizOxideCompressor {
+    compressor: Box<miniz_oxide::deflate::core::CompressorOxide>,
+    output: Vec<u8>,
+}
+
+#[cfg(feature = "deflate")]
+impl MinizOxideCompressor {
+    fn new(level: Option<u32>, strategy: DeflateStrategy) -> MinizOxideCompressor {
+        use miniz_oxide::deflate::core::{create_comp_flags_from_zip_params, CompressionStrategy};
+
+        let strategy = match strategy {
+            DeflateStrategy::Default => CompressionStrategy::Default,
+            DeflateStrategy::Filtered => CompressionStrategy::Filtered,
+            DeflateStrategy::HuffmanOnly => CompressionStrategy::HuffmanOnly,
+            DeflateStrategy::Rle => CompressionStrategy::RLE,
+        };
+        // A negative window size selects a raw deflate stream, without zlib header
+        let flags = create_comp_flags_from_zip_params(
+            level.map_or(-1, |level| level as i32),
+            -15,
+            strategy as i32,
+        );
+        MinizOxideCompressor {
+            compressor: Box::new(miniz_oxide::deflate::core::CompressorOxide::new(flags)),
+            output: vec![0; 1 << 15],
+        }
+    }
+
+    /// Compress `dictionary` without keeping the output, so the data compressed afterwards can
+    /// refer back into it.
+    fn prime(&mut self, dictionary: &[u8]) -> io::Result<()> {
+        // The sync flush ends the output on a block boundary, so what follows is a valid stream
+        self.compress(
+            dictionary,
+            miniz_oxide::deflate::core::TDEFLFlush::Sync,
+            &mut io::sink(),
+        )
+    }
+
+    fn write(&mut self, buf: &[u8], output: &mut dyn Write) -> io::Result<()> {
+        self.compress(buf, miniz_oxide::deflate::core::TDEFLFlush::None, output)
+    }
+
+    fn finish(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        self.compress(&[], miniz_oxide::deflate::core::TDEFLFlush::Finish, output)
+    }
+
+    fn compress(
+        &mut self,
+        mut input: &[u8],
+        flush: miniz_oxide::deflate::core::TDEFLFlush,
+        output: &mut dyn Write,
+    ) -> io::Result<()> {
+        use miniz_oxide::deflate::core::{compress, TDEFLStatus};
+
+        loop {
+            let (status, consumed, produced) =
+                compress(&mut self.compressor, input, &mut self.output, flush);
+            output.write_all(&self.output[..produced])?;
+            input = &input[consumed..];
+            match status {
+                TDEFLStatus::Done => return Ok(()),
+                TDEFLStatus::Okay if input.is_empty() && produced < self.output.len() => {
+                    return Ok(())
+                }
+                TDEFLStatus::Okay => {}
+                TDEFLStatus::BadParam | TDEFLStatus::PutBufFailed => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "Deflate compression failed",
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// The size of the blocks that are compressed separately by a [`ParallelEncoder`]
+#[cfg(feature = "parallel")]
+const PARALLEL_BLOCK_SIZE: usize = 1 << 20;
+
+/// A block to compress, with whether it is the last one of the stream and where to send the
+/// compressed block
+#[cfg(feature = "parallel")]
+type Job = (Vec<u8>, bool, mpsc::Sender<io::Result<Vec<u8>>>);
+
+/// Encoder that compresses blocks of the data on worker threads, and writes the compressed blocks
+/// in order.
+#[cfg(feature = "parallel")]
+pub struct ParallelEncoder<W: Write> {
+    inner: W,
+    /// The data that is not yet handed to a worker
+    block: Vec<u8>,
+    /// The results of the blocks handed to the workers, oldest first
+    pending: VecDeque<mpsc::Receiver<io::Result<Vec<u8>>>>,
+    jobs: mpsc::Sender<Job>,
+    threads: usize,
+}
+
+#[cfg(feature = "parallel")]
+impl<W: Write> ParallelEncoder<W> {
+    fn new(inner: W, level: flate2::Compression, threads: usize) -> ParallelEncoder<W> {
+        let threads = threads.max(1);
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..threads {
+            let receiver = receiver.clone();
+            // The workers stop once the encoder, and with it the sender of the jobs, is dropped
+            thread::spawn(move || loop {
+                let job = receiver.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                let (data, last, result) = match job {
+                    Ok(job) => job,
+                    Err(_) => return,
+                };
+                let _ = result.send(compress_block(&data, level, last));
+            });
+        }
+        ParallelEncoder {
+            inner,
+            block: Vec::with_capacity(PARALLEL_BLOCK_SIZE),
+            pending: VecDeque::new(),
+            jobs,
+            threads,
+        }
+    }
+
+    /// Hand the current block to a worker, first writing the oldest compressed block if enough
+    /// blocks are pending that all workers are busy.
+    fn submit(&mut self, last: bool) -> io::Result<()> {
+        if self.pending.len() >= 2 * self.threads {
+            self.write_oldest()?;
+        }
+        let data = std::mem::replace(&mut self.block, Vec::with_capacity(PARALLEL_BLOCK_SIZE));
+        let (result, receiver) = mpsc::channel();
+        self.jobs
+            .send((data, last, result))
+            .map_err(|_| worker_stopped())?;
+        self.pending.push_back(receiver);
+        Ok(())
+    }
+
+    /// Wait for the oldest pending block to be compressed, and write it.
+    fn write_oldest(&mut self) -> io::Result<()> {
+        if let Some(receiver) = self.pending.pop_front() {
+            let compressed = receiver.recv().map_err(|_| worker_stopped())??;
+            self.inner.write_all(&compressed)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        self.submit(true)?;
+        while !self.pending.is_empty() {
+            self.write_oldest()?;
+        }
+        Ok(self.inner)
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl<W: Write> Write for ParallelEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = buf.len().min(PARALLEL_BLOCK_SIZE - self.block.len());
+        self.block.extend_from_slice(&buf[..count]);
+        if self.block.len() == PARALLEL_BLOCK_SIZE {
+            self.submit(false)?;
+        }
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.pending.is_empty() {
+            self.write_oldest()?;
+        }
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "parallel")]
+fn worker_stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "A compression thread stopped")
+}
+
+/// Compress a block as a raw deflate stream, which is finished if it is the last block, and
+/// otherwise ends with a sync flush so the next block can follow it.
+#[cfg(feature = "parallel")]
+fn compress_block(data: &[u8], level: flate2::Compression, last: bool) -> io::Result<Vec<u8>> {
+    let flush = if last {
+        flate2::FlushCompress::Finish
+    } else {
+        flate2::FlushCompress::Sync
+    };
+    let mut compress = flate2::Compress::new(level, false);
+    let mut output = Vec::with_capacity(data.len() / 2 + 64);
+    loop {
+        let consumed = compress.total_in() as usize;
+        let status = compress
+            .compress_vec(&data[consumed..], &mut output, flush)
+            .map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Deflate compression failed")
+            })?;
+        let done = match status {
+            flate2::Status::StreamEnd => true,
+            _ => {
+                !last
+                    && compress.total_in() as usize == data.len()
+                    && output.len() < output.capacity()
+            }
+        };
+        if done {
+            return Ok(output);
+        }
+        output.reserve(data.len() / 4 + 64);
+    }
+}
+
+/// Preset dictionaries by ID, which is the CRC-32 of the dictionary
+#[cfg(feature = "deflate")]
+#[derive(Clone, Default)]
+pub struct Dictionaries(HashMap<u32, Arc<[u8]>>);
+
+#[cfg(feature = "deflate")]
+impl Dictionaries {
+    /// Add a dictionary, of which only the part that fits in the deflate window is kept, and
+    /// return its ID.
+    pub fn insert(&mut self, dictionary: &[u8]) -> u32 {
+        let dictionary = &dictionary[dictionary.len().saturating_sub(MAX_DICTIONARY_SIZE)..];
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(dictionary);
+        let id = hasher.finalize();
+        self.0.insert(id, dictionary.into());
+        id
+    }
+
+    pub fn get(&self, id: u32) -> Option<Arc<[u8]>> {
+        self.0.get(&id).cloned()
+    }
+}
+
+#[cfg(feature = "deflate")]
+impl fmt::Debug for Dictionaries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+/// Compressor of entries with a preset dictionary
+#[cfg(feature = "deflate")]
+pub struct DictionaryCompressor {
+    pub dictionary: Arc<[u8]>,
+    pub level: Option<u32>,
+    pub strategy: DeflateStrategy,
+}
+
+#[cfg(feature = "deflate")]
+impl Compressor for DictionaryCompressor {
+    fn start_entry(&self) -> Box<dyn CompressorStream> {
+        let level = self.level.map(|level| level.min(MAX_LEVEL));
+        let mut compressor = MinizOxideCompressor::new(level, self.strategy);
+        compressor
+            .prime(&self.dictionary)
+            .expect("Writing to a sink does not fail");
+        Box::new(compressor)
+    }
+}
+
+#[cfg(feature = "deflate")]
+impl CompressorStream for MinizOxideCompressor {
+    fn write(&mut self, data: &[u8], output: &mut dyn Write) -> io::Result<()> {
+        MinizOxideCompressor::write(self, data, output)
+    }
+
+    fn finish(mut self: Box<Self>, output: &mut dyn Write) -> io::Result<()> {
+        MinizOxideCompressor::finish(&mut self, output)
+    }
+}
+
+/// Decompressor of entries with a preset dictionary
+#[cfg(feature = "deflate")]
+pub struct DictionaryDecompressor {
+    pub dictionary: Arc<[u8]>,
+    /// The uncompressed size of the entry, of which at most one byte more is decompressed
+    pub declared_size: u64,
+}
+
+#[cfg(feature = "deflate")]
+impl Decompressor for DictionaryDecompressor {
+    fn decompress<'a>(&self, reader: Box<dyn Read + 'a>) -> Box<dyn Read + 'a> {
+        Box::new(DictionaryDecoder {
+            inner: reader,
+            dictionary: self.dictionary.clone(),
+            declared_size: self.declared_size,
+            output: None,
+        })
+    }
+}
+
+/// Decoder that decompresses all data of the inner reader on the first read, after the
+/// dictionary.
+#[cfg(feature = "deflate")]
+struct DictionaryDecoder<R> {
+    inner: R,
+    dictionary: Arc<[u8]>,
+    declared_size: u64,
+    output: Option<io::Cursor<Vec<u8>>>,
+}
+
+#[cfg(feature = "deflate")]
+impl<R: Read> DictionaryDecoder<R> {
+    fn decompress(&mut self) -> io::Result<Vec<u8>> {
+        use miniz_oxide::inflate::core::{
+            decompress, inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF, DecompressorOxide,
+        };
+        use miniz_oxide::inflate::TINFLStatus;
+
+        let input = read_compressed(&mut self.inner, self.declared_size)?;
+
+        // Matches can refer to everything before the current position of the output. One byte
+        // more than the declared size is kept, for the `LimitedReader` to notice it.
+        let (initial, max_size) = output_sizes(&input, self.declared_size.saturating_add(1));
+        let mut output = self.dictionary.to_vec();
+        output.resize(output.len() + initial, 0);
+        let mut decompressor = Box::<DecompressorOxide>::default();
+        let mut in_pos = 0;
+        let mut out_pos = self.dictionary.len();
+        loop {
+            let mut cursor = io::Cursor::new(output.as_mut_slice());
+            cursor.set_position(out_pos as u64);
+            let (status, consumed, produced) = decompress(
+                &mut decompressor,
+                &input[in_pos..],
+                &mut cursor,
+                TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+            );
+            in_pos += consumed;
+            out_pos += produced;
+            match status {
+                TINFLStatus::Done => {
+                    output.truncate(out_pos);
+                    return Ok(output.split_off(self.dictionary.len()));
+                }
+                TINFLStatus::HasMoreOutput if output.len() - self.dictionary.len() < max_size => {
+                    let size = (output.len() - self.dictionary.len()).saturating_mul(2);
+                    output.resize(self.dictionary.len() + size.min(max_size), 0);
+                }
+                TINFLStatus::HasMoreOutput => {
+                    output.truncate(out_pos);
+                    return Ok(output.split_off(self.dictionary.len()));
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Invalid deflate stream",
+                    ))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "deflate")]
+impl<R: Read> Read for DictionaryDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.output.is_none() {
+            self.output = Some(io::Cursor::new(self.decompress()?));
+        }
+        self.output.as_mut().unwrap().read(buf)
+    }
+}
+
+/// Encoder that compresses all written data with libdeflate when it is finished.
+#[cfg(feature = "deflate-libdeflate")]
+pub struct LibdeflateEncoder<W: Write> {
+    inner: W,
+    buffer: Vec<u8>,
+    level: libdeflater::CompressionLvl,
+}
+
+#[cfg(feature = "deflate-libdeflate")]
+impl<W: Write> LibdeflateEncoder<W> {
+    fn finish(mut self) -> io::Result<W> {
+        let mut compressor = libdeflater::Compressor::new(self.level);
+        let mut output = vec![0; compressor.deflate_compress_bound(self.buffer.len())];
+        let size = compressor
+            .deflate_compress(&self.buffer, &mut output)
+            .map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Deflate compression failed")
+            })?;
+        self.inner.write_all(&output[..size])?;
+        Ok(self.inner)
+    }
+}
+
+#[cfg(feature = "deflate-libdeflate")]
+impl<W: Write> Write for LibdeflateEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Decoder that calls flate2 with a decompression state from a pool.
+#[cfg(not(feature = "deflate-libdeflate"))]
+pub struct Decoder<R> {
+    inner: R,
+    decompress: PooledDecompress,
+}
+
+#[cfg(not(feature = "deflate-libdeflate"))]
+impl<R: BufRead> Decoder<R> {
+    pub fn new(inner: R, buffers: &BufferPool) -> Decoder<R> {
+        Decoder {
+            inner,
+            decompress: buffers.decompress(),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+#[cfg(not(feature = "deflate-libdeflate"))]
+impl<R: BufRead> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let input = self.inner.fill_buf()?;
+            let eof = input.is_empty();
+            let (total_in, total_out) = (self.decompress.total_in(), self.decompress.total_out());
+            let flush = if eof {
+                flate2::FlushDecompress::Finish
+            } else {
+                flate2::FlushDecompress::None
+            };
+            let status = self.decompress.decompress(input, buf, flush);
+            let read = (self.decompress.total_out() - total_out) as usize;
+            let consumed = (self.decompress.total_in() - total_in) as usize;
+            self.inner.consume(consumed);
+            match status {
+                Ok(flate2::Status::Ok) | Ok(flate2::Status::BufError)
+                    if read == 0 && !eof && !buf.is_empty() => {}
+                Ok(_) => return Ok(read),
+                Err(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "corrupt deflate stream",
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// Decoder that decompresses all data of the inner reader on the first read.
+#[cfg(feature = "deflate-libdeflate")]
+pub struct Decoder<R> {
+    inner: R,
+    limit: Option<(u64, String)>,
+    output: Option<io::Cursor<Vec<u8>>>,
+}
+
+#[cfg(feature = "deflate-libdeflate")]
+impl<R: Read> Decoder<R> {
+    /// The buffers are not used, since libdeflate does not decompress a stream.
+    pub fn new(inner: R, _buffers: &BufferPool) -> Decoder<R> {
+        Decoder {
+            inner,
+            limit: None,
+            output: None,
+        }
+    }
+
+    /// Fail like a `LimitedReader` when the data of the entry `name` is longer than
+    /// `declared_size`, before more is allocated for it.
+    pub fn limit(mut self, declared_size: u64, name: String) -> Decoder<R> {
+        self.limit = Some((declared_size, name));
+        self
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn decompress(&mut self) -> io::Result<Vec<u8>> {
+        let declared_size = self.limit.as_ref().map_or(u64::MAX, |limit| limit.0);
+        let input = read_compressed(&mut self.inner, declared_size)?;
+
+        let mut decompressor = libdeflater::Decompressor::new();
+        let (initial, max_size) = output_sizes(&input, declared_size);
+        let mut output = vec![0; initial];
+        loop {
+            match decompressor.deflate_decompress(&input, &mut output) {
+                Ok(size) => {
+                    output.truncate(size);
+                    return Ok(output);
+                }
+                Err(libdeflater::DecompressionError::InsufficientSpace)
+                    if output.len() < max_size =>
+                {
+                    let len = output.len().saturating_mul(2).max(1);
+                    output.resize(len.min(max_size), 0);
+                }
+                Err(libdeflater::DecompressionError::InsufficientSpace) => {
+                    let (declared_size, name) =
+                        self.limit.as_ref().expect("Only a limit is reached");
+                    return Err(crate::limit::size_exceeded(name, *declared_size));
+                }
+                Err(libdeflater::DecompressionError::BadData) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Invalid deflate stream",
+                    ))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "deflate-libdeflate")]
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.output.is_none() {
+            self.output = Some(io::Cursor::new(self.decompress()?));
+        }
+        self.output.as_mut().unwrap().read(buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{encoder, BufferPool, Decoder};
+    use crate::write::DeflateStrategy;
+    use std::io::prelude::*;
+
+    fn roundtrip(level: Option<u32>, strategy: DeflateStrategy) -> usize {
+        let data = b"Hello, World! Hello, World! Hello, World!".repeat(10_000);
+        let mut encoder = encoder(Vec::new(), level, strategy, &BufferPool::default()).unwrap();
+        for chunk in data.chunks(1000) {
+            encoder.write_all(chunk).unwrap();
+        }
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoder = Decoder::new(&compressed[..], &BufferPool::default());
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, data);
+        compressed.len()
+    }
+
+    #[test]
+    fn levels() {
+        let stored = roundtrip(Some(0), DeflateStrategy::Default);
+        let fast = roundtrip(Some(1), DeflateStrategy::Default);
+        let default = roundtrip(None, DeflateStrategy::Default);
+        assert!(fast < stored);
+        assert!(default < stored);
+        roundtrip(Some(100), DeflateStrategy::Default);
+    }
+
+    #[cfg(not(feature = "deflate-libdeflate"))]
+    #[test]
+    fn pooled_states() {
+        let data = b"Hello, World! Hello, World! Hello, World!".repeat(1000);
+        let pool = BufferPool::default();
+        for _ in 0..3 {
+            let mut encoder =
+                encoder(Vec::new(), Some(6), DeflateStrategy::Default, &pool).unwrap();
+            encoder.write_all(&data).unwrap();
+            let compressed = encoder.finish().unwrap();
+            let mut decompressed = Vec::new();
+            Decoder::new(&compressed[..], &pool)
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, data);
+            // The states of the previous entry were taken out of the pool and given back
+            assert_eq!(pool.pooled_states(), (1, 1));
+        }
+        // A compression state of another level is not reused
+        drop(encoder(Vec::new(), Some(1), DeflateStrategy::Default, &pool).unwrap());
+        assert_eq!(pool.pooled_states(), (1, 2));
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn strategies() {
+        let huffman = roundtrip(None, DeflateStrategy::HuffmanOnly);
+        roundtrip(Some(9), DeflateStrategy::Rle);
+        let filtered = roundtrip(Some(6), DeflateStrategy::Filtered);
+        let default = roundtrip(Some(6), DeflateStrategy::Default);
+        assert!(default < huffman);
+        assert!(filtered < huffman);
+    }
+
+    fn zeros() -> Vec<u8> {
+        let mut encoder = encoder(
+            Vec::new(),
+            None,
+            DeflateStrategy::Default,
+            &BufferPool::default(),
+        )
+        .unwrap();
+        encoder.write_all(&[0; 1 << 20]).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn dictionary_bomb() {
+        use crate::codec::Decompressor;
+
+        let decompressor = super::DictionaryDecompressor {
+            dictionary: vec![1; 100].into(),
+            declared_size: 1000,
+        };
+        let compressed = zeros();
+        let mut decompressed = Vec::new();
+        decompressor
+            .decompress(Box::new(&compressed[..]))
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        // The byte after the declared size is left for the `LimitedReader` to fail on
+        assert_eq!(decompressed, [0; 1001]);
+    }
+
+    #[cfg(feature = "deflate-libdeflate")]
+    #[test]
+    fn libdeflate_bomb() {
+        use crate::result::UncompressedSizeExceeded;
+
+        let compressed = zeros();
+        let mut decoder =
+            Decoder::new(&compressed[..], &BufferPool::default()).limit(1000, "bomb".to_owned());
+        let error = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+        let error = error
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<UncompressedSizeExceeded>())
+            .unwrap();
+        assert_eq!(error.declared_size, 1000);
+
+        let mut decompressed = Vec::new();
+        Decoder::new(&compressed[..], &BufferPool::default())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed.len(), 1 << 20);
+
+        // The compressed data is not read when it is much longer than the declared size allows
+        let data: Vec<u8> = (0..1 << 17).map(|_| rand::random()).collect();
+        let mut encoder = encoder(
+            Vec::new(),
+            None,
+            DeflateStrategy::Default,
+            &BufferPool::default(),
+        )
+        .unwrap();
+        encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut decoder =
+            Decoder::new(&compressed[..], &BufferPool::default()).limit(10, "bomb".to_owned());
+        let error = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(!error.get_ref().unwrap().is::<UncompressedSizeExceeded>());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel() {
+        let data: Vec<u8> = (0..3_500_000u32)
+            .map(|i| (i % 251 ^ i / 7919) as u8)
+            .collect();
+        for &threads in &[1, 4] {
+            let mut encoder = super::parallel_encoder(Vec::new(), Some(6), threads);
+            for chunk in data.chunks(100_000) {
+                encoder.write_all(chunk).unwrap();
+            }
+            let compressed = encoder.finish().unwrap();
+            assert!(compressed.len() < data.len() / 2);
+
+            let mut decoder = Decoder::new(&compressed[..], &BufferPool::default());
+            let mut decompressed = Vec::new();
+            decoder.read_to_end(&mut decompressed).unwrap();
+            assert_eq!(decompressed, data);
+        }
+
+        let empty = super::parallel_encoder(Vec::new(), None, 4)
+            .finish()
+            .unwrap();
+        let mut decompressed = Vec::new();
+        Decoder::new(&empty[..], &BufferPool::default())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert!(decompressed.is_empty());
+    }
+}
@@ -0,0 +1,297 @@
+//! Helper module to read files that are followed by a data descriptor from a stream
+//!
+//! The sizes of such files are not in their local header, so the end of their data is only found
+//! by decompressing it. The decoder reads its input from a buffered reader, and only consumes the
+//! compressed data from it, so it never takes the data descriptor that follows. A stream that is
+//! not buffered is read through a buffer of a single byte instead. This only works for
+//! compression methods that mark the end of their data, like Deflate.
+
+use crate::crc32::Hasher;
+use crate::spec::{self, DataDescriptor};
+use std::io;
+use std::io::prelude::*;
+
+#[cfg(feature = "async")]
+use async_compression::futures::bufread::DeflateDecoder as AsyncDeflateDecoder;
+#[cfg(feature = "async")]
+use futures::io::{AsyncBufRead, AsyncRead, BufReader};
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+
+/// Buffered reader that counts the bytes that were read or consumed from the inner reader
+pub struct CountingReader<R> {
+    inner: R,
+    consumed: u64,
+}
+
+impl<R> CountingReader<R> {
+    pub fn new(inner: R) -> CountingReader<R> {
+        CountingReader { inner, consumed: 0 }
+    }
+
+    /// The number of bytes that were consumed
+    pub fn consumed(&self) -> u64 {
+        self.consumed
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+}
+
+impl<R: BufRead> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.consumed += count as u64;
+        Ok(count)
+    }
+}
+
+impl<R: BufRead> BufRead for CountingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.consumed += amt as u64;
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncBufRead + Unpin> AsyncRead for CountingReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        Pin::new(&mut this.inner).poll_read(cx, buf).map(|count| {
+            let count = count?;
+            this.consumed += count as u64;
+            Ok(count)
+        })
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncBufRead + Unpin> AsyncBufRead for CountingReader<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Pin::new(&mut self.get_mut().inner).poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        Pin::new(&mut this.inner).consume(amt);
+        this.consumed += amt as u64;
+    }
+}
+
+/// Check a data descriptor against the data that was read.
+fn check_descriptor(
+    descriptor: &DataDescriptor,
+    crc32: u32,
+    compressed_size: u64,
+    uncompressed_size: u64,
+) -> io::Result<()> {
+    if descriptor.crc32 != crc32 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid checksum",
+        ));
+    }
+    if descriptor.compressed_size != compressed_size
+        || descriptor.uncompressed_size != uncompressed_size
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "The sizes in the data descriptor do not match the data",
+        ));
+    }
+    Ok(())
+}
+
+/// Whether the data descriptor of a file has 8 byte sizes.
+///
+/// This is the case if the local header has a ZIP64 extra field, but also if the sizes don't fit
+/// in 4 bytes, as some writers don't add the field when the sizes are not known in advance.
+fn has_zip64_sizes(large_file: bool, compressed_size: u64, uncompressed_size: u64) -> bool {
+    large_file
+        || compressed_size >= spec::ZIP64_BYTES_THR
+        || uncompressed_size >= spec::ZIP64_BYTES_THR
+}
+
+/// Reader of a deflated file that is followed by a data descriptor.
+///
+/// The data descriptor is read and checked against the data once the end of the data is reached.
+pub struct DescriptorReader<R> {
+    decoder: flate2::bufread::DeflateDecoder<CountingReader<R>>,
+    hasher: Hasher,
+    uncompressed_size: u64,
+    large_file: bool,
+    /// Whether the data descriptor was read, which is not done again if it was invalid
+    finished: bool,
+    descriptor: Option<DataDescriptor>,
+}
+
+impl<R: BufRead> DescriptorReader<R> {
+    /// Get a new DescriptorReader for a file whose local header has a ZIP64 extra field if
+    /// `large_file` is set.
+    pub fn new(inner: R, large_file: bool) -> DescriptorReader<R> {
+        DescriptorReader {
+            decoder: flate2::bufread::DeflateDecoder::new(CountingReader::new(inner)),
+            hasher: Hasher::new(),
+            uncompressed_size: 0,
+            large_file,
+            finished: false,
+            descriptor: None,
+        }
+    }
+
+    /// The data descriptor, once all data was read
+    pub fn descriptor(&self) -> Option<&DataDescriptor> {
+        self.descriptor.as_ref()
+    }
+
+    /// Get a mutable reference to the reader of the compressed data and the data descriptor.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.decoder.get_mut().get_mut()
+    }
+}
+
+impl<R: BufRead> Read for DescriptorReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.finished || buf.is_empty() {
+            return Ok(0);
+        }
+        let count = self.decoder.read(buf)?;
+        if count > 0 {
+            self.hasher.update(&buf[..count]);
+            self.uncompressed_size += count as u64;
+            return Ok(count);
+        }
+
+        let crc32 = self.hasher.clone().finalize();
+        let inner = self.decoder.get_mut();
+        let compressed_size = inner.consumed();
+        let zip64 = has_zip64_sizes(self.large_file, compressed_size, self.uncompressed_size);
+        self.finished = true;
+        let descriptor = DataDescriptor::parse(inner, zip64, crc32)?;
+        check_descriptor(&descriptor, crc32, compressed_size, self.uncompressed_size)?;
+        self.descriptor = Some(descriptor);
+        Ok(0)
+    }
+}
+
+/// Asynchronous reader of a deflated file that is followed by a data descriptor.
+///
+/// See [`DescriptorReader`].
+#[cfg(feature = "async")]
+pub struct AsyncDescriptorReader<R> {
+    decoder: AsyncDeflateDecoder<CountingReader<BufReader<R>>>,
+    hasher: Hasher,
+    uncompressed_size: u64,
+    large_file: bool,
+    /// The compressed size and the bytes of the data descriptor that were read so far, once the
+    /// end of the data is reached
+    descriptor_bytes: Option<(u64, [u8; 24], usize)>,
+    /// Whether the data descriptor was read, which is not done again if it was invalid
+    finished: bool,
+    descriptor: Option<DataDescriptor>,
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + Unpin> AsyncDescriptorReader<R> {
+    /// Get a new AsyncDescriptorReader for a file whose local header has a ZIP64 extra field if
+    /// `large_file` is set.
+    pub fn new(inner: R, large_file: bool) -> AsyncDescriptorReader<R> {
+        AsyncDescriptorReader {
+            decoder: AsyncDeflateDecoder::new(CountingReader::new(BufReader::with_capacity(
+                1, inner,
+            ))),
+            hasher: Hasher::new(),
+            uncompressed_size: 0,
+            large_file,
+            descriptor_bytes: None,
+            finished: false,
+            descriptor: None,
+        }
+    }
+
+    /// Read the data descriptor after the end of the data.
+    fn poll_descriptor(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let crc32 = self.hasher.clone().finalize();
+        let inner = self.decoder.get_mut();
+        let (compressed_size, bytes, read) =
+            self.descriptor_bytes
+                .get_or_insert((inner.consumed(), [0; 24], 0));
+        let compressed_size = *compressed_size;
+        let zip64 = has_zip64_sizes(self.large_file, compressed_size, self.uncompressed_size);
+        let length = DataDescriptor::length(zip64);
+        loop {
+            let signature_length = if *read >= 8 && DataDescriptor::has_signature(&bytes[..], crc32)
+            {
+                4
+            } else {
+                0
+            };
+            let end = length + signature_length;
+            if *read == end {
+                break;
+            }
+            match Pin::new(&mut *inner).poll_read(cx, &mut bytes[*read..end]) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => {
+                    self.finished = true;
+                    return Poll::Ready(Err(e));
+                }
+                Poll::Ready(Ok(0)) => {
+                    self.finished = true;
+                    return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+                }
+                Poll::Ready(Ok(count)) => *read += count,
+            }
+        }
+
+        self.finished = true;
+        let signature_length = *read - length;
+        let descriptor = DataDescriptor::from_bytes(&bytes[signature_length..], zip64);
+        check_descriptor(&descriptor, crc32, compressed_size, self.uncompressed_size)?;
+        self.descriptor = Some(descriptor);
+        Poll::Ready(Ok(()))
+    }
+
+    /// The data descriptor, once all data was read
+    pub fn descriptor(&self) -> Option<&DataDescriptor> {
+        self.descriptor.as_ref()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncDescriptorReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.finished || buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        if this.descriptor_bytes.is_none() {
+            match Pin::new(&mut this.decoder).poll_read(cx, buf) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Ready(Ok(0)) => {}
+                Poll::Ready(Ok(count)) => {
+                    this.hasher.update(&buf[..count]);
+                    this.uncompressed_size += count as u64;
+                    return Poll::Ready(Ok(count));
+                }
+            }
+        }
+        this.poll_descriptor(cx).map_ok(|()| 0)
+    }
+}
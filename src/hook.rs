@@ -0,0 +1,134 @@
+//! Hooks for transforming the data of entries while it is read or written
+//!
+//! A [`ReadHook`] can be set with [`ZipArchive::set_read_hook`] to wrap the decompressed data of
+//! each entry that is read, and a [`WriteHook`] with [`ZipWriter::set_write_hook`] to transform
+//! the data of each file before it is compressed. This allows things like transcoding, content
+//! filtering or hashing the contents without changing the reader or writer.
+//!
+//! A [`WriteObserver`] set with [`ZipWriter::set_observer`] is told how each file was
+//! compressed, so that the method and level of the next files can be chosen from the observed
+//! ratio and throughput.
+//!
+//! [`ZipArchive::set_read_hook`]: crate::read::ZipArchive::set_read_hook
+//! [`ZipWriter::set_write_hook`]: crate::write::ZipWriter::set_write_hook
+//! [`ZipWriter::set_observer`]: crate::write::ZipWriter::set_observer
+
+use crate::compression::CompressionMethod;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Wraps the data of entries that are read.
+pub trait ReadHook: Send + Sync {
+    /// Wrap the reader of the decompressed data of the entry named `name`.
+    ///
+    /// The CRC-32 of the entry is checked by `reader`, so it applies to the data before it is
+    /// transformed. Return `reader` itself to leave an entry unchanged.
+    fn wrap_reader<'a>(&self, name: &str, reader: Box<dyn Read + 'a>) -> Box<dyn Read + 'a>;
+}
+
+/// Transforms the data of files that are written.
+pub trait WriteHook: Send + Sync {
+    /// Start transforming the file named `name`, or return `None` to write it unchanged.
+    fn start_entry(&self, name: &str) -> Option<Box<dyn EntryTransform>>;
+}
+
+/// The state of the transformation of a single file.
+///
+/// The output is compressed and checksummed like the data of a file without a transformation.
+pub trait EntryTransform: Send {
+    /// Transform `data`, writing any output that is ready to `output`.
+    fn write(&mut self, data: &[u8], output: &mut dyn Write) -> io::Result<()>;
+
+    /// Write all remaining output to `output`. Called once at the end of the file.
+    fn finish(self: Box<Self>, output: &mut dyn Write) -> io::Result<()>;
+}
+
+/// Observes the files that are written.
+pub trait WriteObserver: Send + Sync {
+    /// Called when a file is finished, with how it was written.
+    fn entry_written(&self, stats: &EntryStats);
+}
+
+/// How a file was written, see [`WriteObserver`]
+#[derive(Clone, Debug)]
+pub struct EntryStats {
+    pub(crate) name: String,
+    pub(crate) compression: CompressionMethod,
+    pub(crate) size: u64,
+    pub(crate) compressed_size: u64,
+    pub(crate) elapsed: Duration,
+}
+
+impl EntryStats {
+    /// The name of the file
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The compression method of the file
+    pub fn compression(&self) -> CompressionMethod {
+        self.compression
+    }
+
+    /// The size of the data that was written
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The size of the data in the archive, after compression and encryption
+    pub fn compressed_size(&self) -> u64 {
+        self.compressed_size
+    }
+
+    /// The compressed size divided by the size, or 1 for an empty file
+    pub fn ratio(&self) -> f64 {
+        if self.size == 0 {
+            return 1.0;
+        }
+        self.compressed_size as f64 / self.size as f64
+    }
+
+    /// The time spent in the writer on the file, to compress its data and write it
+    ///
+    /// This does not include the time between writes, in which the data was produced.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// An optional hook, which can be part of types deriving `Clone` and `Debug`.
+pub(crate) struct Hook<T: ?Sized>(Option<Arc<T>>);
+
+impl<T: ?Sized> Hook<T> {
+    pub fn set(&mut self, hook: Arc<T>) {
+        self.0 = Some(hook);
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        self.0.as_deref()
+    }
+
+    pub fn get_arc(&self) -> Option<Arc<T>> {
+        self.0.clone()
+    }
+}
+
+impl<T: ?Sized> Default for Hook<T> {
+    fn default() -> Self {
+        Hook(None)
+    }
+}
+
+impl<T: ?Sized> Clone for Hook<T> {
+    fn clone(&self) -> Self {
+        Hook(self.0.clone())
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for Hook<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.0.is_some() { "Some(..)" } else { "None" })
+    }
+}
@@ -0,0 +1,169 @@
+//! Serving the files of an archive in HTTP responses, like with hyper or axum
+//!
+//! An [`HttpEntry`] is the body of a response with one file of an [`AsyncZipArchive`], along with
+//! the values of its `Content-Length` and `Content-Encoding` headers. No HTTP crate is used: the
+//! body is a [`Stream`] of chunks that convert into `bytes::Bytes`, as taken by
+//! `hyper::Body::wrap_stream`.
+//!
+//! ```no_run
+//! use zip::http::{HttpEncoding, HttpEntry};
+//! use zip::AsyncZipArchive;
+//!
+//! # async fn serve(archive: AsyncZipArchive<futures::io::Cursor<Vec<u8>>>) -> zip::result::ZipResult<()> {
+//! let entry = HttpEntry::new(archive.clone(), "index.html", HttpEncoding::Deflate).await?;
+//! let content_length = entry.content_length();
+//! let content_encoding = entry.content_encoding();
+//! // Set the headers and send `entry` as the body
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::compression::CompressionMethod;
+use crate::crc32::Crc32Reader;
+use crate::limit::LimitedReader;
+use crate::read::{self, AsyncZipArchive};
+use crate::result::{ZipError, ZipResult};
+use futures::io::{AsyncRead, AsyncSeek};
+use futures::stream::Stream;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[cfg(feature = "bzip2")]
+use async_compression::futures::bufread::BzDecoder;
+#[cfg(any(
+    feature = "deflate",
+    feature = "deflate-miniz",
+    feature = "deflate-zlib"
+))]
+use async_compression::futures::bufread::DeflateDecoder;
+#[cfg(any(
+    feature = "deflate",
+    feature = "deflate-miniz",
+    feature = "deflate-zlib",
+    feature = "bzip2"
+))]
+use futures::io::BufReader;
+
+/// How the body of an [`HttpEntry`] is encoded
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HttpEncoding {
+    /// The contents of the file, decompressed if needed
+    Identity,
+    /// The data of a deflated file as it is stored, so that it is decompressed by the client
+    /// instead of being decompressed and compressed again
+    ///
+    /// HTTP defines the `deflate` coding as a zlib stream, but the raw deflate data of an archive
+    /// is accepted by most clients. Files that are not deflated are sent like with `Identity`.
+    Deflate,
+}
+
+/// The body of an HTTP response with a file of an archive
+///
+/// The chunks of the body are read from the archive as they are polled. A decompressed body is
+/// checked against the CRC-32 of the file, and fails at its end if it doesn't match.
+pub struct HttpEntry {
+    content_length: u64,
+    content_encoding: Option<&'static str>,
+    body: Pin<Box<dyn AsyncRead + Send>>,
+    buf: Vec<u8>,
+}
+
+impl HttpEntry {
+    /// Prepare the response with the file `name` of `archive`, encoded with `encoding`.
+    ///
+    /// The body owns the reader of the archive, so an archive that serves several responses is
+    /// cloned for each of them. Encrypted files are not supported.
+    pub async fn new<R>(
+        archive: AsyncZipArchive<R>,
+        name: &str,
+        encoding: HttpEncoding,
+    ) -> ZipResult<HttpEntry>
+    where
+        R: AsyncRead + AsyncSeek + Send + Sync + Unpin + 'static,
+    {
+        let (reader, data) = archive.into_stored_data(name).await?;
+        if data.encrypted {
+            return Err(ZipError::UnsupportedArchive(
+                "Encrypted files cannot be served",
+            ));
+        }
+        if read::has_deflate_dictionary(&data) {
+            return Err(ZipError::UnsupportedArchive(
+                "Deflate dictionaries are not supported by the async reader",
+            ));
+        }
+
+        let method = data.compression_method;
+        if encoding == HttpEncoding::Deflate && method == CompressionMethod::DEFLATE {
+            return Ok(HttpEntry::with_body(
+                data.compressed_size,
+                Some("deflate"),
+                Box::pin(reader),
+            ));
+        }
+
+        let size = data.uncompressed_size;
+        let name = String::from(data.file_name);
+        let body: Pin<Box<dyn AsyncRead + Send>> = match method {
+            CompressionMethod::Stored => Box::pin(LimitedReader::new(reader, size, name)),
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-miniz",
+                feature = "deflate-zlib"
+            ))]
+            CompressionMethod::Deflated => {
+                let reader = DeflateDecoder::new(BufReader::new(reader));
+                Box::pin(LimitedReader::new(reader, size, name))
+            }
+            #[cfg(feature = "bzip2")]
+            CompressionMethod::Bzip2 => {
+                let reader = BzDecoder::new(BufReader::new(reader));
+                Box::pin(LimitedReader::new(reader, size, name))
+            }
+            _ => {
+                return Err(ZipError::UnsupportedArchive(
+                    "Compression method not supported",
+                ))
+            }
+        };
+        let body = Box::pin(Crc32Reader::new(body, data.crc32, false));
+        Ok(HttpEntry::with_body(size, None, body))
+    }
+
+    fn with_body(
+        content_length: u64,
+        content_encoding: Option<&'static str>,
+        body: Pin<Box<dyn AsyncRead + Send>>,
+    ) -> HttpEntry {
+        HttpEntry {
+            content_length,
+            content_encoding,
+            body,
+            buf: vec![0; 8192],
+        }
+    }
+
+    /// The value of the `Content-Length` header, the size of the body
+    pub fn content_length(&self) -> u64 {
+        self.content_length
+    }
+
+    /// The value of the `Content-Encoding` header, if the body is encoded
+    pub fn content_encoding(&self) -> Option<&'static str> {
+        self.content_encoding
+    }
+}
+
+impl Stream for HttpEntry {
+    type Item = io::Result<Vec<u8>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        match futures::ready!(this.body.as_mut().poll_read(cx, &mut this.buf)) {
+            Ok(0) => Poll::Ready(None),
+            Ok(count) => Poll::Ready(Some(Ok(this.buf[..count].to_vec()))),
+            Err(e) => Poll::Ready(Some(Err(e))),
+        }
+    }
+}
@@ -7,21 +7,63 @@
 
 pub use crate::compression::CompressionMethod;
 pub use crate::read::ZipArchive;
-pub use crate::types::DateTime;
+pub use crate::types::{
+    AesMode, AesVendorVersion, DateTime, EncryptionMethod, ExtensibleDataBlock,
+    StrongEncryptionAlgorithm, System,
+};
 pub use crate::write::ZipWriter;
 
 #[cfg(feature = "async")]
 pub use crate::read::AsyncZipArchive;
 
+#[cfg(feature = "aes-crypto")]
+mod aes;
 #[cfg(feature = "async")]
 mod async_util;
+#[cfg(feature = "tokio")]
+extern crate tokio_crate as tokio;
 
+pub mod codec;
 mod compression;
 mod cp437;
-mod crc32;
+pub mod crc32;
+#[cfg(any(
+    feature = "deflate",
+    feature = "deflate-miniz",
+    feature = "deflate-zlib"
+))]
+mod deflate;
+#[cfg(any(
+    feature = "deflate",
+    feature = "deflate-miniz",
+    feature = "deflate-zlib"
+))]
+mod descriptor;
+pub mod hook;
+#[cfg(feature = "http")]
+pub mod http;
+mod limit;
+#[cfg(all(feature = "mmap", unix))]
+pub mod mmap;
+#[cfg(all(feature = "mmap", not(unix)))]
+compile_error!("The `mmap` feature is only supported on Unix");
+pub mod multidisk;
+mod name_index;
+pub mod options;
+pub mod overlay;
+mod pool;
 pub mod read;
+pub mod read_at;
 pub mod result;
+pub mod signature;
+#[cfg(feature = "sniff")]
+pub mod sniff;
 mod spec;
+pub mod temp;
+#[cfg(feature = "tokio")]
+pub mod tokio_compat;
 mod types;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
 pub mod write;
 mod zipcrypto;
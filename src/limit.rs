@@ -0,0 +1,230 @@
+//! Helper module to check the uncompressed size of entries and the time spent reading them
+
+use crate::result::{UncompressedSizeExceeded, ZipError, ZipResult};
+use std::io;
+use std::io::prelude::*;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "async")]
+use futures::io::AsyncRead;
+#[cfg(feature = "async")]
+use pin_project::pin_project;
+
+/// Reader of the decompressed data of an entry that fails if there is more data than the
+/// uncompressed size of the entry.
+///
+/// Without this, the recorded size could not be trusted to limit the memory or disk space used to
+/// extract an entry.
+#[cfg_attr(feature = "async", pin_project(project=LimitedReaderProject))]
+pub struct LimitedReader<R> {
+    #[cfg_attr(feature = "async", pin)]
+    inner: R,
+    remaining: u64,
+    declared_size: u64,
+    name: String,
+}
+
+impl<R> LimitedReader<R> {
+    /// Get a new LimitedReader for the entry `name`, which is `declared_size` bytes long.
+    pub fn new(inner: R, declared_size: u64, name: String) -> LimitedReader<R> {
+        LimitedReader {
+            inner,
+            remaining: declared_size,
+            declared_size,
+            name,
+        }
+    }
+
+    /// Consumes this reader, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Get a reference to the underlying reader.
+    #[cfg(any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib"
+    ))]
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying reader, to read past the limit.
+    #[cfg(any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib"
+    ))]
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+}
+
+/// The error of an entry `name` whose data is longer than its `declared_size`
+pub(crate) fn size_exceeded(name: &str, declared_size: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        UncompressedSizeExceeded {
+            name: name.to_owned(),
+            declared_size,
+        },
+    )
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            // All data was read, so only the end of the data may follow
+            return match self.inner.read(&mut [0])? {
+                0 => Ok(0),
+                _ => Err(size_exceeded(&self.name, self.declared_size)),
+            };
+        }
+        let length = (buf.len() as u64).min(self.remaining) as usize;
+        let count = self.inner.read(&mut buf[..length])?;
+        self.remaining -= count as u64;
+        Ok(count)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead> AsyncRead for LimitedReader<R> {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> std::task::Poll<io::Result<usize>> {
+        let LimitedReaderProject {
+            inner,
+            remaining,
+            declared_size,
+            name,
+        } = self.project();
+        if buf.is_empty() {
+            return std::task::Poll::Ready(Ok(0));
+        }
+        if *remaining == 0 {
+            return inner.poll_read(cx, &mut [0]).map(|count| match count? {
+                0 => Ok(0),
+                _ => Err(size_exceeded(name, *declared_size)),
+            });
+        }
+        let length = (buf.len() as u64).min(*remaining) as usize;
+        inner.poll_read(cx, &mut buf[..length]).map(|count| {
+            let count = count?;
+            *remaining -= count as u64;
+            Ok(count)
+        })
+    }
+}
+
+/// The time by which an operation must be done, see [`Config::time_limit`]
+///
+/// [`Config::time_limit`]: crate::read::Config::time_limit
+#[derive(Copy, Clone, Debug)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    /// The deadline `limit` from now, or no deadline if `limit` is `None`
+    pub fn after(limit: Option<Duration>) -> Deadline {
+        Deadline(limit.and_then(|limit| Instant::now().checked_add(limit)))
+    }
+
+    /// Fail with [`ZipError::TimeLimitExceeded`] if the deadline has passed.
+    pub fn check(&self) -> ZipResult<()> {
+        match self.0 {
+            Some(deadline) if Instant::now() >= deadline => Err(ZipError::TimeLimitExceeded),
+            _ => Ok(()),
+        }
+    }
+
+    /// [`ZipError::TimeLimitExceeded`] if the deadline has passed, so that is what caused
+    /// `error`, or else `error`
+    pub fn or_exceeded(&self, error: ZipError) -> ZipError {
+        self.check().err().unwrap_or(error)
+    }
+
+    /// Wrap `inner` in a reader that fails once the deadline has passed.
+    pub fn reader<R>(self, inner: R) -> DeadlineReader<R> {
+        DeadlineReader {
+            inner,
+            deadline: self,
+        }
+    }
+}
+
+/// Reader that fails once its deadline has passed, see [`Deadline::reader`]
+pub struct DeadlineReader<R> {
+    inner: R,
+    deadline: Deadline,
+}
+
+impl<R: Read> Read for DeadlineReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.deadline.check()?;
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_exact_size() {
+        let data: &[u8] = b"1234";
+        let mut buf = Vec::new();
+
+        let mut reader = LimitedReader::new(data, 4, "a".to_owned());
+        assert_eq!(reader.read_to_end(&mut buf).unwrap(), 4);
+        assert_eq!(reader.read(&mut [0; 4]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_shorter_data() {
+        let data: &[u8] = b"12";
+        let mut buf = Vec::new();
+
+        let mut reader = LimitedReader::new(data, 4, "a".to_owned());
+        assert_eq!(reader.read_to_end(&mut buf).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_size_exceeded() {
+        let data: &[u8] = b"12345";
+        let mut buf = Vec::new();
+
+        let mut reader = LimitedReader::new(data, 4, "a".to_owned());
+        let error = reader.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error = error
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<UncompressedSizeExceeded>())
+            .unwrap();
+        assert_eq!(error.name, "a");
+        assert_eq!(error.declared_size, 4);
+        assert_eq!(buf, b"1234");
+    }
+
+    #[test]
+    fn test_deadline() {
+        let data: &[u8] = b"1234";
+        let mut buf = Vec::new();
+
+        let mut reader = Deadline::after(Some(Duration::from_secs(3600))).reader(data);
+        assert_eq!(reader.read_to_end(&mut buf).unwrap(), 4);
+        assert!(Deadline::after(None).check().is_ok());
+
+        let deadline = Deadline::after(Some(Duration::from_secs(0)));
+        let error = deadline.reader(data).read_to_end(&mut buf).unwrap_err();
+        match deadline.or_exceeded(error.into()) {
+            ZipError::TimeLimitExceeded => {}
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+}
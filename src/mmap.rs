@@ -0,0 +1,122 @@
+//! Reading archives that are mapped into memory
+//!
+//! [`ZipArchive::open_mmap`] maps an archive file into memory with `mmap`. The central directory
+//! is then parsed from memory without a system call for every record, stored files are borrowed
+//! from the mapping with [`ZipArchive::stored_data`], and compressed files are decompressed
+//! straight from it.
+//!
+//! ```no_run
+//! use std::io::Read;
+//! use zip::ZipArchive;
+//!
+//! # fn main() -> zip::result::ZipResult<()> {
+//! // Safety: nothing changes the archive while it is open
+//! let mut archive = unsafe { ZipArchive::open_mmap("archive.zip")? };
+//! let stored: &[u8] = archive.stored_data(0)?;
+//! let mut compressed = Vec::new();
+//! archive.by_index(1)?.read_to_end(&mut compressed)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The file must not be changed while it is mapped: the data read from the mapping changes with
+//! it, and the process gets a `SIGBUS` signal if the file is truncated. Nothing can prevent other
+//! processes from doing so, which is why mapping a file is `unsafe`.
+//!
+//! This module is only available on Unix, and enabling the `mmap` feature elsewhere fails to
+//! compile.
+
+use crate::read::ZipArchive;
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+/// A [`ZipArchive`] of a mapped file
+pub type MmapArchive = ZipArchive<io::Cursor<Mmap>>;
+
+/// A read-only mapping of a whole file
+#[derive(Debug)]
+pub struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// The mapping is read-only and owned by the value, like a `Box<[u8]>`. The callers of `Mmap::map`
+// promise that the file doesn't change while it is mapped, so the memory is immutable for as long
+// as the value lives, and can be read from any thread.
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    /// Map the file at `path`.
+    ///
+    /// # Safety
+    ///
+    /// See [`Mmap::map`].
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> io::Result<Mmap> {
+        Mmap::map(&File::open(path)?)
+    }
+
+    /// Map `file`, which can be closed afterwards.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be written to or truncated, by this or any other process, until the
+    /// returned value is dropped. The slice it dereferences to would change, which is undefined
+    /// behavior, or the process would be killed by a `SIGBUS` signal when reading it.
+    pub unsafe fn map(file: &File) -> io::Result<Mmap> {
+        use std::convert::TryFrom;
+
+        let len = usize::try_from(file.metadata()?.len()).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "The file is too large to map")
+        })?;
+        if len == 0 {
+            // Empty mappings are not allowed
+            return Ok(Mmap {
+                ptr: std::ptr::null_mut(),
+                len,
+            });
+        }
+        let ptr = libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        );
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mmap { ptr, len })
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl AsRef<[u8]> for Mmap {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe {
+                libc::munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! Reading and writing archives that are split over several disks
+//!
+//! A split archive is a sequence of files that are read as one: `archive.z01`, `archive.z02`, …
+//! hold the first disks, and `archive.zip` holds the last one with the end of the central
+//! directory. Spanned archives have the same layout, on removable media instead of files. An
+//! entry may start on one disk and continue on the next.
+//!
+//! Some tools name the disks `archive.zip.001`, `archive.zip.002`, … instead, often for an archive
+//! that was cut into pieces after it was written. [`SplitFiles::discover`] finds out which naming
+//! is used.
+//!
+//! A multi-disk archive can be rewritten into a single file with [`merge`].
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use zip::multidisk::SplitFiles;
+//! use zip::write::{FileOptions, SplitZipWriter};
+//! use zip::ZipArchive;
+//!
+//! # fn main() -> zip::result::ZipResult<()> {
+//! // Disks of at most 64 MiB
+//! let mut zip = SplitZipWriter::new_split(SplitFiles::new("archive.zip"), 64 << 20);
+//! zip.start_file("large.bin", FileOptions::default())?;
+//! zip.write_all(b"...")?;
+//! zip.finish()?.finish()?;
+//!
+//! let mut archive = ZipArchive::new_multidisk(SplitFiles::new("archive.zip"))?;
+//! let file = archive.by_name("large.bin")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::read::ZipArchive;
+use crate::result::{ZipError, ZipResult};
+use crate::write::{FileOptions, ZipWriter};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+/// Opens the disks of a multi-disk archive, see [`ZipArchive::new_multidisk`]
+///
+/// [`ZipArchive::new_multidisk`]: crate::read::ZipArchive::new_multidisk
+pub trait DiskProvider {
+    /// The type of the disks
+    type Disk: Read + Seek;
+
+    /// Open the last disk, which holds the end of the central directory.
+    fn open_last(&mut self) -> io::Result<Self::Disk>;
+
+    /// Open the disk with the given number, where 0 is the first disk.
+    ///
+    /// This is only called for the disks before the last one.
+    fn open(&mut self, number: u32) -> io::Result<Self::Disk>;
+
+    /// The number of disks, if it is known without reading them.
+    ///
+    /// The end of the central directory of an archive that was cut into pieces after it was
+    /// written says that there is only one disk. If there are more, they are read as one stream.
+    fn disk_count(&mut self) -> io::Result<Option<u32>> {
+        Ok(None)
+    }
+}
+
+/// Creates the disks of a multi-disk archive that is written, see [`ZipWriter::new_split`]
+///
+/// [`ZipWriter::new_split`]: crate::write::ZipWriter::new_split
+pub trait DiskSink {
+    /// The type of the disks
+    type Disk: Write + Seek;
+
+    /// Create the disk with the given number, where 0 is the first disk.
+    fn create(&mut self, number: u32) -> io::Result<Self::Disk>;
+
+    /// Finish the archive once all of its `disks` are written.
+    fn finish(&mut self, disks: Vec<Self::Disk>) -> io::Result<()>;
+}
+
+/// The files of a split archive, named like `archive.z01`, `archive.z02`, … and `archive.zip`, or
+/// like `archive.zip.001`, `archive.zip.002`, …
+#[derive(Clone, Debug)]
+pub struct SplitFiles {
+    path: PathBuf,
+    numbered: bool,
+}
+
+impl SplitFiles {
+    /// The split archive whose last disk is the file at `path`.
+    pub fn new<P: AsRef<Path>>(path: P) -> SplitFiles {
+        SplitFiles {
+            path: path.as_ref().to_path_buf(),
+            numbered: false,
+        }
+    }
+
+    /// The split archive whose disks are the files at `path` with the extensions `.001`, `.002`,
+    /// …, where the last disk is the one with the highest number.
+    pub fn numbered<P: AsRef<Path>>(path: P) -> SplitFiles {
+        SplitFiles {
+            path: path.as_ref().to_path_buf(),
+            numbered: true,
+        }
+    }
+
+    /// The split archive that the file at `path` belongs to
+    ///
+    /// `path` may be any of the disks, or the name of the archive without a number. The files are
+    /// numbered if `path` ends with a number like `.001`, or if there is no file at `path` but
+    /// there is one with `.001` appended.
+    pub fn discover<P: AsRef<Path>>(path: P) -> SplitFiles {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let is_number =
+            |digits: &str| !digits.is_empty() && digits.bytes().all(|d| d.is_ascii_digit());
+        if extension.len() >= 3 && is_number(extension) {
+            return SplitFiles::numbered(path.with_extension(""));
+        }
+        if extension.len() >= 3 && extension.starts_with('z') && is_number(&extension[1..]) {
+            return SplitFiles::new(path.with_extension("zip"));
+        }
+        let numbered = SplitFiles::numbered(path);
+        if !path.is_file() && numbered.disk_path(0).is_file() {
+            numbered
+        } else {
+            SplitFiles::new(path)
+        }
+    }
+
+    /// The path of the disk with the given number, where 0 is the first disk.
+    pub fn disk_path(&self, number: u32) -> PathBuf {
+        if self.numbered {
+            let mut path = self.path.clone().into_os_string();
+            path.push(format!(".{:03}", number as u64 + 1));
+            return path.into();
+        }
+        self.path
+            .with_extension(format!("z{:02}", number as u64 + 1))
+    }
+
+    /// The number of numbered files that exist, starting from the first one
+    fn numbered_count(&self) -> u32 {
+        let mut count = 0;
+        while count < u32::MAX && self.disk_path(count).is_file() {
+            count += 1;
+        }
+        count
+    }
+}
+
+impl DiskProvider for SplitFiles {
+    type Disk = File;
+
+    fn open_last(&mut self) -> io::Result<File> {
+        if !self.numbered {
+            return File::open(&self.path);
+        }
+        match self.numbered_count() {
+            0 => File::open(self.disk_path(0)),
+            count => File::open(self.disk_path(count - 1)),
+        }
+    }
+
+    fn open(&mut self, number: u32) -> io::Result<File> {
+        File::open(self.disk_path(number))
+    }
+
+    fn disk_count(&mut self) -> io::Result<Option<u32>> {
+        if self.numbered {
+            Ok(Some(self.numbered_count()))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// The disks are written as `.z01`, `.z02`, … files, and the last one is renamed to the path of
+/// the archive once it is finished. Numbered disks keep their names.
+impl DiskSink for SplitFiles {
+    type Disk = File;
+
+    fn create(&mut self, number: u32) -> io::Result<File> {
+        File::create(self.disk_path(number))
+    }
+
+    fn finish(&mut self, disks: Vec<File>) -> io::Result<()> {
+        let last = disks.len().saturating_sub(1) as u32;
+        drop(disks);
+        if self.numbered {
+            return Ok(());
+        }
+        fs::rename(self.disk_path(last), &self.path)
+    }
+}
+
+/// Reader for the disks of a multi-disk archive as one stream, in which they follow each other
+#[derive(Debug)]
+pub struct MultiDiskReader<D> {
+    disks: Vec<D>,
+    starts: Vec<u64>,
+    length: u64,
+    position: u64,
+}
+
+impl<D: Read + Seek> MultiDiskReader<D> {
+    /// Read the `disks`, the first one first.
+    pub fn new(mut disks: Vec<D>) -> io::Result<MultiDiskReader<D>> {
+        let mut starts = Vec::with_capacity(disks.len());
+        let mut length = 0u64;
+        for disk in &mut disks {
+            starts.push(length);
+            length = length
+                .checked_add(disk.seek(io::SeekFrom::End(0))?)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Disks are too long"))?;
+        }
+        Ok(MultiDiskReader {
+            disks,
+            starts,
+            length,
+            position: 0,
+        })
+    }
+}
+
+impl<D> MultiDiskReader<D> {
+    /// The number of disks
+    pub fn disk_count(&self) -> u32 {
+        self.disks.len() as u32
+    }
+
+    /// The position in the stream at which the disk with the given number starts, or `None`
+    /// if there is no such disk.
+    pub fn disk_start(&self, number: u32) -> Option<u64> {
+        self.starts.get(number as usize).copied()
+    }
+
+    /// Unwrap the disks.
+    pub fn into_inner(self) -> Vec<D> {
+        self.disks
+    }
+}
+
+impl<D: Read + Seek> Read for MultiDiskReader<D> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.length || buf.is_empty() {
+            return Ok(0);
+        }
+        // The last disk that starts at the position, so empty disks are skipped
+        let index = self
+            .starts
+            .iter()
+            .rposition(|&start| start <= self.position)
+            .unwrap_or(0);
+        let end = self.starts.get(index + 1).copied().unwrap_or(self.length);
+        let length = (buf.len() as u64).min(end - self.position) as usize;
+
+        let disk = &mut self.disks[index];
+        disk.seek(io::SeekFrom::Start(self.position - self.starts[index]))?;
+        let count = disk.read(&mut buf[..length])?;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<D> Seek for MultiDiskReader<D> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            io::SeekFrom::Start(offset) => (offset, 0),
+            io::SeekFrom::End(offset) => (self.length, offset),
+            io::SeekFrom::Current(offset) => (self.position, offset),
+        };
+        let position = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.wrapping_neg() as u64)
+        };
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// How [`merge`] copies the entries of an archive
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MergeMode {
+    /// Copy the compressed data as it is, see [`ZipWriter::raw_copy_file`]
+    Raw,
+    /// Decompress the data and compress it again with the same method
+    Recompress,
+}
+
+/// Rewrite the multi-disk `archive` into a single-file archive written to `writer`, and return
+/// the writer.
+///
+/// The entries are written one after the other, so they are on the first and only disk and their
+/// offsets are those in the new file. Encrypted entries can't be merged.
+///
+/// ```no_run
+/// use zip::multidisk::{merge, MergeMode, SplitFiles};
+/// use zip::ZipArchive;
+///
+/// # fn main() -> zip::result::ZipResult<()> {
+/// let mut archive = ZipArchive::new_multidisk(SplitFiles::discover("archive.zip"))?;
+/// let output = std::fs::File::create("merged.zip")?;
+/// merge(&mut archive, output, MergeMode::Raw)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn merge<R, W>(archive: &mut ZipArchive<R>, writer: W, mode: MergeMode) -> ZipResult<W>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    let mut zip = ZipWriter::new(writer);
+    zip.set_comment(String::from_utf8_lossy(archive.comment()).into_owned());
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+        if file.encrypted() {
+            return Err(ZipError::UnsupportedArchive(
+                "Encrypted files cannot be merged",
+            ));
+        }
+        if mode == MergeMode::Raw {
+            zip.raw_copy_file(file)?;
+            continue;
+        }
+        let mut options = FileOptions::default()
+            .compression_method(file.compression())
+            .last_modified_time(file.last_modified())
+            .large_file(file.size() > u32::MAX as u64);
+        if let Some(mode) = file.unix_mode() {
+            options = options.unix_permissions(mode);
+        }
+        let name = file.name().to_owned();
+        if file.is_dir() {
+            zip.add_directory(name, options)?;
+            continue;
+        }
+        drop(file);
+        zip.start_file(name, options)?;
+        io::copy(&mut archive.by_index(i)?, &mut zip)?;
+    }
+    zip.finish()
+}
@@ -0,0 +1,165 @@
+//! Index of the files of an archive by their names
+
+use crate::types::{SharedStr, ZipFileData};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+
+/// A hash table of the indices of the files of an archive, looked up by the names of the files
+///
+/// Unlike a map from the names to the indices, this keeps no copy of the names, which are looked
+/// up in the files instead. Of files with the same name, only the last one is in the index.
+#[derive(Clone, Debug)]
+pub struct NameIndex {
+    /// The indices plus one, or 0 for the free slots, in a table with a power of two slots that
+    /// are at most half used
+    ///
+    /// An archive can't have so many files in memory that their indices don't fit in a `u32`.
+    slots: Vec<u32>,
+    hasher: RandomState,
+}
+
+impl NameIndex {
+    /// Index `files`, after storing their names one after the other in shared buffers.
+    pub fn new(files: &mut [ZipFileData]) -> NameIndex {
+        share_names(files);
+        let mut index = NameIndex {
+            slots: vec![0; (files.len() * 2).next_power_of_two()],
+            hasher: RandomState::new(),
+        };
+        for i in 0..files.len() {
+            let slot = index.slot(files, &files[i].file_name);
+            index.slots[slot] = i as u32 + 1;
+        }
+        index
+    }
+
+    /// The slot of `name`, or the free slot where it would go
+    fn slot(&self, files: &[ZipFileData], name: &str) -> usize {
+        let mut hasher = self.hasher.build_hasher();
+        hasher.write(name.as_bytes());
+        let mask = self.slots.len() - 1;
+        let mut slot = hasher.finish() as usize & mask;
+        loop {
+            match self.slots[slot] {
+                0 => return slot,
+                i if files[i as usize - 1].file_name == *name => return slot,
+                _ => slot = (slot + 1) & mask,
+            }
+        }
+    }
+
+    /// The index of the last of `files` that is named `name`
+    ///
+    /// `files` must be the files that were indexed.
+    pub fn get(&self, files: &[ZipFileData], name: &str) -> Option<usize> {
+        match self.slots.get(self.slot(files, name))? {
+            0 => None,
+            &i => Some(i as usize - 1),
+        }
+    }
+
+    /// The names of `files`, without duplicates
+    pub fn names<'a>(&'a self, files: &'a [ZipFileData]) -> impl Iterator<Item = &'a str> {
+        files
+            .iter()
+            .enumerate()
+            .filter(move |&(i, file)| self.get(files, &file.file_name) == Some(i))
+            .map(|(_, file)| file.file_name.as_str())
+    }
+}
+
+/// Store the names of `files` one after the other in buffers of less than 4 GiB each
+fn share_names(mut files: &mut [ZipFileData]) {
+    while !files.is_empty() {
+        let mut len = 0;
+        let count = files
+            .iter()
+            .take_while(|file| {
+                len += file.file_name.len();
+                len < u32::MAX as usize
+            })
+            .count();
+        if count == 0 {
+            // The name is too long to be shared, and keeps a buffer of its own
+            files = &mut files[1..];
+            continue;
+        }
+        let (shared, rest) = files.split_at_mut(count);
+        let mut buffer = String::new();
+        for file in shared.iter() {
+            buffer.push_str(&file.file_name);
+        }
+        let buffer: Arc<str> = buffer.into();
+        let mut start = 0;
+        for file in shared {
+            let len = file.file_name.len();
+            file.file_name = SharedStr::new(&buffer, start, len);
+            start += len;
+        }
+        files = rest;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{DateTime, NameCache, System};
+
+    fn file(name: &str) -> ZipFileData {
+        ZipFileData {
+            system: System::Unix,
+            version_made_by: 0,
+            version_to_extract: 0,
+            encrypted: false,
+            compression_method: crate::compression::CompressionMethod::Stored,
+            last_modified_time: DateTime::default(),
+            crc32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            file_name: name.into(),
+            file_name_raw: Vec::new(),
+            file_comment: String::new(),
+            header_start: 0,
+            disk_number: 0,
+            data_start: 0,
+            central_header_start: 0,
+            external_attributes: 0,
+            aes_mode: None,
+            strong_encryption: None,
+            deflate_dictionary: None,
+            large_file: false,
+            using_data_descriptor: false,
+            name_cache: NameCache::default(),
+            central_header: None,
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let mut files: Vec<_> = ["b", "a", "c", "a", "b"]
+            .iter()
+            .map(|name| file(name))
+            .collect();
+        let index = NameIndex::new(&mut files);
+        assert_eq!(index.get(&files, "a"), Some(3));
+        assert_eq!(index.get(&files, "b"), Some(4));
+        assert_eq!(index.get(&files, "c"), Some(2));
+        assert_eq!(index.get(&files, "d"), None);
+        assert_eq!(index.names(&files).collect::<Vec<_>>(), ["c", "a", "b"]);
+        assert_eq!(NameIndex::new(&mut []).get(&[], "a"), None);
+    }
+
+    #[test]
+    fn shared_names() {
+        let mut files: Vec<_> = ["one", "two", "three"]
+            .iter()
+            .map(|name| file(name))
+            .collect();
+        NameIndex::new(&mut files);
+        let start = files[0].file_name.as_ptr() as usize;
+        assert_eq!(files[1].file_name.as_ptr() as usize, start + 3);
+        assert_eq!(files[2].file_name.as_ptr() as usize, start + 6);
+        assert_eq!(files[2].file_name, "three");
+    }
+}
@@ -0,0 +1,18 @@
+//! All options for reading, extracting and writing archives in one place
+//!
+//! The options are plain values with builder methods, whose defaults are those of the methods
+//! that don't take options:
+//!
+//! * [`Config`] for opening an archive with [`ZipArchive::new_with_config`]
+//! * [`ExtractOptions`] for extracting an archive with [`ZipArchive::extract_with_options`]
+//! * [`ArchiveOptions`] for the settings of a whole archive that is written, set with
+//!   [`ZipWriter::set_options`]
+//! * [`FileOptions`] and [`StoredOptions`] for every file that is written, and
+//!   [`DefaultOptions`] for their defaults
+//!
+//! [`ZipArchive::new_with_config`]: crate::read::ZipArchive::new_with_config
+//! [`ZipArchive::extract_with_options`]: crate::read::ZipArchive::extract_with_options
+//! [`ZipWriter::set_options`]: crate::write::ZipWriter::set_options
+
+pub use crate::read::{Config, ExtractOptions, OverwritePolicy, StoredSizePolicy};
+pub use crate::write::{ArchiveOptions, DefaultOptions, FileOptions, NameOptions, StoredOptions};
@@ -0,0 +1,114 @@
+//! Reading several archives as one, with later archives shadowing earlier ones
+//!
+//! This is the usual layout of game mods and layered asset packs: a base archive is combined with
+//! archives that add entries or replace entries of the same name.
+
+use crate::read::{ZipArchive, ZipFile};
+use crate::result::{InvalidPassword, ZipError, ZipResult};
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+
+/// A stack of archives that is read as a single archive
+///
+/// Each entry is looked up in the topmost layer containing an entry of that name. Names are
+/// compared exactly as they are stored, so `dir/file` and `dir\file` are different entries.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use zip::overlay::OverlayArchive;
+/// use zip::ZipArchive;
+///
+/// # fn main() -> zip::result::ZipResult<()> {
+/// let mut overlay = OverlayArchive::new();
+/// overlay.push_layer(ZipArchive::new(File::open("base.zip")?)?);
+/// overlay.push_layer(ZipArchive::new(File::open("mod.zip")?)?);
+///
+/// // Read from mod.zip if it contains the entry, otherwise from base.zip
+/// let file = overlay.by_name("textures/grass.png")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct OverlayArchive<R: Read + Seek> {
+    layers: Vec<ZipArchive<R>>,
+    names_map: HashMap<String, usize>,
+}
+
+impl<R: Read + Seek> OverlayArchive<R> {
+    /// Create an overlay without any layers.
+    pub fn new() -> OverlayArchive<R> {
+        OverlayArchive {
+            layers: Vec::new(),
+            names_map: HashMap::new(),
+        }
+    }
+
+    /// Add an archive on top of the existing layers.
+    ///
+    /// Its entries shadow the entries of the same name in all previously added archives.
+    pub fn push_layer(&mut self, archive: ZipArchive<R>) {
+        let layer = self.layers.len();
+        for name in archive.file_names() {
+            self.names_map.insert(name.to_owned(), layer);
+        }
+        self.layers.push(archive);
+    }
+
+    /// The archives of the overlay, from the bottom to the top layer.
+    pub fn layers(&self) -> &[ZipArchive<R>] {
+        &self.layers
+    }
+
+    /// Unwrap the archives of the overlay, from the bottom to the top layer.
+    pub fn into_layers(self) -> Vec<ZipArchive<R>> {
+        self.layers
+    }
+
+    /// Number of distinct file names in all layers.
+    pub fn len(&self) -> usize {
+        self.names_map.len()
+    }
+
+    /// Whether none of the layers contain any files
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over all the distinct file and directory names in all layers.
+    pub fn file_names(&self) -> impl Iterator<Item = &str> {
+        self.names_map.keys().map(|s| s.as_str())
+    }
+
+    /// The index of the layer that provides the entry `name`, if any.
+    pub fn layer_of(&self, name: &str) -> Option<usize> {
+        self.names_map.get(name).copied()
+    }
+
+    /// Search for a file entry by name in the topmost layer containing it, decrypt with given
+    /// password
+    pub fn by_name_decrypt<'a>(
+        &'a mut self,
+        name: &str,
+        password: &[u8],
+    ) -> ZipResult<Result<ZipFile<'a>, InvalidPassword>> {
+        self.layer_mut(name)?.by_name_decrypt(name, password)
+    }
+
+    /// Search for a file entry by name in the topmost layer containing it
+    pub fn by_name<'a>(&'a mut self, name: &str) -> ZipResult<ZipFile<'a>> {
+        self.layer_mut(name)?.by_name(name)
+    }
+
+    fn layer_mut(&mut self, name: &str) -> ZipResult<&mut ZipArchive<R>> {
+        match self.names_map.get(name) {
+            Some(layer) => Ok(&mut self.layers[*layer]),
+            None => Err(ZipError::FileNotFound),
+        }
+    }
+}
+
+impl<R: Read + Seek> Default for OverlayArchive<R> {
+    fn default() -> OverlayArchive<R> {
+        OverlayArchive::new()
+    }
+}
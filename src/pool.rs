@@ -0,0 +1,411 @@
+//! Buffers and compression states that are reused by the entries of an archive
+//!
+//! Reading or writing an entry needs a buffer for the compressed data, and a compression state
+//! for Deflate, unless it is done by libdeflate, which keeps no state. Allocating them for every
+//! entry is slow for archives with many small entries, so a [`BufferPool`] keeps them when an
+//! entry is done, for the next one. Clones of a pool share it.
+#![cfg_attr(
+    not(any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib",
+        feature = "bzip2"
+    )),
+    allow(dead_code)
+)]
+
+use std::fmt;
+use std::io::{self, BufRead, Read};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// The size of the buffers that compressed data is read into, unless another one is configured
+pub const DEFAULT_BUFFER_SIZE: usize = 32 * 1024;
+
+/// The most buffers, and compression states of each kind, that a pool keeps
+const MAX_POOLED: usize = 16;
+
+/// A pool of buffers of the same size and of compression states
+#[derive(Clone)]
+pub struct BufferPool {
+    buffer_size: usize,
+    state: Arc<Mutex<PoolState>>,
+}
+
+#[derive(Default)]
+struct PoolState {
+    buffers: Vec<Vec<u8>>,
+    #[cfg(all(
+        any(
+            feature = "deflate",
+            feature = "deflate-miniz",
+            feature = "deflate-zlib"
+        ),
+        not(feature = "deflate-libdeflate")
+    ))]
+    decompressors: Vec<flate2::Decompress>,
+    /// Compression states with their compression level
+    #[cfg(all(
+        any(
+            feature = "deflate",
+            feature = "deflate-miniz",
+            feature = "deflate-zlib"
+        ),
+        not(feature = "deflate-libdeflate")
+    ))]
+    compressors: Vec<(u32, flate2::Compress)>,
+}
+
+impl BufferPool {
+    /// A pool of buffers of `buffer_size` bytes
+    pub fn new(buffer_size: usize) -> BufferPool {
+        BufferPool {
+            buffer_size: buffer_size.max(1),
+            state: Arc::default(),
+        }
+    }
+
+    /// The pool state, unless a thread panicked while holding it, in which case nothing is reused
+    fn lock(&self) -> Option<MutexGuard<'_, PoolState>> {
+        self.state.lock().ok()
+    }
+
+    /// Take a buffer out of the pool, or allocate one.
+    pub fn buffer(&self) -> PooledBuffer {
+        let mut buffer = self
+            .lock()
+            .and_then(|mut state| state.buffers.pop())
+            .unwrap_or_default();
+        buffer.resize(self.buffer_size, 0);
+        PooledBuffer {
+            buffer,
+            pool: self.clone(),
+        }
+    }
+
+    /// Take a decompression state for a raw deflate stream out of the pool, or create one.
+    #[cfg(all(
+        any(
+            feature = "deflate",
+            feature = "deflate-miniz",
+            feature = "deflate-zlib"
+        ),
+        not(feature = "deflate-libdeflate")
+    ))]
+    pub fn decompress(&self) -> PooledDecompress {
+        let state = match self.lock().and_then(|mut state| state.decompressors.pop()) {
+            Some(mut state) => {
+                state.reset(false);
+                state
+            }
+            None => flate2::Decompress::new(false),
+        };
+        PooledDecompress {
+            state: Some(state),
+            pool: self.clone(),
+        }
+    }
+
+    /// Take a compression state for a raw deflate stream of the given level out of the pool, or
+    /// create one.
+    #[cfg(all(
+        any(
+            feature = "deflate",
+            feature = "deflate-miniz",
+            feature = "deflate-zlib"
+        ),
+        not(feature = "deflate-libdeflate")
+    ))]
+    pub fn compress(&self, level: flate2::Compression) -> PooledCompress {
+        let level = level.level();
+        let pooled = self.lock().and_then(|mut state| {
+            let index = state.compressors.iter().position(|(l, _)| *l == level)?;
+            Some(state.compressors.swap_remove(index).1)
+        });
+        let state = match pooled {
+            Some(mut state) => {
+                state.reset();
+                state
+            }
+            None => flate2::Compress::new(flate2::Compression::new(level), false),
+        };
+        PooledCompress {
+            state: Some(state),
+            level,
+            pool: self.clone(),
+        }
+    }
+
+    /// The number of decompression and compression states in the pool
+    #[cfg(all(
+        test,
+        any(
+            feature = "deflate",
+            feature = "deflate-miniz",
+            feature = "deflate-zlib"
+        ),
+        not(feature = "deflate-libdeflate")
+    ))]
+    pub fn pooled_states(&self) -> (usize, usize) {
+        let state = self.lock().expect("The pool is not poisoned");
+        (state.decompressors.len(), state.compressors.len())
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> BufferPool {
+        BufferPool::new(DEFAULT_BUFFER_SIZE)
+    }
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("buffer_size", &self.buffer_size)
+            .finish()
+    }
+}
+
+/// A buffer that is given back to its pool when it is dropped
+pub struct PooledBuffer {
+    buffer: Vec<u8>,
+    pool: BufferPool,
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        if let Some(mut state) = self.pool.lock() {
+            if state.buffers.len() < MAX_POOLED {
+                state.buffers.push(std::mem::take(&mut self.buffer));
+            }
+        }
+    }
+}
+
+/// A decompression state that is given back to its pool when it is dropped
+#[cfg(all(
+    any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib"
+    ),
+    not(feature = "deflate-libdeflate")
+))]
+pub struct PooledDecompress {
+    state: Option<flate2::Decompress>,
+    pool: BufferPool,
+}
+
+#[cfg(all(
+    any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib"
+    ),
+    not(feature = "deflate-libdeflate")
+))]
+impl Deref for PooledDecompress {
+    type Target = flate2::Decompress;
+
+    fn deref(&self) -> &flate2::Decompress {
+        self.state
+            .as_ref()
+            .expect("The state is only taken when dropped")
+    }
+}
+
+#[cfg(all(
+    any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib"
+    ),
+    not(feature = "deflate-libdeflate")
+))]
+impl DerefMut for PooledDecompress {
+    fn deref_mut(&mut self) -> &mut flate2::Decompress {
+        self.state
+            .as_mut()
+            .expect("The state is only taken when dropped")
+    }
+}
+
+#[cfg(all(
+    any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib"
+    ),
+    not(feature = "deflate-libdeflate")
+))]
+impl Drop for PooledDecompress {
+    fn drop(&mut self) {
+        if let Some(mut state) = self.pool.lock() {
+            if state.decompressors.len() < MAX_POOLED {
+                state.decompressors.extend(self.state.take());
+            }
+        }
+    }
+}
+
+/// A compression state that is given back to its pool when it is dropped
+#[cfg(all(
+    any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib"
+    ),
+    not(feature = "deflate-libdeflate")
+))]
+pub struct PooledCompress {
+    state: Option<flate2::Compress>,
+    level: u32,
+    pool: BufferPool,
+}
+
+#[cfg(all(
+    any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib"
+    ),
+    not(feature = "deflate-libdeflate")
+))]
+impl Deref for PooledCompress {
+    type Target = flate2::Compress;
+
+    fn deref(&self) -> &flate2::Compress {
+        self.state
+            .as_ref()
+            .expect("The state is only taken when dropped")
+    }
+}
+
+#[cfg(all(
+    any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib"
+    ),
+    not(feature = "deflate-libdeflate")
+))]
+impl DerefMut for PooledCompress {
+    fn deref_mut(&mut self) -> &mut flate2::Compress {
+        self.state
+            .as_mut()
+            .expect("The state is only taken when dropped")
+    }
+}
+
+#[cfg(all(
+    any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib"
+    ),
+    not(feature = "deflate-libdeflate")
+))]
+impl Drop for PooledCompress {
+    fn drop(&mut self) {
+        if let Some(mut state) = self.pool.lock() {
+            if state.compressors.len() < MAX_POOLED {
+                let level = self.level;
+                state
+                    .compressors
+                    .extend(self.state.take().map(|compress| (level, compress)));
+            }
+        }
+    }
+}
+
+/// A buffered reader with a buffer from a pool
+pub struct PooledReader<R> {
+    inner: R,
+    buffer: PooledBuffer,
+    start: usize,
+    end: usize,
+}
+
+impl<R: Read> PooledReader<R> {
+    pub fn new(inner: R, pool: &BufferPool) -> PooledReader<R> {
+        PooledReader {
+            inner,
+            buffer: pool.buffer(),
+            start: 0,
+            end: 0,
+        }
+    }
+
+    /// Unwrap the inner reader, dropping the data that is buffered.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for PooledReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Large reads bypass the buffer when it is empty
+        if self.start == self.end && buf.len() >= self.buffer.len() {
+            return self.inner.read(buf);
+        }
+        let count = {
+            let available = self.fill_buf()?;
+            let count = available.len().min(buf.len());
+            buf[..count].copy_from_slice(&available[..count]);
+            count
+        };
+        self.consume(count);
+        Ok(count)
+    }
+}
+
+impl<R: Read> BufRead for PooledReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.start == self.end {
+            self.end = self.inner.read(&mut self.buffer)?;
+            self.start = 0;
+        }
+        Ok(&self.buffer[self.start..self.end])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.start = (self.start + amt).min(self.end);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reuse() {
+        let pool = BufferPool::new(16);
+        let mut buffer = pool.buffer();
+        assert_eq!(buffer.len(), 16);
+        buffer[0] = 1;
+        let pointer = buffer.as_ptr();
+        drop(buffer);
+        let buffer = pool.buffer();
+        assert_eq!(buffer.as_ptr(), pointer);
+        assert_eq!(buffer.len(), 16);
+
+        let mut reader = PooledReader::new(&b"some data that does not fit"[..], &pool);
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, b"some data that does not fit");
+    }
+}
@@ -1,18 +1,45 @@
 //! Types for reading ZIP archives
 
+#[cfg(feature = "aes-crypto")]
+use crate::aes::{AesReader, AesReaderValid};
+use crate::codec::{Codecs, Decompressor};
 use crate::compression::CompressionMethod;
-use crate::crc32::Crc32Reader;
+use crate::crc32::{self, Crc32Reader};
+use crate::hook::{Hook, ReadHook};
+use crate::limit::{Deadline, LimitedReader};
+use crate::multidisk::{DiskProvider, MultiDiskReader};
+use crate::name_index::NameIndex;
+#[cfg(any(
+    feature = "deflate",
+    feature = "deflate-miniz",
+    feature = "deflate-zlib",
+    feature = "bzip2"
+))]
+use crate::pool::PooledReader;
+use crate::pool::{BufferPool, DEFAULT_BUFFER_SIZE};
 use crate::result::{InvalidPassword, ZipError, ZipResult};
+use crate::signature::{self, Verifier};
+#[cfg(feature = "sniff")]
+use crate::sniff::{self, ContentType, FlaggedFile, ScanFlag, SCAN_LENGTH, SNIFF_LENGTH};
 use crate::spec;
+use crate::write::{is_absolute_name, is_separator};
 use crate::zipcrypto::ZipCryptoReader;
 use crate::zipcrypto::ZipCryptoReaderValid;
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::io::{self, prelude::*};
-use std::path::{Component, Path};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::cp437::FromCp437;
-use crate::types::{DateTime, System, ZipFileData};
+use crate::types::{
+    AesMode, AesVendorVersion, DateTime, EncryptionMethod, ExtensibleDataBlock, NameCache,
+    StrongEncryptionAlgorithm, System, ZipFileData, AES_ITERATION_COUNT,
+};
 use byteorder::{LittleEndian, ReadBytesExt};
 
 #[cfg(any(
@@ -20,21 +47,38 @@ use byteorder::{LittleEndian, ReadBytesExt};
     feature = "deflate-miniz",
     feature = "deflate-zlib"
 ))]
-use flate2::read::DeflateDecoder;
+use crate::deflate;
+#[cfg(all(
+    feature = "async",
+    any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib"
+    )
+))]
+use crate::descriptor::AsyncDescriptorReader;
+#[cfg(any(
+    feature = "deflate",
+    feature = "deflate-miniz",
+    feature = "deflate-zlib"
+))]
+use crate::descriptor::DescriptorReader;
 
 #[cfg(feature = "bzip2")]
-use bzip2::read::BzDecoder;
+use bzip2::bufread::BzDecoder;
 
 #[cfg(feature = "async")]
-use crate::async_util::CompatExt;
+use crate::async_util::{read_u16_le, read_u32_le};
 #[cfg(feature = "async")]
 use async_compression::futures::bufread::{
     BzDecoder as AsyncBzDecoder, DeflateDecoder as AsyncDeflateDecoder,
 };
 #[cfg(feature = "async")]
 use futures::{
-    io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, BufReader as AsyncBufReader},
-    FutureExt,
+    io::{
+        AsyncBufRead, AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, BufReader as AsyncBufReader,
+    },
+    FutureExt, Stream,
 };
 #[cfg(feature = "async")]
 use pin_project::pin_project;
@@ -42,12 +86,12 @@ use pin_project::pin_project;
 use std::pin::Pin;
 #[cfg(feature = "async")]
 use std::task::Poll;
-#[cfg(feature = "async")]
-use tokio::io::AsyncReadExt as TokioAsyncReadExt;
 
 mod ffi {
+    pub const S_IFMT: u32 = 0o0170000;
     pub const S_IFDIR: u32 = 0o0040000;
     pub const S_IFREG: u32 = 0o0100000;
+    pub const S_IFLNK: u32 = 0o0120000;
 }
 
 /// ZIP archive reader
@@ -70,11 +114,44 @@ mod ffi {
 pub struct ZipArchive<R: Read + io::Seek> {
     reader: R,
     files: Vec<ZipFileData>,
-    names_map: HashMap<String, usize>,
+    claimed_len: usize,
+    names: NameIndex,
     offset: u64,
+    directory_start: u64,
     comment: Vec<u8>,
+    zip64_extensible_data: Option<Vec<u8>>,
+    zip64_end_lookup: Option<Zip64EndLookup>,
+    end_record_mismatch: bool,
+    digital_signature: Option<Vec<u8>>,
+    config: Config,
+    decompressors: Codecs<dyn Decompressor>,
+    #[cfg(feature = "deflate")]
+    dictionaries: deflate::Dictionaries,
+    aes_iteration_count: u32,
+    read_hook: Hook<dyn ReadHook>,
+    crc_retry: Hook<Reconnect<R>>,
+    buffers: BufferPool,
 }
 
+/// The archive offset, central directory start and number of files of an archive, the extensible
+/// data sector of its ZIP64 central directory end record with how that was found, and whether
+/// the two central directory end records disagree
+type DirectoryCounts = (
+    u64,
+    u64,
+    usize,
+    Option<Vec<u8>>,
+    Option<Zip64EndLookup>,
+    bool,
+);
+
+/// The files, the start of the central directory, the comment and the ZIP64 extensible data
+/// sector of an archive, see [`ZipArchive::into_append_parts`]
+type AppendParts = (Vec<ZipFileData>, u64, Vec<u8>, Option<Vec<u8>>);
+
+/// Prepares the reader of an archive for reading an entry again, see [`ZipArchive::set_crc_retry`]
+type Reconnect<R> = dyn Fn(&mut R) -> io::Result<()> + Send + Sync;
+
 /// Async ZIP archive reader
 #[cfg(feature = "async")]
 #[pin_project(project=AsyncZipArchiveProject)]
@@ -83,14 +160,288 @@ pub struct AsyncZipArchive<R: AsyncRead + AsyncSeek + Send + Sync + Unpin> {
     #[pin]
     reader: R,
     files: Vec<ZipFileData>,
-    names_map: HashMap<String, usize>,
+    claimed_len: usize,
+    names: NameIndex,
     offset: u64,
+    directory_start: u64,
     comment: Vec<u8>,
+    zip64_extensible_data: Option<Vec<u8>>,
+    zip64_end_lookup: Option<Zip64EndLookup>,
+    end_record_mismatch: bool,
+    digital_signature: Option<Vec<u8>>,
+    config: Config,
+    aes_iteration_count: u32,
+    cancellation: Cancellation,
+}
+
+/// Flag that cancels the operations of an [`AsyncZipArchive`], see
+/// [`AsyncZipArchive::set_cancellation`]
+///
+/// Clones share the flag, so that it can be set from another task or thread.
+#[cfg(feature = "async")]
+#[derive(Clone, Debug, Default)]
+pub struct Cancellation(Arc<AtomicBool>);
+
+#[cfg(feature = "async")]
+impl Cancellation {
+    /// Create a flag that is not set
+    pub fn new() -> Cancellation {
+        Cancellation::default()
+    }
+
+    /// Set the flag, so that the operations that check it fail with [`ZipError::Cancelled`]
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the flag is set
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Fail with [`ZipError::Cancelled`] if the flag is set.
+    fn check(&self) -> ZipResult<()> {
+        if self.is_cancelled() {
+            return Err(ZipError::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+/// Options for opening an archive with [`ZipArchive::new_with_config`]
+///
+/// The central directory end record is searched backwards from the end of the reader. By default
+/// it may be up to 65557 bytes from the end, the size of the record with the longest possible
+/// comment, and it may be followed by other data in the remaining space. The last record whose
+/// comment ends at the end of the reader is preferred over records followed by other data, as a
+/// comment may itself contain something that looks like a record.
+#[derive(Copy, Clone, Debug)]
+pub struct Config {
+    max_search_distance: u64,
+    trailing_data: bool,
+    stored_sizes: StoredSizePolicy,
+    time_limit: Option<Duration>,
+    record_count: RecordCountPolicy,
+    end_record_precedence: EndRecordPrecedence,
+    buffer_size: usize,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            max_search_distance: spec::MAX_END_DISTANCE,
+            trailing_data: true,
+            stored_sizes: StoredSizePolicy::Error,
+            time_limit: None,
+            record_count: RecordCountPolicy::Claimed,
+            end_record_precedence: EndRecordPrecedence::Zip64,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+        }
+    }
+}
+
+impl Config {
+    /// Set how many bytes before the end of the reader the central directory end record may start
+    ///
+    /// A larger distance allows reading an archive that is embedded in another file, followed by
+    /// more of that file's data.
+    pub fn max_search_distance(mut self, distance: u64) -> Config {
+        self.max_search_distance = distance;
+        self
+    }
+
+    /// Set whether the central directory end record may be followed by other data
+    ///
+    /// This is lenient by default. If strict, the comment of the record must end at the end of the
+    /// reader.
+    pub fn trailing_data(mut self, trailing_data: bool) -> Config {
+        self.trailing_data = trailing_data;
+        self
+    }
+
+    /// Set how to read stored files whose compressed and uncompressed sizes disagree
+    ///
+    /// The sizes of a file that is stored without compression must be equal, but some writers get
+    /// one of them wrong. Such files can't be read by default, see [`StoredSizePolicy::Error`].
+    pub fn stored_sizes(mut self, policy: StoredSizePolicy) -> Config {
+        self.stored_sizes = policy;
+        self
+    }
+
+    /// Set how long opening the archive, and each extraction or validation of its files, may take
+    ///
+    /// Once the limit is exceeded, the operation fails with [`ZipError::TimeLimitExceeded`]. This
+    /// bounds the time spent on an untrusted archive whose entries are many or slow to decompress,
+    /// which size limits alone don't catch. There is no limit by default.
+    pub fn time_limit(mut self, limit: Option<Duration>) -> Config {
+        self.time_limit = limit;
+        self
+    }
+
+    /// Set how many central directory records to read
+    ///
+    /// Some writers get the number of files in the central directory end record wrong. By default
+    /// as many records are read as it claims, see [`RecordCountPolicy::Claimed`]. Both numbers
+    /// are available from [`ZipArchive::claimed_len`] and [`ZipArchive::len`].
+    pub fn record_count(mut self, policy: RecordCountPolicy) -> Config {
+        self.record_count = policy;
+        self
+    }
+
+    /// Set which central directory end record to trust when the ZIP64 one disagrees with the
+    /// other
+    ///
+    /// A field of the central directory end record that is not saturated should be equal to the
+    /// one of the ZIP64 record, but writers exist that get either of them wrong. By default the
+    /// ZIP64 record is trusted, see [`EndRecordPrecedence::Zip64`]. Either way, the disagreement is
+    /// reported as a [`ValidationIssue::EndRecordMismatch`].
+    pub fn end_record_precedence(mut self, precedence: EndRecordPrecedence) -> Config {
+        self.end_record_precedence = precedence;
+        self
+    }
+
+    /// Set the size of the buffers that compressed data is read into, 32 KiB by default
+    ///
+    /// The buffers, and the states of the decompressors, are reused from one entry to the next
+    /// instead of being allocated for every entry. Clones of an archive share them.
+    pub fn buffer_size(mut self, size: usize) -> Config {
+        self.buffer_size = size;
+        self
+    }
+}
+
+/// How to read a stored file whose compressed and uncompressed sizes disagree, see
+/// [`Config::stored_sizes`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StoredSizePolicy {
+    /// Read as many bytes as the uncompressed size
+    TrustUncompressed,
+    /// Read as many bytes as the compressed size
+    TrustCompressed,
+    /// Fail to read the file, as its data is not as long as both sizes
+    Error,
+}
+
+/// How many central directory records to read, see [`Config::record_count`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RecordCountPolicy {
+    /// Read as many records as the central directory end record claims, ignoring any that follow
+    /// and failing if there are fewer
+    Claimed,
+    /// Read all the records that are found, however many are claimed, with a
+    /// [`ValidationIssue::RecordCountMismatch`] if they differ
+    Found,
+}
+
+/// Which central directory end record to trust when they disagree, see
+/// [`Config::end_record_precedence`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EndRecordPrecedence {
+    /// Read the central directory the ZIP64 central directory end record describes
+    Zip64,
+    /// Read the central directory the central directory end record describes, except for the
+    /// fields it saturates
+    Classic,
+}
+
+/// How the ZIP64 central directory end record of an archive was found, see
+/// [`ZipArchive::zip64_end_lookup`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Zip64EndLookup {
+    /// At the offset its locator records, or after it if data was prepended to the archive
+    Locator,
+    /// By searching backwards from its locator, as the recorded offset was wrong
+    Scan,
+}
+
+/// A problem with an archive that is found without reading its files, see
+/// [`ZipArchive::validate`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ValidationIssue {
+    /// The compressed and uncompressed sizes of a stored file disagree, see
+    /// [`Config::stored_sizes`]
+    StoredSizeMismatch {
+        /// The index of the file
+        index: usize,
+        /// The compressed size of the file
+        compressed_size: u64,
+        /// The uncompressed size of the file
+        uncompressed_size: u64,
+    },
+    /// The data of a file does not match its CRC-32, which `ZipArchive::validate_parallel` checks
+    ChecksumMismatch {
+        /// The index of the file
+        index: usize,
+    },
+    /// The number of central directory records differs from the number the central directory end
+    /// record claims, see [`Config::record_count`]
+    RecordCountMismatch {
+        /// The number of records the central directory end record claims
+        claimed: usize,
+        /// The number of records that were read
+        found: usize,
+    },
+    /// The central directory end record and the ZIP64 one disagree on the number of files, the
+    /// size or the offset of the central directory, see [`Config::end_record_precedence`]
+    EndRecordMismatch,
+}
+
+fn has_stored_size_mismatch(data: &ZipFileData) -> bool {
+    data.compression_method == CompressionMethod::Stored
+        && !data.encrypted
+        && data.compressed_size != data.uncompressed_size
+}
+
+/// The number of bytes to read of the file `data` by the `policy`, if its sizes disagree and one
+/// of them is trusted
+fn stored_size(policy: StoredSizePolicy, data: &ZipFileData) -> Option<u64> {
+    if !has_stored_size_mismatch(data) {
+        return None;
+    }
+    match policy {
+        StoredSizePolicy::TrustUncompressed => Some(data.uncompressed_size),
+        StoredSizePolicy::TrustCompressed => Some(data.compressed_size),
+        StoredSizePolicy::Error => None,
+    }
+}
+
+/// The issues of an archive with the entries `files`
+fn validation_issues(
+    files: &[ZipFileData],
+    claimed_len: usize,
+    end_record_mismatch: bool,
+) -> Vec<ValidationIssue> {
+    let mut issues: Vec<_> = files
+        .iter()
+        .enumerate()
+        .filter(|(_, data)| has_stored_size_mismatch(data))
+        .map(|(index, data)| ValidationIssue::StoredSizeMismatch {
+            index,
+            compressed_size: data.compressed_size,
+            uncompressed_size: data.uncompressed_size,
+        })
+        .collect();
+    if files.len() != claimed_len {
+        issues.push(ValidationIssue::RecordCountMismatch {
+            claimed: claimed_len,
+            found: files.len(),
+        });
+    }
+    if end_record_mismatch {
+        issues.push(ValidationIssue::EndRecordMismatch);
+    }
+    issues
 }
 
 enum CryptoReader<'a> {
     Plaintext(io::Take<&'a mut dyn Read>),
     ZipCrypto(ZipCryptoReaderValid<io::Take<&'a mut dyn Read>>),
+    #[cfg(feature = "aes-crypto")]
+    Aes {
+        reader: Box<AesReaderValid<io::Take<&'a mut dyn Read>>>,
+        vendor_version: AesVendorVersion,
+    },
 }
 
 impl<'a> Read for CryptoReader<'a> {
@@ -98,6 +449,8 @@ impl<'a> Read for CryptoReader<'a> {
         match self {
             CryptoReader::Plaintext(r) => r.read(buf),
             CryptoReader::ZipCrypto(r) => r.read(buf),
+            #[cfg(feature = "aes-crypto")]
+            CryptoReader::Aes { reader: r, .. } => r.read(buf),
         }
     }
 }
@@ -106,7 +459,14 @@ impl<'a> Read for CryptoReader<'a> {
 #[pin_project(project=AsyncCryptoReaderProject)]
 enum AsyncCryptoReader<'a> {
     Plaintext(#[pin] futures::io::Take<Pin<&'a mut (dyn AsyncRead + Send + Sync)>>),
-    ZipCrypto(#[pin] ZipCryptoReaderValid<futures::io::Take<Pin<&'a mut (dyn AsyncRead + Send + Sync)>>>),
+    ZipCrypto(
+        #[pin] ZipCryptoReaderValid<futures::io::Take<Pin<&'a mut (dyn AsyncRead + Send + Sync)>>>,
+    ),
+    #[cfg(feature = "aes-crypto")]
+    Aes {
+        reader: Box<AesReaderValid<futures::io::Take<Pin<&'a mut (dyn AsyncRead + Send + Sync)>>>>,
+        vendor_version: AesVendorVersion,
+    },
 }
 
 #[cfg(feature = "async")]
@@ -119,6 +479,10 @@ impl<'a> AsyncRead for AsyncCryptoReader<'a> {
         match self.project() {
             AsyncCryptoReaderProject::Plaintext(r) => r.poll_read(cx, buf),
             AsyncCryptoReaderProject::ZipCrypto(r) => r.poll_read(cx, buf),
+            #[cfg(feature = "aes-crypto")]
+            AsyncCryptoReaderProject::Aes { reader: r, .. } => {
+                Pin::new(&mut **r).poll_read(cx, buf)
+            }
         }
     }
 }
@@ -129,8 +493,24 @@ impl<'a> CryptoReader<'a> {
         match self {
             CryptoReader::Plaintext(r) => r,
             CryptoReader::ZipCrypto(r) => r.into_inner(),
+            #[cfg(feature = "aes-crypto")]
+            CryptoReader::Aes { reader: r, .. } => r.into_inner(),
         }
     }
+
+    /// Returns `true` if the data is encrypted using AE-2, which stores a CRC-32 of 0.
+    fn is_ae2_encrypted(&self) -> bool {
+        #[cfg(feature = "aes-crypto")]
+        return matches!(
+            self,
+            CryptoReader::Aes {
+                vendor_version: AesVendorVersion::Ae2,
+                ..
+            }
+        );
+        #[cfg(not(feature = "aes-crypto"))]
+        false
+    }
 }
 
 #[cfg(feature = "async")]
@@ -140,6 +520,56 @@ impl<'a> AsyncCryptoReader<'a> {
         match self {
             Self::Plaintext(r) => r,
             Self::ZipCrypto(r) => r.into_inner(),
+            #[cfg(feature = "aes-crypto")]
+            Self::Aes { reader: r, .. } => r.into_inner(),
+        }
+    }
+
+    /// See `CryptoReader::is_ae2_encrypted`.
+    fn is_ae2_encrypted(&self) -> bool {
+        #[cfg(feature = "aes-crypto")]
+        return matches!(
+            self,
+            AsyncCryptoReader::Aes {
+                vendor_version: AesVendorVersion::Ae2,
+                ..
+            }
+        );
+        #[cfg(not(feature = "aes-crypto"))]
+        false
+    }
+}
+
+/// A reader that is handed to a wrapper like a custom decompressor, and can be taken back once
+/// the wrapper is dropped
+struct SharedReader<R>(Rc<RefCell<Option<R>>>);
+
+impl<R> SharedReader<R> {
+    fn new(reader: R) -> SharedReader<R> {
+        SharedReader(Rc::new(RefCell::new(Some(reader))))
+    }
+
+    fn share(&self) -> SharedReader<R> {
+        SharedReader(self.0.clone())
+    }
+
+    /// Take the reader back, after which the shared readers fail
+    fn take(self) -> R {
+        self.0
+            .borrow_mut()
+            .take()
+            .expect("The shared reader is only taken once")
+    }
+}
+
+impl<R: Read> Read for SharedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.borrow_mut().as_mut() {
+            Some(reader) => reader.read(buf),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The reader was taken back",
+            )),
         }
     }
 }
@@ -147,15 +577,28 @@ impl<'a> AsyncCryptoReader<'a> {
 enum ZipFileReader<'a> {
     NoReader,
     Raw(io::Take<&'a mut dyn io::Read>),
-    Stored(Crc32Reader<CryptoReader<'a>>),
+    Stored(Crc32Reader<LimitedReader<CryptoReader<'a>>>),
+    #[cfg(any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib"
+    ))]
+    Deflated(Crc32Reader<LimitedReader<deflate::Decoder<PooledReader<CryptoReader<'a>>>>>),
+    /// A deflated file followed by a data descriptor, read from a stream
     #[cfg(any(
         feature = "deflate",
         feature = "deflate-miniz",
         feature = "deflate-zlib"
     ))]
-    Deflated(Crc32Reader<flate2::read::DeflateDecoder<CryptoReader<'a>>>),
+    Described(LimitedReader<DescriptorReader<Box<dyn BufRead + 'a>>>),
     #[cfg(feature = "bzip2")]
-    Bzip2(Crc32Reader<BzDecoder<CryptoReader<'a>>>),
+    Bzip2(Crc32Reader<LimitedReader<BzDecoder<PooledReader<CryptoReader<'a>>>>>),
+    /// The data of a custom decompressor, and the reader of the compressed data it decompresses
+    Custom(
+        Crc32Reader<LimitedReader<Box<dyn Read + 'a>>>,
+        SharedReader<CryptoReader<'a>>,
+    ),
+    Hooked(Box<dyn Read + 'a>),
 }
 
 #[cfg(feature = "async")]
@@ -163,15 +606,80 @@ enum ZipFileReader<'a> {
 enum AsyncZipFileReader<'a> {
     NoReader,
     Raw(#[pin] futures::io::Take<Pin<&'a mut (dyn AsyncRead + Send + Sync)>>),
-    Stored(#[pin] Crc32Reader<AsyncCryptoReader<'a>>),
+    Stored(#[pin] Crc32Reader<LimitedReader<AsyncCryptoReader<'a>>>),
+    #[cfg(any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib"
+    ))]
+    Deflated(
+        #[pin]
+        Crc32Reader<
+            LimitedReader<
+                AsyncDecoder<'a, AsyncDeflateDecoder<AsyncBufReader<AsyncCryptoReader<'a>>>>,
+            >,
+        >,
+    ),
     #[cfg(any(
         feature = "deflate",
         feature = "deflate-miniz",
         feature = "deflate-zlib"
     ))]
-    Deflated(#[pin] Crc32Reader<AsyncDeflateDecoder<AsyncBufReader<AsyncCryptoReader<'a>>>>),
+    Described(
+        #[pin] LimitedReader<AsyncDescriptorReader<Pin<&'a mut (dyn AsyncRead + Send + Sync)>>>,
+    ),
     #[cfg(feature = "bzip2")]
-    Bzip2(#[pin] Crc32Reader<AsyncBzDecoder<AsyncBufReader<AsyncCryptoReader<'a>>>>),
+    Bzip2(
+        #[pin]
+        Crc32Reader<
+            LimitedReader<AsyncDecoder<'a, AsyncBzDecoder<AsyncBufReader<AsyncCryptoReader<'a>>>>>,
+        >,
+    ),
+}
+
+/// Async decoder of the compressed data of an entry, which polls the compressed data for its end
+/// once the decompressed data ends
+///
+/// The sync decoders read their input until it ends, which is where the AES reader checks the
+/// authentication code, but the async decoders stop at the end of the compressed stream.
+#[cfg(all(
+    feature = "async",
+    any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib",
+        feature = "bzip2"
+    )
+))]
+#[pin_project]
+struct AsyncDecoder<'a, D> {
+    #[pin]
+    decoder: D,
+    input: fn(Pin<&mut D>) -> Pin<&mut AsyncBufReader<AsyncCryptoReader<'a>>>,
+}
+
+#[cfg(all(
+    feature = "async",
+    any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib",
+        feature = "bzip2"
+    )
+))]
+impl<'a, D: AsyncRead> AsyncRead for AsyncDecoder<'a, D> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> std::task::Poll<io::Result<usize>> {
+        let mut this = self.project();
+        let count = futures::ready!(this.decoder.as_mut().poll_read(cx, buf))?;
+        if count == 0 && !buf.is_empty() {
+            futures::ready!((this.input)(this.decoder).poll_fill_buf(cx))?;
+        }
+        std::task::Poll::Ready(Ok(count))
+    }
 }
 
 impl<'a> Read for ZipFileReader<'a> {
@@ -186,8 +694,16 @@ impl<'a> Read for ZipFileReader<'a> {
                 feature = "deflate-zlib"
             ))]
             ZipFileReader::Deflated(r) => r.read(buf),
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-miniz",
+                feature = "deflate-zlib"
+            ))]
+            ZipFileReader::Described(r) => r.read(buf),
             #[cfg(feature = "bzip2")]
             ZipFileReader::Bzip2(r) => r.read(buf),
+            ZipFileReader::Custom(r, _) => r.read(buf),
+            ZipFileReader::Hooked(r) => r.read(buf),
         }
     }
 }
@@ -209,6 +725,12 @@ impl<'a> AsyncRead for AsyncZipFileReader<'a> {
                 feature = "deflate-zlib"
             ))]
             AsyncZipFileReaderProject::Deflated(r) => r.poll_read(cx, buf),
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-miniz",
+                feature = "deflate-zlib"
+            ))]
+            AsyncZipFileReaderProject::Described(r) => r.poll_read(cx, buf),
             #[cfg(feature = "bzip2")]
             AsyncZipFileReaderProject::Bzip2(r) => r.poll_read(cx, buf),
         }
@@ -221,15 +743,40 @@ impl<'a> ZipFileReader<'a> {
         match self {
             ZipFileReader::NoReader => panic!("ZipFileReader was in an invalid state"),
             ZipFileReader::Raw(r) => r,
-            ZipFileReader::Stored(r) => r.into_inner().into_inner(),
+            ZipFileReader::Stored(r) => r.into_inner().into_inner().into_inner(),
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-miniz",
+                feature = "deflate-zlib"
+            ))]
+            ZipFileReader::Deflated(r) => r
+                .into_inner()
+                .into_inner()
+                .into_inner()
+                .into_inner()
+                .into_inner(),
             #[cfg(any(
                 feature = "deflate",
                 feature = "deflate-miniz",
                 feature = "deflate-zlib"
             ))]
-            ZipFileReader::Deflated(r) => r.into_inner().into_inner().into_inner(),
+            ZipFileReader::Described(_) => {
+                panic!("The data of a file with a data descriptor has no known length")
+            }
             #[cfg(feature = "bzip2")]
-            ZipFileReader::Bzip2(r) => r.into_inner().into_inner().into_inner(),
+            ZipFileReader::Bzip2(r) => r
+                .into_inner()
+                .into_inner()
+                .into_inner()
+                .into_inner()
+                .into_inner(),
+            ZipFileReader::Custom(r, inner) => {
+                drop(r);
+                inner.take().into_inner()
+            }
+            ZipFileReader::Hooked(_) => {
+                panic!("The inner reader of a hooked entry cannot be recovered")
+            }
         }
     }
 }
@@ -239,6 +786,16 @@ pub struct ZipFile<'a> {
     data: Cow<'a, ZipFileData>,
     crypto_reader: Option<CryptoReader<'a>>,
     reader: ZipFileReader<'a>,
+    decompressor: Option<Arc<dyn Decompressor>>,
+    read_hook: Option<Arc<dyn ReadHook>>,
+    buffers: BufferPool,
+    /// The number of bytes that were read
+    bytes_read: u64,
+    /// Whether the file is read from a stream, whose rest is skipped when the file is dropped
+    streamed: bool,
+    /// Set when the rest of a streamed file could not be skipped, after which the stream can't
+    /// be read any further
+    poisoned: Option<&'a AtomicBool>,
 }
 
 /// A struct for reading a zip file
@@ -249,8 +806,21 @@ pub struct AsyncZipFile<'a> {
     crypto_reader: Option<AsyncCryptoReader<'a>>,
     #[pin]
     reader: AsyncZipFileReader<'a>,
+    /// The data that was read by `poll_fill_buf`, which is consumed from `buffer_start`
+    buffer: Vec<u8>,
+    buffer_start: usize,
+    /// The number of bytes that were read
+    bytes_read: u64,
 }
 
+/// The size of the buffer of an [`AsyncZipFile`] that is read with `AsyncBufRead`
+#[cfg(feature = "async")]
+const ASYNC_BUFFER_SIZE: usize = 8192;
+
+/// The size from which [`ZipArchive::validate_parallel`] hashes a stored file on several threads
+#[cfg(feature = "parallel")]
+const PARALLEL_CRC32_SIZE: u64 = 64 << 20;
+
 fn find_content<'a>(
     data: &mut ZipFileData,
     reader: &'a mut (impl Read + Seek),
@@ -274,23 +844,47 @@ fn find_content<'a>(
 
 fn make_crypto_reader<'a>(
     compression_method: crate::compression::CompressionMethod,
-    crc32: u32,
+    check_bytes: [u8; 2],
     reader: io::Take<&'a mut dyn io::Read>,
     password: Option<&[u8]>,
+    aes_info: Option<(AesMode, AesVendorVersion, u32)>,
+    has_decompressor: bool,
 ) -> ZipResult<Result<CryptoReader<'a>, InvalidPassword>> {
     #[allow(deprecated)]
     {
         if let CompressionMethod::Unsupported(_) = compression_method {
-            return unsupported_zip_error("Compression method not supported");
+            if !has_decompressor {
+                return unsupported_zip_error("Compression method not supported");
+            }
         }
     }
 
-    let reader = match password {
-        None => CryptoReader::Plaintext(reader),
-        Some(password) => match ZipCryptoReader::new(reader, password).validate(crc32)? {
-            None => return Ok(Err(InvalidPassword)),
-            Some(r) => CryptoReader::ZipCrypto(r),
-        },
+    let reader = match (password, aes_info) {
+        #[cfg(not(feature = "aes-crypto"))]
+        (Some(_), Some(_)) => {
+            return Err(ZipError::UnsupportedArchive(
+                "AES encrypted files cannot be decrypted without the aes-crypto feature.",
+            ))
+        }
+        #[cfg(feature = "aes-crypto")]
+        (Some(password), Some((aes_mode, vendor_version, iteration_count))) => {
+            let compressed_size = reader.limit();
+            let reader = AesReader::new(reader, aes_mode, compressed_size);
+            match reader.validate(password, iteration_count)? {
+                None => return Ok(Err(InvalidPassword)),
+                Some(r) => CryptoReader::Aes {
+                    reader: Box::new(r),
+                    vendor_version,
+                },
+            }
+        }
+        (Some(password), None) => {
+            match ZipCryptoReader::new(reader, password).validate(check_bytes)? {
+                None => return Ok(Err(InvalidPassword)),
+                Some(r) => CryptoReader::ZipCrypto(r),
+            }
+        }
+        (None, _) => CryptoReader::Plaintext(reader),
     };
     Ok(Ok(reader))
 }
@@ -298,23 +892,51 @@ fn make_crypto_reader<'a>(
 fn make_reader<'a>(
     compression_method: CompressionMethod,
     crc32: u32,
+    uncompressed_size: u64,
+    name: String,
     reader: CryptoReader<'a>,
+    decompressor: Option<&dyn Decompressor>,
+    #[cfg_attr(
+        not(any(
+            feature = "deflate",
+            feature = "deflate-miniz",
+            feature = "deflate-zlib",
+            feature = "bzip2"
+        )),
+        allow(unused_variables)
+    )]
+    buffers: &BufferPool,
 ) -> ZipFileReader<'a> {
+    let ae2_encrypted = reader.is_ae2_encrypted();
+    if let Some(decompressor) = decompressor {
+        let inner = SharedReader::new(reader);
+        let custom_reader = decompressor.decompress(Box::new(inner.share()));
+        let custom_reader = LimitedReader::new(custom_reader, uncompressed_size, name);
+        let custom_reader = Crc32Reader::new(custom_reader, crc32, ae2_encrypted);
+        return ZipFileReader::Custom(custom_reader, inner);
+    }
     match compression_method {
-        CompressionMethod::Stored => ZipFileReader::Stored(Crc32Reader::new(reader, crc32)),
+        CompressionMethod::Stored => {
+            let reader = LimitedReader::new(reader, uncompressed_size, name);
+            ZipFileReader::Stored(Crc32Reader::new(reader, crc32, ae2_encrypted))
+        }
         #[cfg(any(
             feature = "deflate",
             feature = "deflate-miniz",
             feature = "deflate-zlib"
         ))]
         CompressionMethod::Deflated => {
-            let deflate_reader = DeflateDecoder::new(reader);
-            ZipFileReader::Deflated(Crc32Reader::new(deflate_reader, crc32))
+            let deflate_reader = deflate::Decoder::new(PooledReader::new(reader, buffers), buffers);
+            #[cfg(feature = "deflate-libdeflate")]
+            let deflate_reader = deflate_reader.limit(uncompressed_size, name.clone());
+            let deflate_reader = LimitedReader::new(deflate_reader, uncompressed_size, name);
+            ZipFileReader::Deflated(Crc32Reader::new(deflate_reader, crc32, ae2_encrypted))
         }
         #[cfg(feature = "bzip2")]
         CompressionMethod::Bzip2 => {
-            let bzip2_reader = BzDecoder::new(reader);
-            ZipFileReader::Bzip2(Crc32Reader::new(bzip2_reader, crc32))
+            let bzip2_reader = BzDecoder::new(PooledReader::new(reader, buffers));
+            let bzip2_reader = LimitedReader::new(bzip2_reader, uncompressed_size, name);
+            ZipFileReader::Bzip2(Crc32Reader::new(bzip2_reader, crc32, ae2_encrypted))
         }
         _ => panic!("Compression method not supported"),
     }
@@ -323,9 +945,10 @@ fn make_reader<'a>(
 #[cfg(feature = "async")]
 async fn make_crypto_reader_async<'a>(
     compression_method: crate::compression::CompressionMethod,
-    crc32: u32,
+    check_bytes: [u8; 2],
     reader: futures::io::Take<Pin<&'a mut (dyn AsyncRead + Send + Sync)>>,
     password: Option<&[u8]>,
+    aes_info: Option<(AesMode, AesVendorVersion, u32)>,
 ) -> ZipResult<Result<AsyncCryptoReader<'a>, InvalidPassword>> {
     #[allow(deprecated)]
     {
@@ -334,16 +957,34 @@ async fn make_crypto_reader_async<'a>(
         }
     }
 
-    let reader = match password {
-        None => AsyncCryptoReader::Plaintext(reader),
-        Some(password) => match ZipCryptoReader::new_async(reader, password)
+    let reader = match (password, aes_info) {
+        #[cfg(not(feature = "aes-crypto"))]
+        (Some(_), Some(_)) => {
+            return Err(ZipError::UnsupportedArchive(
+                "AES encrypted files cannot be decrypted without the aes-crypto feature.",
+            ))
+        }
+        #[cfg(feature = "aes-crypto")]
+        (Some(password), Some((aes_mode, vendor_version, iteration_count))) => {
+            let compressed_size = reader.limit();
+            let reader = AesReader::new(reader, aes_mode, compressed_size);
+            match reader.validate_async(password, iteration_count).await? {
+                None => return Ok(Err(InvalidPassword)),
+                Some(r) => AsyncCryptoReader::Aes {
+                    reader: Box::new(r),
+                    vendor_version,
+                },
+            }
+        }
+        (Some(password), None) => match ZipCryptoReader::new_async(reader, password)
             .await
-            .validate_async(crc32)
+            .validate_async(check_bytes)
             .await?
         {
             None => return Ok(Err(InvalidPassword)),
             Some(r) => AsyncCryptoReader::ZipCrypto(r),
         },
+        (None, _) => AsyncCryptoReader::Plaintext(reader),
     };
     Ok(Ok(reader))
 }
@@ -352,23 +993,37 @@ async fn make_crypto_reader_async<'a>(
 async fn make_reader_async<'a>(
     compression_method: crate::compression::CompressionMethod,
     crc32: u32,
+    uncompressed_size: u64,
+    name: String,
     reader: AsyncCryptoReader<'a>,
 ) -> AsyncZipFileReader<'a> {
+    let ae2_encrypted = reader.is_ae2_encrypted();
     match compression_method {
-        CompressionMethod::Stored => AsyncZipFileReader::Stored(Crc32Reader::new(reader, crc32)),
+        CompressionMethod::Stored => {
+            let reader = LimitedReader::new(reader, uncompressed_size, name);
+            AsyncZipFileReader::Stored(Crc32Reader::new(reader, crc32, ae2_encrypted))
+        }
         #[cfg(any(
             feature = "deflate",
             feature = "deflate-miniz",
             feature = "deflate-zlib"
         ))]
         CompressionMethod::Deflated => {
-            let deflate_reader = AsyncDeflateDecoder::new(AsyncBufReader::new(reader));
-            AsyncZipFileReader::Deflated(Crc32Reader::new(deflate_reader, crc32))
+            let deflate_reader = AsyncDecoder {
+                decoder: AsyncDeflateDecoder::new(AsyncBufReader::new(reader)),
+                input: AsyncDeflateDecoder::get_pin_mut,
+            };
+            let deflate_reader = LimitedReader::new(deflate_reader, uncompressed_size, name);
+            AsyncZipFileReader::Deflated(Crc32Reader::new(deflate_reader, crc32, ae2_encrypted))
         }
         #[cfg(feature = "bzip2")]
         CompressionMethod::Bzip2 => {
-            let bzip2_reader = AsyncBzDecoder::new(AsyncBufReader::new(reader));
-            AsyncZipFileReader::Bzip2(Crc32Reader::new(bzip2_reader, crc32))
+            let bzip2_reader = AsyncDecoder {
+                decoder: AsyncBzDecoder::new(AsyncBufReader::new(reader)),
+                input: AsyncBzDecoder::get_pin_mut,
+            };
+            let bzip2_reader = LimitedReader::new(bzip2_reader, uncompressed_size, name);
+            AsyncZipFileReader::Bzip2(Crc32Reader::new(bzip2_reader, crc32, ae2_encrypted))
         }
         _ => panic!("Compression method not supported"),
     }
@@ -381,7 +1036,8 @@ impl<R: Read + io::Seek> ZipArchive<R> {
         reader: &mut R,
         footer: &spec::CentralDirectoryEnd,
         cde_start_pos: u64,
-    ) -> ZipResult<(u64, u64, usize)> {
+        precedence: EndRecordPrecedence,
+    ) -> ZipResult<DirectoryCounts> {
         // See if there's a ZIP64 footer. The ZIP64 locator if present will
         // have its signature 20 bytes in front of the standard footer. The
         // standard footer, in turn, is 22+N bytes large, where N is the
@@ -424,14 +1080,22 @@ impl<R: Read + io::Seek> ZipArchive<R> {
 
                 let directory_start = footer.central_directory_offset as u64 + archive_offset;
                 let number_of_files = footer.number_of_files_on_this_disk as usize;
-                Ok((archive_offset, directory_start, number_of_files))
+                Ok((
+                    archive_offset,
+                    directory_start,
+                    number_of_files,
+                    None,
+                    None,
+                    false,
+                ))
             }
             Some(locator64) => {
                 // If we got here, this is indeed a ZIP64 file.
 
                 if footer.disk_number as u32 != locator64.disk_with_central_directory {
-                    return unsupported_zip_error(
-                        "Support for multi-disk files is not implemented",
+                    return multi_disk_error(
+                        footer.disk_number as u32,
+                        locator64.disk_with_central_directory,
                     );
                 }
 
@@ -448,49 +1112,173 @@ impl<R: Read + io::Seek> ZipArchive<R> {
                     .ok_or(ZipError::InvalidArchive(
                         "File cannot contain ZIP64 central directory end",
                     ))?;
-                let (footer, archive_offset) = spec::Zip64CentralDirectoryEnd::find_and_parse(
+                let found = spec::Zip64CentralDirectoryEnd::find_and_parse(
                     reader,
                     locator64.end_of_central_directory_offset,
                     search_upper_bound,
-                )?;
+                );
+                let (footer64, archive_offset, lookup) = match found {
+                    Ok((footer64, archive_offset)) => {
+                        (footer64, archive_offset, Zip64EndLookup::Locator)
+                    }
+                    Err(e) => {
+                        // Self-extracting archives whose offsets were not adjusted have a locator
+                        // that points to the wrong place, but the record is right in front of it
+                        let (footer64, pos) = spec::Zip64CentralDirectoryEnd::find_before(
+                            reader,
+                            cde_start_pos - 20,
+                            spec::MAX_ZIP64_END_DISTANCE,
+                        )
+                        .map_err(|_| e)?;
+                        let archive_offset = zip64_archive_offset(&footer64, pos)?;
+                        (footer64, archive_offset, Zip64EndLookup::Scan)
+                    }
+                };
 
-                if footer.disk_number != footer.disk_with_central_directory {
-                    return unsupported_zip_error(
-                        "Support for multi-disk files is not implemented",
+                if footer64.disk_number != footer64.disk_with_central_directory {
+                    return multi_disk_error(
+                        footer64.disk_number,
+                        footer64.disk_with_central_directory,
                     );
                 }
+                if let Some(algorithm) = footer64.central_directory_encryption {
+                    let algorithm = StrongEncryptionAlgorithm::from_u16(algorithm);
+                    return Err(ZipError::EncryptedCentralDirectory(algorithm));
+                }
 
-                let directory_start = footer
-                    .central_directory_offset
-                    .checked_add(archive_offset)
-                    .ok_or_else(|| {
-                        ZipError::InvalidArchive("Invalid central directory size or offset")
-                    })?;
+                let (directory_offset, number_of_files, mismatch) =
+                    resolve_end_records(footer, &footer64, precedence);
+                let directory_start = directory_offset.checked_add(archive_offset).ok_or(
+                    ZipError::InvalidArchive("Invalid central directory size or offset"),
+                )?;
 
                 Ok((
                     archive_offset,
                     directory_start,
-                    footer.number_of_files as usize,
+                    number_of_files,
+                    Some(footer64.extensible_data_sector),
+                    Some(lookup),
+                    mismatch,
                 ))
             }
         }
     }
 
+    /// Check whether the central directory of an archive is encrypted with PKWARE strong
+    /// encryption, which makes [`ZipArchive::new`] fail with
+    /// [`ZipError::EncryptedCentralDirectory`].
+    ///
+    /// Returns the algorithm, which is [`StrongEncryptionAlgorithm::Unknown`] if the archive does
+    /// not record it, or `None` if the central directory is not encrypted.
+    pub fn central_directory_encryption(
+        reader: &mut R,
+    ) -> ZipResult<Option<StrongEncryptionAlgorithm>> {
+        let config = Config::default();
+        let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(
+            reader,
+            config.max_search_distance,
+            config.trailing_data,
+        )?;
+        let counts = Self::get_directory_counts(
+            reader,
+            &footer,
+            cde_start_pos,
+            config.end_record_precedence,
+        );
+        let directory_start = match counts {
+            Ok((_, directory_start, _, _, _, _)) => directory_start,
+            Err(ZipError::EncryptedCentralDirectory(algorithm)) => return Ok(Some(algorithm)),
+            Err(e) => return Err(e),
+        };
+        reader.seek(io::SeekFrom::Start(directory_start))?;
+        if reader.read_u32::<LittleEndian>()? == spec::ARCHIVE_EXTRA_DATA_SIGNATURE {
+            Ok(Some(StrongEncryptionAlgorithm::Unknown(0xFFFF)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// List the entries of an archive one at a time, without opening it.
+    ///
+    /// Unlike [`ZipArchive::new`], which keeps the metadata of every entry, this parses each
+    /// central directory record only when the iterator gets to it and keeps nothing of the
+    /// previous ones, so an archive with millions of entries is listed in constant memory.
+    pub fn stream_entries(mut reader: R) -> ZipResult<EntryRecords<R>> {
+        let config = Config::default();
+        let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(
+            &mut reader,
+            config.max_search_distance,
+            config.trailing_data,
+        )?;
+
+        if footer.disk_number != 0 || footer.disk_with_central_directory != 0 {
+            return multi_disk_error(
+                footer.disk_number as u32,
+                footer.disk_with_central_directory as u32,
+            );
+        }
+
+        let (archive_offset, directory_start, number_of_files, _, _, _) =
+            Self::get_directory_counts(
+                &mut reader,
+                &footer,
+                cde_start_pos,
+                config.end_record_precedence,
+            )?;
+
+        if reader.seek(io::SeekFrom::Start(directory_start)).is_err() {
+            return Err(ZipError::InvalidArchive(
+                "Could not seek to start of central directory",
+            ));
+        }
+
+        Ok(EntryRecords {
+            reader,
+            archive_offset,
+            remaining: number_of_files,
+        })
+    }
+
     /// Read a ZIP archive, collecting the files it contains
     ///
-    /// This uses the central directory record of the ZIP file, and ignores local file headers
-    pub fn new(mut reader: R) -> ZipResult<ZipArchive<R>> {
-        let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(&mut reader)?;
+    /// This uses the central directory record of the ZIP file, and ignores local file headers.
+    /// Fails if the central directory does not match the checksum written by
+    /// [`ZipWriter::set_central_directory_checksum`](crate::ZipWriter::set_central_directory_checksum).
+    pub fn new(reader: R) -> ZipResult<ZipArchive<R>> {
+        Self::new_with_config(reader, Config::default())
+    }
 
-        if footer.disk_number != footer.disk_with_central_directory {
-            return unsupported_zip_error("Support for multi-disk files is not implemented");
+    /// Read a ZIP archive like [`ZipArchive::new`], with the given options.
+    pub fn new_with_config(mut reader: R, config: Config) -> ZipResult<ZipArchive<R>> {
+        let deadline = Deadline::after(config.time_limit);
+        let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(
+            &mut reader,
+            config.max_search_distance,
+            config.trailing_data,
+        )?;
+
+        if footer.disk_number != 0 || footer.disk_with_central_directory != 0 {
+            return multi_disk_error(
+                footer.disk_number as u32,
+                footer.disk_with_central_directory as u32,
+            );
         }
 
-        let (archive_offset, directory_start, number_of_files) =
-            Self::get_directory_counts(&mut reader, &footer, cde_start_pos)?;
+        let (
+            archive_offset,
+            directory_start,
+            number_of_files,
+            zip64_extensible_data,
+            zip64_end_lookup,
+            end_record_mismatch,
+        ) = Self::get_directory_counts(
+            &mut reader,
+            &footer,
+            cde_start_pos,
+            config.end_record_precedence,
+        )?;
 
         let mut files = Vec::new();
-        let mut names_map = HashMap::new();
 
         if let Err(_) = reader.seek(io::SeekFrom::Start(directory_start)) {
             return Err(ZipError::InvalidArchive(
@@ -498,57 +1286,494 @@ impl<R: Read + io::Seek> ZipArchive<R> {
             ));
         }
 
-        for _ in 0..number_of_files {
+        loop {
+            let more = match config.record_count {
+                RecordCountPolicy::Claimed => files.len() < number_of_files,
+                RecordCountPolicy::Found => at_central_header(&mut reader)?,
+            };
+            if !more {
+                break;
+            }
+            deadline.check()?;
             let file = central_header_to_zip_file(&mut reader, archive_offset)?;
-            names_map.insert(file.file_name.clone(), files.len());
             files.push(file);
         }
+        let digital_signature = read_digital_signature(&mut reader)?;
 
+        if let Some(checksum) = central_directory_checksum(zip64_extensible_data.as_deref())? {
+            reader.seek(io::SeekFrom::Start(directory_start))?;
+            let directory = read_central_directory(&mut reader, files.len())?;
+            check_central_directory(&directory, checksum)?;
+        }
+
+        let names = NameIndex::new(&mut files);
         Ok(ZipArchive {
             reader,
             files,
-            names_map,
+            claimed_len: number_of_files,
+            names,
             offset: archive_offset,
+            directory_start,
             comment: footer.zip_file_comment,
+            zip64_extensible_data,
+            zip64_end_lookup,
+            end_record_mismatch,
+            digital_signature,
+            config,
+            decompressors: Codecs::default(),
+            #[cfg(feature = "deflate")]
+            dictionaries: deflate::Dictionaries::default(),
+            aes_iteration_count: AES_ITERATION_COUNT,
+            read_hook: Hook::default(),
+            crc_retry: Hook::default(),
+            buffers: BufferPool::new(config.buffer_size),
         })
     }
-    /// Extract a Zip archive into a directory, overwriting files if they
-    /// already exist. Paths are sanitized with [`ZipFile::enclosed_name`].
+
+    /// Re-read the central directory after the archive may have been modified, for example
+    /// appended to by another process.
     ///
-    /// Extraction is not atomic; If an error is encountered, some of the files
-    /// may be left on disk.
-    pub fn extract<P: AsRef<Path>>(&mut self, directory: P) -> ZipResult<()> {
-        use std::fs;
+    /// Returns whether the archive changed. Central directory records of entries that are still
+    /// unchanged at the start of the new central directory are only compared, not parsed again,
+    /// so refreshing an archive that was appended to is cheaper than opening it again. If the new
+    /// central directory can't be read or doesn't match its checksum, the archive is left as it
+    /// was.
+    ///
+    /// Archives opened with [`ZipArchive::new_multidisk`] cannot be refreshed.
+    pub fn refresh(&mut self) -> ZipResult<bool> {
+        let deadline = Deadline::after(self.config.time_limit);
+        let reader = &mut self.reader;
+        let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(
+            reader,
+            self.config.max_search_distance,
+            self.config.trailing_data,
+        )?;
+
+        if footer.disk_number != 0 || footer.disk_with_central_directory != 0 {
+            return multi_disk_error(
+                footer.disk_number as u32,
+                footer.disk_with_central_directory as u32,
+            );
+        }
 
-        for i in 0..self.len() {
-            let mut file = self.by_index(i)?;
-            let filepath = file
-                .enclosed_name()
-                .ok_or(ZipError::InvalidArchive("Invalid file path"))?;
+        let (
+            archive_offset,
+            directory_start,
+            number_of_files,
+            zip64_extensible_data,
+            zip64_end_lookup,
+            end_record_mismatch,
+        ) = Self::get_directory_counts(
+            reader,
+            &footer,
+            cde_start_pos,
+            self.config.end_record_precedence,
+        )?;
+
+        if archive_offset == self.offset
+            && directory_start == self.directory_start
+            && number_of_files == self.claimed_len
+            && footer.zip_file_comment == self.comment
+            && zip64_extensible_data == self.zip64_extensible_data
+        {
+            return Ok(false);
+        }
 
-            let outpath = directory.as_ref().join(filepath);
+        if reader.seek(io::SeekFrom::Start(directory_start)).is_err() {
+            return Err(ZipError::InvalidArchive(
+                "Could not seek to start of central directory",
+            ));
+        }
 
-            if file.name().ends_with('/') {
-                fs::create_dir_all(&outpath)?;
-            } else {
-                if let Some(p) = outpath.parent() {
-                    if !p.exists() {
-                        fs::create_dir_all(&p)?;
+        // Keep the entries whose records did not change. Nothing is changed until the new central
+        // directory was read and checked, so the archive stays as it was if that fails.
+        let mut central_header_starts = Vec::new();
+        if archive_offset == self.offset {
+            let mut central_header_start = directory_start;
+            while central_header_starts.len() < self.files.len().min(number_of_files) {
+                let file = &self.files[central_header_starts.len()];
+                match central_header_matches(reader, file, archive_offset)? {
+                    Some(length) => {
+                        central_header_starts.push(central_header_start);
+                        central_header_start += length;
+                    }
+                    None => {
+                        // Parse the record that did not match again
+                        reader.seek(io::SeekFrom::Start(central_header_start))?;
+                        break;
                     }
-                }
-                let mut outfile = fs::File::create(&outpath)?;
-                io::copy(&mut file, &mut outfile)?;
-            }
-            // Get and Set permissions
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                if let Some(mode) = file.unix_mode() {
-                    fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
                 }
             }
         }
-        Ok(())
+        let unchanged = central_header_starts.len();
+
+        let mut new_files = Vec::new();
+        loop {
+            let more = match self.config.record_count {
+                RecordCountPolicy::Claimed => unchanged + new_files.len() < number_of_files,
+                RecordCountPolicy::Found => at_central_header(&mut *reader)?,
+            };
+            if !more {
+                break;
+            }
+            deadline.check()?;
+            new_files.push(central_header_to_zip_file(reader, archive_offset)?);
+        }
+        let digital_signature = read_digital_signature(&mut *reader)?;
+
+        if let Some(checksum) = central_directory_checksum(zip64_extensible_data.as_deref())? {
+            reader.seek(io::SeekFrom::Start(directory_start))?;
+            let directory = read_central_directory(&mut *reader, unchanged + new_files.len())?;
+            check_central_directory(&directory, checksum)?;
+        }
+
+        self.files.truncate(unchanged);
+        for (file, start) in self.files.iter_mut().zip(central_header_starts) {
+            file.central_header_start = start;
+        }
+        self.files.extend(new_files);
+        self.names = NameIndex::new(&mut self.files);
+        self.claimed_len = number_of_files;
+        self.offset = archive_offset;
+        self.directory_start = directory_start;
+        self.comment = footer.zip_file_comment;
+        self.zip64_extensible_data = zip64_extensible_data;
+        self.zip64_end_lookup = zip64_end_lookup;
+        self.end_record_mismatch = end_record_mismatch;
+        self.digital_signature = digital_signature;
+        Ok(true)
+    }
+
+    /// Extract a Zip archive into a directory, overwriting files if they
+    /// already exist. Paths are sanitized with [`ZipFile::enclosed_name`].
+    ///
+    /// On every platform, names that would leave the directory on Windows are invalid too, like
+    /// `..\\file.txt` or `C:\\file.txt`, so extracting an archive doesn't depend on where it
+    /// happens. Nothing is written through a symlink either: an entry whose path goes through a
+    /// symlink in the directory, whether it was extracted or was there already, makes the
+    /// extraction fail, and a symlink in the place of a file is replaced by the file. The
+    /// [`ExtractSummary`] tells what was done with every entry.
+    ///
+    /// Extraction is not atomic; If an error is encountered, some of the files
+    /// may be left on disk.
+    pub fn extract<P: AsRef<Path>>(&mut self, directory: P) -> ZipResult<ExtractSummary> {
+        self.extract_filtered(directory, |_| true)
+    }
+
+    /// Extract the entries of a Zip archive for which `filter` returns `true` into a directory,
+    /// like [`ZipArchive::extract`].
+    ///
+    /// The entries that are left out are counted in [`ExtractSummary::entries_skipped`].
+    pub fn extract_filtered<P, F>(&mut self, directory: P, filter: F) -> ZipResult<ExtractSummary>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&ZipFile) -> bool,
+    {
+        self.extract_impl(directory.as_ref(), &ExtractOptions::default(), filter)
+    }
+
+    /// Extract a Zip archive into a directory, like [`ZipArchive::extract`] but with the given
+    /// `options`.
+    pub fn extract_with_options<P: AsRef<Path>>(
+        &mut self,
+        directory: P,
+        options: &ExtractOptions,
+    ) -> ZipResult<ExtractSummary> {
+        self.extract_impl(directory.as_ref(), options, |_| true)
+    }
+
+    fn extract_impl<F>(
+        &mut self,
+        directory: &Path,
+        options: &ExtractOptions,
+        mut filter: F,
+    ) -> ZipResult<ExtractSummary>
+    where
+        F: FnMut(&ZipFile) -> bool,
+    {
+        use std::fs;
+
+        let deadline = Deadline::after(self.config.time_limit);
+        let mut summary = ExtractSummary::default();
+        let mut total_size = 0u64;
+        for i in 0..self.len() {
+            deadline.check()?;
+            let file = self.by_index(i)?;
+            if !filter(&file) {
+                summary.entries_skipped += 1;
+                summary.push(i, file.name().to_owned(), ExtractOutcome::Filtered);
+                continue;
+            }
+            let filepath = match extraction_path(file.name()) {
+                Some(filepath) => filepath.to_path_buf(),
+                None if options.skip_invalid_names => {
+                    summary.entries_skipped += 1;
+                    summary.push(i, file.name().to_owned(), ExtractOutcome::InvalidName);
+                    continue;
+                }
+                None => return Err(ZipError::InvalidArchive("Invalid file path")),
+            };
+
+            let is_dir = file.name().ends_with('/');
+            check_parents(directory, &filepath, is_dir)?;
+            let mut outpath = directory.join(&filepath);
+            let mode = file.unix_mode();
+            let is_symlink = options.symlinks
+                && !is_dir
+                && matches!(mode, Some(mode) if mode & ffi::S_IFMT == ffi::S_IFLNK);
+            let permissions = options.permissions_hook.get().map(|hook| hook(&file));
+            let remaining = options
+                .size_limit
+                .map_or(u64::MAX, |limit| limit - total_size);
+            if file.size() > remaining {
+                return Err(ZipError::SizeLimitExceeded(Some(file.name().to_owned())));
+            }
+            drop(file);
+            let name = self.files[i].file_name.to_string();
+
+            if is_dir {
+                fs::create_dir_all(&outpath)?;
+                summary.directories_created += 1;
+                summary.push(i, name, ExtractOutcome::Directory(outpath.clone()));
+            } else {
+                if let Some(p) = outpath.parent() {
+                    if !p.exists() {
+                        fs::create_dir_all(&p)?;
+                    }
+                }
+                if fs::symlink_metadata(&outpath).is_ok() {
+                    match options.overwrite {
+                        OverwritePolicy::Overwrite => summary
+                            .warnings
+                            .push(ExtractWarning::Overwritten(outpath.clone())),
+                        OverwritePolicy::Skip => {
+                            summary.entries_skipped += 1;
+                            summary.push(i, name, ExtractOutcome::Existing(outpath));
+                            continue;
+                        }
+                        OverwritePolicy::Error => {
+                            return Err(ZipError::Io(io::Error::new(
+                                io::ErrorKind::AlreadyExists,
+                                format!("{} already exists", outpath.display()),
+                            )))
+                        }
+                        OverwritePolicy::Rename => {
+                            let renamed = free_path(&outpath);
+                            summary.warnings.push(ExtractWarning::Renamed {
+                                path: outpath,
+                                renamed_to: renamed.clone(),
+                            });
+                            outpath = renamed;
+                        }
+                    }
+                }
+                let written = if is_symlink {
+                    let mut target = Vec::new();
+                    self.by_index(i)?
+                        .take(remaining.min(MAX_SYMLINK_TARGET).saturating_add(1))
+                        .read_to_end(&mut target)?;
+                    if target.len() as u64 > remaining {
+                        return Err(ZipError::SizeLimitExceeded(Some(name)));
+                    }
+                    if target.len() as u64 > MAX_SYMLINK_TARGET
+                        || !symlink_target_is_enclosed(directory, &filepath, &target)
+                    {
+                        return Err(ZipError::InvalidArchive("Invalid symlink target"));
+                    }
+                    if fs::symlink_metadata(&outpath).is_ok() {
+                        fs::remove_file(&outpath)?;
+                    }
+                    create_symlink(&target, &outpath)?;
+                    summary.symlinks_created += 1;
+                    summary.push(i, name, ExtractOutcome::Symlink(outpath.clone()));
+                    target.len() as u64
+                } else {
+                    remove_symlink(&outpath)?;
+                    let mut outfile = fs::File::create(&outpath)?;
+                    let written = self
+                        .read_retrying(i, |file| {
+                            outfile.set_len(0)?;
+                            outfile.seek(io::SeekFrom::Start(0))?;
+                            let mut file = deadline.reader(file.take(remaining.saturating_add(1)));
+                            io::copy(&mut file, &mut outfile)
+                        })
+                        .map_err(|e| deadline.or_exceeded(e))?;
+                    if written > remaining {
+                        return Err(ZipError::SizeLimitExceeded(Some(name)));
+                    }
+                    summary.files_written += 1;
+                    let outcome = ExtractOutcome::File {
+                        path: outpath.clone(),
+                        size: written,
+                    };
+                    summary.push(i, name, outcome);
+                    written
+                };
+                summary.bytes_written += written;
+                total_size += written;
+            }
+            // Get and Set permissions
+            if is_symlink {
+                continue;
+            }
+            if let Some(permissions) = permissions {
+                if let Some(permissions) = permissions {
+                    fs::set_permissions(&outpath, permissions)?;
+                }
+                continue;
+            }
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                if let Some(mode) = mode.filter(|_| options.permissions) {
+                    fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
+                }
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Read the files of the archive into a map from their names to their contents.
+    ///
+    /// This is meant for small archives, like test fixtures and configuration files. Directories
+    /// are left out, and of files with the same name the last one is kept. Reading fails with
+    /// [`ZipError::SizeLimitExceeded`] as soon as the data of the files would exceed `size_limit`
+    /// bytes, which is checked against the recorded size before a file is read, and against the
+    /// data while it is read.
+    pub fn extract_to_map(&mut self, size_limit: u64) -> ZipResult<HashMap<String, Vec<u8>>> {
+        let deadline = Deadline::after(self.config.time_limit);
+        let mut files = HashMap::new();
+        let mut total_size = 0;
+        for i in 0..self.len() {
+            deadline.check()?;
+            let file = self.by_index(i)?;
+            if file.is_dir() {
+                continue;
+            }
+            let name = file.name().to_owned();
+            let remaining = size_limit - total_size;
+            if file.size() > remaining {
+                return Err(ZipError::SizeLimitExceeded(Some(name)));
+            }
+            let size = file.size();
+            drop(file);
+            let data = self
+                .read_retrying(i, |file| {
+                    let mut data = Vec::with_capacity(size as usize);
+                    // A read hook can change the length of the data
+                    deadline
+                        .reader(file.take(remaining.saturating_add(1)))
+                        .read_to_end(&mut data)?;
+                    Ok(data)
+                })
+                .map_err(|e| deadline.or_exceeded(e))?;
+            if data.len() as u64 > remaining {
+                return Err(ZipError::SizeLimitExceeded(Some(name)));
+            }
+            total_size += data.len() as u64;
+            files.insert(name, data);
+        }
+        Ok(files)
+    }
+
+    /// Register a decompressor for entries that use `method`.
+    ///
+    /// This makes it possible to read entries with compression methods that are not supported
+    /// by this crate. A decompressor registered for a supported method takes precedence over the
+    /// built-in implementation.
+    pub fn register_decompressor<D>(&mut self, method: CompressionMethod, decompressor: D)
+    where
+        D: Decompressor + 'static,
+    {
+        self.decompressors.insert(method, Arc::new(decompressor));
+    }
+
+    /// Register a preset dictionary for reading files written with
+    /// [`FileOptions::deflate_dictionary`].
+    ///
+    /// [`FileOptions::deflate_dictionary`]: crate::write::FileOptions::deflate_dictionary
+    #[cfg(feature = "deflate")]
+    pub fn register_deflate_dictionary(&mut self, dictionary: &[u8]) {
+        self.dictionaries.insert(dictionary);
+    }
+
+    /// Set the number of PBKDF2 iterations used to derive the keys of AES encrypted files.
+    ///
+    /// The default is 1000, as required by the WinZip AES format. Another count is only needed
+    /// for files written with [`FileOptions::aes_iteration_count`].
+    ///
+    /// [`FileOptions::aes_iteration_count`]: crate::write::FileOptions::aes_iteration_count
+    #[cfg(feature = "aes-crypto")]
+    pub fn set_aes_iteration_count(&mut self, count: u32) {
+        self.aes_iteration_count = count;
+    }
+
+    /// The AES parameters of a file, with the iteration count used to derive its keys
+    fn aes_info(&self, file_number: usize) -> Option<(AesMode, AesVendorVersion, u32)> {
+        let (mode, vendor_version) = self.files[file_number].aes_mode?;
+        Some((mode, vendor_version, self.aes_iteration_count))
+    }
+
+    /// The decompressor of the data of a file, if it is not decompressed by a built-in decoder
+    fn decompressor(&self, file_number: usize) -> ZipResult<Option<Arc<dyn Decompressor>>> {
+        let data = &self.files[file_number];
+        if has_deflate_dictionary(data) {
+            #[cfg(feature = "deflate")]
+            {
+                let dictionary = data
+                    .deflate_dictionary
+                    .and_then(|id| self.dictionaries.get(id));
+                if let Some(dictionary) = dictionary {
+                    return Ok(Some(Arc::new(deflate::DictionaryDecompressor {
+                        dictionary,
+                        declared_size: data.uncompressed_size,
+                    })));
+                }
+            }
+            return unsupported_zip_error("The deflate dictionary of the file is not registered");
+        }
+        Ok(self.decompressors.get(data.compression_method))
+    }
+
+    /// Set a hook that wraps the data of every entry that is read.
+    ///
+    /// The hook does not apply to [`ZipArchive::by_index_raw`].
+    pub fn set_read_hook<H: ReadHook + 'static>(&mut self, hook: H) {
+        self.read_hook.set(Arc::new(hook));
+    }
+
+    /// Read an entry once more when its data does not match its CRC-32, since transient
+    /// corruption is common with readers of remote archives.
+    ///
+    /// `reconnect` is called with the reader before the entry is read again, for example to
+    /// replace it with a fresh connection, and the error is returned if the entry does not match
+    /// its CRC-32 again. This applies to the entries that the archive reads by itself, with
+    /// [`ZipArchive::extract`], [`ZipArchive::extract_filtered`] and
+    /// [`ZipArchive::extract_to_map`].
+    pub fn set_crc_retry<F>(&mut self, reconnect: F)
+    where
+        F: Fn(&mut R) -> io::Result<()> + Send + Sync + 'static,
+    {
+        self.crc_retry.set(Arc::new(reconnect));
+    }
+
+    /// Read the data of a file with `read`, and read it once more if it does not match its CRC-32
+    /// and a retry was set with [`ZipArchive::set_crc_retry`].
+    fn read_retrying<T, F>(&mut self, file_number: usize, mut read: F) -> ZipResult<T>
+    where
+        F: FnMut(&mut ZipFile<'_>) -> io::Result<T>,
+    {
+        let error = match read(&mut self.by_index(file_number)?) {
+            Ok(result) => return Ok(result),
+            Err(e) => e,
+        };
+        match self.crc_retry.get_arc() {
+            Some(reconnect) if crc32::is_checksum_error(&error) => {
+                reconnect(&mut self.reader)?;
+                Ok(read(&mut self.by_index(file_number)?)?)
+            }
+            _ => Err(error.into()),
+        }
     }
 
     /// Number of files contained in this zip.
@@ -556,11 +1781,361 @@ impl<R: Read + io::Seek> ZipArchive<R> {
         self.files.len()
     }
 
+    /// Number of files the central directory end record claims, which differs from
+    /// [`ZipArchive::len`] if more or fewer records were read, see [`Config::record_count`]
+    pub fn claimed_len(&self) -> usize {
+        self.claimed_len
+    }
+
     /// Whether this zip archive contains no files
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
+    /// Check the records of the files for problems, without reading the files.
+    ///
+    /// This finds stored files whose compressed and uncompressed sizes disagree, which are only
+    /// read as configured with [`Config::stored_sizes`].
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        validation_issues(&self.files, self.claimed_len, self.end_record_mismatch)
+    }
+
+    /// Check the records of the files like [`ZipArchive::validate`], and read the files on
+    /// `threads` threads to check their CRC-32.
+    ///
+    /// Every thread reads from its own reader of the same archive, which is opened with `open`,
+    /// for example by opening the file again. Encrypted files are not read. Reading fails if a file
+    /// can't be read for another reason than a checksum mismatch. Stored files of 64 MiB or more are
+    /// hashed with a [`crc32::ParallelHasher`] on `threads` threads of their own.
+    #[cfg(feature = "parallel")]
+    pub fn validate_parallel<S, F>(
+        &self,
+        threads: usize,
+        mut open: F,
+    ) -> ZipResult<Vec<ValidationIssue>>
+    where
+        S: Read + io::Seek + Send + 'static,
+        F: FnMut() -> io::Result<S>,
+    {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let deadline = Deadline::after(self.config.time_limit);
+        let next = Arc::new(AtomicUsize::new(0));
+        let mut workers = Vec::new();
+        for _ in 0..threads.max(1).min(self.len()) {
+            let mut archive = self.with_reader(open()?);
+            let next = next.clone();
+            workers.push(std::thread::spawn(move || {
+                let mut mismatches = Vec::new();
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= archive.len() {
+                        return Ok(mismatches);
+                    }
+                    let data = &archive.files[index];
+                    if data.encrypted {
+                        continue;
+                    }
+                    deadline.check()?;
+                    if data.compression_method == CompressionMethod::Stored
+                        && data.uncompressed_size >= PARALLEL_CRC32_SIZE
+                    {
+                        // A huge stored file is hashed on all threads, not just this one
+                        let crc32 = data.crc32;
+                        let mut hasher = crc32::ParallelHasher::new(threads);
+                        let mut file = deadline.reader(archive.by_index_raw(index)?);
+                        io::copy(&mut file, &mut hasher)
+                            .map_err(|e| deadline.or_exceeded(ZipError::Io(e)))?;
+                        if hasher.finalize() != crc32 {
+                            mismatches.push(index);
+                        }
+                        continue;
+                    }
+                    let mut file = deadline.reader(archive.by_index(index)?);
+                    match io::copy(&mut file, &mut io::sink()) {
+                        Ok(_) => {}
+                        Err(e) if crc32::is_checksum_error(&e) => mismatches.push(index),
+                        Err(e) => return Err(deadline.or_exceeded(ZipError::Io(e))),
+                    }
+                }
+            }));
+        }
+
+        let mut issues = self.validate();
+        let mut mismatches = Vec::new();
+        for worker in workers {
+            match worker.join() {
+                Ok(result) => mismatches.extend(result?),
+                Err(panic) => std::panic::resume_unwind(panic),
+            }
+        }
+        mismatches.sort_unstable();
+        issues.extend(
+            mismatches
+                .into_iter()
+                .map(|index| ValidationIssue::ChecksumMismatch { index }),
+        );
+        Ok(issues)
+    }
+
+    /// Extract the archive into a directory like [`ZipArchive::extract`], writing the files on
+    /// `threads` threads.
+    ///
+    /// Every thread reads from its own reader of the same archive, which is opened with `open`,
+    /// like with [`ZipArchive::validate_parallel`]. The directories are created first. Of files
+    /// with the same path only the last one is written, and the others are reported as
+    /// [`ExtractWarning::Overwritten`].
+    #[cfg(feature = "parallel")]
+    pub fn extract_parallel<P, S, F>(
+        &self,
+        directory: P,
+        threads: usize,
+        mut open: F,
+    ) -> ZipResult<ExtractSummary>
+    where
+        P: AsRef<Path>,
+        S: Read + io::Seek + Send + 'static,
+        F: FnMut() -> io::Result<S>,
+    {
+        use std::fs;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let deadline = Deadline::after(self.config.time_limit);
+        let mut summary = ExtractSummary::default();
+        let mut directories = Vec::new();
+        let mut paths = HashMap::new();
+        for (index, data) in self.files.iter().enumerate() {
+            let outpath = match extraction_path(&data.file_name) {
+                Some(path) => {
+                    check_parents(directory.as_ref(), path, data.file_name.ends_with('/'))?;
+                    directory.as_ref().join(path)
+                }
+                None => return Err(ZipError::InvalidArchive("Invalid file path")),
+            };
+            if data.file_name.ends_with('/') {
+                fs::create_dir_all(&outpath)?;
+                summary.directories_created += 1;
+                let outcome = ExtractOutcome::Directory(outpath.clone());
+                summary.push(index, data.file_name.to_string(), outcome);
+                directories.push((index, outpath));
+            } else if paths.insert(outpath.clone(), index).is_some() {
+                summary.warnings.push(ExtractWarning::Overwritten(outpath));
+            }
+        }
+        let mut jobs: Vec<_> = paths
+            .into_iter()
+            .map(|(path, index)| (index, path))
+            .collect();
+        jobs.sort_unstable();
+
+        let jobs = Arc::new(jobs);
+        let next = Arc::new(AtomicUsize::new(0));
+        let mut workers = Vec::new();
+        for _ in 0..threads.max(1).min(jobs.len()) {
+            let mut archive = self.with_reader(open()?);
+            let jobs = jobs.clone();
+            let next = next.clone();
+            workers.push(std::thread::spawn(move || {
+                let mut written = Vec::new();
+                loop {
+                    let (index, outpath) = match jobs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        Some((index, outpath)) => (*index, outpath),
+                        None => return ZipResult::Ok(written),
+                    };
+                    deadline.check()?;
+                    let file = archive.by_index(index)?;
+                    let mode = file.unix_mode();
+                    if let Some(parent) = outpath.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    let overwritten = fs::symlink_metadata(outpath).is_ok();
+                    remove_symlink(outpath)?;
+                    let mut outfile = fs::File::create(outpath)?;
+                    let count = io::copy(&mut deadline.reader(file), &mut outfile)
+                        .map_err(|e| deadline.or_exceeded(ZipError::Io(e)))?;
+                    set_permissions(outpath, mode)?;
+                    written.push((index, count, overwritten, outpath.clone()));
+                }
+            }));
+        }
+
+        let mut written = Vec::new();
+        for worker in workers {
+            match worker.join() {
+                Ok(result) => written.extend(result?),
+                Err(panic) => std::panic::resume_unwind(panic),
+            }
+        }
+        written.sort_unstable();
+        for (index, count, overwritten, path) in written {
+            summary.files_written += 1;
+            summary.bytes_written += count;
+            if overwritten {
+                summary
+                    .warnings
+                    .push(ExtractWarning::Overwritten(path.clone()));
+            }
+            let outcome = ExtractOutcome::File { path, size: count };
+            summary.push(index, self.files[index].file_name.to_string(), outcome);
+        }
+        summary.entries.sort_by_key(|entry| entry.index);
+
+        // The permissions of directories are set last, as they may forbid writing into them
+        if !directories.is_empty() {
+            let mut archive = self.with_reader(open()?);
+            for (index, outpath) in directories {
+                set_permissions(&outpath, archive.by_index(index)?.unix_mode())?;
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Extract the archive into a directory like [`ZipArchive::extract`], writing the files with
+    /// io_uring on Linux.
+    ///
+    /// Small files are read into memory, and written in batches that take two system calls, one
+    /// to find out what is at their paths and one to open, write and close them all, instead of
+    /// several calls for every file. Files of 1 MiB or more are written like by
+    /// [`ZipArchive::extract`]. Like with [`ZipArchive::extract_parallel`], the directories are
+    /// created first, of files with the same path only the last one is written, and the
+    /// permissions of the files are set after they are written.
+    ///
+    /// Fails if io_uring is not available, like on kernels before 5.15 or in sandboxes that
+    /// forbid it, before anything is extracted.
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    pub fn extract_io_uring<P: AsRef<Path>>(&mut self, directory: P) -> ZipResult<ExtractSummary> {
+        use std::collections::HashSet;
+        use std::fs;
+
+        let mut writer = crate::uring::FileWriter::new()?;
+        let directory = directory.as_ref();
+        let deadline = Deadline::after(self.config.time_limit);
+        let mut summary = ExtractSummary::default();
+        let mut directories = Vec::new();
+        let mut paths = HashMap::new();
+        for (index, data) in self.files.iter().enumerate() {
+            let outpath = match extraction_path(&data.file_name) {
+                Some(path) => {
+                    check_parents(directory, path, data.file_name.ends_with('/'))?;
+                    directory.join(path)
+                }
+                None => return Err(ZipError::InvalidArchive("Invalid file path")),
+            };
+            if data.file_name.ends_with('/') {
+                fs::create_dir_all(&outpath)?;
+                summary.directories_created += 1;
+                let outcome = ExtractOutcome::Directory(outpath.clone());
+                summary.push(index, data.file_name.to_string(), outcome);
+                directories.push(index);
+            } else if paths.insert(outpath.clone(), index).is_some() {
+                summary.warnings.push(ExtractWarning::Overwritten(outpath));
+            }
+        }
+        let mut jobs: Vec<_> = paths
+            .into_iter()
+            .map(|(path, index)| (index, path))
+            .collect();
+        jobs.sort_unstable();
+
+        let mut parents = HashSet::new();
+        let mut written = Vec::new();
+        let mut modes = HashMap::new();
+        for (index, outpath) in jobs {
+            deadline.check()?;
+            let file = self.by_index(index)?;
+            if let Some(mode) = file.unix_mode() {
+                modes.insert(index, mode);
+            }
+            let size = file.size();
+            let mut file = deadline.reader(file);
+            if let Some(parent) = outpath.parent() {
+                if parents.insert(parent.to_path_buf()) {
+                    fs::create_dir_all(parent)?;
+                }
+            }
+            if size < IO_URING_FILE_SIZE {
+                let mut data = Vec::with_capacity(size as usize);
+                file.read_to_end(&mut data)
+                    .map_err(|e| deadline.or_exceeded(ZipError::Io(e)))?;
+                writer.push(index, outpath, data)?;
+            } else {
+                let overwritten = fs::symlink_metadata(&outpath).is_ok();
+                remove_symlink(&outpath)?;
+                let mut outfile = fs::File::create(&outpath)?;
+                let count = io::copy(&mut file, &mut outfile)
+                    .map_err(|e| deadline.or_exceeded(ZipError::Io(e)))?;
+                written.push((index, count, overwritten, outpath));
+            }
+        }
+        written.extend(
+            writer
+                .finish()?
+                .into_iter()
+                .map(|file| (file.id, file.size, file.overwritten, file.path)),
+        );
+
+        written.sort_unstable();
+        for (index, count, overwritten, path) in written {
+            set_permissions(&path, modes.get(&index).copied())?;
+            summary.files_written += 1;
+            summary.bytes_written += count;
+            if overwritten {
+                summary
+                    .warnings
+                    .push(ExtractWarning::Overwritten(path.clone()));
+            }
+            let outcome = ExtractOutcome::File { path, size: count };
+            summary.push(index, self.files[index].file_name.to_string(), outcome);
+        }
+        summary.entries.sort_by_key(|entry| entry.index);
+
+        // The permissions of directories are set last, as they may forbid writing into them
+        for index in directories {
+            let outpath = directory.join(extraction_path(&self.files[index].file_name).unwrap());
+            set_permissions(&outpath, self.by_index(index)?.unix_mode())?;
+        }
+        Ok(summary)
+    }
+
+    /// A copy of this archive that reads from `reader`, which must be a reader of the same
+    /// archive
+    #[cfg(feature = "parallel")]
+    fn with_reader<S: Read + io::Seek>(&self, reader: S) -> ZipArchive<S> {
+        ZipArchive {
+            reader,
+            files: self.files.clone(),
+            claimed_len: self.claimed_len,
+            names: self.names.clone(),
+            offset: self.offset,
+            directory_start: self.directory_start,
+            comment: self.comment.clone(),
+            zip64_extensible_data: self.zip64_extensible_data.clone(),
+            zip64_end_lookup: self.zip64_end_lookup,
+            end_record_mismatch: self.end_record_mismatch,
+            digital_signature: self.digital_signature.clone(),
+            config: self.config,
+            decompressors: self.decompressors.clone(),
+            #[cfg(feature = "deflate")]
+            dictionaries: self.dictionaries.clone(),
+            aes_iteration_count: self.aes_iteration_count,
+            read_hook: Hook::default(),
+            crc_retry: Hook::default(),
+            buffers: self.buffers.clone(),
+        }
+    }
+
+    /// Get the mechanism used to encrypt the file at `file_number`, or `None` if it is not
+    /// encrypted.
+    ///
+    /// This does not read the file, so it can be used to find out which password to ask for
+    /// before calling [`by_index_decrypt`](Self::by_index_decrypt).
+    pub fn encryption_method(&self, file_number: usize) -> ZipResult<Option<EncryptionMethod>> {
+        self.files
+            .get(file_number)
+            .map(ZipFileData::encryption_method)
+            .ok_or(ZipError::FileNotFound)
+    }
+
     /// Get the offset from the beginning of the underlying reader that this zip begins at, in bytes.
     ///
     /// Normally this value is zero, but if the zip has arbitrary data prepended to it, then this value will be the size
@@ -574,9 +2149,114 @@ impl<R: Read + io::Seek> ZipArchive<R> {
         &self.comment
     }
 
+    /// Get the extensible data sector of the ZIP64 central directory end record, or `None` if the
+    /// archive has no such record.
+    ///
+    /// Use [`ExtensibleDataBlock::parse_all`] to read the blocks of the data.
+    ///
+    /// [`ExtensibleDataBlock::parse_all`]: crate::ExtensibleDataBlock::parse_all
+    pub fn zip64_extensible_data(&self) -> Option<&[u8]> {
+        self.zip64_extensible_data.as_deref()
+    }
+
+    /// Get how the ZIP64 central directory end record was found, or `None` if the archive has no
+    /// such record.
+    pub fn zip64_end_lookup(&self) -> Option<Zip64EndLookup> {
+        self.zip64_end_lookup
+    }
+
+    /// Get the signature of the digital signature record at the end of the central directory, or
+    /// `None` if the archive has no such record.
+    ///
+    /// The signature is not checked when the archive is opened, use
+    /// [`ZipArchive::verify_digital_signature`] for that.
+    pub fn digital_signature(&self) -> Option<&[u8]> {
+        self.digital_signature.as_deref()
+    }
+
+    /// Check the digital signature of the central directory with `verifier`.
+    ///
+    /// Fails if the archive has no digital signature, or if `verifier` rejects it. See the
+    /// [`signature`] module.
+    pub fn verify_digital_signature<V: Verifier + ?Sized>(
+        &mut self,
+        verifier: &V,
+    ) -> ZipResult<()> {
+        let signature = match &self.digital_signature {
+            Some(signature) => signature,
+            None => {
+                return Err(ZipError::InvalidArchive(
+                    "The archive has no digital signature",
+                ))
+            }
+        };
+        self.reader
+            .seek(io::SeekFrom::Start(self.directory_start))?;
+        let directory = read_central_directory(&mut self.reader, self.files.len())?;
+        if !verifier.verify(&directory, signature) {
+            return Err(ZipError::InvalidArchive("Invalid digital signature"));
+        }
+        Ok(())
+    }
+
+    /// The canonical contents of the archive, which signatures of its contents are computed over
+    ///
+    /// For every entry, ordered by name, it holds the length of the UTF-8 name in 4 bytes, the
+    /// name, the CRC-32 in 4 bytes, and the compressed and uncompressed sizes in 8 bytes each, all
+    /// numbers in little endian. Entries with the same name keep their order in the archive.
+    pub fn canonical_contents(&self) -> Vec<u8> {
+        signature::canonical_contents(&self.files)
+    }
+
+    /// Get the signature of the contents that is embedded in the archive, or `None` if there is
+    /// none.
+    ///
+    /// See [`ZipWriter::set_contents_signer`](crate::ZipWriter::set_contents_signer).
+    pub fn embedded_signature(&self) -> Option<Vec<u8>> {
+        embedded_signature(self.zip64_extensible_data.as_deref())
+    }
+
+    /// Check a `signature` of the canonical contents with `verifier`.
+    ///
+    /// The signature can be kept apart from the archive, or be the
+    /// [embedded signature](ZipArchive::embedded_signature).
+    pub fn verify_detached_signature<V: Verifier + ?Sized>(
+        &self,
+        verifier: &V,
+        signature: &[u8],
+    ) -> ZipResult<()> {
+        verify_detached_signature(&self.files, verifier, signature)
+    }
+
     /// Returns an iterator over all the file and directory names in this archive.
     pub fn file_names(&self) -> impl Iterator<Item = &str> {
-        self.names_map.keys().map(|s| s.as_str())
+        self.names.names(&self.files)
+    }
+
+    /// Returns an iterator over the local file headers that are not referenced by the central
+    /// directory.
+    ///
+    /// The data region of the archive is scanned for local file header signatures. Headers of
+    /// entries listed in the central directory are skipped together with their data, so what
+    /// remains are typically entries that were deleted or replaced by a tool that only rewrote
+    /// the central directory.
+    ///
+    /// The scan is heuristic: compressed data that happens to contain a local header signature
+    /// may also be reported.
+    pub fn orphaned_local_headers(&mut self) -> OrphanedLocalHeaders<'_, R> {
+        let known = self
+            .files
+            .iter()
+            .map(|file| (file.header_start, file.compressed_size))
+            .collect();
+        OrphanedLocalHeaders {
+            reader: &mut self.reader,
+            known,
+            buffer: vec![0; 1 << 16],
+            pos: self.offset,
+            end: self.directory_start,
+            done: false,
+        }
     }
 
     /// Search for a file entry by name, decrypt with given password
@@ -598,8 +2278,8 @@ impl<R: Read + io::Seek> ZipArchive<R> {
         name: &str,
         password: Option<&[u8]>,
     ) -> ZipResult<Result<ZipFile<'a>, InvalidPassword>> {
-        let index = match self.names_map.get(name) {
-            Some(index) => *index,
+        let index = match self.names.get(&self.files, name) {
+            Some(index) => index,
             None => {
                 return Err(ZipError::FileNotFound);
             }
@@ -608,6 +2288,8 @@ impl<R: Read + io::Seek> ZipArchive<R> {
     }
 
     /// Get a contained file by index, decrypt with given password
+    ///
+    /// The password only applies to this file, so files can be encrypted with different passwords.
     pub fn by_index_decrypt<'a>(
         &'a mut self,
         file_number: usize,
@@ -616,6 +2298,82 @@ impl<R: Read + io::Seek> ZipArchive<R> {
         self.by_index_with_optional_password(file_number, Some(password))
     }
 
+    /// Check whether `password` is the password of the file at `file_number`, without
+    /// decrypting or decompressing its data.
+    ///
+    /// Only the check value at the start of the encrypted data is read, so a wrong password is
+    /// accepted with a chance of 1 in 256 for ZipCrypto and 1 in 65536 for AES, in which case
+    /// reading the file fails. Returns `true` if the file is not encrypted.
+    pub fn verify_password(&mut self, file_number: usize, password: &[u8]) -> ZipResult<bool> {
+        let data = self
+            .files
+            .get_mut(file_number)
+            .ok_or(ZipError::FileNotFound)?;
+        if !data.encrypted {
+            return Ok(true);
+        }
+        if let Some((algorithm, _)) = data.strong_encryption {
+            return Err(ZipError::UnsupportedEncryption(algorithm));
+        }
+        let aes_info = self.aes_info(file_number);
+        let data = &mut self.files[file_number];
+        let limit_reader = find_content(data, &mut self.reader)?;
+        let crypto_reader = make_crypto_reader(
+            data.compression_method,
+            data.zipcrypto_check_bytes(),
+            limit_reader,
+            Some(password),
+            aes_info,
+            true,
+        )?;
+        Ok(crypto_reader.is_ok())
+    }
+
+    /// Guess the type of the content of the file at `file_number` from its first bytes
+    ///
+    /// Only the start of the file is decompressed, see [`sniff::sniff`] for how the type is
+    /// guessed. Returns an error if the file is encrypted.
+    #[cfg(feature = "sniff")]
+    pub fn content_type(&mut self, file_number: usize) -> ZipResult<Option<ContentType>> {
+        let mut start = Vec::with_capacity(SNIFF_LENGTH);
+        self.by_index(file_number)?
+            .take(SNIFF_LENGTH as u64)
+            .read_to_end(&mut start)?;
+        Ok(sniff::sniff(&start))
+    }
+
+    /// Find the files that can't be inspected, because they are encrypted, are archives with
+    /// encrypted files or look like random data
+    ///
+    /// Only the start of each file is decompressed, see [`sniff::scan`] for how the content is
+    /// checked. Directories are skipped. Returns the flagged files in the order of the archive.
+    #[cfg(feature = "sniff")]
+    pub fn scan(&mut self) -> ZipResult<Vec<FlaggedFile>> {
+        let mut flagged = Vec::new();
+        let mut start = Vec::with_capacity(SCAN_LENGTH);
+        for index in 0..self.len() {
+            let flag = if self.files[index].encrypted {
+                Some(ScanFlag::Encrypted)
+            } else {
+                let file = self.by_index(index)?;
+                if file.is_dir() {
+                    continue;
+                }
+                start.clear();
+                file.take(SCAN_LENGTH as u64).read_to_end(&mut start)?;
+                sniff::scan(&start)
+            };
+            if let Some(flag) = flag {
+                flagged.push(FlaggedFile {
+                    index,
+                    name: self.files[index].file_name.to_string(),
+                    flag,
+                });
+            }
+        }
+        Ok(flagged)
+    }
+
     /// Get a contained file by index
     pub fn by_index<'a>(&'a mut self, file_number: usize) -> ZipResult<ZipFile<'a>> {
         Ok(self
@@ -626,6 +2384,7 @@ impl<R: Read + io::Seek> ZipArchive<R> {
     /// Get a contained file by index without decompressing it
     pub fn by_index_raw<'a>(&'a mut self, file_number: usize) -> ZipResult<ZipFile<'a>> {
         let reader = &mut self.reader;
+        let buffers = self.buffers.clone();
         self.files
             .get_mut(file_number)
             .ok_or(ZipError::FileNotFound)
@@ -634,10 +2393,33 @@ impl<R: Read + io::Seek> ZipArchive<R> {
                     crypto_reader: None,
                     reader: ZipFileReader::Raw(find_content(data, reader)?),
                     data: Cow::Borrowed(data),
+                    decompressor: None,
+                    read_hook: None,
+                    buffers,
+                    bytes_read: 0,
+                    streamed: false,
+                    poisoned: None,
                 })
             })
     }
 
+    /// Get an encrypted file by index without decrypting or decompressing it
+    ///
+    /// The data is read as it is stored in the archive: with ZipCrypto it starts with the
+    /// 12 byte encryption header, with AES it starts with the salt and password verification
+    /// value and ends with the authentication code. [`ZipFile::encryption_method`],
+    /// [`ZipFile::compression`], [`ZipFile::crc32`] and the sizes of the file describe how to
+    /// decrypt and decompress it, so the data can be passed on without knowing the password.
+    ///
+    /// Returns an error if the file is not encrypted.
+    pub fn by_index_raw_encrypted<'a>(&'a mut self, file_number: usize) -> ZipResult<ZipFile<'a>> {
+        let data = self.files.get(file_number).ok_or(ZipError::FileNotFound)?;
+        if !data.encrypted {
+            return unsupported_zip_error("The file is not encrypted");
+        }
+        self.by_index_raw(file_number)
+    }
+
     fn by_index_with_optional_password<'a>(
         &'a mut self,
         file_number: usize,
@@ -647,6 +2429,9 @@ impl<R: Read + io::Seek> ZipArchive<R> {
             return Err(ZipError::FileNotFound);
         }
         let data = &mut self.files[file_number];
+        if let Some((algorithm, _)) = data.strong_encryption {
+            return Err(ZipError::UnsupportedEncryption(algorithm));
+        }
 
         match (password, data.encrypted) {
             (None, true) => {
@@ -657,13 +2442,41 @@ impl<R: Read + io::Seek> ZipArchive<R> {
             (Some(_), false) => password = None, //Password supplied, but none needed! Discard.
             _ => {}
         }
-        let limit_reader = find_content(data, &mut self.reader)?;
+        let decompressor = self.decompressor(file_number)?;
+        let aes_info = self.aes_info(file_number);
+        let data = &mut self.files[file_number];
+        let size = stored_size(self.config.stored_sizes, data);
+        let mut limit_reader = find_content(data, &mut self.reader)?;
+        let data = match size {
+            Some(size) => {
+                limit_reader.set_limit(size);
+                Cow::Owned(ZipFileData {
+                    compressed_size: size,
+                    uncompressed_size: size,
+                    ..data.clone()
+                })
+            }
+            None => Cow::Borrowed(&*data),
+        };
 
-        match make_crypto_reader(data.compression_method, data.crc32, limit_reader, password) {
+        match make_crypto_reader(
+            data.compression_method,
+            data.zipcrypto_check_bytes(),
+            limit_reader,
+            password,
+            aes_info,
+            decompressor.is_some(),
+        ) {
             Ok(Ok(crypto_reader)) => Ok(Ok(ZipFile {
                 crypto_reader: Some(crypto_reader),
                 reader: ZipFileReader::NoReader,
-                data: Cow::Borrowed(data),
+                data,
+                decompressor,
+                read_hook: self.read_hook.get_arc(),
+                buffers: self.buffers.clone(),
+                bytes_read: 0,
+                streamed: false,
+                poisoned: None,
             })),
             Err(e) => Err(e),
             Ok(Err(e)) => Ok(Err(e)),
@@ -676,27 +2489,297 @@ impl<R: Read + io::Seek> ZipArchive<R> {
     pub fn into_inner(self) -> R {
         self.reader
     }
-}
 
-#[cfg(feature = "async")]
-impl<R: AsyncRead + AsyncSeek + Send + Sync + Unpin> AsyncZipArchive<R> {
-    /// Read a ZIP archive, collecting the files it contains
+    /// The files, the start of the central directory, the comment and the ZIP64 extensible data
+    /// sector of the archive, for [`ZipWriter::new_append`](crate::ZipWriter::new_append)
     ///
-    /// This uses the central directory record of the ZIP file, and ignores local file headers
-    pub async fn new(mut reader: R) -> ZipResult<Self> {
-        let mut preader = Pin::new(&mut reader);
-        let (footer, cde_start_pos) =
-            spec::CentralDirectoryEnd::find_and_parse_async(preader.as_mut()).await?;
-
-        if footer.disk_number != footer.disk_with_central_directory {
-            return unsupported_zip_error("Support for multi-disk files is not implemented");
+    /// The files keep their central directory records unless the archive has an offset, which
+    /// the offsets in the records don't account for.
+    pub(crate) fn into_append_parts(mut self) -> ZipResult<AppendParts> {
+        if self.offset == 0 {
+            for file in &mut self.files {
+                self.reader
+                    .seek(io::SeekFrom::Start(file.central_header_start))?;
+                file.central_header = Some(read_central_header(&mut self.reader)?);
+            }
         }
+        Ok((
+            self.files,
+            self.directory_start,
+            self.comment,
+            self.zip64_extensible_data,
+        ))
+    }
+}
 
-        let (archive_offset, directory_start, number_of_files) =
-            Self::get_directory_counts(&mut preader.as_mut(), &footer, cde_start_pos).await?;
-
-        let mut files = Vec::new();
-        let mut names_map = HashMap::new();
+impl<B: AsRef<[u8]>> ZipArchive<io::Cursor<B>> {
+    /// Get the data of a stored file as a slice of the archive, without copying it.
+    ///
+    /// This is for archives that are in memory, or mapped into memory like with
+    /// `ZipArchive::open_mmap`. Compressed files of such an archive are read with
+    /// [`ZipArchive::by_index`], which decompresses them straight from memory.
+    ///
+    /// Returns an error if the file is compressed or encrypted. The CRC-32 is not checked.
+    pub fn stored_data(&self, file_number: usize) -> ZipResult<&[u8]> {
+        let data = self.files.get(file_number).ok_or(ZipError::FileNotFound)?;
+        if data.encrypted {
+            return unsupported_zip_error("The file is encrypted");
+        }
+        if data.compression_method != CompressionMethod::Stored {
+            return unsupported_zip_error("The file is compressed");
+        }
+
+        let archive = self.reader.get_ref().as_ref();
+        let slice = |start: u64, length: u64| {
+            use std::convert::TryFrom;
+            let start = usize::try_from(start).ok()?;
+            archive.get(start..start.checked_add(usize::try_from(length).ok()?)?)
+        };
+        let header = slice(data.header_start, 30)
+            .ok_or(ZipError::InvalidArchive("Invalid local file header"))?;
+        if header[..4] != spec::LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes() {
+            return Err(ZipError::InvalidArchive("Invalid local file header"));
+        }
+        let file_name_length = u16::from_le_bytes([header[26], header[27]]) as u64;
+        let extra_field_length = u16::from_le_bytes([header[28], header[29]]) as u64;
+        let size = stored_size(self.config.stored_sizes, data).unwrap_or(data.compressed_size);
+        let start = data.header_start + 30 + file_name_length + extra_field_length;
+        slice(start, size).ok_or(ZipError::InvalidArchive(
+            "The file data extends past the archive",
+        ))
+    }
+}
+
+#[cfg(all(feature = "mmap", unix))]
+impl ZipArchive<io::Cursor<crate::mmap::Mmap>> {
+    /// Read the archive at `path` by mapping it into memory, see the [`mmap`](crate::mmap)
+    /// module.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be written to or truncated until the archive is dropped, see
+    /// [`Mmap::map`](crate::mmap::Mmap::map).
+    pub unsafe fn open_mmap<P: AsRef<Path>>(path: P) -> ZipResult<crate::mmap::MmapArchive> {
+        ZipArchive::new(io::Cursor::new(crate::mmap::Mmap::open(path)?))
+    }
+}
+
+impl<D: Read + io::Seek> ZipArchive<MultiDiskReader<D>> {
+    /// Read an archive that is split or spanned over several disks, which are opened with
+    /// `provider`.
+    ///
+    /// The central directory and the entries are found on the disks their records point to, and
+    /// entries that continue on the next disk are read across it. An archive that was cut into
+    /// pieces after it was written is read from all of its disks as one stream, if the provider
+    /// knows how many there are, see [`DiskProvider::disk_count`].
+    pub fn new_multidisk<P: DiskProvider<Disk = D>>(
+        mut provider: P,
+    ) -> ZipResult<ZipArchive<MultiDiskReader<D>>> {
+        let config = Config::default();
+        let mut last = provider.open_last()?;
+        let end = spec::CentralDirectoryEnd::find_and_parse(
+            &mut last,
+            config.max_search_distance,
+            config.trailing_data,
+        );
+
+        // An archive that was cut into pieces has no disk numbers, and the end of its central
+        // directory may even be cut itself
+        let cut = match &end {
+            Ok((footer, _)) => footer.disk_number == 0 && footer.disk_with_central_directory == 0,
+            Err(_) => true,
+        };
+        if cut {
+            match provider.disk_count()? {
+                Some(count) if count > 1 => {
+                    let mut disks = Vec::new();
+                    for number in 0..count - 1 {
+                        disks.push(provider.open(number)?);
+                    }
+                    disks.push(last);
+                    return ZipArchive::new(MultiDiskReader::new(disks)?);
+                }
+                _ => {}
+            }
+        }
+        let (footer, cde_start_pos) = end?;
+
+        // The ZIP64 locator is on the last disk, right in front of the standard footer
+        let locator = match cde_start_pos.checked_sub(20) {
+            Some(locator_start) => {
+                last.seek(io::SeekFrom::Start(locator_start))?;
+                match spec::Zip64CentralDirectoryEndLocator::parse(&mut last) {
+                    Ok(locator) => Some(locator),
+                    Err(ZipError::InvalidArchive(_)) => None,
+                    Err(e) => return Err(e),
+                }
+            }
+            None => None,
+        };
+        let number_of_disks = match &locator {
+            Some(locator) => locator.number_of_disks,
+            None => footer.disk_number as u32 + 1,
+        };
+        if number_of_disks == 0 {
+            return Err(ZipError::InvalidArchive("Invalid number of disks"));
+        }
+
+        let mut disks = Vec::new();
+        for number in 0..number_of_disks - 1 {
+            disks.push(provider.open(number)?);
+        }
+        disks.push(last);
+        let mut reader = MultiDiskReader::new(disks)?;
+
+        let (directory_start, number_of_files, zip64_extensible_data) = match locator {
+            None => (
+                disk_offset(
+                    &reader,
+                    footer.disk_with_central_directory as u32,
+                    footer.central_directory_offset as u64,
+                )?,
+                footer.number_of_files as usize,
+                None,
+            ),
+            Some(locator) => {
+                let footer_start = disk_offset(
+                    &reader,
+                    locator.disk_with_central_directory,
+                    locator.end_of_central_directory_offset,
+                )?;
+                let (footer, _) = spec::Zip64CentralDirectoryEnd::find_and_parse(
+                    &mut reader,
+                    footer_start,
+                    footer_start,
+                )?;
+                if let Some(algorithm) = footer.central_directory_encryption {
+                    let algorithm = StrongEncryptionAlgorithm::from_u16(algorithm);
+                    return Err(ZipError::EncryptedCentralDirectory(algorithm));
+                }
+                (
+                    disk_offset(
+                        &reader,
+                        footer.disk_with_central_directory,
+                        footer.central_directory_offset,
+                    )?,
+                    footer.number_of_files as usize,
+                    Some(footer.extensible_data_sector),
+                )
+            }
+        };
+
+        let mut files = Vec::new();
+
+        reader.seek(io::SeekFrom::Start(directory_start))?;
+        for _ in 0..number_of_files {
+            let mut file = central_header_to_zip_file(&mut reader, 0)?;
+            file.header_start = disk_offset(&reader, file.disk_number, file.header_start)?;
+            files.push(file);
+        }
+        let digital_signature = read_digital_signature(&mut reader)?;
+
+        if let Some(checksum) = central_directory_checksum(zip64_extensible_data.as_deref())? {
+            reader.seek(io::SeekFrom::Start(directory_start))?;
+            let directory = read_central_directory(&mut reader, number_of_files)?;
+            check_central_directory(&directory, checksum)?;
+        }
+        let zip64_end_lookup = zip64_extensible_data
+            .as_ref()
+            .map(|_| Zip64EndLookup::Locator);
+
+        let names = NameIndex::new(&mut files);
+        Ok(ZipArchive {
+            reader,
+            files,
+            claimed_len: number_of_files,
+            names,
+            offset: 0,
+            directory_start,
+            comment: footer.zip_file_comment,
+            zip64_extensible_data,
+            zip64_end_lookup,
+            end_record_mismatch: false,
+            digital_signature,
+            config,
+            decompressors: Codecs::default(),
+            #[cfg(feature = "deflate")]
+            dictionaries: deflate::Dictionaries::default(),
+            aes_iteration_count: AES_ITERATION_COUNT,
+            read_hook: Hook::default(),
+            crc_retry: Hook::default(),
+            buffers: BufferPool::new(config.buffer_size),
+        })
+    }
+}
+
+/// Set the unix `mode` of an extracted file
+#[cfg(any(feature = "parallel", all(feature = "io-uring", target_os = "linux")))]
+fn set_permissions(path: &Path, mode: Option<u32>) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Some(mode) = mode {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+    Ok(())
+}
+
+/// The position of `offset` on the disk with the given number in the stream of all disks
+fn disk_offset<D>(reader: &MultiDiskReader<D>, disk: u32, offset: u64) -> ZipResult<u64> {
+    reader
+        .disk_start(disk)
+        .and_then(|start| start.checked_add(offset))
+        .ok_or(ZipError::InvalidArchive("Invalid disk number"))
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + AsyncSeek + Send + Sync + Unpin> AsyncZipArchive<R> {
+    /// Read a ZIP archive, collecting the files it contains
+    ///
+    /// This uses the central directory record of the ZIP file, and ignores local file headers
+    pub async fn new(reader: R) -> ZipResult<Self> {
+        Self::new_with_config(reader, Config::default()).await
+    }
+
+    /// Read a ZIP archive, with the given options.
+    ///
+    /// See [`ZipArchive::new_with_config`].
+    pub async fn new_with_config(mut reader: R, config: Config) -> ZipResult<Self> {
+        let deadline = Deadline::after(config.time_limit);
+        let mut preader = Pin::new(&mut reader);
+        let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse_async(
+            preader.as_mut(),
+            config.max_search_distance,
+            config.trailing_data,
+        )
+        .await?;
+
+        if footer.disk_number != 0 || footer.disk_with_central_directory != 0 {
+            return multi_disk_error(
+                footer.disk_number as u32,
+                footer.disk_with_central_directory as u32,
+            );
+        }
+
+        let (
+            archive_offset,
+            directory_start,
+            number_of_files,
+            zip64_extensible_data,
+            zip64_end_lookup,
+            end_record_mismatch,
+        ) = Self::get_directory_counts(
+            &mut preader.as_mut(),
+            &footer,
+            cde_start_pos,
+            config.end_record_precedence,
+        )
+        .await?;
+
+        let mut files = Vec::new();
 
         if let Err(_) = preader
             .as_mut()
@@ -708,18 +2791,45 @@ impl<R: AsyncRead + AsyncSeek + Send + Sync + Unpin> AsyncZipArchive<R> {
             ));
         }
 
-        for _ in 0..number_of_files {
+        loop {
+            let more = match config.record_count {
+                RecordCountPolicy::Claimed => files.len() < number_of_files,
+                RecordCountPolicy::Found => at_central_header_async(preader.as_mut()).await?,
+            };
+            if !more {
+                break;
+            }
+            deadline.check()?;
             let file = central_header_to_zip_file_async(preader.as_mut(), archive_offset).await?;
-            names_map.insert(file.file_name.clone(), files.len());
             files.push(file);
         }
+        let digital_signature = read_digital_signature_async(preader.as_mut()).await?;
+
+        if let Some(checksum) = central_directory_checksum(zip64_extensible_data.as_deref())? {
+            preader
+                .as_mut()
+                .seek(io::SeekFrom::Start(directory_start))
+                .await?;
+            let directory = read_central_directory_async(preader.as_mut(), files.len()).await?;
+            check_central_directory(&directory, checksum)?;
+        }
 
+        let names = NameIndex::new(&mut files);
         Ok(Self {
             reader,
             files,
-            names_map,
+            claimed_len: number_of_files,
+            names,
             offset: archive_offset,
+            directory_start,
             comment: footer.zip_file_comment,
+            zip64_extensible_data,
+            zip64_end_lookup,
+            end_record_mismatch,
+            digital_signature,
+            config,
+            aes_iteration_count: AES_ITERATION_COUNT,
+            cancellation: Cancellation::default(),
         })
     }
 }
@@ -732,7 +2842,8 @@ impl<R: AsyncRead + AsyncSeek + Send + Sync + Unpin> AsyncZipArchive<R> {
         reader: &mut R,
         footer: &spec::CentralDirectoryEnd,
         cde_start_pos: u64,
-    ) -> ZipResult<(u64, u64, usize)> {
+        precedence: EndRecordPrecedence,
+    ) -> ZipResult<DirectoryCounts> {
         // See if there's a ZIP64 footer. The ZIP64 locator if present will
         // have its signature 20 bytes in front of the standard footer. The
         // standard footer, in turn, is 22+N bytes large, where N is the
@@ -776,14 +2887,22 @@ impl<R: AsyncRead + AsyncSeek + Send + Sync + Unpin> AsyncZipArchive<R> {
 
                 let directory_start = footer.central_directory_offset as u64 + archive_offset;
                 let number_of_files = footer.number_of_files_on_this_disk as usize;
-                Ok((archive_offset, directory_start, number_of_files))
+                Ok((
+                    archive_offset,
+                    directory_start,
+                    number_of_files,
+                    None,
+                    None,
+                    false,
+                ))
             }
             Some(locator64) => {
                 // If we got here, this is indeed a ZIP64 file.
 
                 if footer.disk_number as u32 != locator64.disk_with_central_directory {
-                    return unsupported_zip_error(
-                        "Support for multi-disk files is not implemented",
+                    return multi_disk_error(
+                        footer.disk_number as u32,
+                        locator64.disk_with_central_directory,
                     );
                 }
 
@@ -800,31 +2919,54 @@ impl<R: AsyncRead + AsyncSeek + Send + Sync + Unpin> AsyncZipArchive<R> {
                     .ok_or(ZipError::InvalidArchive(
                         "File cannot contain ZIP64 central directory end",
                     ))?;
-                let (footer, archive_offset) =
-                    spec::Zip64CentralDirectoryEnd::find_and_parse_async(
-                        Pin::new(reader),
-                        locator64.end_of_central_directory_offset,
-                        search_upper_bound,
-                    )
-                    .await?;
+                let found = spec::Zip64CentralDirectoryEnd::find_and_parse_async(
+                    Pin::new(&mut *reader),
+                    locator64.end_of_central_directory_offset,
+                    search_upper_bound,
+                )
+                .await;
+                let (footer64, archive_offset, lookup) = match found {
+                    Ok((footer64, archive_offset)) => {
+                        (footer64, archive_offset, Zip64EndLookup::Locator)
+                    }
+                    Err(e) => {
+                        // See `ZipArchive::get_directory_counts`
+                        let (footer64, pos) = spec::Zip64CentralDirectoryEnd::find_before_async(
+                            Pin::new(reader),
+                            cde_start_pos - 20,
+                            spec::MAX_ZIP64_END_DISTANCE,
+                        )
+                        .await
+                        .map_err(|_| e)?;
+                        let archive_offset = zip64_archive_offset(&footer64, pos)?;
+                        (footer64, archive_offset, Zip64EndLookup::Scan)
+                    }
+                };
 
-                if footer.disk_number != footer.disk_with_central_directory {
-                    return unsupported_zip_error(
-                        "Support for multi-disk files is not implemented",
+                if footer64.disk_number != footer64.disk_with_central_directory {
+                    return multi_disk_error(
+                        footer64.disk_number,
+                        footer64.disk_with_central_directory,
                     );
                 }
+                if let Some(algorithm) = footer64.central_directory_encryption {
+                    let algorithm = StrongEncryptionAlgorithm::from_u16(algorithm);
+                    return Err(ZipError::EncryptedCentralDirectory(algorithm));
+                }
 
-                let directory_start = footer
-                    .central_directory_offset
-                    .checked_add(archive_offset)
-                    .ok_or_else(|| {
-                        ZipError::InvalidArchive("Invalid central directory size or offset")
-                    })?;
+                let (directory_offset, number_of_files, mismatch) =
+                    resolve_end_records(footer, &footer64, precedence);
+                let directory_start = directory_offset.checked_add(archive_offset).ok_or(
+                    ZipError::InvalidArchive("Invalid central directory size or offset"),
+                )?;
 
                 Ok((
                     archive_offset,
                     directory_start,
-                    footer.number_of_files as usize,
+                    number_of_files,
+                    Some(footer64.extensible_data_sector),
+                    Some(lookup),
+                    mismatch,
                 ))
             }
         }
@@ -835,11 +2977,49 @@ impl<R: AsyncRead + AsyncSeek + Send + Sync + Unpin> AsyncZipArchive<R> {
         self.files.len()
     }
 
+    /// See [`ZipArchive::claimed_len`].
+    pub fn claimed_len(&self) -> usize {
+        self.claimed_len
+    }
+
+    /// Set a flag that cancels the operations that read many files, like
+    /// [`AsyncZipArchive::extract_to_map`], `extract_async` and `scan`, before the next file.
+    ///
+    /// They fail with [`ZipError::Cancelled`] once the flag is set. Each file is read from its
+    /// own position in the reader, so the archive can still be used after an operation is
+    /// cancelled, whether with this flag or by dropping its future. Set a new flag to resume.
+    pub fn set_cancellation(&mut self, cancellation: Cancellation) {
+        self.cancellation = cancellation;
+    }
+
+    /// See [`ZipArchive::set_aes_iteration_count`].
+    #[cfg(feature = "aes-crypto")]
+    pub fn set_aes_iteration_count(&mut self, count: u32) {
+        self.aes_iteration_count = count;
+    }
+
     /// Whether this zip archive contains no files
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
+    /// See [`ZipArchive::validate`].
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        validation_issues(&self.files, self.claimed_len, self.end_record_mismatch)
+    }
+
+    /// Get the mechanism used to encrypt the file at `file_number`, or `None` if it is not
+    /// encrypted.
+    ///
+    /// This does not read the file, so it can be used to find out which password to ask for
+    /// before calling [`by_index_decrypt`](Self::by_index_decrypt).
+    pub fn encryption_method(&self, file_number: usize) -> ZipResult<Option<EncryptionMethod>> {
+        self.files
+            .get(file_number)
+            .map(ZipFileData::encryption_method)
+            .ok_or(ZipError::FileNotFound)
+    }
+
     /// Get the offset from the beginning of the underlying reader that this zip begins at, in bytes.
     ///
     /// Normally this value is zero, but if the zip has arbitrary data prepended to it, then this value will be the size
@@ -853,9 +3033,86 @@ impl<R: AsyncRead + AsyncSeek + Send + Sync + Unpin> AsyncZipArchive<R> {
         &self.comment
     }
 
+    /// Get the extensible data sector of the ZIP64 central directory end record, or `None` if the
+    /// archive has no such record.
+    ///
+    /// Use [`ExtensibleDataBlock::parse_all`] to read the blocks of the data.
+    ///
+    /// [`ExtensibleDataBlock::parse_all`]: crate::ExtensibleDataBlock::parse_all
+    pub fn zip64_extensible_data(&self) -> Option<&[u8]> {
+        self.zip64_extensible_data.as_deref()
+    }
+
+    /// See [`ZipArchive::zip64_end_lookup`].
+    pub fn zip64_end_lookup(&self) -> Option<Zip64EndLookup> {
+        self.zip64_end_lookup
+    }
+
+    /// Get the signature of the digital signature record at the end of the central directory.
+    ///
+    /// See [`ZipArchive::digital_signature`].
+    pub fn digital_signature(&self) -> Option<&[u8]> {
+        self.digital_signature.as_deref()
+    }
+
+    /// Check the digital signature of the central directory with `verifier`.
+    ///
+    /// See [`ZipArchive::verify_digital_signature`].
+    pub async fn verify_digital_signature<V: Verifier + ?Sized>(
+        &mut self,
+        verifier: &V,
+    ) -> ZipResult<()> {
+        let signature = match &self.digital_signature {
+            Some(signature) => signature,
+            None => {
+                return Err(ZipError::InvalidArchive(
+                    "The archive has no digital signature",
+                ))
+            }
+        };
+        self.reader
+            .seek(io::SeekFrom::Start(self.directory_start))
+            .await?;
+        let directory = read_central_directory_async(&mut self.reader, self.files.len()).await?;
+        if !verifier.verify(&directory, signature) {
+            return Err(ZipError::InvalidArchive("Invalid digital signature"));
+        }
+        Ok(())
+    }
+
+    /// See [`ZipArchive::canonical_contents`].
+    pub fn canonical_contents(&self) -> Vec<u8> {
+        signature::canonical_contents(&self.files)
+    }
+
+    /// See [`ZipArchive::embedded_signature`].
+    pub fn embedded_signature(&self) -> Option<Vec<u8>> {
+        embedded_signature(self.zip64_extensible_data.as_deref())
+    }
+
+    /// See [`ZipArchive::verify_detached_signature`].
+    pub fn verify_detached_signature<V: Verifier + ?Sized>(
+        &self,
+        verifier: &V,
+        signature: &[u8],
+    ) -> ZipResult<()> {
+        verify_detached_signature(&self.files, verifier, signature)
+    }
+
     /// Returns an iterator over all the file and directory names in this archive.
     pub fn file_names(&self) -> impl Iterator<Item = &str> {
-        self.names_map.keys().map(|s| s.as_str())
+        self.names.names(&self.files)
+    }
+
+    /// Returns a stream of the central directory records of the files in this archive, in the
+    /// order of the central directory.
+    ///
+    /// The records were read when the archive was opened, see [`ZipArchive::stream_entries`]
+    /// for reading them without opening the archive. This is for stream combinators like
+    /// `for_each_concurrent`.
+    pub fn entries_stream(&self) -> impl Stream<Item = EntryRecord> + '_ {
+        let records = self.files.iter().cloned();
+        futures::stream::iter(records.map(|data| EntryRecord { data }))
     }
 
     /// Search for a file entry by name, decrypt with given password
@@ -881,8 +3138,8 @@ impl<R: AsyncRead + AsyncSeek + Send + Sync + Unpin> AsyncZipArchive<R> {
         name: &str,
         password: Option<&[u8]>,
     ) -> ZipResult<Result<AsyncZipFile<'a>, InvalidPassword>> {
-        let index = match self.names_map.get(name) {
-            Some(index) => *index,
+        let index = match self.names.get(&self.files, name) {
+            Some(index) => index,
             None => {
                 return Err(ZipError::FileNotFound);
             }
@@ -891,6 +3148,8 @@ impl<R: AsyncRead + AsyncSeek + Send + Sync + Unpin> AsyncZipArchive<R> {
     }
 
     /// Get a contained file by index, decrypt with given password
+    ///
+    /// The password only applies to this file, so files can be encrypted with different passwords.
     pub async fn by_index_decrypt<'a>(
         &'a mut self,
         file_number: usize,
@@ -900,6 +3159,156 @@ impl<R: AsyncRead + AsyncSeek + Send + Sync + Unpin> AsyncZipArchive<R> {
             .await
     }
 
+    /// Guess the type of the content of the file at `file_number` from its first bytes
+    ///
+    /// Only the start of the file is decompressed, see [`sniff::sniff`] for how the type is
+    /// guessed. Returns an error if the file is encrypted.
+    #[cfg(feature = "sniff")]
+    pub async fn content_type(&mut self, file_number: usize) -> ZipResult<Option<ContentType>> {
+        let mut start = Vec::with_capacity(SNIFF_LENGTH);
+        self.by_index(file_number)
+            .await?
+            .take(SNIFF_LENGTH as u64)
+            .read_to_end(&mut start)
+            .await?;
+        Ok(sniff::sniff(&start))
+    }
+
+    /// Find the files that can't be inspected, because they are encrypted, are archives with
+    /// encrypted files or look like random data
+    ///
+    /// Only the start of each file is decompressed, see [`sniff::scan`] for how the content is
+    /// checked. Directories are skipped. Returns the flagged files in the order of the archive.
+    #[cfg(feature = "sniff")]
+    pub async fn scan(&mut self) -> ZipResult<Vec<FlaggedFile>> {
+        let mut flagged = Vec::new();
+        let mut start = Vec::with_capacity(SCAN_LENGTH);
+        for index in 0..self.len() {
+            self.cancellation.check()?;
+            let flag = if self.files[index].encrypted {
+                Some(ScanFlag::Encrypted)
+            } else {
+                let file = self.by_index(index).await?;
+                if file.is_dir() {
+                    continue;
+                }
+                start.clear();
+                file.take(SCAN_LENGTH as u64)
+                    .read_to_end(&mut start)
+                    .await?;
+                sniff::scan(&start)
+            };
+            if let Some(flag) = flag {
+                flagged.push(FlaggedFile {
+                    index,
+                    name: self.files[index].file_name.to_string(),
+                    flag,
+                });
+            }
+        }
+        Ok(flagged)
+    }
+
+    /// Read the files of the archive into a map from their names to their contents.
+    ///
+    /// See [`ZipArchive::extract_to_map`] for how `size_limit` applies.
+    pub async fn extract_to_map(&mut self, size_limit: u64) -> ZipResult<HashMap<String, Vec<u8>>> {
+        let deadline = Deadline::after(self.config.time_limit);
+        let mut files = HashMap::new();
+        let mut total_size = 0;
+        for i in 0..self.len() {
+            deadline.check()?;
+            self.cancellation.check()?;
+            let file = self.by_index(i).await?;
+            if file.is_dir() {
+                continue;
+            }
+            let name = file.name().to_owned();
+            let remaining = size_limit - total_size;
+            if file.size() > remaining {
+                return Err(ZipError::SizeLimitExceeded(Some(name)));
+            }
+            let mut data = Vec::with_capacity(file.size() as usize);
+            file.take(remaining.saturating_add(1))
+                .read_to_end(&mut data)
+                .await?;
+            if data.len() as u64 > remaining {
+                return Err(ZipError::SizeLimitExceeded(Some(name)));
+            }
+            total_size += data.len() as u64;
+            files.insert(name, data);
+        }
+        Ok(files)
+    }
+
+    /// Extract the archive into a directory with `tokio::fs`, like [`ZipArchive::extract`] with
+    /// the default [`ExtractOptions`].
+    ///
+    /// The data of each file is read into memory, and written while the files after it are read.
+    /// At most `concurrency` files are written at the same time.
+    #[cfg(feature = "tokio")]
+    pub async fn extract_async<P: AsRef<Path>>(
+        &mut self,
+        directory: P,
+        concurrency: usize,
+    ) -> ZipResult<ExtractSummary> {
+        use futures::channel::mpsc;
+        use futures::{SinkExt, StreamExt, TryFutureExt, TryStreamExt};
+
+        let directory = directory.as_ref();
+        let deadline = Deadline::after(self.config.time_limit);
+        let (mut sender, receiver) = mpsc::channel(0);
+        let writes = receiver
+            .map(|(path, data, mode)| write_extracted(path, data, mode))
+            .buffer_unordered(concurrency.max(1))
+            .try_filter_map(|overwritten| futures::future::ready(Ok(overwritten)))
+            .try_collect::<Vec<_>>()
+            .map_err(ZipError::from);
+        let reads = async move {
+            let mut summary = ExtractSummary::default();
+            for i in 0..self.len() {
+                deadline.check()?;
+                self.cancellation.check()?;
+                let mut file = self.by_index(i).await?;
+                let outpath = match extraction_path(file.name()) {
+                    Some(path) => {
+                        check_parents(directory, path, file.name().ends_with('/'))?;
+                        directory.join(path)
+                    }
+                    None => return Err(ZipError::InvalidArchive("Invalid file path")),
+                };
+                let name = file.name().to_owned();
+                let mode = file.unix_mode();
+                if name.ends_with('/') {
+                    drop(file);
+                    tokio::fs::create_dir_all(&outpath).await?;
+                    set_mode(&outpath, mode).await?;
+                    summary.directories_created += 1;
+                    summary.push(i, name, ExtractOutcome::Directory(outpath));
+                    continue;
+                }
+                let mut data = Vec::new();
+                file.read_to_end(&mut data).await?;
+                summary.files_written += 1;
+                summary.bytes_written += data.len() as u64;
+                let outcome = ExtractOutcome::File {
+                    path: outpath.clone(),
+                    size: data.len() as u64,
+                };
+                summary.push(i, name, outcome);
+                if sender.send((outpath, data, mode)).await.is_err() {
+                    // Writing failed, which is the error that is returned
+                    break;
+                }
+            }
+            ZipResult::Ok(summary)
+        };
+        let (mut summary, overwritten) = futures::future::try_join(reads, writes).await?;
+        let warnings = overwritten.into_iter().map(ExtractWarning::Overwritten);
+        summary.warnings.extend(warnings);
+        Ok(summary)
+    }
+
     /// Get a contained file by index
     pub async fn by_index<'a>(
         self: &'a mut Self,
@@ -920,7 +3329,16 @@ impl<R: AsyncRead + AsyncSeek + Send + Sync + Unpin> AsyncZipArchive<R> {
             return Err(ZipError::FileNotFound);
         }
 
+        let aes_iteration_count = self.aes_iteration_count;
         let data = &mut self.files[file_number];
+        if let Some((algorithm, _)) = data.strong_encryption {
+            return Err(ZipError::UnsupportedEncryption(algorithm));
+        }
+        if has_deflate_dictionary(data) {
+            return unsupported_zip_error(
+                "Deflate dictionaries are not supported by the async reader",
+            );
+        }
 
         match (password, data.encrypted) {
             (None, true) => {
@@ -932,35 +3350,37 @@ impl<R: AsyncRead + AsyncSeek + Send + Sync + Unpin> AsyncZipArchive<R> {
             _ => {}
         }
 
-        // Parse local header
-        self.reader
-            .seek(io::SeekFrom::Start(data.header_start))
-            .await?;
-        let signature = self.reader.compat_mut().read_u32_le().await?;
-        if signature != spec::LOCAL_FILE_HEADER_SIGNATURE {
-            return Err(ZipError::InvalidArchive("Invalid local file header"));
-        }
-
-        self.reader.seek(io::SeekFrom::Current(22)).await?;
-        let file_name_length = self.reader.compat_mut().read_u16_le().await? as u64;
-        let extra_field_length = self.reader.compat_mut().read_u16_le().await? as u64;
-        let magic_and_header = 4 + 22 + 2 + 2;
-        data.data_start =
-            data.header_start + magic_and_header + file_name_length + extra_field_length;
-
-        self.reader
-            .seek(io::SeekFrom::Start(data.data_start))
-            .await?;
-        let limit_reader =
-            (Pin::new(&mut self.reader) as Pin<&'a mut (dyn AsyncRead + Send + Sync)>).take(data.compressed_size);
+        find_content_async(data, &mut self.reader).await?;
+        let size = stored_size(self.config.stored_sizes, data);
+        let limit_reader = (Pin::new(&mut self.reader)
+            as Pin<&'a mut (dyn AsyncRead + Send + Sync)>)
+            .take(size.unwrap_or(data.compressed_size));
+        let data = match size {
+            Some(size) => Cow::Owned(ZipFileData {
+                compressed_size: size,
+                uncompressed_size: size,
+                ..data.clone()
+            }),
+            None => Cow::Borrowed(&*data),
+        };
 
-        match make_crypto_reader_async(data.compression_method, data.crc32, limit_reader, password)
-            .await
+        match make_crypto_reader_async(
+            data.compression_method,
+            data.zipcrypto_check_bytes(),
+            limit_reader,
+            password,
+            data.aes_mode
+                .map(|(mode, vendor_version)| (mode, vendor_version, aes_iteration_count)),
+        )
+        .await
         {
             Ok(Ok(crypto_reader)) => Ok(Ok(AsyncZipFile {
                 crypto_reader: Some(crypto_reader),
                 reader: AsyncZipFileReader::NoReader,
-                data: Cow::Borrowed(data),
+                data,
+                buffer: Vec::new(),
+                buffer_start: 0,
+                bytes_read: 0,
             })),
             Err(e) => Err(e),
             Ok(Err(e)) => Ok(Err(e)),
@@ -973,12 +3393,83 @@ impl<R: AsyncRead + AsyncSeek + Send + Sync + Unpin> AsyncZipArchive<R> {
     pub fn into_inner(self) -> R {
         self.reader
     }
+
+    /// Unwrap the inner reader, limited to the data of the file `name` as it is stored, along
+    /// with the file.
+    #[cfg(feature = "http")]
+    pub(crate) async fn into_stored_data(
+        mut self,
+        name: &str,
+    ) -> ZipResult<(futures::io::Take<R>, ZipFileData)> {
+        let index = self
+            .names
+            .get(&self.files, name)
+            .ok_or(ZipError::FileNotFound)?;
+        let mut data = self.files.swap_remove(index);
+        find_content_async(&mut data, &mut self.reader).await?;
+        Ok((self.reader.take(data.compressed_size), data))
+    }
+}
+
+/// Seek to the data of the file, past its local header
+#[cfg(feature = "async")]
+async fn find_content_async<R: AsyncRead + AsyncSeek + Unpin>(
+    data: &mut ZipFileData,
+    reader: &mut R,
+) -> ZipResult<()> {
+    // Parse local header
+    reader.seek(io::SeekFrom::Start(data.header_start)).await?;
+    let signature = read_u32_le(reader).await?;
+    if signature != spec::LOCAL_FILE_HEADER_SIGNATURE {
+        return Err(ZipError::InvalidArchive("Invalid local file header"));
+    }
+
+    reader.seek(io::SeekFrom::Current(22)).await?;
+    let file_name_length = read_u16_le(reader).await? as u64;
+    let extra_field_length = read_u16_le(reader).await? as u64;
+    let magic_and_header = 4 + 22 + 2 + 2;
+    data.data_start = data.header_start + magic_and_header + file_name_length + extra_field_length;
+
+    reader.seek(io::SeekFrom::Start(data.data_start)).await?;
+    Ok(())
+}
+
+/// Whether the file is compressed with a preset dictionary, which is only supported by
+/// [`ZipArchive`]
+pub(crate) fn has_deflate_dictionary(data: &ZipFileData) -> bool {
+    data.deflate_dictionary.is_some() && data.compression_method == CompressionMethod::DEFLATE
 }
 
 fn unsupported_zip_error<T>(detail: &'static str) -> ZipResult<T> {
     Err(ZipError::UnsupportedArchive(detail))
 }
 
+fn multi_disk_error<T>(disk_number: u32, disk_with_central_directory: u32) -> ZipResult<T> {
+    Err(ZipError::MultiDiskUnsupported {
+        disk_number,
+        disk_with_central_directory,
+    })
+}
+
+/// Decode the name of a file, which is returned with the raw name if that is not the same as the
+/// decoded name, see [`ZipFileData::file_name_raw`]
+fn decode_file_name(raw: Vec<u8>, is_utf8: bool) -> (String, Vec<u8>) {
+    if is_utf8 {
+        return match String::from_utf8(raw) {
+            Ok(name) => (name, Vec::new()),
+            Err(e) => {
+                let raw = e.into_bytes();
+                (String::from_utf8_lossy(&raw).into_owned(), raw)
+            }
+        };
+    }
+    if raw.iter().all(|c| *c < 0x80) {
+        (raw.from_cp437(), Vec::new())
+    } else {
+        ((&*raw).from_cp437().into_owned(), raw)
+    }
+}
+
 fn central_header_to_zip_file<R: Read + io::Seek>(
     reader: &mut R,
     archive_offset: u64,
@@ -987,11 +3478,11 @@ fn central_header_to_zip_file<R: Read + io::Seek>(
     // Parse central header
     let signature = reader.read_u32::<LittleEndian>()?;
     if signature != spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE {
-        return Err(ZipError::InvalidArchive("Invalid Central Directory header"));
+        return Err(invalid_central_header(signature));
     }
 
     let version_made_by = reader.read_u16::<LittleEndian>()?;
-    let _version_to_extract = reader.read_u16::<LittleEndian>()?;
+    let version_to_extract = reader.read_u16::<LittleEndian>()?;
     let flags = reader.read_u16::<LittleEndian>()?;
     let encrypted = flags & 1 == 1;
     let is_utf8 = flags & (1 << 11) != 0;
@@ -1004,7 +3495,7 @@ fn central_header_to_zip_file<R: Read + io::Seek>(
     let file_name_length = reader.read_u16::<LittleEndian>()? as usize;
     let extra_field_length = reader.read_u16::<LittleEndian>()? as usize;
     let file_comment_length = reader.read_u16::<LittleEndian>()? as usize;
-    let _disk_number = reader.read_u16::<LittleEndian>()?;
+    let disk_number = reader.read_u16::<LittleEndian>()?;
     let _internal_file_attributes = reader.read_u16::<LittleEndian>()?;
     let external_file_attributes = reader.read_u32::<LittleEndian>()?;
     let offset = reader.read_u32::<LittleEndian>()? as u64;
@@ -1015,10 +3506,7 @@ fn central_header_to_zip_file<R: Read + io::Seek>(
     let mut file_comment_raw = vec![0; file_comment_length];
     reader.read_exact(&mut file_comment_raw)?;
 
-    let file_name = match is_utf8 {
-        true => String::from_utf8_lossy(&*file_name_raw).into_owned(),
-        false => file_name_raw.clone().from_cp437(),
-    };
+    let (file_name, file_name_raw) = decode_file_name(file_name_raw, is_utf8);
     let file_comment = match is_utf8 {
         true => String::from_utf8_lossy(&*file_comment_raw).into_owned(),
         false => file_comment_raw.from_cp437(),
@@ -1028,6 +3516,7 @@ fn central_header_to_zip_file<R: Read + io::Seek>(
     let mut result = ZipFileData {
         system: System::from_u8((version_made_by >> 8) as u8),
         version_made_by: version_made_by as u8,
+        version_to_extract,
         encrypted,
         compression_method: {
             #[allow(deprecated)]
@@ -1037,13 +3526,21 @@ fn central_header_to_zip_file<R: Read + io::Seek>(
         crc32,
         compressed_size: compressed_size as u64,
         uncompressed_size: uncompressed_size as u64,
-        file_name,
+        file_name: file_name.into(),
         file_name_raw,
         file_comment,
         header_start: offset,
+        disk_number: disk_number as u32,
         central_header_start,
         data_start: 0,
         external_attributes: external_file_attributes,
+        aes_mode: None,
+        strong_encryption: strong_encryption(flags),
+        deflate_dictionary: None,
+        large_file: false,
+        using_data_descriptor: flags & (1 << 3) != 0,
+        name_cache: NameCache::default(),
+        central_header: None,
     };
 
     match parse_extra_field(&mut result, &*extra_field) {
@@ -1057,74 +3554,325 @@ fn central_header_to_zip_file<R: Read + io::Seek>(
     Ok(result)
 }
 
+/// The raw central directory record that starts at the position of `reader`
+fn read_central_header<R: Read>(reader: &mut R) -> ZipResult<Vec<u8>> {
+    let mut record = vec![0; 46];
+    reader.read_exact(&mut record)?;
+    let field = |offset: usize| u16::from_le_bytes([record[offset], record[offset + 1]]) as usize;
+    let length = 46 + field(28) + field(30) + field(32);
+    record.resize(length, 0);
+    reader.read_exact(&mut record[46..])?;
+    Ok(record)
+}
+
+/// The central directory checksum in the ZIP64 extensible data sector, if it has one
+fn central_directory_checksum(extensible_data: Option<&[u8]>) -> ZipResult<Option<u32>> {
+    let blocks = match extensible_data.and_then(ExtensibleDataBlock::parse_all) {
+        Some(blocks) => blocks,
+        None => return Ok(None),
+    };
+    match blocks
+        .iter()
+        .rev()
+        .find(|block| block.header_id == ExtensibleDataBlock::CENTRAL_DIRECTORY_CHECKSUM)
+    {
+        Some(block) if block.data.len() == 4 => Ok(Some(u32::from_le_bytes([
+            block.data[0],
+            block.data[1],
+            block.data[2],
+            block.data[3],
+        ]))),
+        Some(_) => Err(ZipError::InvalidArchive(
+            "Invalid central directory checksum block",
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Whether a central directory header starts at the current position of `reader`, which is left
+/// unchanged
+fn at_central_header<R: Read + io::Seek>(mut reader: R) -> ZipResult<bool> {
+    let mut signature = [0; 4];
+    if reader.read_exact(&mut signature).is_err() {
+        return Ok(false);
+    }
+    reader.seek(io::SeekFrom::Current(-4))?;
+    Ok(u32::from_le_bytes(signature) == spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE)
+}
+
+/// Whether a central directory header starts at the current position of `reader`, which is left
+/// unchanged
 #[cfg(feature = "async")]
-async fn central_header_to_zip_file_async<R: AsyncRead + AsyncSeek>(
-    mut reader: Pin<&mut R>,
-    archive_offset: u64,
-) -> ZipResult<ZipFileData> {
-    let central_header_start = reader.seek(io::SeekFrom::Current(0)).await?;
-    let mut reader = reader.compat();
-    // Parse central header
-    let signature = reader.read_u32_le().await?;
-    if signature != spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE {
-        return Err(ZipError::InvalidArchive("Invalid Central Directory header"));
+async fn at_central_header_async<R: AsyncRead + AsyncSeek + Unpin>(
+    mut reader: R,
+) -> ZipResult<bool> {
+    let mut signature = [0; 4];
+    if reader.read_exact(&mut signature).await.is_err() {
+        return Ok(false);
     }
+    reader.seek(io::SeekFrom::Current(-4)).await?;
+    Ok(u32::from_le_bytes(signature) == spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE)
+}
 
-    let version_made_by = reader.read_u16_le().await?;
-    let _version_to_extract = reader.read_u16_le().await?;
-    let flags = reader.read_u16_le().await?;
-    let encrypted = flags & 1 == 1;
-    let is_utf8 = flags & (1 << 11) != 0;
-    let compression_method = reader.read_u16_le().await?;
-    let last_mod_time = reader.read_u16_le().await?;
-    let last_mod_date = reader.read_u16_le().await?;
-    let crc32 = reader.read_u32_le().await?;
-    let compressed_size = reader.read_u32_le().await?;
-    let uncompressed_size = reader.read_u32_le().await?;
-    let file_name_length = reader.read_u16_le().await? as usize;
-    let extra_field_length = reader.read_u16_le().await? as usize;
-    let file_comment_length = reader.read_u16_le().await? as usize;
-    let _disk_number = reader.read_u16_le().await?;
-    let _internal_file_attributes = reader.read_u16_le().await?;
-    let external_file_attributes = reader.read_u32_le().await?;
-    let offset = reader.read_u32_le().await? as u64;
-    let mut file_name_raw = vec![0; file_name_length];
-    let mut reader = reader.into_inner();
-    reader.read_exact(&mut file_name_raw).await?;
-    let mut extra_field = vec![0; extra_field_length];
-    reader.read_exact(&mut extra_field).await?;
-    let mut file_comment_raw = vec![0; file_comment_length];
-    reader.read_exact(&mut file_comment_raw).await?;
+/// The number of bytes in front of an archive whose ZIP64 central directory end record was found
+/// at `pos`, from the central directory it records, which ends there
+fn zip64_archive_offset(footer: &spec::Zip64CentralDirectoryEnd, pos: u64) -> ZipResult<u64> {
+    pos.checked_sub(footer.central_directory_size)
+        .and_then(|x| x.checked_sub(footer.central_directory_offset))
+        .ok_or(ZipError::InvalidArchive(
+            "Invalid central directory size or offset",
+        ))
+}
 
-    let file_name = match is_utf8 {
-        true => String::from_utf8_lossy(&*file_name_raw).into_owned(),
-        false => file_name_raw.clone().from_cp437(),
-    };
-    let file_comment = match is_utf8 {
-        true => String::from_utf8_lossy(&*file_comment_raw).into_owned(),
-        false => file_comment_raw.from_cp437(),
-    };
+/// The central directory offset and number of files of an archive with the central directory end
+/// records `footer` and `footer64`, taken by the `precedence`, and whether the records disagree
+///
+/// The fields of `footer` that are saturated are only recorded in `footer64`.
+fn resolve_end_records(
+    footer: &spec::CentralDirectoryEnd,
+    footer64: &spec::Zip64CentralDirectoryEnd,
+    precedence: EndRecordPrecedence,
+) -> (u64, usize, bool) {
+    let number_of_files = Some(footer.number_of_files as u64).filter(|&n| n != 0xFFFF);
+    let size = Some(footer.central_directory_size as u64).filter(|&n| n != 0xFFFFFFFF);
+    let offset = Some(footer.central_directory_offset as u64).filter(|&n| n != 0xFFFFFFFF);
+    let mismatch = matches!(number_of_files, Some(n) if n != footer64.number_of_files)
+        || matches!(size, Some(n) if n != footer64.central_directory_size)
+        || matches!(offset, Some(n) if n != footer64.central_directory_offset);
+    match precedence {
+        EndRecordPrecedence::Zip64 => (
+            footer64.central_directory_offset,
+            footer64.number_of_files as usize,
+            mismatch,
+        ),
+        EndRecordPrecedence::Classic => (
+            offset.unwrap_or(footer64.central_directory_offset),
+            number_of_files.unwrap_or(footer64.number_of_files) as usize,
+            mismatch,
+        ),
+    }
+}
 
-    // Construct the result
-    let mut result = ZipFileData {
-        system: System::from_u8((version_made_by >> 8) as u8),
-        version_made_by: version_made_by as u8,
-        encrypted,
-        compression_method: {
-            #[allow(deprecated)]
+/// Read the `number_of_files` central directory headers at the current position
+fn read_central_directory<R: Read>(mut reader: R, number_of_files: usize) -> ZipResult<Vec<u8>> {
+    let mut directory = Vec::new();
+    for _ in 0..number_of_files {
+        let start = directory.len();
+        directory.resize(start + 46, 0);
+        reader.read_exact(&mut directory[start..])?;
+        let length = |offset: usize| {
+            u16::from_le_bytes([directory[start + offset], directory[start + offset + 1]]) as usize
+        };
+        let variable_length = length(28) + length(30) + length(32);
+        directory.resize(start + 46 + variable_length, 0);
+        reader.read_exact(&mut directory[start + 46..])?;
+    }
+    Ok(directory)
+}
+
+/// Read the `number_of_files` central directory headers at the current position
+#[cfg(feature = "async")]
+async fn read_central_directory_async<R: AsyncRead + Unpin>(
+    mut reader: R,
+    number_of_files: usize,
+) -> ZipResult<Vec<u8>> {
+    let mut directory = Vec::new();
+    for _ in 0..number_of_files {
+        let start = directory.len();
+        directory.resize(start + 46, 0);
+        reader.read_exact(&mut directory[start..]).await?;
+        let length = |offset: usize| {
+            u16::from_le_bytes([directory[start + offset], directory[start + offset + 1]]) as usize
+        };
+        let variable_length = length(28) + length(30) + length(32);
+        directory.resize(start + 46 + variable_length, 0);
+        reader.read_exact(&mut directory[start + 46..]).await?;
+    }
+    Ok(directory)
+}
+
+/// The signature of the contents in the ZIP64 extensible data sector, if it has one
+fn embedded_signature(extensible_data: Option<&[u8]>) -> Option<Vec<u8>> {
+    extensible_data
+        .and_then(ExtensibleDataBlock::parse_all)?
+        .into_iter()
+        .find(|block| block.header_id == ExtensibleDataBlock::CONTENTS_SIGNATURE)
+        .map(|block| block.data)
+}
+
+/// Check a `signature` of the canonical contents of `files` with `verifier`
+fn verify_detached_signature<V: Verifier + ?Sized>(
+    files: &[ZipFileData],
+    verifier: &V,
+    signature: &[u8],
+) -> ZipResult<()> {
+    if !verifier.verify(&signature::canonical_contents(files), signature) {
+        return Err(ZipError::InvalidArchive(
+            "Invalid signature of the contents",
+        ));
+    }
+    Ok(())
+}
+
+/// Read the digital signature record at the end of the central directory, if there is one
+fn read_digital_signature<R: Read>(mut reader: R) -> ZipResult<Option<Vec<u8>>> {
+    let mut header = [0; 6];
+    if reader.read_exact(&mut header).is_err() {
+        return Ok(None);
+    }
+    if u32::from_le_bytes([header[0], header[1], header[2], header[3]])
+        != spec::DIGITAL_SIGNATURE_SIGNATURE
+    {
+        return Ok(None);
+    }
+    let mut signature = vec![0; u16::from_le_bytes([header[4], header[5]]) as usize];
+    reader.read_exact(&mut signature)?;
+    Ok(Some(signature))
+}
+
+/// Read the digital signature record at the end of the central directory, if there is one
+#[cfg(feature = "async")]
+async fn read_digital_signature_async<R: AsyncRead + Unpin>(
+    mut reader: R,
+) -> ZipResult<Option<Vec<u8>>> {
+    let mut header = [0; 6];
+    if reader.read_exact(&mut header).await.is_err() {
+        return Ok(None);
+    }
+    if u32::from_le_bytes([header[0], header[1], header[2], header[3]])
+        != spec::DIGITAL_SIGNATURE_SIGNATURE
+    {
+        return Ok(None);
+    }
+    let mut signature = vec![0; u16::from_le_bytes([header[4], header[5]]) as usize];
+    reader.read_exact(&mut signature).await?;
+    Ok(Some(signature))
+}
+
+/// Check the central directory headers `directory` against their `checksum`
+fn check_central_directory(directory: &[u8], checksum: u32) -> ZipResult<()> {
+    if crc32fast::hash(directory) != checksum {
+        return Err(ZipError::InvalidArchive(
+            "Central directory checksum mismatch",
+        ));
+    }
+    Ok(())
+}
+
+/// Check whether the central directory record at the current position is the one of `file`.
+///
+/// Only the fields identifying the entry are compared. If it matches, the length of the record is
+/// returned and the reader is left at the next record.
+fn central_header_matches<R: Read + io::Seek>(
+    reader: &mut R,
+    file: &ZipFileData,
+    archive_offset: u64,
+) -> ZipResult<Option<u64>> {
+    let mut header = [0; 46];
+    reader.read_exact(&mut header)?;
+    let field = |offset: usize, len: usize| {
+        header[offset..offset + len]
+            .iter()
+            .rev()
+            .fold(0u64, |value, byte| value << 8 | *byte as u64)
+    };
+    if field(0, 4) as u32 != spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE {
+        return Err(ZipError::InvalidArchive("Invalid Central Directory header"));
+    }
+
+    let crc32 = field(16, 4) as u32;
+    let file_name_length = field(28, 2) as usize;
+    let extra_field_length = field(30, 2);
+    let file_comment_length = field(32, 2);
+    let offset = field(42, 4);
+
+    // The offset is in the Zip64 extra field if it does not fit
+    let offset_matches = offset == 0xFFFFFFFF || offset + archive_offset == file.header_start;
+    if crc32 != file.crc32 || !offset_matches || file_name_length != file.file_name_raw().len() {
+        return Ok(None);
+    }
+    let mut file_name_raw = vec![0; file_name_length];
+    reader.read_exact(&mut file_name_raw)?;
+    if file_name_raw != file.file_name_raw() {
+        return Ok(None);
+    }
+    let skipped = extra_field_length + file_comment_length;
+    reader.seek(io::SeekFrom::Current(skipped as i64))?;
+    Ok(Some(46 + file_name_length as u64 + skipped))
+}
+
+#[cfg(feature = "async")]
+async fn central_header_to_zip_file_async<R: AsyncRead + AsyncSeek>(
+    mut reader: Pin<&mut R>,
+    archive_offset: u64,
+) -> ZipResult<ZipFileData> {
+    let central_header_start = reader.seek(io::SeekFrom::Current(0)).await?;
+    // Parse central header
+    let signature = read_u32_le(&mut reader).await?;
+    if signature != spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE {
+        return Err(invalid_central_header(signature));
+    }
+
+    let version_made_by = read_u16_le(&mut reader).await?;
+    let version_to_extract = read_u16_le(&mut reader).await?;
+    let flags = read_u16_le(&mut reader).await?;
+    let encrypted = flags & 1 == 1;
+    let is_utf8 = flags & (1 << 11) != 0;
+    let compression_method = read_u16_le(&mut reader).await?;
+    let last_mod_time = read_u16_le(&mut reader).await?;
+    let last_mod_date = read_u16_le(&mut reader).await?;
+    let crc32 = read_u32_le(&mut reader).await?;
+    let compressed_size = read_u32_le(&mut reader).await?;
+    let uncompressed_size = read_u32_le(&mut reader).await?;
+    let file_name_length = read_u16_le(&mut reader).await? as usize;
+    let extra_field_length = read_u16_le(&mut reader).await? as usize;
+    let file_comment_length = read_u16_le(&mut reader).await? as usize;
+    let disk_number = read_u16_le(&mut reader).await?;
+    let _internal_file_attributes = read_u16_le(&mut reader).await?;
+    let external_file_attributes = read_u32_le(&mut reader).await?;
+    let offset = read_u32_le(&mut reader).await? as u64;
+    let mut file_name_raw = vec![0; file_name_length];
+    reader.read_exact(&mut file_name_raw).await?;
+    let mut extra_field = vec![0; extra_field_length];
+    reader.read_exact(&mut extra_field).await?;
+    let mut file_comment_raw = vec![0; file_comment_length];
+    reader.read_exact(&mut file_comment_raw).await?;
+
+    let (file_name, file_name_raw) = decode_file_name(file_name_raw, is_utf8);
+    let file_comment = match is_utf8 {
+        true => String::from_utf8_lossy(&*file_comment_raw).into_owned(),
+        false => file_comment_raw.from_cp437(),
+    };
+
+    // Construct the result
+    let mut result = ZipFileData {
+        system: System::from_u8((version_made_by >> 8) as u8),
+        version_made_by: version_made_by as u8,
+        version_to_extract,
+        encrypted,
+        compression_method: {
+            #[allow(deprecated)]
             CompressionMethod::from_u16(compression_method)
         },
         last_modified_time: DateTime::from_msdos(last_mod_date, last_mod_time),
         crc32,
         compressed_size: compressed_size as u64,
         uncompressed_size: uncompressed_size as u64,
-        file_name,
+        file_name: file_name.into(),
         file_name_raw,
         file_comment,
         header_start: offset,
+        disk_number: disk_number as u32,
         central_header_start,
         data_start: 0,
         external_attributes: external_file_attributes,
+        aes_mode: None,
+        strong_encryption: strong_encryption(flags),
+        deflate_dictionary: None,
+        large_file: false,
+        using_data_descriptor: flags & (1 << 3) != 0,
+        name_cache: NameCache::default(),
+        central_header: None,
     };
 
     match parse_extra_field(&mut result, &*extra_field) {
@@ -1138,6 +3886,26 @@ async fn central_header_to_zip_file_async<R: AsyncRead + AsyncSeek>(
     Ok(result)
 }
 
+/// The error for a central directory header with a wrong signature
+///
+/// The archive extra data record precedes a central directory that is encrypted.
+fn invalid_central_header(signature: u32) -> ZipError {
+    if signature == spec::ARCHIVE_EXTRA_DATA_SIGNATURE {
+        ZipError::EncryptedCentralDirectory(StrongEncryptionAlgorithm::Unknown(0xFFFF))
+    } else {
+        ZipError::InvalidArchive("Invalid Central Directory header")
+    }
+}
+
+/// The initial strong encryption information of a file, before its extra field is parsed
+fn strong_encryption(flags: u16) -> Option<(StrongEncryptionAlgorithm, u16)> {
+    if flags & 1 == 1 && flags & (1 << 6) != 0 {
+        Some((StrongEncryptionAlgorithm::Unknown(0xFFFF), 0))
+    } else {
+        None
+    }
+}
+
 fn parse_extra_field(file: &mut ZipFileData, data: &[u8]) -> ZipResult<()> {
     let mut reader = io::Cursor::new(data);
 
@@ -1159,8 +3927,63 @@ fn parse_extra_field(file: &mut ZipFileData, data: &[u8]) -> ZipResult<()> {
                 file.header_start = ReadBytesExt::read_u64::<LittleEndian>(&mut reader)?;
                 len_left -= 8;
             }
-            // Unparsed fields:
-            // u32: disk start number
+            if file.disk_number == 0xFFFF {
+                file.disk_number = ReadBytesExt::read_u32::<LittleEndian>(&mut reader)?;
+                len_left -= 4;
+            }
+        }
+        // Strong encryption header
+        if kind == 0x0017 {
+            if len < 8 {
+                return Err(ZipError::InvalidArchive(
+                    "Strong encryption header is too short",
+                ));
+            }
+            let _format = ReadBytesExt::read_u16::<LittleEndian>(&mut reader)?;
+            let algorithm = ReadBytesExt::read_u16::<LittleEndian>(&mut reader)?;
+            let bit_length = ReadBytesExt::read_u16::<LittleEndian>(&mut reader)?;
+            let _flags = ReadBytesExt::read_u16::<LittleEndian>(&mut reader)?;
+            len_left -= 8;
+
+            file.strong_encryption =
+                Some((StrongEncryptionAlgorithm::from_u16(algorithm), bit_length));
+        }
+        // Preset dictionary of the deflate stream
+        if kind == 0x6444 && len >= 4 {
+            file.deflate_dictionary = Some(ReadBytesExt::read_u32::<LittleEndian>(&mut reader)?);
+            len_left -= 4;
+        }
+        // WinZip AES extra field
+        if kind == 0x9901 {
+            if len < 7 {
+                return Err(ZipError::InvalidArchive("AES extra field is too short"));
+            }
+            let vendor_version = ReadBytesExt::read_u16::<LittleEndian>(&mut reader)?;
+            let vendor_id = ReadBytesExt::read_u16::<LittleEndian>(&mut reader)?;
+            let strength = ReadBytesExt::read_u8(&mut reader)?;
+            let compression_method = ReadBytesExt::read_u16::<LittleEndian>(&mut reader)?;
+            len_left -= 7;
+
+            if vendor_id != 0x4541 {
+                return Err(ZipError::InvalidArchive("Invalid AES vendor"));
+            }
+            let vendor_version = match vendor_version {
+                1 => AesVendorVersion::Ae1,
+                2 => AesVendorVersion::Ae2,
+                _ => return Err(ZipError::InvalidArchive("Invalid AES vendor version")),
+            };
+            let mode = match strength {
+                1 => AesMode::Aes128,
+                2 => AesMode::Aes192,
+                3 => AesMode::Aes256,
+                _ => return Err(ZipError::InvalidArchive("Invalid AES encryption strength")),
+            };
+            file.aes_mode = Some((mode, vendor_version));
+            // The compression method in the header is 99, the actual method is stored here
+            file.compression_method = {
+                #[allow(deprecated)]
+                CompressionMethod::from_u16(compression_method)
+            };
         }
 
         // We could also check for < 0 to check for errors
@@ -1177,11 +4000,61 @@ impl<'a> ZipFile<'a> {
         if let ZipFileReader::NoReader = self.reader {
             let data = &self.data;
             let crypto_reader = self.crypto_reader.take().expect("Invalid reader state");
-            self.reader = make_reader(data.compression_method, data.crc32, crypto_reader)
+            let reader = make_reader(
+                data.compression_method,
+                data.crc32,
+                data.uncompressed_size,
+                data.file_name.to_string(),
+                crypto_reader,
+                self.decompressor.as_deref(),
+                &self.buffers,
+            );
+            self.reader = match self.read_hook {
+                Some(ref hook) => {
+                    ZipFileReader::Hooked(hook.wrap_reader(&data.file_name, Box::new(reader)))
+                }
+                None => reader,
+            };
         }
         &mut self.reader
     }
 
+    /// Take the CRC-32 and sizes of a streamed file from its data descriptor, once it was read.
+    fn update_from_descriptor(&mut self) {
+        #[cfg(any(
+            feature = "deflate",
+            feature = "deflate-miniz",
+            feature = "deflate-zlib"
+        ))]
+        {
+            if let (ZipFileReader::Described(reader), Cow::Owned(data)) =
+                (&self.reader, &mut self.data)
+            {
+                if let Some(descriptor) = reader.get_ref().descriptor() {
+                    data.crc32 = descriptor.crc32;
+                    data.compressed_size = descriptor.compressed_size;
+                    data.uncompressed_size = descriptor.uncompressed_size;
+                }
+            }
+        }
+    }
+
+    /// Whether the raw data of the file can be read, which is not the case for a streamed file
+    /// with a data descriptor, whose end is only found by decompressing it.
+    pub(crate) fn has_raw_data(&self) -> bool {
+        #[cfg(any(
+            feature = "deflate",
+            feature = "deflate-miniz",
+            feature = "deflate-zlib"
+        ))]
+        {
+            if let ZipFileReader::Described(_) = self.reader {
+                return false;
+            }
+        }
+        true
+    }
+
     pub(crate) fn get_raw_reader(&mut self) -> &mut dyn Read {
         if let ZipFileReader::NoReader = self.reader {
             let crypto_reader = self.crypto_reader.take().expect("Invalid reader state");
@@ -1190,6 +4063,11 @@ impl<'a> ZipFile<'a> {
         &mut self.reader
     }
 
+    /// Get the system that made the file, which determines how [`ZipFile::unix_mode`] is derived
+    pub fn system(&self) -> System {
+        self.data.system
+    }
+
     /// Get the version of the file
     pub fn version_made_by(&self) -> (u8, u8) {
         (
@@ -1198,8 +4076,17 @@ impl<'a> ZipFile<'a> {
         )
     }
 
+    /// Get the version of the specification needed to extract the file
+    pub fn version_needed(&self) -> (u8, u8) {
+        let version = self.data.version_to_extract as u8;
+        (version / 10, version % 10)
+    }
+
     /// Get the name of the file
     ///
+    /// Names that are not UTF-8 are decoded from CP437 once, when the central directory is read,
+    /// and looked up by [`ZipArchive::by_name`] in a map of the decoded names.
+    ///
     /// # Warnings
     ///
     /// It is dangerous to use this name directly when extracting an archive.
@@ -1218,7 +4105,7 @@ impl<'a> ZipFile<'a> {
     ///
     /// The encoding of this data is currently undefined.
     pub fn name_raw(&self) -> &[u8] {
-        &self.data.file_name_raw
+        self.data.file_name_raw()
     }
 
     /// Get the name of the file in a sanitized form. It truncates the name to the first NULL byte,
@@ -1259,20 +4146,7 @@ impl<'a> ZipFile<'a> {
     /// to path-based exploits. It is recommended over
     /// [`ZipFile::mangled_name`].
     pub fn enclosed_name(&self) -> Option<&Path> {
-        if self.data.file_name.contains('\0') {
-            return None;
-        }
-        let path = Path::new(&self.data.file_name);
-        let mut depth = 0usize;
-        for component in path.components() {
-            match component {
-                Component::Prefix(_) | Component::RootDir => return None,
-                Component::ParentDir => depth = depth.checked_sub(1)?,
-                Component::Normal(_) => depth += 1,
-                Component::CurDir => (),
-            }
-        }
-        Some(path)
+        cached_enclosed_name(&self.data)
     }
 
     /// Get the comment of the file
@@ -1295,10 +4169,61 @@ impl<'a> ZipFile<'a> {
         self.data.uncompressed_size
     }
 
+    /// Get the bounds of the number of bytes that remain to be read, like
+    /// [`Iterator::size_hint`].
+    ///
+    /// Both bounds are the size of the file less what was read, since the data can't exceed the
+    /// size. There is no upper bound when a read hook can change the data, or for a streamed file
+    /// with a data descriptor, whose size is only known once it was read.
+    pub fn size_hint(&self) -> (u64, Option<u64>) {
+        if self.read_hook.is_some() || !self.has_raw_data() {
+            return (0, None);
+        }
+        let size = match self.reader {
+            ZipFileReader::Raw(_) => self.data.compressed_size,
+            _ => self.data.uncompressed_size,
+        };
+        let remaining = size.saturating_sub(self.bytes_read);
+        (remaining, Some(remaining))
+    }
+
+    /// Read the rest of the file into a vector that is allocated once, with the size of the file,
+    /// instead of growing it while the data is read like [`Read::read_to_end`] does.
+    ///
+    /// The size is the one that is recorded in the archive, which a crafted archive can make up.
+    /// So at most 64 MiB are allocated up front, and no more than the compressed data can expand
+    /// to, after which the vector grows like with [`Read::read_to_end`].
+    pub fn read_to_vec(&mut self) -> io::Result<Vec<u8>> {
+        let (remaining, upper) = self.size_hint();
+        let mut data = Vec::with_capacity(preallocation(remaining, self.data.compressed_size));
+        if let Some(size) = upper {
+            self.by_ref().take(size).read_to_end(&mut data)?;
+            // The read that finds the end of the data checks the CRC-32
+            let mut byte = [0];
+            if self.read(&mut byte)? == 0 {
+                return Ok(data);
+            }
+            data.push(byte[0]);
+        }
+        self.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
     /// Get the time the file was last modified
     pub fn last_modified(&self) -> DateTime {
         self.data.last_modified_time
     }
+
+    /// Returns whether the file is encrypted
+    pub fn encrypted(&self) -> bool {
+        self.data.encrypted
+    }
+
+    /// Get the mechanism used to encrypt the file, if it is encrypted
+    pub fn encryption_method(&self) -> Option<EncryptionMethod> {
+        self.data.encryption_method()
+    }
+
     /// Returns whether the file is actually a directory
     pub fn is_dir(&self) -> bool {
         self.name()
@@ -1321,7 +4246,7 @@ impl<'a> ZipFile<'a> {
 
         match self.data.system {
             System::Unix => Some(self.data.external_attributes >> 16),
-            System::Dos => {
+            System::Dos | System::Ntfs => {
                 // Interpret MSDOS directory bit
                 let mut mode = if 0x10 == (self.data.external_attributes & 0x10) {
                     ffi::S_IFDIR | 0o0775
@@ -1330,7 +4255,7 @@ impl<'a> ZipFile<'a> {
                 };
                 if 0x01 == (self.data.external_attributes & 0x01) {
                     // Read-only bit; strip write permissions
-                    mode &= 0o0555;
+                    mode &= !0o222;
                 }
                 Some(mode)
             }
@@ -1356,6 +4281,55 @@ impl<'a> ZipFile<'a> {
     pub fn central_header_start(&self) -> u64 {
         self.data.central_header_start
     }
+
+    /// Returns whether the file has the same contents as `other`, reading both in chunks
+    ///
+    /// Files of the central directory whose sizes or CRC-32 differ are not read at all.
+    pub fn content_eq(&mut self, other: &mut ZipFile) -> io::Result<bool> {
+        if let (Cow::Borrowed(data), Cow::Borrowed(other_data)) = (&self.data, &other.data) {
+            if data.uncompressed_size != other_data.uncompressed_size
+                || data.crc32 != other_data.crc32
+            {
+                return Ok(false);
+            }
+        }
+
+        let mut buf = [0; 8192];
+        let mut other_buf = [0; 8192];
+        loop {
+            let count = read_full(self, &mut buf)?;
+            if count != read_full(other, &mut other_buf)? || buf[..count] != other_buf[..count] {
+                return Ok(false);
+            }
+            if count < buf.len() {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Write the contents of the file to `hasher` and return it
+    ///
+    /// This works with any hash function that implements [`Write`], like `sha2::Sha256`, whose
+    /// hash is then taken with `finalize`.
+    pub fn content_hash<H: Write>(&mut self, mut hasher: H) -> io::Result<H> {
+        io::copy(self, &mut hasher)?;
+        Ok(hasher)
+    }
+}
+
+/// Read from `reader` until `buf` is full or the end of the data, and return the number of bytes
+/// read
+fn read_full<R: Read + ?Sized>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut count = 0;
+    while count < buf.len() {
+        match reader.read(&mut buf[count..]) {
+            Ok(0) => break,
+            Ok(n) => count += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(count)
 }
 
 /// Methods for retrieving information on zip files
@@ -1365,12 +4339,71 @@ impl<'a> AsyncZipFile<'a> {
         if let AsyncZipFileReader::NoReader = self.reader {
             let data = &self.data;
             let crypto_reader = self.crypto_reader.take().expect("Invalid reader state");
-            self.reader =
-                make_reader_async(data.compression_method, data.crc32, crypto_reader).await
+            self.reader = make_reader_async(
+                data.compression_method,
+                data.crc32,
+                data.uncompressed_size,
+                data.file_name.to_string(),
+                crypto_reader,
+            )
+            .await
         }
         &mut self.reader
     }
 
+    /// Take the CRC-32 and sizes of a streamed file from its data descriptor, once it was read.
+    fn update_from_descriptor(&mut self) {
+        #[cfg(any(
+            feature = "deflate",
+            feature = "deflate-miniz",
+            feature = "deflate-zlib"
+        ))]
+        {
+            if let (AsyncZipFileReader::Described(reader), Cow::Owned(data)) =
+                (&self.reader, &mut self.data)
+            {
+                if let Some(descriptor) = reader.get_ref().descriptor() {
+                    data.crc32 = descriptor.crc32;
+                    data.compressed_size = descriptor.compressed_size;
+                    data.uncompressed_size = descriptor.uncompressed_size;
+                }
+            }
+        }
+    }
+
+    /// Read the data of the file past the buffer of `poll_fill_buf`.
+    fn poll_read_unbuffered(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> std::task::Poll<io::Result<usize>> {
+        let reader = &mut self.get_reader().boxed_local().as_mut().poll(cx);
+
+        let result = match reader {
+            Poll::Ready(reader) => Pin::new(reader).poll_read(cx, buf),
+            Poll::Pending => Poll::Pending,
+        };
+        // reader.poll_read(cx, buf)
+        self.update_from_descriptor();
+        result
+    }
+
+    /// Whether the raw data of the file can be read, which is not the case for a streamed file
+    /// with a data descriptor, whose end is only found by decompressing it.
+    pub(crate) fn has_raw_data(&self) -> bool {
+        #[cfg(any(
+            feature = "deflate",
+            feature = "deflate-miniz",
+            feature = "deflate-zlib"
+        ))]
+        {
+            if let AsyncZipFileReader::Described(_) = self.reader {
+                return false;
+            }
+        }
+        true
+    }
+
     pub(crate) fn get_raw_reader(&mut self) -> &mut (dyn AsyncRead + Send + Sync + Unpin) {
         if let AsyncZipFileReader::NoReader = self.reader {
             let crypto_reader = self.crypto_reader.take().expect("Invalid reader state");
@@ -1379,6 +4412,11 @@ impl<'a> AsyncZipFile<'a> {
         &mut self.reader
     }
 
+    /// Get the system that made the file, which determines how [`ZipFile::unix_mode`] is derived
+    pub fn system(&self) -> System {
+        self.data.system
+    }
+
     /// Get the version of the file
     pub fn version_made_by(&self) -> (u8, u8) {
         (
@@ -1387,6 +4425,12 @@ impl<'a> AsyncZipFile<'a> {
         )
     }
 
+    /// Get the version of the specification needed to extract the file
+    pub fn version_needed(&self) -> (u8, u8) {
+        let version = self.data.version_to_extract as u8;
+        (version / 10, version % 10)
+    }
+
     /// Get the name of the file
     pub fn name(&self) -> &str {
         &self.data.file_name
@@ -1394,7 +4438,7 @@ impl<'a> AsyncZipFile<'a> {
 
     /// Get the name of the file, in the raw (internal) byte representation.
     pub fn name_raw(&self) -> &[u8] {
-        &self.data.file_name_raw
+        self.data.file_name_raw()
     }
 
     /// Get the comment of the file
@@ -1417,10 +4461,65 @@ impl<'a> AsyncZipFile<'a> {
         self.data.uncompressed_size
     }
 
+    /// See [`ZipFile::size_hint`].
+    pub fn size_hint(&self) -> (u64, Option<u64>) {
+        if !self.has_raw_data() {
+            return (0, None);
+        }
+        let size = match self.reader {
+            AsyncZipFileReader::Raw(_) => self.data.compressed_size,
+            _ => self.data.uncompressed_size,
+        };
+        let remaining = size.saturating_sub(self.bytes_read);
+        (remaining, Some(remaining))
+    }
+
+    /// See [`ZipFile::read_to_vec`].
+    pub async fn read_to_vec(&mut self) -> io::Result<Vec<u8>> {
+        let (remaining, upper) = self.size_hint();
+        // `read_to_end` would grow the vector before it is full
+        let mut data = vec![0; preallocation(remaining, self.data.compressed_size)];
+        let mut filled = 0;
+        while filled < data.len() {
+            match self.read(&mut data[filled..]).await? {
+                0 => {
+                    data.truncate(filled);
+                    return Ok(data);
+                }
+                count => filled += count,
+            }
+        }
+        if upper.is_some() && data.len() as u64 == remaining {
+            let mut byte = [0];
+            if self.read(&mut byte).await? == 0 {
+                return Ok(data);
+            }
+            data.push(byte[0]);
+        }
+        self.read_to_end(&mut data).await?;
+        Ok(data)
+    }
+
     /// Get the time the file was last modified
     pub fn last_modified(&self) -> DateTime {
         self.data.last_modified_time
     }
+
+    /// Returns whether the file is encrypted
+    pub fn encrypted(&self) -> bool {
+        self.data.encrypted
+    }
+
+    /// Get the mechanism used to encrypt the file, if it is encrypted
+    pub fn encryption_method(&self) -> Option<EncryptionMethod> {
+        self.data.encryption_method()
+    }
+
+    /// See [`ZipFile::enclosed_name`].
+    pub fn enclosed_name(&self) -> Option<&Path> {
+        cached_enclosed_name(&self.data)
+    }
+
     /// Returns whether the file is actually a directory
     pub fn is_dir(&self) -> bool {
         self.name()
@@ -1443,7 +4542,7 @@ impl<'a> AsyncZipFile<'a> {
 
         match self.data.system {
             System::Unix => Some(self.data.external_attributes >> 16),
-            System::Dos => {
+            System::Dos | System::Ntfs => {
                 // Interpret MSDOS directory bit
                 let mut mode = if 0x10 == (self.data.external_attributes & 0x10) {
                     ffi::S_IFDIR | 0o0775
@@ -1452,122 +4551,1173 @@ impl<'a> AsyncZipFile<'a> {
                 };
                 if 0x01 == (self.data.external_attributes & 0x01) {
                     // Read-only bit; strip write permissions
-                    mode &= 0o0555;
+                    mode &= !0o222;
+                }
+                Some(mode)
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the CRC32 hash of the original file
+    pub fn crc32(&self) -> u32 {
+        self.data.crc32
+    }
+
+    /// Get the starting offset of the data of the compressed file
+    pub fn data_start(&self) -> u64 {
+        self.data.data_start
+    }
+
+    /// Get the starting offset of the zip header for this file
+    pub fn header_start(&self) -> u64 {
+        self.data.header_start
+    }
+    /// Get the starting offset of the zip header in the central directory for this file
+    pub fn central_header_start(&self) -> u64 {
+        self.data.central_header_start
+    }
+}
+
+/// The most memory that is allocated up front to read a file into memory, since the size that is
+/// recorded in the archive may be made up
+const MAX_PREALLOCATION: u64 = 64 << 20;
+
+/// The highest ratio of the size of deflated data to the size it is compressed to
+const MAX_COMPRESSION_RATIO: u64 = 1032;
+
+/// The number of bytes to allocate up front to read the `size` bytes of a file whose data takes
+/// `compressed_size` bytes, which is less if the sizes can't be right
+fn preallocation(size: u64, compressed_size: u64) -> usize {
+    size.min(MAX_PREALLOCATION)
+        .min(compressed_size.saturating_mul(MAX_COMPRESSION_RATIO)) as usize
+}
+
+impl<'a> Read for ZipFile<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.get_reader().read(buf)?;
+        self.update_from_descriptor();
+        self.bytes_read += count as u64;
+        Ok(count)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a> AsyncRead for AsyncZipFile<'a> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> std::task::Poll<io::Result<usize>> {
+        let available = &self.buffer[self.buffer_start..];
+        if available.is_empty() {
+            let count = futures::ready!(self.poll_read_unbuffered(cx, buf))?;
+            self.bytes_read += count as u64;
+            return Poll::Ready(Ok(count));
+        }
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.buffer_start += count;
+        self.bytes_read += count as u64;
+        Poll::Ready(Ok(count))
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a> AsyncBufRead for AsyncZipFile<'a> {
+    fn poll_fill_buf(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if this.buffer_start == this.buffer.len() {
+            let mut buffer = std::mem::take(&mut this.buffer);
+            buffer.resize(ASYNC_BUFFER_SIZE, 0);
+            let result = this.poll_read_unbuffered(cx, &mut buffer);
+            let count = match result {
+                Poll::Ready(Ok(count)) => count,
+                _ => 0,
+            };
+            buffer.truncate(count);
+            this.buffer = buffer;
+            this.buffer_start = 0;
+            futures::ready!(result)?;
+        }
+        Poll::Ready(Ok(&this.buffer[this.buffer_start..]))
+    }
+
+    fn consume(mut self: Pin<&mut Self>, amt: usize) {
+        let amt = amt.min(self.buffer.len() - self.buffer_start);
+        self.buffer_start += amt;
+        self.bytes_read += amt as u64;
+    }
+}
+
+impl<'a> Drop for ZipFile<'a> {
+    fn drop(&mut self) {
+        // This reader is constructed by a streaming reader.
+        // In this case, we want to exhaust the reader so that the next file is accessible.
+        if self.streamed {
+            // The end of a file with a data descriptor is only known after decompressing it
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-miniz",
+                feature = "deflate-zlib"
+            ))]
+            {
+                if let ZipFileReader::Described(reader) = &mut self.reader {
+                    // An entry that is larger than allowed is skipped all the same
+                    if io::copy(reader.get_mut(), &mut io::sink()).is_err() {
+                        // The end of the entry can't be found in corrupt data
+                        match self.poisoned {
+                            Some(poisoned) => poisoned.store(true, Ordering::Relaxed),
+                            None => {
+                                let _ = io::copy(reader.get_mut().get_mut(), &mut io::sink());
+                            }
+                        }
+                    }
+                    return;
+                }
+            }
+
+            let mut buffer = [0; 1 << 16];
+
+            // Get the inner `Take` reader so all decryption, decompression and CRC calculation is skipped.
+            let mut reader: std::io::Take<&mut dyn std::io::Read> = match &mut self.reader {
+                ZipFileReader::NoReader => {
+                    let innerreader = ::std::mem::replace(&mut self.crypto_reader, None);
+                    innerreader.expect("Invalid reader state").into_inner()
+                }
+                reader => {
+                    let innerreader = ::std::mem::replace(reader, ZipFileReader::NoReader);
+                    innerreader.into_inner()
+                }
+            };
+
+            loop {
+                match reader.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(_) => (),
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                    Err(_) => {
+                        if let Some(poisoned) = self.poisoned {
+                            poisoned.store(true, Ordering::Relaxed);
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The first path like `file (1).txt`, `file (2).txt`, … next to `path` at which there is nothing
+fn free_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default();
+    let mut number = 1u64;
+    loop {
+        let mut name = stem.to_os_string();
+        name.push(format!(" ({})", number));
+        if let Some(extension) = path.extension() {
+            name.push(".");
+            name.push(extension);
+        }
+        let candidate = path.with_file_name(name);
+        if std::fs::symlink_metadata(&candidate).is_err() {
+            return candidate;
+        }
+        number += 1;
+    }
+}
+
+/// The longest target of a symlink that is extracted
+const MAX_SYMLINK_TARGET: u64 = 4096;
+
+/// The size from which [`ZipArchive::extract_io_uring`] writes files without io_uring, as they
+/// take long enough to write that the system calls don't matter
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+const IO_URING_FILE_SIZE: u64 = 1 << 20;
+
+/// The file `name` as a relative path, if it stays within the directory it is relative to, see
+/// [`ZipFile::enclosed_name`]
+fn enclosed_name(name: &str) -> Option<&Path> {
+    if name.contains('\0') {
+        return None;
+    }
+    let path = Path::new(name);
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => return None,
+            Component::ParentDir => depth = depth.checked_sub(1)?,
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => (),
+        }
+    }
+    Some(path)
+}
+
+/// [`enclosed_name`] of the file, which is only checked the first time
+fn cached_enclosed_name(data: &ZipFileData) -> Option<&Path> {
+    let name = data.file_name.as_str();
+    if data.name_cache.enclosed(|| enclosed_name(name).is_some()) {
+        Some(Path::new(name))
+    } else {
+        None
+    }
+}
+
+/// The path at which the file `name` is extracted, if it is enclosed on every platform: unlike
+/// [`enclosed_name`], backslashes are separators and drive letters are absolute here too
+fn extraction_path(name: &str) -> Option<&Path> {
+    let path = enclosed_name(name)?;
+    if is_absolute_name(name) {
+        return None;
+    }
+    let mut depth = 0usize;
+    for component in name.split(is_separator) {
+        match component {
+            ".." => depth = depth.checked_sub(1)?,
+            "" | "." => (),
+            _ => depth += 1,
+        }
+    }
+    Some(path)
+}
+
+/// Check that the parents of the relative `path` in `directory` that exist are directories, and
+/// `path` itself if `is_dir`, so that nothing is written outside of `directory` through a symlink,
+/// whether it was extracted before or was there already. Checking stops at the first parent that
+/// doesn't exist, as nothing can be below it.
+fn check_parents(directory: &Path, path: &Path, is_dir: bool) -> ZipResult<()> {
+    let mut parent = directory.to_path_buf();
+    let mut components = path.components();
+    if !is_dir {
+        components.next_back();
+    }
+    for component in components {
+        match component {
+            std::path::Component::Normal(name) => parent.push(name),
+            std::path::Component::ParentDir => {
+                parent.pop();
+                continue;
+            }
+            _ => continue,
+        }
+        match std::fs::symlink_metadata(&parent) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                return Err(ZipError::InvalidArchive(
+                    "Extraction path goes through a symlink",
+                ))
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Remove the symlink at `path` if there is one, so that a file written there replaces it rather
+/// than the file it links to
+fn remove_symlink(path: &Path) -> io::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => std::fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+/// Write the `data` of an extracted file to `path` with `tokio::fs`, and return the path if it
+/// overwrote a file
+#[cfg(feature = "tokio")]
+async fn write_extracted(
+    path: PathBuf,
+    data: Vec<u8>,
+    mode: Option<u32>,
+) -> io::Result<Option<PathBuf>> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let overwritten = match tokio::fs::symlink_metadata(&path).await {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            tokio::fs::remove_file(&path).await?;
+            true
+        }
+        Ok(_) => true,
+        Err(_) => false,
+    };
+    tokio::fs::write(&path, data).await?;
+    set_mode(&path, mode).await?;
+    Ok(if overwritten { Some(path) } else { None })
+}
+
+/// Set the unix `mode` of an extracted file with `tokio::fs`
+#[cfg(feature = "tokio")]
+async fn set_mode(path: &Path, mode: Option<u32>) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Some(mode) = mode {
+            let permissions = std::fs::Permissions::from_mode(mode);
+            tokio::fs::set_permissions(path, permissions).await?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+    Ok(())
+}
+
+/// Whether the `target` of the symlink at the relative path `link` in `directory` stays within
+/// `directory`, following the symlinks in `directory` that the target goes through, like those
+/// that were extracted before
+fn symlink_target_is_enclosed(directory: &Path, link: &Path, target: &[u8]) -> bool {
+    use std::path::Component;
+
+    let target = match std::str::from_utf8(target) {
+        Ok(target) if !target.is_empty() && !target.contains('\0') => Path::new(target),
+        _ => return false,
+    };
+    let parent = link.parent().unwrap_or_else(|| Path::new(""));
+    // The path relative to `directory` that the target leads to so far
+    let mut path = PathBuf::new();
+    for component in parent.components().chain(target.components()) {
+        match component {
+            Component::Normal(name) => path.push(name),
+            Component::CurDir => continue,
+            Component::ParentDir if path.pop() => continue,
+            _ => return false,
+        }
+        let metadata = std::fs::symlink_metadata(directory.join(&path));
+        if matches!(metadata, Ok(metadata) if metadata.file_type().is_symlink()) {
+            let resolved = std::fs::canonicalize(directory.join(&path));
+            let root = std::fs::canonicalize(directory);
+            path = match (resolved, root) {
+                (Ok(resolved), Ok(root)) => match resolved.strip_prefix(&root) {
+                    Ok(relative) => relative.to_path_buf(),
+                    Err(_) => return false,
+                },
+                _ => return false,
+            };
+        }
+    }
+    true
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &[u8], link: &Path) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(target), link)
+}
+
+#[cfg(not(unix))]
+fn create_symlink(_target: &[u8], _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Symlinks can only be extracted on Unix",
+    ))
+}
+
+/// Options for extracting an archive with [`ZipArchive::extract_with_options`]
+///
+/// The defaults are those of [`ZipArchive::extract`]: existing files are overwritten, entries
+/// with invalid names make the extraction fail, there is no size limit, Unix permissions are
+/// applied and symlinks are extracted as files that contain their target.
+#[derive(Clone, Debug)]
+pub struct ExtractOptions {
+    overwrite: OverwritePolicy,
+    skip_invalid_names: bool,
+    size_limit: Option<u64>,
+    permissions: bool,
+    permissions_hook: Hook<PermissionsHook>,
+    symlinks: bool,
+}
+
+/// Chooses the permissions of extracted files, see [`ExtractOptions::permissions_hook`]
+type PermissionsHook = dyn Fn(&ZipFile<'_>) -> Option<std::fs::Permissions> + Send + Sync;
+
+impl Default for ExtractOptions {
+    fn default() -> ExtractOptions {
+        ExtractOptions {
+            overwrite: OverwritePolicy::Overwrite,
+            skip_invalid_names: false,
+            size_limit: None,
+            permissions: true,
+            permissions_hook: Hook::default(),
+            symlinks: false,
+        }
+    }
+}
+
+impl ExtractOptions {
+    /// Set what happens to files that already exist
+    pub fn overwrite(mut self, policy: OverwritePolicy) -> ExtractOptions {
+        self.overwrite = policy;
+        self
+    }
+
+    /// Set whether entries whose names are not [enclosed] are skipped instead of failing
+    ///
+    /// [enclosed]: ZipFile::enclosed_name
+    pub fn skip_invalid_names(mut self, skip: bool) -> ExtractOptions {
+        self.skip_invalid_names = skip;
+        self
+    }
+
+    /// Limit the total size of the extracted files to `limit` bytes.
+    ///
+    /// The extraction fails with [`ZipError::SizeLimitExceeded`] as soon as the limit would be
+    /// exceeded, like [`ZipArchive::extract_to_map`]. The files written so far are kept.
+    pub fn size_limit(mut self, limit: Option<u64>) -> ExtractOptions {
+        self.size_limit = limit;
+        self
+    }
+
+    /// Set whether the Unix permissions of the entries are applied to the extracted files
+    pub fn permissions(mut self, permissions: bool) -> ExtractOptions {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Set a hook that chooses the permissions of every extracted file and directory.
+    ///
+    /// The hook is called with the entry instead of applying its Unix permissions, which allows
+    /// enforcing a policy like removing the setuid bit. The permissions of the file are left as
+    /// they were created if the hook returns `None`. Symlinks keep their permissions.
+    pub fn permissions_hook<F>(mut self, hook: F) -> ExtractOptions
+    where
+        F: Fn(&ZipFile<'_>) -> Option<std::fs::Permissions> + Send + Sync + 'static,
+    {
+        self.permissions_hook.set(Arc::new(hook));
+        self
+    }
+
+    /// Set whether entries with the Unix file type of a symlink are extracted as symlinks
+    ///
+    /// A symlink whose target is absolute or outside of the directory makes the extraction fail,
+    /// also if the target only leaves the directory through symlinks extracted before it, and no
+    /// entry is written through a symlink. Extracting symlinks is only supported on Unix.
+    pub fn symlinks(mut self, symlinks: bool) -> ExtractOptions {
+        self.symlinks = symlinks;
+        self
+    }
+}
+
+/// What happens to a file that already exists when an archive is extracted, see
+/// [`ExtractOptions::overwrite`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Overwrite the file, with an [`ExtractWarning::Overwritten`]
+    Overwrite,
+    /// Keep the file and skip the entry
+    Skip,
+    /// Fail with an error of the kind [`io::ErrorKind::AlreadyExists`]
+    Error,
+    /// Write the file next to the existing one, under the first free name like `file (1).txt`,
+    /// with an [`ExtractWarning::Renamed`]
+    Rename,
+}
+
+/// What was done by [`ZipArchive::extract`]
+#[derive(Clone, Debug, Default)]
+pub struct ExtractSummary {
+    files_written: usize,
+    directories_created: usize,
+    symlinks_created: usize,
+    bytes_written: u64,
+    entries_skipped: usize,
+    warnings: Vec<ExtractWarning>,
+    entries: Vec<ExtractedEntry>,
+}
+
+impl ExtractSummary {
+    /// Number of files that were written
+    pub fn files_written(&self) -> usize {
+        self.files_written
+    }
+
+    /// Number of directory entries that were created
+    pub fn directories_created(&self) -> usize {
+        self.directories_created
+    }
+
+    /// Number of symlinks that were created, see [`ExtractOptions::symlinks`]
+    pub fn symlinks_created(&self) -> usize {
+        self.symlinks_created
+    }
+
+    /// Total size of the files that were written
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Number of entries that were not extracted because of a filter or the [`ExtractOptions`]
+    pub fn entries_skipped(&self) -> usize {
+        self.entries_skipped
+    }
+
+    /// Problems that did not stop the extraction
+    pub fn warnings(&self) -> &[ExtractWarning] {
+        &self.warnings
+    }
+
+    /// What was done with each entry, in the order of the archive
+    ///
+    /// `ZipArchive::extract_parallel` leaves out the files that are not written because a later
+    /// file has the same path.
+    pub fn entries(&self) -> &[ExtractedEntry] {
+        &self.entries
+    }
+
+    fn push(&mut self, index: usize, name: String, outcome: ExtractOutcome) {
+        self.entries.push(ExtractedEntry {
+            index,
+            name,
+            outcome,
+        });
+    }
+}
+
+/// An entry of [`ExtractSummary::entries`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtractedEntry {
+    index: usize,
+    name: String,
+    outcome: ExtractOutcome,
+}
+
+impl ExtractedEntry {
+    /// Index of the entry in the archive
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Name of the entry, as in [`ZipFile::name`]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// What was done with the entry
+    pub fn outcome(&self) -> &ExtractOutcome {
+        &self.outcome
+    }
+}
+
+/// What was done with an entry by [`ZipArchive::extract`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExtractOutcome {
+    /// A file was written
+    File {
+        /// The path of the file
+        path: PathBuf,
+        /// The number of bytes that were written
+        size: u64,
+    },
+    /// A directory was created
+    Directory(PathBuf),
+    /// A symlink was created, see [`ExtractOptions::symlinks`]
+    Symlink(PathBuf),
+    /// The entry was left out by the filter
+    Filtered,
+    /// The entry was skipped because its name is not enclosed, see
+    /// [`ExtractOptions::skip_invalid_names`]
+    InvalidName,
+    /// The entry was skipped because a file existed at this path, see [`OverwritePolicy::Skip`]
+    Existing(PathBuf),
+}
+
+/// A problem that did not stop an extraction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExtractWarning {
+    /// An existing file was overwritten, either one that existed before the extraction or one of
+    /// an earlier entry with the same name
+    Overwritten(PathBuf),
+    /// A file was written under another name because a file existed at its path, see
+    /// [`OverwritePolicy::Rename`]
+    Renamed {
+        /// The path at which a file existed
+        path: PathBuf,
+        /// The path at which the file was written instead
+        renamed_to: PathBuf,
+    },
+}
+
+/// A local file header that is not referenced by the central directory.
+///
+/// Returned by [`ZipArchive::orphaned_local_headers`].
+#[derive(Clone, Debug)]
+pub struct OrphanedLocalHeader {
+    data: ZipFileData,
+    flags: u16,
+}
+
+impl OrphanedLocalHeader {
+    /// Get the name of the file, as recorded in the local header
+    ///
+    /// The same warnings as for [`ZipFile::name`] apply.
+    pub fn name(&self) -> &str {
+        &self.data.file_name
+    }
+
+    /// Get the name of the file, in the raw (internal) byte representation.
+    pub fn name_raw(&self) -> &[u8] {
+        self.data.file_name_raw()
+    }
+
+    /// Get the compression method used to store the file
+    pub fn compression(&self) -> CompressionMethod {
+        self.data.compression_method
+    }
+
+    /// Get the size of the file in the archive, as recorded in the local header
+    ///
+    /// This is zero if the sizes are stored in a data descriptor after the file data.
+    pub fn compressed_size(&self) -> u64 {
+        self.data.compressed_size
+    }
+
+    /// Get the size of the file when uncompressed, as recorded in the local header
+    ///
+    /// This is zero if the sizes are stored in a data descriptor after the file data.
+    pub fn size(&self) -> u64 {
+        self.data.uncompressed_size
+    }
+
+    /// Get the CRC32 hash of the original file, as recorded in the local header
+    pub fn crc32(&self) -> u32 {
+        self.data.crc32
+    }
+
+    /// Get the time the file was last modified
+    pub fn last_modified(&self) -> DateTime {
+        self.data.last_modified_time
+    }
+
+    /// Returns whether the file is encrypted
+    pub fn encrypted(&self) -> bool {
+        self.data.encrypted
+    }
+
+    /// Returns whether the sizes and CRC32 are stored in a data descriptor following the data
+    pub fn has_data_descriptor(&self) -> bool {
+        self.flags & (1 << 3) != 0
+    }
+
+    /// Get the starting offset of the local header
+    pub fn header_start(&self) -> u64 {
+        self.data.header_start
+    }
+
+    /// Get the starting offset of the data of the compressed file
+    pub fn data_start(&self) -> u64 {
+        self.data.data_start
+    }
+}
+
+/// Iterator over the local file headers that are not referenced by the central directory.
+///
+/// Created by [`ZipArchive::orphaned_local_headers`].
+pub struct OrphanedLocalHeaders<'a, R: Read + io::Seek> {
+    reader: &'a mut R,
+    /// Header start and compressed size of every entry in the central directory
+    known: HashMap<u64, u64>,
+    buffer: Vec<u8>,
+    pos: u64,
+    end: u64,
+    done: bool,
+}
+
+impl<'a, R: Read + io::Seek> OrphanedLocalHeaders<'a, R> {
+    /// Find the next local header signature at or after `self.pos`.
+    fn find_signature(&mut self) -> ZipResult<Option<u64>> {
+        let signature = spec::LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes();
+
+        while self.pos + 4 <= self.end {
+            let len = (self.buffer.len() as u64).min(self.end - self.pos) as usize;
+            self.reader.seek(io::SeekFrom::Start(self.pos))?;
+            self.reader.read_exact(&mut self.buffer[..len])?;
+
+            if let Some(i) = self.buffer[..len].windows(4).position(|w| w == signature) {
+                return Ok(Some(self.pos + i as u64));
+            }
+            // The last three bytes may be the start of a signature that crosses the boundary
+            self.pos += len as u64 - 3;
+        }
+        Ok(None)
+    }
+
+    fn next_header(&mut self) -> ZipResult<Option<OrphanedLocalHeader>> {
+        while let Some(header_start) = self.find_signature()? {
+            self.reader.seek(io::SeekFrom::Start(header_start + 4))?;
+            let (mut data, flags) = match parse_local_header(self.reader) {
+                Ok(header) => header,
+                // Not an actual header, but data that looks like a signature
+                Err(ZipError::Io(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    self.pos = header_start + 1;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            data.header_start = header_start;
+            find_content(&mut data, self.reader)?;
+            if data.data_start > self.end {
+                self.pos = header_start + 1;
+                continue;
+            }
+
+            let compressed_size = match self.known.get(&header_start) {
+                Some(&size) => size,
+                None => data.compressed_size,
+            };
+            self.pos = match data.data_start.checked_add(compressed_size) {
+                Some(data_end) if data_end <= self.end => data_end.max(header_start + 1),
+                _ => data.data_start,
+            };
+
+            if !self.known.contains_key(&header_start) {
+                return Ok(Some(OrphanedLocalHeader { data, flags }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<'a, R: Read + io::Seek> Iterator for OrphanedLocalHeaders<'a, R> {
+    type Item = ZipResult<OrphanedLocalHeader>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_header() {
+            Ok(Some(header)) => Some(Ok(header)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// The metadata of an entry, as recorded in the central directory.
+///
+/// Returned by [`ZipArchive::stream_entries`], [`AsyncZipArchive::entries_stream`] and
+/// [`LazyZipArchive::record`].
+#[derive(Clone, Debug)]
+pub struct EntryRecord {
+    data: ZipFileData,
+}
+
+impl EntryRecord {
+    /// Get the name of the file
+    ///
+    /// The same warnings as for [`ZipFile::name`] apply.
+    pub fn name(&self) -> &str {
+        &self.data.file_name
+    }
+
+    /// Get the name of the file, in the raw (internal) byte representation.
+    pub fn name_raw(&self) -> &[u8] {
+        self.data.file_name_raw()
+    }
+
+    /// Get the comment of the file
+    pub fn comment(&self) -> &str {
+        &self.data.file_comment
+    }
+
+    /// Get the compression method used to store the file
+    pub fn compression(&self) -> CompressionMethod {
+        self.data.compression_method
+    }
+
+    /// Get the size of the file in the archive
+    pub fn compressed_size(&self) -> u64 {
+        self.data.compressed_size
+    }
+
+    /// Get the size of the file when uncompressed
+    pub fn size(&self) -> u64 {
+        self.data.uncompressed_size
+    }
+
+    /// Get the CRC32 hash of the original file
+    pub fn crc32(&self) -> u32 {
+        self.data.crc32
+    }
+
+    /// Get the time the file was last modified
+    pub fn last_modified(&self) -> DateTime {
+        self.data.last_modified_time
+    }
+
+    /// Returns whether the file is encrypted
+    pub fn encrypted(&self) -> bool {
+        self.data.encrypted
+    }
+
+    /// Returns whether the file is actually a directory
+    pub fn is_dir(&self) -> bool {
+        self.data.file_name.ends_with('/') || self.data.file_name.ends_with('\\')
+    }
+
+    /// Get the starting offset of the local header
+    pub fn header_start(&self) -> u64 {
+        self.data.header_start
+    }
+}
+
+/// Iterator over the central directory records of an archive.
+///
+/// Created by [`ZipArchive::stream_entries`].
+#[derive(Debug)]
+pub struct EntryRecords<R> {
+    reader: R,
+    archive_offset: u64,
+    remaining: usize,
+}
+
+impl<R> EntryRecords<R> {
+    /// Consumes the iterator, returning the reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read + io::Seek> Iterator for EntryRecords<R> {
+    type Item = ZipResult<EntryRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        match central_header_to_zip_file(&mut self.reader, self.archive_offset) {
+            Ok(data) => {
+                self.remaining -= 1;
+                Some(Ok(EntryRecord { data }))
+            }
+            Err(e) => {
+                // The following records cannot be found
+                self.remaining = 0;
+                Some(Err(e))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+/// A ZIP archive whose central directory records are parsed when their entries are opened
+///
+/// [`ZipArchive::new`] parses and keeps the metadata of every entry, which takes long and uses
+/// much memory for an archive with millions of entries. A lazy archive only records where the
+/// central directory record of each entry starts, and parses a record when its entry is opened.
+/// Finding an entry by name parses the records until it is found.
+///
+/// The checksum of the central directory is not checked, and entries that need a registered
+/// decompressor or deflate dictionary can't be read.
+///
+/// ```no_run
+/// use std::io::Read;
+/// use zip::read::LazyZipArchive;
+///
+/// # fn main() -> zip::result::ZipResult<()> {
+/// let mut archive = LazyZipArchive::new(std::fs::File::open("large.zip")?)?;
+/// let mut contents = Vec::new();
+/// archive.by_name("docs/index.html")?.read_to_end(&mut contents)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct LazyZipArchive<R: Read + io::Seek> {
+    reader: R,
+    /// The offsets of the central directory records
+    offsets: Vec<u64>,
+    claimed_len: usize,
+    offset: u64,
+    comment: Vec<u8>,
+    config: Config,
+    buffers: BufferPool,
+    /// The index and metadata of the entry that was parsed last
+    current: Option<(usize, ZipFileData)>,
+}
+
+impl<R: Read + io::Seek> LazyZipArchive<R> {
+    /// Read the central directory of a ZIP archive, recording where its records start.
+    pub fn new(reader: R) -> ZipResult<LazyZipArchive<R>> {
+        Self::new_with_config(reader, Config::default())
+    }
+
+    /// Read a ZIP archive like [`LazyZipArchive::new`], with the given options.
+    pub fn new_with_config(mut reader: R, config: Config) -> ZipResult<LazyZipArchive<R>> {
+        let deadline = Deadline::after(config.time_limit);
+        let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(
+            &mut reader,
+            config.max_search_distance,
+            config.trailing_data,
+        )?;
+
+        if footer.disk_number != 0 || footer.disk_with_central_directory != 0 {
+            return multi_disk_error(
+                footer.disk_number as u32,
+                footer.disk_with_central_directory as u32,
+            );
+        }
+
+        let (archive_offset, directory_start, number_of_files, _, _, _) =
+            ZipArchive::get_directory_counts(
+                &mut reader,
+                &footer,
+                cde_start_pos,
+                config.end_record_precedence,
+            )?;
+
+        if reader.seek(io::SeekFrom::Start(directory_start)).is_err() {
+            return Err(ZipError::InvalidArchive(
+                "Could not seek to start of central directory",
+            ));
+        }
+
+        let mut offsets = Vec::new();
+        let mut directory = io::BufReader::new(&mut reader);
+        let mut offset = directory_start;
+        let found = config.record_count == RecordCountPolicy::Found;
+        loop {
+            if !found && offsets.len() == number_of_files {
+                break;
+            }
+            deadline.check()?;
+            let mut header = [0; 46];
+            let signature = match directory.read_exact(&mut header) {
+                Ok(()) => u32::from_le_bytes([header[0], header[1], header[2], header[3]]),
+                Err(_) if found => break,
+                Err(e) => return Err(e.into()),
+            };
+            if signature != spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE {
+                if found {
+                    break;
                 }
-                Some(mode)
+                return Err(invalid_central_header(signature));
             }
-            _ => None,
+            // The file name, extra field and file comment follow the fixed size fields
+            let length = |i: usize| u64::from(u16::from_le_bytes([header[i], header[i + 1]]));
+            let variable_length = length(28) + length(30) + length(32);
+            let skipped = io::copy(
+                &mut directory.by_ref().take(variable_length),
+                &mut io::sink(),
+            )?;
+            if skipped != variable_length {
+                return Err(ZipError::Io(io::ErrorKind::UnexpectedEof.into()));
+            }
+            offsets.push(offset);
+            offset += 46 + variable_length;
         }
+
+        Ok(LazyZipArchive {
+            reader,
+            offsets,
+            claimed_len: number_of_files,
+            offset: archive_offset,
+            comment: footer.zip_file_comment,
+            config,
+            buffers: BufferPool::new(config.buffer_size),
+            current: None,
+        })
     }
 
-    /// Get the CRC32 hash of the original file
-    pub fn crc32(&self) -> u32 {
-        self.data.crc32
+    /// Number of files contained in this zip.
+    pub fn len(&self) -> usize {
+        self.offsets.len()
     }
 
-    /// Get the starting offset of the data of the compressed file
-    pub fn data_start(&self) -> u64 {
-        self.data.data_start
+    /// Whether this zip archive contains no files
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
     }
 
-    /// Get the starting offset of the zip header for this file
-    pub fn header_start(&self) -> u64 {
-        self.data.header_start
+    /// See [`ZipArchive::claimed_len`].
+    pub fn claimed_len(&self) -> usize {
+        self.claimed_len
     }
-    /// Get the starting offset of the zip header in the central directory for this file
-    pub fn central_header_start(&self) -> u64 {
-        self.data.central_header_start
+
+    /// Get the offset from the beginning of the underlying reader that this zip begins at, in
+    /// bytes.
+    pub fn offset(&self) -> u64 {
+        self.offset
     }
-}
 
-impl<'a> Read for ZipFile<'a> {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.get_reader().read(buf)
+    /// Get the comment of the zip archive.
+    pub fn comment(&self) -> &[u8] {
+        &self.comment
     }
-}
 
-#[cfg(feature = "async")]
-impl<'a> AsyncRead for AsyncZipFile<'a> {
-    fn poll_read(
-        mut self: Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-        buf: &mut [u8],
-    ) -> std::task::Poll<io::Result<usize>> {
-        let reader = &mut self.get_reader().boxed_local().as_mut().poll(cx);
+    /// Parse the central directory record of the file at `file_number`, unless it was the last
+    /// one to be parsed.
+    fn parse(&mut self, file_number: usize) -> ZipResult<&mut ZipFileData> {
+        let offset = *self
+            .offsets
+            .get(file_number)
+            .ok_or(ZipError::FileNotFound)?;
+        if self.current.as_ref().map(|(i, _)| *i) != Some(file_number) {
+            self.current = None;
+            self.reader.seek(io::SeekFrom::Start(offset))?;
+            let data = central_header_to_zip_file(&mut self.reader, self.offset)?;
+            self.current = Some((file_number, data));
+        }
+        Ok(&mut self.current.as_mut().expect("The record was just parsed").1)
+    }
 
-        match reader {
-            Poll::Ready(reader) => Pin::new(reader).poll_read(cx, buf),
-            Poll::Pending => Poll::Pending,
+    /// Get the metadata of the file at `file_number`, without opening it.
+    pub fn record(&mut self, file_number: usize) -> ZipResult<EntryRecord> {
+        let data = self.parse(file_number)?.clone();
+        Ok(EntryRecord { data })
+    }
+
+    /// Search for a file by name, returning its index.
+    ///
+    /// The records are parsed from the last one, so of files with the same name the last one is
+    /// found, like with [`ZipArchive::by_name`].
+    pub fn index_for_name(&mut self, name: &str) -> ZipResult<Option<usize>> {
+        let deadline = Deadline::after(self.config.time_limit);
+        for i in (0..self.offsets.len()).rev() {
+            deadline.check()?;
+            if self.parse(i)?.file_name == name {
+                return Ok(Some(i));
+            }
         }
-        // reader.poll_read(cx, buf)
+        Ok(None)
     }
-}
 
-impl<'a> Drop for ZipFile<'a> {
-    fn drop(&mut self) {
-        // self.data is Owned, this reader is constructed by a streaming reader.
-        // In this case, we want to exhaust the reader so that the next file is accessible.
-        if let Cow::Owned(_) = self.data {
-            let mut buffer = [0; 1 << 16];
+    /// Search for a file entry by name
+    pub fn by_name(&mut self, name: &str) -> ZipResult<ZipFile<'_>> {
+        match self.index_for_name(name)? {
+            Some(index) => self.by_index(index),
+            None => Err(ZipError::FileNotFound),
+        }
+    }
 
-            // Get the inner `Take` reader so all decryption, decompression and CRC calculation is skipped.
-            let mut reader: std::io::Take<&mut dyn std::io::Read> = match &mut self.reader {
-                ZipFileReader::NoReader => {
-                    let innerreader = ::std::mem::replace(&mut self.crypto_reader, None);
-                    innerreader.expect("Invalid reader state").into_inner()
-                }
-                reader => {
-                    let innerreader = ::std::mem::replace(reader, ZipFileReader::NoReader);
-                    innerreader.into_inner()
-                }
-            };
+    /// Get a contained file by index
+    pub fn by_index(&mut self, file_number: usize) -> ZipResult<ZipFile<'_>> {
+        Ok(self
+            .by_index_with_optional_password(file_number, None)?
+            .unwrap())
+    }
 
-            loop {
-                match reader.read(&mut buffer) {
-                    Ok(0) => break,
-                    Ok(_) => (),
-                    Err(e) => panic!(
-                        "Could not consume all of the output of the current ZipFile: {:?}",
-                        e
-                    ),
-                }
+    /// Get a contained file by index, decrypted with the supplied password
+    ///
+    /// See [`ZipArchive::by_index_decrypt`].
+    pub fn by_index_decrypt(
+        &mut self,
+        file_number: usize,
+        password: &[u8],
+    ) -> ZipResult<Result<ZipFile<'_>, InvalidPassword>> {
+        self.by_index_with_optional_password(file_number, Some(password))
+    }
+
+    /// Get a contained file by index without decompressing it
+    pub fn by_index_raw(&mut self, file_number: usize) -> ZipResult<ZipFile<'_>> {
+        self.parse(file_number)?;
+        let data = &mut self.current.as_mut().expect("The record was just parsed").1;
+        Ok(ZipFile {
+            crypto_reader: None,
+            reader: ZipFileReader::Raw(find_content(data, &mut self.reader)?),
+            data: Cow::Borrowed(data),
+            decompressor: None,
+            read_hook: None,
+            buffers: self.buffers.clone(),
+            bytes_read: 0,
+            streamed: false,
+            poisoned: None,
+        })
+    }
+
+    fn by_index_with_optional_password(
+        &mut self,
+        file_number: usize,
+        mut password: Option<&[u8]>,
+    ) -> ZipResult<Result<ZipFile<'_>, InvalidPassword>> {
+        let data = self.parse(file_number)?;
+        if let Some((algorithm, _)) = data.strong_encryption {
+            return Err(ZipError::UnsupportedEncryption(algorithm));
+        }
+        if has_deflate_dictionary(data) {
+            return unsupported_zip_error("The deflate dictionary of the file is not registered");
+        }
+        match (password, data.encrypted) {
+            (None, true) => {
+                return Err(ZipError::UnsupportedArchive(
+                    "Password required to decrypt file",
+                ))
             }
+            (Some(_), false) => password = None,
+            _ => {}
         }
-    }
-}
 
-/// Read ZipFile structures from a non-seekable reader.
-///
-/// This is an alternative method to read a zip file. If possible, use the ZipArchive functions
-/// as some information will be missing when reading this manner.
-///
-/// Reads a file header from the start of the stream. Will return `Ok(Some(..))` if a file is
-/// present at the start of the stream. Returns `Ok(None)` if the start of the central directory
-/// is encountered. No more files should be read after this.
-///
-/// The Drop implementation of ZipFile ensures that the reader will be correctly positioned after
-/// the structure is done.
-///
-/// Missing fields are:
-/// * `comment`: set to an empty string
-/// * `data_start`: set to 0
-/// * `external_attributes`: `unix_mode()`: will return None
-pub fn read_zipfile_from_stream<'a, R: io::Read>(
-    reader: &'a mut R,
-) -> ZipResult<Option<ZipFile<'_>>> {
-    let signature = reader.read_u32::<LittleEndian>()?;
+        let data = &mut self.current.as_mut().expect("The record was just parsed").1;
+        let aes_info = data
+            .aes_mode
+            .map(|(mode, vendor_version)| (mode, vendor_version, AES_ITERATION_COUNT));
+        let size = stored_size(self.config.stored_sizes, data);
+        let mut limit_reader = find_content(data, &mut self.reader)?;
+        let data = match size {
+            Some(size) => {
+                limit_reader.set_limit(size);
+                Cow::Owned(ZipFileData {
+                    compressed_size: size,
+                    uncompressed_size: size,
+                    ..data.clone()
+                })
+            }
+            None => Cow::Borrowed(&*data),
+        };
 
-    match signature {
-        spec::LOCAL_FILE_HEADER_SIGNATURE => (),
-        spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE => return Ok(None),
-        _ => return Err(ZipError::InvalidArchive("Invalid local file header")),
+        match make_crypto_reader(
+            data.compression_method,
+            data.zipcrypto_check_bytes(),
+            limit_reader,
+            password,
+            aes_info,
+            false,
+        ) {
+            Ok(Ok(crypto_reader)) => Ok(Ok(ZipFile {
+                crypto_reader: Some(crypto_reader),
+                reader: ZipFileReader::NoReader,
+                data,
+                decompressor: None,
+                read_hook: None,
+                buffers: self.buffers.clone(),
+                bytes_read: 0,
+                streamed: false,
+                poisoned: None,
+            })),
+            Err(e) => Err(e),
+            Ok(Err(e)) => Ok(Err(e)),
+        }
     }
 
-    let version_made_by = reader.read_u16::<LittleEndian>()?;
+    /// Unwrap and return the inner reader object
+    ///
+    /// The position of the reader is undefined.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// Parse a local file header, starting right after its signature.
+///
+/// Returns the parsed header together with its general purpose bit flags. The `header_start`
+/// and `data_start` fields are left at zero.
+fn parse_local_header<R: Read>(reader: &mut R) -> ZipResult<(ZipFileData, u16)> {
+    let version_to_extract = reader.read_u16::<LittleEndian>()?;
     let flags = reader.read_u16::<LittleEndian>()?;
     let encrypted = flags & 1 == 1;
     let is_utf8 = flags & (1 << 11) != 0;
-    let using_data_descriptor = flags & (1 << 3) != 0;
     #[allow(deprecated)]
     let compression_method = CompressionMethod::from_u16(reader.read_u16::<LittleEndian>()?);
     let last_mod_time = reader.read_u16::<LittleEndian>()?;
@@ -1583,32 +5733,38 @@ pub fn read_zipfile_from_stream<'a, R: io::Read>(
     let mut extra_field = vec![0; extra_field_length];
     reader.read_exact(&mut extra_field)?;
 
-    let file_name = match is_utf8 {
-        true => String::from_utf8_lossy(&*file_name_raw).into_owned(),
-        false => file_name_raw.clone().from_cp437(),
-    };
+    let (file_name, file_name_raw) = decode_file_name(file_name_raw, is_utf8);
 
     let mut result = ZipFileData {
-        system: System::from_u8((version_made_by >> 8) as u8),
-        version_made_by: version_made_by as u8,
+        system: System::from_u8((version_to_extract >> 8) as u8),
+        version_made_by: version_to_extract as u8,
+        version_to_extract,
         encrypted,
         compression_method,
         last_modified_time: DateTime::from_msdos(last_mod_date, last_mod_time),
         crc32,
         compressed_size: compressed_size as u64,
         uncompressed_size: uncompressed_size as u64,
-        file_name,
+        file_name: file_name.into(),
         file_name_raw,
         file_comment: String::new(), // file comment is only available in the central directory
-        // header_start and data start are not available, but also don't matter, since seeking is
-        // not available.
+        // header_start and data start are not known here, callers that can seek fill them in.
         header_start: 0,
+        disk_number: 0,
         data_start: 0,
         central_header_start: 0,
         // The external_attributes field is only available in the central directory.
         // We set this to zero, which should be valid as the docs state 'If input came
         // from standard input, this field is set to zero.'
         external_attributes: 0,
+        aes_mode: None,
+        strong_encryption: strong_encryption(flags),
+        deflate_dictionary: None,
+        // The local header has a ZIP64 extra field if the sizes in it are set to the maximum
+        large_file: compressed_size == u32::MAX || uncompressed_size == u32::MAX,
+        using_data_descriptor: flags & (1 << 3) != 0,
+        name_cache: NameCache::default(),
+        central_header: None,
     };
 
     match parse_extra_field(&mut result, &extra_field) {
@@ -1616,25 +5772,273 @@ pub fn read_zipfile_from_stream<'a, R: io::Read>(
         Err(e) => return Err(e),
     }
 
-    if encrypted {
+    Ok((result, flags))
+}
+
+/// Read ZipFile structures from a non-seekable reader.
+///
+/// This is an alternative method to read a zip file. If possible, use the ZipArchive functions
+/// as some information will be missing when reading this manner.
+///
+/// Reads a file header from the start of the stream. Will return `Ok(Some(..))` if a file is
+/// present at the start of the stream. Returns `Ok(None)` if the start of the central directory
+/// is encountered. No more files should be read after this.
+///
+/// The Drop implementation of ZipFile ensures that the reader will be correctly positioned after
+/// the structure is done. If the rest of a file with a data descriptor can't be skipped, because
+/// its data is corrupt, the rest of the stream is skipped instead, so that the next call fails.
+///
+/// Files with bit 3 of their flags set have their CRC-32 and sizes in a data descriptor after
+/// their data, with 4 or 8 byte sizes and with or without a signature. Only deflated files of this
+/// kind are supported, as the end of their data is found by decompressing it, which reads the
+/// stream a byte at a time. [`ZipStreamReader`] reads them faster, from a buffer. Their CRC-32 and
+/// sizes are 0 until all of their data was read.
+///
+/// Missing fields are:
+/// * `comment`: set to an empty string
+/// * `data_start`: set to 0
+/// * `external_attributes`: `unix_mode()`: will return None
+pub fn read_zipfile_from_stream<R: io::Read>(reader: &mut R) -> ZipResult<Option<ZipFile<'_>>> {
+    let signature = reader.read_u32::<LittleEndian>()?;
+
+    match signature {
+        spec::LOCAL_FILE_HEADER_SIGNATURE => (),
+        spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE => return Ok(None),
+        _ => return Err(ZipError::InvalidArchive("Invalid local file header")),
+    }
+    read_stream_entry(reader, unbuffered, None, None).map(Some)
+}
+
+/// The stream `reader`, read through a buffer of a single byte so that no more is taken from it
+/// than is consumed
+fn unbuffered<'a, R: io::Read>(reader: &'a mut R) -> Box<dyn BufRead + 'a> {
+    Box::new(io::BufReader::with_capacity(1, reader))
+}
+
+fn buffered<'a, R: BufRead>(reader: &'a mut R) -> Box<dyn BufRead + 'a> {
+    Box::new(reader)
+}
+
+/// Read the entry whose local header follows its signature in the stream `reader`
+///
+/// The data of an entry with a data descriptor is decompressed from `buffer(reader)`. Entries
+/// with more than `size_limit` bytes of data fail.
+/// `poisoned` is set if the rest of the entry can't be skipped when it is dropped.
+fn read_stream_entry<'a, R: io::Read>(
+    reader: &'a mut R,
+    #[cfg_attr(
+        not(any(
+            feature = "deflate",
+            feature = "deflate-miniz",
+            feature = "deflate-zlib"
+        )),
+        allow(unused_variables)
+    )]
+    buffer: fn(&'a mut R) -> Box<dyn BufRead + 'a>,
+    size_limit: Option<u64>,
+    poisoned: Option<&'a AtomicBool>,
+) -> ZipResult<ZipFile<'a>> {
+    let (result, flags) = parse_local_header(reader)?;
+    let using_data_descriptor = flags & (1 << 3) != 0;
+    let size_limit = size_limit.unwrap_or(u64::MAX);
+
+    if let Some((algorithm, _)) = result.strong_encryption {
+        return Err(ZipError::UnsupportedEncryption(algorithm));
+    }
+    if has_deflate_dictionary(&result) {
+        return unsupported_zip_error("Deflate dictionaries are not supported when streaming");
+    }
+    if result.encrypted {
         return unsupported_zip_error("Encrypted files are not supported");
     }
     if using_data_descriptor {
-        return unsupported_zip_error("The file length is not available in the local header");
+        // The end of the data is only known for compression methods that mark it
+        #[cfg(any(
+            feature = "deflate",
+            feature = "deflate-miniz",
+            feature = "deflate-zlib"
+        ))]
+        {
+            if result.compression_method == CompressionMethod::Deflated {
+                let reader = DescriptorReader::new(buffer(reader), result.large_file);
+                // Some writers put the sizes in the local header all the same, which limit the
+                // data like those in the central directory do
+                let declared_size = match result.uncompressed_size {
+                    0 => u64::MAX,
+                    size => size,
+                };
+                let limit = declared_size.min(size_limit);
+                let reader = LimitedReader::new(reader, limit, result.file_name.to_string());
+                return Ok(ZipFile {
+                    data: Cow::Owned(result),
+                    crypto_reader: None,
+                    reader: ZipFileReader::Described(reader),
+                    decompressor: None,
+                    read_hook: None,
+                    buffers: BufferPool::default(),
+                    bytes_read: 0,
+                    streamed: true,
+                    poisoned,
+                });
+            }
+        }
+        return unsupported_zip_error(
+            "Only deflated files can be streamed without their length in the local header",
+        );
     }
 
-    let limit_reader = (reader as &'a mut dyn io::Read).take(result.compressed_size as u64);
+    if result.uncompressed_size > size_limit {
+        return Err(ZipError::SizeLimitExceeded(Some(result.file_name.into())));
+    }
+    let limit_reader = (reader as &mut dyn io::Read).take(result.compressed_size);
 
     let result_crc32 = result.crc32;
     let result_compression_method = result.compression_method;
-    let crypto_reader =
-        make_crypto_reader(result_compression_method, result_crc32, limit_reader, None)?.unwrap();
-
-    Ok(Some(ZipFile {
+    let crypto_reader = make_crypto_reader(
+        result_compression_method,
+        result.zipcrypto_check_bytes(),
+        limit_reader,
+        None,
+        None,
+        false,
+    )?
+    .unwrap();
+    let buffers = BufferPool::default();
+    let file_reader = make_reader(
+        result_compression_method,
+        result_crc32,
+        result.uncompressed_size,
+        result.file_name.to_string(),
+        crypto_reader,
+        None,
+        &buffers,
+    );
+
+    Ok(ZipFile {
         data: Cow::Owned(result),
         crypto_reader: None,
-        reader: make_reader(result_compression_method, result_crc32, crypto_reader),
-    }))
+        reader: file_reader,
+        decompressor: None,
+        read_hook: None,
+        buffers,
+        bytes_read: 0,
+        streamed: true,
+        poisoned,
+    })
+}
+
+/// Reader of the entries of an archive from a stream that can't seek, like a pipe or the body of
+/// an HTTP response
+///
+/// The entries are read one after the other from their local headers, with the same limitations
+/// as [`read_zipfile_from_stream`]. Reading stops at the central directory, or at the end of the
+/// central directory of an archive without entries.
+///
+/// ```no_run
+/// use std::io::Read;
+/// use zip::read::ZipStreamReader;
+///
+/// # fn main() -> zip::result::ZipResult<()> {
+/// let mut stream = ZipStreamReader::new(std::io::stdin());
+/// while let Some(mut file) = stream.next_entry()? {
+///     let mut content = Vec::new();
+///     file.read_to_end(&mut content)?;
+///     println!("{}: {} bytes", file.name(), content.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ZipStreamReader<R> {
+    reader: io::BufReader<R>,
+    size_limit: Option<u64>,
+    started: bool,
+    finished: bool,
+    /// Set when the rest of an entry could not be skipped
+    poisoned: AtomicBool,
+}
+
+impl<R: Read> ZipStreamReader<R> {
+    /// Read the entries of the archive that starts at the current position of `reader`.
+    pub fn new(reader: R) -> ZipStreamReader<R> {
+        ZipStreamReader {
+            reader: io::BufReader::new(reader),
+            size_limit: None,
+            started: false,
+            finished: false,
+            poisoned: AtomicBool::new(false),
+        }
+    }
+
+    /// Set the most data an entry may have, which is the only limit for entries with a data
+    /// descriptor whose local header has no sizes.
+    ///
+    /// An entry that is declared to be larger fails with [`ZipError::SizeLimitExceeded`], and if
+    /// more data is read from an entry, it fails like an entry that is larger than its declared
+    /// size. By default there is no limit.
+    pub fn set_size_limit(&mut self, limit: Option<u64>) {
+        self.size_limit = limit;
+    }
+
+    /// Read the next entry, or return `None` once all entries were read.
+    ///
+    /// The rest of the data of the previous entry is skipped when it is dropped, so the entries
+    /// don't have to be read to the end. If that fails, because the data of an entry with a data
+    /// descriptor is corrupt, the position of the next entry is unknown, and this fails from then
+    /// on.
+    pub fn next_entry(&mut self) -> ZipResult<Option<ZipFile<'_>>> {
+        if *self.poisoned.get_mut() {
+            return Err(ZipError::InvalidArchive(
+                "The rest of the previous entry could not be skipped",
+            ));
+        }
+        if self.finished {
+            return Ok(None);
+        }
+        let mut signature = self.reader.read_u32::<LittleEndian>()?;
+        // The first disk of a split archive may start with the signature of a data descriptor
+        if !self.started && signature == spec::DATA_DESCRIPTOR_SIGNATURE {
+            signature = self.reader.read_u32::<LittleEndian>()?;
+        }
+        self.started = true;
+        match signature {
+            spec::LOCAL_FILE_HEADER_SIGNATURE => {}
+            spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE
+            | spec::ARCHIVE_EXTRA_DATA_SIGNATURE
+            | spec::CENTRAL_DIRECTORY_END_SIGNATURE
+            | spec::ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE => {
+                self.finished = true;
+                return Ok(None);
+            }
+            _ => return Err(ZipError::InvalidArchive("Invalid local file header")),
+        }
+        match read_stream_entry(
+            &mut self.reader,
+            buffered,
+            self.size_limit,
+            Some(&self.poisoned),
+        ) {
+            Ok(file) => Ok(Some(file)),
+            Err(e) => {
+                self.finished = true;
+                Err(e)
+            }
+        }
+    }
+
+    /// Get a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        self.reader.get_ref()
+    }
+
+    /// Unwrap the underlying reader.
+    ///
+    /// The reader is read ahead in blocks, so it is positioned after the data that was read
+    /// ahead, which may be anywhere after the signature of the central directory once all entries
+    /// were read.
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
 }
 
 /// See [read_zipfile_from_stream_async]
@@ -1645,8 +6049,7 @@ pub fn read_zipfile_from_stream<'a, R: io::Read>(
 pub async fn read_zipfile_from_stream_async<'a, R: AsyncRead + Send + Sync + Unpin>(
     reader: &'a mut R,
 ) -> ZipResult<Option<AsyncZipFile<'_>>> {
-    let mut r = reader.compat_mut();
-    let signature = r.read_u32_le().await?;
+    let signature = read_u32_le(reader).await?;
 
     match signature {
         spec::LOCAL_FILE_HEADER_SIGNATURE => (),
@@ -1654,52 +6057,59 @@ pub async fn read_zipfile_from_stream_async<'a, R: AsyncRead + Send + Sync + Unp
         _ => return Err(ZipError::InvalidArchive("Invalid local file header")),
     }
 
-    let version_made_by = r.read_u16_le().await?;
-    let flags = r.read_u16_le().await?;
+    let version_to_extract = read_u16_le(reader).await?;
+    let flags = read_u16_le(reader).await?;
     let encrypted = flags & 1 == 1;
     let is_utf8 = flags & (1 << 11) != 0;
     let using_data_descriptor = flags & (1 << 3) != 0;
     #[allow(deprecated)]
-    let compression_method = CompressionMethod::from_u16(r.read_u16_le().await?);
-    let last_mod_time = r.read_u16_le().await?;
-    let last_mod_date = r.read_u16_le().await?;
-    let crc32 = r.read_u32_le().await?;
-    let compressed_size = r.read_u32_le().await?;
-    let uncompressed_size = r.read_u32_le().await?;
-    let file_name_length = r.read_u16_le().await? as usize;
-    let extra_field_length = r.read_u16_le().await? as usize;
+    let compression_method = CompressionMethod::from_u16(read_u16_le(reader).await?);
+    let last_mod_time = read_u16_le(reader).await?;
+    let last_mod_date = read_u16_le(reader).await?;
+    let crc32 = read_u32_le(reader).await?;
+    let compressed_size = read_u32_le(reader).await?;
+    let uncompressed_size = read_u32_le(reader).await?;
+    let file_name_length = read_u16_le(reader).await? as usize;
+    let extra_field_length = read_u16_le(reader).await? as usize;
 
     let mut file_name_raw = vec![0; file_name_length];
     reader.read_exact(&mut file_name_raw).await?;
     let mut extra_field = vec![0; extra_field_length];
     reader.read_exact(&mut extra_field).await?;
 
-    let file_name = match is_utf8 {
-        true => String::from_utf8_lossy(&*file_name_raw).into_owned(),
-        false => file_name_raw.clone().from_cp437(),
-    };
+    let (file_name, file_name_raw) = decode_file_name(file_name_raw, is_utf8);
 
     let mut result = ZipFileData {
-        system: System::from_u8((version_made_by >> 8) as u8),
-        version_made_by: version_made_by as u8,
+        system: System::from_u8((version_to_extract >> 8) as u8),
+        version_made_by: version_to_extract as u8,
+        version_to_extract,
         encrypted,
         compression_method,
         last_modified_time: DateTime::from_msdos(last_mod_date, last_mod_time),
         crc32,
         compressed_size: compressed_size as u64,
         uncompressed_size: uncompressed_size as u64,
-        file_name,
+        file_name: file_name.into(),
         file_name_raw,
         file_comment: String::new(), // file comment is only available in the central directory
         // header_start and data start are not available, but also don't matter, since seeking is
         // not available.
         header_start: 0,
+        disk_number: 0,
         data_start: 0,
         central_header_start: 0,
         // The external_attributes field is only available in the central directory.
         // We set this to zero, which should be valid as the docs state 'If input came
         // from standard input, this field is set to zero.'
         external_attributes: 0,
+        aes_mode: None,
+        strong_encryption: strong_encryption(flags),
+        deflate_dictionary: None,
+        // The local header has a ZIP64 extra field if the sizes in it are set to the maximum
+        large_file: compressed_size == u32::MAX || uncompressed_size == u32::MAX,
+        using_data_descriptor,
+        name_cache: NameCache::default(),
+        central_header: None,
     };
 
     match parse_extra_field(&mut result, &extra_field) {
@@ -1707,26 +6117,76 @@ pub async fn read_zipfile_from_stream_async<'a, R: AsyncRead + Send + Sync + Unp
         Err(e) => return Err(e),
     }
 
+    if let Some((algorithm, _)) = result.strong_encryption {
+        return Err(ZipError::UnsupportedEncryption(algorithm));
+    }
+    if has_deflate_dictionary(&result) {
+        return unsupported_zip_error("Deflate dictionaries are not supported when streaming");
+    }
     if encrypted {
         return unsupported_zip_error("Encrypted files are not supported");
     }
     if using_data_descriptor {
-        return unsupported_zip_error("The file length is not available in the local header");
+        // The end of the data is only known for compression methods that mark it
+        #[cfg(any(
+            feature = "deflate",
+            feature = "deflate-miniz",
+            feature = "deflate-zlib"
+        ))]
+        {
+            if result.compression_method == CompressionMethod::Deflated {
+                let reader = Pin::new(reader) as Pin<&'a mut (dyn AsyncRead + Send + Sync)>;
+                let reader = AsyncDescriptorReader::new(reader, result.large_file);
+                let declared_size = match result.uncompressed_size {
+                    0 => u64::MAX,
+                    size => size,
+                };
+                let reader =
+                    LimitedReader::new(reader, declared_size, result.file_name.to_string());
+                return Ok(Some(AsyncZipFile {
+                    data: Cow::Owned(result),
+                    crypto_reader: None,
+                    reader: AsyncZipFileReader::Described(reader),
+                    buffer: Vec::new(),
+                    buffer_start: 0,
+                    bytes_read: 0,
+                }));
+            }
+        }
+        return unsupported_zip_error(
+            "Only deflated files can be streamed without their length in the local header",
+        );
     }
 
-    let limit_reader =
-        (Pin::new(reader) as Pin<&'a mut (dyn AsyncRead + Send + Sync)>).take(result.compressed_size as u64);
+    let limit_reader = (Pin::new(reader) as Pin<&'a mut (dyn AsyncRead + Send + Sync)>)
+        .take(result.compressed_size as u64);
 
     let result_crc32 = result.crc32;
     let result_compression_method = result.compression_method;
-    let crypto_reader =
-        make_crypto_reader_async(result_compression_method, result_crc32, limit_reader, None)
-            .await?
-            .unwrap();
+    let crypto_reader = make_crypto_reader_async(
+        result_compression_method,
+        result.zipcrypto_check_bytes(),
+        limit_reader,
+        None,
+        None,
+    )
+    .await?
+    .unwrap();
+    let file_reader = make_reader_async(
+        result_compression_method,
+        result_crc32,
+        result.uncompressed_size,
+        result.file_name.to_string(),
+        crypto_reader,
+    )
+    .await;
     Ok(Some(AsyncZipFile {
         data: Cow::Owned(result),
         crypto_reader: None,
-        reader: make_reader_async(result_compression_method, result_crc32, crypto_reader).await,
+        reader: file_reader,
+        buffer: Vec::new(),
+        buffer_start: 0,
+        bytes_read: 0,
     }))
 }
 
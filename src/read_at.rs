@@ -0,0 +1,183 @@
+//! Reading archives with positioned reads, so that threads read entries of one file at once
+//!
+//! A [`ReadAt`] reader reads at an offset instead of at a position of its own, like `pread`. A
+//! [`ReadAtReader`] keeps the position for it, and the clones of a [`ReadAtArchive`] each have
+//! their own position in the same reader. Threads with a clone each read different entries at the
+//! same time, without a mutex around a shared reader or opening the file again.
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::io::Read;
+//! use std::sync::Arc;
+//! use zip::read_at::{ReadAtArchive, ReadAtReader};
+//!
+//! # fn main() -> zip::result::ZipResult<()> {
+//! let file = Arc::new(File::open("archive.zip")?);
+//! let archive: ReadAtArchive<_> = zip::ZipArchive::new(ReadAtReader::new(file))?;
+//! let threads: Vec<_> = (0..archive.len())
+//!     .map(|i| {
+//!         let mut archive = archive.clone();
+//!         std::thread::spawn(move || -> zip::result::ZipResult<Vec<u8>> {
+//!             let mut contents = Vec::new();
+//!             archive.by_index(i)?.read_to_end(&mut contents)?;
+//!             Ok(contents)
+//!         })
+//!     })
+//!     .collect();
+//! for thread in threads {
+//!     let contents = thread.join().unwrap()?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::read::ZipArchive;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+/// A reader that reads at an offset, without a position of its own
+pub trait ReadAt {
+    /// Read into `buf` the bytes from `offset` on, returning how many bytes were read.
+    ///
+    /// Like [`Read::read`], fewer bytes than `buf` holds may be read, and 0 bytes are read at the
+    /// end.
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize>;
+
+    /// The number of bytes that can be read.
+    fn size(&self) -> io::Result<u64>;
+}
+
+/// Positioned reads with `pread`
+#[cfg(unix)]
+impl ReadAt for std::fs::File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        std::os::unix::fs::FileExt::read_at(self, buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+}
+
+/// Positioned reads with `ReadFile`, which moves the position of the file as well
+#[cfg(windows)]
+impl ReadAt for std::fs::File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        std::os::windows::fs::FileExt::seek_read(self, buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+}
+
+impl ReadAt for [u8] {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        if offset >= self.len() as u64 {
+            return Ok(0);
+        }
+        let available = &self[offset as usize..];
+        let count = buf.len().min(available.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        Ok(count)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.len() as u64)
+    }
+}
+
+impl ReadAt for Vec<u8> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        self[..].read_at(buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.len() as u64)
+    }
+}
+
+impl<T: ReadAt + ?Sized> ReadAt for &T {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        (**self).read_at(buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        (**self).size()
+    }
+}
+
+impl<T: ReadAt + ?Sized> ReadAt for Arc<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        (**self).read_at(buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        (**self).size()
+    }
+}
+
+/// A [`ReadAt`] reader with a position, which implements [`Read`] and [`Seek`]
+///
+/// Clones share the reader if it is shared, like an `Arc<File>`, but each has its own position.
+#[derive(Clone, Debug)]
+pub struct ReadAtReader<R> {
+    inner: R,
+    position: u64,
+}
+
+/// An archive that is read with positioned reads, see the [module documentation](self)
+pub type ReadAtArchive<R> = ZipArchive<ReadAtReader<R>>;
+
+impl<R: ReadAt> ReadAtReader<R> {
+    /// Wrap `inner`, at position 0.
+    pub fn new(inner: R) -> ReadAtReader<R> {
+        ReadAtReader { inner, position: 0 }
+    }
+
+    /// Get a reference to the wrapped reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Unwrap the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: ReadAt> Read for ReadAtReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.inner.read_at(buf, self.position)?;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<R: ReadAt> Seek for ReadAtReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => {
+                self.position = position;
+                return Ok(position);
+            }
+            SeekFrom::End(offset) => (self.inner.size()?, offset),
+            SeekFrom::Current(offset) => (self.position, offset),
+        };
+        let position = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.wrapping_neg() as u64)
+        };
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
@@ -1,5 +1,6 @@
 //! Error types that can be emitted from this library
 
+use crate::types::StrongEncryptionAlgorithm;
 use std::io;
 
 use thiserror::Error;
@@ -12,6 +13,20 @@ pub type ZipResult<T> = Result<T, ZipError>;
 #[error("invalid password for file in archive")]
 pub struct InvalidPassword;
 
+/// The data of an entry is longer than the uncompressed size recorded in the archive
+///
+/// The readers of entries return this in an [`io::Error`] of kind
+/// [`io::ErrorKind::InvalidData`]. Such an entry was most likely crafted to use more memory or disk
+/// space than its recorded size suggests.
+#[derive(Error, Debug)]
+#[error("entry {name:?} is larger than its declared size of {declared_size} bytes")]
+pub struct UncompressedSizeExceeded {
+    /// The name of the entry
+    pub name: String,
+    /// The uncompressed size of the entry according to the archive
+    pub declared_size: u64,
+}
+
 /// Error type for Zip
 #[derive(Debug, Error)]
 pub enum ZipError {
@@ -27,6 +42,83 @@ pub enum ZipError {
     #[error("unsupported Zip archive")]
     UnsupportedArchive(&'static str),
 
+    /// The name of an entry being written was rejected by the writer's [`NameOptions`]
+    ///
+    /// [`NameOptions`]: crate::write::NameOptions
+    #[error("invalid file name")]
+    InvalidFileName(&'static str),
+
+    /// Writing the archive would exceed the limit set with [`ZipWriter::set_size_limit`], or
+    /// reading it would exceed the limit given to [`ZipArchive::extract_to_map`]
+    ///
+    /// Contains the name of the entry being written or read, or `None` if the limit was exceeded
+    /// by the central directory.
+    ///
+    /// [`ZipWriter::set_size_limit`]: crate::ZipWriter::set_size_limit
+    /// [`ZipArchive::extract_to_map`]: crate::ZipArchive::extract_to_map
+    #[error(
+        "archive size limit exceeded by {}",
+        .0.as_deref().unwrap_or("the central directory")
+    )]
+    SizeLimitExceeded(Option<String>),
+
+    /// Writing the archive would require ZIP64, which was forbidden with
+    /// [`FileOptions::forbid_zip64`] or [`ZipWriter::set_forbid_zip64`]
+    ///
+    /// This is returned as soon as the limits of the format without ZIP64 are exceeded, so nothing
+    /// that needs ZIP64 is written.
+    ///
+    /// [`FileOptions::forbid_zip64`]: crate::write::FileOptions::forbid_zip64
+    /// [`ZipWriter::set_forbid_zip64`]: crate::ZipWriter::set_forbid_zip64
+    #[error("ZIP64 is forbidden but required for {entries} entries and {bytes} bytes")]
+    Zip64Required {
+        /// The number of entries in the archive, including the one being written
+        entries: usize,
+        /// The largest size or offset of the entry being written or the central directory
+        bytes: u64,
+    },
+
+    /// The file is encrypted with PKWARE strong encryption, which is not supported
+    #[error("unsupported encryption: {0}")]
+    UnsupportedEncryption(StrongEncryptionAlgorithm),
+
+    /// The central directory of the archive is encrypted with PKWARE strong encryption, which is
+    /// not supported
+    ///
+    /// [`ZipArchive::central_directory_encryption`] detects this without opening the archive.
+    ///
+    /// [`ZipArchive::central_directory_encryption`]: crate::ZipArchive::central_directory_encryption
+    #[error("encrypted central directory: {0}")]
+    EncryptedCentralDirectory(StrongEncryptionAlgorithm),
+
+    /// The archive is split or spanned over several disks, of which only the last one was read
+    ///
+    /// [`ZipArchive::new_multidisk`] reads all of the disks.
+    ///
+    /// [`ZipArchive::new_multidisk`]: crate::ZipArchive::new_multidisk
+    #[error(
+        "multi-disk archive: read disk {disk_number}, central directory on disk {disk_with_central_directory}"
+    )]
+    MultiDiskUnsupported {
+        /// The number of the disk that was read, where 0 is the first disk, so the archive has at
+        /// least one more disk than this
+        disk_number: u32,
+        /// The number of the disk on which the central directory starts
+        disk_with_central_directory: u32,
+    },
+
+    /// Reading the archive took longer than the limit set with [`Config::time_limit`]
+    ///
+    /// [`Config::time_limit`]: crate::read::Config::time_limit
+    #[error("time limit exceeded")]
+    TimeLimitExceeded,
+
+    /// The operation was cancelled with a [`Cancellation`]
+    ///
+    /// [`Cancellation`]: crate::read::Cancellation
+    #[error("operation cancelled")]
+    Cancelled,
+
     /// The requested file could not be found in the archive
     #[error("specified file not found in archive")]
     FileNotFound,
@@ -0,0 +1,58 @@
+//! Digital signatures of archives
+//!
+//! This crate does not implement any signature algorithm: a [`Signer`] computes signatures when
+//! an archive is written, and a [`Verifier`] checks them when it is read. There are two kinds of
+//! signatures:
+//!
+//! * The central directory of an archive can end with a digital signature record, which holds a
+//!   signature of the central directory headers. It is written with [`ZipWriter::set_signer`]
+//!   and checked with [`ZipArchive::verify_digital_signature`].
+//! * A signature of the [canonical contents] of an archive does not depend on
+//!   how the archive is laid out. It can be kept apart from the archive and checked with
+//!   [`ZipArchive::verify_detached_signature`], or be embedded in the archive with
+//!   [`ZipWriter::set_contents_signer`], see [`ZipArchive::embedded_signature`].
+//!
+//! Both cover the data of the entries through their CRC-32, so they are only as strong as the
+//! CRC-32 against an attacker who can choose the data.
+//!
+//! [canonical contents]: crate::read::ZipArchive::canonical_contents
+//! [`ZipWriter::set_signer`]: crate::write::ZipWriter::set_signer
+//! [`ZipWriter::set_contents_signer`]: crate::write::ZipWriter::set_contents_signer
+//! [`ZipArchive::verify_digital_signature`]: crate::read::ZipArchive::verify_digital_signature
+//! [`ZipArchive::verify_detached_signature`]: crate::read::ZipArchive::verify_detached_signature
+//! [`ZipArchive::embedded_signature`]: crate::read::ZipArchive::embedded_signature
+
+use crate::types::ZipFileData;
+use std::io;
+
+/// Computes signatures of archives that are written.
+pub trait Signer: Send + Sync {
+    /// Sign `data`, which are the central directory headers or the canonical contents of an
+    /// archive.
+    ///
+    /// The signature must not be longer than 65535 bytes.
+    fn sign(&self, data: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+/// Checks signatures of archives that are read.
+pub trait Verifier {
+    /// Whether `signature` is a valid signature of `data`, which are the central directory
+    /// headers or the canonical contents of an archive.
+    fn verify(&self, data: &[u8], signature: &[u8]) -> bool;
+}
+
+/// The canonical contents of an archive with the entries `files`, see
+/// [`ZipArchive::canonical_contents`](crate::read::ZipArchive::canonical_contents)
+pub(crate) fn canonical_contents(files: &[ZipFileData]) -> Vec<u8> {
+    let mut files: Vec<_> = files.iter().collect();
+    files.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+    let mut contents = Vec::new();
+    for file in files {
+        contents.extend_from_slice(&(file.file_name.len() as u32).to_le_bytes());
+        contents.extend_from_slice(file.file_name.as_bytes());
+        contents.extend_from_slice(&file.crc32.to_le_bytes());
+        contents.extend_from_slice(&file.compressed_size.to_le_bytes());
+        contents.extend_from_slice(&file.uncompressed_size.to_le_bytes());
+    }
+    contents
+}
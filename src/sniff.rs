@@ -0,0 +1,388 @@
+//! Guessing the type of the content of entries from their first bytes
+//!
+//! [`sniff`] checks the start of the data for the magic numbers of common formats, which allows
+//! classifying the files in an archive without extracting them. Use
+//! [`ZipArchive::content_type`](crate::read::ZipArchive::content_type) to sniff a file of an
+//! archive directly. The result is only a guess: the magic numbers of some formats are short, so
+//! other data can start with the same bytes, and the data of a file is not validated.
+//!
+//! [`scan`] builds on this to flag content that can't be inspected, like nested archives with
+//! encrypted files or data that looks random. Use
+//! [`ZipArchive::scan`](crate::read::ZipArchive::scan) to scan all files of an archive.
+
+use byteorder::{ByteOrder, LittleEndian};
+use std::fmt;
+
+/// The number of bytes at the start of the data that [`sniff`] looks at.
+///
+/// Passing less data is fine, but some formats can then not be recognized.
+pub const SNIFF_LENGTH: usize = 512;
+
+/// The number of bytes at the start of the data that [`scan`] looks at.
+///
+/// This is larger than [`SNIFF_LENGTH`], because the entropy can't be estimated from a few bytes.
+pub const SCAN_LENGTH: usize = 4096;
+
+/// The number of bytes that [`scan`] needs to flag data as [`ScanFlag::HighEntropy`]
+const MIN_ENTROPY_LENGTH: usize = 1024;
+
+/// The entropy in bits per byte from which [`scan`] considers data to be random
+const HIGH_ENTROPY: f64 = 7.5;
+
+/// A type of content recognized by [`sniff`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ContentType {
+    /// ZIP archive, which includes formats based on it like JAR, OpenDocument and Office Open XML
+    Zip,
+    /// gzip compressed data
+    Gzip,
+    /// bzip2 compressed data
+    Bzip2,
+    /// xz compressed data
+    Xz,
+    /// Zstandard compressed data
+    Zstd,
+    /// 7-Zip archive
+    SevenZip,
+    /// RAR archive
+    Rar,
+    /// POSIX tar archive
+    Tar,
+    /// PDF document
+    Pdf,
+    /// PNG image
+    Png,
+    /// JPEG image
+    Jpeg,
+    /// GIF image
+    Gif,
+    /// WebP image
+    Webp,
+    /// BMP image
+    Bmp,
+    /// TIFF image
+    Tiff,
+    /// MP3 audio
+    Mp3,
+    /// Ogg container
+    Ogg,
+    /// FLAC audio
+    Flac,
+    /// WAVE audio
+    Wav,
+    /// MP4 or another ISO base media file
+    Mp4,
+    /// WebAssembly binary module
+    Wasm,
+    /// ELF executable or library
+    Elf,
+    /// Windows executable or library
+    PortableExecutable,
+    /// Mach-O executable or library
+    MachO,
+    /// SQLite database
+    Sqlite,
+    /// HTML document
+    Html,
+    /// XML document
+    Xml,
+    /// Other UTF-8 text
+    Text,
+}
+
+impl ContentType {
+    /// The MIME type of the content
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ContentType::Zip => "application/zip",
+            ContentType::Gzip => "application/gzip",
+            ContentType::Bzip2 => "application/x-bzip2",
+            ContentType::Xz => "application/x-xz",
+            ContentType::Zstd => "application/zstd",
+            ContentType::SevenZip => "application/x-7z-compressed",
+            ContentType::Rar => "application/vnd.rar",
+            ContentType::Tar => "application/x-tar",
+            ContentType::Pdf => "application/pdf",
+            ContentType::Png => "image/png",
+            ContentType::Jpeg => "image/jpeg",
+            ContentType::Gif => "image/gif",
+            ContentType::Webp => "image/webp",
+            ContentType::Bmp => "image/bmp",
+            ContentType::Tiff => "image/tiff",
+            ContentType::Mp3 => "audio/mpeg",
+            ContentType::Ogg => "application/ogg",
+            ContentType::Flac => "audio/flac",
+            ContentType::Wav => "audio/wav",
+            ContentType::Mp4 => "video/mp4",
+            ContentType::Wasm => "application/wasm",
+            ContentType::Elf => "application/x-executable",
+            ContentType::PortableExecutable => "application/vnd.microsoft.portable-executable",
+            ContentType::MachO => "application/x-mach-binary",
+            ContentType::Sqlite => "application/vnd.sqlite3",
+            ContentType::Html => "text/html",
+            ContentType::Xml => "application/xml",
+            ContentType::Text => "text/plain",
+        }
+    }
+}
+
+impl fmt::Display for ContentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.mime_type())
+    }
+}
+
+/// Magic numbers that identify a type by the bytes at an offset
+const MAGIC_NUMBERS: &[(usize, &[u8], ContentType)] = &[
+    (0, b"PK\x03\x04", ContentType::Zip),
+    (0, b"PK\x05\x06", ContentType::Zip),
+    (0, b"PK\x07\x08", ContentType::Zip),
+    (0, b"\x1F\x8B", ContentType::Gzip),
+    (0, b"BZh", ContentType::Bzip2),
+    (0, b"\xFD7zXZ\x00", ContentType::Xz),
+    (0, b"\x28\xB5\x2F\xFD", ContentType::Zstd),
+    (0, b"7z\xBC\xAF\x27\x1C", ContentType::SevenZip),
+    (0, b"Rar!\x1A\x07", ContentType::Rar),
+    (257, b"ustar", ContentType::Tar),
+    (0, b"%PDF-", ContentType::Pdf),
+    (0, b"\x89PNG\r\n\x1A\n", ContentType::Png),
+    (0, b"\xFF\xD8\xFF", ContentType::Jpeg),
+    (0, b"GIF87a", ContentType::Gif),
+    (0, b"GIF89a", ContentType::Gif),
+    (8, b"WEBP", ContentType::Webp),
+    (0, b"II*\x00", ContentType::Tiff),
+    (0, b"MM\x00*", ContentType::Tiff),
+    (0, b"ID3", ContentType::Mp3),
+    (0, b"\xFF\xFB", ContentType::Mp3),
+    (0, b"\xFF\xF3", ContentType::Mp3),
+    (0, b"\xFF\xF2", ContentType::Mp3),
+    (0, b"OggS", ContentType::Ogg),
+    (0, b"fLaC", ContentType::Flac),
+    (8, b"WAVE", ContentType::Wav),
+    (4, b"ftyp", ContentType::Mp4),
+    (0, b"\x00asm", ContentType::Wasm),
+    (0, b"\x7FELF", ContentType::Elf),
+    (0, b"MZ", ContentType::PortableExecutable),
+    (0, b"\xFE\xED\xFA\xCE", ContentType::MachO),
+    (0, b"\xFE\xED\xFA\xCF", ContentType::MachO),
+    (0, b"\xCE\xFA\xED\xFE", ContentType::MachO),
+    (0, b"\xCF\xFA\xED\xFE", ContentType::MachO),
+    (0, b"SQLite format 3\x00", ContentType::Sqlite),
+];
+
+/// Guess the type of content that starts with `data`
+///
+/// Looks at up to [`SNIFF_LENGTH`] bytes. Returns `None` if the type is not recognized, for
+/// example because `data` is empty or binary data of an unknown format.
+pub fn sniff(data: &[u8]) -> Option<ContentType> {
+    let data = &data[..data.len().min(SNIFF_LENGTH)];
+    let has_magic =
+        |offset: usize, magic: &[u8]| data.get(offset..offset + magic.len()) == Some(magic);
+
+    for &(offset, magic, content_type) in MAGIC_NUMBERS {
+        // WebP and WAVE are both RIFF containers
+        let riff = offset != 8 || has_magic(0, b"RIFF");
+        if riff && has_magic(offset, magic) {
+            return Some(content_type);
+        }
+    }
+    // The magic number of BMP is short, so check that the reserved fields are zero as well
+    if has_magic(0, b"BM") && data.get(6..10) == Some(&[0; 4]) {
+        return Some(ContentType::Bmp);
+    }
+    sniff_text(data)
+}
+
+/// The reason for flagging content in [`scan`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ScanFlag {
+    /// The file is encrypted, so its content can't be inspected without the password
+    Encrypted,
+    /// The content is a ZIP archive that contains encrypted files
+    EncryptedArchive,
+    /// The content is of an unknown type and looks random, which is typical for encrypted data
+    HighEntropy,
+}
+
+/// A file of an archive that was flagged by [`ZipArchive::scan`](crate::read::ZipArchive::scan)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlaggedFile {
+    /// The index of the file in the archive
+    pub index: usize,
+    /// The name of the file
+    pub name: String,
+    /// Why the file was flagged
+    pub flag: ScanFlag,
+}
+
+/// Check whether content that starts with `data` should be flagged because it can't be inspected
+///
+/// Looks at up to [`SCAN_LENGTH`] bytes. Content is flagged if it is a ZIP archive with an
+/// encrypted file among the local headers in `data`, or if it is of a type that is not recognized
+/// by [`sniff`] and its entropy is high. Encrypted content in other formats, like 7-Zip or RAR
+/// archives, is not detected.
+pub fn scan(data: &[u8]) -> Option<ScanFlag> {
+    let data = &data[..data.len().min(SCAN_LENGTH)];
+    match sniff(data) {
+        Some(ContentType::Zip) if has_encrypted_local_header(data) => {
+            Some(ScanFlag::EncryptedArchive)
+        }
+        None if data.len() >= MIN_ENTROPY_LENGTH && entropy(data) >= HIGH_ENTROPY => {
+            Some(ScanFlag::HighEntropy)
+        }
+        _ => None,
+    }
+}
+
+/// Estimate the entropy of `data` in bits per byte
+///
+/// This is the Shannon entropy of the distribution of the bytes, which is close to 8 for random
+/// data and lower for text or code.
+pub fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+    let length = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / length;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Check the flags of the local headers at the start of a ZIP archive for encryption
+fn has_encrypted_local_header(data: &[u8]) -> bool {
+    let u16_at = |offset: usize| data.get(offset..offset + 2).map(LittleEndian::read_u16);
+    let u32_at = |offset: usize| data.get(offset..offset + 4).map(LittleEndian::read_u32);
+    let mut offset = 0;
+    while data.get(offset..offset + 4) == Some(b"PK\x03\x04") {
+        let (flags, compressed_size, name_length, extra_length) = match (
+            u16_at(offset + 6),
+            u32_at(offset + 18),
+            u16_at(offset + 26),
+            u16_at(offset + 28),
+        ) {
+            (Some(a), Some(b), Some(c), Some(d)) => (a, b, c, d),
+            _ => return false,
+        };
+        if flags & 1 != 0 {
+            return true;
+        }
+        // The size of the data is unknown if it is followed by a data descriptor
+        if flags & (1 << 3) != 0 || compressed_size as usize >= data.len() {
+            return false;
+        }
+        offset += 30 + name_length as usize + extra_length as usize + compressed_size as usize;
+    }
+    false
+}
+
+fn sniff_text(data: &[u8]) -> Option<ContentType> {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        // The data may end in the middle of a character
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&data[..e.valid_up_to()]).ok()?,
+        Err(_) => return None,
+    };
+    let is_binary = |c: char| c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0C' | '\x1B');
+    if text.is_empty() || text.chars().any(is_binary) {
+        return None;
+    }
+
+    let start = text.trim_start_matches('\u{FEFF}').trim_start().as_bytes();
+    let starts_with = |prefix: &str| {
+        start.len() >= prefix.len() && start[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+    };
+    if starts_with("<?xml") {
+        Some(ContentType::Xml)
+    } else if starts_with("<!doctype html") || starts_with("<html") {
+        Some(ContentType::Html)
+    } else {
+        Some(ContentType::Text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn magic_numbers() {
+        assert_eq!(sniff(b"PK\x03\x04\x14\x00"), Some(ContentType::Zip));
+        assert_eq!(sniff(b"\x89PNG\r\n\x1A\n\x00\x00"), Some(ContentType::Png));
+        assert_eq!(
+            sniff(b"RIFF\x00\x00\x00\x00WEBPVP8 "),
+            Some(ContentType::Webp)
+        );
+        assert_eq!(
+            sniff(b"RIFF\x00\x00\x00\x00WAVEfmt "),
+            Some(ContentType::Wav)
+        );
+        assert_eq!(sniff(b"\x00\x00\x00\x00\x00\x00\x00\x00WEBP"), None);
+        assert_eq!(
+            sniff(b"BM\x36\x00\x00\x00\x00\x00\x00\x00"),
+            Some(ContentType::Bmp)
+        );
+
+        let mut tar = vec![0; 512];
+        tar[..5].copy_from_slice(b"a.txt");
+        tar[257..263].copy_from_slice(b"ustar\x00");
+        assert_eq!(sniff(&tar), Some(ContentType::Tar));
+        assert_eq!(sniff(&tar[..260]), None);
+    }
+
+    #[test]
+    fn scan_data() {
+        let mut local_header = b"PK\x03\x04\x14\x00\x00\x00\x00\x00".to_vec();
+        local_header.extend_from_slice(&[0; 8]);
+        local_header.extend_from_slice(&[3, 0, 0, 0, 3, 0, 0, 0, 5, 0, 0, 0]);
+        local_header.extend_from_slice(b"a.txtabc");
+        let mut archive = local_header.repeat(2);
+        assert_eq!(scan(&archive), None);
+        archive[local_header.len() + 6] = 1;
+        assert_eq!(scan(&archive), Some(ScanFlag::EncryptedArchive));
+        // With a data descriptor, the following local headers are not found
+        archive[6] = 1 << 3;
+        assert_eq!(scan(&archive), None);
+
+        // A simple LCG is random enough to have a high entropy
+        let mut state = 1u32;
+        let random: Vec<u8> = (0..SCAN_LENGTH)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect();
+        assert!(entropy(&random) > HIGH_ENTROPY);
+        assert_eq!(scan(&random), Some(ScanFlag::HighEntropy));
+        assert_eq!(scan(&random[..MIN_ENTROPY_LENGTH / 2]), None);
+
+        let text = b"not random at all ".repeat(100);
+        assert!(entropy(&text) < 4.0);
+        assert_eq!(scan(&text), None);
+        assert_eq!(entropy(&[]), 0.0);
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(sniff(b"BMW"), Some(ContentType::Text));
+        assert_eq!(sniff("héllo\r\n".as_bytes()), Some(ContentType::Text));
+        assert_eq!(sniff(&"é".as_bytes()[..1]), None);
+        assert_eq!(
+            sniff("aé".as_bytes()[..2].as_ref()),
+            Some(ContentType::Text)
+        );
+        assert_eq!(
+            sniff(b"\xEF\xBB\xBF  <?xml version=\"1.0\"?>"),
+            Some(ContentType::Xml)
+        );
+        assert_eq!(sniff(b"<!DOCTYPE html>\n<html>"), Some(ContentType::Html));
+        assert_eq!(sniff(b"text\x00with a null byte"), None);
+        assert_eq!(sniff(b"\xFF\xFE\xFD"), None);
+        assert_eq!(sniff(b""), None);
+    }
+}
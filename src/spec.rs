@@ -1,24 +1,101 @@
 use crate::result::{ZipError, ZipResult};
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
 use std::io;
 use std::io::prelude::*;
 
 #[cfg(feature = "async")]
-use crate::async_util::Compat;
+use crate::async_util::{write_u16_le, write_u32_le, write_u64_le};
 #[cfg(feature = "async")]
-use crate::async_util::CompatExt;
-#[cfg(feature = "async")]
-use futures::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite};
+use futures::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
 #[cfg(feature = "async")]
 use std::pin::Pin;
-#[cfg(feature = "async")]
-use tokio::io::{AsyncReadExt as TokioAsyncReadExt, AsyncWriteExt as TokioAsyncWriteExt};
 
 pub const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x04034b50;
 pub const CENTRAL_DIRECTORY_HEADER_SIGNATURE: u32 = 0x02014b50;
-const CENTRAL_DIRECTORY_END_SIGNATURE: u32 = 0x06054b50;
+pub const CENTRAL_DIRECTORY_END_SIGNATURE: u32 = 0x06054b50;
 pub const ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE: u32 = 0x06064b50;
+pub const ARCHIVE_EXTRA_DATA_SIGNATURE: u32 = 0x08064b50;
 const ZIP64_CENTRAL_DIRECTORY_END_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+pub const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+pub const DIGITAL_SIGNATURE_SIGNATURE: u32 = 0x05054b50;
+
+/// The size of the central directory end record without the comment
+const HEADER_SIZE: u64 = 22;
+/// The largest distance of the central directory end record from the end of an archive, when it
+/// has the longest possible comment and is not followed by other data
+pub const MAX_END_DISTANCE: u64 = HEADER_SIZE + u16::MAX as u64;
+/// The number of bytes that are read at once while searching for the central directory end records
+const SEARCH_CHUNK_SIZE: u64 = 4096;
+
+/// Sizes and offsets from this value on are stored in ZIP64 records
+pub const ZIP64_BYTES_THR: u64 = u32::MAX as u64;
+/// Numbers of entries from this value on are stored in ZIP64 records
+pub const ZIP64_ENTRY_THR: usize = u16::MAX as usize;
+
+/// What a [`Parser`] needs next
+pub enum Step<T> {
+    /// The `length` bytes at `offset` of the input, which are fewer when the input ends before
+    /// them
+    Read { offset: u64, length: u64 },
+    /// Nothing, the result is ready
+    Done(T),
+}
+
+/// A parser of records that does no I/O itself
+///
+/// A frontend asks the parser for its next [`Step`] and feeds it the bytes it asked for, until it
+/// is done. The sync and async readers run the same parsers this way, with [`drive`] and
+/// [`drive_async`].
+pub trait Parser {
+    type Output;
+
+    /// The next step, after the bytes of the previous one were fed
+    fn step(&mut self) -> ZipResult<Step<Self::Output>>;
+
+    /// Feed the bytes of the last [`Step::Read`].
+    fn feed(&mut self, bytes: &[u8]);
+}
+
+/// Run `parser` on `reader`, which is left after the bytes that were read last
+pub fn drive<P: Parser, T: Read + io::Seek>(mut parser: P, reader: &mut T) -> ZipResult<P::Output> {
+    let mut bytes = Vec::new();
+    loop {
+        match parser.step()? {
+            Step::Read { offset, length } => {
+                reader.seek(io::SeekFrom::Start(offset))?;
+                bytes.clear();
+                reader.by_ref().take(length).read_to_end(&mut bytes)?;
+                parser.feed(&bytes);
+            }
+            Step::Done(output) => return Ok(output),
+        }
+    }
+}
+
+/// See [`drive`].
+#[cfg(feature = "async")]
+pub async fn drive_async<P: Parser, T: AsyncRead + AsyncSeek>(
+    mut parser: P,
+    mut reader: Pin<&mut T>,
+) -> ZipResult<P::Output> {
+    let mut bytes = Vec::new();
+    loop {
+        match parser.step()? {
+            Step::Read { offset, length } => {
+                reader.seek(io::SeekFrom::Start(offset)).await?;
+                bytes.clear();
+                (&mut reader).take(length).read_to_end(&mut bytes).await?;
+                parser.feed(&bytes);
+            }
+            Step::Done(output) => return Ok(output),
+        }
+    }
+}
+
+/// The error for bytes that end before the record in them
+fn unexpected_eof() -> ZipError {
+    io::Error::from(io::ErrorKind::UnexpectedEof).into()
+}
 
 pub struct CentralDirectoryEnd {
     pub disk_number: u16,
@@ -31,136 +108,61 @@ pub struct CentralDirectoryEnd {
 }
 
 impl CentralDirectoryEnd {
-    pub fn parse<T: Read>(reader: &mut T) -> ZipResult<CentralDirectoryEnd> {
-        let magic = reader.read_u32::<LittleEndian>()?;
-        if magic != CENTRAL_DIRECTORY_END_SIGNATURE {
-            return Err(ZipError::InvalidArchive("Invalid digital signature header"));
+    /// Parse the record from `bytes`, which start with its signature and hold its comment.
+    pub fn from_bytes(bytes: &[u8]) -> ZipResult<CentralDirectoryEnd> {
+        if bytes.len() < 4 {
+            return Err(unexpected_eof());
         }
-        let disk_number = reader.read_u16::<LittleEndian>()?;
-        let disk_with_central_directory = reader.read_u16::<LittleEndian>()?;
-        let number_of_files_on_this_disk = reader.read_u16::<LittleEndian>()?;
-        let number_of_files = reader.read_u16::<LittleEndian>()?;
-        let central_directory_size = reader.read_u32::<LittleEndian>()?;
-        let central_directory_offset = reader.read_u32::<LittleEndian>()?;
-        let zip_file_comment_length = reader.read_u16::<LittleEndian>()? as usize;
-        let mut zip_file_comment = vec![0; zip_file_comment_length];
-        reader.read_exact(&mut zip_file_comment)?;
-
-        Ok(CentralDirectoryEnd {
-            disk_number,
-            disk_with_central_directory,
-            number_of_files_on_this_disk,
-            number_of_files,
-            central_directory_size,
-            central_directory_offset,
-            zip_file_comment,
-        })
-    }
-
-    #[cfg(feature = "async")]
-    pub async fn parse_async<T: AsyncRead>(reader: Pin<&mut T>) -> ZipResult<CentralDirectoryEnd> {
-        let mut reader = reader.compat();
-        let magic = reader.read_u32_le().await?;
-        if magic != CENTRAL_DIRECTORY_END_SIGNATURE {
+        if LittleEndian::read_u32(bytes) != CENTRAL_DIRECTORY_END_SIGNATURE {
             return Err(ZipError::InvalidArchive("Invalid digital signature header"));
         }
-        let disk_number = reader.read_u16_le().await?;
-        let disk_with_central_directory = reader.read_u16_le().await?;
-        let number_of_files_on_this_disk = reader.read_u16_le().await?;
-        let number_of_files = reader.read_u16_le().await?;
-        let central_directory_size = reader.read_u32_le().await?;
-        let central_directory_offset = reader.read_u32_le().await?;
-        let zip_file_comment_length = reader.read_u16_le().await? as usize;
-        let mut zip_file_comment = vec![0; zip_file_comment_length];
-
-        let mut reader = reader.into_inner();
-        reader.read_exact(&mut zip_file_comment).await?;
+        if bytes.len() < HEADER_SIZE as usize {
+            return Err(unexpected_eof());
+        }
+        let zip_file_comment_length = LittleEndian::read_u16(&bytes[20..]) as usize;
+        let zip_file_comment = match bytes.get(22..22 + zip_file_comment_length) {
+            Some(comment) => comment.to_vec(),
+            None => return Err(unexpected_eof()),
+        };
 
         Ok(CentralDirectoryEnd {
-            disk_number,
-            disk_with_central_directory,
-            number_of_files_on_this_disk,
-            number_of_files,
-            central_directory_size,
-            central_directory_offset,
+            disk_number: LittleEndian::read_u16(&bytes[4..]),
+            disk_with_central_directory: LittleEndian::read_u16(&bytes[6..]),
+            number_of_files_on_this_disk: LittleEndian::read_u16(&bytes[8..]),
+            number_of_files: LittleEndian::read_u16(&bytes[10..]),
+            central_directory_size: LittleEndian::read_u32(&bytes[12..]),
+            central_directory_offset: LittleEndian::read_u32(&bytes[16..]),
             zip_file_comment,
         })
     }
 
+    /// Find the central directory end record by searching backwards from the end of `reader`, and
+    /// parse it.
+    ///
+    /// The record must start at most `max_search_distance` bytes before the end. The last record
+    /// whose comment reaches exactly to the end is taken, so that a record in the comment of
+    /// another one is not mistaken for it. If `trailing_data` is set and there is no such record,
+    /// the last record whose comment fits is taken instead.
     pub fn find_and_parse<T: Read + io::Seek>(
         reader: &mut T,
+        max_search_distance: u64,
+        trailing_data: bool,
     ) -> ZipResult<(CentralDirectoryEnd, u64)> {
-        const HEADER_SIZE: u64 = 22;
-        const BYTES_BETWEEN_MAGIC_AND_COMMENT_SIZE: u64 = HEADER_SIZE - 6;
         let file_length = reader.seek(io::SeekFrom::End(0))?;
-
-        let search_upper_bound = file_length.saturating_sub(HEADER_SIZE + ::std::u16::MAX as u64);
-
-        if file_length < HEADER_SIZE {
-            return Err(ZipError::InvalidArchive("Invalid zip header"));
-        }
-
-        let mut pos = file_length - HEADER_SIZE;
-        while pos >= search_upper_bound {
-            reader.seek(io::SeekFrom::Start(pos as u64))?;
-            if reader.read_u32::<LittleEndian>()? == CENTRAL_DIRECTORY_END_SIGNATURE {
-                reader.seek(io::SeekFrom::Current(
-                    BYTES_BETWEEN_MAGIC_AND_COMMENT_SIZE as i64,
-                ))?;
-                let cde_start_pos = reader.seek(io::SeekFrom::Start(pos as u64))?;
-                return CentralDirectoryEnd::parse(reader).map(|cde| (cde, cde_start_pos));
-            }
-            pos = match pos.checked_sub(1) {
-                Some(p) => p,
-                None => break,
-            };
-        }
-        Err(ZipError::InvalidArchive(
-            "Could not find central directory end",
-        ))
+        let parser = EndRecordParser::new(file_length, max_search_distance, trailing_data);
+        drive(parser, reader)
     }
 
+    /// See [`CentralDirectoryEnd::find_and_parse`].
     #[cfg(feature = "async")]
     pub async fn find_and_parse_async<T: AsyncRead + AsyncSeek>(
         mut reader: Pin<&mut T>,
+        max_search_distance: u64,
+        trailing_data: bool,
     ) -> ZipResult<(CentralDirectoryEnd, u64)> {
-        const HEADER_SIZE: u64 = 22;
-        const BYTES_BETWEEN_MAGIC_AND_COMMENT_SIZE: u64 = HEADER_SIZE - 6;
         let file_length = reader.seek(io::SeekFrom::End(0)).await?;
-
-        let search_upper_bound = file_length.saturating_sub(HEADER_SIZE + ::std::u16::MAX as u64);
-
-        if file_length < HEADER_SIZE {
-            return Err(ZipError::InvalidArchive("Invalid zip header"));
-        }
-
-        let mut pos = file_length - HEADER_SIZE;
-        while pos >= search_upper_bound {
-            reader.seek(io::SeekFrom::Start(pos as u64)).await?;
-            if reader.compat_mut().read_u32_le().await.expect("fff")
-                == CENTRAL_DIRECTORY_END_SIGNATURE
-            {
-                reader
-                    .seek(io::SeekFrom::Current(
-                        BYTES_BETWEEN_MAGIC_AND_COMMENT_SIZE as i64,
-                    ))
-                    .await?;
-                let comment_length = reader.compat_mut().read_u16_le().await? as u64;
-                if file_length - pos - HEADER_SIZE == comment_length {
-                    let cde_start_pos = reader.seek(io::SeekFrom::Start(pos as u64)).await?;
-                    return CentralDirectoryEnd::parse_async(reader)
-                        .await
-                        .map(|cde| (cde, cde_start_pos));
-                }
-            }
-            pos = match pos.checked_sub(1) {
-                Some(p) => p,
-                None => break,
-            };
-        }
-        Err(ZipError::InvalidArchive(
-            "Could not find central directory end",
-        ))
+        let parser = EndRecordParser::new(file_length, max_search_distance, trailing_data);
+        drive_async(parser, reader).await
     }
 
     pub fn write<T: Write>(&self, writer: &mut T) -> ZipResult<()> {
@@ -178,21 +180,14 @@ impl CentralDirectoryEnd {
 
     #[cfg(feature = "async")]
     pub async fn write_async<T: AsyncWrite + Unpin>(&self, writer: &mut T) -> ZipResult<()> {
-        let mut writer = Compat(writer);
-        writer.write_u32_le(CENTRAL_DIRECTORY_END_SIGNATURE).await?;
-        writer.write_u16_le(self.disk_number).await?;
-        writer
-            .write_u16_le(self.disk_with_central_directory)
-            .await?;
-        writer
-            .write_u16_le(self.number_of_files_on_this_disk)
-            .await?;
-        writer.write_u16_le(self.number_of_files).await?;
-        writer.write_u32_le(self.central_directory_size).await?;
-        writer.write_u32_le(self.central_directory_offset).await?;
-        writer
-            .write_u16_le(self.zip_file_comment.len() as u16)
-            .await?;
+        write_u32_le(writer, CENTRAL_DIRECTORY_END_SIGNATURE).await?;
+        write_u16_le(writer, self.disk_number).await?;
+        write_u16_le(writer, self.disk_with_central_directory).await?;
+        write_u16_le(writer, self.number_of_files_on_this_disk).await?;
+        write_u16_le(writer, self.number_of_files).await?;
+        write_u32_le(writer, self.central_directory_size).await?;
+        write_u32_le(writer, self.central_directory_offset).await?;
+        write_u16_le(writer, self.zip_file_comment.len() as u16).await?;
         writer.write_all(&self.zip_file_comment).await?;
         Ok(())
     }
@@ -205,43 +200,57 @@ pub struct Zip64CentralDirectoryEndLocator {
 }
 
 impl Zip64CentralDirectoryEndLocator {
-    pub fn parse<T: Read>(reader: &mut T) -> ZipResult<Zip64CentralDirectoryEndLocator> {
-        let magic = reader.read_u32::<LittleEndian>()?;
-        if magic != ZIP64_CENTRAL_DIRECTORY_END_LOCATOR_SIGNATURE {
+    /// Parse the locator from `bytes`, which start with its signature.
+    pub fn from_bytes(bytes: &[u8]) -> ZipResult<Zip64CentralDirectoryEndLocator> {
+        if bytes.len() < 4 {
+            return Err(unexpected_eof());
+        }
+        if LittleEndian::read_u32(bytes) != ZIP64_CENTRAL_DIRECTORY_END_LOCATOR_SIGNATURE {
             return Err(ZipError::InvalidArchive(
                 "Invalid zip64 locator digital signature header",
             ));
         }
-        let disk_with_central_directory = reader.read_u32::<LittleEndian>()?;
-        let end_of_central_directory_offset = reader.read_u64::<LittleEndian>()?;
-        let number_of_disks = reader.read_u32::<LittleEndian>()?;
+        if bytes.len() < 20 {
+            return Err(unexpected_eof());
+        }
 
         Ok(Zip64CentralDirectoryEndLocator {
-            disk_with_central_directory,
-            end_of_central_directory_offset,
-            number_of_disks,
+            disk_with_central_directory: LittleEndian::read_u32(&bytes[4..]),
+            end_of_central_directory_offset: LittleEndian::read_u64(&bytes[8..]),
+            number_of_disks: LittleEndian::read_u32(&bytes[16..]),
         })
     }
 
+    pub fn parse<T: Read>(reader: &mut T) -> ZipResult<Zip64CentralDirectoryEndLocator> {
+        let mut bytes = Vec::new();
+        reader.take(20).read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+
     #[cfg(feature = "async")]
     pub async fn parse_async<T: AsyncRead>(
-        mut reader: Pin<&mut T>,
+        reader: Pin<&mut T>,
     ) -> ZipResult<Zip64CentralDirectoryEndLocator> {
-        let magic = reader.compat_mut().read_u32_le().await?;
-        if magic != ZIP64_CENTRAL_DIRECTORY_END_LOCATOR_SIGNATURE {
-            return Err(ZipError::InvalidArchive(
-                "Invalid zip64 locator digital signature header",
-            ));
-        }
-        let disk_with_central_directory = reader.compat_mut().read_u32_le().await?;
-        let end_of_central_directory_offset = reader.compat_mut().read_u64_le().await?;
-        let number_of_disks = reader.compat_mut().read_u32_le().await?;
+        let mut bytes = Vec::new();
+        reader.take(20).read_to_end(&mut bytes).await?;
+        Self::from_bytes(&bytes)
+    }
 
-        Ok(Zip64CentralDirectoryEndLocator {
-            disk_with_central_directory,
-            end_of_central_directory_offset,
-            number_of_disks,
-        })
+    pub fn write<T: Write>(&self, writer: &mut T) -> ZipResult<()> {
+        writer.write_u32::<LittleEndian>(ZIP64_CENTRAL_DIRECTORY_END_LOCATOR_SIGNATURE)?;
+        writer.write_u32::<LittleEndian>(self.disk_with_central_directory)?;
+        writer.write_u64::<LittleEndian>(self.end_of_central_directory_offset)?;
+        writer.write_u32::<LittleEndian>(self.number_of_disks)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn write_async<T: AsyncWrite + Unpin>(&self, writer: &mut T) -> ZipResult<()> {
+        write_u32_le(writer, ZIP64_CENTRAL_DIRECTORY_END_LOCATOR_SIGNATURE).await?;
+        write_u32_le(writer, self.disk_with_central_directory).await?;
+        write_u64_le(writer, self.end_of_central_directory_offset).await?;
+        write_u32_le(writer, self.number_of_disks).await?;
+        Ok(())
     }
 }
 
@@ -254,7 +263,45 @@ pub struct Zip64CentralDirectoryEnd {
     pub number_of_files: u64,
     pub central_directory_size: u64,
     pub central_directory_offset: u64,
-    //pub extensible_data_sector: Vec<u8>, <-- We don't do anything with this at the moment.
+    /// The algorithm the central directory is encrypted with, from version 2 of this record
+    pub central_directory_encryption: Option<u16>,
+    /// The rest of the record, which starts with the central directory encryption fields in
+    /// version 2
+    pub extensible_data_sector: Vec<u8>,
+}
+
+/// The version needed to extract archives with an encrypted central directory
+const CENTRAL_DIRECTORY_ENCRYPTION_VERSION: u16 = 62;
+
+/// The size of version 1 of the ZIP64 central directory end record, after the size field
+const ZIP64_CENTRAL_DIRECTORY_END_V1_SIZE: u64 = 44;
+
+/// The size of version 2 of the ZIP64 central directory end record, after the size field
+const ZIP64_CENTRAL_DIRECTORY_END_V2_SIZE: u64 = 72;
+
+/// The size of version 1 of the ZIP64 central directory end record, with its signature and size
+const ZIP64_CENTRAL_DIRECTORY_END_MIN_SIZE: usize =
+    12 + ZIP64_CENTRAL_DIRECTORY_END_V1_SIZE as usize;
+
+/// The largest distance of the ZIP64 central directory end record from its locator that is
+/// searched when the offset in the locator is wrong
+pub const MAX_ZIP64_END_DISTANCE: u64 = 1 << 16;
+
+/// The algorithm of the central directory encryption fields of version 2 of the ZIP64 central
+/// directory end record, if it is that version and the algorithm is set
+fn central_directory_encryption(version_needed_to_extract: u16, sector: &[u8]) -> Option<u16> {
+    let v2_fields_size =
+        (ZIP64_CENTRAL_DIRECTORY_END_V2_SIZE - ZIP64_CENTRAL_DIRECTORY_END_V1_SIZE) as usize;
+    if version_needed_to_extract & 0xFF < CENTRAL_DIRECTORY_ENCRYPTION_VERSION
+        || sector.len() < v2_fields_size
+    {
+        return None;
+    }
+    // After the compression method, compressed size and original size
+    match LittleEndian::read_u16(&sector[18..20]) {
+        0 => None,
+        algorithm => Some(algorithm),
+    }
 }
 
 impl Zip64CentralDirectoryEnd {
@@ -263,96 +310,590 @@ impl Zip64CentralDirectoryEnd {
         nominal_offset: u64,
         search_upper_bound: u64,
     ) -> ZipResult<(Zip64CentralDirectoryEnd, u64)> {
-        let mut pos = nominal_offset;
-
-        while pos <= search_upper_bound {
-            reader.seek(io::SeekFrom::Start(pos))?;
-
-            if reader.read_u32::<LittleEndian>()? == ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE {
-                let archive_offset = pos - nominal_offset;
-
-                let _record_size = reader.read_u64::<LittleEndian>()?;
-                // We would use this value if we did anything with the "zip64 extensible data sector".
-
-                let version_made_by = reader.read_u16::<LittleEndian>()?;
-                let version_needed_to_extract = reader.read_u16::<LittleEndian>()?;
-                let disk_number = reader.read_u32::<LittleEndian>()?;
-                let disk_with_central_directory = reader.read_u32::<LittleEndian>()?;
-                let number_of_files_on_this_disk = reader.read_u64::<LittleEndian>()?;
-                let number_of_files = reader.read_u64::<LittleEndian>()?;
-                let central_directory_size = reader.read_u64::<LittleEndian>()?;
-                let central_directory_offset = reader.read_u64::<LittleEndian>()?;
-
-                return Ok((
-                    Zip64CentralDirectoryEnd {
-                        version_made_by,
-                        version_needed_to_extract,
-                        disk_number,
-                        disk_with_central_directory,
-                        number_of_files_on_this_disk,
-                        number_of_files,
-                        central_directory_size,
-                        central_directory_offset,
-                    },
-                    archive_offset,
-                ));
-            }
-
-            pos += 1;
-        }
+        drive(
+            Zip64EndParser::new(nominal_offset, search_upper_bound),
+            reader,
+        )
+    }
 
-        Err(ZipError::InvalidArchive(
-            "Could not find ZIP64 central directory end",
-        ))
+    /// Search backwards for the record that ends at `end`, where its locator starts, in at most
+    /// `max_search_distance` bytes before it, for when the offset in the locator is wrong
+    ///
+    /// Returns the record and its position.
+    pub fn find_before<T: Read + io::Seek>(
+        reader: &mut T,
+        end: u64,
+        max_search_distance: u64,
+    ) -> ZipResult<(Zip64CentralDirectoryEnd, u64)> {
+        drive(Zip64EndBeforeParser::new(end, max_search_distance), reader)
     }
 
     #[cfg(feature = "async")]
     pub async fn find_and_parse_async<T: AsyncRead + AsyncSeek>(
-        mut reader: Pin<&mut T>,
+        reader: Pin<&mut T>,
         nominal_offset: u64,
         search_upper_bound: u64,
     ) -> ZipResult<(Zip64CentralDirectoryEnd, u64)> {
-        let mut pos = nominal_offset;
-
-        while pos <= search_upper_bound {
-            reader.seek(io::SeekFrom::Start(pos)).await?;
-
-            if reader.compat_mut().read_u32_le().await? == ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE {
-                let archive_offset = pos - nominal_offset;
-                let mut reader = reader.compat_mut();
-
-                let _record_size = reader.read_u64_le().await?;
-                // We would use this value if we did anything with the "zip64 extensible data sector".
-
-                let version_made_by = reader.read_u16_le().await?;
-                let version_needed_to_extract = reader.read_u16_le().await?;
-                let disk_number = reader.read_u32_le().await?;
-                let disk_with_central_directory = reader.read_u32_le().await?;
-                let number_of_files_on_this_disk = reader.read_u64_le().await?;
-                let number_of_files = reader.read_u64_le().await?;
-                let central_directory_size = reader.read_u64_le().await?;
-                let central_directory_offset = reader.read_u64_le().await?;
-
-                return Ok((
-                    Zip64CentralDirectoryEnd {
-                        version_made_by,
-                        version_needed_to_extract,
-                        disk_number,
-                        disk_with_central_directory,
-                        number_of_files_on_this_disk,
-                        number_of_files,
-                        central_directory_size,
-                        central_directory_offset,
-                    },
-                    archive_offset,
-                ));
+        let parser = Zip64EndParser::new(nominal_offset, search_upper_bound);
+        drive_async(parser, reader).await
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn find_before_async<T: AsyncRead + AsyncSeek>(
+        reader: Pin<&mut T>,
+        end: u64,
+        max_search_distance: u64,
+    ) -> ZipResult<(Zip64CentralDirectoryEnd, u64)> {
+        let parser = Zip64EndBeforeParser::new(end, max_search_distance);
+        drive_async(parser, reader).await
+    }
+
+    /// Parse the record from `bytes`, which start after its signature. The extensible data sector
+    /// is cut short when `bytes` end before it does.
+    fn from_bytes(bytes: &[u8]) -> ZipResult<Zip64CentralDirectoryEnd> {
+        let fixed_size = 8 + ZIP64_CENTRAL_DIRECTORY_END_V1_SIZE as usize;
+        if bytes.len() < fixed_size {
+            return Err(unexpected_eof());
+        }
+        let record_size = LittleEndian::read_u64(bytes);
+        let version_needed_to_extract = LittleEndian::read_u16(&bytes[10..]);
+        let sector_size = record_size.saturating_sub(ZIP64_CENTRAL_DIRECTORY_END_V1_SIZE);
+        let sector_end = (fixed_size as u64).saturating_add(sector_size);
+        let sector_end = sector_end.min(bytes.len() as u64) as usize;
+        let extensible_data_sector = bytes[fixed_size..sector_end].to_vec();
+        let central_directory_encryption =
+            central_directory_encryption(version_needed_to_extract, &extensible_data_sector);
+
+        Ok(Zip64CentralDirectoryEnd {
+            version_made_by: LittleEndian::read_u16(&bytes[8..]),
+            version_needed_to_extract,
+            disk_number: LittleEndian::read_u32(&bytes[12..]),
+            disk_with_central_directory: LittleEndian::read_u32(&bytes[16..]),
+            number_of_files_on_this_disk: LittleEndian::read_u64(&bytes[20..]),
+            number_of_files: LittleEndian::read_u64(&bytes[28..]),
+            central_directory_size: LittleEndian::read_u64(&bytes[36..]),
+            central_directory_offset: LittleEndian::read_u64(&bytes[44..]),
+            central_directory_encryption,
+            extensible_data_sector,
+        })
+    }
+
+    /// Write the record, which is version 1 unless the extensible data sector starts with the
+    /// fields of version 2
+    pub fn write<T: Write>(&self, writer: &mut T) -> ZipResult<()> {
+        writer.write_u32::<LittleEndian>(ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE)?;
+        writer.write_u64::<LittleEndian>(self.record_size())?;
+        writer.write_u16::<LittleEndian>(self.version_made_by)?;
+        writer.write_u16::<LittleEndian>(self.version_needed_to_extract)?;
+        writer.write_u32::<LittleEndian>(self.disk_number)?;
+        writer.write_u32::<LittleEndian>(self.disk_with_central_directory)?;
+        writer.write_u64::<LittleEndian>(self.number_of_files_on_this_disk)?;
+        writer.write_u64::<LittleEndian>(self.number_of_files)?;
+        writer.write_u64::<LittleEndian>(self.central_directory_size)?;
+        writer.write_u64::<LittleEndian>(self.central_directory_offset)?;
+        writer.write_all(&self.extensible_data_sector)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn write_async<T: AsyncWrite + Unpin>(&self, writer: &mut T) -> ZipResult<()> {
+        write_u32_le(writer, ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE).await?;
+        write_u64_le(writer, self.record_size()).await?;
+        write_u16_le(writer, self.version_made_by).await?;
+        write_u16_le(writer, self.version_needed_to_extract).await?;
+        write_u32_le(writer, self.disk_number).await?;
+        write_u32_le(writer, self.disk_with_central_directory).await?;
+        write_u64_le(writer, self.number_of_files_on_this_disk).await?;
+        write_u64_le(writer, self.number_of_files).await?;
+        write_u64_le(writer, self.central_directory_size).await?;
+        write_u64_le(writer, self.central_directory_offset).await?;
+        writer.write_all(&self.extensible_data_sector).await?;
+        Ok(())
+    }
+
+    /// The size of the record after the size field
+    fn record_size(&self) -> u64 {
+        ZIP64_CENTRAL_DIRECTORY_END_V1_SIZE + self.extensible_data_sector.len() as u64
+    }
+}
+
+/// The search for the central directory end record, see [`CentralDirectoryEnd::find_and_parse`]
+pub struct EndRecordParser {
+    file_length: u64,
+    search_upper_bound: u64,
+    trailing_data: bool,
+    state: EndRecordState,
+    /// The end of the next chunk that is searched
+    chunk_end: u64,
+    /// The position and comment length of the last record whose comment fits, with trailing data
+    found: Option<(u64, u64)>,
+    bytes: Vec<u8>,
+}
+
+enum EndRecordState {
+    Start,
+    /// The chunk from `start` to `end` was asked for, with the 18 bytes after it that hold the
+    /// comment lengths of the records starting in it
+    Chunk {
+        start: u64,
+        end: u64,
+    },
+    /// The record at this position was asked for
+    Record(u64),
+}
+
+impl EndRecordParser {
+    /// Search the last `max_search_distance` bytes of an input of `file_length` bytes.
+    pub fn new(file_length: u64, max_search_distance: u64, trailing_data: bool) -> EndRecordParser {
+        EndRecordParser {
+            file_length,
+            search_upper_bound: file_length.saturating_sub(max_search_distance),
+            trailing_data,
+            state: EndRecordState::Start,
+            chunk_end: 0,
+            found: None,
+            bytes: Vec::new(),
+        }
+    }
+
+    fn read_record(&mut self, pos: u64, comment_length: u64) -> Step<(CentralDirectoryEnd, u64)> {
+        self.state = EndRecordState::Record(pos);
+        Step::Read {
+            offset: pos,
+            length: HEADER_SIZE + comment_length,
+        }
+    }
+}
+
+impl Parser for EndRecordParser {
+    type Output = (CentralDirectoryEnd, u64);
+
+    fn step(&mut self) -> ZipResult<Step<(CentralDirectoryEnd, u64)>> {
+        match self.state {
+            EndRecordState::Start => {
+                if self.file_length < HEADER_SIZE {
+                    return Err(ZipError::InvalidArchive("Invalid zip header"));
+                }
+                self.chunk_end = self.file_length - HEADER_SIZE + 4;
             }
+            EndRecordState::Chunk { start, end } => {
+                if (self.bytes.len() as u64) < end + 18 - start {
+                    return Err(unexpected_eof());
+                }
+                // The signatures in the chunk are searched from the last to the first. Chunks
+                // overlap by 3 bytes, so that signatures crossing their boundary are found too.
+                for offset in (0..(end - start) as usize - 3).rev() {
+                    let bytes = &self.bytes[offset..];
+                    if LittleEndian::read_u32(bytes) != CENTRAL_DIRECTORY_END_SIGNATURE {
+                        continue;
+                    }
+                    let pos = start + offset as u64;
+                    let comment_length = LittleEndian::read_u16(&bytes[20..]) as u64;
+                    let trailing_length = self.file_length - pos - HEADER_SIZE;
+                    if trailing_length == comment_length {
+                        return Ok(self.read_record(pos, comment_length));
+                    }
+                    if self.trailing_data
+                        && trailing_length > comment_length
+                        && self.found.is_none()
+                    {
+                        self.found = Some((pos, comment_length));
+                    }
+                }
+                self.chunk_end = start + 3;
+            }
+            EndRecordState::Record(pos) => {
+                return CentralDirectoryEnd::from_bytes(&self.bytes)
+                    .map(|cde| Step::Done((cde, pos)));
+            }
+        }
+
+        if self.chunk_end >= self.search_upper_bound + 4 {
+            let start = self
+                .chunk_end
+                .saturating_sub(SEARCH_CHUNK_SIZE)
+                .max(self.search_upper_bound);
+            let end = self.chunk_end;
+            self.state = EndRecordState::Chunk { start, end };
+            return Ok(Step::Read {
+                offset: start,
+                length: end + 18 - start,
+            });
+        }
+        match self.found {
+            Some((pos, comment_length)) => Ok(self.read_record(pos, comment_length)),
+            None => Err(ZipError::InvalidArchive(
+                "Could not find central directory end",
+            )),
+        }
+    }
+
+    fn feed(&mut self, bytes: &[u8]) {
+        self.bytes.clear();
+        self.bytes.extend_from_slice(bytes);
+    }
+}
+
+/// The search forwards for the ZIP64 central directory end record, from the offset in its locator
+/// to `search_upper_bound`, see [`Zip64CentralDirectoryEnd::find_and_parse`]
+///
+/// The position of the record relative to the offset in the locator is returned with it.
+pub struct Zip64EndParser {
+    nominal_offset: u64,
+    search_upper_bound: u64,
+    state: Zip64EndState,
+    bytes: Vec<u8>,
+}
+
+enum Zip64EndState {
+    Start,
+    /// The chunk with the signatures at the positions from `start` to `last` was asked for
+    Chunk {
+        start: u64,
+        last: u64,
+    },
+    /// The fixed fields of the record at this position were asked for
+    Fixed(u64),
+    /// The extensible data sector of the record at this position was asked for
+    Extensible(u64),
+}
+
+impl Zip64EndParser {
+    pub fn new(nominal_offset: u64, search_upper_bound: u64) -> Zip64EndParser {
+        Zip64EndParser {
+            nominal_offset,
+            search_upper_bound,
+            state: Zip64EndState::Start,
+            bytes: Vec::new(),
+        }
+    }
+}
+
+impl Parser for Zip64EndParser {
+    type Output = (Zip64CentralDirectoryEnd, u64);
+
+    fn step(&mut self) -> ZipResult<Step<(Zip64CentralDirectoryEnd, u64)>> {
+        let next = match self.state {
+            Zip64EndState::Start => self.nominal_offset,
+            Zip64EndState::Chunk { start, last } => {
+                for offset in 0..=(last - start) as usize {
+                    let signature = match self.bytes.get(offset..offset + 4) {
+                        Some(signature) => LittleEndian::read_u32(signature),
+                        None => return Err(unexpected_eof()),
+                    };
+                    if signature == ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE {
+                        let pos = start + offset as u64;
+                        self.state = Zip64EndState::Fixed(pos);
+                        return Ok(Step::Read {
+                            offset: pos + 4,
+                            length: 8 + ZIP64_CENTRAL_DIRECTORY_END_V1_SIZE,
+                        });
+                    }
+                }
+                last + 1
+            }
+            Zip64EndState::Fixed(pos) => {
+                if self.bytes.len() < ZIP64_CENTRAL_DIRECTORY_END_MIN_SIZE - 4 {
+                    return Err(unexpected_eof());
+                }
+                let record_size = LittleEndian::read_u64(&self.bytes);
+                self.state = Zip64EndState::Extensible(pos);
+                return Ok(Step::Read {
+                    offset: pos + 12 + ZIP64_CENTRAL_DIRECTORY_END_V1_SIZE,
+                    length: record_size.saturating_sub(ZIP64_CENTRAL_DIRECTORY_END_V1_SIZE),
+                });
+            }
+            Zip64EndState::Extensible(pos) => {
+                let archive_offset = pos - self.nominal_offset;
+                return Zip64CentralDirectoryEnd::from_bytes(&self.bytes)
+                    .map(|footer| Step::Done((footer, archive_offset)));
+            }
+        };
+
+        if next > self.search_upper_bound {
+            return Err(ZipError::InvalidArchive(
+                "Could not find ZIP64 central directory end",
+            ));
+        }
+        let last = (next + SEARCH_CHUNK_SIZE - 4).min(self.search_upper_bound);
+        self.state = Zip64EndState::Chunk { start: next, last };
+        Ok(Step::Read {
+            offset: next,
+            length: last - next + 4,
+        })
+    }
+
+    fn feed(&mut self, bytes: &[u8]) {
+        match self.state {
+            // The extensible data sector is parsed together with the fixed fields before it
+            Zip64EndState::Extensible(_) => {}
+            _ => self.bytes.clear(),
+        }
+        self.bytes.extend_from_slice(bytes);
+    }
+}
 
-            pos += 1;
+/// The search backwards for the ZIP64 central directory end record that ends at the start of its
+/// locator, see [`Zip64CentralDirectoryEnd::find_before`]
+pub struct Zip64EndBeforeParser {
+    start: u64,
+    end: u64,
+    window: Option<Vec<u8>>,
+}
+
+impl Zip64EndBeforeParser {
+    pub fn new(end: u64, max_search_distance: u64) -> Zip64EndBeforeParser {
+        Zip64EndBeforeParser {
+            start: end.saturating_sub(max_search_distance),
+            end,
+            window: None,
+        }
+    }
+}
+
+impl Parser for Zip64EndBeforeParser {
+    type Output = (Zip64CentralDirectoryEnd, u64);
+
+    fn step(&mut self) -> ZipResult<Step<(Zip64CentralDirectoryEnd, u64)>> {
+        let window = match &self.window {
+            Some(window) => window,
+            None => {
+                return Ok(Step::Read {
+                    offset: self.start,
+                    length: self.end - self.start,
+                })
+            }
+        };
+        if (window.len() as u64) < self.end - self.start {
+            return Err(unexpected_eof());
+        }
+        for offset in
+            (0..(window.len() + 1).saturating_sub(ZIP64_CENTRAL_DIRECTORY_END_MIN_SIZE)).rev()
+        {
+            if LittleEndian::read_u32(&window[offset..]) != ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE {
+                continue;
+            }
+            let pos = self.start + offset as u64;
+            let record_size = LittleEndian::read_u64(&window[offset + 4..])
+                .max(ZIP64_CENTRAL_DIRECTORY_END_V1_SIZE);
+            if pos.checked_add(12).and_then(|n| n.checked_add(record_size)) == Some(self.end) {
+                let footer = Zip64CentralDirectoryEnd::from_bytes(&window[offset + 4..])?;
+                return Ok(Step::Done((footer, pos)));
+            }
         }
 
         Err(ZipError::InvalidArchive(
             "Could not find ZIP64 central directory end",
         ))
     }
+
+    fn feed(&mut self, bytes: &[u8]) {
+        self.window = Some(bytes.to_vec());
+    }
+}
+
+/// The CRC-32 and sizes that follow the data of a file with bit 3 of its flags set, in which case
+/// they are missing from the local header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DataDescriptor {
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+}
+
+/// Reading data descriptors, which is only needed to stream deflated files
+#[cfg(any(
+    feature = "deflate",
+    feature = "deflate-miniz",
+    feature = "deflate-zlib"
+))]
+impl DataDescriptor {
+    /// The length of a data descriptor without its signature, which has 8 byte sizes if `zip64`
+    /// is set and 4 byte sizes otherwise.
+    pub fn length(zip64: bool) -> usize {
+        if zip64 {
+            20
+        } else {
+            12
+        }
+    }
+
+    /// Whether the data descriptor at the start of `bytes`, which are at least 8 bytes, has a
+    /// signature.
+    ///
+    /// The signature is optional, so a CRC-32 that equals it is told apart by `crc32`, the
+    /// checksum of the data that was read.
+    pub fn has_signature(bytes: &[u8], crc32: u32) -> bool {
+        LittleEndian::read_u32(bytes) == DATA_DESCRIPTOR_SIGNATURE
+            && (crc32 != DATA_DESCRIPTOR_SIGNATURE || LittleEndian::read_u32(&bytes[4..]) == crc32)
+    }
+
+    /// Parse a data descriptor without its signature from `bytes`, which are
+    /// [`DataDescriptor::length`] bytes.
+    pub fn from_bytes(bytes: &[u8], zip64: bool) -> DataDescriptor {
+        let crc32 = LittleEndian::read_u32(bytes);
+        let (compressed_size, uncompressed_size) = if zip64 {
+            (
+                LittleEndian::read_u64(&bytes[4..]),
+                LittleEndian::read_u64(&bytes[12..]),
+            )
+        } else {
+            (
+                LittleEndian::read_u32(&bytes[4..]) as u64,
+                LittleEndian::read_u32(&bytes[8..]) as u64,
+            )
+        };
+        DataDescriptor {
+            crc32,
+            compressed_size,
+            uncompressed_size,
+        }
+    }
+
+    /// Read a data descriptor with or without its signature, which follows data with the
+    /// checksum `crc32`.
+    pub fn parse<T: Read>(reader: &mut T, zip64: bool, crc32: u32) -> ZipResult<DataDescriptor> {
+        let length = Self::length(zip64);
+        let mut bytes = [0; 24];
+        reader.read_exact(&mut bytes[..length])?;
+        if !Self::has_signature(&bytes, crc32) {
+            return Ok(Self::from_bytes(&bytes, zip64));
+        }
+        reader.read_exact(&mut bytes[length..length + 4])?;
+        Ok(Self::from_bytes(&bytes[4..], zip64))
+    }
+}
+
+impl DataDescriptor {
+    /// Write the data descriptor with its signature and 8 byte sizes if `zip64` is set.
+    pub fn write<T: Write>(&self, writer: &mut T, zip64: bool) -> ZipResult<()> {
+        writer.write_u32::<LittleEndian>(DATA_DESCRIPTOR_SIGNATURE)?;
+        writer.write_u32::<LittleEndian>(self.crc32)?;
+        if zip64 {
+            writer.write_u64::<LittleEndian>(self.compressed_size)?;
+            writer.write_u64::<LittleEndian>(self.uncompressed_size)?;
+        } else {
+            writer.write_u32::<LittleEndian>(self.compressed_size as u32)?;
+            writer.write_u32::<LittleEndian>(self.uncompressed_size as u32)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn write_async<T: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut T,
+        zip64: bool,
+    ) -> ZipResult<()> {
+        write_u32_le(writer, DATA_DESCRIPTOR_SIGNATURE).await?;
+        write_u32_le(writer, self.crc32).await?;
+        if zip64 {
+            write_u64_le(writer, self.compressed_size).await?;
+            write_u64_le(writer, self.uncompressed_size).await?;
+        } else {
+            write_u32_le(writer, self.compressed_size as u32).await?;
+            write_u32_le(writer, self.uncompressed_size as u32).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Run `parser` on `input` without a reader, as another frontend would
+    fn run<P: Parser>(mut parser: P, input: &[u8]) -> ZipResult<P::Output> {
+        loop {
+            match parser.step()? {
+                Step::Read { offset, length } => {
+                    let start = (offset as usize).min(input.len());
+                    let end = (offset.saturating_add(length) as usize).min(input.len());
+                    parser.feed(&input[start..end]);
+                }
+                Step::Done(output) => return Ok(output),
+            }
+        }
+    }
+
+    fn end_record(comment: &[u8]) -> Vec<u8> {
+        let footer = CentralDirectoryEnd {
+            disk_number: 0,
+            disk_with_central_directory: 0,
+            number_of_files_on_this_disk: 1,
+            number_of_files: 1,
+            central_directory_size: 2,
+            central_directory_offset: 3,
+            zip_file_comment: comment.to_vec(),
+        };
+        let mut bytes = Vec::new();
+        footer.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn end_record_parser() {
+        // A record in the comment of the last one is skipped
+        let mut input = vec![0; 5000];
+        let inner = end_record(b"");
+        let mut comment = inner.clone();
+        comment.extend_from_slice(b" and more");
+        input.extend_from_slice(&end_record(&comment));
+
+        let (footer, pos) = run(
+            EndRecordParser::new(input.len() as u64, 1 << 16, false),
+            &input,
+        )
+        .unwrap();
+        assert_eq!(pos, 5000);
+        assert_eq!(footer.zip_file_comment, comment);
+        assert_eq!(footer.central_directory_offset, 3);
+
+        let mut reader = Cursor::new(&input);
+        let (footer, pos) =
+            CentralDirectoryEnd::find_and_parse(&mut reader, 1 << 16, false).unwrap();
+        assert_eq!(pos, 5000);
+        assert_eq!(footer.zip_file_comment, comment);
+        assert_eq!(reader.position(), input.len() as u64);
+
+        input.extend_from_slice(b"trailing data");
+        let parser = EndRecordParser::new(input.len() as u64, 1 << 16, false);
+        assert!(run(parser, &input).is_err());
+        // With trailing data, the last record whose comment fits is the one in the comment
+        let parser = EndRecordParser::new(input.len() as u64, 1 << 16, true);
+        assert_eq!(run(parser, &input).unwrap().1, 5022);
+        assert!(run(EndRecordParser::new(10, 1 << 16, false), &input[..10]).is_err());
+    }
+
+    #[test]
+    fn zip64_end_parsers() {
+        let footer = Zip64CentralDirectoryEnd {
+            version_made_by: 45,
+            version_needed_to_extract: 45,
+            disk_number: 0,
+            disk_with_central_directory: 0,
+            number_of_files_on_this_disk: 70000,
+            number_of_files: 70000,
+            central_directory_size: 10,
+            central_directory_offset: 20,
+            central_directory_encryption: None,
+            extensible_data_sector: b"sector".to_vec(),
+        };
+        let mut input = vec![0; 100];
+        footer.write(&mut input).unwrap();
+        let end = input.len() as u64;
+        input.extend_from_slice(&[0; 20]);
+
+        let (found, archive_offset) = run(Zip64EndParser::new(40, 100), &input).unwrap();
+        assert_eq!(archive_offset, 60);
+        assert_eq!(found.number_of_files, 70000);
+        assert_eq!(found.extensible_data_sector, b"sector");
+        assert!(run(Zip64EndParser::new(40, 99), &input).is_err());
+
+        let (found, pos) = run(Zip64EndBeforeParser::new(end, 1 << 16), &input).unwrap();
+        assert_eq!(pos, 100);
+        assert_eq!(found.central_directory_offset, 20);
+        assert!(run(Zip64EndBeforeParser::new(end - 1, 1 << 16), &input).is_err());
+    }
 }
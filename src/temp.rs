@@ -0,0 +1,140 @@
+//! Temporary storage for data that is kept before it is written out
+//!
+//! A [`ZipWriter`] keeps the compressed data of an encrypted entry until the entry is finished,
+//! because the encryption header depends on all of it. A [`TempStore`] supplies the temporary
+//! files it is kept in: [`MemoryStore`], the default, keeps them in memory, and [`DirStore`]
+//! creates them in a directory. Sandboxed or diskless environments can implement the trait to
+//! keep them anywhere else.
+//!
+//! The temporary files are [`Read`] + [`Write`] + [`Seek`], so they can also be the spool of
+//! [`ZipWriter::new_spooled`].
+//!
+//! ```
+//! use std::io::Write;
+//! use zip::temp::DirStore;
+//! use zip::write::FileOptions;
+//! use zip::ZipWriter;
+//!
+//! # fn main() -> zip::result::ZipResult<()> {
+//! let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+//! zip.set_temp_store(DirStore::new(std::env::temp_dir()));
+//! zip.start_file("secret.txt", FileOptions::default().with_password(b"password"))?;
+//! zip.write_all(b"Hello, World!")?;
+//! zip.finish()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ZipWriter`]: crate::write::ZipWriter
+//! [`ZipWriter::new_spooled`]: crate::write::ZipWriter::new_spooled
+
+use crate::zipcrypto::fill_random;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+/// Creates temporary files, see the [module documentation](self)
+pub trait TempStore: Send + Sync {
+    /// Create an empty temporary file.
+    ///
+    /// The file is dropped once the data in it is written out, and should be deleted then.
+    fn create(&self) -> io::Result<Box<dyn TempFile>>;
+}
+
+/// A temporary file created by a [`TempStore`]
+pub trait TempFile: Read + Write + Seek + Send {}
+
+impl<T: Read + Write + Seek + Send> TempFile for T {}
+
+/// A [`TempStore`] that keeps the temporary files in memory
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemoryStore;
+
+impl TempStore for MemoryStore {
+    fn create(&self) -> io::Result<Box<dyn TempFile>> {
+        Ok(Box::new(io::Cursor::new(Vec::new())))
+    }
+}
+
+/// A [`TempStore`] that creates the temporary files in a directory, and deletes them when they
+/// are dropped
+#[derive(Clone, Debug)]
+pub struct DirStore {
+    dir: PathBuf,
+}
+
+impl DirStore {
+    /// Create the temporary files in `dir`, which must exist.
+    pub fn new<P: AsRef<Path>>(dir: P) -> DirStore {
+        DirStore {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl TempStore for DirStore {
+    fn create(&self) -> io::Result<Box<dyn TempFile>> {
+        loop {
+            let mut random = [0; 8];
+            fill_random(&mut random)?;
+            let path = self.dir.join(format!(
+                ".zip-{}-{:016x}.tmp",
+                std::process::id(),
+                u64::from_le_bytes(random)
+            ));
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&path);
+            match file {
+                Ok(file) => {
+                    return Ok(Box::new(DirFile {
+                        file,
+                        _path: RemoveOnDrop(path),
+                    }))
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// A temporary file of a [`DirStore`]
+struct DirFile {
+    file: File,
+    /// Dropped after `file`, so the file is closed when it is deleted
+    _path: RemoveOnDrop,
+}
+
+/// The path of a file that is deleted when this is dropped
+struct RemoveOnDrop(PathBuf);
+
+impl Read for DirFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Write for DirFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for DirFile {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
@@ -0,0 +1,165 @@
+//! Use of the async types with the `tokio::io` traits
+//!
+//! The async types of this crate are built on the `futures::io` traits. [`TokioCompat`] wraps a
+//! reader or writer of the `tokio::io` traits, like a `tokio::fs::File`, so that it can be read
+//! by an [`AsyncZipArchive`] or written by an [`AsyncZipWriter`]. [`AsyncZipFile`] and
+//! [`AsyncZipWriter`] implement the `tokio::io` traits themselves.
+//!
+//! ```no_run
+//! use futures::io::AsyncReadExt;
+//! use zip::tokio_compat::TokioCompat;
+//! use zip::AsyncZipArchive;
+//!
+//! # async fn read<F>(file: F) -> zip::result::ZipResult<()>
+//! # where
+//! #     F: tokio_crate::io::AsyncRead + tokio_crate::io::AsyncSeek + Send + Sync + Unpin,
+//! # {
+//! let mut archive = AsyncZipArchive::new(TokioCompat::new(file)).await?;
+//! let mut contents = Vec::new();
+//! archive.by_index(0).await?.read_to_end(&mut contents).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`AsyncZipArchive`]: crate::read::AsyncZipArchive
+//! [`AsyncZipFile`]: crate::read::AsyncZipFile
+//! [`AsyncZipWriter`]: crate::write::AsyncZipWriter
+
+use crate::read::AsyncZipFile;
+use crate::write::AsyncZipWriter;
+use futures::io::{AsyncBufRead, AsyncRead, AsyncSeek, AsyncWrite};
+use pin_project::pin_project;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{
+    AsyncBufRead as TokioAsyncBufRead, AsyncRead as TokioAsyncRead, AsyncSeek as TokioAsyncSeek,
+    AsyncWrite as TokioAsyncWrite, ReadBuf,
+};
+
+/// Wrapper that implements the `futures::io` traits for a type that implements the `tokio::io`
+/// traits
+#[pin_project]
+#[derive(Debug)]
+pub struct TokioCompat<T> {
+    #[pin]
+    inner: T,
+    /// The position of a seek that was started but has not completed
+    seek_position: Option<io::SeekFrom>,
+}
+
+impl<T> TokioCompat<T> {
+    /// Wrap `inner`.
+    pub fn new(inner: T) -> TokioCompat<T> {
+        TokioCompat {
+            inner,
+            seek_position: None,
+        }
+    }
+
+    /// Get a reference to the wrapped value.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped value.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwrap the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: TokioAsyncRead> AsyncRead for TokioCompat<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut buf = ReadBuf::new(buf);
+        self.project()
+            .inner
+            .poll_read(cx, &mut buf)
+            .map(|result| result.map(|()| buf.filled().len()))
+    }
+}
+
+impl<T: TokioAsyncSeek> AsyncSeek for TokioCompat<T> {
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        position: io::SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        let mut this = self.project();
+        if *this.seek_position != Some(position) {
+            // A seek to another position that was started before must complete first
+            futures::ready!(this.inner.as_mut().poll_complete(cx))?;
+            this.inner.as_mut().start_seek(position)?;
+            *this.seek_position = Some(position);
+        }
+        let result = futures::ready!(this.inner.poll_complete(cx));
+        *this.seek_position = None;
+        Poll::Ready(result)
+    }
+}
+
+impl<T: TokioAsyncWrite> AsyncWrite for TokioCompat<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.project().inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_shutdown(cx)
+    }
+}
+
+impl<'a> TokioAsyncRead for AsyncZipFile<'a> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let count = futures::ready!(AsyncRead::poll_read(self, cx, buf.initialize_unfilled()))?;
+        buf.advance(count);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<'a> TokioAsyncBufRead for AsyncZipFile<'a> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        AsyncBufRead::poll_fill_buf(self, cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        AsyncBufRead::consume(self, amt)
+    }
+}
+
+impl<W: AsyncWrite + AsyncSeek + Unpin> TokioAsyncWrite for AsyncZipWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        AsyncWrite::poll_write(self, cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        AsyncWrite::poll_flush(self, cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        AsyncWrite::poll_close(self, cx)
+    }
+}
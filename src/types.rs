@@ -1,19 +1,34 @@
 //! Types that specify what is contained in a ZIP.
 
+use crate::spec;
+use std::fmt;
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The system that made a file, which determines how its external attributes are interpreted
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum System {
+    /// MS-DOS and other FAT file systems, with DOS attributes
     Dos = 0,
+    /// Unix, with the file mode in the upper 16 bits of the attributes
     Unix = 3,
-    Unknown,
+    /// Windows NTFS, with DOS attributes
+    Ntfs = 10,
+    /// A system that is not known to this crate
+    Unknown = 255,
 }
 
 impl System {
+    /// Converts the upper byte of the "version made by" field
     pub fn from_u8(system: u8) -> System {
         use self::System::*;
 
         match system {
             0 => Dos,
             3 => Unix,
+            10 => Ntfs,
             _ => Unknown,
         }
     }
@@ -207,6 +222,199 @@ impl DateTime {
 
 pub const DEFAULT_VERSION: u8 = 46;
 
+/// The number of PBKDF2 iterations the WinZip AES format uses to derive the keys
+pub const AES_ITERATION_COUNT: u32 = 1000;
+
+/// The encryption strength of a file encrypted with WinZip AES
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AesMode {
+    /// AES with a 128 bit key
+    Aes128,
+    /// AES with a 192 bit key
+    Aes192,
+    /// AES with a 256 bit key
+    Aes256,
+}
+
+impl AesMode {
+    /// The length of the key in bytes
+    pub fn key_length(&self) -> usize {
+        match self {
+            AesMode::Aes128 => 16,
+            AesMode::Aes192 => 24,
+            AesMode::Aes256 => 32,
+        }
+    }
+
+    /// The length of the salt in bytes, which is half the key length
+    pub fn salt_length(&self) -> usize {
+        self.key_length() / 2
+    }
+}
+
+/// The version of the WinZip AES format of a file
+///
+/// AE-1 files contain the CRC-32 of the data, AE-2 files store a CRC-32 of 0 instead and only rely
+/// on the authentication code. Because the CRC-32 reveals information about the data, AE-2 is
+/// preferable, especially for small files.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AesVendorVersion {
+    /// The CRC-32 of the data is stored
+    Ae1,
+    /// The CRC-32 is not stored
+    Ae2,
+}
+
+/// The algorithm of a file encrypted with PKWARE strong encryption, which is not supported
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StrongEncryptionAlgorithm {
+    /// DES
+    Des,
+    /// RC2
+    Rc2,
+    /// Triple DES with a 168 bit key
+    TripleDes168,
+    /// Triple DES with a 112 bit key
+    TripleDes112,
+    /// AES with a 128 bit key
+    Aes128,
+    /// AES with a 192 bit key
+    Aes192,
+    /// AES with a 256 bit key
+    Aes256,
+    /// RC4
+    Rc4,
+    /// Blowfish
+    Blowfish,
+    /// Twofish
+    Twofish,
+    /// An algorithm with an unknown identifier, or a file without a strong encryption header
+    Unknown(u16),
+}
+
+impl StrongEncryptionAlgorithm {
+    /// Converts the algorithm identifier of the strong encryption header
+    pub fn from_u16(id: u16) -> StrongEncryptionAlgorithm {
+        match id {
+            0x6601 => StrongEncryptionAlgorithm::Des,
+            0x6602 | 0x6702 => StrongEncryptionAlgorithm::Rc2,
+            0x6603 => StrongEncryptionAlgorithm::TripleDes168,
+            0x6609 => StrongEncryptionAlgorithm::TripleDes112,
+            0x660E => StrongEncryptionAlgorithm::Aes128,
+            0x660F => StrongEncryptionAlgorithm::Aes192,
+            0x6610 => StrongEncryptionAlgorithm::Aes256,
+            0x6801 => StrongEncryptionAlgorithm::Rc4,
+            0x6720 => StrongEncryptionAlgorithm::Blowfish,
+            0x6721 => StrongEncryptionAlgorithm::Twofish,
+            v => StrongEncryptionAlgorithm::Unknown(v),
+        }
+    }
+}
+
+impl fmt::Display for StrongEncryptionAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StrongEncryptionAlgorithm::Des => f.write_str("DES"),
+            StrongEncryptionAlgorithm::Rc2 => f.write_str("RC2"),
+            StrongEncryptionAlgorithm::TripleDes168 => f.write_str("3DES-168"),
+            StrongEncryptionAlgorithm::TripleDes112 => f.write_str("3DES-112"),
+            StrongEncryptionAlgorithm::Aes128 => f.write_str("AES-128"),
+            StrongEncryptionAlgorithm::Aes192 => f.write_str("AES-192"),
+            StrongEncryptionAlgorithm::Aes256 => f.write_str("AES-256"),
+            StrongEncryptionAlgorithm::Rc4 => f.write_str("RC4"),
+            StrongEncryptionAlgorithm::Blowfish => f.write_str("Blowfish"),
+            StrongEncryptionAlgorithm::Twofish => f.write_str("Twofish"),
+            StrongEncryptionAlgorithm::Unknown(id) => write!(f, "unknown algorithm {:#06x}", id),
+        }
+    }
+}
+
+/// The mechanism used to encrypt a file
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EncryptionMethod {
+    /// The traditional PKWARE encryption, also known as ZipCrypto
+    ZipCrypto,
+    /// WinZip AES encryption
+    Aes {
+        /// The encryption strength
+        mode: AesMode,
+        /// The version of the format
+        vendor_version: AesVendorVersion,
+    },
+    /// PKWARE strong encryption, which cannot be decrypted by this crate
+    Strong {
+        /// The encryption algorithm
+        algorithm: StrongEncryptionAlgorithm,
+        /// The length of the key in bits, or 0 if it is not known
+        bit_length: u16,
+    },
+}
+
+/// A block of special purpose data in the extensible data sector of the ZIP64 central directory
+/// end record
+///
+/// Each block starts with a 2 byte header ID, which identifies the type of the data, and the
+/// size of the data in 4 bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtensibleDataBlock {
+    /// The header ID of the block
+    pub header_id: u16,
+    /// The data of the block
+    pub data: Vec<u8>,
+}
+
+impl ExtensibleDataBlock {
+    /// The header ID of the block with the checksum of the central directory
+    ///
+    /// Its data is the CRC-32 of the central directory headers, in 4 little endian bytes. It is
+    /// written by [`ZipWriter::set_central_directory_checksum`] and checked when the archive is
+    /// opened. This detects accidental or careless changes, but offers no protection against
+    /// deliberate tampering, as anyone can compute a new checksum.
+    ///
+    /// [`ZipWriter::set_central_directory_checksum`]: crate::ZipWriter::set_central_directory_checksum
+    pub const CENTRAL_DIRECTORY_CHECKSUM: u16 = 0x6463;
+
+    /// The header ID of the block with a signature of the contents of the archive
+    ///
+    /// Its data is the signature of the canonical contents, as written by
+    /// [`ZipWriter::set_contents_signer`].
+    ///
+    /// [`ZipWriter::set_contents_signer`]: crate::ZipWriter::set_contents_signer
+    pub const CONTENTS_SIGNATURE: u16 = 0x7363;
+
+    /// Parse the blocks of an extensible data sector
+    ///
+    /// Returns `None` if the sector doesn't consist of complete blocks.
+    pub fn parse_all(mut sector: &[u8]) -> Option<Vec<ExtensibleDataBlock>> {
+        let mut blocks = Vec::new();
+        while !sector.is_empty() {
+            if sector.len() < 6 {
+                return None;
+            }
+            let header_id = u16::from_le_bytes([sector[0], sector[1]]);
+            let size = u32::from_le_bytes([sector[2], sector[3], sector[4], sector[5]]) as usize;
+            let data = sector[6..].get(..size)?;
+            blocks.push(ExtensibleDataBlock {
+                header_id,
+                data: data.to_vec(),
+            });
+            sector = &sector[6 + size..];
+        }
+        Some(blocks)
+    }
+
+    /// Encode blocks into an extensible data sector
+    pub fn encode_all(blocks: &[ExtensibleDataBlock]) -> Vec<u8> {
+        let mut sector = Vec::new();
+        for block in blocks {
+            sector.extend_from_slice(&block.header_id.to_le_bytes());
+            sector.extend_from_slice(&(block.data.len() as u32).to_le_bytes());
+            sector.extend_from_slice(&block.data);
+        }
+        sector
+    }
+}
+
 /// Structure representing a ZIP file.
 #[derive(Debug, Clone)]
 pub struct ZipFileData {
@@ -214,6 +422,10 @@ pub struct ZipFileData {
     pub system: System,
     /// Specification version
     pub version_made_by: u8,
+    /// Version needed to extract the file, as read from its header
+    ///
+    /// The writer computes the version with [`ZipFileData::version_needed`] instead.
+    pub version_to_extract: u16,
     /// True if the file is encrypted.
     pub encrypted: bool,
     /// Compression method used to store the file
@@ -226,14 +438,18 @@ pub struct ZipFileData {
     pub compressed_size: u64,
     /// Size of the file when extracted
     pub uncompressed_size: u64,
-    /// Name of the file
-    pub file_name: String,
-    /// Raw file name. To be used when file_name was incorrectly decoded.
+    /// Name of the file, which is stored with the names of the other files of an archive that is
+    /// read
+    pub file_name: SharedStr,
+    /// Raw file name, which is only kept if it is not the same as `file_name`, see
+    /// [`ZipFileData::file_name_raw`]
     pub file_name_raw: Vec<u8>,
     /// File comment
     pub file_comment: String,
     /// Specifies where the local header of the file starts
     pub header_start: u64,
+    /// Number of the disk the local header of the file is on, in a multi-disk archive
+    pub disk_number: u32,
     /// Specifies where the central header of the file starts
     ///
     /// Note that when this is not known, it is set to 0
@@ -242,10 +458,179 @@ pub struct ZipFileData {
     pub data_start: u64,
     /// External file attributes
     pub external_attributes: u32,
+    /// The AES mode if the file is encrypted with AES
+    pub aes_mode: Option<(AesMode, AesVendorVersion)>,
+    /// The algorithm and key length in bits if the file uses PKWARE strong encryption
+    pub strong_encryption: Option<(StrongEncryptionAlgorithm, u16)>,
+    /// The ID of the preset dictionary if the file is compressed with one
+    pub deflate_dictionary: Option<u32>,
+    /// Whether the local header of the file has a ZIP64 extra field for its sizes
+    pub large_file: bool,
+    /// Whether the CRC-32 and sizes of the file follow its data in a data descriptor
+    pub using_data_descriptor: bool,
+    /// The forms of `file_name` that are checked or rewritten for extraction, which must be reset
+    /// when `file_name` changes
+    pub name_cache: NameCache,
+    /// The central directory record of the file in the archive it was read from, which is
+    /// written again instead of a new one while the file is unchanged
+    pub central_header: Option<Vec<u8>>,
+}
+
+/// A string in a buffer that is shared with other strings
+///
+/// The names of the files of an archive that is read are stored one after the other in a single
+/// buffer, so that a file keeps no allocation of its own for its name.
+#[derive(Clone)]
+pub struct SharedStr {
+    buffer: Arc<str>,
+    start: u32,
+    /// The length of the string, or `u32::MAX` for a string that is the whole buffer, which can
+    /// be longer
+    len: u32,
+}
+
+impl SharedStr {
+    /// The string of `len` bytes at `start` of `buffer`, which must both be less than 4 GiB
+    pub fn new(buffer: &Arc<str>, start: usize, len: usize) -> SharedStr {
+        debug_assert!(start + len < u32::MAX as usize);
+        SharedStr {
+            buffer: buffer.clone(),
+            start: start as u32,
+            len: len as u32,
+        }
+    }
+
+    /// The string
+    pub fn as_str(&self) -> &str {
+        if self.len == u32::MAX {
+            return &self.buffer;
+        }
+        let start = self.start as usize;
+        &self.buffer[start..start + self.len as usize]
+    }
+}
+
+impl Deref for SharedStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<String> for SharedStr {
+    fn from(string: String) -> SharedStr {
+        let len = if string.len() < u32::MAX as usize {
+            string.len() as u32
+        } else {
+            u32::MAX
+        };
+        SharedStr {
+            buffer: string.into(),
+            start: 0,
+            len,
+        }
+    }
+}
+
+impl From<&str> for SharedStr {
+    fn from(string: &str) -> SharedStr {
+        string.to_owned().into()
+    }
+}
+
+impl From<SharedStr> for String {
+    fn from(string: SharedStr) -> String {
+        string.as_str().to_owned()
+    }
+}
+
+impl PartialEq for SharedStr {
+    fn eq(&self, other: &SharedStr) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl PartialEq<str> for SharedStr {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for SharedStr {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for SharedStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// The forms of the name of a file that are computed the first time they are needed, so that
+/// listing an archive doesn't go through every name again on each call
+#[derive(Debug, Default)]
+pub struct NameCache {
+    /// 0 if it isn't known yet, 1 if the name is enclosed and 2 if it isn't
+    enclosed: AtomicU8,
+    mangled: Mutex<Option<PathBuf>>,
+}
+
+impl NameCache {
+    /// Whether the name is enclosed, which is found with `check` the first time
+    pub fn enclosed(&self, check: impl FnOnce() -> bool) -> bool {
+        match self.enclosed.load(Ordering::Relaxed) {
+            0 => {
+                let enclosed = check();
+                self.enclosed
+                    .store(if enclosed { 1 } else { 2 }, Ordering::Relaxed);
+                enclosed
+            }
+            known => known == 1,
+        }
+    }
+
+    /// The mangled name, which is made with `mangle` the first time
+    pub fn mangled(&self, mangle: impl FnOnce() -> PathBuf) -> PathBuf {
+        let mut mangled = self.mangled.lock().unwrap_or_else(|e| e.into_inner());
+        mangled.get_or_insert_with(mangle).clone()
+    }
+}
+
+impl Clone for NameCache {
+    fn clone(&self) -> NameCache {
+        NameCache {
+            enclosed: AtomicU8::new(self.enclosed.load(Ordering::Relaxed)),
+            mangled: Mutex::new(
+                self.mangled
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .clone(),
+            ),
+        }
+    }
 }
 
 impl ZipFileData {
+    /// The raw file name, to be used when `file_name` was incorrectly decoded.
+    ///
+    /// The raw name of most files is the same as the decoded one, so it is only kept when it is
+    /// not, which saves a copy of every name in the memory of an archive.
+    pub fn file_name_raw(&self) -> &[u8] {
+        if self.file_name_raw.is_empty() {
+            self.file_name.as_bytes()
+        } else {
+            &self.file_name_raw
+        }
+    }
+
     pub fn file_name_sanitized(&self) -> ::std::path::PathBuf {
+        self.name_cache.mangled(|| self.mangle_file_name())
+    }
+
+    fn mangle_file_name(&self) -> ::std::path::PathBuf {
         let no_null_filename = match self.file_name.find('\0') {
             Some(index) => &self.file_name[0..index],
             None => &self.file_name,
@@ -275,12 +660,95 @@ impl ZipFileData {
             })
     }
 
+    /// The mechanism used to encrypt the file, if it is encrypted
+    pub fn encryption_method(&self) -> Option<EncryptionMethod> {
+        if !self.encrypted {
+            return None;
+        }
+        if let Some((algorithm, bit_length)) = self.strong_encryption {
+            return Some(EncryptionMethod::Strong {
+                algorithm,
+                bit_length,
+            });
+        }
+        Some(match self.aes_mode {
+            Some((mode, vendor_version)) => EncryptionMethod::Aes {
+                mode,
+                vendor_version,
+            },
+            None => EncryptionMethod::ZipCrypto,
+        })
+    }
+
+    /// The compression method stored in the headers, which is 99 for AES encrypted files
+    pub fn header_compression_method(&self) -> u16 {
+        if self.aes_mode.is_some() {
+            return 99;
+        }
+        #[allow(deprecated)]
+        self.compression_method.to_u16()
+    }
+
+    /// The bytes that the ZipCrypto encryption header of the file may end with.
+    ///
+    /// This is the high byte of the CRC-32, or of the modification time for files with a data
+    /// descriptor, whose CRC-32 is not known when the header is written. Some writers use the
+    /// CRC-32 for those as well.
+    pub fn zipcrypto_check_bytes(&self) -> [u8; 2] {
+        let crc32_byte = (self.crc32 >> 24) as u8;
+        if self.using_data_descriptor {
+            [(self.last_modified_time.timepart() >> 8) as u8, crc32_byte]
+        } else {
+            [crc32_byte, crc32_byte]
+        }
+    }
+
+    /// Whether the sizes or the offset of the file don't fit in the headers without ZIP64
+    pub fn zip64_extension(&self) -> bool {
+        self.large_file
+            || self.uncompressed_size >= spec::ZIP64_BYTES_THR
+            || self.compressed_size >= spec::ZIP64_BYTES_THR
+            || self.header_start >= spec::ZIP64_BYTES_THR
+    }
+
+    /// The lowest version of the specification that supports the features used by the file
+    ///
+    /// These are its compression method, encryption, ZIP64 and whether it is a directory, as
+    /// listed in section 4.4.3.2 of the APPNOTE.
     pub fn version_needed(&self) -> u16 {
-        match self.compression_method {
-            #[cfg(feature = "bzip2")]
-            crate::compression::CompressionMethod::Bzip2 => 46,
+        #[allow(deprecated)]
+        let mut version = match self.compression_method.to_u16() {
+            // Stored, and the methods of PKZIP 1.0
+            0..=6 => 10,
+            8 => 20,
+            // Deflate64
+            9 => 21,
+            // PKWARE DCL Implode
+            10 => 25,
+            12 => 46,
+            // LZMA, Zstandard, XZ and PPMd
+            14 | 93 | 95 | 98 => 63,
             _ => 20,
+        };
+        if self.encrypted || self.file_name.ends_with('/') {
+            version = version.max(20);
+        }
+        if self.zip64_extension() {
+            version = version.max(45);
         }
+        if let Some((algorithm, _)) = self.strong_encryption {
+            version = version.max(match algorithm {
+                StrongEncryptionAlgorithm::Aes128
+                | StrongEncryptionAlgorithm::Aes192
+                | StrongEncryptionAlgorithm::Aes256 => 51,
+                StrongEncryptionAlgorithm::Blowfish | StrongEncryptionAlgorithm::Twofish => 63,
+                _ => 50,
+            });
+        }
+        if self.aes_mode.is_some() {
+            version = version.max(51);
+        }
+        version
     }
 }
 
@@ -293,6 +761,8 @@ mod test {
         assert_eq!(System::Unix as u16, 3u16);
         assert_eq!(System::from_u8(0), System::Dos);
         assert_eq!(System::from_u8(3), System::Unix);
+        assert_eq!(System::from_u8(10), System::Ntfs);
+        assert_eq!(System::from_u8(11), System::Unknown);
     }
 
     #[test]
@@ -302,24 +772,62 @@ mod test {
         let data = ZipFileData {
             system: System::Dos,
             version_made_by: 0,
+            version_to_extract: 0,
             encrypted: false,
             compression_method: crate::compression::CompressionMethod::Stored,
             last_modified_time: DateTime::default(),
             crc32: 0,
             compressed_size: 0,
             uncompressed_size: 0,
-            file_name: file_name.clone(),
+            file_name: file_name.clone().into(),
             file_name_raw: file_name.into_bytes(),
             file_comment: String::new(),
             header_start: 0,
+            disk_number: 0,
             data_start: 0,
             central_header_start: 0,
             external_attributes: 0,
+            aes_mode: None,
+            strong_encryption: None,
+            deflate_dictionary: None,
+            large_file: false,
+            using_data_descriptor: false,
+            name_cache: NameCache::default(),
+            central_header: None,
         };
         assert_eq!(
             data.file_name_sanitized(),
             ::std::path::PathBuf::from("path/etc/passwd")
         );
+        // The second call uses the cached name
+        assert_eq!(
+            data.name_cache.mangled(|| unreachable!()),
+            ::std::path::PathBuf::from("path/etc/passwd")
+        );
+        assert_eq!(
+            data.clone().file_name_sanitized(),
+            ::std::path::PathBuf::from("path/etc/passwd")
+        );
+    }
+
+    #[test]
+    fn extensible_data_blocks() {
+        use super::ExtensibleDataBlock;
+        let blocks = vec![
+            ExtensibleDataBlock {
+                header_id: 0x4242,
+                data: b"data".to_vec(),
+            },
+            ExtensibleDataBlock {
+                header_id: 0x0001,
+                data: Vec::new(),
+            },
+        ];
+        let sector = ExtensibleDataBlock::encode_all(&blocks);
+        assert_eq!(&sector[..10], b"\x42\x42\x04\x00\x00\x00data");
+        assert_eq!(ExtensibleDataBlock::parse_all(&sector), Some(blocks));
+        assert_eq!(ExtensibleDataBlock::parse_all(&sector[..8]), None);
+        assert_eq!(ExtensibleDataBlock::parse_all(&[]), Some(Vec::new()));
     }
 
     #[test]
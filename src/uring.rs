@@ -0,0 +1,274 @@
+//! Writing extracted files with io_uring on Linux
+//!
+//! Extracting many small files spends most of its time in system calls, four or five for every
+//! file. [`FileWriter`] queues the files instead, and writes a batch of them with two submissions
+//! to an io_uring: one that checks what is at their paths, and one that opens, writes and closes
+//! every file with linked requests.
+
+use io_uring_crate::{opcode, squeue, types, IoUring};
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+/// The number of requests that fit in the ring, which also limits the files of a batch
+const RING_ENTRIES: u32 = 256;
+
+/// The most data that is queued before a batch is written
+const BATCH_SIZE: usize = 16 << 20;
+
+/// The most data written by a single request
+const WRITE_SIZE: usize = 1 << 30;
+
+/// A file that is queued to be written
+struct QueuedFile {
+    /// The value that identifies the file in [`WrittenFile::id`]
+    id: usize,
+    path: PathBuf,
+    c_path: CString,
+    data: Vec<u8>,
+}
+
+/// A file that was written by a [`FileWriter`]
+pub struct WrittenFile {
+    /// The value the file was queued with
+    pub id: usize,
+    pub path: PathBuf,
+    pub size: u64,
+    /// Whether there was a file or symlink at the path, which was replaced
+    pub overwritten: bool,
+}
+
+/// Writer of whole files with io_uring, in batches
+pub struct FileWriter {
+    ring: IoUring,
+    queued: Vec<QueuedFile>,
+    queued_size: usize,
+    /// The requests that the queued files need
+    queued_requests: usize,
+    written: Vec<WrittenFile>,
+}
+
+impl FileWriter {
+    /// Set up an io_uring, which fails on kernels before 5.15 or where io_uring is forbidden.
+    pub fn new() -> io::Result<FileWriter> {
+        let ring = IoUring::new(RING_ENTRIES)?;
+        // Every file of a batch is opened into the registered file slot with its index, so the
+        // requests that write and close it can refer to it before it is opened
+        ring.submitter().register_files_sparse(RING_ENTRIES)?;
+        Ok(FileWriter {
+            ring,
+            queued: Vec::new(),
+            queued_size: 0,
+            queued_requests: 0,
+            written: Vec::new(),
+        })
+    }
+
+    /// Queue `data` to be written to a new file at `path`, which replaces a file or symlink that
+    /// is there. The queued files are written first if the batch is full.
+    ///
+    /// The parent directory of `path` must exist once the file is written.
+    pub fn push(&mut self, id: usize, path: PathBuf, data: Vec<u8>) -> io::Result<()> {
+        // Opening and closing the file, and writing its data
+        let requests = 2 + data.chunks(WRITE_SIZE).count();
+        if !self.queued.is_empty()
+            && (self.queued_requests + requests > RING_ENTRIES as usize
+                || self.queued_size + data.len() > BATCH_SIZE)
+        {
+            self.flush()?;
+        }
+        if requests > RING_ENTRIES as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The file is too large to be written at once",
+            ));
+        }
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.queued_requests += requests;
+        self.queued_size += data.len();
+        self.queued.push(QueuedFile {
+            id,
+            path,
+            c_path,
+            data,
+        });
+        Ok(())
+    }
+
+    /// Write the files that are queued.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.queued.is_empty() {
+            return Ok(());
+        }
+        let overwritten = self.find_existing()?;
+        self.write_queued()?;
+        let queued = std::mem::take(&mut self.queued);
+        self.written.extend(
+            queued
+                .into_iter()
+                .zip(overwritten)
+                .map(|(file, overwritten)| WrittenFile {
+                    id: file.id,
+                    path: file.path,
+                    size: file.data.len() as u64,
+                    overwritten,
+                }),
+        );
+        self.queued_size = 0;
+        self.queued_requests = 0;
+        Ok(())
+    }
+
+    /// Write the files that are queued, and return all files that were written.
+    pub fn finish(mut self) -> io::Result<Vec<WrittenFile>> {
+        self.flush()?;
+        Ok(self.written)
+    }
+
+    /// Find out whether there is something at the paths of the queued files, and remove the
+    /// symlinks among them, which must be replaced instead of being written through.
+    fn find_existing(&mut self) -> io::Result<Vec<bool>> {
+        // Safety: an all-zero `statx` is valid, it only has integer fields
+        let mut stats: Vec<libc::statx> = (0..self.queued.len())
+            .map(|_| unsafe { std::mem::zeroed() })
+            .collect();
+        let entries: Vec<squeue::Entry> = self
+            .queued
+            .iter()
+            .zip(stats.iter_mut())
+            .enumerate()
+            .map(|(i, (file, stat))| {
+                opcode::Statx::new(
+                    types::Fd(libc::AT_FDCWD),
+                    file.c_path.as_ptr(),
+                    stat as *mut libc::statx as *mut types::statx,
+                )
+                .flags(libc::AT_SYMLINK_NOFOLLOW)
+                .mask(libc::STATX_TYPE)
+                .build()
+                .user_data(i as u64)
+            })
+            .collect();
+        let results = match self.submit(&entries) {
+            Ok(results) => results,
+            Err(e) => {
+                std::mem::forget(stats);
+                return Err(e);
+            }
+        };
+
+        let mut overwritten = Vec::with_capacity(results.len());
+        for (i, result) in results.into_iter().enumerate() {
+            match result {
+                0 => {
+                    let file_type = u32::from(stats[i].stx_mode) & libc::S_IFMT;
+                    if file_type == libc::S_IFLNK {
+                        std::fs::remove_file(&self.queued[i].path)?;
+                    }
+                    overwritten.push(true);
+                }
+                result if -result == libc::ENOENT => overwritten.push(false),
+                result => return Err(io::Error::from_raw_os_error(-result)),
+            }
+        }
+        Ok(overwritten)
+    }
+
+    /// Open, write and close the queued files.
+    fn write_queued(&mut self) -> io::Result<()> {
+        // The result that every request must have, as a short write fails like an error
+        let mut expected = Vec::with_capacity(self.queued_requests);
+        let mut entries = Vec::with_capacity(self.queued_requests);
+        for (slot, file) in self.queued.iter().enumerate() {
+            let slot = slot as u32;
+            let destination = types::DestinationSlot::try_from_slot_target(slot)
+                .expect("The slots of a batch are valid");
+            // The requests of a file are linked, so they run one after the other, and the rest
+            // is cancelled if one fails
+            let open = opcode::OpenAt::new(types::Fd(libc::AT_FDCWD), file.c_path.as_ptr())
+                .file_index(Some(destination))
+                .flags(libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC | libc::O_NOFOLLOW)
+                .mode(0o666)
+                .build();
+            entries.push(
+                open.flags(squeue::Flags::IO_LINK)
+                    .user_data(entries.len() as u64),
+            );
+            expected.push(0);
+            let mut offset = 0;
+            for chunk in file.data.chunks(WRITE_SIZE) {
+                let write =
+                    opcode::Write::new(types::Fixed(slot), chunk.as_ptr(), chunk.len() as u32)
+                        .offset(offset)
+                        .build();
+                entries.push(
+                    write
+                        .flags(squeue::Flags::IO_LINK)
+                        .user_data(entries.len() as u64),
+                );
+                expected.push(chunk.len() as i32);
+                offset += chunk.len() as u64;
+            }
+            let close = opcode::Close::new(types::Fixed(slot)).build();
+            entries.push(close.user_data(entries.len() as u64));
+            expected.push(0);
+        }
+        let results = self.submit(&entries)?;
+
+        let mut cancelled = false;
+        for (result, expected) in results.into_iter().zip(expected) {
+            if result == expected {
+                continue;
+            }
+            if result == -libc::ECANCELED {
+                // Cancelled because an earlier request of the same file failed
+                cancelled = true;
+                continue;
+            }
+            if result < 0 {
+                return Err(io::Error::from_raw_os_error(-result));
+            }
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        if cancelled {
+            return Err(io::Error::from_raw_os_error(libc::ECANCELED));
+        }
+        Ok(())
+    }
+
+    /// Submit `entries`, whose user data is their index, and wait for their results.
+    ///
+    /// The pointers of the entries must stay valid until this returns.
+    fn submit(&mut self, entries: &[squeue::Entry]) -> io::Result<Vec<i32>> {
+        let mut results = vec![0; entries.len()];
+        {
+            let mut submission = self.ring.submission();
+            for entry in entries {
+                // Safety: the entries only point to data that outlives this call, and this call
+                // waits until the kernel is done with all of them
+                unsafe { submission.push(entry) }.expect("The requests of a batch fit in the ring");
+            }
+        }
+        let mut completed = 0;
+        while completed < entries.len() {
+            match self.ring.submit_and_wait(entries.len() - completed) {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    // The kernel may still use the data of the requests that are not completed,
+                    // which must not be freed
+                    let queued = std::mem::take(&mut self.queued);
+                    std::mem::forget(queued);
+                    return Err(e);
+                }
+            }
+            for completion in self.ring.completion() {
+                results[completion.user_data() as usize] = completion.result();
+                completed += 1;
+            }
+        }
+        Ok(results)
+    }
+}
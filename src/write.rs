@@ -1,29 +1,48 @@
 //! Types for creating ZIP archives
 
+#[cfg(feature = "aes-crypto")]
+use crate::aes::{self, AesPassword};
+use crate::codec::{Codecs, Compressor, CustomEncoder};
 use crate::compression::CompressionMethod;
-use crate::read::ZipFile;
+use crate::crc32::Crc32Writer;
+use crate::hook::{EntryStats, EntryTransform, Hook, WriteHook, WriteObserver};
+use crate::multidisk::DiskSink;
+use crate::pool::BufferPool;
+use crate::read::{ZipArchive, ZipFile};
 use crate::result::{ZipError, ZipResult};
+use crate::signature::{self, Signer};
 use crate::spec;
-use crate::types::{DateTime, System, ZipFileData, DEFAULT_VERSION};
+use crate::temp::{MemoryStore, TempFile, TempStore};
+#[cfg(feature = "aes-crypto")]
+use crate::types::AES_ITERATION_COUNT;
+use crate::types::{
+    AesMode, AesVendorVersion, DateTime, ExtensibleDataBlock, NameCache, System, ZipFileData,
+    DEFAULT_VERSION,
+};
+use crate::zipcrypto::{self, ZipCryptoKeys};
 use byteorder::{LittleEndian, WriteBytesExt};
 use crc32fast::Hasher;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::default::Default;
 use std::io;
 use std::io::prelude::*;
 use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 #[cfg(any(
     feature = "deflate",
     feature = "deflate-miniz",
     feature = "deflate-zlib"
 ))]
-use flate2::write::DeflateEncoder;
+use crate::deflate;
 
 #[cfg(feature = "bzip2")]
 use bzip2::write::BzEncoder;
 
 #[cfg(feature = "async")]
-use crate::async_util::Compat;
+use crate::async_util::{write_u16_le, write_u32_le, write_u64_le};
 #[cfg(feature = "async")]
 use crate::read::AsyncZipFile;
 #[cfg(feature = "async")]
@@ -32,7 +51,8 @@ use async_compression::futures::write::{
 };
 #[cfg(feature = "async")]
 use futures::{
-    io::{AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt},
+    io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt},
+    stream::{Stream, StreamExt},
     task::Poll,
 };
 #[cfg(feature = "async")]
@@ -41,8 +61,6 @@ use pin_project::{pin_project, pinned_drop};
 use std::pin::Pin;
 #[cfg(feature = "async")]
 use std::task::Context;
-#[cfg(feature = "async")]
-use tokio::io::AsyncWriteExt as TokioAsyncWriteExt;
 
 enum GenericZipWriter<W: Write + io::Seek> {
     Closed,
@@ -52,9 +70,10 @@ enum GenericZipWriter<W: Write + io::Seek> {
         feature = "deflate-miniz",
         feature = "deflate-zlib"
     ))]
-    Deflater(DeflateEncoder<W>),
+    Deflater(deflate::Encoder<W>),
     #[cfg(feature = "bzip2")]
     Bzip2(BzEncoder<W>),
+    Custom(CustomEncoder<W>),
 }
 
 #[cfg(feature = "async")]
@@ -67,9 +86,34 @@ enum AsyncGenericZipWriter<W: AsyncWrite + AsyncSeek> {
         feature = "deflate-miniz",
         feature = "deflate-zlib"
     ))]
-    Deflater(#[pin] AsyncDeflateEncoder<W>),
+    Deflater(#[pin] AsyncDeflateEncoder<KeepOpen<W>>),
     #[cfg(feature = "bzip2")]
-    Bzip2(#[pin] AsyncBzEncoder<W>),
+    Bzip2(#[pin] AsyncBzEncoder<KeepOpen<W>>),
+}
+
+/// Writer that is only flushed when it is closed, so that an encoder can be finished by closing
+/// it while the archive is still written
+#[cfg(feature = "async")]
+#[pin_project]
+struct KeepOpen<W>(#[pin] W);
+
+#[cfg(feature = "async")]
+impl<W: AsyncWrite> AsyncWrite for KeepOpen<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.project().0.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().0.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().0.poll_flush(cx)
+    }
 }
 
 /// ZIP archive generator
@@ -101,14 +145,64 @@ enum AsyncGenericZipWriter<W: AsyncWrite + AsyncSeek> {
 /// # doit().unwrap();
 /// ```
 pub struct ZipWriter<W: Write + io::Seek> {
-    inner: GenericZipWriter<W>,
+    inner: GenericZipWriter<SizeTracker<W>>,
     files: Vec<ZipFileData>,
     stats: ZipWriterStats,
     writing_to_file: bool,
-    comment: String,
+    comment: Vec<u8>,
     writing_raw: bool,
+    compressors: Codecs<dyn Compressor>,
+    name_options: NameOptions,
+    names: HashSet<String>,
+    size_limit: Option<u64>,
+    checkpoint_interval: Option<usize>,
+    /// The number of files when the central directory was written last by a checkpoint
+    checkpointed: usize,
+    sample: Option<Sample>,
+    write_hook: Hook<dyn WriteHook>,
+    transform: Option<Box<dyn EntryTransform>>,
+    encrypting: Option<Encrypting>,
+    temp_store: Arc<dyn TempStore>,
+    checking_stored: bool,
+    checking_raw: bool,
+    forbid_zip64: bool,
+    forbidding_zip64: bool,
+    defaults: DefaultOptions,
+    zip64_extensible_data: Vec<u8>,
+    central_directory_checksum: bool,
+    signer: Hook<dyn Signer>,
+    contents_signer: Hook<dyn Signer>,
+    observer: Hook<dyn WriteObserver>,
+    streaming: bool,
+    spooled: bool,
+    disk_layout: Option<Arc<Mutex<DiskLayout>>>,
+    #[cfg(feature = "deflate")]
+    dictionaries: deflate::Dictionaries,
+    entry_ids: Vec<EntryId>,
+    next_entry_id: u64,
+    buffers: BufferPool,
+    /// The length that the archive must not get below, which is the length of the archive that
+    /// was opened with [`ZipWriter::new_append`], or the end of a file that was aborted or of a
+    /// checkpoint
+    min_length: u64,
+    /// The number of files of the archive that was opened with [`ZipWriter::new_append`]
+    appended_files: usize,
+    /// The number of files at the start that were finished already, so that a file isn't
+    /// finished twice
+    finished_files: usize,
+    /// The entries of the archive that was appended to that were superseded, with the identifier
+    /// of the entry that superseded them and their indices and identifiers, so that
+    /// [`ZipWriter::abort_file`] can put them back
+    superseded: Vec<(EntryId, usize, EntryId, ZipFileData)>,
 }
 
+/// A stable identifier of an entry written by a [`ZipWriter`]
+///
+/// Unlike the index of an entry, which shifts when entries before it are removed, the identifier
+/// keeps referring to the same entry for the lifetime of the writer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EntryId(u64);
+
 /// Async ZIP archive generator
 ///
 /// Handles the bookkeeping involved in building an archive, and provides an
@@ -124,15 +218,526 @@ pub struct AsyncZipWriter<W: AsyncWrite + AsyncSeek + Unpin> {
     files: Vec<ZipFileData>,
     stats: ZipWriterStats,
     writing_to_file: bool,
-    comment: String,
+    comment: Vec<u8>,
     writing_raw: bool,
+    name_options: NameOptions,
+    names: HashSet<String>,
+    forbid_zip64: bool,
+    forbidding_zip64: bool,
+    defaults: DefaultOptions,
+    zip64_extensible_data: Vec<u8>,
+    central_directory_checksum: bool,
+    signer: Hook<dyn Signer>,
+    contents_signer: Hook<dyn Signer>,
+    observer: Hook<dyn WriteObserver>,
 }
 
-#[derive(Default)]
+/// Writer that keeps track of the size of the archive, so it can be checked while an encoder owns
+/// the writer.
+struct SizeTracker<W> {
+    inner: W,
+    /// Position of the inner writer where the archive starts, known after the first seek
+    origin: Option<u64>,
+    /// Position relative to `origin`
+    position: u64,
+    size: u64,
+}
+
+impl<W> SizeTracker<W> {
+    fn new(inner: W) -> SizeTracker<W> {
+        SizeTracker {
+            inner,
+            origin: None,
+            position: 0,
+            size: 0,
+        }
+    }
+
+    /// Take the archive to start at position 0 of the inner writer, and to be written from
+    /// `position`, which the inner writer is at.
+    fn start_at(&mut self, position: u64) {
+        self.origin = Some(0);
+        self.position = position;
+        self.size = position;
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The number of bytes by which the archive grows if it is written up to `end`, a position of
+    /// the inner writer
+    fn growth_to(&self, end: u64) -> u64 {
+        end.saturating_sub(self.origin.unwrap_or(0) + self.size)
+    }
+
+    /// The position of the inner writer, as returned by seeking it
+    fn position(&self) -> u64 {
+        self.origin.unwrap_or(0) + self.position
+    }
+}
+
+impl<W: Write> Write for SizeTracker<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = self.inner.write(buf)?;
+        self.position += count as u64;
+        self.size = self.size.max(self.position);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: io::Seek> io::Seek for SizeTracker<W> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let new_pos = self.inner.seek(pos)?;
+        let origin = match self.origin {
+            Some(origin) => origin,
+            None => {
+                let origin = new_pos.saturating_sub(self.position);
+                self.origin = Some(origin);
+                origin
+            }
+        };
+        self.position = new_pos.saturating_sub(origin);
+        self.size = self.size.max(self.position);
+        Ok(new_pos)
+    }
+}
+
+/// Writer for archives that are written to an output that cannot seek, like a socket or stdout
+///
+/// The position in the output is tracked, and seeking only succeeds if it doesn't move away from
+/// it. This is enough for a [`ZipWriter`] created with [`ZipWriter::new_stream`].
+pub struct StreamWriter<W> {
+    inner: W,
+    position: u64,
+}
+
+impl<W> StreamWriter<W> {
+    /// Wrap `inner`, taking its current position as the start of the archive.
+    pub fn new(inner: W) -> StreamWriter<W> {
+        StreamWriter { inner, position: 0 }
+    }
+
+    /// Get a reference to the wrapped writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Unwrap the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W> io::Seek for StreamWriter<W> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let stays = match pos {
+            io::SeekFrom::Start(offset) => offset == self.position,
+            io::SeekFrom::Current(offset) | io::SeekFrom::End(offset) => offset == 0,
+        };
+        if stays {
+            Ok(self.position)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "A StreamWriter cannot seek to another position",
+            ))
+        }
+    }
+}
+
+impl<W: Write> Write for StreamWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = self.inner.write(buf)?;
+        self.position += count as u64;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writer for archives that are written to an output that cannot seek, which keeps the last bytes
+/// that were written in a seekable spool
+///
+/// The output is written to the spool first, and only sent to the inner writer when the spool is
+/// full, so seeking succeeds as long as it stays within the spool. This is enough for a
+/// [`ZipWriter`] created with [`ZipWriter::new_spooled`].
+pub struct SpoolWriter<W, S> {
+    inner: W,
+    spool: S,
+    capacity: u64,
+    /// The length of the output that was sent to the inner writer
+    sent: u64,
+    /// The length of the output, of which the part that was not sent is in the spool
+    length: u64,
+    position: u64,
+}
+
+impl<W: Write, S: Read + Write + io::Seek> SpoolWriter<W, S> {
+    /// Wrap `inner`, keeping up to `capacity` bytes in `spool`, which is used from its start.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is 0.
+    pub fn new(inner: W, spool: S, capacity: u64) -> SpoolWriter<W, S> {
+        assert!(capacity > 0, "The capacity of a SpoolWriter must not be 0");
+        SpoolWriter {
+            inner,
+            spool,
+            capacity,
+            sent: 0,
+            length: 0,
+            position: 0,
+        }
+    }
+
+    /// Get a reference to the wrapped writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Write out the rest of the spool, and return the wrapped writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let length = self.length;
+        self.send(length)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    /// Send the output up to `end` from the spool to the inner writer.
+    fn send(&mut self, end: u64) -> io::Result<()> {
+        let mut buffer = [0; 8192];
+        while self.sent < end {
+            let offset = self.sent % self.capacity;
+            let count = (end - self.sent)
+                .min(self.capacity - offset)
+                .min(buffer.len() as u64) as usize;
+            self.spool.seek(io::SeekFrom::Start(offset))?;
+            self.spool.read_exact(&mut buffer[..count])?;
+            self.inner.write_all(&buffer[..count])?;
+            self.sent += count as u64;
+        }
+        Ok(())
+    }
+}
+
+impl<W, S> io::Seek for SpoolWriter<W, S> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            io::SeekFrom::Start(offset) => (offset, 0),
+            io::SeekFrom::Current(offset) => (self.position, offset),
+            io::SeekFrom::End(offset) => (self.length, offset),
+        };
+        let position = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.wrapping_neg() as u64)
+        };
+        match position {
+            Some(position) if position >= self.sent && position <= self.length => {
+                self.position = position;
+                Ok(position)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "A SpoolWriter can only seek within its spool",
+            )),
+        }
+    }
+}
+
+impl<W: Write, S: Read + Write + io::Seek> Write for SpoolWriter<W, S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The spool is used as a ring buffer, write up to where it wraps around
+        let offset = self.position % self.capacity;
+        let count = (buf.len() as u64).min(self.capacity - offset);
+        let end = self.position + count;
+        if end > self.sent + self.capacity {
+            self.send(end - self.capacity)?;
+        }
+        self.spool.seek(io::SeekFrom::Start(offset))?;
+        self.spool.write_all(&buf[..count as usize])?;
+        self.position = end;
+        self.length = self.length.max(end);
+        Ok(count as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The positions at which the disks of a split archive start, which are shared by a [`ZipWriter`]
+/// and its [`SplitWriter`]
+#[derive(Debug)]
+struct DiskLayout {
+    volume_size: u64,
+    starts: Vec<u64>,
+}
+
+impl DiskLayout {
+    /// Start a new disk at `position`, which is at the end of the output, unless `length` bytes
+    /// still fit on the last disk or don't fit on any disk.
+    fn keep_together(&mut self, position: u64, length: u64) {
+        let used = match position.checked_sub(*self.starts.last().unwrap()) {
+            Some(used) => used,
+            None => return,
+        };
+        if used >= self.volume_size
+            || (used > 0 && used + length > self.volume_size && length <= self.volume_size)
+        {
+            self.starts.push(position);
+        }
+    }
+
+    /// The number of the disk that `position` is on, and the offset on that disk
+    fn locate(&self, position: u64) -> (u32, u64) {
+        let disk = self
+            .starts
+            .iter()
+            .rposition(|&start| start <= position)
+            .unwrap_or(0);
+        (disk as u32, position - self.starts[disk])
+    }
+
+    /// The entries with the numbers of the disks their local headers are on, and the offsets of
+    /// the headers on these disks
+    fn locate_files(&self, files: &[ZipFileData]) -> Vec<ZipFileData> {
+        let mut files = files.to_vec();
+        for file in &mut files {
+            let (disk, offset) = self.locate(file.header_start);
+            file.disk_number = disk;
+            file.header_start = offset;
+        }
+        files
+    }
+
+    /// Plan for the central directory records of `files` starting at `central_start` to be
+    /// written without splitting any of them, and return the offset of the first one on its disk.
+    fn keep_records_together(
+        &mut self,
+        files: &[ZipFileData],
+        central_start: u64,
+    ) -> ZipResult<u64> {
+        let mut position = central_start;
+        for file in files {
+            let size = central_header_size(file)?;
+            self.keep_together(position, size);
+            position += size;
+        }
+        self.keep_together(central_start, 1);
+        Ok(self.locate(central_start).1)
+    }
+
+    /// Plan for the records of the central directory end, which follow the central directory
+    /// of `files`, to be written together on the last disk, and record the disks in them.
+    fn locate_directory_end(
+        &mut self,
+        files: &[ZipFileData],
+        central_start: u64,
+        central_size: u64,
+        zip64_footer: Option<&mut spec::Zip64CentralDirectoryEnd>,
+        footer: &mut spec::CentralDirectoryEnd,
+        locator: &mut spec::Zip64CentralDirectoryEndLocator,
+    ) -> ZipResult<()> {
+        let end_start = central_start + central_size;
+        let mut end_size = 22 + footer.zip_file_comment.len() as u64;
+        if let Some(zip64_footer) = &zip64_footer {
+            end_size += 56 + zip64_footer.extensible_data_sector.len() as u64 + 20;
+        }
+        self.keep_together(end_start, end_size);
+        if self.starts.len() > u16::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "A split archive cannot have more than 65535 disks",
+            )
+            .into());
+        }
+
+        let (last_disk, end_offset) = self.locate(end_start);
+        let (central_disk, _) = self.locate(central_start);
+        // The number of central directory records that start on the last disk
+        let mut position = central_start;
+        let mut records_on_last_disk = 0;
+        for file in files {
+            if self.locate(position).0 == last_disk {
+                records_on_last_disk += 1;
+            }
+            position += central_header_size(file)?;
+        }
+
+        footer.disk_number = last_disk as u16;
+        footer.disk_with_central_directory = central_disk as u16;
+        footer.number_of_files_on_this_disk =
+            records_on_last_disk.min(spec::ZIP64_ENTRY_THR) as u16;
+        if let Some(zip64_footer) = zip64_footer {
+            zip64_footer.disk_number = last_disk;
+            zip64_footer.disk_with_central_directory = central_disk;
+            zip64_footer.number_of_files_on_this_disk = records_on_last_disk as u64;
+            locator.disk_with_central_directory = last_disk;
+            locator.end_of_central_directory_offset = end_offset;
+            locator.number_of_disks = last_disk + 1;
+        }
+        Ok(())
+    }
+
+    /// The position at which the disk after `disk` starts, or up to which the last disk can grow
+    fn disk_end(&self, disk: usize) -> u64 {
+        match self.starts.get(disk + 1) {
+            Some(&start) => start,
+            None => self.starts[disk] + self.volume_size,
+        }
+    }
+}
+
+fn lock(layout: &Mutex<DiskLayout>) -> std::sync::MutexGuard<'_, DiskLayout> {
+    layout.lock().unwrap()
+}
+
+/// Writer for archives that are split over disks of a limited size
+///
+/// The disks are created with a [`DiskSink`] as the output reaches them, and kept until the
+/// archive is finished, so that local headers can still be rewritten. This is what a
+/// [`ZipWriter`] created with [`ZipWriter::new_split`] writes to.
+pub struct SplitWriter<K: DiskSink> {
+    sink: K,
+    disks: Vec<K::Disk>,
+    layout: Arc<Mutex<DiskLayout>>,
+    length: u64,
+    position: u64,
+}
+
+impl<K: DiskSink> SplitWriter<K> {
+    fn new(sink: K, layout: Arc<Mutex<DiskLayout>>) -> SplitWriter<K> {
+        SplitWriter {
+            sink,
+            disks: Vec::new(),
+            layout,
+            length: 0,
+            position: 0,
+        }
+    }
+
+    /// The number of disks that were created so far
+    pub fn disk_count(&self) -> u32 {
+        self.disks.len() as u32
+    }
+
+    /// Flush the disks and hand them to [`DiskSink::finish`], and return the sink.
+    pub fn finish(mut self) -> io::Result<K> {
+        for disk in &mut self.disks {
+            disk.flush()?;
+        }
+        self.sink.finish(self.disks)?;
+        Ok(self.sink)
+    }
+}
+
+impl<K: DiskSink> io::Seek for SplitWriter<K> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            io::SeekFrom::Start(offset) => (offset, 0),
+            io::SeekFrom::Current(offset) => (self.position, offset),
+            io::SeekFrom::End(offset) => (self.length, offset),
+        };
+        let position = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.wrapping_neg() as u64)
+        };
+        match position {
+            Some(position) if position <= self.length => {
+                self.position = position;
+                Ok(position)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "A SplitWriter can only seek within the output",
+            )),
+        }
+    }
+}
+
+impl<K: DiskSink> Write for SplitWriter<K> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut layout = lock(&self.layout);
+        if self.position == self.length {
+            layout.keep_together(self.position, 1);
+        }
+        let (disk, offset) = layout.locate(self.position);
+        if disk >= u16::MAX as u32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "A split archive cannot have more than 65535 disks",
+            ));
+        }
+        let count = (buf.len() as u64).min(layout.disk_end(disk as usize) - self.position);
+        drop(layout);
+
+        while self.disks.len() <= disk as usize {
+            let disk = self.sink.create(self.disks.len() as u32)?;
+            self.disks.push(disk);
+        }
+        let disk = &mut self.disks[disk as usize];
+        disk.seek(io::SeekFrom::Start(offset))?;
+        let count = disk.write(&buf[..count as usize])?;
+        self.position += count as u64;
+        self.length = self.length.max(self.position);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for disk in &mut self.disks {
+            disk.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// The start of a file that is buffered to decide whether to compress it
+struct Sample {
+    data: Vec<u8>,
+    options: FileOptions,
+}
+
+/// The encryption of a file
+#[derive(Copy, Clone)]
+enum EncryptWith {
+    ZipCrypto(ZipCryptoKeys),
+    #[cfg(feature = "aes-crypto")]
+    Aes {
+        mode: AesMode,
+        password: AesPassword,
+    },
+}
+
+/// The compressed data of an encrypted file, which is encrypted when the file is finished
+struct Encrypting {
+    encrypt_with: EncryptWith,
+    #[cfg(feature = "aes-crypto")]
+    aes_iteration_count: u32,
+    writer: GenericZipWriter<Box<dyn TempFile>>,
+}
+
+/// Size of the sample used by [`FileOptions::store_if_incompressible`]
+const SAMPLE_SIZE: usize = 1 << 16;
+
 struct ZipWriterStats {
-    hasher: Hasher,
+    /// The checksum and size of the data of the current file, which is either sampled or
+    /// compressed, so it is hashed before it is written anywhere
+    data: Crc32Writer<io::Sink>,
     start: u64,
-    bytes_written: u64,
+    /// The time spent in writes of the current file
+    elapsed: Duration,
 }
 
 struct ZipRawValues {
@@ -142,11 +747,51 @@ struct ZipRawValues {
 }
 
 /// Metadata for a file to be written
+///
+/// The compression method, last modified time and permissions that are not set fall back to the
+/// [`DefaultOptions`] of the writer.
 #[derive(Copy, Clone)]
 pub struct FileOptions {
     compression_method: CompressionMethod,
     last_modified_time: DateTime,
     permissions: Option<u32>,
+    system: Option<System>,
+    deflate_level: Option<u32>,
+    deflate_strategy: DeflateStrategy,
+    #[cfg(feature = "parallel")]
+    deflate_threads: usize,
+    store_threshold: Option<f32>,
+    encrypt_with: Option<EncryptWith>,
+    aes_vendor_version: AesVendorVersion,
+    #[cfg(feature = "aes-crypto")]
+    aes_iteration_count: u32,
+    deflate_dictionary: Option<DictionaryId>,
+    large_file: bool,
+    forbid_zip64: bool,
+    data_descriptor: bool,
+    compression_method_set: bool,
+    last_modified_time_set: bool,
+}
+
+/// Identifies a preset dictionary registered with [`ZipWriter::register_deflate_dictionary`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DictionaryId(u32);
+
+/// Strategy used by the deflate compressor
+///
+/// The strategies other than `Default` help for specific kinds of data, like images with
+/// repeating values. They are only supported by the miniz_oxide backend, so the `deflate` feature
+/// needs to be enabled to use them. The asynchronous writer does not support them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DeflateStrategy {
+    /// Find matches of any length
+    Default,
+    /// Only use matches that are at least 5 bytes long
+    Filtered,
+    /// Don't look for matches, only Huffman encode the data
+    HuffmanOnly,
+    /// Only look for matches at a distance of 1 byte, which is run-length encoding
+    Rle,
 }
 
 impl FileOptions {
@@ -170,6 +815,47 @@ impl FileOptions {
             #[cfg(not(feature = "time"))]
             last_modified_time: DateTime::default(),
             permissions: None,
+            system: None,
+            deflate_level: None,
+            deflate_strategy: DeflateStrategy::Default,
+            #[cfg(feature = "parallel")]
+            deflate_threads: 1,
+            store_threshold: None,
+            encrypt_with: None,
+            aes_vendor_version: AesVendorVersion::Ae2,
+            #[cfg(feature = "aes-crypto")]
+            aes_iteration_count: AES_ITERATION_COUNT,
+            deflate_dictionary: None,
+            large_file: false,
+            forbid_zip64: false,
+            data_descriptor: false,
+            compression_method_set: false,
+            last_modified_time_set: false,
+        }
+    }
+
+    /// Options for a stored entry, without looking up the current time
+    fn stored() -> FileOptions {
+        FileOptions {
+            compression_method: CompressionMethod::Stored,
+            last_modified_time: DateTime::default(),
+            permissions: None,
+            system: None,
+            deflate_level: None,
+            deflate_strategy: DeflateStrategy::Default,
+            #[cfg(feature = "parallel")]
+            deflate_threads: 1,
+            store_threshold: None,
+            encrypt_with: None,
+            aes_vendor_version: AesVendorVersion::Ae2,
+            #[cfg(feature = "aes-crypto")]
+            aes_iteration_count: AES_ITERATION_COUNT,
+            deflate_dictionary: None,
+            large_file: false,
+            forbid_zip64: false,
+            data_descriptor: false,
+            compression_method_set: false,
+            last_modified_time_set: false,
         }
     }
 
@@ -180,6 +866,158 @@ impl FileOptions {
     /// otherwise.
     pub fn compression_method(mut self, method: CompressionMethod) -> FileOptions {
         self.compression_method = method;
+        self.compression_method_set = true;
+        self
+    }
+
+    /// Set the deflate compression level, from 0 (no compression) to 9 (best compression)
+    ///
+    /// Higher levels are clamped to 9. The default is the default level of the deflate backend,
+    /// which is 6 for most backends. This has no effect on other compression methods.
+    pub fn deflate_level(mut self, level: u32) -> FileOptions {
+        self.deflate_level = Some(level);
+        self
+    }
+
+    /// Set the deflate compression strategy
+    ///
+    /// The default is `DeflateStrategy::Default`. This has no effect on other compression
+    /// methods.
+    pub fn deflate_strategy(mut self, strategy: DeflateStrategy) -> FileOptions {
+        self.deflate_strategy = strategy;
+        self
+    }
+
+    /// Set the number of threads that compress the file with deflate
+    ///
+    /// With more than one thread, the data is split into blocks of 1 MiB that are compressed on
+    /// worker threads, which makes the file slightly larger. The default is 1. This has no
+    /// effect on other compression methods, on files with a deflate strategy or dictionary, or
+    /// on the asynchronous writer.
+    #[cfg(feature = "parallel")]
+    pub fn deflate_threads(mut self, threads: usize) -> FileOptions {
+        self.deflate_threads = threads;
+        self
+    }
+
+    /// Store the file uncompressed if compression doesn't save enough space
+    ///
+    /// The first 64 KiB of the file are compressed on trial. If the result is larger than
+    /// `threshold` times the uncompressed size (for example 0.95 to require at least 5% savings),
+    /// the file is stored instead and its local header is rewritten. This saves time and space for
+    /// files that are already compressed, like most media files.
+    ///
+    /// Only the synchronous [`ZipWriter`] supports this; the asynchronous writer ignores it, as does
+    /// a writer created with [`ZipWriter::new_stream`], but not one created with
+    /// [`ZipWriter::new_spooled`].
+    pub fn store_if_incompressible(mut self, threshold: f32) -> FileOptions {
+        self.store_threshold = Some(threshold);
+        self
+    }
+
+    /// Encrypt the file with ZipCrypto, the traditional PKWARE encryption
+    ///
+    /// ZipCrypto is supported by practically all tools, but it is weak and should not be relied
+    /// upon to keep data confidential. The compressed data of the file is kept until the file is
+    /// finished, because the encryption header depends on its checksum. It is kept in memory
+    /// unless another store is set with [`ZipWriter::set_temp_store`].
+    ///
+    /// Only the synchronous [`ZipWriter`] supports this; the asynchronous writer returns an error.
+    pub fn with_password(mut self, password: &[u8]) -> FileOptions {
+        self.encrypt_with = Some(EncryptWith::ZipCrypto(ZipCryptoKeys::derive(password)));
+        self
+    }
+
+    /// Encrypt the file with WinZip AES
+    ///
+    /// AES encryption is supported by most current tools. The format version used is AE-2 unless
+    /// it is changed with [`FileOptions::aes_vendor_version`]. Like with
+    /// [`FileOptions::with_password`], the compressed data of the file is kept until the file is
+    /// finished.
+    ///
+    /// Only the synchronous [`ZipWriter`] supports this; the asynchronous writer returns an error.
+    #[cfg(feature = "aes-crypto")]
+    pub fn with_aes_encryption(mut self, mode: AesMode, password: &[u8]) -> FileOptions {
+        self.encrypt_with = Some(EncryptWith::Aes {
+            mode,
+            password: AesPassword::new(password),
+        });
+        self
+    }
+
+    /// Set the version of the WinZip AES format used by [`FileOptions::with_aes_encryption`]
+    ///
+    /// The default is [`AesVendorVersion::Ae2`], which does not store the CRC-32 of the data.
+    pub fn aes_vendor_version(mut self, version: AesVendorVersion) -> FileOptions {
+        self.aes_vendor_version = version;
+        self
+    }
+
+    /// Set the number of PBKDF2 iterations used by [`FileOptions::with_aes_encryption`] to derive
+    /// the keys from the password
+    ///
+    /// The WinZip AES format always uses 1000 iterations, which is the default. Other tools can't
+    /// decrypt files written with another count, and [`ZipArchive`] only can after
+    /// [`ZipArchive::set_aes_iteration_count`] is called with the same count. Fewer iterations
+    /// make encryption faster, but also make it easier to guess the password.
+    ///
+    /// [`ZipArchive`]: crate::read::ZipArchive
+    /// [`ZipArchive::set_aes_iteration_count`]: crate::read::ZipArchive::set_aes_iteration_count
+    #[cfg(feature = "aes-crypto")]
+    pub fn aes_iteration_count(mut self, count: u32) -> FileOptions {
+        self.aes_iteration_count = count;
+        self
+    }
+
+    /// Compress the file with a preset dictionary registered with
+    /// [`ZipWriter::register_deflate_dictionary`]
+    ///
+    /// This only applies to deflated files. Reading the file requires registering the same
+    /// dictionary with [`ZipArchive::register_deflate_dictionary`], so other tools cannot read it.
+    /// The asynchronous writer does not support dictionaries.
+    ///
+    /// [`ZipArchive::register_deflate_dictionary`]: crate::read::ZipArchive::register_deflate_dictionary
+    #[cfg(feature = "deflate")]
+    pub fn deflate_dictionary(mut self, id: DictionaryId) -> FileOptions {
+        self.deflate_dictionary = Some(id);
+        self
+    }
+
+    /// Set whether the file may be 4 GiB or larger
+    ///
+    /// The local header of such a file needs a ZIP64 extra field, which has to be written before
+    /// the size of the file is known, so writing more than 4 GiB fails without this option. The
+    /// default is `false`, which keeps the headers of smaller files readable by tools without
+    /// ZIP64 support. Files with known sizes, like those added with
+    /// [`ZipWriter::start_stored_file`] or [`ZipWriter::raw_copy_file`], get the extra field
+    /// whenever they need it.
+    pub fn large_file(mut self, large: bool) -> FileOptions {
+        self.large_file = large;
+        self
+    }
+
+    /// Set whether writing the file fails instead of using ZIP64
+    ///
+    /// This is for archives that are read by tools without ZIP64 support. Starting the file fails
+    /// with [`ZipError::Zip64Required`] if [`FileOptions::large_file`] is set, if the size of a
+    /// file with known size is 4 GiB or larger, or if the file starts at an offset of 4 GiB or
+    /// more. Writing to the file fails as soon as 4 GiB or more were written. See
+    /// [`ZipWriter::set_forbid_zip64`] to forbid ZIP64 for the whole archive.
+    pub fn forbid_zip64(mut self, forbid: bool) -> FileOptions {
+        self.forbid_zip64 = forbid;
+        self
+    }
+
+    /// Set whether the CRC-32 and sizes of the file are written in a data descriptor after its
+    /// data, instead of in its local header
+    ///
+    /// This sets bit 3 of the general purpose flags. The data descriptor has 8 byte sizes if the
+    /// local header has a ZIP64 extra field, see [`FileOptions::large_file`]. Streaming readers,
+    /// like [`read_zipfile_from_stream`](crate::read::read_zipfile_from_stream), only find the end
+    /// of such a file if it is deflated. Directories never have a data descriptor. The default is
+    /// `false`.
+    pub fn data_descriptor(mut self, data_descriptor: bool) -> FileOptions {
+        self.data_descriptor = data_descriptor;
         self
     }
 
@@ -189,6 +1027,7 @@ impl FileOptions {
     /// otherwise
     pub fn last_modified_time(mut self, mod_time: DateTime) -> FileOptions {
         self.last_modified_time = mod_time;
+        self.last_modified_time_set = true;
         self
     }
 
@@ -201,6 +1040,16 @@ impl FileOptions {
         self.permissions = Some(mode & 0o777);
         self
     }
+
+    /// Set the system that the attributes of the file are written for
+    ///
+    /// The default is [`System::Unix`], for which the permissions are written as a Unix file
+    /// mode. For [`System::Dos`] and [`System::Ntfs`] they are written as DOS attributes instead,
+    /// which only mark directories and read-only files, as tools on Windows expect.
+    pub fn system(mut self, system: System) -> FileOptions {
+        self.system = Some(system);
+        self
+    }
 }
 
 impl Default for FileOptions {
@@ -209,6 +1058,406 @@ impl Default for FileOptions {
     }
 }
 
+/// Metadata for a stored file whose size and CRC-32 are known before it is written
+///
+/// Files started with [`ZipWriter::start_stored_file`] are written without a compressor and with
+/// their final header, so the writer does not need to seek back to update the header when the
+/// file is finished. This is faster for data that is already compressed, like images.
+#[derive(Copy, Clone)]
+pub struct StoredOptions {
+    options: FileOptions,
+    size: u64,
+    crc32: u32,
+}
+
+impl StoredOptions {
+    /// Options for a file of `size` bytes with the given CRC-32
+    pub fn new(size: u64, crc32: u32) -> StoredOptions {
+        StoredOptions {
+            options: FileOptions::default().compression_method(CompressionMethod::Stored),
+            size,
+            crc32,
+        }
+    }
+
+    /// Options for a file containing `data`
+    pub fn for_data(data: &[u8]) -> StoredOptions {
+        let mut hasher = Hasher::new();
+        hasher.update(data);
+        StoredOptions::new(data.len() as u64, hasher.finalize())
+    }
+
+    /// Set the last modified time
+    ///
+    /// The default is the same as for [`FileOptions::last_modified_time`].
+    pub fn last_modified_time(mut self, mod_time: DateTime) -> StoredOptions {
+        self.options = self.options.last_modified_time(mod_time);
+        self
+    }
+
+    /// Set the permissions for the new file, like [`FileOptions::unix_permissions`].
+    pub fn unix_permissions(mut self, mode: u32) -> StoredOptions {
+        self.options = self.options.unix_permissions(mode);
+        self
+    }
+
+    /// Set the system that the attributes of the file are written for, like
+    /// [`FileOptions::system`].
+    pub fn system(mut self, system: System) -> StoredOptions {
+        self.options = self.options.system(system);
+        self
+    }
+}
+
+/// Defaults for the options of the files of a writer, which apply where the [`FileOptions`] of a
+/// file don't set the option
+///
+/// This allows enforcing a policy for all archives in one place, for example by creating every
+/// writer with the same defaults through [`ZipWriter::set_default_options`]. The defaults are a
+/// plain value, so they can be shared between threads and changed for a single writer. Options
+/// that are not set here keep the defaults described at [`FileOptions`].
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DefaultOptions {
+    compression_method: Option<CompressionMethod>,
+    last_modified_time: Option<DateTime>,
+    file_permissions: Option<u32>,
+    directory_permissions: Option<u32>,
+    system: Option<System>,
+}
+
+impl DefaultOptions {
+    /// Defaults for reproducible archives, with 1980-01-01 as the last modified time of all
+    /// files, `0o644` as the permissions of files and `0o755` as those of directories
+    pub fn deterministic() -> DefaultOptions {
+        DefaultOptions::default()
+            .last_modified_time(DateTime::default())
+            .file_permissions(0o644)
+            .directory_permissions(0o755)
+    }
+
+    /// Set the default compression method of files, like [`FileOptions::compression_method`]
+    pub fn compression_method(mut self, method: CompressionMethod) -> DefaultOptions {
+        self.compression_method = Some(method);
+        self
+    }
+
+    /// Set the default last modified time of files, which replaces the current time
+    pub fn last_modified_time(mut self, mod_time: DateTime) -> DefaultOptions {
+        self.last_modified_time = Some(mod_time);
+        self
+    }
+
+    /// Set the default permissions of files, like [`FileOptions::unix_permissions`]
+    pub fn file_permissions(mut self, mode: u32) -> DefaultOptions {
+        self.file_permissions = Some(mode & 0o777);
+        self
+    }
+
+    /// Set the default permissions of directories, like [`FileOptions::unix_permissions`]
+    pub fn directory_permissions(mut self, mode: u32) -> DefaultOptions {
+        self.directory_permissions = Some(mode & 0o777);
+        self
+    }
+
+    /// Set the default system of files and directories, like [`FileOptions::system`]
+    pub fn system(mut self, system: System) -> DefaultOptions {
+        self.system = Some(system);
+        self
+    }
+
+    /// Fill in the options that are not set by `options`
+    fn apply(&self, mut options: FileOptions, directory: bool) -> FileOptions {
+        if let (Some(method), false) = (self.compression_method, options.compression_method_set) {
+            options.compression_method = method;
+        }
+        if let (Some(time), false) = (self.last_modified_time, options.last_modified_time_set) {
+            options.last_modified_time = time;
+        }
+        if options.permissions.is_none() {
+            options.permissions = if directory {
+                self.directory_permissions
+            } else {
+                self.file_permissions
+            };
+        }
+        options.system = options.system.or(self.system);
+        options
+    }
+}
+
+/// Checks applied to the names of entries added to a writer
+///
+/// By default every name is written as-is. Enabling the checks makes the writer refuse names
+/// that safe extraction (see [`ZipFile::enclosed_name`]) or other tools would reject, by
+/// returning [`ZipError::InvalidFileName`] before anything is written for the entry.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NameOptions {
+    normalize: bool,
+    reject_duplicates: bool,
+    reject_absolute: bool,
+    reject_parent_dir: bool,
+    reject_backslashes: bool,
+    reject_non_portable: bool,
+    max_length: Option<usize>,
+}
+
+impl NameOptions {
+    /// Reject duplicate names, absolute paths, `..` components and backslashes
+    pub fn strict() -> NameOptions {
+        NameOptions {
+            normalize: false,
+            reject_duplicates: true,
+            reject_absolute: true,
+            reject_parent_dir: true,
+            reject_backslashes: true,
+            reject_non_portable: false,
+            max_length: None,
+        }
+    }
+
+    /// Normalize names with [`normalize_name`] before checking them
+    ///
+    /// This rewrites backslashes to `/`, drops `.` components and makes absolute paths relative,
+    /// which is useful when names come from Windows paths. Names that cannot be normalized are
+    /// rejected.
+    pub fn normalize(mut self, normalize: bool) -> NameOptions {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Reject names that were already used by a previous entry
+    pub fn reject_duplicates(mut self, reject: bool) -> NameOptions {
+        self.reject_duplicates = reject;
+        self
+    }
+
+    /// Reject names starting with a path separator or a drive letter, like `/etc/passwd` or `C:\foo`
+    pub fn reject_absolute(mut self, reject: bool) -> NameOptions {
+        self.reject_absolute = reject;
+        self
+    }
+
+    /// Reject names with a `..` component
+    pub fn reject_parent_dir(mut self, reject: bool) -> NameOptions {
+        self.reject_parent_dir = reject;
+        self
+    }
+
+    /// Reject names containing a backslash
+    ///
+    /// The ZIP specification requires `/` as the path separator, but some extractors treat `\` as
+    /// one too.
+    pub fn reject_backslashes(mut self, reject: bool) -> NameOptions {
+        self.reject_backslashes = reject;
+        self
+    }
+
+    /// Reject names containing characters other than printable ASCII
+    ///
+    /// Such names are encoded the same way by every reader, including legacy systems that know
+    /// neither UTF-8 nor the IBM code page 437.
+    pub fn reject_non_portable(mut self, reject: bool) -> NameOptions {
+        self.reject_non_portable = reject;
+        self
+    }
+
+    /// Reject names longer than `max_length` bytes
+    ///
+    /// The length includes the `/` at the end of the names of directories.
+    pub fn max_length(mut self, max_length: Option<usize>) -> NameOptions {
+        self.max_length = max_length;
+        self
+    }
+
+    fn check(&self, name: String, names: &HashSet<String>) -> ZipResult<String> {
+        let name = if self.normalize {
+            normalize_name(&name)
+                .ok_or(ZipError::InvalidFileName("File name cannot be normalized"))?
+        } else {
+            name
+        };
+        if self.reject_absolute && is_absolute_name(&name) {
+            return Err(ZipError::InvalidFileName("File name is an absolute path"));
+        }
+        if self.reject_parent_dir && name.split(is_separator).any(|c| c == "..") {
+            return Err(ZipError::InvalidFileName(
+                "File name contains a '..' component",
+            ));
+        }
+        if self.reject_backslashes && name.contains('\\') {
+            return Err(ZipError::InvalidFileName("File name contains a backslash"));
+        }
+        if self.reject_non_portable && !name.bytes().all(|b| (b' '..=b'~').contains(&b)) {
+            return Err(ZipError::InvalidFileName(
+                "File name contains a character that is not printable ASCII",
+            ));
+        }
+        if matches!(self.max_length, Some(length) if name.len() > length) {
+            return Err(ZipError::InvalidFileName("File name is too long"));
+        }
+        if self.reject_duplicates && names.contains(&name) {
+            return Err(ZipError::InvalidFileName("Duplicate file name"));
+        }
+        Ok(name)
+    }
+}
+
+/// The settings of a writer that apply to the whole archive, set at once with
+/// [`ZipWriter::set_options`]
+///
+/// By default the names are not checked, files have the defaults described at [`FileOptions`],
+/// ZIP64 is used where it is needed and no checksum of the central directory is written.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ArchiveOptions {
+    names: NameOptions,
+    defaults: DefaultOptions,
+    forbid_zip64: bool,
+    central_directory_checksum: bool,
+}
+
+impl ArchiveOptions {
+    /// Set the checks applied to the names of entries, see [`ZipWriter::set_name_options`]
+    pub fn name_options(mut self, options: NameOptions) -> ArchiveOptions {
+        self.names = options;
+        self
+    }
+
+    /// Set the defaults for the options of files and directories, see
+    /// [`ZipWriter::set_default_options`]
+    pub fn default_options(mut self, defaults: DefaultOptions) -> ArchiveOptions {
+        self.defaults = defaults;
+        self
+    }
+
+    /// Set whether writing fails instead of using ZIP64, see [`ZipWriter::set_forbid_zip64`]
+    pub fn forbid_zip64(mut self, forbid: bool) -> ArchiveOptions {
+        self.forbid_zip64 = forbid;
+        self
+    }
+
+    /// Set whether a checksum of the central directory is written, see
+    /// [`ZipWriter::set_central_directory_checksum`]
+    pub fn central_directory_checksum(mut self, checksum: bool) -> ArchiveOptions {
+        self.central_directory_checksum = checksum;
+        self
+    }
+}
+
+/// The edits of an archive that is copied with [`ZipWriter::rewrite`]
+///
+/// Entries are named as they are stored in the source archive. Entries that are not renamed,
+/// removed or replaced are copied without being decompressed.
+#[derive(Default)]
+pub struct EditPlan<'a> {
+    renames: HashMap<String, String>,
+    removals: HashSet<String>,
+    replacements: HashMap<String, (Box<dyn Read + 'a>, FileOptions)>,
+    additions: Vec<(String, Box<dyn Read + 'a>, FileOptions)>,
+}
+
+impl<'a> EditPlan<'a> {
+    /// Create a plan that copies every entry unchanged.
+    pub fn new() -> EditPlan<'a> {
+        EditPlan::default()
+    }
+
+    /// Store the entry `name` under the name `new_name`
+    pub fn rename<S, T>(mut self, name: S, new_name: T) -> EditPlan<'a>
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        self.renames.insert(name.into(), new_name.into());
+        self
+    }
+
+    /// Leave out the entry `name`
+    pub fn remove<S: Into<String>>(mut self, name: S) -> EditPlan<'a> {
+        self.removals.insert(name.into());
+        self
+    }
+
+    /// Replace the contents of the entry `name` by the contents of `reader`, written with
+    /// `options`
+    ///
+    /// The entry keeps its place in the archive, and is also renamed if the plan renames it.
+    pub fn replace<S, R>(mut self, name: S, reader: R, options: FileOptions) -> EditPlan<'a>
+    where
+        S: Into<String>,
+        R: Read + 'a,
+    {
+        self.replacements
+            .insert(name.into(), (Box::new(reader), options));
+        self
+    }
+
+    /// Add a file `name` with the contents of `reader`, written with `options` after the entries
+    /// of the source archive
+    pub fn add<S, R>(mut self, name: S, reader: R, options: FileOptions) -> EditPlan<'a>
+    where
+        S: Into<String>,
+        R: Read + 'a,
+    {
+        self.additions
+            .push((name.into(), Box::new(reader), options));
+        self
+    }
+}
+
+/// Rewrite an entry name into a relative path using `/` as the separator
+///
+/// - Backslashes are replaced by `/`
+/// - Leading separators and drive letters are removed
+/// - Empty and `.` components are removed
+/// - `..` components are resolved against the preceding component
+/// - A trailing separator, marking a directory, is preserved
+///
+/// Returns `None` if a `..` would leave the root, or if nothing remains of the name.
+///
+/// ```
+/// use zip::write::normalize_name;
+///
+/// assert_eq!(normalize_name("/foo\\bar/./baz.txt").as_deref(), Some("foo/bar/baz.txt"));
+/// assert_eq!(normalize_name("C:\\foo\\..\\bar\\").as_deref(), Some("bar/"));
+/// assert_eq!(normalize_name("foo/../../bar"), None);
+/// ```
+pub fn normalize_name(name: &str) -> Option<String> {
+    let is_dir = name.ends_with(is_separator);
+    let mut components = Vec::new();
+    for (i, component) in name.split(is_separator).enumerate() {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop()?;
+            }
+            c if i == 0 && is_drive_letter(c) => {}
+            c => components.push(c),
+        }
+    }
+    if components.is_empty() {
+        return None;
+    }
+    let mut normalized = components.join("/");
+    if is_dir {
+        normalized.push('/');
+    }
+    Some(normalized)
+}
+
+pub(crate) fn is_separator(c: char) -> bool {
+    c == '/' || c == '\\'
+}
+
+fn is_drive_letter(component: &str) -> bool {
+    let bytes = component.as_bytes();
+    bytes.len() == 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+pub(crate) fn is_absolute_name(name: &str) -> bool {
+    let first = name.split(is_separator).next().unwrap_or_default();
+    name.starts_with(is_separator) || is_drive_letter(first)
+}
+
 impl<W: Write + io::Seek> Write for ZipWriter<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         if !self.writing_to_file {
@@ -217,24 +1466,92 @@ impl<W: Write + io::Seek> Write for ZipWriter<W> {
                 "No file has been started",
             ));
         }
-        match self.inner.ref_mut() {
-            Some(ref mut w) => {
-                let write_result = w.write(buf);
-                if let Ok(count) = write_result {
-                    self.stats.update(&buf[0..count]);
-                }
-                write_result
+        let started = Instant::now();
+        let result = match self.transform.take() {
+            Some(mut transform) => {
+                let result = transform.write(buf, &mut EntryWriter(self));
+                self.transform = Some(transform);
+                result.map(|_| buf.len())
             }
+            None => self.write_entry_data(buf),
+        };
+        self.stats.elapsed += started.elapsed();
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.inner.ref_mut() {
+            Some(ref mut w) => w.flush(),
             None => Err(io::Error::new(
                 io::ErrorKind::BrokenPipe,
                 "ZipWriter was already closed",
             )),
         }
     }
+}
+
+/// Writer for the output of an [`EntryTransform`], which bypasses the transform.
+struct EntryWriter<'w, W: Write + io::Seek>(&'w mut ZipWriter<W>);
+
+impl<'w, W: Write + io::Seek> Write for EntryWriter<'w, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write_entry_data(buf)
+    }
 
     fn flush(&mut self) -> io::Result<()> {
-        match self.inner.ref_mut() {
-            Some(ref mut w) => w.flush(),
+        self.0.flush()
+    }
+}
+
+impl<W: Write + io::Seek> ZipWriter<W> {
+    /// The writer for the compressed data of the current file.
+    fn data_writer(&mut self) -> Option<&mut dyn Write> {
+        match self.encrypting {
+            Some(ref mut encrypting) => encrypting.writer.ref_mut(),
+            None => self.inner.ref_mut(),
+        }
+    }
+
+    /// Switch the compression of the data of the current file.
+    fn switch_data_to(
+        &mut self,
+        compression: CompressionMethod,
+        options: &FileOptions,
+        compressor: Option<&dyn Compressor>,
+    ) -> ZipResult<()> {
+        match self.encrypting {
+            Some(ref mut encrypting) => {
+                encrypting
+                    .writer
+                    .switch_to(compression, options, compressor, &self.buffers)
+            }
+            None => self
+                .inner
+                .switch_to(compression, options, compressor, &self.buffers),
+        }
+    }
+
+    /// Write data of the current file, after it has been transformed.
+    fn write_entry_data(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(sample) = self.sample.as_mut() {
+            let count = buf.len().min(SAMPLE_SIZE - sample.data.len());
+            sample.data.extend_from_slice(&buf[..count]);
+            self.stats.update(&buf[..count]);
+            if sample.data.len() == SAMPLE_SIZE {
+                self.end_sample()?;
+            }
+            return Ok(count);
+        }
+        match self.data_writer() {
+            Some(w) => {
+                let write_result = w.write(buf);
+                if let Ok(count) = write_result {
+                    self.stats.update(&buf[0..count]);
+                    self.check_size_limit(0, false)?;
+                    self.check_zip64_data()?;
+                }
+                write_result
+            }
             None => Err(io::Error::new(
                 io::ErrorKind::BrokenPipe,
                 "ZipWriter was already closed",
@@ -256,14 +1573,27 @@ impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncWrite for AsyncZipWriter<W> {
                 "No file has been started",
             )));
         }
+        let started = Instant::now();
         let AsyncZipWriterProject {
-            mut inner, stats, ..
+            mut inner,
+            stats,
+            files,
+            forbidding_zip64,
+            ..
         } = self.project();
         match inner.ref_mut() {
             Some(w) => {
                 let write_result = w.poll_write(cx, buf);
+                stats.elapsed += started.elapsed();
                 if let Poll::Ready(Ok(count)) = write_result {
                     stats.update(&buf[0..count]);
+                    // The compressed size is only known when the file is finished
+                    let bytes = stats.data.bytes_written();
+                    if *forbidding_zip64 && bytes >= spec::ZIP64_BYTES_THR {
+                        let entries = files.len();
+                        let error = ZipError::Zip64Required { entries, bytes };
+                        return Poll::Ready(Err(error.into()));
+                    }
                 }
                 write_result
             }
@@ -298,10 +1628,50 @@ impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncWrite for AsyncZipWriter<W> {
     }
 }
 
+impl Sample {
+    /// Compress the sample on trial, to see whether it is worth compressing the file.
+    fn is_compressible(
+        &self,
+        compressor: Option<&dyn Compressor>,
+        buffers: &BufferPool,
+    ) -> ZipResult<bool> {
+        if self.data.is_empty() {
+            return Ok(false);
+        }
+        let mut trial = GenericZipWriter::Storer(io::Cursor::new(Vec::new()));
+        let method = self.options.compression_method;
+        trial.switch_to(method, &self.options, compressor, buffers)?;
+        if let Some(w) = trial.ref_mut() {
+            w.write_all(&self.data)?;
+        }
+        trial.switch_to(
+            CompressionMethod::Stored,
+            &FileOptions::stored(),
+            None,
+            buffers,
+        )?;
+        let compressed_size = trial.unwrap().into_inner().len();
+
+        let threshold = self.options.store_threshold.unwrap_or(1.0);
+        Ok((compressed_size as f64) <= self.data.len() as f64 * f64::from(threshold))
+    }
+}
+
+impl Default for ZipWriterStats {
+    fn default() -> ZipWriterStats {
+        ZipWriterStats {
+            data: Crc32Writer::new(io::sink()),
+            start: 0,
+            elapsed: Duration::default(),
+        }
+    }
+}
+
 impl ZipWriterStats {
     fn update(&mut self, buf: &[u8]) {
-        self.hasher.update(buf);
-        self.bytes_written += buf.len() as u64;
+        self.data
+            .write_all(buf)
+            .expect("Writing to a sink never fails");
     }
 }
 
@@ -311,12 +1681,45 @@ impl<W: Write + io::Seek> ZipWriter<W> {
     /// Before writing to this object, the [`ZipWriter::start_file`] function should be called.
     pub fn new(inner: W) -> ZipWriter<W> {
         ZipWriter {
-            inner: GenericZipWriter::Storer(inner),
+            inner: GenericZipWriter::Storer(SizeTracker::new(inner)),
             files: Vec::new(),
             stats: Default::default(),
             writing_to_file: false,
-            comment: String::new(),
+            comment: Vec::new(),
             writing_raw: false,
+            compressors: Codecs::default(),
+            name_options: NameOptions::default(),
+            names: HashSet::new(),
+            size_limit: None,
+            checkpoint_interval: None,
+            checkpointed: 0,
+            sample: None,
+            write_hook: Hook::default(),
+            transform: None,
+            encrypting: None,
+            temp_store: Arc::new(MemoryStore),
+            checking_stored: false,
+            checking_raw: false,
+            forbid_zip64: false,
+            forbidding_zip64: false,
+            defaults: DefaultOptions::default(),
+            zip64_extensible_data: Vec::new(),
+            central_directory_checksum: false,
+            signer: Hook::default(),
+            contents_signer: Hook::default(),
+            observer: Hook::default(),
+            streaming: false,
+            spooled: false,
+            disk_layout: None,
+            #[cfg(feature = "deflate")]
+            dictionaries: deflate::Dictionaries::default(),
+            entry_ids: Vec::new(),
+            next_entry_id: 0,
+            buffers: BufferPool::default(),
+            min_length: 0,
+            appended_files: 0,
+            finished_files: 0,
+            superseded: Vec::new(),
         }
     }
 
@@ -325,7 +1728,229 @@ impl<W: Write + io::Seek> ZipWriter<W> {
     where
         S: Into<String>,
     {
-        self.comment = comment.into();
+        self.comment = comment.into().into_bytes();
+    }
+
+    /// Set the extensible data sector of the ZIP64 central directory end record.
+    ///
+    /// The data should consist of blocks as described at [`ExtensibleDataBlock`], which
+    /// [`ExtensibleDataBlock::encode_all`] creates. A ZIP64 record is written for any data that is
+    /// not empty, so [`ZipWriter::set_forbid_zip64`] makes finishing the archive fail then.
+    ///
+    /// [`ExtensibleDataBlock`]: crate::ExtensibleDataBlock
+    /// [`ExtensibleDataBlock::encode_all`]: crate::ExtensibleDataBlock::encode_all
+    pub fn set_zip64_extensible_data(&mut self, data: Vec<u8>) {
+        self.zip64_extensible_data = data;
+    }
+
+    /// Write a checksum of the central directory when finishing the archive.
+    ///
+    /// The checksum is appended to the extensible data sector as the block described at
+    /// [`ExtensibleDataBlock::CENTRAL_DIRECTORY_CHECKSUM`], and [`ZipArchive::new`] fails if it
+    /// does not match. As it needs a ZIP64 record, [`ZipWriter::set_forbid_zip64`] makes finishing
+    /// the archive fail then.
+    ///
+    /// [`ExtensibleDataBlock::CENTRAL_DIRECTORY_CHECKSUM`]: crate::ExtensibleDataBlock::CENTRAL_DIRECTORY_CHECKSUM
+    /// [`ZipArchive::new`]: crate::ZipArchive::new
+    pub fn set_central_directory_checksum(&mut self, checksum: bool) {
+        self.central_directory_checksum = checksum;
+    }
+
+    /// The identifier of the entry that was started last, if any.
+    pub fn last_entry_id(&self) -> Option<EntryId> {
+        self.entry_ids.last().copied()
+    }
+
+    /// The current index of the entry `id`, or `None` if it is no longer part of the archive.
+    pub fn entry_index(&self, id: EntryId) -> Option<usize> {
+        // Identifiers are handed out in increasing order, and entries keep their relative order
+        self.entry_ids.binary_search(&id).ok()
+    }
+
+    /// The name of the entry `id`, or `None` if it is no longer part of the archive.
+    pub fn entry_name(&self, id: EntryId) -> Option<&str> {
+        self.entry_index(id)
+            .map(|index| self.files[index].file_name.as_str())
+    }
+
+    /// Limit the size of the archive to `limit` bytes.
+    ///
+    /// Writing fails with [`ZipError::SizeLimitExceeded`] as soon as the limit is exceeded, rather
+    /// than after the whole archive has been written. Raw copies of entries are checked before
+    /// any of their data is copied, and the central directory before it is written. Some encoders
+    /// buffer their output, so the limit may be exceeded by the size of that buffer before it is
+    /// detected. The data written so far is kept.
+    pub fn set_size_limit(&mut self, limit: Option<u64>) {
+        self.size_limit = limit;
+    }
+
+    /// Write a central directory and end record after every `entries` entries, so that the
+    /// entries so far can be read if the writer is never finished, like after a crash.
+    ///
+    /// The central directory is written when the last of the entries is finished, by
+    /// [`ZipWriter::finish_file`] or by starting the next entry. The next entry is written over
+    /// it, like the central directory that [`ZipWriter::finish`] writes, so the finished archive
+    /// contains no checkpoints. A crash after the next entry was started leaves an end record that
+    /// refers to overwritten data, so call [`ZipWriter::finish_file`] to keep the archive readable
+    /// until then. Checkpoints are not written for archives that are written to a stream or split
+    /// over disks, which can't seek back.
+    pub fn set_checkpoint_interval(&mut self, entries: Option<usize>) {
+        self.checkpoint_interval = entries;
+    }
+
+    /// Set whether writing fails instead of using ZIP64 anywhere in the archive
+    ///
+    /// Every file started from now on is written as if [`FileOptions::forbid_zip64`] was set.
+    /// Starting a file fails with [`ZipError::Zip64Required`] if the archive would have 65535 or
+    /// more files, and [`ZipWriter::finish`] fails before writing the central directory if it
+    /// would start at an offset of 4 GiB or more or be that large itself.
+    pub fn set_forbid_zip64(&mut self, forbid: bool) {
+        self.forbid_zip64 = forbid;
+    }
+
+    /// Set the defaults for the options of files and directories added from now on.
+    ///
+    /// Raw copies of files keep the metadata of the copied file.
+    pub fn set_default_options(&mut self, defaults: DefaultOptions) {
+        self.defaults = defaults;
+    }
+
+    /// Set all settings of the archive at once, which apply to the entries added from now on.
+    pub fn set_options(&mut self, options: ArchiveOptions) {
+        self.set_name_options(options.names);
+        self.set_default_options(options.defaults);
+        self.set_forbid_zip64(options.forbid_zip64);
+        self.set_central_directory_checksum(options.central_directory_checksum);
+    }
+
+    /// Set a hook that transforms the data of every file started from now on.
+    ///
+    /// The hook does not apply to directories and raw copies of files.
+    pub fn set_write_hook<H: WriteHook + 'static>(&mut self, hook: H) {
+        self.write_hook.set(Arc::new(hook));
+    }
+
+    /// Set a signer that signs the central directory when finishing the archive.
+    ///
+    /// The signature is written to a digital signature record at the end of the central
+    /// directory, see the [`signature`] module.
+    pub fn set_signer<S: Signer + 'static>(&mut self, signer: S) {
+        self.signer.set(Arc::new(signer));
+    }
+
+    /// Set a signer that signs the canonical contents of the archive when finishing it.
+    ///
+    /// The signature is appended to the extensible data sector as the block described at
+    /// [`ExtensibleDataBlock::CONTENTS_SIGNATURE`], which needs a ZIP64 record like
+    /// [`ZipWriter::set_central_directory_checksum`].
+    ///
+    /// [`ExtensibleDataBlock::CONTENTS_SIGNATURE`]: crate::ExtensibleDataBlock::CONTENTS_SIGNATURE
+    pub fn set_contents_signer<S: Signer + 'static>(&mut self, signer: S) {
+        self.contents_signer.set(Arc::new(signer));
+    }
+
+    /// Set an observer that is told how each file is written once it is finished.
+    ///
+    /// Directories and raw copies of files are not observed.
+    pub fn set_observer<O: WriteObserver + 'static>(&mut self, observer: O) {
+        self.observer.set(Arc::new(observer));
+    }
+
+    /// Set where the compressed data of encrypted files is kept until they are finished, which
+    /// is in memory by default, see the [`temp`](crate::temp) module.
+    pub fn set_temp_store<T: TempStore + 'static>(&mut self, store: T) {
+        self.temp_store = Arc::new(store);
+    }
+
+    /// Check that the archive and `additional` bytes fit in the size limit.
+    ///
+    /// `central_directory` selects whether the current entry or the central directory is blamed.
+    fn check_size_limit(&self, additional: u64, central_directory: bool) -> ZipResult<()> {
+        let limit = match self.size_limit {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let size = self.inner.get_ref().map_or(0, SizeTracker::size);
+        if size.saturating_add(additional) <= limit {
+            return Ok(());
+        }
+        let entry = if central_directory {
+            None
+        } else {
+            self.files.last().map(|file| file.file_name.to_string())
+        };
+        Err(ZipError::SizeLimitExceeded(entry))
+    }
+
+    /// Check that the data of the current file fits without ZIP64 if it is forbidden.
+    fn check_zip64_data(&self) -> ZipResult<()> {
+        if !self.forbidding_zip64 {
+            return Ok(());
+        }
+        let position = self.inner.get_ref().map_or(0, SizeTracker::position);
+        let bytes = position
+            .saturating_sub(self.stats.start)
+            .max(self.stats.data.bytes_written());
+        if bytes < spec::ZIP64_BYTES_THR {
+            return Ok(());
+        }
+        Err(ZipError::Zip64Required {
+            entries: self.files.len(),
+            bytes,
+        })
+    }
+
+    /// Register a compressor for entries that use `method`.
+    ///
+    /// This makes it possible to write entries with compression methods that are not supported
+    /// by this crate. A compressor registered for a supported method takes precedence over the
+    /// built-in implementation.
+    pub fn register_compressor<C>(&mut self, method: CompressionMethod, compressor: C)
+    where
+        C: Compressor + 'static,
+    {
+        self.compressors.insert(method, Arc::new(compressor));
+    }
+
+    /// Register a preset dictionary for deflated files, which improves the compression of small
+    /// files that are similar to the dictionary.
+    ///
+    /// Only the last 32 KiB of the dictionary are used. Files are compressed with the dictionary
+    /// if they are started with [`FileOptions::deflate_dictionary`] and the returned ID.
+    #[cfg(feature = "deflate")]
+    pub fn register_deflate_dictionary(&mut self, dictionary: &[u8]) -> DictionaryId {
+        DictionaryId(self.dictionaries.insert(dictionary))
+    }
+
+    /// The compressor of the data of a file, if it is not compressed by a built-in encoder
+    fn compressor(&self, options: &FileOptions) -> ZipResult<Option<Arc<dyn Compressor>>> {
+        #[cfg(feature = "deflate")]
+        {
+            if let (Some(id), CompressionMethod::Deflated) =
+                (options.deflate_dictionary, options.compression_method)
+            {
+                let dictionary = self.dictionaries.get(id.0);
+                let dictionary = dictionary.ok_or(ZipError::UnsupportedArchive(
+                    "The deflate dictionary is not registered with this writer",
+                ))?;
+                return Ok(Some(Arc::new(deflate::DictionaryCompressor {
+                    dictionary,
+                    level: options.deflate_level,
+                    strategy: options.deflate_strategy,
+                })));
+            }
+        }
+        Ok(self.compressors.get(options.compression_method))
+    }
+
+    /// Set the checks applied to the names of entries added from now on.
+    pub fn set_name_options(&mut self, options: NameOptions) {
+        self.name_options = options;
+        self.names = if options.reject_duplicates {
+            self.files.iter().map(|f| f.file_name.to_string()).collect()
+        } else {
+            HashSet::new()
+        };
     }
 
     /// Start a new file for with the requested options.
@@ -338,6 +1963,7 @@ impl<W: Write + io::Seek> ZipWriter<W> {
     where
         S: Into<String>,
     {
+        let name = self.name_options.check(name.into(), &self.names)?;
         self.finish_file()?;
 
         let is_raw = raw_values.is_some();
@@ -346,16 +1972,32 @@ impl<W: Write + io::Seek> ZipWriter<W> {
             compressed_size: 0,
             uncompressed_size: 0,
         });
+        let compressor = if is_raw {
+            None
+        } else {
+            self.compressor(&options)?
+        };
+        let encrypting = match options.encrypt_with {
+            Some(encrypt_with) if !is_raw => Some(Encrypting {
+                encrypt_with,
+                #[cfg(feature = "aes-crypto")]
+                aes_iteration_count: options.aes_iteration_count,
+                writer: GenericZipWriter::Storer(self.temp_store.create()?),
+            }),
+            _ => None,
+        };
 
         {
             let writer = self.inner.get_plain();
             let header_start = writer.seek(io::SeekFrom::Current(0))?;
 
             let permissions = options.permissions.unwrap_or(0o100644);
+            let system = options.system.unwrap_or(System::Unix);
             let mut file = ZipFileData {
-                system: System::Unix,
+                system,
                 version_made_by: DEFAULT_VERSION,
-                encrypted: false,
+                version_to_extract: 0,
+                encrypted: options.encrypt_with.is_some() && !is_raw,
                 compression_method: options.compression_method,
                 last_modified_time: options.last_modified_time,
                 crc32: raw_values.crc32,
@@ -365,68 +2007,328 @@ impl<W: Write + io::Seek> ZipWriter<W> {
                 file_name_raw: Vec::new(), // Never used for saving
                 file_comment: String::new(),
                 header_start,
+                disk_number: 0,
                 data_start: 0,
                 central_header_start: 0,
-                external_attributes: permissions << 16,
+                external_attributes: external_attributes(system, permissions),
+                aes_mode: match options.encrypt_with {
+                    #[cfg(feature = "aes-crypto")]
+                    Some(EncryptWith::Aes { mode, .. }) if !is_raw => {
+                        Some((mode, options.aes_vendor_version))
+                    }
+                    _ => None,
+                },
+                strong_encryption: None,
+                deflate_dictionary: options
+                    .deflate_dictionary
+                    .filter(|_| !is_raw && options.compression_method == CompressionMethod::DEFLATE)
+                    .map(|id| id.0),
+                large_file: options.large_file
+                    || raw_values.compressed_size >= spec::ZIP64_BYTES_THR
+                    || raw_values.uncompressed_size >= spec::ZIP64_BYTES_THR,
+                using_data_descriptor: options.data_descriptor,
+                name_cache: NameCache::default(),
+                central_header: None,
             };
+            let entries = self.files.len() + 1;
+            let forbid_zip64 = options.forbid_zip64 || self.forbid_zip64;
+            if (forbid_zip64 && file.zip64_extension())
+                || (self.forbid_zip64 && entries >= spec::ZIP64_ENTRY_THR)
+            {
+                return Err(zip64_required(entries, &file));
+            }
+            self.forbidding_zip64 = forbid_zip64;
+            if let Some(layout) = &self.disk_layout {
+                let mut header = Vec::new();
+                write_local_file_header(&mut header, &file)?;
+                lock(layout).keep_together(header_start, header.len() as u64);
+            }
             write_local_file_header(writer, &file)?;
 
             let header_end = writer.seek(io::SeekFrom::Current(0))?;
             self.stats.start = header_end;
             file.data_start = header_end;
 
-            self.stats.bytes_written = 0;
-            self.stats.hasher = Hasher::new();
+            self.stats.data = Crc32Writer::new(io::sink());
+            self.stats.elapsed = Duration::default();
 
+            if self.name_options.reject_duplicates {
+                self.names.insert(file.file_name.to_string());
+            }
+            // The new entry supersedes the entries of the archive that was appended to with its
+            // name, which are kept in case it is aborted
+            let new_id = EntryId(self.next_entry_id);
+            while let Some(index) = self.files[..self.appended_files]
+                .iter()
+                .position(|f| f.file_name == file.file_name)
+            {
+                let id = self.entry_ids.remove(index);
+                let superseded = self.files.remove(index);
+                self.superseded.push((new_id, index, id, superseded));
+                self.appended_files -= 1;
+                self.finished_files -= 1;
+            }
             self.files.push(file);
+            self.entry_ids.push(new_id);
+            self.next_entry_id += 1;
         }
+        self.check_size_limit(raw_values.compressed_size, false)?;
 
         self.writing_raw = is_raw;
-        self.inner.switch_to(if is_raw {
-            CompressionMethod::Stored
+        self.encrypting = encrypting;
+        if is_raw {
+            self.inner
+                .switch_to(CompressionMethod::Stored, &options, None, &self.buffers)?;
+        } else if options.store_threshold.is_some()
+            && options.compression_method != CompressionMethod::Stored
+            && (!self.streaming || self.spooled)
+        {
+            // The method is only decided after the sample, until then the data is buffered
+            self.sample = Some(Sample {
+                data: Vec::new(),
+                options,
+            });
         } else {
-            options.compression_method
-        })?;
+            self.switch_data_to(options.compression_method, &options, compressor.as_deref())?;
+        }
 
         Ok(())
     }
 
-    fn finish_file(&mut self) -> ZipResult<()> {
-        self.inner.switch_to(CompressionMethod::Stored)?;
+    /// Decide on the compression method of the current file and write out its sample.
+    fn end_sample(&mut self) -> ZipResult<()> {
+        let sample = match self.sample.take() {
+            Some(sample) => sample,
+            None => return Ok(()),
+        };
+        let mut options = sample.options;
+        let mut compressor = self.compressor(&options)?;
+        if !sample.is_compressible(compressor.as_deref(), &self.buffers)? {
+            options.compression_method = CompressionMethod::Stored;
+            compressor = None;
+            // Nothing was written after the header yet
+            let file = self.files.last_mut().unwrap();
+            file.compression_method = CompressionMethod::Stored;
+            file.deflate_dictionary = None;
+            let writer = self.inner.get_plain();
+            update_local_file_method(writer, file)?;
+            writer.seek(io::SeekFrom::Start(file.data_start))?;
+        }
+
+        self.switch_data_to(options.compression_method, &options, compressor.as_deref())?;
+        match self.data_writer() {
+            Some(w) => w.write_all(&sample.data)?,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "ZipWriter was already closed",
+                )
+                .into())
+            }
+        }
+        self.check_size_limit(0, false)
+    }
+
+    /// Finish the file that is being written, which starting the next file or finishing the
+    /// archive does too.
+    ///
+    /// With [`ZipWriter::set_checkpoint_interval`], this writes the checkpoint that is due, which
+    /// the archive can be read from until the next entry is started.
+    pub fn finish_file(&mut self) -> ZipResult<()> {
+        self.finish_entry()?;
+        match self.checkpoint_interval {
+            Some(interval) if self.files.len() >= self.checkpointed + interval.max(1) => {
+                self.checkpoint()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn finish_entry(&mut self) -> ZipResult<()> {
+        let started = Instant::now();
+        if let Some(transform) = self.transform.take() {
+            transform.finish(&mut EntryWriter(self))?;
+        }
+        self.end_sample()?;
+        if let Some(mut encrypting) = self.encrypting.take() {
+            encrypting.writer.switch_to(
+                CompressionMethod::Stored,
+                &FileOptions::stored(),
+                None,
+                &self.buffers,
+            )?;
+            let mut data = encrypting.writer.unwrap();
+            data.seek(io::SeekFrom::Start(0))?;
+            let mut buffer = self.buffers.buffer();
+            let writer = self.inner.get_plain();
+            match encrypting.encrypt_with {
+                EncryptWith::ZipCrypto(keys) => {
+                    let file = self.files.last_mut().unwrap();
+                    file.crc32 = self.stats.data.current();
+                    let check_byte = file.zipcrypto_check_bytes()[0];
+                    zipcrypto::encrypt_entry(keys, check_byte, &mut data, &mut buffer, writer)?;
+                }
+                #[cfg(feature = "aes-crypto")]
+                EncryptWith::Aes { mode, password } => {
+                    let count = encrypting.aes_iteration_count;
+                    aes::encrypt_entry(mode, &password, count, &mut data, &mut buffer, writer)?;
+                }
+            }
+        }
+        self.inner.switch_to(
+            CompressionMethod::Stored,
+            &FileOptions::stored(),
+            None,
+            &self.buffers,
+        )?;
         let writer = self.inner.get_plain();
 
         if !self.writing_raw {
-            let file = match self.files.last_mut() {
+            let entries = self.files.len();
+            let file = match self.files[self.finished_files..].last_mut() {
                 None => return Ok(()),
                 Some(f) => f,
             };
-            file.crc32 = self.stats.hasher.clone().finalize();
-            file.uncompressed_size = self.stats.bytes_written;
+            file.crc32 = match file.aes_mode {
+                Some((_, AesVendorVersion::Ae2)) => 0,
+                _ => self.stats.data.current(),
+            };
+            file.uncompressed_size = self.stats.data.bytes_written();
 
             let file_end = writer.seek(io::SeekFrom::Current(0))?;
             file.compressed_size = file_end - self.stats.start;
+            if self.forbidding_zip64 && file.zip64_extension() {
+                return Err(zip64_required(entries, file));
+            }
+            observe(&self.observer, file, self.stats.elapsed + started.elapsed());
+
+            if file.using_data_descriptor {
+                write_data_descriptor(writer, file)?;
+            } else if !self.streaming {
+                // When streaming, only directories have no data descriptor, and their header is
+                // complete already
+                update_local_file_header(writer, file)?;
+                writer.seek(io::SeekFrom::Start(file_end))?;
+            }
+        } else if self.checking_stored {
+            self.checking_stored = false;
+            let file = self.files.last().unwrap();
+            if self.stats.data.bytes_written() != file.uncompressed_size
+                || self.stats.data.current() != file.crc32
+            {
+                self.writing_to_file = false;
+                self.writing_raw = false;
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "The written data does not match the size and CRC-32 of the stored file",
+                )
+                .into());
+            }
+        } else if self.checking_raw {
+            self.checking_raw = false;
+            let file = self.files.last().unwrap();
+            if self.stats.data.bytes_written() != file.compressed_size {
+                self.writing_to_file = false;
+                self.writing_raw = false;
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "The written data does not match the compressed size of the file",
+                )
+                .into());
+            }
+        }
+        if self.writing_raw {
+            if let Some(file) = self.files.last().filter(|f| f.using_data_descriptor) {
+                write_data_descriptor(self.inner.get_plain(), file)?;
+            }
+        }
+
+        self.finished_files = self.files.len();
+        // Only the file that is being written can be aborted
+        self.superseded.clear();
+        self.writing_to_file = false;
+        self.writing_raw = false;
+        self.check_size_limit(0, false)
+    }
 
-            update_local_file_header(writer, file)?;
-            writer.seek(io::SeekFrom::Start(file_end))?;
+    /// Remove the file that is being written from the archive, discarding what was written of it.
+    ///
+    /// The writer seeks back to the local header of the file, so the next entry is written over
+    /// it. The archive doesn't get shorter, so if the entries and central directory written after
+    /// that end before the aborted file did, the central directory is preceded by zeros. The
+    /// entries of an archive opened with [`ZipWriter::new_append`] that the file superseded are
+    /// kept again. This fails for archives that are written to a stream or split over disks, which
+    /// can't seek back, and with [`ZipError::FileNotFound`] if no file is being written, like
+    /// after [`ZipWriter::finish_file`] or when the next file was started.
+    pub fn abort_file(&mut self) -> ZipResult<()> {
+        if self.streaming || self.disk_layout.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Files can't be aborted when writing to a stream or to several disks",
+            )
+            .into());
+        }
+        let header_start = match self.files[self.appended_files..].last() {
+            Some(file) if self.writing_to_file => file.header_start,
+            _ => return Err(ZipError::FileNotFound),
+        };
+        if self.inner.is_closed() {
+            return Err(
+                io::Error::new(io::ErrorKind::BrokenPipe, "ZipWriter was already closed").into(),
+            );
         }
 
+        self.transform = None;
+        self.sample = None;
+        self.encrypting = None;
+        self.inner.switch_to(
+            CompressionMethod::Stored,
+            &FileOptions::stored(),
+            None,
+            &self.buffers,
+        )?;
+        let writer = self.inner.get_plain();
+        let end = writer.position();
+        writer.seek(io::SeekFrom::Start(header_start))?;
+        self.min_length = self.min_length.max(end);
+
+        let file = self.files.pop().unwrap();
+        let aborted = self.entry_ids.pop();
+        if self.name_options.reject_duplicates {
+            self.names.remove(file.file_name.as_str());
+        }
+        while self.superseded.last().map(|s| s.0) == aborted {
+            let (_, index, id, superseded) = self.superseded.pop().unwrap();
+            self.files.insert(index, superseded);
+            self.entry_ids.insert(index, id);
+            self.appended_files += 1;
+        }
+        // The file before the aborted one was finished when the aborted one was started
+        self.finished_files = self.files.len();
         self.writing_to_file = false;
         self.writing_raw = false;
+        self.checking_stored = false;
+        self.checking_raw = false;
         Ok(())
     }
 
     /// Create a file in the archive and start writing its' contents.
     ///
     /// The data should be written using the [`io::Write`] implementation on this [`ZipWriter`]
-    pub fn start_file<S>(&mut self, name: S, mut options: FileOptions) -> ZipResult<()>
+    pub fn start_file<S>(&mut self, name: S, options: FileOptions) -> ZipResult<()>
     where
         S: Into<String>,
     {
+        let mut options = self.defaults.apply(options, false);
         if options.permissions.is_none() {
             options.permissions = Some(0o644);
         }
         *options.permissions.as_mut().unwrap() |= 0o100000;
+        options.data_descriptor |= self.streaming;
         self.start_entry(name, options, None)?;
+        if let (Some(hook), Some(file)) = (self.write_hook.get(), self.files.last()) {
+            self.transform = hook.start_entry(&file.file_name);
+        }
         self.writing_to_file = true;
         Ok(())
     }
@@ -447,6 +2349,60 @@ impl<W: Write + io::Seek> ZipWriter<W> {
         self.start_file(path_to_string(path), options)
     }
 
+    /// Create a stored file whose size and CRC-32 are given by `options`, and start writing its
+    /// contents.
+    ///
+    /// The data is written to the output as it is, without going through a compressor or the
+    /// [`WriteHook`]. Finishing the file fails if the data does not match the size and CRC-32.
+    pub fn start_stored_file<S>(&mut self, name: S, options: StoredOptions) -> ZipResult<()>
+    where
+        S: Into<String>,
+    {
+        let mut file_options = self.defaults.apply(options.options, false);
+        *file_options.permissions.get_or_insert(0o644) |= 0o100000;
+        let raw_values = ZipRawValues {
+            crc32: options.crc32,
+            compressed_size: options.size,
+            uncompressed_size: options.size,
+        };
+        self.start_entry(name, file_options, Some(raw_values))?;
+        self.checking_stored = true;
+        self.writing_to_file = true;
+        Ok(())
+    }
+
+    /// Create a file from data that is compressed already, and start writing the compressed data.
+    ///
+    /// The data is written to the output as it is, without going through a compressor or the
+    /// [`WriteHook`], and must be compressed with the compression method of `options`, which for
+    /// [`CompressionMethod::Deflated`] is a raw deflate stream. `crc32` and `uncompressed_size`
+    /// describe the data once it is decompressed, and are not checked. Finishing the file fails if
+    /// the number of bytes written is not `compressed_size`. The file is not encrypted.
+    pub fn start_file_raw<S>(
+        &mut self,
+        name: S,
+        options: FileOptions,
+        crc32: u32,
+        compressed_size: u64,
+        uncompressed_size: u64,
+    ) -> ZipResult<()>
+    where
+        S: Into<String>,
+    {
+        let mut options = self.defaults.apply(options, false);
+        *options.permissions.get_or_insert(0o644) |= 0o100000;
+        options.encrypt_with = None;
+        let raw_values = ZipRawValues {
+            crc32,
+            compressed_size,
+            uncompressed_size,
+        };
+        self.start_entry(name, options, Some(raw_values))?;
+        self.checking_raw = true;
+        self.writing_to_file = true;
+        Ok(())
+    }
+
     /// Add a new file using the already compressed data from a ZIP file being read and renames it, this
     /// allows faster copies of the `ZipFile` since there is no need to decompress and compress it again.
     /// Any `ZipFile` metadata is copied and not checked, for example the file CRC.
@@ -477,12 +2433,16 @@ impl<W: Write + io::Seek> ZipWriter<W> {
     where
         S: Into<String>,
     {
-        let options = FileOptions::default()
+        if !file.has_raw_data() {
+            return Err(ZipError::UnsupportedArchive(
+                "Streamed files with a data descriptor cannot be copied raw",
+            ));
+        }
+        let mut options = FileOptions::default()
             .last_modified_time(file.last_modified())
             .compression_method(file.compression());
-        if let Some(perms) = file.unix_mode() {
-            options.unix_permissions(perms);
-        }
+        // The whole mode is kept, including the type of the file
+        options.permissions = file.unix_mode();
 
         let raw_values = ZipRawValues {
             crc32: file.crc32(),
@@ -526,18 +2486,114 @@ impl<W: Write + io::Seek> ZipWriter<W> {
         self.raw_copy_file_rename(file, name)
     }
 
+    /// Copy all entries of `src` to the archive in one pass, applying the edits of `plan`.
+    ///
+    /// Untouched and renamed entries are copied raw like with [`ZipWriter::raw_copy_file`], and
+    /// the files added by the plan are written after the copied entries. Returns
+    /// [`ZipError::FileNotFound`] before anything is written if the plan refers to an entry that
+    /// `src` doesn't contain.
+    pub fn rewrite<R: Read + io::Seek>(
+        &mut self,
+        src: &mut ZipArchive<R>,
+        mut plan: EditPlan,
+    ) -> ZipResult<()> {
+        let names: HashSet<&str> = src.file_names().collect();
+        let all_found = plan
+            .renames
+            .keys()
+            .chain(plan.removals.iter())
+            .chain(plan.replacements.keys())
+            .all(|name| names.contains(name.as_str()));
+        if !all_found {
+            return Err(ZipError::FileNotFound);
+        }
+
+        for i in 0..src.len() {
+            let file = src.by_index(i)?;
+            if plan.removals.contains(file.name()) {
+                continue;
+            }
+            let name = match plan.renames.get(file.name()) {
+                Some(new_name) => new_name.clone(),
+                None => file.name().to_owned(),
+            };
+            match plan.replacements.remove(file.name()) {
+                Some((mut reader, options)) => {
+                    drop(file);
+                    self.start_file(name, options)?;
+                    io::copy(&mut reader, self)?;
+                }
+                None => self.raw_copy_file_rename(file, name)?,
+            }
+        }
+        for (name, mut reader, options) in plan.additions {
+            self.start_file(name, options)?;
+            io::copy(&mut reader, self)?;
+        }
+        Ok(())
+    }
+
+    /// Rename the last entry named `name` to `new_name`, without writing its data again.
+    ///
+    /// Only the record of the entry in the central directory, which [`ZipWriter::finish`] writes,
+    /// gets the new name, which passes through the writer's [`NameOptions`] like the names of new
+    /// entries. The local header keeps the old name, which readers using the central directory
+    /// don't look at; [`ZipWriter::rename_with_local_header`] also renames the local header. With
+    /// [`ZipWriter::new_append`], the entries of an existing archive can be renamed.
+    ///
+    /// The file that is being written can't be renamed.
+    pub fn rename<S: Into<String>>(&mut self, name: &str, new_name: S) -> ZipResult<()> {
+        let (index, new_name) = self.check_rename(name, new_name.into())?;
+        self.apply_rename(index, new_name);
+        Ok(())
+    }
+
+    /// Find the entry to rename to `new_name`, and check the new name.
+    fn check_rename(&self, name: &str, new_name: String) -> ZipResult<(usize, String)> {
+        let index = self
+            .files
+            .iter()
+            .rposition(|file| file.file_name == name)
+            .ok_or(ZipError::FileNotFound)?;
+        if self.writing_to_file && index + 1 == self.files.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The file that is being written can't be renamed",
+            )
+            .into());
+        }
+        let new_name = self.name_options.check(new_name, &self.names)?;
+        Ok((index, new_name))
+    }
+
+    fn apply_rename(&mut self, index: usize, new_name: String) {
+        let file = &mut self.files[index];
+        if self.name_options.reject_duplicates {
+            self.names.remove(file.file_name.as_str());
+            self.names.insert(new_name.clone());
+        }
+        file.file_name = new_name.into();
+        // The name isn't decoded from the raw name anymore
+        file.file_name_raw = Vec::new();
+        file.name_cache = NameCache::default();
+        file.central_header = None;
+    }
+
     /// Add a directory entry.
     ///
     /// You can't write data to the file afterwards.
-    pub fn add_directory<S>(&mut self, name: S, mut options: FileOptions) -> ZipResult<()>
+    pub fn add_directory<S>(&mut self, name: S, options: FileOptions) -> ZipResult<()>
     where
         S: Into<String>,
     {
+        let mut options = self.defaults.apply(options, true);
         if options.permissions.is_none() {
             options.permissions = Some(0o755);
         }
         *options.permissions.as_mut().unwrap() |= 0o40000;
         options.compression_method = CompressionMethod::Stored;
+        options.encrypt_with = None;
+        options.data_descriptor = false;
 
         let name_as_string = name.into();
         // Append a slash to the filename if it does not end with it.
@@ -567,6 +2623,72 @@ impl<W: Write + io::Seek> ZipWriter<W> {
         self.add_directory(path_to_string(path), options)
     }
 
+    /// Recursively add the contents of a directory on the filesystem.
+    ///
+    /// Entries are named by their path relative to `path`, joined with `/`, and are added in
+    /// sorted order. Symbolic links are followed, except to a directory that is being added, which
+    /// fails with an error of the kind [`io::ErrorKind::InvalidInput`]. The names pass through the
+    /// writer's [`NameOptions`] like any other, so `NameOptions::default().normalize(true)` can be
+    /// used to rewrite backslashes in file names.
+    ///
+    /// If `options` has no permissions set, the permissions of the files on disk are used on Unix.
+    pub fn add_dir_all<P: AsRef<std::path::Path>>(
+        &mut self,
+        path: P,
+        options: FileOptions,
+    ) -> ZipResult<()> {
+        let path = path.as_ref();
+        self.add_dir_contents(path, "", options, &mut vec![path.canonicalize()?])
+    }
+
+    /// Add the contents of `dir`, whose canonical path and those of the directories it is in are
+    /// the last of `ancestors`
+    fn add_dir_contents(
+        &mut self,
+        dir: &std::path::Path,
+        prefix: &str,
+        options: FileOptions,
+        ancestors: &mut Vec<std::path::PathBuf>,
+    ) -> ZipResult<()> {
+        use std::fs;
+
+        let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let path = entry.path();
+            let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+            let metadata = fs::metadata(&path)?;
+
+            #[allow(unused_mut)]
+            let mut entry_options = options;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                if options.permissions.is_none() {
+                    entry_options = options.unix_permissions(metadata.permissions().mode());
+                }
+            }
+
+            if metadata.is_dir() {
+                let canonical = path.canonicalize()?;
+                if ancestors.contains(&canonical) {
+                    return Err(ZipError::Io(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("{} links to a directory it is in", path.display()),
+                    )));
+                }
+                self.add_directory(name.clone(), entry_options)?;
+                ancestors.push(canonical);
+                self.add_dir_contents(&path, &(name + "/"), options, ancestors)?;
+                ancestors.pop();
+            } else {
+                self.start_file(name, entry_options)?;
+                io::copy(&mut fs::File::open(&path)?, self)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Finish the last file and write all other zip-structures
     ///
     /// This will return the writer, but one should normally not append any data to the end of the file.
@@ -574,31 +2696,122 @@ impl<W: Write + io::Seek> ZipWriter<W> {
     pub fn finish(&mut self) -> ZipResult<W> {
         self.finalize()?;
         let inner = mem::replace(&mut self.inner, GenericZipWriter::Closed);
-        Ok(inner.unwrap())
+        Ok(inner.unwrap().inner)
     }
 
     fn finalize(&mut self) -> ZipResult<()> {
-        self.finish_file()?;
+        self.finish_entry()?;
+        self.write_central_directory()
+    }
 
-        {
-            let writer = self.inner.get_plain();
+    /// Write the central directory of the files so far, and seek back to where it starts, so
+    /// that the next entry or central directory is written over it.
+    fn checkpoint(&mut self) -> ZipResult<()> {
+        if self.streaming || self.disk_layout.is_some() {
+            return Ok(());
+        }
+        let start = self.inner.get_plain().position();
+        self.write_central_directory()?;
+        let writer = self.inner.get_plain();
+        writer.flush()?;
+        // The archive keeps its length, so that no part of the checkpoint is left after what is
+        // written over it
+        self.min_length = self.min_length.max(writer.position());
+        writer.seek(io::SeekFrom::Start(start))?;
+        self.checkpointed = self.files.len();
+        Ok(())
+    }
 
-            let central_start = writer.seek(io::SeekFrom::Current(0))?;
-            for file in self.files.iter() {
-                write_central_directory_header(writer, file)?;
+    /// Write the central directory and the end records from the current position.
+    fn write_central_directory(&mut self) -> ZipResult<()> {
+        let files = match &self.disk_layout {
+            Some(layout) => Cow::Owned(lock(layout).locate_files(&self.files)),
+            None => Cow::Borrowed(&self.files[..]),
+        };
+        let signature_record = signature_record(&files, self.signer.get())?;
+        let headers_size = central_headers_size(&files)? + signature_record.len() as u64;
+        let contents_signature = match self.contents_signer.get() {
+            Some(signer) => Some(signer.sign(&signature::canonical_contents(&self.files))?),
+            None => None,
+        };
+        let extensible_data = extensible_data(
+            &self.zip64_extensible_data,
+            contents_signature.as_deref(),
+            self.central_directory_checksum,
+        );
+        let position = self.inner.get_plain().stream_position()?;
+        // The archive must not get shorter than what was written before, which could leave an old
+        // end record after the new one, so the central directory starts after a gap then
+        let start_after_gap =
+            |size: u64| position + self.min_length.saturating_sub(position + size);
+        let needs_zip64 = |start: u64| {
+            self.files.len() >= spec::ZIP64_ENTRY_THR
+                || start >= spec::ZIP64_BYTES_THR
+                || headers_size >= spec::ZIP64_BYTES_THR
+                || !extensible_data.is_empty()
+        };
+        let mut central_directory_size = headers_size + 22 + self.comment.len() as u64;
+        let mut central_start = start_after_gap(central_directory_size);
+        if needs_zip64(central_start) {
+            // ZIP64 central directory end record and locator
+            central_directory_size += 56 + 20 + extensible_data.len() as u64;
+            // The shorter gap is only taken if the central directory still needs ZIP64 after it
+            let shorter_gap_start = start_after_gap(central_directory_size);
+            if needs_zip64(shorter_gap_start) {
+                central_start = shorter_gap_start;
             }
-            let central_size = writer.seek(io::SeekFrom::Current(0))? - central_start;
+        }
+        let end = central_start + central_directory_size;
+        let growth = self
+            .inner
+            .get_ref()
+            .map_or(0, |writer| writer.growth_to(end));
+        self.check_size_limit(growth, true)?;
 
-            let footer = spec::CentralDirectoryEnd {
-                disk_number: 0,
-                disk_with_central_directory: 0,
-                number_of_files_on_this_disk: self.files.len() as u16,
-                number_of_files: self.files.len() as u16,
-                central_directory_size: central_size as u32,
-                central_directory_offset: central_start as u32,
-                zip_file_comment: self.comment.as_bytes().to_vec(),
+        {
+            let writer = self.inner.get_plain();
+            io::copy(&mut io::repeat(0).take(central_start - position), writer)?;
+            let central_offset = match &self.disk_layout {
+                Some(layout) => lock(layout).keep_records_together(&files, central_start)?,
+                None => central_start,
             };
-
+            let (mut zip64_footer, mut footer) = central_directory_end(
+                self.files.len(),
+                central_offset,
+                headers_size,
+                &self.comment,
+                &extensible_data,
+            );
+            if zip64_footer.is_some() && self.forbid_zip64 {
+                return Err(ZipError::Zip64Required {
+                    entries: self.files.len(),
+                    bytes: central_start.max(headers_size),
+                });
+            }
+            let mut checksum_writer = Crc32Writer::new(&mut *writer);
+            for file in files.iter() {
+                write_central_directory_header(&mut checksum_writer, file)?;
+            }
+            if self.central_directory_checksum {
+                set_checksum(zip64_footer.as_mut(), checksum_writer.current());
+            }
+            writer.write_all(&signature_record)?;
+            let central_size = writer.stream_position()? - central_start;
+            let mut locator = zip64_locator(central_start + central_size);
+            if let Some(layout) = &self.disk_layout {
+                lock(layout).locate_directory_end(
+                    &files,
+                    central_start,
+                    central_size,
+                    zip64_footer.as_mut(),
+                    &mut footer,
+                    &mut locator,
+                )?;
+            }
+            if let Some(zip64_footer) = zip64_footer {
+                zip64_footer.write(writer)?;
+                locator.write(writer)?;
+            }
             footer.write(writer)?;
         }
 
@@ -606,6 +2819,239 @@ impl<W: Write + io::Seek> ZipWriter<W> {
     }
 }
 
+impl<A: Read + Write + io::Seek> ZipWriter<A> {
+    /// Initializes the archive by opening an existing archive, to which files are added.
+    ///
+    /// The new files are written from where the central directory of the archive starts, so the
+    /// files that are in the archive are not written again, and [`ZipWriter::finish`] writes the
+    /// central directory with all the files. The archive can't be read until then.
+    ///
+    /// The central directory records of the files in the archive are written again as they are,
+    /// so their comments and extra fields are kept, unless a file is renamed or the archive has
+    /// an offset. The comment of the archive and the blocks of its ZIP64 extensible data sector
+    /// are kept too, but not a contents signature, which doesn't match the new contents, and a
+    /// central directory checksum is computed again.
+    ///
+    /// The archive doesn't get shorter, so if the new central directory ends before the old one,
+    /// because entries are superseded or the comment is shortened, it is preceded by zeros.
+    ///
+    /// A new entry with the name of an entry of the archive supersedes it: the central directory
+    /// only refers to the new entry, and the data of the old one is left in the archive. With
+    /// [`NameOptions::reject_duplicates`], starting such an entry fails instead.
+    pub fn new_append(mut file: A) -> ZipResult<ZipWriter<A>> {
+        let (files, directory_start, comment, extensible_data) =
+            ZipArchive::new(&mut file)?.into_append_parts()?;
+        let length = file.seek(io::SeekFrom::End(0))?;
+        file.seek(io::SeekFrom::Start(directory_start))?;
+
+        let mut writer = ZipWriter::new(file);
+        if let GenericZipWriter::Storer(inner) = &mut writer.inner {
+            inner.start_at(directory_start);
+        }
+        writer.entry_ids = (0..files.len() as u64).map(EntryId).collect();
+        writer.next_entry_id = files.len() as u64;
+        writer.files = files;
+        writer.comment = comment;
+        if let Some(data) = extensible_data {
+            match ExtensibleDataBlock::parse_all(&data) {
+                Some(blocks) => {
+                    writer.central_directory_checksum = blocks.iter().any(|block| {
+                        block.header_id == ExtensibleDataBlock::CENTRAL_DIRECTORY_CHECKSUM
+                    });
+                    let kept: Vec<_> = blocks
+                        .into_iter()
+                        .filter(|block| {
+                            block.header_id != ExtensibleDataBlock::CENTRAL_DIRECTORY_CHECKSUM
+                                && block.header_id != ExtensibleDataBlock::CONTENTS_SIGNATURE
+                        })
+                        .collect();
+                    writer.zip64_extensible_data = ExtensibleDataBlock::encode_all(&kept);
+                }
+                None => writer.zip64_extensible_data = data,
+            }
+        }
+        writer.min_length = length;
+        writer.appended_files = writer.files.len();
+        writer.finished_files = writer.files.len();
+        Ok(writer)
+    }
+
+    /// Rename the last entry named `name` to `new_name` like [`ZipWriter::rename`], and also
+    /// rename its local header.
+    ///
+    /// The name in the local header is overwritten in place, so the new name must have as many
+    /// bytes as the name in the local header, or [`ZipError::InvalidFileName`] is returned and
+    /// nothing is renamed.
+    pub fn rename_with_local_header<S>(&mut self, name: &str, new_name: S) -> ZipResult<()>
+    where
+        S: Into<String>,
+    {
+        let (index, new_name) = self.check_rename(name, new_name.into())?;
+        let header_start = self.files[index].header_start;
+        let writer = match &mut self.inner {
+            GenericZipWriter::Storer(writer) => writer,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Local headers can't be renamed while a file is compressed",
+                )
+                .into())
+            }
+        };
+        let position = writer.position();
+
+        writer.seek(io::SeekFrom::Start(header_start))?;
+        let mut header = [0; 30];
+        writer.inner.read_exact(&mut header)?;
+        let field = |offset: usize| u16::from_le_bytes([header[offset], header[offset + 1]]);
+        let signature = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        if signature != spec::LOCAL_FILE_HEADER_SIGNATURE {
+            writer.seek(io::SeekFrom::Start(position))?;
+            return Err(ZipError::InvalidArchive("Invalid local file header"));
+        }
+        if field(26) as usize != new_name.len() {
+            writer.seek(io::SeekFrom::Start(position))?;
+            return Err(ZipError::InvalidFileName(
+                "The new name must have as many bytes as the name in the local header",
+            ));
+        }
+        // Bit 11 marks names encoded in UTF-8
+        let flags = if new_name.is_ascii() {
+            field(6) & !(1 << 11)
+        } else {
+            field(6) | 1 << 11
+        };
+        writer.seek(io::SeekFrom::Start(header_start + 6))?;
+        writer.write_u16::<LittleEndian>(flags)?;
+        writer.seek(io::SeekFrom::Start(header_start + 30))?;
+        writer.write_all(new_name.as_bytes())?;
+        writer.seek(io::SeekFrom::Start(position))?;
+
+        self.apply_rename(index, new_name);
+        Ok(())
+    }
+}
+
+impl<W: Write> ZipWriter<StreamWriter<W>> {
+    /// Initializes an archive that is written to an output that cannot seek.
+    ///
+    /// The sizes and CRC-32 of a file can't be written to its local header once its data is
+    /// known, so every file gets a data descriptor as if [`FileOptions::data_descriptor`] was set.
+    /// [`FileOptions::store_if_incompressible`] is ignored, as it would rewrite the header too.
+    /// [`ZipWriter::finish`] returns the [`StreamWriter`], which holds the output.
+    pub fn new_stream(inner: W) -> ZipWriter<StreamWriter<W>> {
+        let mut writer = ZipWriter::new(StreamWriter::new(inner));
+        writer.streaming = true;
+        writer
+    }
+}
+
+impl<W: Write, S: Read + Write + io::Seek> ZipWriter<SpoolWriter<W, S>> {
+    /// Initializes an archive that is written to an output that cannot seek, keeping the last
+    /// `capacity` bytes that were written in `spool`.
+    ///
+    /// Like with [`ZipWriter::new_stream`], every file gets a data descriptor, but the local
+    /// header of a file can still be rewritten while it is in the spool, so
+    /// [`FileOptions::store_if_incompressible`] is supported if the spool holds the longest local
+    /// header. The spool only needs a few KiB, so archives of any size can be written to network
+    /// storage without being staged in a local file first.
+    ///
+    /// The spool can be a `Cursor<Vec<u8>>` in memory, a file at any location, or a file created
+    /// by a [`TempStore`], for sandboxed or diskless environments.
+    ///
+    /// [`ZipWriter::finish`] returns the [`SpoolWriter`], whose [`SpoolWriter::finish`] writes
+    /// out the rest of the spool.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is 0.
+    pub fn new_spooled(inner: W, spool: S, capacity: u64) -> ZipWriter<SpoolWriter<W, S>> {
+        let mut writer = ZipWriter::new(SpoolWriter::new(inner, spool, capacity));
+        writer.streaming = true;
+        writer.spooled = true;
+        writer
+    }
+}
+
+/// A [`ZipWriter`] for archives that are split over several disks
+pub type SplitZipWriter<K> = ZipWriter<SplitWriter<K>>;
+
+impl<K: DiskSink> ZipWriter<SplitWriter<K>> {
+    /// Initializes an archive that is split over disks of at most `volume_size` bytes, which are
+    /// created with `sink`.
+    ///
+    /// A new disk is started before a local header or central directory record that doesn't fit
+    /// on the current one, so only the data of a file spans disks, and the records of the end of
+    /// the central directory are kept together on the last disk. The disk numbers and offsets in
+    /// the central directory are relative to the disks, so the archive can be read with
+    /// [`ZipArchive::new_multidisk`](crate::read::ZipArchive::new_multidisk).
+    ///
+    /// [`ZipWriter::finish`] returns the [`SplitWriter`], whose [`SplitWriter::finish`] hands the
+    /// disks to the sink.
+    ///
+    /// # Panics
+    ///
+    /// If `volume_size` is 0.
+    pub fn new_split(sink: K, volume_size: u64) -> SplitZipWriter<K> {
+        assert!(
+            volume_size > 0,
+            "The volume size of a split archive must not be 0"
+        );
+        let layout = Arc::new(Mutex::new(DiskLayout {
+            volume_size,
+            starts: vec![0],
+        }));
+        let mut writer = ZipWriter::new(SplitWriter::new(sink, layout.clone()));
+        writer.disk_layout = Some(layout);
+        writer
+    }
+}
+
+impl ZipWriter<io::Cursor<Vec<u8>>> {
+    /// Write an archive with the given entries to memory in one call, and return its bytes.
+    ///
+    /// Every entry is a name, its data and the options to store it with. Names ending with a `/`
+    /// are added as directories, whose data must be empty.
+    ///
+    /// ```
+    /// use zip::write::{FileOptions, ZipWriter};
+    ///
+    /// let options = FileOptions::default();
+    /// let bytes = ZipWriter::archive_from_entries(vec![
+    ///     ("config/", &b""[..], options),
+    ///     ("config/settings.toml", &b"verbose = true"[..], options),
+    /// ])?;
+    /// let archive = zip::ZipArchive::new(std::io::Cursor::new(bytes))?;
+    /// assert_eq!(archive.len(), 2);
+    /// # Ok::<(), zip::result::ZipError>(())
+    /// ```
+    pub fn archive_from_entries<I, S, D>(entries: I) -> ZipResult<Vec<u8>>
+    where
+        I: IntoIterator<Item = (S, D, FileOptions)>,
+        S: Into<String>,
+        D: AsRef<[u8]>,
+    {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        for (name, data, options) in entries {
+            let name = name.into();
+            let data = data.as_ref();
+            if name.ends_with('/') {
+                if !data.is_empty() {
+                    return Err(ZipError::Io(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "Directories cannot have data",
+                    )));
+                }
+                writer.add_directory(name, options)?;
+            } else {
+                writer.start_file(name, options)?;
+                writer.write_all(data)?;
+            }
+        }
+        Ok(writer.finish()?.into_inner())
+    }
+}
+
 #[cfg(feature = "async")]
 impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncZipWriter<Pin<Box<W>>> {
     /// Initializes the archive.
@@ -617,8 +3063,18 @@ impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncZipWriter<Pin<Box<W>>> {
             files: Vec::new(),
             stats: Default::default(),
             writing_to_file: false,
-            comment: String::new(),
+            comment: Vec::new(),
             writing_raw: false,
+            name_options: NameOptions::default(),
+            names: HashSet::new(),
+            forbid_zip64: false,
+            forbidding_zip64: false,
+            defaults: DefaultOptions::default(),
+            zip64_extensible_data: Vec::new(),
+            central_directory_checksum: false,
+            signer: Hook::default(),
+            contents_signer: Hook::default(),
+            observer: Hook::default(),
         }
     }
 
@@ -627,7 +3083,66 @@ impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncZipWriter<Pin<Box<W>>> {
     where
         S: Into<String>,
     {
-        self.comment = comment.into();
+        self.comment = comment.into().into_bytes();
+    }
+
+    /// Set the extensible data sector of the ZIP64 central directory end record.
+    ///
+    /// See [`ZipWriter::set_zip64_extensible_data`].
+    pub fn set_zip64_extensible_data(&mut self, data: Vec<u8>) {
+        self.zip64_extensible_data = data;
+    }
+
+    /// See [`ZipWriter::set_central_directory_checksum`].
+    pub fn set_central_directory_checksum(&mut self, checksum: bool) {
+        self.central_directory_checksum = checksum;
+    }
+
+    /// Set the checks applied to the names of entries added from now on.
+    pub fn set_name_options(&mut self, options: NameOptions) {
+        self.name_options = options;
+        self.names = if options.reject_duplicates {
+            self.files.iter().map(|f| f.file_name.to_string()).collect()
+        } else {
+            HashSet::new()
+        };
+    }
+
+    /// Set whether writing fails instead of using ZIP64 anywhere in the archive
+    ///
+    /// See [`ZipWriter::set_forbid_zip64`].
+    pub fn set_forbid_zip64(&mut self, forbid: bool) {
+        self.forbid_zip64 = forbid;
+    }
+
+    /// See [`ZipWriter::set_signer`].
+    pub fn set_signer<S: Signer + 'static>(&mut self, signer: S) {
+        self.signer.set(Arc::new(signer));
+    }
+
+    /// See [`ZipWriter::set_contents_signer`].
+    pub fn set_contents_signer<S: Signer + 'static>(&mut self, signer: S) {
+        self.contents_signer.set(Arc::new(signer));
+    }
+
+    /// See [`ZipWriter::set_observer`].
+    pub fn set_observer<O: WriteObserver + 'static>(&mut self, observer: O) {
+        self.observer.set(Arc::new(observer));
+    }
+
+    /// Set the defaults for the options of files and directories added from now on.
+    ///
+    /// See [`ZipWriter::set_default_options`].
+    pub fn set_default_options(&mut self, defaults: DefaultOptions) {
+        self.defaults = defaults;
+    }
+
+    /// See [`ZipWriter::set_options`].
+    pub fn set_options(&mut self, options: ArchiveOptions) {
+        self.set_name_options(options.names);
+        self.set_default_options(options.defaults);
+        self.set_forbid_zip64(options.forbid_zip64);
+        self.set_central_directory_checksum(options.central_directory_checksum);
     }
 
     /// Start a new file for with the requested options.
@@ -640,6 +3155,17 @@ impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncZipWriter<Pin<Box<W>>> {
     where
         S: Into<String>,
     {
+        if options.encrypt_with.is_some() {
+            return Err(ZipError::UnsupportedArchive(
+                "Encryption is not supported by the async writer",
+            ));
+        }
+        if options.deflate_dictionary.is_some() {
+            return Err(ZipError::UnsupportedArchive(
+                "Deflate dictionaries are not supported by the async writer",
+            ));
+        }
+        let name = self.name_options.check(name.into(), &self.names)?;
         self.finish_file().await?;
 
         let is_raw = raw_values.is_some();
@@ -654,9 +3180,11 @@ impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncZipWriter<Pin<Box<W>>> {
             let header_start = writer.seek(io::SeekFrom::Current(0)).await?;
 
             let permissions = options.permissions.unwrap_or(0o100644);
+            let system = options.system.unwrap_or(System::Unix);
             let mut file = ZipFileData {
-                system: System::Unix,
+                system,
                 version_made_by: DEFAULT_VERSION,
+                version_to_extract: 0,
                 encrypted: false,
                 compression_method: options.compression_method,
                 last_modified_time: options.last_modified_time,
@@ -667,51 +3195,89 @@ impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncZipWriter<Pin<Box<W>>> {
                 file_name_raw: Vec::new(), // Never used for saving
                 file_comment: String::new(),
                 header_start,
+                disk_number: 0,
                 data_start: 0,
                 central_header_start: 0,
-                external_attributes: permissions << 16,
+                external_attributes: external_attributes(system, permissions),
+                aes_mode: None,
+                strong_encryption: None,
+                deflate_dictionary: None,
+                large_file: options.large_file
+                    || raw_values.compressed_size >= spec::ZIP64_BYTES_THR
+                    || raw_values.uncompressed_size >= spec::ZIP64_BYTES_THR,
+                using_data_descriptor: options.data_descriptor,
+                name_cache: NameCache::default(),
+                central_header: None,
             };
+            let entries = self.files.len() + 1;
+            let forbid_zip64 = options.forbid_zip64 || self.forbid_zip64;
+            if (forbid_zip64 && file.zip64_extension())
+                || (self.forbid_zip64 && entries >= spec::ZIP64_ENTRY_THR)
+            {
+                return Err(zip64_required(entries, &file));
+            }
+            self.forbidding_zip64 = forbid_zip64;
             write_local_file_header_async(writer, &file).await?;
 
             let header_end = writer.seek(io::SeekFrom::Current(0)).await?;
             self.stats.start = header_end;
             file.data_start = header_end;
 
-            self.stats.bytes_written = 0;
-            self.stats.hasher = Hasher::new();
+            self.stats.data = Crc32Writer::new(io::sink());
+            self.stats.elapsed = Duration::default();
 
+            if self.name_options.reject_duplicates {
+                self.names.insert(file.file_name.to_string());
+            }
             self.files.push(file);
         }
 
         self.writing_raw = is_raw;
         self.inner
-            .switch_to(if is_raw {
-                CompressionMethod::Stored
-            } else {
-                options.compression_method
-            })
+            .switch_to(
+                if is_raw {
+                    CompressionMethod::Stored
+                } else {
+                    options.compression_method
+                },
+                &options,
+            )
             .await?;
 
         Ok(())
     }
 
     async fn finish_file(&mut self) -> ZipResult<()> {
-        self.inner.switch_to(CompressionMethod::Stored).await?;
+        let started = Instant::now();
+        self.inner
+            .switch_to(CompressionMethod::Stored, &FileOptions::stored())
+            .await?;
         let writer = self.inner.get_plain();
 
         if !self.writing_raw {
+            let entries = self.files.len();
             let file = match self.files.last_mut() {
                 None => return Ok(()),
                 Some(f) => f,
             };
-            file.crc32 = self.stats.hasher.clone().finalize();
-            file.uncompressed_size = self.stats.bytes_written;
+            file.crc32 = self.stats.data.current();
+            file.uncompressed_size = self.stats.data.bytes_written();
 
             let file_end = writer.seek(io::SeekFrom::Current(0)).await?;
             file.compressed_size = file_end - self.stats.start;
+            if self.forbidding_zip64 && file.zip64_extension() {
+                return Err(zip64_required(entries, file));
+            }
+            observe(&self.observer, file, self.stats.elapsed + started.elapsed());
 
-            update_local_file_header_async(writer, file).await?;
-            writer.seek(io::SeekFrom::Start(file_end)).await?;
+            if file.using_data_descriptor {
+                write_data_descriptor_async(writer, file).await?;
+            } else {
+                update_local_file_header_async(writer, file).await?;
+                writer.seek(io::SeekFrom::Start(file_end)).await?;
+            }
+        } else if let Some(file) = self.files.last().filter(|f| f.using_data_descriptor) {
+            write_data_descriptor_async(writer, file).await?;
         }
 
         self.writing_to_file = false;
@@ -722,10 +3288,11 @@ impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncZipWriter<Pin<Box<W>>> {
     /// Create a file in the archive and start writing its' contents.
     ///
     /// The data should be written using the [`io::Write`] implementation on this [`ZipWriter`]
-    pub async fn start_file<S>(&mut self, name: S, mut options: FileOptions) -> ZipResult<()>
+    pub async fn start_file<S>(&mut self, name: S, options: FileOptions) -> ZipResult<()>
     where
         S: Into<String>,
     {
+        let mut options = self.defaults.apply(options, false);
         if options.permissions.is_none() {
             options.permissions = Some(0o644);
         }
@@ -785,12 +3352,16 @@ impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncZipWriter<Pin<Box<W>>> {
     where
         S: Into<String>,
     {
-        let options = FileOptions::default()
+        if !file.has_raw_data() {
+            return Err(ZipError::UnsupportedArchive(
+                "Streamed files with a data descriptor cannot be copied raw",
+            ));
+        }
+        let mut options = FileOptions::default()
             .last_modified_time(file.last_modified())
             .compression_method(file.compression());
-        if let Some(perms) = file.unix_mode() {
-            options.unix_permissions(perms);
-        }
+        // The whole mode is kept, including the type of the file
+        options.permissions = file.unix_mode();
 
         let raw_values = ZipRawValues {
             crc32: file.crc32(),
@@ -837,15 +3408,18 @@ impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncZipWriter<Pin<Box<W>>> {
     /// Add a directory entry.
     ///
     /// You can't write data to the file afterwards.
-    pub async fn add_directory<S>(&mut self, name: S, mut options: FileOptions) -> ZipResult<()>
+    pub async fn add_directory<S>(&mut self, name: S, options: FileOptions) -> ZipResult<()>
     where
         S: Into<String>,
     {
+        let mut options = self.defaults.apply(options, true);
         if options.permissions.is_none() {
             options.permissions = Some(0o755);
         }
         *options.permissions.as_mut().unwrap() |= 0o40000;
         options.compression_method = CompressionMethod::Stored;
+        options.encrypt_with = None;
+        options.data_descriptor = false;
 
         let name_as_string = name.into();
         // Append a slash to the filename if it does not end with it.
@@ -887,28 +3461,59 @@ impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncZipWriter<Pin<Box<W>>> {
         Ok(inner.unwrap())
     }
 
+    /// Finish the archive like [`AsyncZipWriter::finish`], and return the writer itself rather than
+    /// a pinned box.
+    pub async fn finish_into_inner(self) -> ZipResult<W> {
+        let inner = self.finish().await?;
+        Ok(*Pin::into_inner(inner))
+    }
+
     async fn finalize(&mut self) -> ZipResult<()> {
         self.finish_file().await?;
+        let signature_record = signature_record(&self.files, self.signer.get())?;
+        let headers_size = central_headers_size(&self.files)? + signature_record.len() as u64;
+        let contents_signature = match self.contents_signer.get() {
+            Some(signer) => Some(signer.sign(&signature::canonical_contents(&self.files))?),
+            None => None,
+        };
+        let extensible_data = extensible_data(
+            &self.zip64_extensible_data,
+            contents_signature.as_deref(),
+            self.central_directory_checksum,
+        );
 
         {
             let writer = self.inner.get_plain();
 
             let central_start = writer.seek(io::SeekFrom::Current(0)).await?;
+            let (mut zip64_footer, footer) = central_directory_end(
+                self.files.len(),
+                central_start,
+                headers_size,
+                &self.comment,
+                &extensible_data,
+            );
+            if zip64_footer.is_some() && self.forbid_zip64 {
+                return Err(ZipError::Zip64Required {
+                    entries: self.files.len(),
+                    bytes: central_start.max(headers_size),
+                });
+            }
+            let mut checksum_writer = Crc32Writer::new(&mut *writer);
             for file in self.files.iter() {
-                write_central_directory_header_async(writer, file).await?;
+                write_central_directory_header_async(&mut checksum_writer, file).await?;
+            }
+            if self.central_directory_checksum {
+                set_checksum(zip64_footer.as_mut(), checksum_writer.current());
+            }
+            writer.write_all(&signature_record).await?;
+            let central_size = writer.seek(io::SeekFrom::Current(0)).await? - central_start;
+            if let Some(zip64_footer) = zip64_footer {
+                zip64_footer.write_async(writer).await?;
+                zip64_locator(central_start + central_size)
+                    .write_async(writer)
+                    .await?;
             }
-            let central_size = writer.seek(io::SeekFrom::Current(0)).await? - central_start;
-
-            let footer = spec::CentralDirectoryEnd {
-                disk_number: 0,
-                disk_with_central_directory: 0,
-                number_of_files_on_this_disk: self.files.len() as u16,
-                number_of_files: self.files.len() as u16,
-                central_directory_size: central_size as u32,
-                central_directory_offset: central_start as u32,
-                zip_file_comment: self.comment.as_bytes().to_vec(),
-            };
-
             footer.write_async(writer).await?;
         }
 
@@ -934,10 +3539,157 @@ impl<W: AsyncWrite + AsyncSeek + Unpin> PinnedDrop for AsyncZipWriter<W> {
     }
 }
 
+/// An archive that is generated while it is sent, like as the body of an HTTP response
+///
+/// The archive is written like with [`ZipWriter::new_stream`], so it never seeks and every file
+/// gets a data descriptor. The source of each file is only read once the stream gets to it, and
+/// the archive is produced in chunks, so archives of any size are sent without being held in
+/// memory. A chunk converts into a `bytes::Bytes` with `From`, for the body of a hyper or axum
+/// response.
+#[cfg(feature = "async")]
+pub struct ZipStream {
+    inner: Pin<Box<dyn Stream<Item = ZipResult<Vec<u8>>> + Send>>,
+}
+
+#[cfg(feature = "async")]
+impl ZipStream {
+    /// Generate an archive of the files of `entries`, each a name and the source of its contents,
+    /// which are all written with `options`.
+    pub fn new<E, S, R>(entries: E, options: FileOptions) -> ZipStream
+    where
+        E: Stream<Item = (S, R)> + Send + 'static,
+        S: Into<String> + 'static,
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        let buffer = ChunkBuffer::default();
+        let state = ZipStreamState {
+            entries: Box::pin(entries),
+            source: None,
+            writer: Some(ZipWriter::new_stream(buffer.clone())),
+            buffer,
+            read_buf: vec![0; 16384],
+            options,
+        };
+        ZipStream {
+            inner: Box::pin(futures::stream::try_unfold(
+                state,
+                ZipStreamState::next_chunk,
+            )),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Stream for ZipStream {
+    type Item = ZipResult<Vec<u8>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+#[cfg(feature = "async")]
+struct ZipStreamState<E, R> {
+    entries: Pin<Box<E>>,
+    /// The source of the file that is being written
+    source: Option<R>,
+    /// The writer, until the archive is finished
+    writer: Option<ZipWriter<StreamWriter<ChunkBuffer>>>,
+    buffer: ChunkBuffer,
+    read_buf: Vec<u8>,
+    options: FileOptions,
+}
+
+#[cfg(feature = "async")]
+impl<E, S, R> ZipStreamState<E, R>
+where
+    E: Stream<Item = (S, R)>,
+    S: Into<String>,
+    R: AsyncRead + Unpin,
+{
+    /// Write the archive until the next chunk of it is produced.
+    async fn next_chunk(mut self) -> ZipResult<Option<(Vec<u8>, Self)>> {
+        loop {
+            let writer = match self.writer.as_mut() {
+                Some(writer) => writer,
+                None => return Ok(None),
+            };
+            if let Some(source) = self.source.as_mut() {
+                let count = source.read(&mut self.read_buf).await?;
+                if count == 0 {
+                    self.source = None;
+                } else {
+                    writer.write_all(&self.read_buf[..count])?;
+                }
+            } else if let Some((name, source)) = self.entries.next().await {
+                writer.start_file(name, self.options)?;
+                self.source = Some(source);
+            } else {
+                writer.finish()?;
+                self.writer = None;
+            }
+
+            let chunk = self.buffer.take();
+            if !chunk.is_empty() {
+                return Ok(Some((chunk, self)));
+            }
+        }
+    }
+}
+
+/// The output of a [`ZipStream`], from which the chunk that was written is taken
+#[cfg(feature = "async")]
+#[derive(Clone, Default)]
+struct ChunkBuffer(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(feature = "async")]
+impl ChunkBuffer {
+    fn take(&self) -> Vec<u8> {
+        mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+#[cfg(feature = "async")]
+impl Write for ChunkBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 impl<W: Write + io::Seek> GenericZipWriter<W> {
-    fn switch_to(&mut self, compression: CompressionMethod) -> ZipResult<()> {
+    fn switch_to(
+        &mut self,
+        compression: CompressionMethod,
+        #[cfg_attr(
+            not(any(
+                feature = "deflate",
+                feature = "deflate-miniz",
+                feature = "deflate-zlib"
+            )),
+            allow(unused_variables)
+        )]
+        options: &FileOptions,
+        compressor: Option<&dyn Compressor>,
+        #[cfg_attr(
+            not(any(
+                feature = "deflate",
+                feature = "deflate-miniz",
+                feature = "deflate-zlib"
+            )),
+            allow(unused_variables)
+        )]
+        buffers: &BufferPool,
+    ) -> ZipResult<()> {
+        let is_custom = matches!(self, GenericZipWriter::Custom(..));
         match self.current_compression() {
-            Some(method) if method == compression => return Ok(()),
+            Some(method) if method == compression && compressor.is_none() && !is_custom => {
+                return Ok(())
+            }
             None => {
                 return Err(io::Error::new(
                     io::ErrorKind::BrokenPipe,
@@ -958,6 +3710,7 @@ impl<W: Write + io::Seek> GenericZipWriter<W> {
             GenericZipWriter::Deflater(w) => w.finish()?,
             #[cfg(feature = "bzip2")]
             GenericZipWriter::Bzip2(w) => w.finish()?,
+            GenericZipWriter::Custom(w) => w.finish()?,
             GenericZipWriter::Closed => {
                 return Err(io::Error::new(
                     io::ErrorKind::BrokenPipe,
@@ -967,6 +3720,11 @@ impl<W: Write + io::Seek> GenericZipWriter<W> {
             }
         };
 
+        if let Some(compressor) = compressor {
+            *self = GenericZipWriter::Custom(CustomEncoder::new(bare, compressor, compression));
+            return Ok(());
+        }
+
         *self = {
             #[allow(deprecated)]
             match compression {
@@ -976,10 +3734,28 @@ impl<W: Write + io::Seek> GenericZipWriter<W> {
                     feature = "deflate-miniz",
                     feature = "deflate-zlib"
                 ))]
-                CompressionMethod::Deflated => GenericZipWriter::Deflater(DeflateEncoder::new(
+                #[cfg(feature = "parallel")]
+                CompressionMethod::Deflated
+                    if options.deflate_threads > 1
+                        && options.deflate_strategy == DeflateStrategy::Default =>
+                {
+                    GenericZipWriter::Deflater(deflate::parallel_encoder(
+                        bare,
+                        options.deflate_level,
+                        options.deflate_threads,
+                    ))
+                }
+                #[cfg(any(
+                    feature = "deflate",
+                    feature = "deflate-miniz",
+                    feature = "deflate-zlib"
+                ))]
+                CompressionMethod::Deflated => GenericZipWriter::Deflater(deflate::encoder(
                     bare,
-                    flate2::Compression::default(),
-                )),
+                    options.deflate_level,
+                    options.deflate_strategy,
+                    buffers,
+                )?),
                 #[cfg(feature = "bzip2")]
                 CompressionMethod::Bzip2 => {
                     GenericZipWriter::Bzip2(BzEncoder::new(bare, bzip2::Compression::Default))
@@ -1004,6 +3780,7 @@ impl<W: Write + io::Seek> GenericZipWriter<W> {
             GenericZipWriter::Deflater(ref mut w) => Some(w as &mut dyn Write),
             #[cfg(feature = "bzip2")]
             GenericZipWriter::Bzip2(ref mut w) => Some(w as &mut dyn Write),
+            GenericZipWriter::Custom(ref mut w) => Some(w as &mut dyn Write),
             GenericZipWriter::Closed => None,
         }
     }
@@ -1015,6 +3792,22 @@ impl<W: Write + io::Seek> GenericZipWriter<W> {
         }
     }
 
+    fn get_ref(&self) -> Option<&W> {
+        match *self {
+            GenericZipWriter::Storer(ref w) => Some(w),
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-miniz",
+                feature = "deflate-zlib"
+            ))]
+            GenericZipWriter::Deflater(ref w) => Some(w.get_ref()),
+            #[cfg(feature = "bzip2")]
+            GenericZipWriter::Bzip2(ref w) => Some(w.get_ref()),
+            GenericZipWriter::Custom(ref w) => Some(w.get_ref()),
+            GenericZipWriter::Closed => None,
+        }
+    }
+
     fn get_plain(&mut self) -> &mut W {
         match *self {
             GenericZipWriter::Storer(ref mut w) => w,
@@ -1033,6 +3826,7 @@ impl<W: Write + io::Seek> GenericZipWriter<W> {
             GenericZipWriter::Deflater(..) => Some(CompressionMethod::Deflated),
             #[cfg(feature = "bzip2")]
             GenericZipWriter::Bzip2(..) => Some(CompressionMethod::Bzip2),
+            GenericZipWriter::Custom(ref w) => Some(w.method()),
             GenericZipWriter::Closed => None,
         }
     }
@@ -1047,7 +3841,19 @@ impl<W: Write + io::Seek> GenericZipWriter<W> {
 
 #[cfg(feature = "async")]
 impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncGenericZipWriter<W> {
-    async fn switch_to(&mut self, compression: CompressionMethod) -> ZipResult<()> {
+    async fn switch_to(
+        &mut self,
+        compression: CompressionMethod,
+        #[cfg_attr(
+            not(any(
+                feature = "deflate",
+                feature = "deflate-miniz",
+                feature = "deflate-zlib"
+            )),
+            allow(unused_variables)
+        )]
+        options: &FileOptions,
+    ) -> ZipResult<()> {
         match self.current_compression() {
             Some(method) if method == compression => return Ok(()),
             None => {
@@ -1068,13 +3874,13 @@ impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncGenericZipWriter<W> {
                 feature = "deflate-zlib"
             ))]
             Self::Deflater(mut w) => {
-                w.flush().await?;
-                w.into_inner()
+                w.close().await?;
+                w.into_inner().0
             }
             #[cfg(feature = "bzip2")]
             Self::Bzip2(mut w) => {
-                w.flush().await?;
-                w.into_inner()
+                w.close().await?;
+                w.into_inner().0
             }
             Self::Closed => {
                 return Err(io::Error::new(
@@ -1094,9 +3900,21 @@ impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncGenericZipWriter<W> {
                     feature = "deflate-miniz",
                     feature = "deflate-zlib"
                 ))]
-                CompressionMethod::Deflated => Self::Deflater(AsyncDeflateEncoder::new(bare)),
+                CompressionMethod::Deflated => {
+                    if options.deflate_strategy != DeflateStrategy::Default {
+                        return Err(ZipError::UnsupportedArchive(
+                            "Deflate strategies are not supported by the async writer",
+                        ));
+                    }
+                    let level = options
+                        .deflate_level
+                        .map_or(async_compression::Level::Default, |level| {
+                            async_compression::Level::Precise(level.min(deflate::MAX_LEVEL))
+                        });
+                    Self::Deflater(AsyncDeflateEncoder::with_quality(KeepOpen(bare), level))
+                }
                 #[cfg(feature = "bzip2")]
-                CompressionMethod::Bzip2 => Self::Bzip2(AsyncBzEncoder::new(bare)),
+                CompressionMethod::Bzip2 => Self::Bzip2(AsyncBzEncoder::new(KeepOpen(bare))),
                 CompressionMethod::Unsupported(..) => {
                     return Err(ZipError::UnsupportedArchive("Unsupported compression"))
                 }
@@ -1168,24 +3986,28 @@ fn write_local_file_header<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipR
         1u16 << 11
     } else {
         0
-    };
+    } | if file.encrypted { 1u16 << 0 } else { 0 }
+        | if file.using_data_descriptor {
+            1u16 << 3
+        } else {
+            0
+        };
     writer.write_u16::<LittleEndian>(flag)?;
     // Compression method
-    #[allow(deprecated)]
-    writer.write_u16::<LittleEndian>(file.compression_method.to_u16())?;
+    writer.write_u16::<LittleEndian>(file.header_compression_method())?;
     // last mod file time and last mod file date
     writer.write_u16::<LittleEndian>(file.last_modified_time.timepart())?;
     writer.write_u16::<LittleEndian>(file.last_modified_time.datepart())?;
     // crc-32
-    writer.write_u32::<LittleEndian>(file.crc32)?;
-    // compressed size
-    writer.write_u32::<LittleEndian>(file.compressed_size as u32)?;
-    // uncompressed size
-    writer.write_u32::<LittleEndian>(file.uncompressed_size as u32)?;
+    writer.write_u32::<LittleEndian>(local_crc32(file))?;
+    // compressed size and uncompressed size, which are in the ZIP64 extra field of large files
+    let (compressed_size, uncompressed_size) = local_sizes(file);
+    writer.write_u32::<LittleEndian>(compressed_size)?;
+    writer.write_u32::<LittleEndian>(uncompressed_size)?;
     // file name length
     writer.write_u16::<LittleEndian>(file.file_name.as_bytes().len() as u16)?;
     // extra field length
-    let extra_field = build_extra_field(file)?;
+    let extra_field = build_extra_field(file, true)?;
     writer.write_u16::<LittleEndian>(extra_field.len() as u16)?;
     // file name
     writer.write_all(file.file_name.as_bytes())?;
@@ -1200,45 +4022,37 @@ async fn write_local_file_header_async<T: AsyncWrite + Unpin>(
     writer: &mut T,
     file: &ZipFileData,
 ) -> ZipResult<()> {
-    let mut writer = Compat(writer);
     // local file header signature
-    writer
-        .write_u32_le(spec::LOCAL_FILE_HEADER_SIGNATURE)
-        .await?;
+    write_u32_le(writer, spec::LOCAL_FILE_HEADER_SIGNATURE).await?;
     // version needed to extract
-    writer.write_u16_le(file.version_needed()).await?;
+    write_u16_le(writer, file.version_needed()).await?;
     // general purpose bit flag
     let flag = if !file.file_name.is_ascii() {
         1u16 << 11
     } else {
         0
+    } | if file.using_data_descriptor {
+        1u16 << 3
+    } else {
+        0
     };
-    writer.write_u16_le(flag).await?;
+    write_u16_le(writer, flag).await?;
     // Compression method
-    #[allow(deprecated)]
-    writer
-        .write_u16_le(file.compression_method.to_u16())
-        .await?;
+    write_u16_le(writer, file.header_compression_method()).await?;
     // last mod file time and last mod file date
-    writer
-        .write_u16_le(file.last_modified_time.timepart())
-        .await?;
-    writer
-        .write_u16_le(file.last_modified_time.datepart())
-        .await?;
+    write_u16_le(writer, file.last_modified_time.timepart()).await?;
+    write_u16_le(writer, file.last_modified_time.datepart()).await?;
     // crc-32
-    writer.write_u32_le(file.crc32).await?;
-    // compressed size
-    writer.write_u32_le(file.compressed_size as u32).await?;
-    // uncompressed size
-    writer.write_u32_le(file.uncompressed_size as u32).await?;
+    write_u32_le(writer, local_crc32(file)).await?;
+    // compressed size and uncompressed size, which are in the ZIP64 extra field of large files
+    let (compressed_size, uncompressed_size) = local_sizes(file);
+    write_u32_le(writer, compressed_size).await?;
+    write_u32_le(writer, uncompressed_size).await?;
     // file name length
-    writer
-        .write_u16_le(file.file_name.as_bytes().len() as u16)
-        .await?;
+    write_u16_le(writer, file.file_name.len() as u16).await?;
     // extra field length
-    let extra_field = build_extra_field(file)?;
-    writer.write_u16_le(extra_field.len() as u16).await?;
+    let extra_field = build_extra_field(file, true)?;
+    write_u16_le(writer, extra_field.len() as u16).await?;
     // file name
     writer.write_all(file.file_name.as_bytes()).await?;
     // extra field
@@ -1247,15 +4061,49 @@ async fn write_local_file_header_async<T: AsyncWrite + Unpin>(
     Ok(())
 }
 
+fn update_local_file_method<T: Write + io::Seek>(
+    writer: &mut T,
+    file: &ZipFileData,
+) -> ZipResult<()> {
+    const VERSION_OFFSET: u64 = 4;
+    const METHOD_OFFSET: u64 = 8;
+    writer.seek(io::SeekFrom::Start(file.header_start + VERSION_OFFSET))?;
+    writer.write_u16::<LittleEndian>(file.version_needed())?;
+    writer.seek(io::SeekFrom::Start(file.header_start + METHOD_OFFSET))?;
+    writer.write_u16::<LittleEndian>(file.header_compression_method())?;
+    if file.aes_mode.is_some() {
+        // The actual method is in the AES extra field, which follows the ZIP64 extra field
+        const AES_METHOD_OFFSET: u64 = 30 + 9;
+        let name_length = file.file_name.len() as u64;
+        writer.seek(io::SeekFrom::Start(
+            file.header_start + AES_METHOD_OFFSET + name_length + local_zip64_field_size(file),
+        ))?;
+        #[allow(deprecated)]
+        writer.write_u16::<LittleEndian>(file.compression_method.to_u16())?;
+    }
+    Ok(())
+}
+
 fn update_local_file_header<T: Write + io::Seek>(
     writer: &mut T,
     file: &ZipFileData,
 ) -> ZipResult<()> {
     const CRC32_OFFSET: u64 = 14;
+    check_local_sizes(file)?;
     writer.seek(io::SeekFrom::Start(file.header_start + CRC32_OFFSET))?;
     writer.write_u32::<LittleEndian>(file.crc32)?;
-    writer.write_u32::<LittleEndian>(file.compressed_size as u32)?;
-    writer.write_u32::<LittleEndian>(file.uncompressed_size as u32)?;
+    if file.large_file {
+        // The sizes are in the ZIP64 extra field
+        let name_length = file.file_name.len() as u64;
+        writer.seek(io::SeekFrom::Start(
+            file.header_start + 30 + name_length + 4,
+        ))?;
+        writer.write_u64::<LittleEndian>(file.uncompressed_size)?;
+        writer.write_u64::<LittleEndian>(file.compressed_size)?;
+    } else {
+        writer.write_u32::<LittleEndian>(file.compressed_size as u32)?;
+        writer.write_u32::<LittleEndian>(file.uncompressed_size as u32)?;
+    }
     Ok(())
 }
 
@@ -1264,19 +4112,34 @@ async fn update_local_file_header_async<T: AsyncWrite + AsyncSeek + Unpin>(
     writer: &mut T,
     file: &ZipFileData,
 ) -> ZipResult<()> {
-    let mut writer = Compat(writer);
     const CRC32_OFFSET: u64 = 14;
+    check_local_sizes(file)?;
     writer
-        .0
         .seek(io::SeekFrom::Start(file.header_start + CRC32_OFFSET))
         .await?;
-    writer.write_u32_le(file.crc32).await?;
-    writer.write_u32_le(file.compressed_size as u32).await?;
-    writer.write_u32_le(file.uncompressed_size as u32).await?;
+    write_u32_le(writer, file.crc32).await?;
+    if file.large_file {
+        // The sizes are in the ZIP64 extra field
+        let name_length = file.file_name.len() as u64;
+        writer
+            .seek(io::SeekFrom::Start(
+                file.header_start + 30 + name_length + 4,
+            ))
+            .await?;
+        write_u64_le(writer, file.uncompressed_size).await?;
+        write_u64_le(writer, file.compressed_size).await?;
+    } else {
+        write_u32_le(writer, file.compressed_size as u32).await?;
+        write_u32_le(writer, file.uncompressed_size as u32).await?;
+    }
     Ok(())
 }
 
 fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipResult<()> {
+    if let Some(record) = &file.central_header {
+        writer.write_all(record)?;
+        return Ok(());
+    }
     // central file header signature
     writer.write_u32::<LittleEndian>(spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE)?;
     // version made by
@@ -1289,35 +4152,39 @@ fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData)
         1u16 << 11
     } else {
         0
-    };
+    } | if file.encrypted { 1u16 << 0 } else { 0 }
+        | if file.using_data_descriptor {
+            1u16 << 3
+        } else {
+            0
+        };
     writer.write_u16::<LittleEndian>(flag)?;
     // compression method
-    #[allow(deprecated)]
-    writer.write_u16::<LittleEndian>(file.compression_method.to_u16())?;
+    writer.write_u16::<LittleEndian>(file.header_compression_method())?;
     // last mod file time + date
     writer.write_u16::<LittleEndian>(file.last_modified_time.timepart())?;
     writer.write_u16::<LittleEndian>(file.last_modified_time.datepart())?;
     // crc-32
     writer.write_u32::<LittleEndian>(file.crc32)?;
     // compressed size
-    writer.write_u32::<LittleEndian>(file.compressed_size as u32)?;
+    writer.write_u32::<LittleEndian>(file.compressed_size.min(spec::ZIP64_BYTES_THR) as u32)?;
     // uncompressed size
-    writer.write_u32::<LittleEndian>(file.uncompressed_size as u32)?;
+    writer.write_u32::<LittleEndian>(file.uncompressed_size.min(spec::ZIP64_BYTES_THR) as u32)?;
     // file name length
     writer.write_u16::<LittleEndian>(file.file_name.as_bytes().len() as u16)?;
     // extra field length
-    let extra_field = build_extra_field(file)?;
+    let extra_field = build_extra_field(file, false)?;
     writer.write_u16::<LittleEndian>(extra_field.len() as u16)?;
     // file comment length
     writer.write_u16::<LittleEndian>(0)?;
     // disk number start
-    writer.write_u16::<LittleEndian>(0)?;
+    writer.write_u16::<LittleEndian>(file.disk_number as u16)?;
     // internal file attribytes
     writer.write_u16::<LittleEndian>(0)?;
     // external file attributes
     writer.write_u32::<LittleEndian>(file.external_attributes)?;
     // relative offset of local header
-    writer.write_u32::<LittleEndian>(file.header_start as u32)?;
+    writer.write_u32::<LittleEndian>(file.header_start.min(spec::ZIP64_BYTES_THR) as u32)?;
     // file name
     writer.write_all(file.file_name.as_bytes())?;
     // extra field
@@ -1333,58 +4200,58 @@ async fn write_central_directory_header_async<T: AsyncWrite + Unpin>(
     writer: &mut T,
     file: &ZipFileData,
 ) -> ZipResult<()> {
-    let mut writer = Compat(writer);
     // central file header signature
-    writer
-        .write_u32_le(spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE)
-        .await?;
+    write_u32_le(writer, spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE).await?;
     // version made by
     let version_made_by = (file.system as u16) << 8 | (file.version_made_by as u16);
-    writer.write_u16_le(version_made_by).await?;
+    write_u16_le(writer, version_made_by).await?;
     // version needed to extract
-    writer.write_u16_le(file.version_needed()).await?;
+    write_u16_le(writer, file.version_needed()).await?;
     // general puprose bit flag
     let flag = if !file.file_name.is_ascii() {
         1u16 << 11
     } else {
         0
+    } | if file.using_data_descriptor {
+        1u16 << 3
+    } else {
+        0
     };
-    writer.write_u16_le(flag).await?;
+    write_u16_le(writer, flag).await?;
     // compression method
-    #[allow(deprecated)]
-    writer
-        .write_u16_le(file.compression_method.to_u16())
-        .await?;
+    write_u16_le(writer, file.header_compression_method()).await?;
     // last mod file time + date
-    writer
-        .write_u16_le(file.last_modified_time.timepart())
-        .await?;
-    writer
-        .write_u16_le(file.last_modified_time.datepart())
-        .await?;
+    write_u16_le(writer, file.last_modified_time.timepart()).await?;
+    write_u16_le(writer, file.last_modified_time.datepart()).await?;
     // crc-32
-    writer.write_u32_le(file.crc32).await?;
+    write_u32_le(writer, file.crc32).await?;
     // compressed size
-    writer.write_u32_le(file.compressed_size as u32).await?;
+    write_u32_le(
+        writer,
+        file.compressed_size.min(spec::ZIP64_BYTES_THR) as u32,
+    )
+    .await?;
     // uncompressed size
-    writer.write_u32_le(file.uncompressed_size as u32).await?;
+    write_u32_le(
+        writer,
+        file.uncompressed_size.min(spec::ZIP64_BYTES_THR) as u32,
+    )
+    .await?;
     // file name length
-    writer
-        .write_u16_le(file.file_name.as_bytes().len() as u16)
-        .await?;
+    write_u16_le(writer, file.file_name.len() as u16).await?;
     // extra field length
-    let extra_field = build_extra_field(file)?;
-    writer.write_u16_le(extra_field.len() as u16).await?;
+    let extra_field = build_extra_field(file, false)?;
+    write_u16_le(writer, extra_field.len() as u16).await?;
     // file comment length
-    writer.write_u16_le(0).await?;
+    write_u16_le(writer, 0).await?;
     // disk number start
-    writer.write_u16_le(0).await?;
+    write_u16_le(writer, file.disk_number as u16).await?;
     // internal file attribytes
-    writer.write_u16_le(0).await?;
+    write_u16_le(writer, 0).await?;
     // external file attributes
-    writer.write_u32_le(file.external_attributes).await?;
+    write_u32_le(writer, file.external_attributes).await?;
     // relative offset of local header
-    writer.write_u32_le(file.header_start as u32).await?;
+    write_u32_le(writer, file.header_start.min(spec::ZIP64_BYTES_THR) as u32).await?;
     // file name
     writer.write_all(file.file_name.as_bytes()).await?;
     // extra field
@@ -1395,9 +4262,307 @@ async fn write_central_directory_header_async<T: AsyncWrite + Unpin>(
     Ok(())
 }
 
-fn build_extra_field(_file: &ZipFileData) -> ZipResult<Vec<u8>> {
-    let writer = Vec::new();
-    // Future work
+/// The external attributes of a file with the Unix `mode`, for `system`
+fn external_attributes(system: System, mode: u32) -> u32 {
+    match system {
+        System::Dos | System::Ntfs => {
+            const DIRECTORY: u32 = 0x10;
+            const READ_ONLY: u32 = 0x01;
+            // Set on files that have not been backed up, which is what Windows does for new files
+            const ARCHIVE: u32 = 0x20;
+            let mut attributes = if mode & 0o170000 == 0o040000 {
+                DIRECTORY
+            } else {
+                ARCHIVE
+            };
+            if mode & 0o200 == 0 {
+                attributes |= READ_ONLY;
+            }
+            attributes
+        }
+        _ => mode << 16,
+    }
+}
+
+/// The size of the central directory headers of `files`
+fn central_headers_size(files: &[ZipFileData]) -> ZipResult<u64> {
+    let mut size = 0;
+    for file in files {
+        size += central_header_size(file)?;
+    }
+    Ok(size)
+}
+
+/// The size of the central directory header of `file`
+fn central_header_size(file: &ZipFileData) -> ZipResult<u64> {
+    if let Some(record) = &file.central_header {
+        return Ok(record.len() as u64);
+    }
+    let extra_field = build_extra_field(file, false)?;
+    Ok(46 + file.file_name.len() as u64 + extra_field.len() as u64)
+}
+
+/// Tell `observer` how `file` was written, unless it is a directory.
+fn observe(observer: &Hook<dyn WriteObserver>, file: &ZipFileData, elapsed: Duration) {
+    if let Some(observer) = observer.get().filter(|_| !file.file_name.ends_with('/')) {
+        observer.entry_written(&EntryStats {
+            name: file.file_name.to_string(),
+            compression: file.compression_method,
+            size: file.uncompressed_size,
+            compressed_size: file.compressed_size,
+            elapsed,
+        });
+    }
+}
+
+/// The error for `file`, which needs ZIP64 although it is forbidden
+fn zip64_required(entries: usize, file: &ZipFileData) -> ZipError {
+    ZipError::Zip64Required {
+        entries,
+        bytes: file
+            .header_start
+            .max(file.compressed_size)
+            .max(file.uncompressed_size),
+    }
+}
+
+/// The digital signature record of the central directory with the headers of `files`, or nothing
+/// if there is no `signer`
+fn signature_record(files: &[ZipFileData], signer: Option<&dyn Signer>) -> ZipResult<Vec<u8>> {
+    let signer = match signer {
+        Some(signer) => signer,
+        None => return Ok(Vec::new()),
+    };
+    let mut directory = Vec::new();
+    for file in files {
+        write_central_directory_header(&mut directory, file)?;
+    }
+    let signature = signer.sign(&directory)?;
+    if signature.len() > u16::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The digital signature is longer than 65535 bytes",
+        )
+        .into());
+    }
+    let mut record = Vec::with_capacity(6 + signature.len());
+    record.extend_from_slice(&spec::DIGITAL_SIGNATURE_SIGNATURE.to_le_bytes());
+    record.extend_from_slice(&(signature.len() as u16).to_le_bytes());
+    record.extend_from_slice(&signature);
+    Ok(record)
+}
+
+/// The extensible data sector of an archive, with the `contents_signature` if there is one and a
+/// placeholder for the central directory checksum if `checksum` is set
+fn extensible_data(data: &[u8], contents_signature: Option<&[u8]>, checksum: bool) -> Vec<u8> {
+    let mut sector = data.to_vec();
+    if let Some(signature) = contents_signature {
+        sector.extend_from_slice(&ExtensibleDataBlock::encode_all(&[ExtensibleDataBlock {
+            header_id: ExtensibleDataBlock::CONTENTS_SIGNATURE,
+            data: signature.to_vec(),
+        }]));
+    }
+    if checksum {
+        sector.extend_from_slice(&ExtensibleDataBlock::encode_all(&[ExtensibleDataBlock {
+            header_id: ExtensibleDataBlock::CENTRAL_DIRECTORY_CHECKSUM,
+            data: vec![0; 4],
+        }]));
+    }
+    sector
+}
+
+/// Replace the placeholder of [`extensible_data`] in `zip64_footer` with `checksum`
+fn set_checksum(zip64_footer: Option<&mut spec::Zip64CentralDirectoryEnd>, checksum: u32) {
+    if let Some(zip64_footer) = zip64_footer {
+        let sector = &mut zip64_footer.extensible_data_sector;
+        let length = sector.len();
+        sector[length - 4..].copy_from_slice(&checksum.to_le_bytes());
+    }
+}
+
+/// The records at the end of the central directory, which include a ZIP64 record if the number of
+/// files or the size or offset of the central directory don't fit in the normal record, or if
+/// there is extensible data for the ZIP64 record
+fn central_directory_end(
+    number_of_files: usize,
+    central_start: u64,
+    central_size: u64,
+    comment: &[u8],
+    extensible_data: &[u8],
+) -> (
+    Option<spec::Zip64CentralDirectoryEnd>,
+    spec::CentralDirectoryEnd,
+) {
+    let zip64_footer = if number_of_files >= spec::ZIP64_ENTRY_THR
+        || central_start >= spec::ZIP64_BYTES_THR
+        || central_size >= spec::ZIP64_BYTES_THR
+        || !extensible_data.is_empty()
+    {
+        Some(spec::Zip64CentralDirectoryEnd {
+            version_made_by: (System::Unix as u16) << 8 | DEFAULT_VERSION as u16,
+            version_needed_to_extract: 45,
+            disk_number: 0,
+            disk_with_central_directory: 0,
+            number_of_files_on_this_disk: number_of_files as u64,
+            number_of_files: number_of_files as u64,
+            central_directory_size: central_size,
+            central_directory_offset: central_start,
+            central_directory_encryption: None,
+            extensible_data_sector: extensible_data.to_vec(),
+        })
+    } else {
+        None
+    };
+    let number_of_files = number_of_files.min(spec::ZIP64_ENTRY_THR) as u16;
+    let footer = spec::CentralDirectoryEnd {
+        disk_number: 0,
+        disk_with_central_directory: 0,
+        number_of_files_on_this_disk: number_of_files,
+        number_of_files,
+        central_directory_size: central_size.min(spec::ZIP64_BYTES_THR) as u32,
+        central_directory_offset: central_start.min(spec::ZIP64_BYTES_THR) as u32,
+        zip_file_comment: comment.to_vec(),
+    };
+    (zip64_footer, footer)
+}
+
+/// The locator of the ZIP64 central directory end record written at `zip64_footer_start`
+fn zip64_locator(zip64_footer_start: u64) -> spec::Zip64CentralDirectoryEndLocator {
+    spec::Zip64CentralDirectoryEndLocator {
+        disk_with_central_directory: 0,
+        end_of_central_directory_offset: zip64_footer_start,
+        number_of_disks: 1,
+    }
+}
+
+/// Check that the sizes of `file` fit in its local header
+fn check_local_sizes(file: &ZipFileData) -> ZipResult<()> {
+    let too_large = file.compressed_size >= spec::ZIP64_BYTES_THR
+        || file.uncompressed_size >= spec::ZIP64_BYTES_THR;
+    if too_large && !file.large_file {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The file is 4 GiB or larger, which requires FileOptions::large_file",
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// The size of the ZIP64 extra field in the local header of `file`
+fn local_zip64_field_size(file: &ZipFileData) -> u64 {
+    if file.large_file {
+        20
+    } else {
+        0
+    }
+}
+
+/// The CRC-32 in the local header of `file`, which is 0 if it is in the data descriptor
+fn local_crc32(file: &ZipFileData) -> u32 {
+    if file.using_data_descriptor {
+        0
+    } else {
+        file.crc32
+    }
+}
+
+/// The compressed and uncompressed size in the local header of `file`, which are 0 if they are in
+/// the data descriptor
+fn local_sizes(file: &ZipFileData) -> (u32, u32) {
+    if file.large_file {
+        (spec::ZIP64_BYTES_THR as u32, spec::ZIP64_BYTES_THR as u32)
+    } else if file.using_data_descriptor {
+        (0, 0)
+    } else {
+        (file.compressed_size as u32, file.uncompressed_size as u32)
+    }
+}
+
+/// The data descriptor of `file`
+fn data_descriptor(file: &ZipFileData) -> ZipResult<spec::DataDescriptor> {
+    check_local_sizes(file)?;
+    Ok(spec::DataDescriptor {
+        crc32: file.crc32,
+        compressed_size: file.compressed_size,
+        uncompressed_size: file.uncompressed_size,
+    })
+}
+
+/// Write the data descriptor of `file`, which has 8 byte sizes if its local header has a ZIP64
+/// extra field.
+fn write_data_descriptor<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipResult<()> {
+    data_descriptor(file)?.write(writer, file.large_file)
+}
+
+#[cfg(feature = "async")]
+async fn write_data_descriptor_async<T: AsyncWrite + Unpin>(
+    writer: &mut T,
+    file: &ZipFileData,
+) -> ZipResult<()> {
+    data_descriptor(file)?
+        .write_async(writer, file.large_file)
+        .await
+}
+
+/// Build the extra field of the local header of `file` if `local` is set, and otherwise of its
+/// central directory header.
+fn build_extra_field(file: &ZipFileData, local: bool) -> ZipResult<Vec<u8>> {
+    let mut writer = Vec::new();
+    // ZIP64 extended information, which is the first field so its offset in the local header is
+    // known when the sizes are updated. The local header always has both sizes, the central
+    // directory header only the values that don't fit in it.
+    let zip64_values = if local {
+        if file.large_file && file.using_data_descriptor {
+            vec![0, 0]
+        } else if file.large_file {
+            vec![file.uncompressed_size, file.compressed_size]
+        } else {
+            Vec::new()
+        }
+    } else {
+        vec![
+            file.uncompressed_size,
+            file.compressed_size,
+            file.header_start,
+        ]
+        .into_iter()
+        .filter(|value| *value >= spec::ZIP64_BYTES_THR)
+        .collect()
+    };
+    if !zip64_values.is_empty() {
+        writer.extend_from_slice(&0x0001u16.to_le_bytes());
+        writer.extend_from_slice(&(8 * zip64_values.len() as u16).to_le_bytes());
+        for value in zip64_values {
+            writer.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    if let Some((mode, vendor_version)) = file.aes_mode {
+        // WinZip AES extra field
+        let vendor_version: u16 = match vendor_version {
+            AesVendorVersion::Ae1 => 1,
+            AesVendorVersion::Ae2 => 2,
+        };
+        let strength: u8 = match mode {
+            AesMode::Aes128 => 1,
+            AesMode::Aes192 => 2,
+            AesMode::Aes256 => 3,
+        };
+        #[allow(deprecated)]
+        let compression_method = file.compression_method.to_u16();
+        writer.extend_from_slice(&0x9901u16.to_le_bytes());
+        writer.extend_from_slice(&7u16.to_le_bytes());
+        writer.extend_from_slice(&vendor_version.to_le_bytes());
+        writer.extend_from_slice(b"AE");
+        writer.push(strength);
+        writer.extend_from_slice(&compression_method.to_le_bytes());
+    }
+    if let Some(id) = file.deflate_dictionary {
+        // Preset dictionary extra field
+        writer.extend_from_slice(&0x6444u16.to_le_bytes());
+        writer.extend_from_slice(&4u16.to_le_bytes());
+        writer.extend_from_slice(&id.to_le_bytes());
+    }
     Ok(writer)
 }
 
@@ -1416,9 +4581,12 @@ fn path_to_string(path: &std::path::Path) -> String {
 
 #[cfg(test)]
 mod test {
-    use super::{FileOptions, ZipWriter};
+    use super::{DefaultOptions, DeflateStrategy, FileOptions, NameOptions, ZipWriter};
     use crate::compression::CompressionMethod;
-    use crate::types::DateTime;
+    use crate::result::ZipError;
+    #[cfg(feature = "aes-crypto")]
+    use crate::types::AES_ITERATION_COUNT;
+    use crate::types::{AesVendorVersion, DateTime};
     use std::io;
     use std::io::Write;
 
@@ -1469,6 +4637,22 @@ mod test {
             compression_method: CompressionMethod::Stored,
             last_modified_time: DateTime::default(),
             permissions: Some(33188),
+            system: None,
+            deflate_level: None,
+            deflate_strategy: DeflateStrategy::Default,
+            #[cfg(feature = "parallel")]
+            deflate_threads: 1,
+            store_threshold: None,
+            encrypt_with: None,
+            aes_vendor_version: AesVendorVersion::Ae2,
+            #[cfg(feature = "aes-crypto")]
+            aes_iteration_count: AES_ITERATION_COUNT,
+            deflate_dictionary: None,
+            large_file: false,
+            forbid_zip64: false,
+            data_descriptor: false,
+            compression_method_set: true,
+            last_modified_time_set: true,
         };
         writer.start_file("mimetype", options).unwrap();
         writer
@@ -1477,8 +4661,10 @@ mod test {
         let result = writer.finish().unwrap();
 
         assert_eq!(result.get_ref().len(), 153);
+        // tests/data/mimetype.zip records version 2.0 for its stored entry, where the writer
+        // records the 1.0 that a stored entry needs
         let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!("../tests/data/mimetype.zip"));
+        v.extend_from_slice(include_bytes!("../tests/data/mimetype_written.zip"));
         assert_eq!(result.get_ref(), &v);
     }
 
@@ -1496,4 +4682,145 @@ mod test {
         let path_str = super::path_to_string(&path);
         assert_eq!(path_str, "windows/system32");
     }
+
+    #[test]
+    fn strict_names() {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.set_name_options(NameOptions::strict());
+        let options = FileOptions::default();
+        for name in &["/etc/passwd", "C:/foo", "foo/../../bar", "foo\\bar"] {
+            match writer.start_file(*name, options) {
+                Err(ZipError::InvalidFileName(_)) => {}
+                _ => panic!("{} was not rejected", name),
+            }
+        }
+        writer.start_file("foo", options).unwrap();
+        writer.write_all(b"still writable").unwrap();
+        assert!(writer.start_file("foo", options).is_err());
+        writer.write_all(b" after a rejected name").unwrap();
+        writer.add_directory("foo", options).unwrap();
+        assert!(writer.add_directory("foo/", options).is_err());
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn normalized_names() {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.set_name_options(NameOptions::strict().normalize(true));
+        let options = FileOptions::default();
+        writer.start_file("\\foo\\.\\bar.txt", options).unwrap();
+        assert!(writer.start_file("foo/bar.txt", options).is_err());
+        assert!(writer.start_file("../bar.txt", options).is_err());
+        assert_eq!(writer.files[0].file_name, "foo/bar.txt");
+    }
+
+    #[test]
+    fn portable_names() {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let name_options = NameOptions::default()
+            .reject_non_portable(true)
+            .max_length(Some(8));
+        writer.set_name_options(name_options);
+        let options = FileOptions::default();
+        for name in &["caf\u{e9}", "tab\there", "del\u{7f}", "123456789"] {
+            match writer.start_file(*name, options) {
+                Err(ZipError::InvalidFileName(_)) => {}
+                _ => panic!("{:?} was not rejected", name),
+            }
+        }
+        writer.start_file("12345678", options).unwrap();
+        writer.start_file("a b/~.c", options).unwrap();
+        assert!(writer.add_directory("1234567", options).is_ok());
+        assert!(writer.add_directory("12345678", options).is_err());
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn duplicates_allowed_by_default() {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.start_file("foo", FileOptions::default()).unwrap();
+        writer.start_file("foo", FileOptions::default()).unwrap();
+        writer.set_name_options(NameOptions::default().reject_duplicates(true));
+        assert!(writer.start_file("foo", FileOptions::default()).is_err());
+    }
+
+    #[test]
+    fn entry_ids() {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        assert_eq!(writer.last_entry_id(), None);
+        writer.start_file("foo", FileOptions::default()).unwrap();
+        let foo = writer.last_entry_id().unwrap();
+        writer.add_directory("bar", FileOptions::default()).unwrap();
+        let bar = writer.last_entry_id().unwrap();
+        assert_ne!(foo, bar);
+        assert_eq!(writer.entry_index(foo), Some(0));
+        assert_eq!(writer.entry_index(bar), Some(1));
+        assert_eq!(writer.entry_name(bar), Some("bar/"));
+    }
+
+    #[cfg(feature = "bzip2")]
+    #[test]
+    fn default_options() {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let defaults = DefaultOptions::deterministic()
+            .compression_method(CompressionMethod::Bzip2)
+            .directory_permissions(0o700);
+        writer.set_default_options(defaults);
+        writer.start_file("foo", FileOptions::default()).unwrap();
+        writer.add_directory("dir", FileOptions::default()).unwrap();
+        let time = DateTime::from_date_and_time(2020, 2, 3, 4, 5, 6).unwrap();
+        let options = FileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .last_modified_time(time)
+            .unix_permissions(0o600);
+        writer.start_file("bar", options).unwrap();
+
+        let files = &writer.files;
+        assert_eq!(files[0].compression_method, CompressionMethod::Bzip2);
+        assert_eq!(files[0].last_modified_time.timepart(), 0);
+        assert_eq!(
+            files[0].last_modified_time.datepart(),
+            DateTime::default().datepart()
+        );
+        assert_eq!(files[0].external_attributes >> 16, 0o100644);
+        assert_eq!(files[1].compression_method, CompressionMethod::Stored);
+        assert_eq!(files[1].external_attributes >> 16, 0o40700);
+        assert_eq!(files[2].compression_method, CompressionMethod::Stored);
+        assert_eq!(files[2].last_modified_time.datepart(), time.datepart());
+        assert_eq!(files[2].external_attributes >> 16, 0o100600);
+    }
+
+    #[test]
+    fn normalize_name() {
+        use super::normalize_name;
+        assert_eq!(normalize_name("a/b/../c").as_deref(), Some("a/c"));
+        assert_eq!(normalize_name("a/b/..").as_deref(), Some("a"));
+        assert_eq!(normalize_name("dir\\").as_deref(), Some("dir/"));
+        assert_eq!(normalize_name("c:"), None);
+        assert_eq!(normalize_name("./"), None);
+        assert_eq!(normalize_name(".."), None);
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn deflate_options() {
+        let data = b"Hello, World! ".repeat(1000);
+        let write = |options: FileOptions| {
+            let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+            writer.start_file("data", options).unwrap();
+            writer.write_all(&data).unwrap();
+            writer.finish().unwrap().into_inner()
+        };
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        let stored = write(options.deflate_level(0));
+        let best = write(options.deflate_level(9));
+        let huffman = write(options.deflate_strategy(DeflateStrategy::HuffmanOnly));
+        assert!(best.len() < huffman.len());
+        assert!(huffman.len() < stored.len());
+
+        let mut archive = crate::ZipArchive::new(io::Cursor::new(huffman)).unwrap();
+        let mut contents = Vec::new();
+        io::copy(&mut archive.by_index(0).unwrap(), &mut contents).unwrap();
+        assert_eq!(contents, data);
+    }
 }
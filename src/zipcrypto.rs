@@ -11,7 +11,8 @@ use futures::io::{AsyncRead, AsyncReadExt};
 use pin_project::pin_project;
 
 /// A container to hold the current key state
-struct ZipCryptoKeys {
+#[derive(Clone, Copy)]
+pub(crate) struct ZipCryptoKeys {
     key_0: Wrapping<u32>,
     key_1: Wrapping<u32>,
     key_2: Wrapping<u32>,
@@ -26,6 +27,15 @@ impl ZipCryptoKeys {
         }
     }
 
+    /// Key the cipher with a password.
+    pub(crate) fn derive(password: &[u8]) -> ZipCryptoKeys {
+        let mut keys = ZipCryptoKeys::new();
+        for byte in password.iter() {
+            keys.update(*byte);
+        }
+        keys
+    }
+
     fn update(&mut self, input: u8) {
         self.key_0 = ZipCryptoKeys::crc32(self.key_0, input);
         self.key_1 =
@@ -44,7 +54,6 @@ impl ZipCryptoKeys {
         plain_byte
     }
 
-    #[allow(dead_code)]
     fn encrypt_byte(&mut self, plain_byte: u8) -> u8 {
         let cipher_byte: u8 = self.stream_byte() ^ plain_byte;
         self.update(plain_byte);
@@ -56,6 +65,58 @@ impl ZipCryptoKeys {
     }
 }
 
+/// Encrypt the data of an entry and write it, prefixed with the encryption header.
+///
+/// The header ends with `check_byte`, which is how readers verify the password. It is the high
+/// byte of the CRC-32 of the plaintext, so the data can only be encrypted once all of it is
+/// known, or of the modification time for entries with a data descriptor. The data is read in
+/// chunks of the size of `buffer`.
+pub(crate) fn encrypt_entry<R: std::io::Read, W: std::io::Write>(
+    mut keys: ZipCryptoKeys,
+    check_byte: u8,
+    data: &mut R,
+    buffer: &mut [u8],
+    writer: &mut W,
+) -> std::io::Result<()> {
+    let mut header = [0u8; 12];
+    fill_random(&mut header)?;
+    header[11] = check_byte;
+    for byte in header.iter_mut() {
+        *byte = keys.encrypt_byte(*byte);
+    }
+    writer.write_all(&header)?;
+    for_each_chunk(data, buffer, |chunk| {
+        for byte in chunk.iter_mut() {
+            *byte = keys.encrypt_byte(*byte);
+        }
+        writer.write_all(chunk)
+    })
+}
+
+/// Read all of `data` into `buffer`, passing each chunk that was read to `f`
+pub(crate) fn for_each_chunk<R: std::io::Read>(
+    data: &mut R,
+    buffer: &mut [u8],
+    mut f: impl FnMut(&mut [u8]) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    loop {
+        match data.read(buffer) {
+            Ok(0) => return Ok(()),
+            Ok(count) => f(&mut buffer[..count])?,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Fill `buf` with random bytes from the random number generator of the operating system
+///
+/// These are used for encryption headers and salts, which must not be predictable.
+pub(crate) fn fill_random(buf: &mut [u8]) -> std::io::Result<()> {
+    getrandom::getrandom(buf)?;
+    Ok(())
+}
+
 /// A ZipCrypto reader with unverified password
 #[cfg_attr(feature = "async", pin_project(project=ZipCryptoReaderProject))]
 pub struct ZipCryptoReader<R> {
@@ -86,9 +147,12 @@ impl<R: std::io::Read> ZipCryptoReader<R> {
     }
 
     /// Read the ZipCrypto header bytes and validate the password.
+    ///
+    /// The password is valid if the header ends with one of `check_bytes`, see
+    /// `ZipFileData::zipcrypto_check_bytes`.
     pub fn validate(
         mut self,
-        crc32_plaintext: u32,
+        check_bytes: [u8; 2],
     ) -> Result<Option<ZipCryptoReaderValid<R>>, std::io::Error> {
         // ZipCrypto prefixes a file with a 12 byte header
         let mut header_buf = [0u8; 12];
@@ -101,7 +165,7 @@ impl<R: std::io::Read> ZipCryptoReader<R> {
         // PKZIP 2.0+ used 1 byte CRC check. It's more secure.
         // We also use 1 byte CRC.
 
-        if (crc32_plaintext >> 24) as u8 != header_buf[11] {
+        if !check_bytes.contains(&header_buf[11]) {
             return Ok(None); // Wrong password
         }
         Ok(Some(ZipCryptoReaderValid { reader: self }))
@@ -132,9 +196,12 @@ impl<R: AsyncRead + Unpin> ZipCryptoReader<R> {
     }
 
     /// Read the ZipCrypto header bytes and validate the password.
+    ///
+    /// The password is valid if the header ends with one of `check_bytes`, see
+    /// `ZipFileData::zipcrypto_check_bytes`.
     pub async fn validate_async(
         mut self,
-        crc32_plaintext: u32,
+        check_bytes: [u8; 2],
     ) -> Result<Option<ZipCryptoReaderValid<R>>, std::io::Error> {
         // ZipCrypto prefixes a file with a 12 byte header
         let mut header_buf = [0u8; 12];
@@ -147,7 +214,7 @@ impl<R: AsyncRead + Unpin> ZipCryptoReader<R> {
         // PKZIP 2.0+ used 1 byte CRC check. It's more secure.
         // We also use 1 byte CRC.
 
-        if (crc32_plaintext >> 24) as u8 != header_buf[11] {
+        if !check_bytes.contains(&header_buf[11]) {
             return Ok(None); // Wrong password
         }
         Ok(Some(ZipCryptoReaderValid { reader: self }))
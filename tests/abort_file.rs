@@ -0,0 +1,107 @@
+use std::io::{Cursor, Read, Write};
+use zip::result::ZipError;
+use zip::write::{FileOptions, NameOptions};
+use zip::{ZipArchive, ZipWriter};
+
+fn contents(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+    let mut contents = String::new();
+    archive
+        .by_name(name)
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    contents
+}
+
+#[test]
+fn abort_file() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_name_options(NameOptions::default().reject_duplicates(true));
+    assert!(matches!(zip.abort_file(), Err(ZipError::FileNotFound)));
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"a").unwrap();
+    zip.start_file("b.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"half of b").unwrap();
+    zip.abort_file().unwrap();
+    assert!(zip.write_all(b"the rest of b").is_err());
+
+    // The name of the aborted file is free again
+    zip.start_file("b.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"b").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.len(), 2);
+    assert_eq!(contents(&mut archive, "a.txt"), "a");
+    assert_eq!(contents(&mut archive, "b.txt"), "b");
+}
+
+#[test]
+fn abort_long_file() {
+    // The central directory is shorter than the aborted file
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"a").unwrap();
+    zip.start_file("long.txt", FileOptions::default()).unwrap();
+    for i in 0..10000 {
+        write!(zip, "{} ", i).unwrap();
+    }
+    zip.abort_file().unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.len(), 1);
+    assert_eq!(contents(&mut archive, "a.txt"), "a");
+}
+
+#[test]
+fn abort_superseding_file() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for name in &["a.txt", "b.txt"] {
+        zip.start_file(*name, FileOptions::default()).unwrap();
+        zip.write_all(b"old").unwrap();
+    }
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut zip = ZipWriter::new_append(Cursor::new(bytes)).unwrap();
+    zip.start_file("b.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"new").unwrap();
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"new").unwrap();
+    zip.abort_file().unwrap();
+    // The new b.txt was finished when a.txt was started
+    assert!(matches!(zip.abort_file(), Err(ZipError::FileNotFound)));
+    zip.start_file("c.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"new").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let names: Vec<_> = archive.file_names().collect();
+    assert_eq!(names, ["a.txt", "b.txt", "c.txt"]);
+    assert_eq!(contents(&mut archive, "a.txt"), "old");
+    assert_eq!(contents(&mut archive, "b.txt"), "new");
+    assert_eq!(contents(&mut archive, "c.txt"), "new");
+}
+
+#[test]
+fn abort_finished_file() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"a").unwrap();
+    zip.finish_file().unwrap();
+    assert!(matches!(zip.abort_file(), Err(ZipError::FileNotFound)));
+    zip.add_directory("b", FileOptions::default()).unwrap();
+    assert!(matches!(zip.abort_file(), Err(ZipError::FileNotFound)));
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.len(), 2);
+    assert_eq!(contents(&mut archive, "a.txt"), "a");
+}
+
+#[test]
+fn abort_stream_file() {
+    let mut zip = ZipWriter::new_stream(Vec::new());
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    assert!(zip.abort_file().is_err());
+}
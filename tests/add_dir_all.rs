@@ -0,0 +1,78 @@
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::PathBuf;
+use zip::write::{FileOptions, NameOptions};
+use zip::{ZipArchive, ZipWriter};
+
+fn source_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zip-add-dir-all-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub/empty")).unwrap();
+    fs::write(dir.join("top.txt"), b"top").unwrap();
+    fs::write(dir.join("sub/nested.txt"), b"nested").unwrap();
+    dir
+}
+
+#[test]
+fn adds_tree_in_order() {
+    let dir = source_dir("tree");
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.add_dir_all(&dir, FileOptions::default()).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let names: Vec<_> = (0..archive.len())
+        .map(|i| archive.by_index(i).unwrap().name().to_owned())
+        .collect();
+    assert_eq!(names, ["sub/", "sub/empty/", "sub/nested.txt", "top.txt"]);
+
+    let mut contents = String::new();
+    archive
+        .by_name("sub/nested.txt")
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    assert_eq!(contents, "nested");
+}
+
+#[cfg(unix)]
+#[test]
+fn normalizes_backslashes() {
+    let dir = source_dir("backslash");
+    fs::write(dir.join("windows\\style.txt"), b"").unwrap();
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_name_options(NameOptions::default().normalize(true));
+    zip.add_dir_all(&dir, FileOptions::default()).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert!(archive.file_names().any(|name| name == "windows/style.txt"));
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks() {
+    use std::io;
+    use zip::result::ZipError;
+
+    let dir = source_dir("symlinks");
+    std::os::unix::fs::symlink(dir.join("sub"), dir.join("linked")).unwrap();
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.add_dir_all(&dir, FileOptions::default()).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    let archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert!(archive.file_names().any(|name| name == "linked/nested.txt"));
+
+    // A link back to a directory that contains it would be followed forever
+    std::os::unix::fs::symlink("..", dir.join("sub/empty/loop")).unwrap();
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let error = zip.add_dir_all(&dir, FileOptions::default()).unwrap_err();
+    fs::remove_dir_all(&dir).unwrap();
+    match error {
+        ZipError::Io(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidInput),
+        e => panic!("unexpected error {:?}", e),
+    }
+}
@@ -0,0 +1,375 @@
+#![cfg(feature = "aes-crypto")]
+
+use rand::{Rng, SeedableRng};
+use std::io::{self, Read, Write};
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::{AesMode, AesVendorVersion, CompressionMethod, EncryptionMethod, ZipArchive, ZipWriter};
+#[cfg(feature = "async")]
+use {futures::io::AsyncReadExt, zip::AsyncZipArchive};
+
+const SECRET_CONTENT: &str = "This is a test file encrypted with AES.\n";
+const PASSWORD: &[u8] = b"some password";
+
+fn archive() -> ZipArchive<io::Cursor<&'static [u8]>> {
+    let bytes = include_bytes!("data/aes_archive.zip");
+    ZipArchive::new(io::Cursor::new(&bytes[..])).unwrap()
+}
+
+fn read_decrypted(name: &str) -> String {
+    let mut archive = archive();
+    let mut file = archive
+        .by_name_decrypt(name, PASSWORD)
+        .unwrap()
+        .expect("the password is correct");
+    let mut content = String::new();
+    file.read_to_string(&mut content).unwrap();
+    content
+}
+
+#[test]
+fn aes256_encrypted_file() {
+    assert_eq!(read_decrypted("secret_data_256"), SECRET_CONTENT.repeat(10));
+    assert_eq!(
+        read_decrypted("secret_data_256_uncompressed"),
+        "Uncompressed AE-2 entry\n"
+    );
+}
+
+#[test]
+fn aes192_encrypted_file() {
+    assert_eq!(read_decrypted("secret_data_192"), SECRET_CONTENT.repeat(10));
+}
+
+#[test]
+fn aes128_encrypted_file() {
+    assert_eq!(read_decrypted("secret_data_128"), SECRET_CONTENT.repeat(10));
+}
+
+#[test]
+fn aes_file_metadata() {
+    let mut archive = archive();
+    let file = archive
+        .by_name_decrypt("secret_data_256", PASSWORD)
+        .unwrap()
+        .unwrap();
+    assert_eq!(file.compression(), CompressionMethod::Deflated);
+    drop(file);
+    let file = archive
+        .by_name_decrypt("secret_data_128", PASSWORD)
+        .unwrap()
+        .unwrap();
+    assert_eq!(file.compression(), CompressionMethod::Stored);
+    assert_eq!(file.size(), SECRET_CONTENT.len() as u64 * 10);
+}
+
+#[test]
+fn aes_encryption_methods() {
+    let mut archive = archive();
+    for i in 0..archive.len() {
+        let name = archive.by_index_raw(i).unwrap().name().to_owned();
+        let (mode, vendor_version) = match &name[..] {
+            "secret_data_256" | "secret_data_256_uncompressed" => {
+                (AesMode::Aes256, AesVendorVersion::Ae2)
+            }
+            "secret_data_192" => (AesMode::Aes192, AesVendorVersion::Ae1),
+            "secret_data_128" => (AesMode::Aes128, AesVendorVersion::Ae1),
+            _ => panic!("unexpected entry {}", name),
+        };
+        let expected = Some(EncryptionMethod::Aes {
+            mode,
+            vendor_version,
+        });
+        assert_eq!(archive.encryption_method(i).unwrap(), expected);
+        let file = archive.by_index_decrypt(i, PASSWORD).unwrap().unwrap();
+        assert!(file.encrypted());
+        assert_eq!(file.encryption_method(), expected);
+    }
+    assert!(archive.encryption_method(archive.len()).is_err());
+}
+
+#[test]
+fn per_entry_passwords() {
+    let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+    writer
+        .start_file("zipcrypto", FileOptions::default().with_password(b"first"))
+        .unwrap();
+    writer.write_all(b"first secret").unwrap();
+    let options = FileOptions::default().with_aes_encryption(AesMode::Aes128, b"second");
+    writer.start_file("aes", options).unwrap();
+    writer.write_all(b"second secret").unwrap();
+    writer.start_file("plain", FileOptions::default()).unwrap();
+    writer.write_all(b"not a secret").unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(io::Cursor::new(bytes)).unwrap();
+    assert_eq!(
+        archive.encryption_method(0).unwrap(),
+        Some(EncryptionMethod::ZipCrypto)
+    );
+    assert_eq!(
+        archive.encryption_method(1).unwrap(),
+        Some(EncryptionMethod::Aes {
+            mode: AesMode::Aes128,
+            vendor_version: AesVendorVersion::Ae2,
+        })
+    );
+    assert_eq!(archive.encryption_method(2).unwrap(), None);
+
+    for &(index, password, expected) in &[
+        (0, &b"first"[..], "first secret"),
+        (1, b"second", "second secret"),
+        (2, b"ignored", "not a secret"),
+    ] {
+        let mut file = archive.by_index_decrypt(index, password).unwrap().unwrap();
+        let mut content = String::new();
+        file.read_to_string(&mut content).unwrap();
+        assert_eq!(content, expected);
+    }
+    assert!(archive.by_index_decrypt(0, b"second").unwrap().is_err());
+    assert!(archive.by_index_decrypt(1, b"first").unwrap().is_err());
+}
+
+#[test]
+fn aes_wrong_password() {
+    let mut archive = archive();
+    for i in 0..archive.len() {
+        match archive.by_index_decrypt(i, b"wrong password") {
+            Ok(Err(zip::result::InvalidPassword)) => {}
+            _ => panic!("Expected InvalidPassword error"),
+        }
+    }
+}
+
+#[test]
+fn aes_verify_password() {
+    let mut archive = archive();
+    for i in 0..archive.len() {
+        assert!(archive.verify_password(i, PASSWORD).unwrap());
+        assert!(!archive.verify_password(i, b"wrong password").unwrap());
+    }
+}
+
+#[test]
+fn aes_password_required() {
+    let mut archive = archive();
+    match archive.by_name("secret_data_256").map(|_| ()) {
+        Err(ZipError::UnsupportedArchive("Password required to decrypt file")) => {}
+        _ => panic!("Expected an error when opening an AES encrypted file without password"),
+    }
+}
+
+#[test]
+fn aes_tampered_data() {
+    let mut bytes = include_bytes!("data/aes_archive.zip").to_vec();
+    let mut archive = ZipArchive::new(io::Cursor::new(bytes.clone())).unwrap();
+    let data_start = archive
+        .by_name_decrypt("secret_data_128", PASSWORD)
+        .unwrap()
+        .unwrap()
+        .data_start();
+    // Flip a bit of the encrypted data, after the salt and password verification value
+    bytes[data_start as usize + 8 + 2 + 5] ^= 1;
+
+    let mut archive = ZipArchive::new(io::Cursor::new(bytes)).unwrap();
+    let mut file = archive
+        .by_name_decrypt("secret_data_128", PASSWORD)
+        .unwrap()
+        .unwrap();
+    let mut content = Vec::new();
+    let error = file.read_to_end(&mut content).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+fn write_encrypted(options: FileOptions, content: &[u8]) -> Vec<u8> {
+    let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+    writer.start_file("secret", options).unwrap();
+    writer.write_all(content).unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+fn read_written(bytes: Vec<u8>, password: &[u8]) -> Vec<u8> {
+    let mut archive = ZipArchive::new(io::Cursor::new(bytes)).unwrap();
+    let mut file = archive.by_index_decrypt(0, password).unwrap().unwrap();
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).unwrap();
+    content
+}
+
+#[test]
+fn aes_roundtrip() {
+    let content = SECRET_CONTENT.repeat(100);
+    for &mode in &[AesMode::Aes128, AesMode::Aes192, AesMode::Aes256] {
+        for &method in &[CompressionMethod::Stored, CompressionMethod::Deflated] {
+            let options = FileOptions::default()
+                .compression_method(method)
+                .with_aes_encryption(mode, PASSWORD);
+            let bytes = write_encrypted(options, content.as_bytes());
+            assert_eq!(read_written(bytes, PASSWORD), content.as_bytes());
+        }
+    }
+}
+
+#[test]
+fn aes_long_password() {
+    let password = [b'x'; 100];
+    let options = FileOptions::default().with_aes_encryption(AesMode::Aes256, &password);
+    let bytes = write_encrypted(options, SECRET_CONTENT.as_bytes());
+    assert_eq!(read_written(bytes, &password), SECRET_CONTENT.as_bytes());
+}
+
+#[test]
+fn aes_vendor_versions() {
+    let content = SECRET_CONTENT.as_bytes();
+    let options = FileOptions::default().with_aes_encryption(AesMode::Aes128, PASSWORD);
+
+    let ae2 = write_encrypted(options, content);
+    let ae1 = write_encrypted(options.aes_vendor_version(AesVendorVersion::Ae1), content);
+    for bytes in &[ae1.clone(), ae2.clone()] {
+        let mut archive = ZipArchive::new(io::Cursor::new(bytes.clone())).unwrap();
+        match archive.by_index_decrypt(0, b"wrong password") {
+            Ok(Err(zip::result::InvalidPassword)) => {}
+            _ => panic!("Expected InvalidPassword error"),
+        }
+        assert_eq!(read_written(bytes.clone(), PASSWORD), content);
+    }
+
+    // Compression method 99, and the CRC-32 is only stored by AE-1
+    let crc32 = crc32fast::hash(content);
+    assert_eq!(&ae1[8..10], &[99, 0]);
+    assert_eq!(&ae2[8..10], &[99, 0]);
+    assert_eq!(ae1[14..18], crc32.to_le_bytes());
+    assert_eq!(ae2[14..18], [0; 4]);
+}
+
+#[test]
+fn aes_iteration_count() {
+    let content = SECRET_CONTENT.as_bytes();
+    let options = FileOptions::default()
+        .with_aes_encryption(AesMode::Aes256, PASSWORD)
+        .aes_iteration_count(10);
+    let bytes = write_encrypted(options, content);
+
+    let mut archive = ZipArchive::new(io::Cursor::new(bytes)).unwrap();
+    match archive.by_index_decrypt(0, PASSWORD) {
+        Ok(Err(zip::result::InvalidPassword)) => {}
+        _ => panic!("Expected InvalidPassword error"),
+    }
+    archive.set_aes_iteration_count(10);
+    let mut file = archive.by_index_decrypt(0, PASSWORD).unwrap().unwrap();
+    let mut decrypted = Vec::new();
+    file.read_to_end(&mut decrypted).unwrap();
+    assert_eq!(decrypted, content);
+}
+
+#[test]
+fn aes_store_if_incompressible() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    let content: Vec<u8> = (0..100_000).map(|_| rng.gen()).collect();
+    let options = FileOptions::default()
+        .store_if_incompressible(0.95)
+        .with_aes_encryption(AesMode::Aes256, PASSWORD);
+    let bytes = write_encrypted(options, &content);
+
+    let mut archive = ZipArchive::new(io::Cursor::new(bytes.clone())).unwrap();
+    let file = archive.by_index_decrypt(0, PASSWORD).unwrap().unwrap();
+    assert_eq!(file.compression(), CompressionMethod::Stored);
+    drop(file);
+    assert_eq!(read_written(bytes, PASSWORD), content);
+}
+
+#[test]
+fn aes_raw_encrypted() {
+    let content = SECRET_CONTENT.repeat(10);
+    let options = FileOptions::default().with_aes_encryption(AesMode::Aes256, PASSWORD);
+    let bytes = write_encrypted(options, content.as_bytes());
+
+    let mut archive = ZipArchive::new(io::Cursor::new(bytes.clone())).unwrap();
+    let mut file = archive.by_index_raw_encrypted(0).unwrap();
+    assert_eq!(
+        file.encryption_method(),
+        Some(EncryptionMethod::Aes {
+            mode: AesMode::Aes256,
+            vendor_version: AesVendorVersion::Ae2,
+        })
+    );
+    assert_eq!(file.compression(), CompressionMethod::Deflated);
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).unwrap();
+    assert_eq!(data.len() as u64, file.compressed_size());
+    let start = file.data_start() as usize;
+    assert_eq!(data, &bytes[start..start + data.len()]);
+    drop(file);
+
+    let mut archive = ZipArchive::new(io::Cursor::new(write_encrypted(
+        FileOptions::default(),
+        b"plain",
+    )))
+    .unwrap();
+    match archive.by_index_raw_encrypted(0).map(|_| ()) {
+        Err(ZipError::UnsupportedArchive("The file is not encrypted")) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn aes_encrypted_file_async() {
+    let bytes = include_bytes!("data/aes_archive.zip");
+    let reader = futures::io::Cursor::new(&bytes[..]);
+    let mut archive = AsyncZipArchive::new(reader).await.unwrap();
+    for &name in &["secret_data_128", "secret_data_192", "secret_data_256"] {
+        match archive.by_name_decrypt(name, b"wrong password").await {
+            Ok(Err(zip::result::InvalidPassword)) => {}
+            _ => panic!("Expected InvalidPassword error"),
+        }
+        let mut file = archive
+            .by_name_decrypt(name, PASSWORD)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut content = String::new();
+        file.read_to_string(&mut content).await.unwrap();
+        assert_eq!(content, SECRET_CONTENT.repeat(10));
+    }
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn aes_roundtrip_async() {
+    let content = SECRET_CONTENT.repeat(100);
+    for &method in &[CompressionMethod::Stored, CompressionMethod::Deflated] {
+        let options = FileOptions::default()
+            .compression_method(method)
+            .with_aes_encryption(AesMode::Aes256, PASSWORD)
+            .aes_iteration_count(10);
+        let mut bytes = write_encrypted(options, content.as_bytes());
+
+        let reader = futures::io::Cursor::new(bytes.clone());
+        let mut archive = AsyncZipArchive::new(reader).await.unwrap();
+        archive.set_aes_iteration_count(10);
+        let mut file = archive
+            .by_index_decrypt(0, PASSWORD)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut decrypted = Vec::new();
+        file.read_to_end(&mut decrypted).await.unwrap();
+        assert_eq!(decrypted, content.as_bytes());
+
+        // Flip a bit of the authentication code at the end of the file data
+        let data_end = file.data_start() + file.compressed_size() - 1;
+        drop(file);
+        bytes[data_end as usize] ^= 1;
+        let reader = futures::io::Cursor::new(bytes);
+        let mut archive = AsyncZipArchive::new(reader).await.unwrap();
+        archive.set_aes_iteration_count(10);
+        let mut file = archive
+            .by_index_decrypt(0, PASSWORD)
+            .await
+            .unwrap()
+            .unwrap();
+        let error = file.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
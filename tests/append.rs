@@ -0,0 +1,195 @@
+use std::io::{Cursor, Read, Write};
+use zip::result::ZipError;
+use zip::write::{FileOptions, NameOptions};
+use zip::{CompressionMethod, ExtensibleDataBlock, ZipArchive, ZipWriter};
+
+fn contents(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+    let mut contents = String::new();
+    archive
+        .by_name(name)
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    contents
+}
+
+#[test]
+fn append() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_comment("appended to");
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("stored.txt", stored).unwrap();
+    zip.write_all(b"stored").unwrap();
+    zip.start_file("deflated.txt", FileOptions::default())
+        .unwrap();
+    zip.write_all(b"deflated").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut zip = ZipWriter::new_append(Cursor::new(bytes)).unwrap();
+    zip.start_file("new.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"new").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.len(), 3);
+    assert_eq!(archive.comment(), b"appended to");
+    assert_eq!(contents(&mut archive, "stored.txt"), "stored");
+    assert_eq!(contents(&mut archive, "deflated.txt"), "deflated");
+    assert_eq!(contents(&mut archive, "new.txt"), "new");
+    assert_eq!(archive.offset(), 0);
+
+    // Appending again keeps all files
+    let bytes = archive.into_inner().into_inner();
+    let mut zip = ZipWriter::new_append(Cursor::new(bytes)).unwrap();
+    zip.add_directory("dir", FileOptions::default()).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    let archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let mut names: Vec<_> = archive.file_names().collect();
+    names.sort_unstable();
+    assert_eq!(names, ["deflated.txt", "dir/", "new.txt", "stored.txt"]);
+}
+
+#[test]
+fn append_to_external_archive() {
+    let bytes = include_bytes!("data/files_and_dirs.zip").to_vec();
+    let mut zip = ZipWriter::new_append(Cursor::new(bytes)).unwrap();
+    zip.start_file("file1.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"appended").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.len(), 4);
+    assert!(archive.by_name("dir1/").unwrap().is_dir());
+    assert_eq!(contents(&mut archive, "file0.txt").len(), 18);
+    assert_eq!(contents(&mut archive, "file1.txt"), "appended");
+}
+
+#[cfg(feature = "aes-crypto")]
+#[test]
+fn append_to_encrypted_archive() {
+    let bytes = include_bytes!("data/aes_archive.zip").to_vec();
+    let mut zip = ZipWriter::new_append(Cursor::new(bytes)).unwrap();
+    zip.start_file("plain.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"plain").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.len(), 5);
+    let mut secret = String::new();
+    archive
+        .by_name_decrypt("secret_data_256", b"some password")
+        .unwrap()
+        .unwrap()
+        .read_to_string(&mut secret)
+        .unwrap();
+    assert!(!secret.is_empty());
+    assert_eq!(contents(&mut archive, "plain.txt"), "plain");
+}
+
+#[test]
+fn append_keeps_records() {
+    let bytes = include_bytes!("data/comments.zip").to_vec();
+    let mut archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+    let start = archive.by_index(0).unwrap().central_header_start() as usize;
+    let record = bytes[start..start + 46 + 13 + 8 + 12].to_vec();
+    assert!(record.ends_with(b"file comment"));
+
+    let mut zip = ZipWriter::new_append(Cursor::new(bytes)).unwrap();
+    zip.start_file("new.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"new").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.comment(), b"archive comment \xff\xfe");
+    let start = archive
+        .by_name("commented.txt")
+        .unwrap()
+        .central_header_start() as usize;
+    assert_eq!(
+        archive.by_name("commented.txt").unwrap().comment(),
+        "file comment"
+    );
+    let bytes = archive.into_inner().into_inner();
+    // The record with the comment and the unknown extra field is written again as it is
+    assert_eq!(bytes[start..start + record.len()], record[..]);
+}
+
+#[test]
+fn append_keeps_extensible_data() {
+    let blocks = vec![ExtensibleDataBlock {
+        header_id: 0x4242,
+        data: vec![0x42; 1000],
+    }];
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_zip64_extensible_data(ExtensibleDataBlock::encode_all(&blocks));
+    zip.set_central_directory_checksum(true);
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"a").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut zip = ZipWriter::new_append(Cursor::new(bytes)).unwrap();
+    zip.start_file("b.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"b").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    // Reading checks the central directory checksum, which is written again
+    let archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.len(), 2);
+    let sector = ExtensibleDataBlock::parse_all(archive.zip64_extensible_data().unwrap()).unwrap();
+    assert_eq!(sector[0], blocks[0]);
+    assert_eq!(sector.len(), 2);
+}
+
+#[test]
+fn append_without_shrinking() {
+    // The comment is shortened, so the central directory that is written again is shorter than
+    // the one that was appended to
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_comment("a".repeat(1000));
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"a").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    let length = bytes.len();
+
+    let mut zip = ZipWriter::new_append(Cursor::new(bytes)).unwrap();
+    zip.set_comment("");
+    zip.start_file("b.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"b").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    assert_eq!(bytes.len(), length);
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.len(), 2);
+    assert_eq!(contents(&mut archive, "a.txt"), "a");
+    assert_eq!(contents(&mut archive, "b.txt"), "b");
+}
+
+#[test]
+fn append_replace() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for &(name, contents) in &[("a.txt", "old a"), ("b.txt", "b"), ("a.txt", "older a")] {
+        zip.start_file(name, FileOptions::default()).unwrap();
+        zip.write_all(contents.as_bytes()).unwrap();
+    }
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut zip = ZipWriter::new_append(Cursor::new(bytes.clone())).unwrap();
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"new a").unwrap();
+    let replaced = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(replaced)).unwrap();
+    assert_eq!(archive.len(), 2);
+    assert_eq!(archive.by_index(0).unwrap().name(), "b.txt");
+    assert_eq!(contents(&mut archive, "a.txt"), "new a");
+
+    let mut zip = ZipWriter::new_append(Cursor::new(bytes)).unwrap();
+    zip.set_name_options(NameOptions::default().reject_duplicates(true));
+    assert!(matches!(
+        zip.start_file("a.txt", FileOptions::default()),
+        Err(ZipError::InvalidFileName(_))
+    ));
+    zip.start_file("c.txt", FileOptions::default()).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    assert_eq!(ZipArchive::new(Cursor::new(bytes)).unwrap().len(), 4);
+}
@@ -0,0 +1,38 @@
+use std::io::{Cursor, Read, Write};
+use zip::read::Config;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// The contents of the `i`th file, large enough for several buffers of the smallest size
+fn contents(i: usize) -> Vec<u8> {
+    format!("file {} ", i).repeat(i * 50).into_bytes()
+}
+
+#[test]
+fn many_entries() {
+    let methods = [
+        CompressionMethod::Stored,
+        CompressionMethod::Deflated,
+        CompressionMethod::Bzip2,
+    ];
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for i in 0..60 {
+        let options = FileOptions::default()
+            .compression_method(methods[i % methods.len()])
+            .deflate_level(i as u32 % 9 + 1);
+        zip.start_file(format!("{}.txt", i), options).unwrap();
+        zip.write_all(&contents(i)).unwrap();
+    }
+    let bytes = zip.finish().unwrap().into_inner();
+
+    for &buffer_size in &[1, 1024, 1 << 20] {
+        let config = Config::default().buffer_size(buffer_size);
+        let mut archive = ZipArchive::new_with_config(Cursor::new(&bytes), config).unwrap();
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i).unwrap();
+            let mut read = Vec::new();
+            file.read_to_end(&mut read).unwrap();
+            assert_eq!(read, contents(i), "{} with buffers of {}", i, buffer_size);
+        }
+    }
+}
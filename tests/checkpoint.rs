@@ -0,0 +1,152 @@
+use std::cell::RefCell;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::rc::Rc;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+/// A writer whose output can be looked at while the archive is written
+#[derive(Clone, Default)]
+struct Shared(Rc<RefCell<Cursor<Vec<u8>>>>);
+
+impl Shared {
+    fn bytes(&self) -> Vec<u8> {
+        self.0.borrow().get_ref().clone()
+    }
+}
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for Shared {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.borrow_mut().seek(pos)
+    }
+}
+
+/// Write the entries `{i}.txt` for every `i` in `entries`, which contain their names followed by
+/// `padding` spaces, and finish each of them
+fn write_entries<W: Write + Seek>(zip: &mut ZipWriter<W>, entries: Range<usize>, padding: usize) {
+    let stored = FileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .last_modified_time(DateTime::default());
+    for i in entries {
+        let name = format!("{}.txt", i);
+        zip.start_file(name.as_str(), stored).unwrap();
+        zip.write_all(name.as_bytes()).unwrap();
+        zip.write_all(&vec![b' '; padding]).unwrap();
+        zip.finish_file().unwrap();
+    }
+}
+
+fn names(bytes: Vec<u8>) -> Vec<String> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    (0..archive.len())
+        .map(|i| {
+            let mut file = archive.by_index(i).unwrap();
+            let mut contents = String::new();
+            file.read_to_string(&mut contents).unwrap();
+            assert_eq!(contents.trim_end(), file.name());
+            file.name().to_owned()
+        })
+        .collect()
+}
+
+/// The number of times `signature` occurs in `bytes`
+fn count(bytes: &[u8], signature: &[u8; 4]) -> usize {
+    bytes.windows(4).filter(|w| w == signature).count()
+}
+
+#[test]
+fn checkpoint() {
+    let output = Shared::default();
+    let mut zip = ZipWriter::new(output.clone());
+    zip.set_checkpoint_interval(Some(3));
+
+    // The writer is not finished, as after a crash, but every third file was checkpointed
+    for written in 1..=7 {
+        write_entries(&mut zip, written - 1..written, 200);
+        if written % 3 == 0 {
+            assert_eq!(names(output.bytes()).len(), written);
+        }
+    }
+
+    zip.finish().unwrap();
+    assert_eq!(names(output.bytes()).len(), 7);
+}
+
+#[test]
+fn checkpoints_are_overwritten() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    write_entries(&mut zip, 0..200, 0);
+    let plain = zip.finish().unwrap().into_inner();
+
+    let output = Shared::default();
+    let mut zip = ZipWriter::new(output.clone());
+    zip.set_checkpoint_interval(Some(1));
+    write_entries(&mut zip, 0..200, 0);
+    assert_eq!(count(&output.bytes(), b"PK\x05\x06"), 1);
+    assert_eq!(names(output.bytes()).len(), 200);
+
+    // No central directory of a checkpoint is left in the finished archive
+    zip.finish().unwrap();
+    let bytes = output.bytes();
+    assert_eq!(count(&bytes, b"PK\x01\x02"), 200);
+    assert_eq!(count(&bytes, b"PK\x05\x06"), 1);
+    assert_eq!(bytes, plain);
+}
+
+#[test]
+fn checkpoint_longer_than_archive() {
+    // The central directory that is written over the checkpoint is shorter than it
+    let output = Shared::default();
+    let mut zip = ZipWriter::new(output.clone());
+    zip.set_checkpoint_interval(Some(50));
+    write_entries(&mut zip, 0..50, 0);
+    let checkpoint_length = output.bytes().len();
+    zip.rename("10.txt", "a").unwrap();
+    zip.finish().unwrap();
+
+    let bytes = output.bytes();
+    assert_eq!(bytes.len(), checkpoint_length);
+    assert_eq!(count(&bytes, b"PK\x01\x02"), 50);
+    assert_eq!(count(&bytes, b"PK\x05\x06"), 1);
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.len(), 50);
+    assert_eq!(archive.by_name("a").unwrap().size(), 6);
+}
+
+#[test]
+fn checkpoint_before_long_file() {
+    let output = Shared::default();
+    let mut zip = ZipWriter::new(output.clone());
+    zip.set_checkpoint_interval(Some(2));
+    write_entries(&mut zip, 0..2, 0);
+    assert_eq!(names(output.bytes()), ["0.txt", "1.txt"]);
+
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("long.bin", stored).unwrap();
+    zip.write_all(&[0x42; 100_000]).unwrap();
+    zip.finish().unwrap();
+    let mut archive = ZipArchive::new(Cursor::new(output.bytes())).unwrap();
+    assert_eq!(archive.len(), 3);
+    assert_eq!(archive.by_name("long.bin").unwrap().size(), 100_000);
+}
+
+#[test]
+fn checkpoint_stream() {
+    // Checkpoints can't be overwritten in a stream, so none are written
+    let mut zip = ZipWriter::new_stream(Vec::new());
+    zip.set_checkpoint_interval(Some(1));
+    write_entries(&mut zip, 0..3, 0);
+    let bytes = zip.finish().unwrap().into_inner();
+    assert_eq!(count(&bytes, b"PK\x05\x06"), 1);
+    assert_eq!(names(bytes).len(), 3);
+}
@@ -0,0 +1,140 @@
+use std::io::{self, Cursor, Read, Write};
+use zip::codec::{Compressor, CompressorStream, Decompressor};
+use zip::read::{Config, StoredSizePolicy};
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+// A toy "compression" method that flips all bits, registered under an unassigned method ID.
+const METHOD_ID: u16 = 200;
+
+struct Invert;
+
+struct InvertStream;
+
+struct InvertReader<'a>(Box<dyn Read + 'a>);
+
+impl Compressor for Invert {
+    fn start_entry(&self) -> Box<dyn CompressorStream> {
+        Box::new(InvertStream)
+    }
+}
+
+impl CompressorStream for InvertStream {
+    fn write(&mut self, data: &[u8], output: &mut dyn Write) -> io::Result<()> {
+        let inverted: Vec<u8> = data.iter().map(|b| !b).collect();
+        output.write_all(&inverted)
+    }
+
+    fn finish(self: Box<Self>, _output: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Decompressor for Invert {
+    fn decompress<'a>(&self, reader: Box<dyn Read + 'a>) -> Box<dyn Read + 'a> {
+        Box::new(InvertReader(reader))
+    }
+}
+
+impl<'a> Read for InvertReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.0.read(buf)?;
+        for byte in &mut buf[..count] {
+            *byte = !*byte;
+        }
+        Ok(count)
+    }
+}
+
+fn write_archive() -> Vec<u8> {
+    let method = CompressionMethod::custom(METHOD_ID);
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.register_compressor(method, Invert);
+    zip.start_file(
+        "inverted.txt",
+        FileOptions::default().compression_method(method),
+    )
+    .unwrap();
+    zip.write_all(b"Hello, World!").unwrap();
+    zip.start_file("deflated.txt", FileOptions::default())
+        .unwrap();
+    zip.write_all(b"Hello, World!").unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+#[test]
+fn custom_codec_roundtrip() {
+    let mut archive = ZipArchive::new(Cursor::new(write_archive())).unwrap();
+    archive.register_decompressor(CompressionMethod::custom(METHOD_ID), Invert);
+
+    let mut file = archive.by_name("inverted.txt").unwrap();
+    assert_eq!(file.compression(), CompressionMethod::custom(METHOD_ID));
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "Hello, World!");
+    drop(file);
+
+    let mut contents = String::new();
+    archive
+        .by_name("deflated.txt")
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    assert_eq!(contents, "Hello, World!");
+}
+
+#[test]
+fn custom_codec_stores_transformed_data() {
+    let mut archive = ZipArchive::new(Cursor::new(write_archive())).unwrap();
+    let mut raw = Vec::new();
+    archive
+        .by_index_raw(0)
+        .unwrap()
+        .read_to_end(&mut raw)
+        .unwrap();
+    let expected: Vec<u8> = b"Hello, World!".iter().map(|b| !b).collect();
+    assert_eq!(raw, expected);
+}
+
+#[test]
+fn unregistered_method_is_unsupported() {
+    let mut archive = ZipArchive::new(Cursor::new(write_archive())).unwrap();
+    let result = archive.by_name("inverted.txt").map(|_| ());
+    match result {
+        Err(ZipError::UnsupportedArchive(_)) => {}
+        Err(e) => panic!("unexpected error: {:?}", e),
+        Ok(()) => panic!("opened an entry with an unknown compression method"),
+    }
+}
+
+#[test]
+fn custom_decompressor_dropped_early() {
+    // A stored file whose compressed size in the central directory is too large is opened with
+    // adjusted sizes, and its rest is read through the inner reader when it is dropped
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("stored.txt", options).unwrap();
+    zip.write_all(b"Hello, World!").unwrap();
+    let mut bytes = zip.finish().unwrap().into_inner();
+    let central_header = bytes
+        .windows(4)
+        .position(|window| window == b"PK\x01\x02")
+        .unwrap();
+    bytes[central_header + 20] += 5;
+
+    let config = Config::default().stored_sizes(StoredSizePolicy::TrustUncompressed);
+    let mut archive = ZipArchive::new_with_config(Cursor::new(bytes), config).unwrap();
+    archive.register_decompressor(CompressionMethod::Stored, Invert);
+    let mut file = archive.by_index(0).unwrap();
+    let mut start = [0; 5];
+    file.read_exact(&mut start).unwrap();
+    let expected: Vec<u8> = b"Hello".iter().map(|b| !b).collect();
+    assert_eq!(start[..], expected[..]);
+}
+
+#[test]
+fn custom_method_constant_equivalence() {
+    assert_eq!(CompressionMethod::custom(0), CompressionMethod::STORE);
+    assert_eq!(CompressionMethod::custom(8), CompressionMethod::DEFLATE);
+}
@@ -0,0 +1,300 @@
+#![cfg(feature = "deflate")]
+
+use std::io::{Cursor, Read, Write};
+use zip::read::read_zipfile_from_stream;
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const DATA: &[u8] = b"data that is followed by a data descriptor, data that is followed by it";
+
+/// Write an archive with two files with the given options, of which the second is empty.
+fn archive(options: FileOptions) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = options.data_descriptor(true);
+    zip.start_file("a.txt", options).unwrap();
+    zip.write_all(DATA).unwrap();
+    zip.start_file("b.txt", options).unwrap();
+    zip.add_directory("dir", options).unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+/// The offsets of the data descriptors of the files in `bytes`
+fn descriptor_offsets(bytes: &[u8]) -> Vec<usize> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    (0..2)
+        .map(|i| {
+            let file = archive.by_index(i).unwrap();
+            (file.data_start() + file.compressed_size()) as usize
+        })
+        .collect()
+}
+
+/// Remove the optional signatures of the data descriptors from `bytes`, which leaves the central
+/// directory at the wrong offset, but is still readable as a stream.
+fn strip_signatures(bytes: &[u8]) -> Vec<u8> {
+    let mut stripped = bytes.to_vec();
+    for offset in descriptor_offsets(bytes).into_iter().rev() {
+        assert_eq!(stripped[offset..offset + 4], 0x08074b50u32.to_le_bytes());
+        stripped.drain(offset..offset + 4);
+    }
+    stripped
+}
+
+/// Read the files from the stream `bytes`, and check that the sizes are taken from the data
+/// descriptors.
+fn check_stream(bytes: &[u8]) {
+    let mut reader = bytes;
+    let mut file = read_zipfile_from_stream(&mut reader).unwrap().unwrap();
+    assert_eq!(file.name(), "a.txt");
+    assert_eq!(file.size(), 0);
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).unwrap();
+    assert_eq!(content, DATA);
+    assert_eq!(file.size(), DATA.len() as u64);
+    assert_eq!(file.crc32(), crc32fast::hash(DATA));
+    assert!(file.compressed_size() > 0);
+    drop(file);
+
+    // Dropping the file without reading it skips to the next one
+    let file = read_zipfile_from_stream(&mut reader).unwrap().unwrap();
+    assert_eq!(file.name(), "b.txt");
+    drop(file);
+
+    let mut file = read_zipfile_from_stream(&mut reader).unwrap().unwrap();
+    assert_eq!(file.name(), "dir/");
+    assert!(file.read(&mut [0]).unwrap() == 0);
+    drop(file);
+    assert!(read_zipfile_from_stream(&mut reader).unwrap().is_none());
+}
+
+#[test]
+fn write_data_descriptor() {
+    for &large_file in &[false, true] {
+        let bytes = archive(FileOptions::default().large_file(large_file));
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+        let mut content = Vec::new();
+        archive
+            .by_name("a.txt")
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, DATA);
+
+        // Bit 3 is set and the CRC-32 and sizes in the local header are 0, or in the ZIP64 extra
+        // field of large files
+        assert_eq!(bytes[6] & 1 << 3, 1 << 3);
+        let sizes = if large_file { [0xFF; 8] } else { [0; 8] };
+        assert_eq!(bytes[14..18], [0; 4]);
+        assert_eq!(bytes[18..26], sizes);
+        let file = archive.by_name("a.txt").unwrap();
+        let offset = (file.data_start() + file.compressed_size()) as usize;
+        let size_length = if large_file { 8 } else { 4 };
+        let descriptor = &bytes[offset..offset + 4 + 4 + 2 * size_length];
+        assert_eq!(descriptor[4..8], file.crc32().to_le_bytes());
+        assert_eq!(
+            descriptor[8..8 + size_length],
+            file.compressed_size().to_le_bytes()[..size_length]
+        );
+        assert_eq!(
+            descriptor[8 + size_length..],
+            (DATA.len() as u64).to_le_bytes()[..size_length]
+        );
+        drop(file);
+
+        // Directories don't get a data descriptor
+        let directory = archive.by_name("dir/").unwrap();
+        assert_eq!(bytes[directory.header_start() as usize + 6] & 1 << 3, 0);
+    }
+}
+
+#[test]
+fn stream_data_descriptor() {
+    for &large_file in &[false, true] {
+        let bytes = archive(FileOptions::default().large_file(large_file));
+        check_stream(&bytes);
+        check_stream(&strip_signatures(&bytes));
+    }
+}
+
+#[test]
+fn stream_invalid_data_descriptor() {
+    let mut bytes = archive(FileOptions::default());
+    let offset = descriptor_offsets(&bytes)[0];
+    // Uncompressed size
+    bytes[offset + 12] ^= 1;
+
+    let mut reader = &bytes[..];
+    let mut file = read_zipfile_from_stream(&mut reader).unwrap().unwrap();
+    assert!(file.read_to_end(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn stream_corrupt_data_descriptor_file() {
+    let mut bytes = archive(FileOptions::default());
+    let data_start = ZipArchive::new(Cursor::new(&bytes))
+        .unwrap()
+        .by_index(0)
+        .unwrap()
+        .data_start() as usize;
+    // A deflate block of the reserved type
+    bytes[data_start] = 0xFF;
+
+    let mut reader = &bytes[..];
+    let mut file = read_zipfile_from_stream(&mut reader).unwrap().unwrap();
+    assert!(file.read_to_end(&mut Vec::new()).is_err());
+    // Dropping the file doesn't panic, and the next file can't be found
+    drop(file);
+    assert!(read_zipfile_from_stream(&mut reader).is_err());
+
+    // Also when the file is dropped without being read
+    let mut reader = &bytes[..];
+    drop(read_zipfile_from_stream(&mut reader).unwrap().unwrap());
+    assert!(read_zipfile_from_stream(&mut reader).is_err());
+}
+
+#[test]
+fn stream_stored_data_descriptor() {
+    let bytes = archive(FileOptions::default().compression_method(CompressionMethod::Stored));
+    match read_zipfile_from_stream(&mut &bytes[..]).map(|_| ()) {
+        Err(ZipError::UnsupportedArchive(_)) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn zipcrypto_data_descriptor() {
+    let bytes = archive(FileOptions::default().with_password(b"password"));
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let mut content = Vec::new();
+    archive
+        .by_name_decrypt("a.txt", b"password")
+        .unwrap()
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap();
+    assert_eq!(content, DATA);
+    assert!(archive
+        .by_name_decrypt("a.txt", b"wrong password")
+        .unwrap()
+        .is_err());
+}
+
+#[test]
+fn raw_copy_streamed_data_descriptor() {
+    let bytes = archive(FileOptions::default());
+    let mut reader = &bytes[..];
+    let file = read_zipfile_from_stream(&mut reader).unwrap().unwrap();
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    match zip.raw_copy_file(file) {
+        Err(ZipError::UnsupportedArchive(_)) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+/// Check the archive written by [`stream_archive`]
+fn check_stream_archive(bytes: Vec<u8>) {
+    // The files are followed by data descriptors, so the archive can be read as a stream
+    check_stream(&bytes);
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let mut content = Vec::new();
+    let mut file = archive.by_name("a.txt").unwrap();
+    assert_eq!(file.compression(), CompressionMethod::Deflated);
+    file.read_to_end(&mut content).unwrap();
+    assert_eq!(content, DATA);
+}
+
+#[test]
+fn stream_writer() {
+    // A Vec<u8> cannot seek
+    let mut zip = ZipWriter::new_stream(Vec::new());
+    let options = FileOptions::default().store_if_incompressible(0.0);
+    zip.start_file("a.txt", options).unwrap();
+    zip.write_all(DATA).unwrap();
+    zip.start_file("b.txt", options).unwrap();
+    zip.add_directory("dir", options).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    check_stream_archive(bytes);
+}
+
+#[test]
+fn stream_writer_seek() {
+    use std::io::{Seek, SeekFrom};
+    use zip::write::StreamWriter;
+
+    let mut writer = StreamWriter::new(Vec::new());
+    writer.write_all(b"data").unwrap();
+    assert_eq!(writer.seek(SeekFrom::End(0)).unwrap(), 4);
+    assert_eq!(writer.seek(SeekFrom::Start(4)).unwrap(), 4);
+    assert!(writer.seek(SeekFrom::Start(0)).is_err());
+    assert!(writer.seek(SeekFrom::End(-1)).is_err());
+    assert_eq!(writer.into_inner(), b"data");
+}
+
+#[test]
+fn spooled_writer() {
+    use rand::{Rng, SeedableRng};
+
+    let mut incompressible = vec![0; 1000];
+    rand::rngs::StdRng::seed_from_u64(0).fill(&mut incompressible[..]);
+    let mut zip = ZipWriter::new_spooled(Vec::new(), Cursor::new(Vec::new()), 128);
+    let options = FileOptions::default().store_if_incompressible(0.95);
+    zip.start_file("a.txt", options).unwrap();
+    zip.write_all(DATA).unwrap();
+    zip.start_file("random.bin", options).unwrap();
+    zip.write_all(&incompressible).unwrap();
+    let bytes = zip.finish().unwrap().finish().unwrap();
+
+    // The local header of the incompressible file was rewritten in the spool
+    let mut archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+    for &(name, data, method) in &[("a.txt", DATA, 8), ("random.bin", &incompressible[..], 0)] {
+        let mut file = archive.by_name(name).unwrap();
+        assert_eq!(bytes[file.header_start() as usize + 8], method);
+        let mut content = Vec::new();
+        file.read_to_end(&mut content).unwrap();
+        assert_eq!(content, data);
+    }
+}
+
+#[test]
+fn spool_writer_seek() {
+    use std::io::{Seek, SeekFrom};
+    use zip::write::SpoolWriter;
+
+    let mut writer = SpoolWriter::new(Vec::new(), Cursor::new(Vec::new()), 4);
+    writer.write_all(b"abcdef").unwrap();
+    assert_eq!(writer.get_ref(), b"ab");
+    assert!(writer.seek(SeekFrom::Start(1)).is_err());
+    assert!(writer.seek(SeekFrom::End(1)).is_err());
+    assert_eq!(writer.seek(SeekFrom::End(-4)).unwrap(), 2);
+    writer.write_all(b"C").unwrap();
+    assert_eq!(writer.seek(SeekFrom::End(0)).unwrap(), 6);
+    writer.write_all(b"g").unwrap();
+    assert_eq!(writer.finish().unwrap(), b"abCdefg");
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn async_stream_data_descriptor() {
+    use futures::io::AsyncReadExt;
+    use zip::read::read_zipfile_from_stream_async;
+
+    for &large_file in &[false, true] {
+        let bytes = archive(FileOptions::default().large_file(large_file));
+        for bytes in &[bytes.clone(), strip_signatures(&bytes)] {
+            let mut reader = futures::io::Cursor::new(bytes);
+            for &(name, data) in &[("a.txt", DATA), ("b.txt", &b""[..])] {
+                let mut file = read_zipfile_from_stream_async(&mut reader)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(file.name(), name);
+                let mut content = Vec::new();
+                file.read_to_end(&mut content).await.unwrap();
+                assert_eq!(content, data);
+                assert_eq!(file.size(), data.len() as u64);
+                assert_eq!(file.crc32(), crc32fast::hash(data));
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+#![cfg(feature = "deflate")]
+
+use std::io::{Cursor, Read, Write};
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const DICTIONARY: &[u8] =
+    br#"{"language": "en", "key": "menu.", "value": "", "context": "main menu"}"#;
+
+fn entry(i: usize) -> String {
+    format!(
+        r#"{{"language": "en", "key": "menu.item{}", "value": "Item {}", "context": "main menu"}}"#,
+        i, i
+    )
+}
+
+fn write(entries: &[String], dictionary: Option<&[u8]>) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let mut options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    if let Some(dictionary) = dictionary {
+        options = options.deflate_dictionary(zip.register_deflate_dictionary(dictionary));
+    }
+    for (i, content) in entries.iter().enumerate() {
+        zip.start_file(format!("{}.json", i), options).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+fn compressed_size(bytes: Vec<u8>) -> u64 {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    (0..archive.len())
+        .map(|i| archive.by_index_raw(i).unwrap().compressed_size())
+        .sum()
+}
+
+fn read_all(archive: &mut ZipArchive<Cursor<Vec<u8>>>) -> Vec<String> {
+    (0..archive.len())
+        .map(|i| {
+            let mut content = String::new();
+            archive
+                .by_index(i)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            content
+        })
+        .collect()
+}
+
+#[test]
+fn dictionary_roundtrip() {
+    let entries: Vec<_> = (0..20).map(entry).collect();
+    let bytes = write(&entries, Some(DICTIONARY));
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+    archive.register_deflate_dictionary(DICTIONARY);
+    assert_eq!(read_all(&mut archive), entries);
+
+    assert!(compressed_size(bytes) < compressed_size(write(&entries, None)));
+}
+
+#[test]
+fn dictionary_not_registered() {
+    let bytes = write(&[entry(0)], Some(DICTIONARY));
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    match archive.by_index(0).map(|_| ()) {
+        Err(ZipError::UnsupportedArchive(_)) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+
+    archive.register_deflate_dictionary(b"another dictionary");
+    assert!(archive.by_index(0).is_err());
+}
+
+#[test]
+fn large_dictionary_and_entry() {
+    // Only the end of the dictionary fits in the window
+    let mut dictionary = vec![b'x'; 100_000];
+    dictionary.extend_from_slice(DICTIONARY);
+    let mut content = String::new();
+    for i in 0..10_000 {
+        content.push_str(&entry(i));
+    }
+    let entries = vec![content];
+    let bytes = write(&entries, Some(&dictionary));
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    archive.register_deflate_dictionary(&dictionary);
+    assert_eq!(read_all(&mut archive), entries);
+}
+
+#[test]
+fn dictionary_of_another_writer() {
+    let mut other = ZipWriter::new(Cursor::new(Vec::new()));
+    let id = other.register_deflate_dictionary(DICTIONARY);
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .deflate_dictionary(id);
+    assert!(zip.start_file("a.json", options).is_err());
+}
+
+#[test]
+fn dictionary_ignored_by_other_methods() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let id = zip.register_deflate_dictionary(DICTIONARY);
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .deflate_dictionary(id);
+    zip.start_file("a.json", options).unwrap();
+    zip.write_all(entry(0).as_bytes()).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(read_all(&mut archive), [entry(0)]);
+}
@@ -52,6 +52,161 @@ fn copy() {
     check_zip_file_contents(&mut tgt_archive, COPY_ENTRY_NAME);
 }
 
+// This test asserts that a renamed raw copy keeps the mode of the file, and has the new name in
+// its local header too.
+#[test]
+fn copy_rename() {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().unix_permissions(0o755);
+    zip.start_file("script.sh", options).unwrap();
+    zip.write_all(b"#!/bin/sh").unwrap();
+    zip.add_directory("dir", FileOptions::default()).unwrap();
+    let mut src_archive = zip::ZipArchive::new(zip.finish().unwrap()).unwrap();
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    zip.raw_copy_file_rename(src_archive.by_index(0).unwrap(), "bin/run.sh")
+        .unwrap();
+    zip.raw_copy_file_rename(src_archive.by_index(1).unwrap(), "renamed/")
+        .unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut tgt_archive = zip::ZipArchive::new(Cursor::new(&bytes)).unwrap();
+    let file = tgt_archive.by_name("bin/run.sh").unwrap();
+    assert_eq!(file.unix_mode(), Some(0o100755));
+    drop(file);
+    assert_eq!(
+        tgt_archive.by_name("renamed/").unwrap().unix_mode(),
+        src_archive.by_index(1).unwrap().unix_mode()
+    );
+
+    let mut reader = &bytes[..];
+    let mut file = zip::read::read_zipfile_from_stream(&mut reader)
+        .unwrap()
+        .unwrap();
+    assert_eq!(file.name(), "bin/run.sh");
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "#!/bin/sh");
+}
+
+#[test]
+fn archive_from_entries() {
+    let options = FileOptions::default();
+    let bytes = zip::ZipWriter::archive_from_entries(vec![
+        ("test/", &b""[..], options),
+        (ENTRY_NAME, LOREM_IPSUM, options),
+        (
+            "test/stored.txt",
+            b"stored",
+            options.compression_method(CompressionMethod::Stored),
+        ),
+    ])
+    .unwrap();
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.len(), 3);
+    assert!(archive.by_name("test/").unwrap().is_dir());
+    check_zip_file_contents(&mut archive, ENTRY_NAME);
+    let mut contents = Vec::new();
+    archive
+        .by_name("test/stored.txt")
+        .unwrap()
+        .read_to_end(&mut contents)
+        .unwrap();
+    assert_eq!(contents, b"stored");
+
+    assert!(zip::ZipWriter::archive_from_entries(vec![("dir/", b"data", options)]).is_err());
+}
+
+#[test]
+fn version_needed() {
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut entries = vec![
+        ("stored", stored, (1, 0)),
+        ("dir/", stored, (2, 0)),
+        ("deflated", FileOptions::default(), (2, 0)),
+        ("encrypted", stored.with_password(b"password"), (2, 0)),
+        ("large", stored.large_file(true), (4, 5)),
+    ];
+    #[cfg(feature = "bzip2")]
+    entries.push((
+        "bzip2",
+        FileOptions::default().compression_method(CompressionMethod::Bzip2),
+        (4, 6),
+    ));
+    #[cfg(feature = "aes-crypto")]
+    entries.push((
+        "aes",
+        stored.with_aes_encryption(zip::AesMode::Aes256, b"password"),
+        (5, 1),
+    ));
+    let bytes = zip::ZipWriter::archive_from_entries(
+        entries
+            .iter()
+            .map(|&(name, options, _)| (name, &b""[..], options)),
+    )
+    .unwrap();
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+    for (i, &(name, _, version)) in entries.iter().enumerate() {
+        let file = archive.by_index_raw(i).unwrap();
+        assert_eq!(file.name(), name);
+        assert_eq!(file.version_needed(), version, "{}", name);
+    }
+}
+
+#[test]
+fn system() {
+    use zip::write::DefaultOptions;
+    use zip::System;
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_default_options(DefaultOptions::default().system(System::Ntfs));
+    let options = FileOptions::default();
+    zip.start_file("ntfs", options.unix_permissions(0o444))
+        .unwrap();
+    zip.add_directory("ntfs_dir", options).unwrap();
+    zip.start_file("dos", options.system(System::Dos)).unwrap();
+    zip.start_file("unix", options.system(System::Unix).unix_permissions(0o600))
+        .unwrap();
+    let mut archive = zip::ZipArchive::new(zip.finish().unwrap()).unwrap();
+
+    let expected = [
+        ("ntfs", System::Ntfs, 0o100444),
+        ("ntfs_dir/", System::Ntfs, 0o40775),
+        ("dos", System::Dos, 0o100664),
+        ("unix", System::Unix, 0o100600),
+    ];
+    for &(name, system, mode) in &expected {
+        let file = archive.by_name(name).unwrap();
+        assert_eq!(file.system(), system);
+        assert_eq!(file.unix_mode(), Some(mode), "{}", name);
+    }
+}
+
+#[test]
+fn stream_entries() {
+    let mut file = Cursor::new(Vec::new());
+    write_to_zip(&mut file).expect("file written");
+    let mut archive = zip::ZipArchive::new(file.clone()).unwrap();
+
+    let records = zip::ZipArchive::stream_entries(file).unwrap();
+    assert_eq!(records.size_hint(), (0, Some(archive.len())));
+    let records: Vec<_> = records.map(Result::unwrap).collect();
+    assert_eq!(records.len(), archive.len());
+    for (i, record) in records.iter().enumerate() {
+        let file = archive.by_index(i).unwrap();
+        assert_eq!(record.name(), file.name());
+        assert_eq!(record.compression(), file.compression());
+        assert_eq!(record.compressed_size(), file.compressed_size());
+        assert_eq!(record.size(), file.size());
+        assert_eq!(record.crc32(), file.crc32());
+        assert_eq!(record.is_dir(), file.is_dir());
+        assert_eq!(record.header_start(), file.header_start());
+    }
+    assert!(records[0].is_dir());
+}
+
 #[cfg(feature = "async")]
 #[async_test]
 async fn async_end_to_end() {
@@ -68,6 +223,93 @@ async fn async_end_to_end() {
     assert!(file_contents.as_bytes() == LOREM_IPSUM);
 }
 
+#[cfg(feature = "async")]
+#[async_test]
+async fn async_entries_stream() {
+    use futures::StreamExt;
+
+    let mut file = Cursor::new(Vec::new());
+    write_to_zip(&mut file).expect("file written");
+    let file = futures::io::Cursor::new(file.into_inner());
+    let archive = zip::AsyncZipArchive::new(file).await.unwrap();
+
+    let names: Vec<_> = archive
+        .entries_stream()
+        .map(|record| record.name().to_owned())
+        .collect()
+        .await;
+    assert_eq!(names, ["test/", "test/☃.txt", ENTRY_NAME]);
+
+    let sizes = std::sync::atomic::AtomicU64::new(0);
+    archive
+        .entries_stream()
+        .for_each_concurrent(None, |record| {
+            sizes.fetch_add(record.size(), std::sync::atomic::Ordering::SeqCst);
+            futures::future::ready(())
+        })
+        .await;
+    let expected = 14 + LOREM_IPSUM.len() as u64;
+    assert_eq!(sizes.into_inner(), expected);
+}
+
+#[cfg(feature = "async")]
+#[async_test]
+async fn async_cancellation() {
+    let mut file = Cursor::new(Vec::new());
+    write_to_zip(&mut file).expect("file written");
+    let file = futures::io::Cursor::new(file.into_inner());
+    let mut archive = zip::AsyncZipArchive::new(file).await.unwrap();
+
+    // Dropping a file that was partly read leaves the archive usable
+    let mut start = [0; 4];
+    let mut entry = archive.by_name(ENTRY_NAME).await.unwrap();
+    entry.read_exact(&mut start).await.unwrap();
+    drop(entry);
+
+    let cancellation = zip::read::Cancellation::new();
+    archive.set_cancellation(cancellation.clone());
+    cancellation.cancel();
+    match archive.extract_to_map(u64::MAX).await {
+        Err(zip::result::ZipError::Cancelled) => (),
+        result => panic!("unexpected result {:?}", result.map(|files| files.len())),
+    }
+
+    archive.set_cancellation(zip::read::Cancellation::new());
+    let files = archive.extract_to_map(u64::MAX).await.unwrap();
+    assert_eq!(files[ENTRY_NAME], LOREM_IPSUM);
+}
+
+#[cfg(feature = "async")]
+#[async_test]
+async fn async_buf_read() {
+    use futures::AsyncBufReadExt;
+
+    let mut file = Cursor::new(Vec::new());
+    write_to_zip(&mut file).expect("file written");
+    let file = futures::io::Cursor::new(file.into_inner());
+    let mut archive = zip::AsyncZipArchive::new(file).await.unwrap();
+
+    let mut entry = archive.by_name(ENTRY_NAME).await.unwrap();
+    let mut line = String::new();
+    entry.read_line(&mut line).await.unwrap();
+    let mut rest = Vec::new();
+    entry.read_to_end(&mut rest).await.unwrap();
+    assert_eq!([line.as_bytes(), &rest].concat(), LOREM_IPSUM);
+    assert!(line.len() < LOREM_IPSUM.len());
+    assert!(line.ends_with('\n'));
+}
+
+#[cfg(feature = "async")]
+#[async_test]
+async fn async_write_end_to_end() {
+    let file = futures::io::Cursor::new(Vec::new());
+
+    let file = write_to_zip_async(file).await.expect("file written");
+    let file = &mut Cursor::new(file.into_inner());
+
+    check_zip_contents(file, ENTRY_NAME);
+}
+
 fn write_to_zip(file: &mut Cursor<Vec<u8>>) -> zip::result::ZipResult<()> {
     let mut zip = zip::ZipWriter::new(file);
 
@@ -86,6 +328,28 @@ fn write_to_zip(file: &mut Cursor<Vec<u8>>) -> zip::result::ZipResult<()> {
     Ok(())
 }
 
+#[cfg(feature = "async")]
+async fn write_to_zip_async(
+    file: futures::io::Cursor<Vec<u8>>,
+) -> zip::result::ZipResult<futures::io::Cursor<Vec<u8>>> {
+    use futures::AsyncWriteExt;
+
+    let mut zip = zip::write::AsyncZipWriter::new(file);
+
+    zip.add_directory("test/", Default::default()).await?;
+
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .unix_permissions(0o755);
+    zip.start_file("test/☃.txt", options).await?;
+    zip.write_all(b"Hello, World!\n").await?;
+
+    zip.start_file(ENTRY_NAME, Default::default()).await?;
+    zip.write_all(LOREM_IPSUM).await?;
+
+    zip.finish_into_inner().await
+}
+
 fn read_zip<R: Read + Seek>(zip_file: R) -> zip::result::ZipResult<zip::ZipArchive<R>> {
     let archive = zip::ZipArchive::new(zip_file).unwrap();
 
@@ -146,3 +410,89 @@ inceptos himenaeos. Maecenas feugiat velit in ex ultrices scelerisque id id nequ
 const ENTRY_NAME: &str = "test/lorem_ipsum.txt";
 
 const COPY_ENTRY_NAME: &str = "test/lorem_ipsum_renamed.txt";
+
+#[test]
+fn archive_options() {
+    use zip::options::{ArchiveOptions, DefaultOptions, NameOptions};
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_options(
+        ArchiveOptions::default()
+            .name_options(NameOptions::strict())
+            .default_options(DefaultOptions::default().file_permissions(0o600))
+            .central_directory_checksum(true),
+    );
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    assert!(zip.start_file("a.txt", FileOptions::default()).is_err());
+    let mut archive = zip::ZipArchive::new(zip.finish().unwrap()).unwrap();
+    assert_eq!(archive.by_index(0).unwrap().unix_mode(), Some(0o100600));
+    assert!(archive.zip64_extensible_data().is_some());
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_options(
+        ArchiveOptions::default()
+            .forbid_zip64(true)
+            .central_directory_checksum(true),
+    );
+    assert!(zip.finish().is_err());
+}
+
+// This test asserts that entries are compared by their contents, whatever their compression.
+#[test]
+fn content_eq() {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let contents: Vec<u8> = (0..20000u32).map(|i| (i % 251) as u8).collect();
+    for &(name, method) in &[
+        ("stored", CompressionMethod::Stored),
+        ("deflated", CompressionMethod::Deflated),
+    ] {
+        zip.start_file(name, FileOptions::default().compression_method(method))
+            .unwrap();
+        zip.write_all(&contents).unwrap();
+    }
+    zip.start_file("other", FileOptions::default()).unwrap();
+    zip.write_all(&contents[1..]).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+    let mut other_archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let mut stored = archive.by_name("stored").unwrap();
+    assert!(stored
+        .content_eq(&mut other_archive.by_name("deflated").unwrap())
+        .unwrap());
+    drop(stored);
+    let mut stored = archive.by_name("stored").unwrap();
+    assert!(!stored
+        .content_eq(&mut other_archive.by_name("other").unwrap())
+        .unwrap());
+    drop(stored);
+
+    let hashed = archive
+        .by_name("deflated")
+        .unwrap()
+        .content_hash(Vec::new())
+        .unwrap();
+    assert_eq!(hashed, contents);
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn deflate_threads() {
+    let contents: Vec<u8> = (0..5_000_000u32).map(|i| (i / 3 % 97) as u8).collect();
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("threads", FileOptions::default().deflate_threads(4))
+        .unwrap();
+    zip.write_all(&contents).unwrap();
+    zip.start_file("single", FileOptions::default()).unwrap();
+    zip.write_all(&contents).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let single_crc32 = archive.by_name("single").unwrap().crc32();
+    let mut file = archive.by_name("threads").unwrap();
+    assert_eq!(file.crc32(), single_crc32);
+    assert!(file.compressed_size() < contents.len() as u64 / 10);
+    let mut decompressed = Vec::new();
+    file.read_to_end(&mut decompressed).unwrap();
+    assert!(decompressed == contents);
+}
@@ -0,0 +1,109 @@
+use std::io::{self, Cursor, Read, Write};
+use zip::hook::ReadHook;
+use zip::read::{Config, StoredSizePolicy, ValidationIssue};
+use zip::result::UncompressedSizeExceeded;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const CONTENT: &[u8] = b"the data of this entry is longer than the archive says";
+
+/// Write an archive with one file, and then change its uncompressed size in the central directory
+fn archive(method: CompressionMethod, declared_size: u32) -> Vec<u8> {
+    patched_archive(method, 24, declared_size)
+}
+
+/// Write an archive with one file, and then change the size at `offset` in its central header
+fn patched_archive(method: CompressionMethod, offset: usize, size: u32) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(method);
+    zip.start_file("bomb.txt", options).unwrap();
+    zip.write_all(CONTENT).unwrap();
+    let mut bytes = zip.finish().unwrap().into_inner();
+
+    let central_header = bytes
+        .windows(4)
+        .position(|window| window == b"PK\x01\x02")
+        .unwrap();
+    bytes[central_header + offset..central_header + offset + 4]
+        .copy_from_slice(&size.to_le_bytes());
+    bytes
+}
+
+fn read(bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let mut file = archive.by_index(0).unwrap();
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+#[test]
+fn declared_size_exceeded() {
+    for &method in &[CompressionMethod::Stored, CompressionMethod::Deflated] {
+        let error = read(archive(method, 10)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error = error
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<UncompressedSizeExceeded>())
+            .unwrap();
+        assert_eq!(error.name, "bomb.txt");
+        assert_eq!(error.declared_size, 10);
+    }
+}
+
+#[test]
+fn declared_size_matches() {
+    for &method in &[CompressionMethod::Stored, CompressionMethod::Deflated] {
+        let bytes = archive(method, CONTENT.len() as u32);
+        assert_eq!(read(bytes).unwrap(), CONTENT);
+    }
+}
+
+#[test]
+fn stored_size_policy() {
+    let long = CONTENT.len() as u32 + 5;
+    let wrong_compressed = patched_archive(CompressionMethod::Stored, 20, long);
+    let wrong_uncompressed = archive(CompressionMethod::Stored, 10);
+    for (bytes, policy) in &[
+        (wrong_compressed, StoredSizePolicy::TrustUncompressed),
+        (wrong_uncompressed, StoredSizePolicy::TrustCompressed),
+    ] {
+        let zip = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(zip.validate().len(), 1);
+        assert!(read(bytes.clone()).is_err());
+
+        let config = Config::default().stored_sizes(*policy);
+        let mut zip = ZipArchive::new_with_config(Cursor::new(bytes.clone()), config).unwrap();
+        let mut file = zip.by_index(0).unwrap();
+        assert_eq!(file.size(), CONTENT.len() as u64);
+        let mut content = Vec::new();
+        file.read_to_end(&mut content).unwrap();
+        assert_eq!(content, CONTENT);
+    }
+
+    // An entry with adjusted sizes is not skipped to its end when it is dropped, which could not
+    // be done through a hook
+    struct Unchanged;
+    impl ReadHook for Unchanged {
+        fn wrap_reader<'a>(&self, _: &str, reader: Box<dyn Read + 'a>) -> Box<dyn Read + 'a> {
+            reader
+        }
+    }
+    let bytes = patched_archive(CompressionMethod::Stored, 20, long);
+    let config = Config::default().stored_sizes(StoredSizePolicy::TrustUncompressed);
+    let mut zip = ZipArchive::new_with_config(Cursor::new(bytes.clone()), config).unwrap();
+    zip.set_read_hook(Unchanged);
+    zip.by_index(0).unwrap().read_exact(&mut [0; 4]).unwrap();
+
+    let zip = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(
+        zip.validate(),
+        vec![ValidationIssue::StoredSizeMismatch {
+            index: 0,
+            compressed_size: long as u64,
+            uncompressed_size: CONTENT.len() as u64,
+        }]
+    );
+    let zip = ZipArchive::new(Cursor::new(archive(CompressionMethod::Deflated, 10))).unwrap();
+    assert!(zip.validate().is_empty());
+}
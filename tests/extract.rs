@@ -0,0 +1,613 @@
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+use zip::hook::ReadHook;
+use zip::options::{Config, ExtractOptions, OverwritePolicy};
+use zip::read::{ExtractOutcome, ExtractWarning};
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+fn target_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zip-extract-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn archive() -> ZipArchive<Cursor<Vec<u8>>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.add_directory("dir", FileOptions::default()).unwrap();
+    zip.start_file("dir/a.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"first").unwrap();
+    zip.start_file("b.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"second file").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    ZipArchive::new(Cursor::new(bytes)).unwrap()
+}
+
+#[test]
+fn extract_summary() {
+    let dir = target_dir("summary");
+    let summary = archive().extract(&dir).unwrap();
+    assert_eq!(summary.files_written(), 2);
+    assert_eq!(summary.directories_created(), 1);
+    assert_eq!(summary.bytes_written(), 16);
+    assert_eq!(summary.entries_skipped(), 0);
+    assert!(summary.warnings().is_empty());
+    assert_eq!(fs::read(dir.join("dir/a.txt")).unwrap(), b"first");
+
+    let summary = archive().extract(&dir).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        summary.warnings(),
+        [
+            ExtractWarning::Overwritten(dir.join("dir/a.txt")),
+            ExtractWarning::Overwritten(dir.join("b.txt")),
+        ]
+    );
+}
+
+#[test]
+fn extract_filtered() {
+    let dir = target_dir("filtered");
+    let summary = archive()
+        .extract_filtered(&dir, |file| file.name() != "b.txt")
+        .unwrap();
+    let b_exists = dir.join("b.txt").exists();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(summary.files_written(), 1);
+    assert_eq!(summary.bytes_written(), 5);
+    assert_eq!(summary.entries_skipped(), 1);
+    assert!(!b_exists);
+}
+
+#[test]
+fn extract_with_options() {
+    let dir = target_dir("options");
+    fs::create_dir_all(dir.join("dir")).unwrap();
+    fs::write(dir.join("dir/a.txt"), b"kept").unwrap();
+
+    let options = ExtractOptions::default().overwrite(OverwritePolicy::Skip);
+    let summary = archive().extract_with_options(&dir, &options).unwrap();
+    assert_eq!(summary.files_written(), 1);
+    assert_eq!(summary.entries_skipped(), 1);
+    assert_eq!(fs::read(dir.join("dir/a.txt")).unwrap(), b"kept");
+
+    let options = options.overwrite(OverwritePolicy::Error);
+    match archive().extract_with_options(&dir, &options) {
+        Err(ZipError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::AlreadyExists),
+        result => panic!("unexpected result {:?}", result),
+    }
+    fs::remove_dir_all(&dir).unwrap();
+
+    let options = ExtractOptions::default().size_limit(Some(15));
+    match archive().extract_with_options(&dir, &options) {
+        Err(ZipError::SizeLimitExceeded(Some(name))) => assert_eq!(name, "b.txt"),
+        result => panic!("unexpected result {:?}", result),
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn extract_renamed() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for &(name, content) in &[("a.txt", "first"), ("a.txt", "second"), ("README", "third")] {
+        zip.start_file(name, FileOptions::default()).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let dir = target_dir("renamed");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.txt"), b"kept").unwrap();
+    fs::write(dir.join("README"), b"kept").unwrap();
+    let options = ExtractOptions::default().overwrite(OverwritePolicy::Rename);
+    let summary = ZipArchive::new(Cursor::new(bytes))
+        .unwrap()
+        .extract_with_options(&dir, &options)
+        .unwrap();
+    let contents: Vec<_> = ["a.txt", "a (1).txt", "a (2).txt", "README (1)"]
+        .iter()
+        .map(|name| fs::read_to_string(dir.join(name)).unwrap())
+        .collect();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(summary.files_written(), 3);
+    assert_eq!(contents, ["kept", "first", "second", "third"]);
+    assert_eq!(
+        summary.warnings()[1],
+        ExtractWarning::Renamed {
+            path: dir.join("a.txt"),
+            renamed_to: dir.join("a (2).txt"),
+        }
+    );
+}
+
+#[test]
+fn time_limit() {
+    let bytes = archive().into_inner().into_inner();
+    let config = Config::default().time_limit(Some(Duration::from_secs(0)));
+    match ZipArchive::new_with_config(Cursor::new(bytes.clone()), config) {
+        Err(ZipError::TimeLimitExceeded) => {}
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+
+    let config = Config::default().time_limit(Some(Duration::from_secs(3600)));
+    let mut archive = ZipArchive::new_with_config(Cursor::new(bytes), config).unwrap();
+    let dir = target_dir("time-limit");
+    let summary = archive.extract(&dir).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(summary.files_written(), 2);
+    assert_eq!(archive.extract_to_map(1024).unwrap().len(), 2);
+}
+
+#[test]
+fn extract_invalid_names() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("../outside.txt", FileOptions::default())
+        .unwrap();
+    zip.start_file("inside.txt", FileOptions::default())
+        .unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let dir = target_dir("invalid-names");
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert!(archive.extract(&dir).is_err());
+    let options = ExtractOptions::default().skip_invalid_names(true);
+    let summary = archive.extract_with_options(&dir, &options).unwrap();
+    let inside_exists = dir.join("inside.txt").exists();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(summary.entries_skipped(), 1);
+    assert!(inside_exists);
+}
+
+#[test]
+fn extract_windows_names() {
+    // Names that only escape the directory on Windows are invalid everywhere
+    let names = [
+        "..\\outside.txt",
+        "a\\..\\..\\outside.txt",
+        "C:/outside.txt",
+        "\\outside.txt",
+    ];
+    for name in &names {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file(*name, FileOptions::default()).unwrap();
+        let mut archive = ZipArchive::new(zip.finish().unwrap()).unwrap();
+        let dir = target_dir("windows-names");
+        assert!(archive.extract(&dir).is_err(), "{}", name);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("C:\\outside.txt", FileOptions::default())
+        .unwrap();
+    zip.add_directory("dir", FileOptions::default()).unwrap();
+    zip.start_file("dir\\..\\inside.txt", FileOptions::default())
+        .unwrap();
+    zip.write_all(b"inside").unwrap();
+    let mut archive = ZipArchive::new(zip.finish().unwrap()).unwrap();
+    let dir = target_dir("windows-names-skipped");
+    let options = ExtractOptions::default().skip_invalid_names(true);
+    let summary = archive.extract_with_options(&dir, &options).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let outcomes: Vec<_> = summary
+        .entries()
+        .iter()
+        .map(|entry| (entry.index(), entry.name(), entry.outcome().clone()))
+        .collect();
+    assert_eq!(
+        outcomes,
+        [
+            (0, "C:\\outside.txt", ExtractOutcome::InvalidName),
+            (1, "dir/", ExtractOutcome::Directory(dir.join("dir/"))),
+            (
+                2,
+                "dir\\..\\inside.txt",
+                ExtractOutcome::File {
+                    path: dir.join("dir\\..\\inside.txt"),
+                    size: 6,
+                }
+            ),
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn extract_permissions_hook() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().unix_permissions(0o777);
+    zip.start_file("script.sh", options).unwrap();
+    zip.start_file("kept.txt", options).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let dir = target_dir("permissions-hook");
+    let options = ExtractOptions::default().permissions_hook(|file| {
+        if file.name() == "kept.txt" {
+            return None;
+        }
+        // Only the owner may change the file
+        let mode = file.unix_mode()? & 0o755;
+        Some(fs::Permissions::from_mode(mode))
+    });
+    ZipArchive::new(Cursor::new(bytes))
+        .unwrap()
+        .extract_with_options(&dir, &options)
+        .unwrap();
+    let script = fs::metadata(dir.join("script.sh")).unwrap().permissions();
+    let kept = fs::metadata(dir.join("kept.txt")).unwrap().permissions();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(script.mode() & 0o7777, 0o755);
+    assert_ne!(kept.mode() & 0o7777, 0o777);
+}
+
+/// An archive with the symlinks `(name, target)` in `links` followed by the files
+/// `(name, contents)`
+#[cfg(unix)]
+fn links_archive(links: &[(&str, &str)], files: &[(&str, &str)]) -> ZipArchive<Cursor<Vec<u8>>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, contents) in links.iter().chain(files) {
+        zip.start_file(*name, FileOptions::default()).unwrap();
+        zip.write_all(contents.as_bytes()).unwrap();
+    }
+    let mut bytes = zip.finish().unwrap().into_inner();
+
+    // Mark the links as symlinks in the external attributes of their central headers
+    let central_headers: Vec<_> = bytes
+        .windows(4)
+        .enumerate()
+        .filter(|(_, window)| window == b"PK\x01\x02")
+        .map(|(start, _)| start)
+        .take(links.len())
+        .collect();
+    let attributes = 0o120777u32 << 16;
+    for start in central_headers {
+        bytes[start + 38..start + 42].copy_from_slice(&attributes.to_le_bytes());
+    }
+    ZipArchive::new(Cursor::new(bytes)).unwrap()
+}
+
+/// An archive with a symlink `link` to `target`, and a file that is written through it
+#[cfg(unix)]
+fn symlink_archive(target: &str) -> ZipArchive<Cursor<Vec<u8>>> {
+    links_archive(
+        &[("link", target)],
+        &[("dir/", ""), ("link/file.txt", "through the link")],
+    )
+}
+
+#[cfg(unix)]
+#[test]
+fn extract_symlinks() {
+    let dir = target_dir("symlinks");
+    let options = ExtractOptions::default().symlinks(true);
+    let summary = links_archive(&[("link", "dir")], &[("dir/", "")])
+        .extract_with_options(&dir, &options)
+        .unwrap();
+    let link = fs::read_link(dir.join("link")).unwrap();
+    assert_eq!(summary.symlinks_created(), 1);
+    assert_eq!(link, PathBuf::from("dir"));
+    fs::remove_dir_all(&dir).unwrap();
+
+    // Nothing is written through a symlink
+    match symlink_archive("dir").extract_with_options(&dir, &options) {
+        Err(ZipError::InvalidArchive("Extraction path goes through a symlink")) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+    assert!(!dir.join("dir/file.txt").exists());
+    fs::remove_dir_all(&dir).unwrap();
+
+    // Without the option, the symlink is a file that contains its target
+    symlink_archive("dir").extract(&dir).unwrap_err();
+    assert_eq!(fs::read(dir.join("link")).unwrap(), b"dir");
+    fs::remove_dir_all(&dir).unwrap();
+
+    for target in &["..", "/tmp", "dir/../.."] {
+        match symlink_archive(target).extract_with_options(&dir, &options) {
+            Err(ZipError::InvalidArchive("Invalid symlink target")) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(fs::symlink_metadata(dir.join("link")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // The target of a symlink goes through the symlinks before it
+    let chained = [("d1/d2/x", "../.."), ("d1/d2/y", "x/..")];
+    match links_archive(&chained, &[("d1/d2/y/escaped.txt", "escaped")])
+        .extract_with_options(&dir, &options)
+    {
+        Err(ZipError::InvalidArchive("Invalid symlink target")) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+    assert!(fs::symlink_metadata(dir.join("d1/d2/x")).is_ok());
+    assert!(fs::symlink_metadata(dir.join("d1/d2/y")).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+
+    let enclosed = [("d1/d2/x", "../.."), ("d1/d2/y", "x/d1")];
+    links_archive(&enclosed, &[])
+        .extract_with_options(&dir, &options)
+        .unwrap();
+    let target = fs::read_link(dir.join("d1/d2/y")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(target, PathBuf::from("x/d1"));
+}
+
+#[cfg(unix)]
+#[test]
+fn extract_through_existing_symlink() {
+    let dir = target_dir("existing-symlink");
+    let outside = target_dir("existing-symlink-outside");
+    fs::create_dir_all(&dir).unwrap();
+    fs::create_dir_all(&outside).unwrap();
+    std::os::unix::fs::symlink(&outside, dir.join("out")).unwrap();
+    std::os::unix::fs::symlink(outside.join("file.txt"), dir.join("file.txt")).unwrap();
+
+    let archive = links_archive(&[], &[("out/escaped.txt", "escaped")]);
+    match archive.clone().extract(&dir) {
+        Err(ZipError::InvalidArchive("Extraction path goes through a symlink")) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+    let archive = links_archive(&[], &[("out/", "")]);
+    assert!(archive.clone().extract(&dir).is_err());
+    #[cfg(feature = "parallel")]
+    {
+        let archive = links_archive(&[], &[("out/escaped.txt", "escaped")]);
+        let bytes = archive.into_inner().into_inner();
+        let archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+        assert!(archive
+            .extract_parallel(&dir, 2, || Ok(Cursor::new(bytes.clone())))
+            .is_err());
+    }
+
+    // A symlink in the place of a file is replaced
+    links_archive(&[], &[("file.txt", "replaced")])
+        .extract(&dir)
+        .unwrap();
+    assert_eq!(fs::read(dir.join("file.txt")).unwrap(), b"replaced");
+    let outside_files = fs::read_dir(&outside).unwrap().count();
+    fs::remove_dir_all(&dir).unwrap();
+    fs::remove_dir_all(&outside).unwrap();
+    assert_eq!(outside_files, 0);
+}
+
+#[test]
+fn extract_to_map() {
+    let files = archive().extract_to_map(16).unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files["dir/a.txt"], b"first");
+    assert_eq!(files["b.txt"], b"second file");
+
+    match archive().extract_to_map(15) {
+        Err(ZipError::SizeLimitExceeded(Some(name))) => assert_eq!(name, "b.txt"),
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn extract_to_map_hooked() {
+    struct Repeat;
+    impl ReadHook for Repeat {
+        fn wrap_reader<'a>(&self, _name: &str, reader: Box<dyn Read + 'a>) -> Box<dyn Read + 'a> {
+            Box::new(reader.chain(&b"repeated"[..]))
+        }
+    }
+
+    let mut archive = archive();
+    archive.set_read_hook(Repeat);
+    match archive.extract_to_map(10) {
+        Err(ZipError::SizeLimitExceeded(Some(name))) => assert_eq!(name, "dir/a.txt"),
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+/// An archive whose stored file is corrupted at the returned offset
+fn corrupted_archive() -> (ZipArchive<Cursor<Vec<u8>>>, usize) {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("b.txt", options).unwrap();
+    zip.write_all(b"second file").unwrap();
+    let mut bytes = zip.finish().unwrap().into_inner();
+    let offset = bytes
+        .windows(11)
+        .position(|data| data == b"second file")
+        .unwrap();
+    bytes[offset] = b'S';
+    (ZipArchive::new(Cursor::new(bytes)).unwrap(), offset)
+}
+
+#[test]
+fn crc_retry() {
+    let (mut archive, offset) = corrupted_archive();
+    assert!(archive.extract_to_map(16).is_err());
+
+    // Reading the file again gets the right data
+    archive.set_crc_retry(move |reader| {
+        reader.get_mut()[offset] = b's';
+        Ok(())
+    });
+    let files = archive.extract_to_map(16).unwrap();
+    assert_eq!(files["b.txt"], b"second file");
+
+    let (mut archive, offset) = corrupted_archive();
+    archive.set_crc_retry(move |reader| {
+        reader.get_mut()[offset] = b's';
+        Ok(())
+    });
+    let dir = target_dir("crc-retry");
+    archive.extract(&dir).unwrap();
+    assert_eq!(fs::read(dir.join("b.txt")).unwrap(), b"second file");
+    fs::remove_dir_all(&dir).unwrap();
+
+    // The data is still corrupted when it is read again
+    let (mut archive, _) = corrupted_archive();
+    archive.set_crc_retry(|_| Ok(()));
+    match archive.extract_to_map(16) {
+        Err(ZipError::Io(e)) => assert_eq!(e.to_string(), "Invalid checksum"),
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn validate_parallel() {
+    use zip::read::ValidationIssue;
+
+    let bytes = archive().into_inner().into_inner();
+    let archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+    let issues = archive
+        .validate_parallel(4, || Ok(Cursor::new(bytes.clone())))
+        .unwrap();
+    assert!(issues.is_empty());
+
+    let (archive, _) = corrupted_archive();
+    let bytes = archive.into_inner().into_inner();
+    let archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+    let issues = archive
+        .validate_parallel(2, || Ok(Cursor::new(bytes.clone())))
+        .unwrap();
+    assert_eq!(issues, [ValidationIssue::ChecksumMismatch { index: 0 }]);
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn validate_parallel_large_stored() {
+    use zip::read::ValidationIssue;
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("large", options).unwrap();
+    let block: Vec<u8> = (0..1 << 20).map(|i| (i % 251) as u8).collect();
+    for _ in 0..65 {
+        zip.write_all(&block).unwrap();
+    }
+    let mut bytes = zip.finish().unwrap().into_inner();
+
+    let archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+    let issues = archive
+        .validate_parallel(3, || Ok(Cursor::new(bytes.clone())))
+        .unwrap();
+    assert!(issues.is_empty());
+
+    bytes[40 << 20] ^= 1;
+    let archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+    let issues = archive
+        .validate_parallel(3, || Ok(Cursor::new(bytes.clone())))
+        .unwrap();
+    assert_eq!(issues, [ValidationIssue::ChecksumMismatch { index: 0 }]);
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn extract_parallel() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.add_directory("dir", FileOptions::default()).unwrap();
+    for i in 0..20 {
+        zip.start_file(format!("dir/{}.txt", i), FileOptions::default())
+            .unwrap();
+        zip.write_all(format!("file {}", i).as_bytes()).unwrap();
+    }
+    zip.start_file("dir/0.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"last").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    let archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+
+    let dir = target_dir("parallel");
+    let summary = archive
+        .extract_parallel(&dir, 4, || Ok(Cursor::new(bytes.clone())))
+        .unwrap();
+    assert_eq!(summary.files_written(), 20);
+    assert_eq!(summary.directories_created(), 1);
+    assert_eq!(
+        summary.warnings(),
+        [ExtractWarning::Overwritten(dir.join("dir/0.txt"))]
+    );
+    // The first dir/0.txt is not written
+    let indices: Vec<_> = summary
+        .entries()
+        .iter()
+        .map(|entry| entry.index())
+        .collect();
+    assert_eq!(indices, (0..22).filter(|&i| i != 1).collect::<Vec<_>>());
+    assert_eq!(fs::read(dir.join("dir/0.txt")).unwrap(), b"last");
+    for i in 1..20 {
+        let content = fs::read(dir.join(format!("dir/{}.txt", i))).unwrap();
+        assert_eq!(content, format!("file {}", i).as_bytes());
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+#[test]
+fn extract_io_uring() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.add_directory("dir", FileOptions::default()).unwrap();
+    // More files than fit in a batch
+    for i in 0..300 {
+        let options = FileOptions::default().unix_permissions(0o640);
+        zip.start_file(format!("dir/{}.txt", i), options).unwrap();
+        zip.write_all(format!("file {}", i).as_bytes()).unwrap();
+    }
+    zip.start_file("dir/0.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"last").unwrap();
+    zip.start_file("empty.txt", FileOptions::default()).unwrap();
+    zip.start_file("large.bin", FileOptions::default()).unwrap();
+    zip.write_all(&[0x42; 3 << 20]).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    // A file and a symlink are in the way
+    let dir = target_dir("io-uring");
+    let outside = target_dir("io-uring-outside");
+    fs::create_dir_all(&outside).unwrap();
+    fs::create_dir_all(dir.join("dir")).unwrap();
+    fs::write(dir.join("dir/1.txt"), b"old").unwrap();
+    std::os::unix::fs::symlink(outside.join("2.txt"), dir.join("dir/2.txt")).unwrap();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let summary = archive.extract_io_uring(&dir).unwrap();
+    assert_eq!(summary.files_written(), 302);
+    assert_eq!(summary.directories_created(), 1);
+    assert_eq!(summary.bytes_written(), 2288 + (3 << 20));
+    assert_eq!(
+        summary.warnings(),
+        [
+            ExtractWarning::Overwritten(dir.join("dir/0.txt")),
+            ExtractWarning::Overwritten(dir.join("dir/1.txt")),
+            ExtractWarning::Overwritten(dir.join("dir/2.txt")),
+        ]
+    );
+    // The first dir/0.txt is not written
+    assert_eq!(summary.entries().len(), 303);
+    assert_eq!(fs::read(dir.join("dir/0.txt")).unwrap(), b"last");
+    for i in 1..300 {
+        let path = dir.join(format!("dir/{}.txt", i));
+        assert_eq!(fs::read(&path).unwrap(), format!("file {}", i).as_bytes());
+        let mode = fs::symlink_metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+    assert_eq!(fs::read(dir.join("empty.txt")).unwrap(), b"");
+    assert_eq!(
+        fs::read(dir.join("large.bin")).unwrap(),
+        vec![0x42; 3 << 20]
+    );
+    let outside_files = fs::read_dir(&outside).unwrap().count();
+    fs::remove_dir_all(&dir).unwrap();
+    fs::remove_dir_all(&outside).unwrap();
+    assert_eq!(outside_files, 0);
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn async_extract_to_map() {
+    let bytes = archive().into_inner().into_inner();
+    let mut archive = zip::AsyncZipArchive::new(futures::io::Cursor::new(bytes))
+        .await
+        .unwrap();
+    let files = archive.extract_to_map(16).await.unwrap();
+    assert_eq!(files["b.txt"], b"second file");
+    assert!(archive.extract_to_map(4).await.is_err());
+}
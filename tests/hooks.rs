@@ -0,0 +1,138 @@
+use std::io::{self, Cursor, Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use zip::hook::{EntryStats, EntryTransform, ReadHook, WriteHook, WriteObserver};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+// Uppercases the contents of `.txt` files
+struct Uppercase;
+
+struct UppercaseTransform;
+
+impl WriteHook for Uppercase {
+    fn start_entry(&self, name: &str) -> Option<Box<dyn EntryTransform>> {
+        if name.ends_with(".txt") {
+            Some(Box::new(UppercaseTransform))
+        } else {
+            None
+        }
+    }
+}
+
+impl EntryTransform for UppercaseTransform {
+    fn write(&mut self, data: &[u8], output: &mut dyn Write) -> io::Result<()> {
+        output.write_all(&data.to_ascii_uppercase())
+    }
+
+    fn finish(self: Box<Self>, output: &mut dyn Write) -> io::Result<()> {
+        output.write_all(b"!")
+    }
+}
+
+// Counts the bytes read from all entries
+struct Counter(Arc<AtomicUsize>);
+
+struct CountingReader<'a>(Box<dyn Read + 'a>, Arc<AtomicUsize>);
+
+impl ReadHook for Counter {
+    fn wrap_reader<'a>(&self, _name: &str, reader: Box<dyn Read + 'a>) -> Box<dyn Read + 'a> {
+        Box::new(CountingReader(reader, self.0.clone()))
+    }
+}
+
+impl<'a> Read for CountingReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.0.read(buf)?;
+        self.1.fetch_add(count, Ordering::SeqCst);
+        Ok(count)
+    }
+}
+
+fn write_archive() -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_write_hook(Uppercase);
+    zip.start_file("hello.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"hello, ").unwrap();
+    zip.write_all(b"world").unwrap();
+    zip.start_file("hello.bin", FileOptions::default()).unwrap();
+    zip.write_all(b"hello, world").unwrap();
+    zip.add_directory("dir.txt", FileOptions::default())
+        .unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+fn read(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+    let mut contents = String::new();
+    archive
+        .by_name(name)
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    contents
+}
+
+#[test]
+fn write_hook_transforms_files() {
+    let mut archive = ZipArchive::new(Cursor::new(write_archive())).unwrap();
+    assert_eq!(read(&mut archive, "hello.txt"), "HELLO, WORLD!");
+    assert_eq!(archive.by_name("hello.txt").unwrap().size(), 13);
+    assert_eq!(read(&mut archive, "hello.bin"), "hello, world");
+    assert_eq!(read(&mut archive, "dir.txt/"), "");
+}
+
+#[test]
+fn read_hook_wraps_entries() {
+    let count = Arc::new(AtomicUsize::new(0));
+    let mut archive = ZipArchive::new(Cursor::new(write_archive())).unwrap();
+    archive.set_read_hook(Counter(count.clone()));
+    assert_eq!(read(&mut archive, "hello.txt"), "HELLO, WORLD!");
+    assert_eq!(read(&mut archive, "hello.bin"), "hello, world");
+    assert_eq!(count.load(Ordering::SeqCst), 25);
+
+    let mut raw = Vec::new();
+    archive
+        .by_index_raw(0)
+        .unwrap()
+        .read_to_end(&mut raw)
+        .unwrap();
+    assert_eq!(count.load(Ordering::SeqCst), 25);
+}
+
+// Records the stats of all files
+struct Recorder(Arc<Mutex<Vec<EntryStats>>>);
+
+impl WriteObserver for Recorder {
+    fn entry_written(&self, stats: &EntryStats) {
+        self.0.lock().unwrap().push(stats.clone());
+    }
+}
+
+#[test]
+fn write_observer_sees_files() {
+    let stats = Arc::new(Mutex::new(Vec::new()));
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_observer(Recorder(stats.clone()));
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("stored.txt", stored).unwrap();
+    zip.write_all(b"hello").unwrap();
+    zip.add_directory("dir", FileOptions::default()).unwrap();
+    zip.start_file("deflated.txt", FileOptions::default())
+        .unwrap();
+    zip.write_all(&[b'a'; 1000]).unwrap();
+    zip.start_file("empty.txt", FileOptions::default()).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let stats = stats.lock().unwrap();
+    let names: Vec<_> = stats.iter().map(EntryStats::name).collect();
+    assert_eq!(names, ["stored.txt", "deflated.txt", "empty.txt"]);
+    assert_eq!(stats[0].compression(), CompressionMethod::Stored);
+    assert_eq!(stats[0].ratio(), 1.0);
+    let deflated = archive.by_name("deflated.txt").unwrap();
+    assert_eq!(stats[1].compression(), CompressionMethod::Deflated);
+    assert_eq!(stats[1].size(), 1000);
+    assert_eq!(stats[1].compressed_size(), deflated.compressed_size());
+    assert!(stats[1].ratio() < 0.1);
+    assert_eq!(stats[2].size(), 0);
+}
@@ -0,0 +1,65 @@
+#![cfg(all(feature = "http", feature = "deflate"))]
+
+use futures::stream::TryStreamExt;
+use std::io::{Cursor, Read, Write};
+use zip::http::{HttpEncoding, HttpEntry};
+use zip::write::FileOptions;
+use zip::{AsyncZipArchive, CompressionMethod, ZipWriter};
+
+const CONTENTS: &str = "<p>Hello, world!</p>";
+
+async fn archive() -> AsyncZipArchive<futures::io::Cursor<Vec<u8>>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for &(name, method) in &[
+        ("stored.html", CompressionMethod::Stored),
+        ("deflated.html", CompressionMethod::Deflated),
+    ] {
+        let options = FileOptions::default().compression_method(method);
+        zip.start_file(name, options).unwrap();
+        zip.write_all(CONTENTS.repeat(100).as_bytes()).unwrap();
+    }
+    let bytes = zip.finish().unwrap().into_inner();
+    AsyncZipArchive::new(futures::io::Cursor::new(bytes))
+        .await
+        .unwrap()
+}
+
+async fn body(entry: HttpEntry) -> Vec<u8> {
+    let chunks: Vec<Vec<u8>> = entry.try_collect().await.unwrap();
+    chunks.concat()
+}
+
+#[futures_await_test::async_test]
+async fn identity() {
+    let archive = archive().await;
+    for &name in &["stored.html", "deflated.html"] {
+        let entry = HttpEntry::new(archive.clone(), name, HttpEncoding::Identity)
+            .await
+            .unwrap();
+        assert_eq!(entry.content_length(), CONTENTS.len() as u64 * 100);
+        assert_eq!(entry.content_encoding(), None);
+        assert_eq!(body(entry).await, CONTENTS.repeat(100).as_bytes());
+    }
+}
+
+#[futures_await_test::async_test]
+async fn deflate() {
+    let archive = archive().await;
+    let entry = HttpEntry::new(archive.clone(), "deflated.html", HttpEncoding::Deflate)
+        .await
+        .unwrap();
+    assert_eq!(entry.content_encoding(), Some("deflate"));
+    let content_length = entry.content_length();
+    let deflated = body(entry).await;
+    assert_eq!(deflated.len() as u64, content_length);
+    let mut contents = String::new();
+    flate2::read::DeflateDecoder::new(&deflated[..])
+        .read_to_string(&mut contents)
+        .unwrap();
+    assert_eq!(contents, CONTENTS.repeat(100));
+
+    let entry = HttpEntry::new(archive, "stored.html", HttpEncoding::Deflate)
+        .await
+        .unwrap();
+    assert_eq!(entry.content_encoding(), None);
+}
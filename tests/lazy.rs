@@ -0,0 +1,87 @@
+use std::io::{Cursor, Read, Write};
+use zip::read::{Config, LazyZipArchive, RecordCountPolicy};
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+/// Write an archive with files named after their index, and a second file named `0.txt`.
+fn archive() -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_comment("lazy");
+    for i in 0..100 {
+        let options = FileOptions::default();
+        zip.start_file(format!("{}.txt", i), options).unwrap();
+        write!(zip, "contents of {}", i).unwrap();
+    }
+    zip.add_directory("dir", FileOptions::default()).unwrap();
+    zip.start_file("0.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"the last 0.txt").unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+#[test]
+fn lazy() {
+    let bytes = archive();
+    let mut eager = ZipArchive::new(Cursor::new(&bytes)).unwrap();
+    let mut lazy = LazyZipArchive::new(Cursor::new(&bytes)).unwrap();
+    assert_eq!(lazy.len(), eager.len());
+    assert_eq!(lazy.claimed_len(), eager.len());
+    assert_eq!(lazy.comment(), b"lazy");
+
+    // In reverse, so that no record is the one that was parsed before
+    for i in (0..eager.len()).rev() {
+        let record = lazy.record(i).unwrap();
+        let mut expected = eager.by_index(i).unwrap();
+        assert_eq!(record.name(), expected.name());
+        assert_eq!(record.size(), expected.size());
+        assert_eq!(record.crc32(), expected.crc32());
+        assert_eq!(record.header_start(), expected.header_start());
+        assert_eq!(record.is_dir(), expected.is_dir());
+
+        let mut file = lazy.by_index(i).unwrap();
+        assert_eq!(file.name(), expected.name());
+        let (mut contents, mut expected_contents) = (Vec::new(), Vec::new());
+        file.read_to_end(&mut contents).unwrap();
+        expected.read_to_end(&mut expected_contents).unwrap();
+        assert_eq!(contents, expected_contents);
+    }
+
+    let mut contents = String::new();
+    lazy.by_name("0.txt")
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    assert_eq!(contents, "the last 0.txt");
+    assert_eq!(lazy.index_for_name("42.txt").unwrap(), Some(42));
+    assert_eq!(lazy.index_for_name("missing.txt").unwrap(), None);
+    assert!(matches!(
+        lazy.by_name("missing.txt"),
+        Err(ZipError::FileNotFound)
+    ));
+    assert!(matches!(lazy.by_index(102), Err(ZipError::FileNotFound)));
+
+    let compressed_size = eager.by_index(7).unwrap().compressed_size();
+    let mut raw = Vec::new();
+    lazy.by_index_raw(7).unwrap().read_to_end(&mut raw).unwrap();
+    assert_eq!(raw.len() as u64, compressed_size);
+}
+
+#[test]
+fn lazy_record_count() {
+    let mut bytes = archive();
+    // The end record is followed by the comment
+    let end = bytes.len() - 22 - 4;
+    bytes[end + 8..end + 10].copy_from_slice(&101u16.to_le_bytes());
+    bytes[end + 10..end + 12].copy_from_slice(&101u16.to_le_bytes());
+
+    let lazy = LazyZipArchive::new(Cursor::new(&bytes)).unwrap();
+    assert_eq!(lazy.len(), 101);
+    let config = Config::default().record_count(RecordCountPolicy::Found);
+    let lazy = LazyZipArchive::new_with_config(Cursor::new(&bytes), config).unwrap();
+    assert_eq!(lazy.len(), 102);
+    assert_eq!(lazy.claimed_len(), 101);
+
+    bytes[end + 8..end + 10].copy_from_slice(&103u16.to_le_bytes());
+    bytes[end + 10..end + 12].copy_from_slice(&103u16.to_le_bytes());
+    assert!(LazyZipArchive::new(Cursor::new(&bytes)).is_err());
+}
@@ -0,0 +1,40 @@
+#![cfg(all(feature = "mmap", unix))]
+
+use std::io::{Cursor, Read, Write};
+use zip::mmap::Mmap;
+use zip::write::{FileOptions, StoredOptions};
+use zip::{ZipArchive, ZipWriter};
+
+#[test]
+fn open_mmap() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_stored_file("stored.txt", StoredOptions::for_data(b"stored"))
+        .unwrap();
+    zip.write_all(b"stored").unwrap();
+    zip.start_file("compressed.txt", FileOptions::default())
+        .unwrap();
+    zip.write_all(&b"compressed".repeat(100)).unwrap();
+    let path = std::env::temp_dir().join(format!("zip-mmap-{}.zip", std::process::id()));
+    std::fs::write(&path, zip.finish().unwrap().into_inner()).unwrap();
+
+    let mut archive = unsafe { ZipArchive::open_mmap(&path) }.unwrap();
+    assert_eq!(archive.stored_data(0).unwrap(), b"stored");
+    let mut contents = Vec::new();
+    archive
+        .by_name("compressed.txt")
+        .unwrap()
+        .read_to_end(&mut contents)
+        .unwrap();
+    assert_eq!(contents, b"compressed".repeat(100));
+    drop(archive);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn map_empty_file() {
+    let path = std::env::temp_dir().join(format!("zip-mmap-empty-{}.zip", std::process::id()));
+    std::fs::write(&path, b"").unwrap();
+    assert!(unsafe { Mmap::open(&path) }.unwrap().is_empty());
+    assert!(unsafe { ZipArchive::open_mmap(&path) }.is_err());
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,342 @@
+use std::fs;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use zip::multidisk::{merge, DiskProvider, DiskSink, MergeMode, MultiDiskReader, SplitFiles};
+use zip::result::ZipError;
+use zip::write::{FileOptions, SplitZipWriter};
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// The disks of a split archive in memory
+struct Disks(Vec<Vec<u8>>);
+
+impl DiskProvider for Disks {
+    type Disk = Cursor<Vec<u8>>;
+
+    fn open_last(&mut self) -> io::Result<Cursor<Vec<u8>>> {
+        Ok(Cursor::new(self.0.last().unwrap().clone()))
+    }
+
+    fn open(&mut self, number: u32) -> io::Result<Cursor<Vec<u8>>> {
+        match self.0.get(number as usize) {
+            Some(disk) => Ok(Cursor::new(disk.clone())),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> usize {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]]) as usize
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u64 {
+    let mut field = [0; 4];
+    field.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(field) as u64
+}
+
+fn write_u32(bytes: &mut [u8], offset: usize, value: u64) {
+    bytes[offset..offset + 4].copy_from_slice(&(value as u32).to_le_bytes());
+}
+
+/// Split the archive `bytes` into disks that start at `cuts`, the last one at the start of the
+/// central directory, and point the records at the disks they are on.
+fn split(mut bytes: Vec<u8>, cuts: &[usize]) -> Vec<Vec<u8>> {
+    let end = bytes.len() - 22;
+    let number_of_files = read_u16(&bytes, end + 10);
+    let directory_start = read_u32(&bytes, end + 16) as usize;
+    assert_eq!(*cuts.last().unwrap(), directory_start);
+    let disk_of = |offset: u64| cuts.iter().rposition(|&cut| cut as u64 <= offset);
+
+    let mut record = directory_start;
+    for _ in 0..number_of_files {
+        let header_start = read_u32(&bytes, record + 42);
+        let disk = disk_of(header_start).map_or(0, |disk| disk + 1);
+        let disk_start = if disk == 0 { 0 } else { cuts[disk - 1] as u64 };
+        bytes[record + 34..record + 36].copy_from_slice(&(disk as u16).to_le_bytes());
+        write_u32(&mut bytes, record + 42, header_start - disk_start);
+        record += 46
+            + read_u16(&bytes, record + 28)
+            + read_u16(&bytes, record + 30)
+            + read_u16(&bytes, record + 32);
+    }
+    let last_disk = (cuts.len() as u16).to_le_bytes();
+    bytes[end + 4..end + 6].copy_from_slice(&last_disk);
+    bytes[end + 6..end + 8].copy_from_slice(&last_disk);
+    write_u32(&mut bytes, end + 16, 0);
+
+    let mut disks = Vec::new();
+    let mut start = 0;
+    for &cut in cuts {
+        disks.push(bytes[start..cut].to_vec());
+        start = cut;
+    }
+    disks.push(bytes[start..].to_vec());
+    disks
+}
+
+const DATA: &[u8] = b"the data of an entry that continues on the next disk";
+
+/// An archive with an entry that spans two disks, and an entry that starts on the second one
+fn split_archive() -> Vec<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("a.txt", options).unwrap();
+    zip.write_all(DATA).unwrap();
+    zip.start_file("b.txt", options).unwrap();
+    zip.write_all(b"b").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+    let b_start = archive.by_index_raw(1).unwrap().header_start() as usize;
+    let directory_start = read_u32(&bytes, bytes.len() - 22 + 16) as usize;
+    split(bytes, &[b_start - DATA.len() / 2, directory_start])
+}
+
+#[test]
+fn multidisk() {
+    let disks = split_archive();
+    assert_eq!(disks.len(), 3);
+    match ZipArchive::new(Cursor::new(disks[2].clone())) {
+        Err(ZipError::MultiDiskUnsupported {
+            disk_number: 2,
+            disk_with_central_directory: 2,
+        }) => {}
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+
+    let mut archive = ZipArchive::new_multidisk(Disks(disks)).unwrap();
+    assert_eq!(archive.len(), 2);
+    for &(name, data) in &[("a.txt", DATA), ("b.txt", &b"b"[..])] {
+        let mut content = Vec::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, data);
+    }
+    assert!(archive.refresh().is_err());
+}
+
+#[test]
+fn missing_disks() {
+    let last = split_archive().pop().unwrap();
+    match ZipArchive::new_multidisk(Disks(vec![last])) {
+        Err(ZipError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn multidisk_single_disk() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    zip.write_all(DATA).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new_multidisk(Disks(vec![bytes])).unwrap();
+    let mut content = Vec::new();
+    archive
+        .by_name("a.txt")
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap();
+    assert_eq!(content, DATA);
+}
+
+#[test]
+fn multidisk_reader() {
+    let disks = vec![
+        Cursor::new(b"ab".to_vec()),
+        Cursor::new(Vec::new()),
+        Cursor::new(b"cde".to_vec()),
+    ];
+    let mut reader = MultiDiskReader::new(disks).unwrap();
+    assert_eq!(reader.disk_count(), 3);
+    assert_eq!(reader.disk_start(2), Some(2));
+    assert_eq!(reader.disk_start(3), None);
+
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content).unwrap();
+    assert_eq!(content, b"abcde");
+    assert_eq!(reader.seek(SeekFrom::End(-4)).unwrap(), 1);
+    let mut buf = [0; 2];
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"bc");
+    assert!(reader.seek(SeekFrom::Current(-4)).is_err());
+}
+
+impl DiskSink for Disks {
+    type Disk = Cursor<Vec<u8>>;
+
+    fn create(&mut self, number: u32) -> io::Result<Cursor<Vec<u8>>> {
+        assert_eq!(number as usize, self.0.len());
+        self.0.push(Vec::new());
+        Ok(Cursor::new(Vec::new()))
+    }
+
+    fn finish(&mut self, disks: Vec<Cursor<Vec<u8>>>) -> io::Result<()> {
+        self.0 = disks.into_iter().map(Cursor::into_inner).collect();
+        Ok(())
+    }
+}
+
+#[test]
+fn write_split_archive() {
+    let long_data = DATA.repeat(10);
+    for &checksum in &[false, true] {
+        let mut zip = SplitZipWriter::new_split(Disks(Vec::new()), 200);
+        zip.set_central_directory_checksum(checksum);
+        zip.start_file("a.txt", FileOptions::default()).unwrap();
+        zip.write_all(DATA).unwrap();
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        zip.start_file("long.txt", options).unwrap();
+        zip.write_all(&long_data).unwrap();
+        for name in &["b.txt", "c.txt", "d.txt"] {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+        let disks = zip.finish().unwrap().finish().unwrap().0;
+        assert!(disks.len() > 3);
+        assert!(disks.iter().all(|disk| disk.len() <= 200));
+
+        // The end of the central directory is on the last disk, which can't be opened alone
+        let last = disks.last().unwrap();
+        assert_eq!(read_u32(last, last.len() - 22), 0x06054b50);
+        assert!(ZipArchive::new(Cursor::new(last.clone())).is_err());
+
+        let mut archive = ZipArchive::new_multidisk(Disks(disks)).unwrap();
+        assert_eq!(archive.len(), 5);
+        for &(name, data) in &[
+            ("a.txt", DATA),
+            ("long.txt", &long_data[..]),
+            ("d.txt", &b"d.txt"[..]),
+        ] {
+            let mut content = Vec::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_end(&mut content)
+                .unwrap();
+            assert_eq!(content, data);
+        }
+    }
+}
+
+#[test]
+fn split_file_names() {
+    let files = SplitFiles::new("dir/archive.zip");
+    assert_eq!(files.disk_path(0), std::path::Path::new("dir/archive.z01"));
+    assert_eq!(
+        files.disk_path(99),
+        std::path::Path::new("dir/archive.z100")
+    );
+    let files = SplitFiles::numbered("dir/archive.zip");
+    assert_eq!(files.disk_path(0), Path::new("dir/archive.zip.001"));
+    assert_eq!(files.disk_path(999), Path::new("dir/archive.zip.1000"));
+
+    for path in &["dir/archive.zip.001", "dir/archive.zip.012"] {
+        let files = SplitFiles::discover(path);
+        assert_eq!(files.disk_path(1), Path::new("dir/archive.zip.002"));
+    }
+    for path in &["dir/archive.z01", "dir/archive.zip"] {
+        let files = SplitFiles::discover(path);
+        assert_eq!(files.disk_path(1), Path::new("dir/archive.z02"));
+    }
+}
+
+fn split_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zip-split-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn read_all(archive: &mut ZipArchive<MultiDiskReader<fs::File>>) -> Vec<Vec<u8>> {
+    (0..archive.len())
+        .map(|i| {
+            let mut content = Vec::new();
+            archive
+                .by_index(i)
+                .unwrap()
+                .read_to_end(&mut content)
+                .unwrap();
+            content
+        })
+        .collect()
+}
+
+#[test]
+fn cut_archive_files() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("a.txt", options).unwrap();
+    zip.write_all(DATA).unwrap();
+    zip.start_file("b.txt", options).unwrap();
+    zip.write_all(b"b").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    // The archive is cut into pieces of 60 bytes, and its records don't know about them
+    let dir = split_dir("cut");
+    for (i, piece) in bytes.chunks(60).enumerate() {
+        fs::write(dir.join(format!("archive.zip.{:03}", i + 1)), piece).unwrap();
+    }
+    assert!(bytes.len() > 120);
+
+    for name in &["archive.zip", "archive.zip.001", "archive.zip.002"] {
+        let files = SplitFiles::discover(dir.join(name));
+        let mut archive = ZipArchive::new_multidisk(files).unwrap();
+        assert_eq!(read_all(&mut archive), vec![DATA.to_vec(), b"b".to_vec()]);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn split_archive_files() {
+    let long_data = DATA.repeat(10);
+    for &numbered in &[false, true] {
+        let dir = split_dir(if numbered { "numbered" } else { "zip" });
+        let path = dir.join("archive.zip");
+        let files = if numbered {
+            SplitFiles::numbered(&path)
+        } else {
+            SplitFiles::new(&path)
+        };
+        let mut zip = SplitZipWriter::new_split(files, 200);
+        zip.start_file(
+            "long.txt",
+            FileOptions::default().compression_method(CompressionMethod::Stored),
+        )
+        .unwrap();
+        zip.write_all(&long_data).unwrap();
+        zip.finish().unwrap().finish().unwrap();
+
+        assert_eq!(path.is_file(), !numbered);
+        assert_eq!(dir.join("archive.zip.001").is_file(), numbered);
+        assert_eq!(dir.join("archive.z01").is_file(), !numbered);
+
+        let mut archive = ZipArchive::new_multidisk(SplitFiles::discover(&path)).unwrap();
+        assert_eq!(read_all(&mut archive), vec![long_data.clone()]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
+
+#[test]
+fn merge_split_archive() {
+    for &mode in &[MergeMode::Raw, MergeMode::Recompress] {
+        let mut archive = ZipArchive::new_multidisk(Disks(split_archive())).unwrap();
+        let bytes = merge(&mut archive, Cursor::new(Vec::new()), mode)
+            .unwrap()
+            .into_inner();
+
+        let mut merged = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(merged.len(), 2);
+        for &(name, data) in &[("a.txt", DATA), ("b.txt", &b"b"[..])] {
+            let mut content = Vec::new();
+            let mut file = merged.by_name(name).unwrap();
+            assert_eq!(file.compression(), CompressionMethod::Stored);
+            file.read_to_end(&mut content).unwrap();
+            assert_eq!(content, data);
+        }
+    }
+}
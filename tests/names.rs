@@ -0,0 +1,58 @@
+use std::io::{Cursor, Read, Write};
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+#[test]
+fn duplicate_names() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for &(name, contents) in &[("b", "first b"), ("a", "a"), ("b", "last b")] {
+        zip.start_file(name, FileOptions::default()).unwrap();
+        zip.write_all(contents.as_bytes()).unwrap();
+    }
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.len(), 3);
+    let mut names: Vec<_> = archive.file_names().collect();
+    names.sort_unstable();
+    assert_eq!(names, ["a", "b"]);
+    let mut contents = String::new();
+    archive
+        .by_name("b")
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    assert_eq!(contents, "last b");
+    assert!(archive.by_name("c").is_err());
+}
+
+#[test]
+fn raw_names() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("caf_.txt", FileOptions::default()).unwrap();
+    zip.start_file("tea.txt", FileOptions::default()).unwrap();
+    zip.start_file("\u{e9}.txt", FileOptions::default())
+        .unwrap();
+    let mut bytes = zip.finish().unwrap().into_inner();
+
+    // Turn the first name into one in codepage 437, where 0x82 is an e with an acute accent
+    for i in 0..bytes.len() - 8 {
+        if &bytes[i..i + 8] == b"caf_.txt" {
+            bytes[i + 3] = 0x82;
+        }
+    }
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let file = archive.by_index(0).unwrap();
+    assert_eq!(file.name(), "caf\u{e9}.txt");
+    assert_eq!(file.name_raw(), b"caf\x82.txt");
+    drop(file);
+    let file = archive.by_index(1).unwrap();
+    assert_eq!(file.name(), "tea.txt");
+    assert_eq!(file.name_raw(), b"tea.txt");
+    drop(file);
+    let file = archive.by_index(2).unwrap();
+    assert_eq!(file.name_raw(), "\u{e9}.txt".as_bytes());
+    drop(file);
+    assert!(archive.by_name("caf\u{e9}.txt").is_ok());
+}
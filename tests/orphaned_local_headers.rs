@@ -0,0 +1,67 @@
+use std::io::{Cursor, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+// Builds an archive containing "deleted.txt" and "kept.txt", then removes the central directory
+// record of "deleted.txt" the way a tool that only rewrites the central directory would.
+fn archive_with_deleted_entry() -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("deleted.txt", options).unwrap();
+    zip.write_all(b"this entry was deleted").unwrap();
+    zip.start_file("kept.txt", options).unwrap();
+    zip.write_all(b"this entry was kept").unwrap();
+    let mut bytes = zip.finish().unwrap().into_inner();
+
+    let (deleted_start, kept_start) = {
+        let mut archive = ZipArchive::new(Cursor::new(&bytes)).unwrap();
+        let deleted = archive
+            .by_name("deleted.txt")
+            .unwrap()
+            .central_header_start();
+        let kept = archive.by_name("kept.txt").unwrap().central_header_start();
+        (deleted as usize, kept as usize)
+    };
+    let record_len = kept_start - deleted_start;
+    bytes.drain(deleted_start..kept_start);
+
+    // Fix up the entry counts and the central directory size in the end of central directory
+    let eocd = bytes.len() - 22;
+    bytes[eocd + 8..eocd + 10].copy_from_slice(&1u16.to_le_bytes());
+    bytes[eocd + 10..eocd + 12].copy_from_slice(&1u16.to_le_bytes());
+    let mut size = [0; 4];
+    size.copy_from_slice(&bytes[eocd + 12..eocd + 16]);
+    let size = u32::from_le_bytes(size) - record_len as u32;
+    bytes[eocd + 12..eocd + 16].copy_from_slice(&size.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn finds_deleted_entry() {
+    let mut archive = ZipArchive::new(Cursor::new(archive_with_deleted_entry())).unwrap();
+    assert_eq!(archive.len(), 1);
+
+    let orphans = archive
+        .orphaned_local_headers()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(orphans.len(), 1);
+    assert_eq!(orphans[0].name(), "deleted.txt");
+    assert_eq!(orphans[0].header_start(), 0);
+    assert_eq!(orphans[0].size(), 22);
+    assert_eq!(orphans[0].compression(), CompressionMethod::Stored);
+}
+
+#[test]
+fn no_orphans_in_intact_archive() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("a.txt", options).unwrap();
+    zip.write_all(b"PK\x03\x04 looks like a header, but is not")
+        .unwrap();
+    zip.add_directory("dir", FileOptions::default()).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.orphaned_local_headers().count(), 0);
+}
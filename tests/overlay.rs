@@ -0,0 +1,59 @@
+use std::io::{Cursor, Read, Write};
+use zip::overlay::OverlayArchive;
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+fn archive(files: &[(&str, &str)]) -> ZipArchive<Cursor<Vec<u8>>> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, contents) in files {
+        writer.start_file(*name, FileOptions::default()).unwrap();
+        writer.write_all(contents.as_bytes()).unwrap();
+    }
+    let bytes = writer.finish().unwrap().into_inner();
+    ZipArchive::new(Cursor::new(bytes)).unwrap()
+}
+
+fn read(overlay: &mut OverlayArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+    let mut contents = String::new();
+    overlay
+        .by_name(name)
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    contents
+}
+
+#[test]
+fn later_layers_shadow_earlier_ones() {
+    let mut overlay = OverlayArchive::new();
+    assert!(overlay.is_empty());
+    overlay.push_layer(archive(&[("a.txt", "base a"), ("b.txt", "base b")]));
+    overlay.push_layer(archive(&[("b.txt", "mod b"), ("c.txt", "mod c")]));
+    overlay.push_layer(archive(&[("c.txt", "patch c")]));
+
+    assert_eq!(overlay.len(), 3);
+    let mut names: Vec<_> = overlay.file_names().collect();
+    names.sort_unstable();
+    assert_eq!(names, ["a.txt", "b.txt", "c.txt"]);
+
+    assert_eq!(read(&mut overlay, "a.txt"), "base a");
+    assert_eq!(read(&mut overlay, "b.txt"), "mod b");
+    assert_eq!(read(&mut overlay, "c.txt"), "patch c");
+    assert_eq!(overlay.layer_of("a.txt"), Some(0));
+    assert_eq!(overlay.layer_of("b.txt"), Some(1));
+    assert_eq!(overlay.layer_of("c.txt"), Some(2));
+    assert_eq!(overlay.layer_of("d.txt"), None);
+    assert_eq!(overlay.layers().len(), 3);
+}
+
+#[test]
+fn missing_file() {
+    let mut overlay = OverlayArchive::new();
+    overlay.push_layer(archive(&[("a.txt", "a")]));
+    match overlay.by_name("b.txt").map(|_| ()) {
+        Err(ZipError::FileNotFound) => {}
+        Err(e) => panic!("unexpected error: {:?}", e),
+        Ok(()) => panic!("found a file that is in no layer"),
+    }
+}
@@ -0,0 +1,74 @@
+#![cfg(feature = "deflate")]
+
+use std::io::{Cursor, Read, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const DATA: &[u8] = b"data that is compressed once, data that is compressed once";
+
+/// The deflated data of `DATA`, with its CRC-32
+fn compressed() -> (Vec<u8>, u32) {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("data.txt", FileOptions::default()).unwrap();
+    zip.write_all(DATA).unwrap();
+    let mut archive = ZipArchive::new(zip.finish().unwrap()).unwrap();
+    let mut file = archive.by_index_raw(0).unwrap();
+    let crc32 = file.crc32();
+    let mut compressed = Vec::new();
+    file.read_to_end(&mut compressed).unwrap();
+    (compressed, crc32)
+}
+
+#[test]
+fn raw_file() {
+    let (compressed, crc32) = compressed();
+    let size = DATA.len() as u64;
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file_raw("raw.txt", options, crc32, compressed.len() as u64, size)
+        .unwrap();
+    zip.write_all(&compressed).unwrap();
+    let described = options.data_descriptor(true);
+    zip.start_file_raw(
+        "described.txt",
+        described,
+        crc32,
+        compressed.len() as u64,
+        size,
+    )
+    .unwrap();
+    zip.write_all(&compressed).unwrap();
+    zip.start_file("after.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"after").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    for name in &["raw.txt", "described.txt"] {
+        let mut file = archive.by_name(name).unwrap();
+        assert_eq!(file.compression(), CompressionMethod::Deflated);
+        assert_eq!(file.compressed_size(), compressed.len() as u64);
+        assert_eq!(file.unix_mode(), Some(0o100644));
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, DATA);
+    }
+    let mut contents = String::new();
+    archive
+        .by_name("after.txt")
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    assert_eq!(contents, "after");
+}
+
+#[test]
+fn raw_file_mismatch() {
+    let (compressed, crc32) = compressed();
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    let size = compressed.len() as u64 + 1;
+    zip.start_file_raw("short.txt", options, crc32, size, DATA.len() as u64)
+        .unwrap();
+    zip.write_all(&compressed).unwrap();
+    assert!(zip.finish().is_err());
+}
@@ -0,0 +1,57 @@
+use std::io::{Cursor, Read, Write};
+use std::sync::Arc;
+use zip::read_at::{ReadAtArchive, ReadAtReader};
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+fn archive() -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for i in 0..8 {
+        zip.start_file(format!("{}.txt", i), FileOptions::default())
+            .unwrap();
+        zip.write_all(format!("file {}", i).repeat(1000).as_bytes())
+            .unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+/// Read every entry of `archive` on a thread of its own
+fn read_concurrently<R: zip::read_at::ReadAt + Clone + Send + 'static>(archive: &ReadAtArchive<R>) {
+    let threads: Vec<_> = (0..archive.len())
+        .map(|i| {
+            let mut archive = archive.clone();
+            std::thread::spawn(move || {
+                let mut contents = String::new();
+                archive
+                    .by_index(i)
+                    .unwrap()
+                    .read_to_string(&mut contents)
+                    .unwrap();
+                contents
+            })
+        })
+        .collect();
+    for (i, thread) in threads.into_iter().enumerate() {
+        assert_eq!(thread.join().unwrap(), format!("file {}", i).repeat(1000));
+    }
+}
+
+#[test]
+fn read_at() {
+    let bytes = Arc::new(archive());
+    let archive = ZipArchive::new(ReadAtReader::new(bytes)).unwrap();
+    assert_eq!(archive.len(), 8);
+    read_concurrently(&archive);
+}
+
+#[cfg(any(unix, windows))]
+#[test]
+fn read_at_file() {
+    let path = std::env::temp_dir().join(format!("zip-read-at-{}.zip", std::process::id()));
+    std::fs::write(&path, archive()).unwrap();
+    let file = Arc::new(std::fs::File::open(&path).unwrap());
+    let archive = ZipArchive::new(ReadAtReader::new(file)).unwrap();
+    read_concurrently(&archive);
+    drop(archive);
+    std::fs::remove_file(&path).unwrap();
+}
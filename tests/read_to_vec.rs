@@ -0,0 +1,129 @@
+use std::io::{Cursor, Read, Write};
+use zip::read::read_zipfile_from_stream;
+use zip::write::FileOptions;
+#[cfg(feature = "async")]
+use zip::AsyncZipArchive;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const DATA: &[u8] = b"data that is read into a vector, data that is read into a vector";
+
+/// Write an archive with a stored file, a deflated file and a deflated file with a data
+/// descriptor.
+fn archive() -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("stored.txt", stored).unwrap();
+    zip.write_all(DATA).unwrap();
+    zip.start_file("deflated.txt", FileOptions::default())
+        .unwrap();
+    zip.write_all(DATA).unwrap();
+    let described = FileOptions::default().data_descriptor(true);
+    zip.start_file("described.txt", described).unwrap();
+    zip.write_all(DATA).unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+#[test]
+fn read_to_vec() {
+    let bytes = archive();
+    let mut archive = ZipArchive::new(Cursor::new(&bytes)).unwrap();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).unwrap();
+        let size = DATA.len() as u64;
+        assert_eq!(file.size_hint(), (size, Some(size)));
+        let mut start = [0; 10];
+        file.read_exact(&mut start).unwrap();
+        assert_eq!(file.size_hint(), (size - 10, Some(size - 10)));
+
+        let rest = file.read_to_vec().unwrap();
+        assert_eq!(rest, &DATA[10..]);
+        assert_eq!(rest.capacity(), rest.len());
+        assert_eq!(file.size_hint(), (0, Some(0)));
+    }
+
+    let mut file = archive.by_index_raw(1).unwrap();
+    let compressed_size = file.compressed_size();
+    assert_eq!(file.size_hint(), (compressed_size, Some(compressed_size)));
+    assert_eq!(file.read_to_vec().unwrap().len() as u64, compressed_size);
+
+    // The size of a streamed file with a data descriptor is not known until it was read
+    let mut reader = &bytes[..];
+    for _ in 0..2 {
+        read_zipfile_from_stream(&mut reader).unwrap().unwrap();
+    }
+    let mut file = read_zipfile_from_stream(&mut reader).unwrap().unwrap();
+    assert_eq!(file.size_hint(), (0, None));
+    assert_eq!(file.read_to_vec().unwrap(), DATA);
+}
+
+#[test]
+fn read_to_vec_corrupt() {
+    let mut bytes = archive();
+    let data_start = ZipArchive::new(Cursor::new(&bytes))
+        .unwrap()
+        .by_index(0)
+        .unwrap()
+        .data_start() as usize;
+    bytes[data_start] ^= 1;
+    let mut archive = ZipArchive::new(Cursor::new(&bytes)).unwrap();
+    assert!(archive.by_index(0).unwrap().read_to_vec().is_err());
+}
+
+/// The archive with the uncompressed size of the deflated file in the central directory set to
+/// almost 4 GiB, which is a lot more than its compressed data can expand to
+fn oversized_archive() -> Vec<u8> {
+    let mut bytes = archive();
+    let offset = bytes
+        .windows(4)
+        .enumerate()
+        .filter(|(_, window)| *window == b"PK\x01\x02")
+        .nth(1)
+        .unwrap()
+        .0;
+    bytes[offset + 24..offset + 28].copy_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn read_to_vec_oversized() {
+    let bytes = oversized_archive();
+    let mut archive = ZipArchive::new(Cursor::new(&bytes)).unwrap();
+    let mut file = archive.by_name("deflated.txt").unwrap();
+    assert_eq!(file.size(), 0xFFFF_FFF0);
+    let data = file.read_to_vec().unwrap();
+    assert_eq!(data, DATA);
+    assert!(data.capacity() < 1 << 20);
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn read_to_vec_oversized_async() {
+    let reader = futures::io::Cursor::new(oversized_archive());
+    let mut archive = AsyncZipArchive::new(reader).await.unwrap();
+    let mut file = archive.by_index(1).await.unwrap();
+    assert_eq!(file.size(), 0xFFFF_FFF0);
+    let data = file.read_to_vec().await.unwrap();
+    assert_eq!(data, DATA);
+    assert!(data.capacity() < 1 << 20);
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn read_to_vec_async() {
+    use futures::io::AsyncReadExt;
+
+    let reader = futures::io::Cursor::new(archive());
+    let mut archive = AsyncZipArchive::new(reader).await.unwrap();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).await.unwrap();
+        let size = DATA.len() as u64;
+        assert_eq!(file.size_hint(), (size, Some(size)));
+        let mut start = [0; 10];
+        file.read_exact(&mut start).await.unwrap();
+        assert_eq!(file.size_hint(), (size - 10, Some(size - 10)));
+
+        let rest = file.read_to_vec().await.unwrap();
+        assert_eq!(rest, &DATA[10..]);
+        assert_eq!(rest.capacity(), rest.len());
+    }
+}
@@ -0,0 +1,74 @@
+use std::io::{Cursor, Read, Write};
+use zip::read::{Config, RecordCountPolicy, ValidationIssue};
+use zip::write::FileOptions;
+#[cfg(feature = "async")]
+use zip::AsyncZipArchive;
+use zip::{ZipArchive, ZipWriter};
+
+/// Write an archive with three files, and then change the number of files its central directory
+/// end record claims
+fn archive(claimed: u16) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for &name in &["a.txt", "b.txt", "c.txt"] {
+        zip.start_file(name, FileOptions::default()).unwrap();
+        zip.write_all(name.as_bytes()).unwrap();
+    }
+    let mut bytes = zip.finish().unwrap().into_inner();
+
+    let end = bytes.len() - 22;
+    bytes[end + 8..end + 10].copy_from_slice(&claimed.to_le_bytes());
+    bytes[end + 10..end + 12].copy_from_slice(&claimed.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn claimed_count() {
+    let zip = ZipArchive::new(Cursor::new(archive(2))).unwrap();
+    assert_eq!(zip.len(), 2);
+    assert_eq!(zip.claimed_len(), 2);
+    assert!(zip.validate().is_empty());
+
+    assert!(ZipArchive::new(Cursor::new(archive(4))).is_err());
+}
+
+#[test]
+fn found_count() {
+    let config = Config::default().record_count(RecordCountPolicy::Found);
+    for &claimed in &[2, 3, 4] {
+        let bytes = archive(claimed);
+        let mut zip = ZipArchive::new_with_config(Cursor::new(bytes), config).unwrap();
+        assert_eq!(zip.len(), 3);
+        assert_eq!(zip.claimed_len(), claimed as usize);
+        let issues = zip.validate();
+        if claimed == 3 {
+            assert!(issues.is_empty());
+        } else {
+            assert_eq!(
+                issues,
+                vec![ValidationIssue::RecordCountMismatch {
+                    claimed: claimed as usize,
+                    found: 3,
+                }]
+            );
+        }
+
+        let mut content = String::new();
+        zip.by_index(2)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "c.txt");
+    }
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn found_count_async() {
+    let config = Config::default().record_count(RecordCountPolicy::Found);
+    let reader = futures::io::Cursor::new(archive(2));
+    let zip = AsyncZipArchive::new_with_config(reader, config)
+        .await
+        .unwrap();
+    assert_eq!(zip.len(), 3);
+    assert_eq!(zip.claimed_len(), 2);
+}
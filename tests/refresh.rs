@@ -0,0 +1,137 @@
+use std::cell::RefCell;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::rc::Rc;
+use zip::write::FileOptions;
+use zip::{DateTime, ZipArchive, ZipWriter};
+
+/// A buffer that can be changed while an archive is reading it
+#[derive(Clone, Default)]
+struct SharedBuffer {
+    data: Rc<RefCell<Vec<u8>>>,
+    position: u64,
+}
+
+impl Read for SharedBuffer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.data.borrow();
+        let mut remaining = data.get(self.position as usize..).unwrap_or(&[]);
+        let count = remaining.read(buf)?;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl Seek for SharedBuffer {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = match pos {
+            SeekFrom::Start(offset) => offset,
+            SeekFrom::End(offset) => (self.data.borrow().len() as i64 + offset) as u64,
+            SeekFrom::Current(offset) => (self.position as i64 + offset) as u64,
+        };
+        Ok(self.position)
+    }
+}
+
+fn write_archive(buffer: &SharedBuffer, files: &[(&str, &str)], comment: &str) {
+    let options = FileOptions::default()
+        .last_modified_time(DateTime::from_date_and_time(2021, 1, 1, 0, 0, 0).unwrap());
+    let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+    writer.set_comment(comment);
+    for (name, contents) in files {
+        writer.start_file(*name, options).unwrap();
+        writer.write_all(contents.as_bytes()).unwrap();
+    }
+    *buffer.data.borrow_mut() = writer.finish().unwrap().into_inner();
+}
+
+fn read(archive: &mut ZipArchive<SharedBuffer>, name: &str) -> String {
+    let mut contents = String::new();
+    archive
+        .by_name(name)
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    contents
+}
+
+#[test]
+fn refresh_unchanged() {
+    let buffer = SharedBuffer::default();
+    write_archive(&buffer, &[("a.txt", "a")], "");
+    let mut archive = ZipArchive::new(buffer).unwrap();
+
+    assert!(!archive.refresh().unwrap());
+    assert_eq!(archive.len(), 1);
+    assert_eq!(read(&mut archive, "a.txt"), "a");
+}
+
+#[test]
+fn refresh_appended_entries() {
+    let buffer = SharedBuffer::default();
+    write_archive(&buffer, &[("a.txt", "a"), ("b.txt", "b")], "");
+    let mut archive = ZipArchive::new(buffer.clone()).unwrap();
+
+    write_archive(
+        &buffer,
+        &[("a.txt", "a"), ("b.txt", "b"), ("c.txt", "c")],
+        "",
+    );
+    assert!(archive.refresh().unwrap());
+    assert_eq!(archive.len(), 3);
+    assert_eq!(read(&mut archive, "a.txt"), "a");
+    assert_eq!(read(&mut archive, "c.txt"), "c");
+    assert_eq!(archive.by_index(2).unwrap().name(), "c.txt");
+    assert!(!archive.refresh().unwrap());
+}
+
+#[test]
+fn refresh_rewritten_archive() {
+    let buffer = SharedBuffer::default();
+    write_archive(&buffer, &[("a.txt", "a"), ("b.txt", "b")], "");
+    let mut archive = ZipArchive::new(buffer.clone()).unwrap();
+
+    write_archive(&buffer, &[("a.txt", "a"), ("c.txt", "new c")], "");
+    assert!(archive.refresh().unwrap());
+    assert_eq!(archive.len(), 2);
+    assert!(archive.by_name("b.txt").is_err());
+    assert_eq!(read(&mut archive, "a.txt"), "a");
+    assert_eq!(read(&mut archive, "c.txt"), "new c");
+
+    write_archive(&buffer, &[("a.txt", "changed")], "comment");
+    assert!(archive.refresh().unwrap());
+    assert_eq!(archive.len(), 1);
+    assert_eq!(archive.comment(), b"comment");
+    assert_eq!(read(&mut archive, "a.txt"), "changed");
+}
+
+#[test]
+fn refresh_failed() {
+    let buffer = SharedBuffer::default();
+    write_archive(&buffer, &[("a.txt", "a"), ("b.txt", "b")], "");
+    let mut archive = ZipArchive::new(buffer.clone()).unwrap();
+    let old = buffer.data.borrow().clone();
+
+    // The last record of the new central directory doesn't match its checksum
+    let options = FileOptions::default()
+        .last_modified_time(DateTime::from_date_and_time(2021, 1, 1, 0, 0, 0).unwrap());
+    let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+    writer.set_central_directory_checksum(true);
+    for name in &["a.txt", "c.txt", "d.txt"] {
+        writer.start_file(*name, options).unwrap();
+        writer.write_all(name.as_bytes()).unwrap();
+    }
+    let mut data = writer.finish().unwrap().into_inner();
+    let name = data.windows(5).rposition(|w| w == b"d.txt").unwrap();
+    data[name] = b'e';
+    *buffer.data.borrow_mut() = data;
+    assert!(archive.refresh().is_err());
+
+    // The archive is unchanged, and can be read once the old data is back
+    *buffer.data.borrow_mut() = old;
+    assert_eq!(archive.len(), 2);
+    assert!(archive.by_name("c.txt").is_err());
+    assert!(archive.by_name("e.txt").is_err());
+    assert_eq!(read(&mut archive, "a.txt"), "a");
+    assert_eq!(read(&mut archive, "b.txt"), "b");
+    assert!(!archive.refresh().unwrap());
+}
@@ -0,0 +1,73 @@
+use std::io::{Cursor, Read, Write};
+use zip::read::read_zipfile_from_stream;
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+#[test]
+fn rename() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for name in &["old/a.txt", "old/b.txt", "old/c.txt"] {
+        zip.start_file(*name, FileOptions::default()).unwrap();
+        zip.write_all(name.as_bytes()).unwrap();
+    }
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut zip = ZipWriter::new_append(Cursor::new(bytes)).unwrap();
+    zip.rename_with_local_header("old/a.txt", "new/a.txt")
+        .unwrap();
+    zip.rename("old/b.txt", "newer/b.txt").unwrap();
+    assert!(matches!(
+        zip.rename_with_local_header("old/c.txt", "newer/c.txt"),
+        Err(ZipError::InvalidFileName(_))
+    ));
+    assert!(matches!(
+        zip.rename("missing.txt", "new/missing.txt"),
+        Err(ZipError::FileNotFound)
+    ));
+    zip.start_file("d.txt", FileOptions::default()).unwrap();
+    assert!(zip.rename("d.txt", "new/d.txt").is_err());
+    // The local header can't be written while the data of d.txt is compressed
+    assert!(zip
+        .rename_with_local_header("old/c.txt", "n\u{e9}/c.txt")
+        .is_err());
+    zip.write_all(b"d").unwrap();
+    zip.add_directory("e", FileOptions::default()).unwrap();
+    zip.rename_with_local_header("old/c.txt", "n\u{e9}/c.txt")
+        .unwrap();
+    zip.rename("d.txt", "new/d.txt").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(&bytes)).unwrap();
+    let names: Vec<_> = (0..archive.len())
+        .map(|i| archive.by_index(i).unwrap().name().to_owned())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "new/a.txt",
+            "newer/b.txt",
+            "n\u{e9}/c.txt",
+            "new/d.txt",
+            "e/"
+        ]
+    );
+    let mut contents = String::new();
+    archive
+        .by_name("newer/b.txt")
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    assert_eq!(contents, "old/b.txt");
+
+    // Only the local headers that were renamed have the new names
+    let mut reader = &bytes[..];
+    let mut local_names = Vec::new();
+    while let Some(file) = read_zipfile_from_stream(&mut reader).unwrap() {
+        local_names.push(file.name().to_owned());
+    }
+    assert_eq!(
+        local_names,
+        ["new/a.txt", "old/b.txt", "n\u{e9}/c.txt", "d.txt", "e/"]
+    );
+}
@@ -0,0 +1,53 @@
+use std::io::{Cursor, Read, Write};
+use zip::result::ZipError;
+use zip::write::{EditPlan, FileOptions};
+use zip::{ZipArchive, ZipWriter};
+
+fn source() -> ZipArchive<Cursor<Vec<u8>>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for &name in &["a.txt", "b.txt", "c.txt", "d.txt"] {
+        zip.start_file(name, FileOptions::default()).unwrap();
+        zip.write_all(name.as_bytes()).unwrap();
+    }
+    ZipArchive::new(zip.finish().unwrap()).unwrap()
+}
+
+#[test]
+fn rewrite() {
+    let mut src = source();
+    let plan = EditPlan::new()
+        .rename("a.txt", "renamed.txt")
+        .remove("b.txt")
+        .replace("c.txt", &b"replaced"[..], FileOptions::default())
+        .add("e.txt", &b"added"[..], FileOptions::default());
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.rewrite(&mut src, plan).unwrap();
+    let mut archive = ZipArchive::new(zip.finish().unwrap()).unwrap();
+
+    let expected = [
+        ("renamed.txt", "a.txt"),
+        ("c.txt", "replaced"),
+        ("d.txt", "d.txt"),
+        ("e.txt", "added"),
+    ];
+    assert_eq!(archive.len(), expected.len());
+    for (i, &(name, content)) in expected.iter().enumerate() {
+        let mut file = archive.by_index(i).unwrap();
+        assert_eq!(file.name(), name);
+        let mut read = String::new();
+        file.read_to_string(&mut read).unwrap();
+        assert_eq!(read, content);
+    }
+}
+
+#[test]
+fn rewrite_missing_entry() {
+    let mut src = source();
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let plan = EditPlan::new().remove("missing.txt");
+    assert!(matches!(
+        zip.rewrite(&mut src, plan),
+        Err(ZipError::FileNotFound)
+    ));
+    zip.finish().unwrap();
+}
@@ -0,0 +1,203 @@
+use std::io::{self, Cursor, Read, Write};
+use zip::result::ZipError;
+use zip::signature::{Signer, Verifier};
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+/// A "signature" that is the CRC-32 of a key and the signed data, which is enough to test that the
+/// right bytes are signed.
+struct KeyedChecksum(&'static [u8]);
+
+impl KeyedChecksum {
+    fn signature(&self, data: &[u8]) -> Vec<u8> {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(self.0);
+        hasher.update(data);
+        hasher.finalize().to_le_bytes().to_vec()
+    }
+}
+
+impl Signer for KeyedChecksum {
+    fn sign(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        Ok(self.signature(data))
+    }
+}
+
+impl Verifier for KeyedChecksum {
+    fn verify(&self, data: &[u8], signature: &[u8]) -> bool {
+        self.signature(data) == signature
+    }
+}
+
+fn signed_archive(checksum: bool) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_signer(KeyedChecksum(b"key"));
+    zip.set_central_directory_checksum(checksum);
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"a").unwrap();
+    zip.add_directory("dir", FileOptions::default()).unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+#[test]
+fn digital_signature() {
+    for &checksum in &[false, true] {
+        let bytes = signed_archive(checksum);
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.len(), 2);
+        assert_eq!(archive.digital_signature().map(<[u8]>::len), Some(4));
+        archive
+            .verify_digital_signature(&KeyedChecksum(b"key"))
+            .unwrap();
+        match archive.verify_digital_signature(&KeyedChecksum(b"other key")) {
+            Err(ZipError::InvalidArchive("Invalid digital signature")) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        // The files are still found after the record
+        let mut content = String::new();
+        archive
+            .by_name("a.txt")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "a");
+    }
+}
+
+#[test]
+fn tampered_digital_signature() {
+    let mut bytes = signed_archive(false);
+    let offset = bytes.windows(5).rposition(|name| name == b"a.txt").unwrap();
+    bytes[offset] = b'b';
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert!(archive.by_name("b.txt").is_ok());
+    assert!(archive
+        .verify_digital_signature(&KeyedChecksum(b"key"))
+        .is_err());
+}
+
+#[test]
+fn no_digital_signature() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.digital_signature(), None);
+    match archive.verify_digital_signature(&KeyedChecksum(b"key")) {
+        Err(ZipError::InvalidArchive("The archive has no digital signature")) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+struct LongSignature;
+
+impl Signer for LongSignature {
+    fn sign(&self, _: &[u8]) -> io::Result<Vec<u8>> {
+        Ok(vec![0; u16::MAX as usize + 1])
+    }
+}
+
+#[test]
+fn long_digital_signature() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_signer(LongSignature);
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    assert!(zip.finish().is_err());
+}
+
+#[test]
+fn detached_signature() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("b.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"b").unwrap();
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let contents = archive.canonical_contents();
+    // The entries are ordered by name
+    assert_eq!(contents.len(), 2 * (4 + 5 + 4 + 8 + 8));
+    assert_eq!(contents[..9], b"\x05\x00\x00\x00a.txt"[..]);
+    assert_eq!(archive.embedded_signature(), None);
+
+    let signer = KeyedChecksum(b"key");
+    let signature = signer.sign(&contents).unwrap();
+    archive
+        .verify_detached_signature(&signer, &signature)
+        .unwrap();
+    match archive.verify_detached_signature(&KeyedChecksum(b"other key"), &signature) {
+        Err(ZipError::InvalidArchive("Invalid signature of the contents")) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn embedded_signature() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_contents_signer(KeyedChecksum(b"key"));
+    zip.set_central_directory_checksum(true);
+    zip.start_file("b.txt", FileOptions::default()).unwrap();
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"a").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let signature = archive.embedded_signature().unwrap();
+    archive
+        .verify_detached_signature(&KeyedChecksum(b"key"), &signature)
+        .unwrap();
+    assert!(archive
+        .verify_detached_signature(&KeyedChecksum(b"other key"), &signature)
+        .is_err());
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn async_digital_signature() {
+    let mut zip = zip::write::AsyncZipWriter::new(futures::io::Cursor::new(Vec::new()));
+    zip.set_signer(KeyedChecksum(b"key"));
+    zip.start_file("a.txt", FileOptions::default())
+        .await
+        .unwrap();
+    let bytes = zip.finish().await.unwrap().get_ref().clone();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+    archive
+        .verify_digital_signature(&KeyedChecksum(b"key"))
+        .unwrap();
+
+    let mut archive = zip::AsyncZipArchive::new(futures::io::Cursor::new(bytes))
+        .await
+        .unwrap();
+    assert!(archive.digital_signature().is_some());
+    archive
+        .verify_digital_signature(&KeyedChecksum(b"key"))
+        .await
+        .unwrap();
+    assert!(archive
+        .verify_digital_signature(&KeyedChecksum(b"other key"))
+        .await
+        .is_err());
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn async_embedded_signature() {
+    let mut zip = zip::write::AsyncZipWriter::new(futures::io::Cursor::new(Vec::new()));
+    zip.set_contents_signer(KeyedChecksum(b"key"));
+    zip.start_file("a.txt", FileOptions::default())
+        .await
+        .unwrap();
+    let bytes = zip.finish().await.unwrap().get_ref().clone();
+
+    let archive = zip::AsyncZipArchive::new(futures::io::Cursor::new(bytes.clone()))
+        .await
+        .unwrap();
+    let signature = archive.embedded_signature().unwrap();
+    archive
+        .verify_detached_signature(&KeyedChecksum(b"key"), &signature)
+        .unwrap();
+    let archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.embedded_signature(), Some(signature));
+}
@@ -0,0 +1,144 @@
+use std::io::{self, Cursor, Seek, SeekFrom, Write};
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+fn stored() -> FileOptions {
+    FileOptions::default().compression_method(CompressionMethod::Stored)
+}
+
+#[test]
+fn within_limit() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("a.txt", stored()).unwrap();
+    zip.write_all(&[0; 100]).unwrap();
+    let size = zip.finish().unwrap().into_inner().len() as u64;
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_size_limit(Some(size));
+    zip.start_file("a.txt", stored()).unwrap();
+    zip.write_all(&[0; 100]).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    assert_eq!(bytes.len() as u64, size);
+    ZipArchive::new(Cursor::new(bytes)).unwrap();
+}
+
+#[test]
+fn entry_exceeds_limit() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_size_limit(Some(1000));
+    zip.start_file("small.txt", stored()).unwrap();
+    zip.write_all(&[0; 100]).unwrap();
+    zip.start_file("large.txt", stored()).unwrap();
+    let error = zip.write_all(&[0; 2000]).unwrap_err();
+    let error = error.into_inner().unwrap().downcast::<ZipError>().unwrap();
+    match *error {
+        ZipError::SizeLimitExceeded(Some(ref name)) => assert_eq!(name, "large.txt"),
+        ref e => panic!("unexpected error: {:?}", e),
+    }
+}
+
+#[test]
+fn central_directory_exceeds_limit() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_size_limit(Some(100));
+    zip.start_file("a.txt", stored()).unwrap();
+    zip.write_all(&[0; 40]).unwrap();
+    match zip.finish() {
+        Err(ZipError::SizeLimitExceeded(None)) => {}
+        Err(e) => panic!("unexpected error: {:?}", e),
+        Ok(_) => panic!("the limit was not enforced"),
+    }
+}
+
+#[test]
+fn raw_copy_is_checked_before_copying() {
+    let mut source = ZipWriter::new(Cursor::new(Vec::new()));
+    source.start_file("a.txt", stored()).unwrap();
+    source.write_all(&[0; 1000]).unwrap();
+    let source = source.finish().unwrap();
+    let mut source = ZipArchive::new(source).unwrap();
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_size_limit(Some(500));
+    match zip.raw_copy_file(source.by_index(0).unwrap()) {
+        Err(ZipError::SizeLimitExceeded(Some(ref name))) if name == "a.txt" => {}
+        Err(e) => panic!("unexpected error: {:?}", e),
+        Ok(_) => panic!("the limit was not enforced"),
+    }
+}
+
+#[test]
+fn gap_before_central_directory_counts() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_comment("c".repeat(1000));
+    zip.start_file("a.txt", stored()).unwrap();
+    zip.write_all(&[0; 100]).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    let length = bytes.len() as u64;
+
+    // Without the comment the central directory is shorter, so a gap keeps the archive from
+    // getting shorter than it was
+    let append = |limit: u64| {
+        let mut zip = ZipWriter::new_append(Cursor::new(bytes.clone())).unwrap();
+        zip.set_comment("");
+        zip.set_size_limit(Some(limit));
+        zip.finish().map(|output| output.into_inner().len() as u64)
+    };
+    assert_eq!(append(length).unwrap(), length);
+    match append(length - 1) {
+        Err(ZipError::SizeLimitExceeded(None)) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+/// A writer that only keeps track of its position, which starts after 4 GiB
+struct Far {
+    position: u64,
+    length: u64,
+}
+
+impl Write for Far {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.position += buf.len() as u64;
+        self.length = self.length.max(self.position);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for Far {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = match pos {
+            SeekFrom::Start(position) => position,
+            SeekFrom::Current(offset) => (self.position as i64 + offset) as u64,
+            SeekFrom::End(offset) => (self.length as i64 + offset) as u64,
+        };
+        Ok(self.position)
+    }
+}
+
+#[test]
+fn zip64_end_record_counts() {
+    const START: u64 = 5 << 30;
+    let write = |limit: Option<u64>| {
+        let mut zip = ZipWriter::new(Far {
+            position: START,
+            length: START,
+        });
+        zip.set_size_limit(limit);
+        zip.start_file("a.txt", stored()).unwrap();
+        zip.write_all(&[0; 100]).unwrap();
+        zip.finish().map(|far| far.length - START)
+    };
+    // The central directory starts after 4 GiB, so it needs the ZIP64 end record and locator
+    let size = write(None).unwrap();
+    assert_eq!(write(Some(size)).unwrap(), size);
+    match write(Some(size - 1)) {
+        Err(ZipError::SizeLimitExceeded(None)) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
@@ -0,0 +1,128 @@
+#![cfg(feature = "sniff")]
+
+use std::io::{Cursor, Write};
+use zip::sniff::{ContentType, FlaggedFile, ScanFlag};
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+fn archive() -> Vec<u8> {
+    let mut inner = ZipWriter::new(Cursor::new(Vec::new()));
+    inner.start_file("a.txt", FileOptions::default()).unwrap();
+    inner.write_all(b"a").unwrap();
+    let inner = inner.finish().unwrap().into_inner();
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("notes.txt", FileOptions::default()).unwrap();
+    zip.write_all("Some notes\n".repeat(1000).as_bytes())
+        .unwrap();
+    zip.start_file("image.png", FileOptions::default()).unwrap();
+    zip.write_all(b"\x89PNG\r\n\x1A\n\x00\x00\x00\x0DIHDR")
+        .unwrap();
+    zip.start_file("nested.zip", FileOptions::default())
+        .unwrap();
+    zip.write_all(&inner).unwrap();
+    zip.start_file("empty", FileOptions::default()).unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+const EXPECTED: &[Option<ContentType>] = &[
+    Some(ContentType::Text),
+    Some(ContentType::Png),
+    Some(ContentType::Zip),
+    None,
+];
+
+#[test]
+fn content_type() {
+    let mut archive = ZipArchive::new(Cursor::new(archive())).unwrap();
+    let types: Vec<_> = (0..archive.len())
+        .map(|i| archive.content_type(i).unwrap())
+        .collect();
+    assert_eq!(types, EXPECTED);
+    assert_eq!(ContentType::Png.mime_type(), "image/png");
+    assert!(archive.content_type(archive.len()).is_err());
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn content_type_async() {
+    let mut archive = zip::AsyncZipArchive::new(futures::io::Cursor::new(archive()))
+        .await
+        .unwrap();
+    let mut types = Vec::new();
+    for i in 0..archive.len() {
+        types.push(archive.content_type(i).await.unwrap());
+    }
+    assert_eq!(types, EXPECTED);
+}
+
+fn scanned_archive() -> Vec<u8> {
+    let mut inner = ZipWriter::new(Cursor::new(Vec::new()));
+    inner.add_directory("dir", FileOptions::default()).unwrap();
+    inner
+        .start_file(
+            "dir/secret.txt",
+            FileOptions::default().with_password(b"secret"),
+        )
+        .unwrap();
+    inner.write_all(b"secret").unwrap();
+    let inner = inner.finish().unwrap().into_inner();
+
+    let mut state = 1u32;
+    let random: Vec<u8> = (0..10_000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) as u8
+        })
+        .collect();
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.add_directory("dir", FileOptions::default()).unwrap();
+    zip.start_file("dir/notes.txt", FileOptions::default())
+        .unwrap();
+    zip.write_all("Some notes\n".repeat(1000).as_bytes())
+        .unwrap();
+    zip.start_file("archive.zip", FileOptions::default())
+        .unwrap();
+    zip.write_all(&archive()).unwrap();
+    zip.start_file(
+        "secret.txt",
+        FileOptions::default().with_password(b"secret"),
+    )
+    .unwrap();
+    zip.write_all(b"secret").unwrap();
+    zip.start_file("secret.zip", FileOptions::default())
+        .unwrap();
+    zip.write_all(&inner).unwrap();
+    zip.start_file("random.bin", FileOptions::default())
+        .unwrap();
+    zip.write_all(&random).unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+fn expected_flags() -> Vec<FlaggedFile> {
+    let flagged = |index: usize, name: &str, flag: ScanFlag| FlaggedFile {
+        index,
+        name: name.to_owned(),
+        flag,
+    };
+    vec![
+        flagged(3, "secret.txt", ScanFlag::Encrypted),
+        flagged(4, "secret.zip", ScanFlag::EncryptedArchive),
+        flagged(5, "random.bin", ScanFlag::HighEntropy),
+    ]
+}
+
+#[test]
+fn scan() {
+    let mut archive = ZipArchive::new(Cursor::new(scanned_archive())).unwrap();
+    assert_eq!(archive.scan().unwrap(), expected_flags());
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn scan_async() {
+    let reader = futures::io::Cursor::new(scanned_archive());
+    let mut archive = zip::AsyncZipArchive::new(reader).await.unwrap();
+    assert_eq!(archive.scan().await.unwrap(), expected_flags());
+}
@@ -0,0 +1,61 @@
+use rand::{Rng, SeedableRng};
+use std::io::{Cursor, Read, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+fn random_data(len: usize) -> Vec<u8> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    (0..len).map(|_| rng.gen()).collect()
+}
+
+#[test]
+fn stores_incompressible_files() {
+    let random = random_data(200_000);
+    let text = b"Hello, World! ".repeat(20_000);
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .store_if_incompressible(0.95);
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("random.bin", options).unwrap();
+    zip.write_all(&random).unwrap();
+    zip.start_file("text.txt", options).unwrap();
+    zip.write_all(&text).unwrap();
+    zip.start_file("small.bin", options).unwrap();
+    zip.write_all(&random[..100]).unwrap();
+    zip.start_file("empty.txt", options).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let expected: [(&str, &[u8], CompressionMethod); 4] = [
+        ("random.bin", &random, CompressionMethod::Stored),
+        ("text.txt", &text, CompressionMethod::Deflated),
+        ("small.bin", &random[..100], CompressionMethod::Stored),
+        ("empty.txt", b"", CompressionMethod::Stored),
+    ];
+    for (name, data, method) in expected.iter() {
+        let mut file = archive.by_name(name).unwrap();
+        assert_eq!(file.compression(), *method, "{}", name);
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).unwrap();
+        assert_eq!(&contents[..], *data, "{}", name);
+    }
+}
+
+#[test]
+fn local_header_is_rewritten() {
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .store_if_incompressible(0.5);
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("random.bin", options).unwrap();
+    zip.write_all(&random_data(1000)).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    // The compression method of the first local header, read by the streaming reader
+    let mut reader = &bytes[..];
+    let file = zip::read::read_zipfile_from_stream(&mut reader)
+        .unwrap()
+        .unwrap();
+    assert_eq!(file.compression(), CompressionMethod::Stored);
+}
@@ -0,0 +1,126 @@
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use zip::write::{FileOptions, StoredOptions};
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// A writer that counts how often it seeks to an absolute position
+#[derive(Default)]
+struct SeekCounter {
+    inner: Cursor<Vec<u8>>,
+    absolute_seeks: usize,
+}
+
+impl Write for SeekCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Seek for SeekCounter {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        if let SeekFrom::Start(_) = pos {
+            self.absolute_seeks += 1;
+        }
+        self.inner.seek(pos)
+    }
+}
+
+fn read(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> Vec<u8> {
+    let mut content = Vec::new();
+    archive
+        .by_name(name)
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap();
+    content
+}
+
+#[test]
+fn stored_files_are_not_updated() {
+    let mut zip = ZipWriter::new(SeekCounter::default());
+    for (name, data) in &[("a.png", &b"not really an image"[..]), ("b.png", b"")] {
+        zip.start_stored_file(*name, StoredOptions::for_data(data))
+            .unwrap();
+        zip.write_all(data).unwrap();
+    }
+    let output = zip.finish().unwrap();
+    assert_eq!(output.absolute_seeks, 0);
+
+    let mut archive = ZipArchive::new(output.inner).unwrap();
+    assert_eq!(
+        archive.by_name("a.png").unwrap().compression(),
+        CompressionMethod::Stored
+    );
+    assert_eq!(
+        archive.by_name("a.png").unwrap().unix_mode(),
+        Some(0o100644)
+    );
+    assert_eq!(read(&mut archive, "a.png"), b"not really an image");
+    assert_eq!(read(&mut archive, "b.png"), b"");
+}
+
+#[test]
+fn stored_file_between_compressed_files() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("before.txt", FileOptions::default())
+        .unwrap();
+    zip.write_all(b"before").unwrap();
+    let options = StoredOptions::for_data(b"stored").unix_permissions(0o600);
+    zip.start_stored_file("stored.bin", options).unwrap();
+    zip.write_all(b"stored").unwrap();
+    zip.start_file("after.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"after").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(read(&mut archive, "before.txt"), b"before");
+    assert_eq!(read(&mut archive, "stored.bin"), b"stored");
+    assert_eq!(read(&mut archive, "after.txt"), b"after");
+    assert_eq!(
+        archive.by_name("stored.bin").unwrap().unix_mode(),
+        Some(0o100600)
+    );
+}
+
+#[test]
+fn stored_file_mismatch() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_stored_file("short.bin", StoredOptions::for_data(b"expected"))
+        .unwrap();
+    zip.write_all(b"short").unwrap();
+    assert!(zip.finish().is_err());
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_stored_file("changed.bin", StoredOptions::for_data(b"expected"))
+        .unwrap();
+    zip.write_all(b"unexpect").unwrap();
+    assert!(zip.start_file("next", FileOptions::default()).is_err());
+}
+
+#[test]
+fn stored_data() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("stored", stored).unwrap();
+    zip.write_all(b"stored contents").unwrap();
+    zip.start_file("deflated", FileOptions::default()).unwrap();
+    zip.write_all(b"deflated contents").unwrap();
+    zip.start_file("encrypted", stored.with_password(b"password"))
+        .unwrap();
+    zip.write_all(b"encrypted contents").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let archive = ZipArchive::new(Cursor::new(&bytes[..])).unwrap();
+    let data = archive.stored_data(0).unwrap();
+    assert_eq!(data, b"stored contents");
+    // The slice borrows the archive data, no copy is made
+    let start = data.as_ptr() as usize;
+    assert!(start > bytes.as_ptr() as usize);
+    assert!(start + data.len() < bytes.as_ptr() as usize + bytes.len());
+    assert!(archive.stored_data(1).is_err());
+    assert!(archive.stored_data(2).is_err());
+    assert!(archive.stored_data(3).is_err());
+}
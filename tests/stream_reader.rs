@@ -0,0 +1,159 @@
+use std::io::{self, Cursor, Read, Write};
+use zip::read::ZipStreamReader;
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// A reader that can't seek, like a pipe
+struct Pipe<'a>(&'a [u8]);
+
+impl Read for Pipe<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+#[test]
+fn stream_reader() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("stored.txt", stored).unwrap();
+    zip.write_all(b"stored data").unwrap();
+    #[cfg(feature = "deflate")]
+    {
+        let options = FileOptions::default().data_descriptor(true);
+        zip.start_file("described.txt", options).unwrap();
+        zip.write_all(b"data followed by a data descriptor")
+            .unwrap();
+    }
+    zip.add_directory("dir", FileOptions::default()).unwrap();
+    zip.start_file("skipped.txt", stored).unwrap();
+    zip.write_all(b"not read").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut stream = ZipStreamReader::new(Pipe(&bytes));
+    let mut entries = Vec::new();
+    while let Some(mut file) = stream.next_entry().unwrap() {
+        let mut content = Vec::new();
+        if file.name() != "skipped.txt" {
+            file.read_to_end(&mut content).unwrap();
+        }
+        entries.push((file.name().to_owned(), content));
+    }
+    assert!(stream.next_entry().unwrap().is_none());
+
+    let mut expected = vec![("stored.txt".to_owned(), b"stored data".to_vec())];
+    #[cfg(feature = "deflate")]
+    expected.push((
+        "described.txt".to_owned(),
+        b"data followed by a data descriptor".to_vec(),
+    ));
+    expected.push(("dir/".to_owned(), Vec::new()));
+    expected.push(("skipped.txt".to_owned(), Vec::new()));
+    assert_eq!(entries, expected);
+}
+
+#[test]
+fn stream_reader_empty_archive() {
+    let bytes = ZipWriter::new(Cursor::new(Vec::new()))
+        .finish()
+        .unwrap()
+        .into_inner();
+    let mut stream = ZipStreamReader::new(Pipe(&bytes));
+    assert!(stream.next_entry().unwrap().is_none());
+}
+
+#[test]
+fn stream_reader_split_marker() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"a").unwrap();
+    let mut bytes = 0x08074b50u32.to_le_bytes().to_vec();
+    bytes.extend(zip.finish().unwrap().into_inner());
+
+    let mut stream = ZipStreamReader::new(Pipe(&bytes));
+    let mut content = String::new();
+    let mut file = stream.next_entry().unwrap().unwrap();
+    file.read_to_string(&mut content).unwrap();
+    assert_eq!((file.name(), content.as_str()), ("a.txt", "a"));
+    drop(file);
+    assert!(stream.next_entry().unwrap().is_none());
+
+    let mut stream = ZipStreamReader::new(Pipe(b"not an archive"));
+    assert!(stream.next_entry().is_err());
+}
+
+#[cfg(feature = "deflate")]
+#[test]
+fn stream_reader_descriptors() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().data_descriptor(true);
+    for i in 0..3 {
+        zip.start_file(format!("{}.txt", i), options).unwrap();
+        for _ in 0..1000 {
+            write!(zip, "{} ", i).unwrap();
+        }
+    }
+    let bytes = zip.finish().unwrap().into_inner();
+
+    // The data of the entries is read ahead, but only what belongs to them is decompressed
+    let mut stream = ZipStreamReader::new(Pipe(&bytes));
+    for i in 0..3 {
+        let mut file = stream.next_entry().unwrap().unwrap();
+        let mut content = String::new();
+        file.read_to_string(&mut content).unwrap();
+        assert_eq!(content, format!("{} ", i).repeat(1000));
+        assert_eq!(file.size(), 2000);
+    }
+    assert!(stream.next_entry().unwrap().is_none());
+
+    let mut stream = ZipStreamReader::new(Pipe(&bytes));
+    stream.set_size_limit(Some(1000));
+    let mut file = stream.next_entry().unwrap().unwrap();
+    let error = file.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    drop(file);
+    // The entry is skipped all the same
+    assert_eq!(stream.next_entry().unwrap().unwrap().name(), "1.txt");
+}
+
+#[cfg(feature = "deflate")]
+#[test]
+fn stream_reader_corrupt_descriptor_entry() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().data_descriptor(true);
+    for name in &["a.txt", "b.txt"] {
+        zip.start_file(*name, options).unwrap();
+        zip.write_all(&[b'a'; 2000]).unwrap();
+    }
+    let mut bytes = zip.finish().unwrap().into_inner();
+    // A deflate block of the reserved type at the start of the data of the first entry
+    let name_length = u16::from_le_bytes([bytes[26], bytes[27]]) as usize;
+    let extra_length = u16::from_le_bytes([bytes[28], bytes[29]]) as usize;
+    bytes[30 + name_length + extra_length] = 0xFF;
+
+    let mut stream = ZipStreamReader::new(Pipe(&bytes));
+    let mut file = stream.next_entry().unwrap().unwrap();
+    assert!(file.read_to_end(&mut Vec::new()).is_err());
+    drop(file);
+    match stream.next_entry().map(|_| ()) {
+        Err(ZipError::InvalidArchive(_)) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+    assert!(stream.next_entry().is_err());
+}
+
+#[test]
+fn stream_reader_size_limit() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("large.txt", FileOptions::default()).unwrap();
+    zip.write_all(&[b'a'; 2000]).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut stream = ZipStreamReader::new(Pipe(&bytes));
+    stream.set_size_limit(Some(1000));
+    match stream.next_entry().map(|_| ()) {
+        Err(ZipError::SizeLimitExceeded(Some(name))) => assert_eq!(name, "large.txt"),
+        result => panic!("unexpected result {:?}", result),
+    };
+}
@@ -0,0 +1,188 @@
+use std::io::Cursor;
+use zip::result::ZipError;
+use zip::{EncryptionMethod, StrongEncryptionAlgorithm, ZipArchive};
+
+const NAME: &[u8] = b"secret.txt";
+const DATA: &[u8] = b"encrypted data that cannot be read";
+
+/// Build an archive with a single file that uses strong encryption, with the strong encryption
+/// header in the central directory if `algorithm` is given.
+fn archive(algorithm: Option<(u16, u16)>) -> Vec<u8> {
+    // Encrypted and strong encryption
+    let flags: u16 = 1 | 1 << 6;
+    let mut extra_field = Vec::new();
+    if let Some((id, bit_length)) = algorithm {
+        for value in &[0x0017, 8, 2, id, bit_length, 1] {
+            extra_field.extend_from_slice(&u16::to_le_bytes(*value));
+        }
+    }
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&0x04034b50u32.to_le_bytes());
+    for value in &[51, flags, 0, 0, 0] {
+        bytes.extend_from_slice(&u16::to_le_bytes(*value));
+    }
+    for value in &[0, DATA.len() as u32, DATA.len() as u32] {
+        bytes.extend_from_slice(&u32::to_le_bytes(*value));
+    }
+    bytes.extend_from_slice(&(NAME.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    bytes.extend_from_slice(NAME);
+    bytes.extend_from_slice(DATA);
+
+    let central_directory_start = bytes.len() as u32;
+    bytes.extend_from_slice(&0x02014b50u32.to_le_bytes());
+    for value in &[51, 51, flags, 0, 0, 0] {
+        bytes.extend_from_slice(&u16::to_le_bytes(*value));
+    }
+    for value in &[0, DATA.len() as u32, DATA.len() as u32] {
+        bytes.extend_from_slice(&u32::to_le_bytes(*value));
+    }
+    for value in &[NAME.len() as u16, extra_field.len() as u16, 0, 0, 0] {
+        bytes.extend_from_slice(&u16::to_le_bytes(*value));
+    }
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    bytes.extend_from_slice(NAME);
+    bytes.extend_from_slice(&extra_field);
+    let central_directory_size = bytes.len() as u32 - central_directory_start;
+
+    bytes.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    for value in &[0, 0, 1, 1] {
+        bytes.extend_from_slice(&u16::to_le_bytes(*value));
+    }
+    bytes.extend_from_slice(&central_directory_size.to_le_bytes());
+    bytes.extend_from_slice(&central_directory_start.to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn strong_encryption_header() {
+    let mut archive = ZipArchive::new(Cursor::new(archive(Some((0x6610, 256))))).unwrap();
+    assert_eq!(
+        archive.encryption_method(0).unwrap(),
+        Some(EncryptionMethod::Strong {
+            algorithm: StrongEncryptionAlgorithm::Aes256,
+            bit_length: 256,
+        })
+    );
+
+    match archive.by_index_decrypt(0, b"password").map(|_| ()) {
+        Err(ZipError::UnsupportedEncryption(StrongEncryptionAlgorithm::Aes256)) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+    match archive.by_index(0).map(|_| ()) {
+        Err(error @ ZipError::UnsupportedEncryption(_)) => {
+            assert_eq!(error.to_string(), "unsupported encryption: AES-256")
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+    assert!(archive.verify_password(0, b"password").is_err());
+}
+
+#[test]
+fn strong_encryption_without_header() {
+    let mut archive = ZipArchive::new(Cursor::new(archive(None))).unwrap();
+    let algorithm = StrongEncryptionAlgorithm::Unknown(0xFFFF);
+    assert_eq!(
+        archive.encryption_method(0).unwrap(),
+        Some(EncryptionMethod::Strong {
+            algorithm,
+            bit_length: 0,
+        })
+    );
+    match archive.by_index(0).map(|_| ()) {
+        Err(ZipError::UnsupportedEncryption(a)) if a == algorithm => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn strong_encryption_streaming() {
+    let bytes = archive(Some((0x6603, 168)));
+    match zip::read::read_zipfile_from_stream(&mut &bytes[..]).map(|_| ()) {
+        Err(ZipError::UnsupportedEncryption(StrongEncryptionAlgorithm::Unknown(0xFFFF))) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+/// Build an archive with an encrypted central directory, which is described by a version 2 ZIP64
+/// central directory end record if `zip64` is set, and otherwise only recognizable by the archive
+/// extra data record in front of it.
+fn encrypted_directory_archive(zip64: bool) -> Vec<u8> {
+    let mut bytes = vec![0; 30];
+    bytes[..4].copy_from_slice(&0x04034b50u32.to_le_bytes());
+
+    let central_directory_start = bytes.len() as u64;
+    if !zip64 {
+        bytes.extend_from_slice(&0x08064b50u32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+    }
+    bytes.extend_from_slice(b"encrypted records");
+    let central_directory_size = bytes.len() as u64 - central_directory_start;
+
+    if zip64 {
+        let zip64_end_start = bytes.len() as u64;
+        bytes.extend_from_slice(&0x06064b50u32.to_le_bytes());
+        bytes.extend_from_slice(&72u64.to_le_bytes());
+        for value in &[62u16, 62] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in &[0u32, 0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in &[1, 1, central_directory_size, central_directory_start] {
+            bytes.extend_from_slice(&u64::to_le_bytes(*value));
+        }
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        for value in &[central_directory_size, central_directory_size] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in &[0x6610u16, 256, 1, 0, 0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&0x07064b50u32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&zip64_end_start.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+    }
+
+    bytes.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    for value in &[0, 0, 1, 1] {
+        bytes.extend_from_slice(&u16::to_le_bytes(*value));
+    }
+    if zip64 {
+        bytes.extend_from_slice(&[0xFF; 8]);
+    } else {
+        bytes.extend_from_slice(&(central_directory_size as u32).to_le_bytes());
+        bytes.extend_from_slice(&(central_directory_start as u32).to_le_bytes());
+    }
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn encrypted_central_directory() {
+    for &(zip64, algorithm) in &[
+        (true, StrongEncryptionAlgorithm::Aes256),
+        (false, StrongEncryptionAlgorithm::Unknown(0xFFFF)),
+    ] {
+        let mut reader = Cursor::new(encrypted_directory_archive(zip64));
+        assert_eq!(
+            ZipArchive::central_directory_encryption(&mut reader).unwrap(),
+            Some(algorithm)
+        );
+        match ZipArchive::new(reader).map(|_| ()) {
+            Err(ZipError::EncryptedCentralDirectory(a)) if a == algorithm => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    let mut reader = Cursor::new(archive(None));
+    assert_eq!(
+        ZipArchive::central_directory_encryption(&mut reader).unwrap(),
+        None
+    );
+}
@@ -0,0 +1,77 @@
+use std::fs;
+use std::io::{self, Cursor, Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use zip::temp::{DirStore, MemoryStore, TempFile, TempStore};
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+/// A store that counts the temporary files it creates
+#[derive(Clone, Default)]
+struct Counting(Arc<AtomicUsize>);
+
+impl TempStore for Counting {
+    fn create(&self) -> io::Result<Box<dyn TempFile>> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        MemoryStore.create()
+    }
+}
+
+fn write_encrypted<T: TempStore + 'static>(store: T, data: &[u8]) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_temp_store(store);
+    let options = FileOptions::default().with_password(b"password");
+    zip.start_file("a.txt", options).unwrap();
+    zip.write_all(data).unwrap();
+    zip.start_file("b.txt", options).unwrap();
+    zip.write_all(data).unwrap();
+    zip.start_file("plain.txt", FileOptions::default()).unwrap();
+    zip.write_all(data).unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+fn check_contents(bytes: Vec<u8>, data: &[u8]) {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    for name in &["a.txt", "b.txt"] {
+        let mut contents = Vec::new();
+        archive
+            .by_name_decrypt(name, b"password")
+            .unwrap()
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert_eq!(contents, data);
+    }
+}
+
+#[test]
+fn custom_store() {
+    let data = b"Hello, World! ".repeat(10_000);
+    let store = Counting::default();
+    let bytes = write_encrypted(store.clone(), &data);
+    // Only the encrypted files are kept in temporary files
+    assert_eq!(store.0.load(Ordering::SeqCst), 2);
+    check_contents(bytes, &data);
+}
+
+#[test]
+fn dir_store() {
+    let dir = std::env::temp_dir().join(format!("zip-temp-store-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let data = b"Hello, World! ".repeat(10_000);
+    let bytes = write_encrypted(DirStore::new(&dir), &data);
+    // The temporary files are deleted once the files are finished
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    fs::remove_dir_all(&dir).unwrap();
+    check_contents(bytes, &data);
+}
+
+#[test]
+fn missing_dir() {
+    let dir = std::env::temp_dir().join(format!("zip-temp-store-missing-{}", std::process::id()));
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_temp_store(DirStore::new(&dir));
+    let options = FileOptions::default().with_password(b"password");
+    assert!(zip.start_file("a.txt", options).is_err());
+}
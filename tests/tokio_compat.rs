@@ -0,0 +1,95 @@
+#![cfg(feature = "tokio")]
+
+use futures::io::AsyncReadExt;
+use std::io::{Cursor, Write};
+use std::pin::Pin;
+use tokio_crate::io::{AsyncRead, AsyncWrite, ReadBuf};
+use zip::tokio_compat::TokioCompat;
+use zip::write::{AsyncZipWriter, FileOptions};
+use zip::{AsyncZipArchive, CompressionMethod, ZipWriter};
+
+/// Read all of `reader` through the `tokio::io` trait, a few bytes at a time
+async fn read_to_end<R: AsyncRead + ?Sized>(mut reader: Pin<&mut R>) -> Vec<u8> {
+    let mut contents = Vec::new();
+    let mut buf = [0; 3];
+    loop {
+        let mut buf = ReadBuf::new(&mut buf);
+        futures::future::poll_fn(|cx| reader.as_mut().poll_read(cx, &mut buf))
+            .await
+            .unwrap();
+        if buf.filled().is_empty() {
+            return contents;
+        }
+        contents.extend_from_slice(buf.filled());
+    }
+}
+
+#[futures_await_test::async_test]
+async fn tokio_compat() {
+    let mut zip = AsyncZipWriter::new(TokioCompat::new(Cursor::new(Vec::new())));
+    for &name in &["a.txt", "b.txt"] {
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        zip.start_file(name, options).await.unwrap();
+        let content = format!("contents of {}", name);
+        let written =
+            futures::future::poll_fn(|cx| Pin::new(&mut zip).poll_write(cx, content.as_bytes()))
+                .await
+                .unwrap();
+        assert_eq!(written, content.len());
+    }
+    let bytes = zip.finish().await.unwrap().get_ref().get_ref().clone();
+
+    let mut archive = AsyncZipArchive::new(TokioCompat::new(Cursor::new(bytes)))
+        .await
+        .unwrap();
+    assert_eq!(archive.len(), 2);
+    let file = archive.by_name("b.txt").await.unwrap();
+    assert_eq!(
+        read_to_end(Box::pin(file).as_mut()).await,
+        b"contents of b.txt"
+    );
+    let mut contents = String::new();
+    archive
+        .by_index(0)
+        .await
+        .unwrap()
+        .read_to_string(&mut contents)
+        .await
+        .unwrap();
+    assert_eq!(contents, "contents of a.txt");
+}
+
+#[test]
+fn extract_async() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.add_directory("dir", FileOptions::default()).unwrap();
+    for i in 0..5 {
+        zip.start_file(format!("dir/{}.txt", i), FileOptions::default())
+            .unwrap();
+        zip.write_all(format!("file {}", i).as_bytes()).unwrap();
+    }
+    let bytes = zip.finish().unwrap().into_inner();
+    let dir = std::env::temp_dir().join(format!("zip-extract-async-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let runtime = tokio_crate::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let reader = futures::io::Cursor::new(bytes);
+        let mut archive = AsyncZipArchive::new(reader).await.unwrap();
+        let summary = archive.extract_async(&dir, 2).await.unwrap();
+        assert_eq!(summary.files_written(), 5);
+        assert_eq!(summary.directories_created(), 1);
+        assert_eq!(summary.bytes_written(), 30);
+        assert!(summary.warnings().is_empty());
+
+        let summary = archive.extract_async(&dir, 2).await.unwrap();
+        assert_eq!(summary.warnings().len(), 5);
+    });
+    for i in 0..5 {
+        let content = std::fs::read(dir.join(format!("dir/{}.txt", i))).unwrap();
+        assert_eq!(content, format!("file {}", i).as_bytes());
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
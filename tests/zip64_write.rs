@@ -0,0 +1,483 @@
+use std::collections::BTreeMap;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use zip::read::{Config, EndRecordPrecedence, ValidationIssue, Zip64EndLookup};
+use zip::result::ZipError;
+use zip::write::{FileOptions, StoredOptions};
+use zip::{CompressionMethod, ExtensibleDataBlock, ZipArchive, ZipWriter};
+
+const ZIP64_LIMIT: u64 = u32::MAX as u64;
+/// The CRC-32 of `ZIP64_LIMIT + 10` zeros
+const LARGE_ZEROS_CRC32: u32 = 0xE38A6876;
+
+/// A file that only keeps writes smaller than `BLOCK_SIZE`, while larger writes must be zeros,
+/// which are not stored.
+///
+/// This makes it possible to write archives larger than 4 GiB without keeping the data in memory,
+/// because the data of the files is written in blocks and the headers in smaller pieces.
+#[derive(Default)]
+struct SparseFile {
+    chunks: BTreeMap<u64, Vec<u8>>,
+    position: u64,
+    length: u64,
+}
+
+const BLOCK_SIZE: usize = 1 << 20;
+
+impl Write for SparseFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() < BLOCK_SIZE {
+            self.chunks.insert(self.position, buf.to_vec());
+        }
+        self.position += buf.len() as u64;
+        self.length = self.length.max(self.position);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for SparseFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let position = self.position;
+        let mut count = (buf.len() as u64).min(self.length.saturating_sub(position)) as usize;
+        for byte in buf[..count].iter_mut() {
+            *byte = 0;
+        }
+        if let Some((start, chunk)) = self.chunks.range(..=position).next_back() {
+            let offset = (position - start) as usize;
+            if offset < chunk.len() {
+                count = count.min(chunk.len() - offset);
+                buf[..count].copy_from_slice(&chunk[offset..offset + count]);
+            }
+        }
+        if let Some((start, _)) = self.chunks.range(position + 1..).next() {
+            count = count.min((start - position) as usize);
+        }
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl Seek for SparseFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = match pos {
+            SeekFrom::Start(offset) => offset,
+            SeekFrom::End(offset) => (self.length as i64 + offset) as u64,
+            SeekFrom::Current(offset) => (self.position as i64 + offset) as u64,
+        };
+        Ok(self.position)
+    }
+}
+
+/// Write `size` zeros in large blocks
+fn write_zeros<W: Write>(writer: &mut W, size: u64) {
+    let block = vec![0; BLOCK_SIZE];
+    let mut remaining = size;
+    while remaining > 0 {
+        let count = remaining.min(block.len() as u64) as usize;
+        writer.write_all(&block[..count]).unwrap();
+        remaining -= count as u64;
+    }
+}
+
+#[test]
+fn many_entries() {
+    let count = u16::MAX as usize + 10;
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    for i in 0..count {
+        zip.start_file(i.to_string(), options).unwrap();
+    }
+    let bytes = zip.finish().unwrap().into_inner();
+
+    // The end of central directory record refers to the ZIP64 record
+    let end = &bytes[bytes.len() - 22..];
+    assert_eq!(&end[8..12], &[0xFF; 4]);
+    assert_eq!(&bytes[bytes.len() - 42..bytes.len() - 38], b"PK\x06\x07");
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.len(), count);
+    assert_eq!(archive.by_index(count - 1).unwrap().name(), "65544");
+    assert_eq!(archive.zip64_extensible_data(), Some(&[][..]));
+}
+
+#[test]
+fn large_file_option() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().large_file(true);
+    zip.start_file("small.txt", options).unwrap();
+    zip.write_all(b"small file in a ZIP64 local header")
+        .unwrap();
+    zip.start_file("other.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"other").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    // Version needed to extract and sizes of the local header
+    assert_eq!(&bytes[4..6], &45u16.to_le_bytes());
+    assert_eq!(&bytes[18..26], &[0xFF; 8]);
+
+    let mut content = String::new();
+    let mut archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+    archive
+        .by_name("small.txt")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, "small file in a ZIP64 local header");
+
+    let mut reader = &bytes[..];
+    let mut file = zip::read::read_zipfile_from_stream(&mut reader)
+        .unwrap()
+        .unwrap();
+    assert_eq!(file.size(), 34);
+    content.clear();
+    file.read_to_string(&mut content).unwrap();
+    assert_eq!(content, "small file in a ZIP64 local header");
+}
+
+#[test]
+fn large_file_required() {
+    let size = ZIP64_LIMIT + 1;
+    let mut zip = ZipWriter::new(SparseFile::default());
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("large", options).unwrap();
+    write_zeros(&mut zip, size);
+    assert!(zip.finish().is_err());
+}
+
+#[test]
+fn large_entries() {
+    let size = ZIP64_LIMIT + 10;
+    let mut zip = ZipWriter::new(SparseFile::default());
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .large_file(true);
+    zip.start_file("streamed", options).unwrap();
+    write_zeros(&mut zip, size);
+    let stored_options = StoredOptions::new(size, LARGE_ZEROS_CRC32);
+    zip.start_stored_file("stored", stored_options).unwrap();
+    write_zeros(&mut zip, size);
+    zip.start_file("after", FileOptions::default()).unwrap();
+    zip.write_all(b"after the large files").unwrap();
+    let file = zip.finish().unwrap();
+
+    let mut archive = ZipArchive::new(file).unwrap();
+    for name in &["streamed", "stored"] {
+        let file = archive.by_name(name).unwrap();
+        assert_eq!(file.size(), size);
+        assert_eq!(file.compressed_size(), size);
+    }
+    assert!(archive.by_name("after").unwrap().header_start() > 2 * ZIP64_LIMIT);
+    let mut content = String::new();
+    archive
+        .by_name("after")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, "after the large files");
+}
+
+/// The number of entries and bytes of the error that ZIP64 is required
+fn zip64_required<T>(result: zip::result::ZipResult<T>) -> (usize, u64) {
+    match result {
+        Err(ZipError::Zip64Required { entries, bytes }) => (entries, bytes),
+        Err(e) => panic!("unexpected error {:?}", e),
+        Ok(_) => panic!("ZIP64 was not forbidden"),
+    }
+}
+
+#[test]
+fn forbid_zip64_options() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().forbid_zip64(true);
+    assert_eq!(
+        zip64_required(zip.start_file("large", options.large_file(true))),
+        (1, 0)
+    );
+    zip.start_file("small", options).unwrap();
+    zip.write_all(b"small").unwrap();
+
+    zip.set_forbid_zip64(true);
+    let stored_options = StoredOptions::new(ZIP64_LIMIT, 0);
+    assert_eq!(
+        zip64_required(zip.start_stored_file("stored", stored_options)),
+        (2, ZIP64_LIMIT)
+    );
+    let bytes = zip.finish().unwrap().into_inner();
+    assert_eq!(ZipArchive::new(Cursor::new(bytes)).unwrap().len(), 1);
+}
+
+#[test]
+fn forbid_zip64_streamed() {
+    let mut zip = ZipWriter::new(SparseFile::default());
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .forbid_zip64(true);
+    zip.start_file("large", options).unwrap();
+    write_zeros(&mut zip, ZIP64_LIMIT - 1);
+    // The write that reaches the limit fails
+    let error = zip.write_all(&[0]).unwrap_err();
+    let error = *error.into_inner().unwrap().downcast::<ZipError>().unwrap();
+    assert_eq!(zip64_required(Err::<(), _>(error)), (1, ZIP64_LIMIT));
+}
+
+#[test]
+fn forbid_zip64_offsets() {
+    let mut zip = ZipWriter::new(SparseFile::default());
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("large", options.large_file(true)).unwrap();
+    write_zeros(&mut zip, ZIP64_LIMIT + 10);
+    let (entries, bytes) = zip64_required(zip.start_file("after", options.forbid_zip64(true)));
+    assert_eq!(entries, 2);
+    assert!(bytes > ZIP64_LIMIT + 10);
+    zip.set_forbid_zip64(true);
+    let (entries, central_start) = zip64_required(zip.finish());
+    assert_eq!(entries, 1);
+    // Nothing was written for the entry that failed
+    assert_eq!(central_start, bytes);
+}
+
+#[test]
+fn forbid_zip64_many_entries() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_forbid_zip64(true);
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    for i in 0..u16::MAX - 1 {
+        zip.start_file(i.to_string(), options).unwrap();
+    }
+    let (entries, _) = zip64_required(zip.start_file("last", options));
+    assert_eq!(entries, u16::MAX as usize);
+    let archive = ZipArchive::new(zip.finish().unwrap()).unwrap();
+    assert_eq!(archive.len(), u16::MAX as usize - 1);
+}
+
+fn extensible_data_blocks() -> Vec<ExtensibleDataBlock> {
+    vec![ExtensibleDataBlock {
+        header_id: 0x4242,
+        data: b"custom data".to_vec(),
+    }]
+}
+
+#[test]
+fn extensible_data() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_zip64_extensible_data(ExtensibleDataBlock::encode_all(&extensible_data_blocks()));
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"a").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let data = archive.zip64_extensible_data().unwrap();
+    assert_eq!(
+        ExtensibleDataBlock::parse_all(data),
+        Some(extensible_data_blocks())
+    );
+    let mut content = String::new();
+    archive
+        .by_name("a.txt")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, "a");
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    let archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.zip64_extensible_data(), None);
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_forbid_zip64(true);
+    zip.set_zip64_extensible_data(b"data".to_vec());
+    assert_eq!(zip64_required(zip.finish()), (0, 0));
+}
+
+#[test]
+fn wrong_locator_offset() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_zip64_extensible_data(ExtensibleDataBlock::encode_all(&extensible_data_blocks()));
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"a").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    let archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+    assert_eq!(archive.zip64_end_lookup(), Some(Zip64EndLookup::Locator));
+
+    // The offset in the locator is past the record, or past the end, and the archive may have
+    // data prepended like a self-extracting archive
+    let locator = bytes.len() - 22 - 20;
+    let mut record = [0; 8];
+    record.copy_from_slice(&bytes[locator + 8..locator + 16]);
+    let record = u64::from_le_bytes(record);
+    for &(prefix, offset) in &[(0, record + 1), (0, u64::MAX), (100, u64::MAX)] {
+        let mut bytes = [vec![0; prefix], bytes.clone()].concat();
+        bytes[prefix + locator + 8..prefix + locator + 16].copy_from_slice(&offset.to_le_bytes());
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.zip64_end_lookup(), Some(Zip64EndLookup::Scan));
+        assert_eq!(archive.offset(), prefix as u64);
+        let data = archive.zip64_extensible_data().unwrap();
+        assert_eq!(
+            ExtensibleDataBlock::parse_all(data),
+            Some(extensible_data_blocks())
+        );
+        let mut content = String::new();
+        archive
+            .by_name("a.txt")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "a");
+    }
+}
+
+#[test]
+fn end_record_mismatch() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_zip64_extensible_data(ExtensibleDataBlock::encode_all(&extensible_data_blocks()));
+    for &name in &["a.txt", "b.txt"] {
+        zip.start_file(name, FileOptions::default()).unwrap();
+        zip.write_all(name.as_bytes()).unwrap();
+    }
+    let mut bytes = zip.finish().unwrap().into_inner();
+    assert!(ZipArchive::new(Cursor::new(bytes.clone()))
+        .unwrap()
+        .validate()
+        .is_empty());
+
+    // The central directory end record claims a single file, the ZIP64 one two
+    let end = bytes.len() - 22;
+    bytes[end + 8..end + 10].copy_from_slice(&1u16.to_le_bytes());
+    bytes[end + 10..end + 12].copy_from_slice(&1u16.to_le_bytes());
+    for &(precedence, len) in &[
+        (EndRecordPrecedence::Zip64, 2),
+        (EndRecordPrecedence::Classic, 1),
+    ] {
+        let config = Config::default().end_record_precedence(precedence);
+        let archive = ZipArchive::new_with_config(Cursor::new(bytes.clone()), config).unwrap();
+        assert_eq!(archive.len(), len);
+        assert_eq!(archive.validate(), vec![ValidationIssue::EndRecordMismatch]);
+    }
+}
+
+/// Rename `a.txt` to `b.txt` in the central directory of `bytes`
+fn tamper_central_directory(bytes: &mut [u8]) {
+    let offset = bytes.windows(5).rposition(|name| name == b"a.txt").unwrap();
+    bytes[offset] = b'b';
+}
+
+#[test]
+fn central_directory_checksum() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_zip64_extensible_data(ExtensibleDataBlock::encode_all(&extensible_data_blocks()));
+    zip.set_central_directory_checksum(true);
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"a").unwrap();
+    let mut bytes = zip.finish().unwrap().into_inner();
+
+    let archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+    let blocks = ExtensibleDataBlock::parse_all(archive.zip64_extensible_data().unwrap()).unwrap();
+    assert_eq!(blocks[..1], extensible_data_blocks()[..]);
+    assert_eq!(
+        blocks[1].header_id,
+        ExtensibleDataBlock::CENTRAL_DIRECTORY_CHECKSUM
+    );
+
+    tamper_central_directory(&mut bytes);
+    match ZipArchive::new(Cursor::new(bytes)) {
+        Err(ZipError::InvalidArchive("Central directory checksum mismatch")) => {}
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.set_forbid_zip64(true);
+    zip.set_central_directory_checksum(true);
+    assert_eq!(zip64_required(zip.finish()), (0, 0));
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn async_many_entries() {
+    use futures::io::AsyncWriteExt;
+
+    let count = u16::MAX as usize + 10;
+    let mut zip = zip::write::AsyncZipWriter::new(futures::io::Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("large_file", options.large_file(true))
+        .await
+        .unwrap();
+    zip.write_all(b"data").await.unwrap();
+    for i in 1..count {
+        zip.start_file(i.to_string(), options).await.unwrap();
+    }
+    let bytes = zip.finish().await.unwrap().get_ref().clone();
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.len(), count);
+    let mut content = Vec::new();
+    archive
+        .by_name("large_file")
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap();
+    assert_eq!(content, b"data");
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn async_forbid_zip64() {
+    let mut zip = zip::write::AsyncZipWriter::new(futures::io::Cursor::new(Vec::new()));
+    zip.set_forbid_zip64(true);
+    let options = FileOptions::default().large_file(true);
+    assert_eq!(
+        zip64_required(zip.start_file("large", options).await),
+        (1, 0)
+    );
+    zip.start_file("small", FileOptions::default())
+        .await
+        .unwrap();
+    let bytes = zip.finish().await.unwrap().get_ref().clone();
+    assert_eq!(ZipArchive::new(Cursor::new(bytes)).unwrap().len(), 1);
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn async_extensible_data() {
+    let mut zip = zip::write::AsyncZipWriter::new(futures::io::Cursor::new(Vec::new()));
+    zip.set_zip64_extensible_data(ExtensibleDataBlock::encode_all(&extensible_data_blocks()));
+    zip.start_file("a.txt", FileOptions::default())
+        .await
+        .unwrap();
+    let bytes = zip.finish().await.unwrap().get_ref().clone();
+
+    let archive = zip::AsyncZipArchive::new(futures::io::Cursor::new(bytes))
+        .await
+        .unwrap();
+    let data = archive.zip64_extensible_data().unwrap();
+    assert_eq!(
+        ExtensibleDataBlock::parse_all(data),
+        Some(extensible_data_blocks())
+    );
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn async_central_directory_checksum() {
+    let mut zip = zip::write::AsyncZipWriter::new(futures::io::Cursor::new(Vec::new()));
+    zip.set_central_directory_checksum(true);
+    zip.start_file("a.txt", FileOptions::default())
+        .await
+        .unwrap();
+    let mut bytes = zip.finish().await.unwrap().get_ref().clone();
+
+    zip::AsyncZipArchive::new(futures::io::Cursor::new(bytes.clone()))
+        .await
+        .unwrap();
+    ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+
+    tamper_central_directory(&mut bytes);
+    match zip::AsyncZipArchive::new(futures::io::Cursor::new(bytes)).await {
+        Err(ZipError::InvalidArchive("Central directory checksum mismatch")) => {}
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+}
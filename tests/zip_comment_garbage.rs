@@ -28,3 +28,99 @@ fn correctly_handle_zip_with_garbage_after_comment() {
 
     assert_eq!(archive.comment(), "short.".as_bytes());
 }
+
+/// An archive with a single file, followed by `trailing` bytes of other data
+fn embedded_archive(trailing: usize) -> Vec<u8> {
+    use std::io::Write;
+    let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+    zip.start_file("a.txt", Default::default()).unwrap();
+    zip.write_all(b"a").unwrap();
+    let mut bytes = zip.finish().unwrap().into_inner();
+    bytes.resize(bytes.len() + trailing, 0x50);
+    bytes
+}
+
+#[test]
+fn search_distance() {
+    use zip::read::Config;
+
+    let bytes = embedded_archive(100_000);
+    assert!(ZipArchive::new(io::Cursor::new(bytes.clone())).is_err());
+    let config = Config::default().max_search_distance(200_000);
+    let archive = ZipArchive::new_with_config(io::Cursor::new(bytes), config).unwrap();
+    assert_eq!(archive.len(), 1);
+
+    // The record must be at most the distance from the end, including its own size
+    let bytes = embedded_archive(0);
+    let config = Config::default().max_search_distance(21);
+    assert!(ZipArchive::new_with_config(io::Cursor::new(bytes.clone()), config).is_err());
+    let config = Config::default().max_search_distance(22);
+    assert!(ZipArchive::new_with_config(io::Cursor::new(bytes), config).is_ok());
+}
+
+#[test]
+fn trailing_data() {
+    use zip::read::Config;
+
+    let v = include_bytes!("../tests/data/comment_garbage.zip").to_vec();
+    let config = Config::default().trailing_data(false);
+    assert!(ZipArchive::new_with_config(io::Cursor::new(v), config).is_err());
+    let bytes = embedded_archive(0);
+    assert!(ZipArchive::new_with_config(io::Cursor::new(bytes), config).is_ok());
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn async_trailing_data() {
+    use zip::read::Config;
+    use zip::AsyncZipArchive;
+
+    let bytes = embedded_archive(10);
+    let archive = AsyncZipArchive::new(futures::io::Cursor::new(bytes.clone()))
+        .await
+        .unwrap();
+    assert_eq!(archive.len(), 1);
+    let config = Config::default().trailing_data(false);
+    assert!(
+        AsyncZipArchive::new_with_config(futures::io::Cursor::new(bytes), config)
+            .await
+            .is_err()
+    );
+}
+
+/// An archive whose comment contains a central directory end record, which is followed by more
+/// of the comment, so it does not reach exactly to the end
+fn fake_record_in_comment() -> Vec<u8> {
+    use std::io::Write;
+    let mut comment = b"PK\x05\x06".to_vec();
+    comment.resize(22, 0);
+    comment.extend_from_slice(b" and more comment");
+    let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+    zip.set_comment(String::from_utf8(comment).unwrap());
+    zip.start_file("a.txt", Default::default()).unwrap();
+    zip.write_all(b"a").unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+#[test]
+fn record_in_comment() {
+    use zip::read::Config;
+
+    let bytes = fake_record_in_comment();
+    for &trailing_data in &[false, true] {
+        let config = Config::default().trailing_data(trailing_data);
+        let archive = ZipArchive::new_with_config(io::Cursor::new(bytes.clone()), config).unwrap();
+        assert_eq!(archive.len(), 1);
+        assert!(archive.comment().ends_with(b" and more comment"));
+    }
+}
+
+#[cfg(feature = "async")]
+#[futures_await_test::async_test]
+async fn async_record_in_comment() {
+    let bytes = fake_record_in_comment();
+    let archive = zip::AsyncZipArchive::new(futures::io::Cursor::new(bytes))
+        .await
+        .unwrap();
+    assert_eq!(archive.len(), 1);
+}
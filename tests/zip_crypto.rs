@@ -141,3 +141,58 @@ async fn encrypted_file_async() {
         assert_eq!(data, "abcdefghijklmnopqrstuvwxyz123456789".as_bytes());
     }
 }
+
+#[test]
+fn encrypted_file_roundtrip() {
+    use std::io::Write;
+    use zip::write::FileOptions;
+
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    writer
+        .start_file("test.txt", FileOptions::default().with_password(b"test"))
+        .unwrap();
+    writer
+        .write_all(b"abcdefghijklmnopqrstuvwxyz123456789")
+        .unwrap();
+    writer
+        .start_file("plain.txt", FileOptions::default())
+        .unwrap();
+    writer.write_all(b"not encrypted").unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    // General purpose bit flag of the first local file header
+    assert_eq!(bytes[6] & 1, 1);
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(
+        archive.encryption_method(0).unwrap(),
+        Some(zip::EncryptionMethod::ZipCrypto)
+    );
+    assert_eq!(archive.encryption_method(1).unwrap(), None);
+    assert!(archive.verify_password(0, b"test").unwrap());
+    assert!(!archive.verify_password(0, b"wrong password").unwrap());
+    assert!(archive.verify_password(1, b"anything").unwrap());
+    assert!(archive.verify_password(2, b"test").is_err());
+    assert!(archive.by_name("test.txt").is_err());
+    match archive.by_name_decrypt("test.txt", b"wrong password") {
+        Ok(Err(zip::result::InvalidPassword)) => (),
+        _ => panic!("Expected InvalidPassword error when opening with the wrong password"),
+    }
+
+    let mut data = Vec::new();
+    archive
+        .by_name_decrypt("test.txt", b"test")
+        .unwrap()
+        .unwrap()
+        .read_to_end(&mut data)
+        .unwrap();
+    assert_eq!(data, "abcdefghijklmnopqrstuvwxyz123456789".as_bytes());
+
+    let mut data = Vec::new();
+    archive
+        .by_name("plain.txt")
+        .unwrap()
+        .read_to_end(&mut data)
+        .unwrap();
+    assert_eq!(data, b"not encrypted");
+}
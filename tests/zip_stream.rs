@@ -0,0 +1,29 @@
+#![cfg(feature = "async")]
+
+use futures::stream::{self, TryStreamExt};
+use std::io::{Cursor, Read};
+use zip::write::{FileOptions, ZipStream};
+use zip::ZipArchive;
+
+#[futures_await_test::async_test]
+async fn zip_stream() {
+    let entries = stream::iter((0..3).map(|i| {
+        let content = format!("contents of file {}", i).repeat(1000);
+        (format!("{}.txt", i), futures::io::Cursor::new(content))
+    }));
+    let chunks: Vec<Vec<u8>> = ZipStream::new(entries, FileOptions::default())
+        .try_collect()
+        .await
+        .unwrap();
+    assert!(chunks.len() > 1);
+
+    let mut archive = ZipArchive::new(Cursor::new(chunks.concat())).unwrap();
+    assert_eq!(archive.len(), 3);
+    for i in 0..3 {
+        let mut file = archive.by_index(i).unwrap();
+        assert_eq!(file.name(), format!("{}.txt", i));
+        let mut content = String::new();
+        file.read_to_string(&mut content).unwrap();
+        assert_eq!(content, format!("contents of file {}", i).repeat(1000));
+    }
+}